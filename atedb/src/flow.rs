@@ -169,6 +169,7 @@ impl OpenFlow for ChainFlow {
                 .invoke(GroupDetailsRequest {
                     group,
                     session: None,
+                    database: None,
                 })
                 .await?;
             let advert = match advert {
@@ -213,6 +214,23 @@ impl OpenFlow for ChainFlow {
 
             builder = builder.add_root_public_key(&role.write);
 
+            // Roles that have been bound to this database may also write to it
+            for binding in advert
+                .bindings
+                .iter()
+                .filter(|b| b.chain == dbname && b.write)
+            {
+                if let Some(role) = advert
+                    .roles
+                    .iter()
+                    .filter(|r| r.purpose == binding.purpose)
+                    .next()
+                {
+                    debug!("role ({}) may write to {}", role.purpose, path);
+                    builder = builder.add_root_public_key(&role.write);
+                }
+            }
+
             // Prefix the name with 'redo'
             let mut key_name = key.name.clone();
             if key_name.starts_with("/") {
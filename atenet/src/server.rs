@@ -286,7 +286,7 @@ for SessionFactory
                 // access this domain
                 let path = shellexpand::tilde(self.token_path.as_str()).to_string();
                 let session = if let Ok(token) = std::fs::read_to_string(path) {
-                    b64_to_session(token)
+                    b64_to_session(token)?
                 } else {
                     warn!("token is missing - {}", self.token_path);
                     let err: wasmer_auth::error::GatherError = wasmer_auth::error::GatherErrorKind::NoMasterKey.into();
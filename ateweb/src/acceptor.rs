@@ -155,7 +155,7 @@ impl HyperAcceptor {
                 acme.touch_alpn(sni.to_string()).await?;
             } else {
                 trace!("connection attempt SNI: {}", sni);
-                acme.touch_web(sni.to_string(), chrono::Duration::days(30))
+                acme.touch_host(sni.to_string(), chrono::Duration::days(30))
                     .await?;
             }
             break;
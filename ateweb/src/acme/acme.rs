@@ -29,6 +29,8 @@ pub const LETS_ENCRYPT_PRODUCTION_DIRECTORY: &str =
     "https://acme-v02.api.letsencrypt.org/directory";
 pub const PEBBLE_DIRECTORY: &str = "https://localhost:14000/dir";
 pub const ACME_TLS_ALPN_NAME: &[u8] = b"acme-tls/1";
+pub const ACME_HTTP_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+pub const ACME_DNS_CHALLENGE_PREFIX: &str = "_acme-challenge.";
 
 #[derive(Debug)]
 pub struct Account {
@@ -193,6 +195,47 @@ impl Account {
 
         Ok((challenge, certified_key, cert_pem, pk_pem))
     }
+
+    /// Returns the HTTP-01 challenge along with the key authorization that
+    /// must be served under /.well-known/acme-challenge/[token]
+    pub fn http_01<'a>(
+        &self,
+        challenges: &'a Vec<Challenge>,
+    ) -> Result<(&'a Challenge, String), AcmeError> {
+        let challenge = challenges
+            .iter()
+            .filter(|c| c.typ == ChallengeType::Http01)
+            .next();
+
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None => return Err(AcmeErrorKind::NoHttp01Challenge.into()),
+        };
+
+        let key_auth = key_authorization(&self.key_pair, &*challenge.token)?;
+        Ok((challenge, key_auth))
+    }
+
+    /// Returns the DNS-01 challenge along with the value of the TXT record
+    /// that must be published under _acme-challenge.[domain]
+    pub fn dns_01<'a>(
+        &self,
+        challenges: &'a Vec<Challenge>,
+    ) -> Result<(&'a Challenge, String), AcmeError> {
+        let challenge = challenges
+            .iter()
+            .filter(|c| c.typ == ChallengeType::Dns01)
+            .next();
+
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None => return Err(AcmeErrorKind::NoDns01Challenge.into()),
+        };
+
+        let key_auth = key_authorization_sha256(&self.key_pair, &*challenge.token)?;
+        let value = base64::encode_config(key_auth.as_ref(), URL_SAFE_NO_PAD);
+        Ok((challenge, value))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -221,7 +264,7 @@ impl Directory {
     }
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
 pub enum ChallengeType {
     #[serde(rename = "http-01")]
     Http01,
@@ -254,6 +297,8 @@ pub enum Auth {
     Pending {
         identifier: Identifier,
         challenges: Vec<Challenge>,
+        #[serde(default)]
+        wildcard: bool,
     },
     Valid,
     Invalid,
@@ -267,7 +312,7 @@ pub enum Identifier {
     Dns(String),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Challenge {
     #[serde(rename = "type")]
     pub typ: ChallengeType,
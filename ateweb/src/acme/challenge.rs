@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use error_chain::bail;
+use std::str::FromStr;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use crate::error::*;
+
+/// Type of challenge that is used to prove to the certificate authority
+/// that the domain is controlled by this server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcmeChallenge {
+    /// Answered during the TLS handshake (needs the server to terminate TLS)
+    TlsAlpn01,
+    /// Answered by the HTTP listener under /.well-known/acme-challenge/
+    Http01,
+    /// Answered by publishing a TXT record (needed for wildcard domains)
+    Dns01,
+}
+
+impl Default for AcmeChallenge {
+    fn default() -> Self {
+        AcmeChallenge::TlsAlpn01
+    }
+}
+
+impl std::fmt::Display for AcmeChallenge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcmeChallenge::TlsAlpn01 => write!(f, "tls-alpn-01"),
+            AcmeChallenge::Http01 => write!(f, "http-01"),
+            AcmeChallenge::Dns01 => write!(f, "dns-01"),
+        }
+    }
+}
+
+impl FromStr for AcmeChallenge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tls-alpn-01" => Ok(AcmeChallenge::TlsAlpn01),
+            "http-01" => Ok(AcmeChallenge::Http01),
+            "dns-01" => Ok(AcmeChallenge::Dns01),
+            _ => Err(format!(
+                "unknown challenge ({}) - expected tls-alpn-01, http-01 or dns-01",
+                s
+            )),
+        }
+    }
+}
+
+/// Publishes the TXT records that answer DNS-01 challenges
+#[async_trait]
+pub trait DnsProvider
+where
+    Self: Send + Sync,
+{
+    /// Adds a TXT record once it can be seen by the certificate authority
+    /// (records with the same name must be kept as wildcard orders need
+    /// more than one of them at the same time)
+    async fn add_txt(&self, name: &str, value: &str) -> Result<(), AcmeError>;
+
+    /// Removes a TXT record that was added earlier
+    async fn remove_txt(&self, name: &str, value: &str) -> Result<(), AcmeError>;
+}
+
+/// DNS provider that runs a command to change the records
+/// (e.g. `hook add _acme-challenge.example.com [value]`)
+pub struct DnsHook {
+    command: String,
+}
+
+impl DnsHook {
+    pub fn new(command: &str) -> DnsHook {
+        DnsHook {
+            command: command.to_string(),
+        }
+    }
+
+    async fn run(&self, action: &str, name: &str, value: &str) -> Result<(), AcmeError> {
+        debug!("dns hook: {} {} {}", self.command, action, name);
+        let status = tokio::process::Command::new(self.command.as_str())
+            .arg(action)
+            .arg(name)
+            .arg(value)
+            .status()
+            .await?;
+        if status.success() == false {
+            bail!(AcmeErrorKind::DnsProvider(format!(
+                "{} {} {} exited with {}",
+                self.command, action, name, status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DnsProvider for DnsHook {
+    async fn add_txt(&self, name: &str, value: &str) -> Result<(), AcmeError> {
+        self.run("add", name, value).await
+    }
+
+    async fn remove_txt(&self, name: &str, value: &str) -> Result<(), AcmeError> {
+        self.run("remove", name, value).await
+    }
+}
@@ -1,7 +1,11 @@
 mod acme;
+mod challenge;
+mod order;
 mod resolver;
 mod security;
 
 pub use acme::*;
+pub use challenge::*;
+pub use order::*;
 pub use resolver::*;
 pub use security::*;
@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use rcgen::{CertificateParams, DistinguishedName, PKCS_ECDSA_P256_SHA256};
+use rustls::sign::any_supported_type;
+use rustls::sign::CertifiedKey;
+use rustls::Certificate as RustlsCertificate;
+use rustls::PrivateKey;
+use std::sync::Arc;
+use std::time::Duration;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use x509_parser::parse_x509_certificate;
+
+use super::acme::{Account, Auth, Challenge, Directory, Identifier, Order};
+use crate::error::*;
+
+/// Answers the challenges that the certificate authority hands out
+#[async_trait]
+pub trait AcmeResponder
+where
+    Self: Send + Sync,
+{
+    /// Prepares whatever is needed to answer one of the challenges for a
+    /// domain and returns the challenge that should be triggered
+    async fn prepare(
+        &self,
+        account: &Account,
+        domain: &str,
+        wildcard: bool,
+        challenges: &Vec<Challenge>,
+    ) -> Result<Challenge, OrderError>;
+
+    /// Removes whatever was prepared once the authorization has finished
+    async fn cleanup(
+        &self,
+        account: &Account,
+        domain: &str,
+        challenge: &Challenge,
+    ) -> Result<(), OrderError>;
+}
+
+/// Orders a certificate for a list of domains (the first one is the main
+/// domain) returning the certificate along with its chain and private key
+/// in PEM format
+pub async fn order_certificate(
+    directory_url: &str,
+    domains: Vec<String>,
+    duration: chrono::Duration,
+    responder: &dyn AcmeResponder,
+) -> Result<(CertifiedKey, String, String), OrderError> {
+    let contacts = domains
+        .iter()
+        .take(1)
+        .map(|d| format!("mailto:info@{}", d))
+        .collect::<Vec<_>>();
+    let not_before = chrono::Utc::now();
+    let mut not_after = not_before.clone();
+    if let Some(not_after_next) = not_before.checked_add_signed(duration) {
+        not_after = not_after_next;
+    }
+
+    let mut params = CertificateParams::new(domains.clone());
+    params.distinguished_name = DistinguishedName::new();
+    params.alg = &PKCS_ECDSA_P256_SHA256;
+    params.not_before = not_before;
+    params.not_after = not_after;
+
+    let cert = rcgen::Certificate::from_params(params)?;
+    let pk_pem = cert.serialize_private_key_pem();
+    let pk_bytes = cert.serialize_private_key_der();
+    let pk = any_supported_type(&PrivateKey(pk_bytes.clone())).unwrap();
+
+    debug!("load_or_create account");
+    let directory = Directory::discover(directory_url).await?;
+    let account = Account::load_or_create(directory, &contacts).await?;
+
+    debug!("new order for {:?}", domains);
+    let mut wait = 0u32;
+    let (mut order, kid) = account.new_order(domains.clone()).await?;
+    loop {
+        order = match order {
+            Order::Pending {
+                authorizations,
+                finalize,
+            } => {
+                let auth_futures = authorizations
+                    .iter()
+                    .map(|url| authorize(&account, url, responder));
+                try_join_all(auth_futures).await?;
+                debug!("completed all authorizations");
+                Order::Ready { finalize }
+            }
+            Order::Ready { finalize } => {
+                debug!("sending csr");
+                let csr = cert.serialize_request_der()?;
+                account.finalize(finalize.as_str(), csr).await?
+            }
+            Order::Processing => {
+                debug!("processing certificate");
+                wait += 1;
+                if wait > 30 {
+                    return Err(OrderErrorKind::Timeout.into());
+                }
+                ate::engine::sleep(Duration::from_secs(1)).await;
+                account.check(kid.as_str()).await?
+            }
+            Order::Valid { certificate } => {
+                debug!("download certificate");
+
+                let acme_cert_pem = account.certificate(certificate.as_str()).await?;
+                let acme_cert_pem = acme_cert_pem.replace(
+                    "-----BEGINCERTIFICATE-----",
+                    "-----BEGIN CERTIFICATE-----\n",
+                );
+                let acme_cert_pem = acme_cert_pem
+                    .replace("-----ENDCERTIFICATE-----", "\n-----END CERTIFICATE-----\n");
+
+                let pems = pem::parse_many(&acme_cert_pem);
+                let cert_chain: Vec<rustls::Certificate> = pems
+                    .into_iter()
+                    .map(|p| RustlsCertificate(p.contents))
+                    .collect();
+
+                let cert_key = CertifiedKey::new(cert_chain, Arc::new(pk));
+                return Ok((cert_key, acme_cert_pem, pk_pem));
+            }
+            Order::Invalid => return Err(OrderErrorKind::BadOrder(order).into()),
+        }
+    }
+}
+
+async fn authorize(
+    account: &Account,
+    url: &String,
+    responder: &dyn AcmeResponder,
+) -> Result<(), OrderError> {
+    debug!("starting authorization for {}", url);
+    let (domain, challenge) = match account.auth(url).await? {
+        Auth::Pending {
+            identifier,
+            challenges,
+            wildcard,
+        } => {
+            let Identifier::Dns(domain) = identifier;
+            info!("trigger challenge for {}", &domain);
+            let challenge = responder
+                .prepare(account, domain.as_str(), wildcard, &challenges)
+                .await?;
+            account.challenge(&challenge.url).await?;
+            (domain, challenge)
+        }
+        Auth::Valid => return Ok(()),
+        auth => return Err(OrderErrorKind::BadAuth(auth).into()),
+    };
+
+    let ret = wait_for_auth(account, url, domain.as_str(), &challenge).await;
+    if let Err(err) = responder
+        .cleanup(account, domain.as_str(), &challenge)
+        .await
+    {
+        warn!("failed to clean up the challenge for {} - {}", domain, err);
+    }
+    ret
+}
+
+async fn wait_for_auth(
+    account: &Account,
+    url: &String,
+    domain: &str,
+    challenge: &Challenge,
+) -> Result<(), OrderError> {
+    for i in 0u64..5 {
+        ate::engine::sleep(Duration::from_secs(1 << i)).await;
+        match account.auth(url).await? {
+            Auth::Pending { .. } => {
+                info!("authorization for {} still pending", domain);
+                account.challenge(&challenge.url).await?
+            }
+            Auth::Valid => return Ok(()),
+            auth => return Err(OrderErrorKind::BadAuth(auth).into()),
+        }
+    }
+    Err(OrderErrorKind::TooManyAttemptsAuth(domain.to_string()).into())
+}
+
+/// Returns how long until a certificate should be renewed (which is the
+/// renewal period before it expires)
+pub fn duration_until_renewal(cert_key: &CertifiedKey, renewal: chrono::Duration) -> Duration {
+    for cert in cert_key.cert.iter() {
+        if let Ok((_, cert)) = parse_x509_certificate(cert.0.as_slice()) {
+            let time_stamp = chrono::DateTime::<chrono::Utc>::from_utc(
+                chrono::NaiveDateTime::from_timestamp(cert.validity().not_after.timestamp(), 0),
+                chrono::Utc,
+            );
+            trace!("valid until {}", time_stamp);
+            let valid_until = cert.validity().not_after.timestamp();
+            let valid_secs = (valid_until - chrono::Utc::now().timestamp()).max(0);
+            let valid_secs = (valid_secs - renewal.num_seconds()).max(0);
+            return Duration::from_secs(valid_secs as u64);
+        }
+    }
+    chrono::Duration::days(365).to_std().unwrap()
+}
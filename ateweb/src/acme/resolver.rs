@@ -1,19 +1,18 @@
 use super::acme::{
     Account,
-    Auth,
-    Directory,
-    Identifier,
-    Order,
+    Challenge,
+    ChallengeType,
+    ACME_DNS_CHALLENGE_PREFIX,
     ACME_TLS_ALPN_NAME,
-    LETS_ENCRYPT_PRODUCTION_DIRECTORY,
     //LETS_ENCRYPT_STAGING_DIRECTORY,
     //PEBBLE_DIRECTORY,
 };
+use super::challenge::*;
+use super::order::*;
+use async_trait::async_trait;
 use ate::prelude::*;
 use bytes::Bytes;
-use futures::future::try_join_all;
 use fxhash::FxHashMap;
-use rcgen::{CertificateParams, DistinguishedName, PKCS_ECDSA_P256_SHA256};
 use rustls::sign::any_supported_type;
 use rustls::sign::CertifiedKey;
 use rustls::Certificate as RustlsCertificate;
@@ -29,6 +28,7 @@ use tokio::sync::Mutex;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 use ttl_cache::TtlCache;
+use x509_parser::extensions::GeneralName;
 use x509_parser::parse_x509_certificate;
 use ate_files::repo::*;
 
@@ -46,15 +46,26 @@ pub struct AcmeResolver {
     pub certs: StdRwLock<TtlCache<String, CertifiedKey>>,
     pub auths: StdRwLock<TtlCache<String, CertifiedKey>>,
     pub locks: StdMutex<FxHashMap<String, Arc<Mutex<AcmeState>>>>,
+    pub directory_url: String,
+    pub challenge: AcmeChallenge,
+    pub dns: Option<Arc<dyn DnsProvider>>,
 }
 
 impl AcmeResolver {
-    pub async fn new(repo: &Arc<Repository>) -> Result<Arc<AcmeResolver>, AteError> {
+    pub async fn new(
+        repo: &Arc<Repository>,
+        directory_url: &str,
+        challenge: AcmeChallenge,
+        dns: Option<Arc<dyn DnsProvider>>,
+    ) -> Result<Arc<AcmeResolver>, AteError> {
         let ret = AcmeResolver {
             repo: Arc::clone(repo),
             certs: StdRwLock::new(TtlCache::new(65536usize)),
             auths: StdRwLock::new(TtlCache::new(1024usize)),
             locks: StdMutex::new(FxHashMap::default()),
+            directory_url: directory_url.to_string(),
+            challenge,
+            dns,
         };
         Ok(Arc::new(ret))
     }
@@ -129,6 +140,26 @@ impl AcmeResolver {
         Ok(())
     }
 
+    /// Loads (or orders) the certificate of a host, when DNS-01 is used the
+    /// wildcard certificate of the domain that it belongs to is also loaded
+    /// as it covers hosts that have no web site of their own
+    pub async fn touch_host(
+        &self,
+        sni: String,
+        renewal: chrono::Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.touch_web(sni.clone(), renewal).await?;
+        if self.challenge == AcmeChallenge::Dns01 {
+            let loaded = self.certs.read().unwrap().contains_key(&sni);
+            if let Some((_, parent)) = sni.split_once(".") {
+                if loaded == false && parent.contains(".") {
+                    self.touch_web(parent.to_string(), renewal).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub async fn touch_web(
         &self,
         sni: String,
@@ -230,14 +261,17 @@ impl AcmeResolver {
             }
         }
 
-        let directory_url = LETS_ENCRYPT_PRODUCTION_DIRECTORY;
-        //let directory_url = LETS_ENCRYPT_STAGING_DIRECTORY;
-        //let directory_url = PEBBLE_DIRECTORY;
         let expires = chrono::Duration::days(90);
 
+        // Wildcard certificates can only be ordered with DNS-01 challenges
+        let domains = match self.challenge {
+            AcmeChallenge::Dns01 => vec![sni.clone(), format!("*.{}", sni)],
+            _ => vec![sni.clone()],
+        };
+
         // Order the certificate using lets encrypt
         debug!("ordering of certificate started");
-        match self.order(&directory_url, sni.as_str(), expires).await {
+        match order_certificate(self.directory_url.as_str(), domains, expires, self).await {
             Ok((cert_key, cert_pem, pk_pem)) => {
                 debug!("successfully ordered certificate");
                 lock.err_cnt = 0i64;
@@ -270,132 +304,33 @@ impl AcmeResolver {
         cert_key: &CertifiedKey,
         renewal: chrono::Duration,
     ) -> Duration {
-        for cert in cert_key.cert.iter() {
-            if let Ok((_, cert)) = parse_x509_certificate(cert.0.as_slice()) {
-                let time_stamp = chrono::DateTime::<chrono::Utc>::from_utc(
-                    chrono::NaiveDateTime::from_timestamp(cert.validity().not_after.timestamp(), 0),
-                    chrono::Utc,
-                );
-                trace!("valid until {}", time_stamp);
-                let valid_until = cert.validity().not_after.timestamp();
-                let valid_secs = (valid_until - chrono::Utc::now().timestamp()).max(0);
-                let valid_secs = (valid_secs - renewal.num_seconds()).max(0);
-                return Duration::from_secs(valid_secs as u64);
-            }
-        }
-        chrono::Duration::days(365).to_std().unwrap()
-    }
-
-    async fn order(
-        &self,
-        directory_url: &str,
-        domain: &str,
-        duration: chrono::Duration,
-    ) -> Result<(CertifiedKey, String, String), OrderError> {
-        let contacts = vec![format!("mailto:info@{}", domain)];
-        let domains = vec![domain.to_string()];
-        let not_before = chrono::Utc::now();
-        let mut not_after = not_before.clone();
-        if let Some(not_after_next) = not_before.checked_add_signed(duration) {
-            not_after = not_after_next;
-        }
-
-        let mut params = CertificateParams::new(domains.clone());
-        params.distinguished_name = DistinguishedName::new();
-        params.alg = &PKCS_ECDSA_P256_SHA256;
-        params.not_before = not_before;
-        params.not_after = not_after;
-
-        let cert = rcgen::Certificate::from_params(params)?;
-        let pk_pem = cert.serialize_private_key_pem();
-        let pk_bytes = cert.serialize_private_key_der();
-        let pk = any_supported_type(&PrivateKey(pk_bytes.clone())).unwrap();
-
-        debug!("load_or_create account");
-        let directory = Directory::discover(directory_url).await?;
-        let account = Account::load_or_create(directory, &contacts).await?;
-
-        debug!("new order for {:?}", domains);
-        let mut wait = 0u32;
-        let (mut order, kid) = account.new_order(domains.clone()).await?;
-        loop {
-            order = match order {
-                Order::Pending {
-                    authorizations,
-                    finalize,
-                } => {
-                    let auth_futures = authorizations
-                        .iter()
-                        .map(|url| self.authorize(&account, domain, url));
-                    try_join_all(auth_futures).await?;
-                    debug!("completed all authorizations");
-                    Order::Ready { finalize }
-                }
-                Order::Ready { finalize } => {
-                    debug!("sending csr");
-                    let csr = cert.serialize_request_der()?;
-                    account.finalize(finalize.as_str(), csr).await?
-                }
-                Order::Processing => {
-                    debug!("processing certificate");
-                    wait += 1;
-                    if wait > 30 {
-                        return Err(OrderErrorKind::Timeout.into());
-                    }
-                    ate::engine::sleep(Duration::from_secs(1)).await;
-                    account.check(kid.as_str()).await?
-                }
-                Order::Valid { certificate } => {
-                    debug!("download certificate");
-
-                    let acme_cert_pem = account.certificate(certificate.as_str()).await?;
-                    let acme_cert_pem = acme_cert_pem.replace(
-                        "-----BEGINCERTIFICATE-----",
-                        "-----BEGIN CERTIFICATE-----\n",
-                    );
-                    let acme_cert_pem = acme_cert_pem
-                        .replace("-----ENDCERTIFICATE-----", "\n-----END CERTIFICATE-----\n");
-
-                    let pems = pem::parse_many(&acme_cert_pem);
-                    let cert_chain: Vec<rustls::Certificate> = pems
-                        .into_iter()
-                        .map(|p| RustlsCertificate(p.contents))
-                        .collect();
-
-                    let cert_key = CertifiedKey::new(cert_chain, Arc::new(pk));
-                    return Ok((cert_key, acme_cert_pem, pk_pem));
-                }
-                Order::Invalid => return Err(OrderErrorKind::BadOrder(order).into()),
-            }
-        }
+        duration_until_renewal(cert_key, renewal)
     }
+}
 
-    async fn authorize(
+#[async_trait]
+impl AcmeResponder for AcmeResolver {
+    async fn prepare(
         &self,
         account: &Account,
-        sni: &str,
-        url: &String,
-    ) -> Result<(), OrderError> {
-        debug!("starting authorization for {}", url);
-        let (domain, challenge_url) = match account.auth(url).await? {
-            Auth::Pending {
-                identifier,
-                challenges,
-            } => {
-                let Identifier::Dns(domain) = identifier;
-                info!("trigger challenge for {}", &domain);
+        domain: &str,
+        _wildcard: bool,
+        challenges: &Vec<Challenge>,
+    ) -> Result<Challenge, OrderError> {
+        let key = ChainKey::from(format!("{}/www", domain));
+        match self.challenge {
+            AcmeChallenge::TlsAlpn01 => {
                 let (challenge, _auth_key, cert_pem, pk_pem) =
-                    account.tls_alpn_01(&challenges, domain.clone())?;
+                    account.tls_alpn_01(challenges, domain.to_string())?;
 
-                let key = ChainKey::from(format!("{}/www", sni));
                 self.repo
-                    .set_file(&key, sni, WEB_CONF_FILES_ALPN_CERT, cert_pem.as_bytes())
+                    .set_file(&key, domain, WEB_CONF_FILES_ALPN_CERT, cert_pem.as_bytes())
                     .await?;
                 self.repo
-                    .set_file(&key, sni, WEB_CONF_FILES_ALPN_KEY, pk_pem.as_bytes())
+                    .set_file(&key, domain, WEB_CONF_FILES_ALPN_KEY, pk_pem.as_bytes())
                     .await?;
 
-                self.auths.write().unwrap().remove(&domain);
+                self.auths.write().unwrap().remove(domain);
 
                 /*
                 self.auths
@@ -403,28 +338,81 @@ impl AcmeResolver {
                     .unwrap()
                     .insert(domain.clone(), _auth_key, Duration::from_secs(300));
                 */
+                Ok(challenge.clone())
+            }
+            AcmeChallenge::Http01 => {
+                // The answer is saved in the chain so that whichever web
+                // server the certificate authority reaches can serve it
+                let (challenge, key_auth) = account.http_01(challenges)?;
+                let path = format!("{}{}", WEB_CONF_FILES_ACME, challenge.token);
+                self.repo
+                    .set_file(&key, domain, path.as_str(), key_auth.as_bytes())
+                    .await?;
+                Ok(challenge.clone())
+            }
+            AcmeChallenge::Dns01 => {
+                let dns = match self.dns.as_ref() {
+                    Some(a) => a,
+                    None => return Err(OrderErrorKind::NoDnsProvider.into()),
+                };
+                let (challenge, value) = account.dns_01(challenges)?;
+                let name = format!("{}{}", ACME_DNS_CHALLENGE_PREFIX, domain);
+                dns.add_txt(name.as_str(), value.as_str()).await?;
+                Ok(challenge.clone())
+            }
+        }
+    }
 
-                account.challenge(&challenge.url).await?;
-                (domain, challenge.url.clone())
+    async fn cleanup(
+        &self,
+        account: &Account,
+        domain: &str,
+        challenge: &Challenge,
+    ) -> Result<(), OrderError> {
+        match challenge.typ {
+            ChallengeType::Http01 => {
+                let key = ChainKey::from(format!("{}/www", domain));
+                let path = format!("{}{}", WEB_CONF_FILES_ACME, challenge.token);
+                self.repo.remove_file(&key, domain, path.as_str()).await?;
             }
-            Auth::Valid => return Ok(()),
-            auth => return Err(OrderErrorKind::BadAuth(auth).into()),
-        };
-        for i in 0u64..5 {
-            ate::engine::sleep(Duration::from_secs(1 << i)).await;
-            match account.auth(url).await? {
-                Auth::Pending { .. } => {
-                    info!("authorization for {} still pending", &domain);
-                    account.challenge(&challenge_url).await?
+            ChallengeType::Dns01 => {
+                if let Some(dns) = self.dns.as_ref() {
+                    let (_, value) = account.dns_01(&vec![challenge.clone()])?;
+                    let name = format!("{}{}", ACME_DNS_CHALLENGE_PREFIX, domain);
+                    dns.remove_txt(name.as_str(), value.as_str()).await?;
                 }
-                Auth::Valid => return Ok(()),
-                auth => return Err(OrderErrorKind::BadAuth(auth).into()),
             }
+            ChallengeType::TlsAlpn01 => {}
         }
-        Err(OrderErrorKind::TooManyAttemptsAuth(domain).into())
+        Ok(())
     }
 }
 
+/// Returns true if the certificate has a wildcard that covers the host
+fn covers_host(cert_key: &CertifiedKey, sni: &str) -> bool {
+    let parent = match sni.split_once(".") {
+        Some((_, parent)) => parent,
+        None => return false,
+    };
+    let wildcard = format!("*.{}", parent);
+    cert_key
+        .cert
+        .iter()
+        .take(1)
+        .filter_map(|cert| parse_x509_certificate(cert.0.as_slice()).ok())
+        .filter_map(|(_, cert)| {
+            cert.tbs_certificate
+                .subject_alternative_name()
+                .map(|(_, san)| {
+                    san.general_names.iter().any(|name| match name {
+                        GeneralName::DNSName(name) => name.eq_ignore_ascii_case(wildcard.as_str()),
+                        _ => false,
+                    })
+                })
+        })
+        .any(|a| a)
+}
+
 impl ResolvesServerCert for AcmeResolver {
     fn resolve(&self, client_hello: ClientHello) -> Option<CertifiedKey> {
         if let Some(sni) = client_hello.server_name() {
@@ -447,6 +435,13 @@ impl ResolvesServerCert for AcmeResolver {
             return if let Some(cert) = guard.get(&sni) {
                 trace!("tls_hello: cert_hit={:?}", sni);
                 Some(cert.clone())
+            } else if let Some(cert) = sni
+                .split_once(".")
+                .and_then(|(_, parent)| guard.get(parent))
+                .filter(|cert| covers_host(cert, sni.as_str()))
+            {
+                trace!("tls_hello: wildcard_hit={:?}", sni);
+                Some(cert.clone())
             } else {
                 trace!("tls_hello: cert_miss={:?}", sni);
                 None
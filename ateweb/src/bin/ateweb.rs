@@ -79,13 +79,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let web_key: EncryptKey = load_key(run.web_key_path.clone(), ".read");
 
             conf.log_path = Some(run.log_path);
-            let server = ServerBuilder::new(run.remote, run.auth_url)
+            let mut builder = ServerBuilder::new(run.remote, run.auth_url)
                 .with_web_master_key(web_key)
                 .with_conf(&conf)
                 .ttl(Duration::from_secs(run.ttl))
-                .add_listener(run.listen, run.port, run.port == 443u16)
-                .build()
-                .await?;
+                .with_dav(run.dav)
+                .with_acme_directory(run.acme_directory.as_str())
+                .with_acme_challenge(run.acme_challenge)
+                .add_listener(run.listen, run.port, run.port == 443u16);
+            if let Some(hook) = run.acme_dns_hook {
+                builder = builder.with_dns_provider(DnsHook::new(hook.as_str()));
+            }
+            let server = builder.build().await?;
             server.run().await?;
         }
 
@@ -139,14 +144,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             router.set_default_route(root);
 
             conf.log_path = Some(run.log_path);
-            let server = ServerBuilder::new(run.remote, run.auth_url)
+            let mut builder = ServerBuilder::new(run.remote, run.auth_url)
                 .with_web_master_key(web_key)
                 .with_conf(&conf)
                 .ttl(Duration::from_secs(run.ttl))
                 .with_callback(router)
-                .add_listener(run.listen, run.port, run.port == 443u16)
-                .build()
-                .await?;
+                .with_dav(run.dav)
+                .with_acme_directory(run.acme_directory.as_str())
+                .with_acme_challenge(run.acme_challenge)
+                .add_listener(run.listen, run.port, run.port == 443u16);
+            if let Some(hook) = run.acme_dns_hook {
+                builder = builder.with_dns_provider(DnsHook::new(hook.as_str()));
+            }
+            let server = builder.build().await?;
             server.run().await?;
         }
     }
@@ -4,7 +4,10 @@ use std::time::Duration;
 use url::Url;
 
 use ate::prelude::*;
+use ate_files::repo::RepositorySessionFactory;
 
+use super::acme::AcmeChallenge;
+use super::acme::DnsProvider;
 use super::conf::*;
 use super::server::*;
 
@@ -15,6 +18,8 @@ pub struct ServerBuilder {
     pub(crate) web_master_key: Option<EncryptKey>,
     pub(crate) session_cert_store: Option<AteSessionGroup>,
     pub(crate) callback: Option<Arc<dyn ServerCallback>>,
+    pub(crate) dns_provider: Option<Arc<dyn DnsProvider>>,
+    pub(crate) session_factory: Option<Box<dyn RepositorySessionFactory>>,
 }
 
 impl ServerBuilder {
@@ -26,6 +31,8 @@ impl ServerBuilder {
             web_master_key: None,
             session_cert_store: None,
             callback: None,
+            dns_provider: None,
+            session_factory: None,
         }
     }
 
@@ -50,6 +57,37 @@ impl ServerBuilder {
         self
     }
 
+    pub fn with_dav(mut self, val: bool) -> Self {
+        self.conf.dav = val;
+        self
+    }
+
+    pub fn with_acme_directory(mut self, url: &str) -> Self {
+        self.conf.acme_directory = url.to_string();
+        self
+    }
+
+    pub fn with_acme_challenge(mut self, challenge: AcmeChallenge) -> Self {
+        self.conf.acme_challenge = challenge;
+        self
+    }
+
+    pub fn with_dns_provider(mut self, provider: impl DnsProvider + 'static) -> Self {
+        let provider = Arc::new(provider);
+        self.dns_provider = Some(provider);
+        self
+    }
+
+    /// Replaces how the sessions that web sites are read with are created
+    /// (by default the permissions of the host are gathered from wasmer-auth)
+    pub fn with_session_factory(
+        mut self,
+        factory: impl RepositorySessionFactory + 'static,
+    ) -> Self {
+        self.session_factory = Some(Box::new(factory));
+        self
+    }
+
     pub fn ttl(mut self, ttl: Duration) -> Self {
         self.conf.ttl = ttl;
         self
@@ -3,6 +3,9 @@ use std::time::Duration;
 
 use ate::prelude::*;
 
+use super::acme::AcmeChallenge;
+use super::acme::LETS_ENCRYPT_PRODUCTION_DIRECTORY;
+
 #[derive(Debug, Clone)]
 pub struct ServerListen {
     pub addr: SocketAddr,
@@ -14,6 +17,12 @@ pub struct ServerConf {
     pub cfg_ate: ConfAte,
     pub ttl: Duration,
     pub listen: Vec<ServerListen>,
+    /// Serves the file systems over WebDAV (under /dav/)
+    pub dav: bool,
+    /// Directory of the certificate authority that certificates are ordered from
+    pub acme_directory: String,
+    /// Challenge used to prove that the domains are controlled by this server
+    pub acme_challenge: AcmeChallenge,
 }
 
 impl Default for ServerConf {
@@ -22,6 +31,9 @@ impl Default for ServerConf {
             cfg_ate: ConfAte::default(),
             ttl: Duration::from_secs(60),
             listen: Vec::new(),
+            dav: false,
+            acme_directory: LETS_ENCRYPT_PRODUCTION_DIRECTORY.to_string(),
+            acme_challenge: AcmeChallenge::default(),
         }
     }
 }
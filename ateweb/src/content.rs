@@ -0,0 +1,368 @@
+use async_compression::tokio::write::BrotliEncoder;
+use async_compression::tokio::write::GzipEncoder;
+use chrono::TimeZone;
+use http::HeaderMap;
+use tokio::io::AsyncWriteExt;
+
+/// Largest file that will be compressed on the fly
+pub const COMPRESS_MAX: u64 = 8388608;
+/// Size of the blocks that file contents are streamed in
+pub const READ_SIZE: u32 = 1048576;
+
+/// Part of a file that was asked for by the `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    Full,
+    /// Inclusive range of bytes
+    Partial {
+        start: u64,
+        end: u64,
+    },
+    Unsatisfiable,
+}
+
+/// Encoding that a response body can be compressed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Brotli,
+    Gzip,
+}
+
+impl ContentEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "-br",
+            ContentEncoding::Gzip => "-gz",
+        }
+    }
+
+    pub async fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Brotli => {
+                let mut encoder = BrotliEncoder::new(Vec::new());
+                encoder.write_all(data).await?;
+                encoder.shutdown().await?;
+                Ok(encoder.into_inner())
+            }
+            ContentEncoding::Gzip => {
+                let mut encoder = GzipEncoder::new(Vec::new());
+                encoder.write_all(data).await?;
+                encoder.shutdown().await?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|a| a.to_str().ok())
+        .map(|a| a.trim())
+}
+
+/// Formats a timestamp (in milliseconds) as used by `Last-Modified`
+pub fn http_date(ms: u64) -> String {
+    chrono::Utc
+        .timestamp_millis(ms as i64)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Strong entity tag of a representation of a file (compressed bodies are
+/// different representations so they get their own tags)
+pub fn etag(hash: &str, encoding: Option<ContentEncoding>) -> String {
+    match encoding {
+        Some(encoding) => format!("\"{}{}\"", hash, encoding.suffix()),
+        None => format!("\"{}\"", hash),
+    }
+}
+
+/// Returns true if a list of entity tags refers to the contents of the file
+/// (a weak comparison is used as required for `If-None-Match`)
+fn etag_matches(list: &str, hash: &str) -> bool {
+    list.split(",").map(|a| a.trim()).any(|tag| {
+        if tag == "*" {
+            return true;
+        }
+        let tag = tag.trim_start_matches("W/").trim_matches('"');
+        let tag = tag
+            .strip_suffix(ContentEncoding::Brotli.suffix())
+            .or_else(|| tag.strip_suffix(ContentEncoding::Gzip.suffix()))
+            .unwrap_or(tag);
+        tag == hash
+    })
+}
+
+/// Returns true if the client already holds the current contents of the
+/// file and should be sent a 304 (Not Modified)
+pub fn not_modified(headers: &HeaderMap, hash: &str, updated: u64) -> bool {
+    if let Some(list) = header(headers, "If-None-Match") {
+        return etag_matches(list, hash);
+    }
+    if let Some(since) = header(headers, "If-Modified-Since") {
+        if let Ok(since) = chrono::DateTime::parse_from_rfc2822(since) {
+            return (updated / 1000) as i64 <= since.timestamp();
+        }
+    }
+    false
+}
+
+/// Works out which part of a file was requested (ranges that are malformed
+/// or that span multiple parts are ignored and the whole file is sent)
+pub fn parse_range(headers: &HeaderMap, len: u64, hash: &str) -> ByteRange {
+    let range = match header(headers, "Range") {
+        Some(a) => a,
+        None => return ByteRange::Full,
+    };
+
+    // The range only applies if the client still holds the same contents
+    // (ranges are always taken from the uncompressed representation)
+    if let Some(tag) = header(headers, "If-Range") {
+        if tag != etag(hash, None) {
+            return ByteRange::Full;
+        }
+    }
+
+    let spec = match range.strip_prefix("bytes=") {
+        Some(a) if a.contains(",") == false => a.trim(),
+        _ => return ByteRange::Full,
+    };
+    let (start, end) = match spec.split_once("-") {
+        Some(a) => a,
+        None => return ByteRange::Full,
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(n) => (len.saturating_sub(n), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+    if len == 0 || start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial { start, end }
+}
+
+/// Picks the encoding the client prefers out of the ones that are supported
+pub fn negotiate_encoding(headers: &HeaderMap) -> Option<ContentEncoding> {
+    let accept = header(headers, "Accept-Encoding")?;
+
+    let mut ret: Option<(ContentEncoding, f32)> = None;
+    for part in accept.split(",") {
+        let mut part = part.split(";");
+        let name = part.next().unwrap_or("").trim().to_lowercase();
+        let quality = part
+            .filter_map(|a| a.trim().strip_prefix("q="))
+            .filter_map(|a| a.parse::<f32>().ok())
+            .next()
+            .unwrap_or(1.0);
+        let encoding = match name.as_str() {
+            "br" => ContentEncoding::Brotli,
+            "gzip" | "x-gzip" => ContentEncoding::Gzip,
+            _ => continue,
+        };
+        if quality <= 0.0 {
+            continue;
+        }
+        // Brotli wins ties as it compresses better
+        ret = match ret {
+            Some((_, q)) if q > quality => ret,
+            Some((ContentEncoding::Brotli, q)) if q == quality => ret,
+            _ => Some((encoding, quality)),
+        };
+    }
+    ret.map(|(encoding, _)| encoding)
+}
+
+/// Returns true for content that is worth compressing (media formats are
+/// already compressed)
+pub fn is_compressible(mime: Option<&str>) -> bool {
+    match mime {
+        Some(mime) => {
+            mime.starts_with("text/")
+                || mime.ends_with("+xml")
+                || mime.ends_with("+json")
+                || match mime {
+                    "application/javascript"
+                    | "application/json"
+                    | "application/xml"
+                    | "application/wasm"
+                    | "application/x-sh"
+                    | "application/x-csh"
+                    | "image/svg+xml"
+                    | "image/bmp"
+                    | "image/vnd.microsoft.icon" => true,
+                    _ => false,
+                }
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(list: &[(&'static str, &str)]) -> HeaderMap {
+        let mut ret = HeaderMap::new();
+        for (name, value) in list.iter() {
+            ret.insert(*name, value.parse().unwrap());
+        }
+        ret
+    }
+
+    fn range(value: &str, len: u64) -> ByteRange {
+        parse_range(&headers(&[("Range", value)]), len, "abc")
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(&HeaderMap::new(), 100, "abc"), ByteRange::Full);
+        assert_eq!(
+            range("bytes=0-9", 100),
+            ByteRange::Partial { start: 0, end: 9 }
+        );
+        assert_eq!(
+            range("bytes=90-", 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            range("bytes=-10", 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            range("bytes=-500", 100),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            range("bytes=50-500", 100),
+            ByteRange::Partial { start: 50, end: 99 }
+        );
+
+        // Ranges past the end can not be served
+        assert_eq!(range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=-0", 100), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=0-0", 0), ByteRange::Unsatisfiable);
+
+        // Anything malformed or with several parts gets the whole file
+        assert_eq!(range("bytes=9-0", 100), ByteRange::Full);
+        assert_eq!(range("bytes=0-9,20-29", 100), ByteRange::Full);
+        assert_eq!(range("bytes=a-b", 100), ByteRange::Full);
+        assert_eq!(range("bytes=5", 100), ByteRange::Full);
+        assert_eq!(range("items=0-9", 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_parse_range_if_range() {
+        let current = headers(&[("Range", "bytes=0-9"), ("If-Range", "\"abc\"")]);
+        assert_eq!(
+            parse_range(&current, 100, "abc"),
+            ByteRange::Partial { start: 0, end: 9 }
+        );
+
+        // Clients that hold other contents (or a compressed body) get it all
+        let stale = headers(&[("Range", "bytes=0-9"), ("If-Range", "\"def\"")]);
+        assert_eq!(parse_range(&stale, 100, "abc"), ByteRange::Full);
+        let compressed = headers(&[("Range", "bytes=0-9"), ("If-Range", "\"abc-gz\"")]);
+        assert_eq!(parse_range(&compressed, 100, "abc"), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        let negotiate = |value: &str| negotiate_encoding(&headers(&[("Accept-Encoding", value)]));
+        assert_eq!(negotiate_encoding(&HeaderMap::new()), None);
+        assert_eq!(negotiate("identity"), None);
+        assert_eq!(negotiate("gzip"), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate("x-gzip"), Some(ContentEncoding::Gzip));
+        assert_eq!(
+            negotiate("gzip, deflate, br"),
+            Some(ContentEncoding::Brotli)
+        );
+        assert_eq!(
+            negotiate("br;q=0.5, gzip;q=0.8"),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(negotiate("br;q=0, gzip"), Some(ContentEncoding::Gzip));
+        assert_eq!(
+            negotiate("BR;q=0.8, GZIP;q=0.8"),
+            Some(ContentEncoding::Brotli)
+        );
+        assert_eq!(negotiate("br;q=0, gzip;q=0"), None);
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("\"abc\"", "abc"));
+        assert!(etag_matches("W/\"abc\"", "abc"));
+        assert!(etag_matches("\"def\", \"abc\"", "abc"));
+        assert!(etag_matches("*", "abc"));
+
+        // Compressed representations are the same contents
+        assert!(etag_matches(
+            etag("abc", Some(ContentEncoding::Brotli)).as_str(),
+            "abc"
+        ));
+        assert!(etag_matches(
+            etag("abc", Some(ContentEncoding::Gzip)).as_str(),
+            "abc"
+        ));
+
+        assert!(etag_matches("\"def\"", "abc") == false);
+        assert!(etag_matches("\"abcd\"", "abc") == false);
+        assert!(etag_matches("", "abc") == false);
+    }
+
+    #[test]
+    fn test_not_modified() {
+        // 2021-01-01T00:00:00Z
+        let updated = 1_609_459_200_000u64;
+
+        assert!(not_modified(&HeaderMap::new(), "abc", updated) == false);
+        assert!(not_modified(
+            &headers(&[("If-None-Match", "\"abc\"")]),
+            "abc",
+            updated
+        ));
+        assert!(not_modified(&headers(&[("If-None-Match", "\"def\"")]), "abc", updated) == false);
+
+        let since = |value: &str| headers(&[("If-Modified-Since", value)]);
+        assert!(not_modified(
+            &since(http_date(updated).as_str()),
+            "abc",
+            updated
+        ));
+        assert!(not_modified(
+            &since(http_date(updated + 60_000).as_str()),
+            "abc",
+            updated
+        ));
+        assert!(
+            not_modified(&since(http_date(updated - 60_000).as_str()), "abc", updated) == false
+        );
+        assert!(not_modified(&since("yesterday"), "abc", updated) == false);
+
+        // The entity tag wins over the date when both are sent
+        let both = headers(&[
+            ("If-None-Match", "\"def\""),
+            ("If-Modified-Since", http_date(updated).as_str()),
+        ]);
+        assert!(not_modified(&both, "abc", updated) == false);
+    }
+}
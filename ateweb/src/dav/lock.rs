@@ -0,0 +1,272 @@
+use fxhash::FxHashMap;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use std::sync::Arc;
+use std::time::Instant;
+
+use ate::prelude::*;
+use ate_files::prelude::*;
+
+use crate::error::WebServerError;
+
+/// Write lock that a WebDAV client holds on a resource (it is also held as
+/// an advisory lock on the file system so that it applies to FUSE mounts
+/// and other web servers too)
+pub struct DavLock {
+    pub token: String,
+    /// Identity of the user that took the lock (its token is worthless to
+    /// anyone else)
+    pub principal: String,
+    pub href: String,
+    pub infinite: bool,
+    pub owner: u64,
+    pub ino: u64,
+    pub accessor: Arc<FileAccessor>,
+    pub expires: Instant,
+}
+
+impl DavLock {
+    /// Returns true if the lock applies to the supplied resource
+    pub fn covers(&self, href: &str) -> bool {
+        if self.href == href {
+            return true;
+        }
+        self.infinite && is_descendant(self.href.as_str(), href)
+    }
+}
+
+fn is_descendant(parent: &str, href: &str) -> bool {
+    href.starts_with(parent) && (parent.ends_with("/") || href[parent.len()..].starts_with("/"))
+}
+
+#[derive(Default)]
+pub struct DavLocks {
+    locks: FxHashMap<String, DavLock>,
+}
+
+impl DavLocks {
+    pub fn insert(&mut self, lock: DavLock) {
+        self.locks.insert(lock.token.clone(), lock);
+    }
+
+    /// Returns a lock that the principal holds
+    pub fn get_mut(&mut self, token: &str, principal: &str) -> Option<&mut DavLock> {
+        self.locks
+            .get_mut(token)
+            .filter(|a| a.principal.as_str() == principal)
+    }
+
+    /// Removes a lock that the principal holds
+    pub fn remove(&mut self, token: &str, principal: &str) -> Option<DavLock> {
+        match self.get_mut(token, principal) {
+            Some(_) => self.locks.remove(token),
+            None => None,
+        }
+    }
+
+    /// Returns the first lock that prevents a resource (or anything below it)
+    /// from being modified by a principal that submitted the supplied tokens
+    /// (tokens only count for the principal that took the lock)
+    pub fn conflict(&self, href: &str, tokens: &[String], principal: &str) -> Option<&DavLock> {
+        self.locks
+            .values()
+            .filter(|a| a.principal.as_str() != principal || tokens.contains(&a.token) == false)
+            .filter(|a| a.covers(href) || is_descendant(href, a.href.as_str()))
+            .next()
+    }
+
+    /// Removes all the locks that have timed out
+    pub fn drain_expired(&mut self) -> Vec<DavLock> {
+        let now = Instant::now();
+        let expired = self
+            .locks
+            .iter()
+            .filter(|(_, a)| a.expires <= now)
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        expired
+            .into_iter()
+            .filter_map(|k| self.locks.remove(&k))
+            .collect()
+    }
+}
+
+/// Generates a new lock token (a random UUID from the secure random number
+/// generator of the operating system)
+pub fn new_token() -> Result<String, WebServerError> {
+    let mut uuid = [0u8; 16];
+    SystemRandom::new().fill(&mut uuid)?;
+    uuid[6] = (uuid[6] & 0x0F) | 0x40;
+    uuid[8] = (uuid[8] & 0x3F) | 0x80;
+
+    let hex = uuid
+        .iter()
+        .map(|a| format!("{:02x}", a))
+        .collect::<String>();
+    Ok(format!(
+        "opaquelocktoken:{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+/// Returns the principal that locks are bound to for a session (the user
+/// behind it, as group sessions are named after the group)
+pub fn principal(session: &AteSessionType) -> String {
+    session.user().identity().to_string()
+}
+
+/// Extracts the lock tokens that a client submitted in its `If` header
+pub fn if_tokens(headers: &http::HeaderMap) -> Vec<String> {
+    let val = match headers.get("If").and_then(|a| a.to_str().ok()) {
+        Some(a) => a,
+        None => {
+            return Vec::new();
+        }
+    };
+    val.split('<')
+        .skip(1)
+        .filter_map(|a| a.split_once('>'))
+        .map(|(token, _)| token.to_string())
+        .filter(|a| a.starts_with("opaquelocktoken:"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn test_accessor() -> Arc<FileAccessor> {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_dav_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session.identity = "alice@example.com".to_string();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        Arc::new(
+            FileAccessor::new(
+                chain,
+                None,
+                session.into(),
+                TransactionScope::Full,
+                TransactionScope::Full,
+                false,
+                false,
+            )
+            .await,
+        )
+    }
+
+    fn lock(accessor: &Arc<FileAccessor>, href: &str, principal: &str, infinite: bool) -> DavLock {
+        DavLock {
+            token: new_token().unwrap(),
+            principal: principal.to_string(),
+            href: href.to_string(),
+            infinite,
+            owner: 1,
+            ino: 1,
+            accessor: Arc::clone(accessor),
+            expires: Instant::now() + Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_new_token() {
+        let a = new_token().unwrap();
+        let b = new_token().unwrap();
+        assert_ne!(a, b);
+
+        let uuid = a.strip_prefix("opaquelocktoken:").unwrap();
+        let parts = uuid.split('-').map(|a| a.len()).collect::<Vec<_>>();
+        assert_eq!(parts, vec![8, 4, 4, 4, 12]);
+        assert!(uuid.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+        assert_eq!(&uuid[14..15], "4");
+    }
+
+    #[test]
+    fn test_if_tokens() {
+        let mut headers = http::HeaderMap::new();
+        assert!(if_tokens(&headers).is_empty());
+
+        headers.insert(
+            "If",
+            "</dav/g/fs/a> (<opaquelocktoken:1234> [\"etag\"]) (Not <DAV:no-lock>)"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            if_tokens(&headers),
+            vec!["opaquelocktoken:1234".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_covers() {
+        assert!(is_descendant("/dav/g/fs/dir", "/dav/g/fs/dir/file"));
+        assert!(is_descendant("/dav/g/fs/", "/dav/g/fs/file"));
+        assert!(is_descendant("/dav/g/fs/dir", "/dav/g/fs/directory") == false);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_locks_are_bound_to_their_principal() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+
+        let mut locks = DavLocks::default();
+        let dir = lock(&accessor, "/dav/g/fs/dir", "alice", true);
+        let dir_token = dir.token.clone();
+        locks.insert(dir);
+
+        // The owner of the lock may modify anything below it with the token
+        let tokens = vec![dir_token.clone()];
+        assert!(locks
+            .conflict("/dav/g/fs/dir/a", &tokens[..], "alice")
+            .is_none());
+        assert!(locks.conflict("/dav/g/fs/dir/a", &[], "alice").is_some());
+        assert!(locks.conflict("/dav/g/fs/other", &[], "alice").is_none());
+
+        // Another principal gets nothing from submitting a stolen token
+        assert!(locks
+            .conflict("/dav/g/fs/dir/a", &tokens[..], "bob")
+            .is_some());
+        assert!(locks.conflict("/dav/g/fs", &tokens[..], "bob").is_some());
+        assert!(locks.get_mut(dir_token.as_str(), "bob").is_none());
+        assert!(locks.remove(dir_token.as_str(), "bob").is_none());
+
+        assert!(locks.get_mut(dir_token.as_str(), "alice").is_some());
+        assert!(locks.remove(dir_token.as_str(), "alice").is_some());
+        assert!(locks.conflict("/dav/g/fs/dir/a", &[], "bob").is_none());
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_expired_locks_are_drained() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        assert_eq!(principal(&accessor.session), "alice@example.com");
+
+        let mut locks = DavLocks::default();
+        let mut expired = lock(&accessor, "/dav/g/fs/a", "alice", false);
+        expired.expires = Instant::now();
+        let expired_token = expired.token.clone();
+        locks.insert(expired);
+        locks.insert(lock(&accessor, "/dav/g/fs/b", "alice", false));
+
+        let drained = locks.drain_expired();
+        assert_eq!(drained.len(), 1);
+        assert_eq!(drained[0].token, expired_token);
+        assert!(locks.conflict("/dav/g/fs/a", &[], "bob").is_none());
+        assert!(locks.conflict("/dav/g/fs/b", &[], "bob").is_some());
+    }
+}
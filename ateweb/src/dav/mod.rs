@@ -0,0 +1,1196 @@
+mod lock;
+mod xml;
+
+use error_chain::bail;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::Mutex;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use ttl_cache::TtlCache;
+use wasmer_auth::cmd::gather_command;
+
+use hyper::body::HttpBody;
+use hyper::header::HeaderValue;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+
+use ate::prelude::*;
+use ate_files::codes::*;
+use ate_files::lock::F_UNLCK;
+use ate_files::lock::F_WRLCK;
+use ate_files::prelude::*;
+
+use super::error::WebServerError;
+use super::error::WebServerErrorKind;
+use super::model::WebConf;
+use super::model::WEB_CONF_FILES_CONF;
+use super::server::Server;
+use crate::helper::decode_path;
+use crate::helper::encode_path;
+use crate::helper::escape;
+
+use lock::*;
+use xml::*;
+
+/// Path under which the file systems are served over WebDAV
+/// (e.g. /dav/mygroup/myfs/some/file.txt)
+pub const DAV_PREFIX: &'static str = "/dav/";
+
+const DAV_LOCK_TIMEOUT: u64 = 600; // Seconds a lock is held when the client does not ask for a timeout
+const DAV_LOCK_TIMEOUT_MAX: u64 = 3600; // Longest that a client may hold a lock without refreshing it
+const DAV_READ_SIZE: u32 = 1048576; // Size of the blocks that are streamed back to clients
+
+/// Serves the ATE file systems over WebDAV so that they can be used by
+/// machines that are unable to mount them with FUSE
+pub struct DavServer {
+    registry: Arc<Registry>,
+    db_url: url::Url,
+    auth_url: url::Url,
+    ttl: Duration,
+    accessors: Mutex<TtlCache<String, Arc<FileAccessor>>>,
+    locks: Mutex<DavLocks>,
+}
+
+/// File system and path that a request refers to
+struct DavTarget {
+    accessor: Arc<FileAccessor>,
+    ctx: RequestContext,
+    principal: String,
+    base: String,
+    path: String,
+}
+
+impl DavTarget {
+    fn href(&self, path: &str, kind: FileKind) -> String {
+        let mut ret = format!("{}{}", self.base, path);
+        if kind == FileKind::Directory && ret.ends_with("/") == false {
+            ret.push('/');
+        }
+        encode_path(ret.as_str())
+    }
+
+    /// Returns true if the path is the configuration of a web site (which is
+    /// found in the www file system of the site)
+    fn is_web_conf(&self, path: &str) -> bool {
+        self.base.ends_with("/www") && path == format!("/{}", WEB_CONF_FILES_CONF)
+    }
+
+    fn lock_href(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+
+    async fn stat(&self, path: &str) -> Result<Option<FileAttr>, WebServerError> {
+        match self.accessor.search(&self.ctx, path).await {
+            Ok(a) => Ok(a),
+            Err(FileSystemError(FileSystemErrorKind::NoEntry, _))
+            | Err(FileSystemError(FileSystemErrorKind::DoesNotExist, _))
+            | Err(FileSystemError(FileSystemErrorKind::NotDirectory, _)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn children(&self, ino: u64) -> Result<Vec<(String, FileAttr)>, WebServerError> {
+        let open = self
+            .accessor
+            .opendir(&self.ctx, ino, O_RDONLY as u32)
+            .await?;
+        let ret = open
+            .children
+            .iter()
+            .filter(|a| a.name != "." && a.name != "..")
+            .map(|a| (a.name.clone(), a.attr.clone()))
+            .collect::<Vec<_>>();
+        self.accessor.releasedir(&self.ctx, ino, open.fh, 0).await?;
+        Ok(ret)
+    }
+
+    /// Removes a file or a directory along with everything in it
+    async fn remove(&self, path: &str, attr: &FileAttr) -> Result<(), WebServerError> {
+        let mut work = vec![(path.to_string(), attr.clone(), false)];
+        while let Some((path, attr, expanded)) = work.pop() {
+            let (parent, name) = split_parent(path.as_str());
+            let parent = match self.stat(parent.as_str()).await? {
+                Some(a) => a,
+                None => continue,
+            };
+            if attr.kind != FileKind::Directory {
+                self.accessor
+                    .unlink(&self.ctx, parent.ino, name.as_str())
+                    .await?;
+            } else if expanded {
+                self.accessor
+                    .rmdir(&self.ctx, parent.ino, name.as_str())
+                    .await?;
+            } else {
+                let children = self.children(attr.ino).await?;
+                work.push((path.clone(), attr, true));
+                for (name, attr) in children {
+                    work.push((join(path.as_str(), name.as_str()), attr, false));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies a file or a directory (and everything in it when deep is set)
+    async fn copy(
+        &self,
+        src: &FileAttr,
+        parent: u64,
+        name: &str,
+        deep: bool,
+    ) -> Result<(), WebServerError> {
+        let mut work = vec![(src.clone(), parent, name.to_string())];
+        while let Some((src, parent, name)) = work.pop() {
+            if src.kind == FileKind::Directory {
+                let dir = self
+                    .accessor
+                    .mkdir(&self.ctx, parent, name.as_str(), src.mode)
+                    .await?;
+                if deep {
+                    for (name, attr) in self.children(src.ino).await? {
+                        work.push((attr, dir.ino, name));
+                    }
+                }
+                continue;
+            }
+
+            let dst = self
+                .accessor
+                .create(&self.ctx, parent, name.as_str(), src.mode)
+                .await?;
+            let open = self
+                .accessor
+                .open(&self.ctx, src.ino, O_RDONLY as u32)
+                .await?;
+
+            // Whole chunks are shared between the files rather than copied
+            let mut offset = 0u64;
+            while offset < src.size {
+                let copied = self
+                    .accessor
+                    .copy_file_range(
+                        &self.ctx,
+                        src.ino,
+                        open.fh,
+                        offset,
+                        dst.inode,
+                        dst.fh,
+                        offset,
+                        src.size - offset,
+                    )
+                    .await?;
+                if copied == 0 {
+                    break;
+                }
+                offset += copied;
+            }
+
+            self.accessor
+                .release(&self.ctx, src.ino, open.fh, 0, 0, false)
+                .await?;
+            self.accessor
+                .release(&self.ctx, dst.inode, dst.fh, 0, 0, true)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+fn split_parent(path: &str) -> (String, String) {
+    match path.rsplit_once("/") {
+        Some((parent, name)) if parent.len() > 0 => (parent.to_string(), name.to_string()),
+        Some((_, name)) => ("/".to_string(), name.to_string()),
+        None => ("/".to_string(), path.to_string()),
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    match path.ends_with("/") {
+        true => format!("{}{}", path, name),
+        false => format!("{}/{}", path, name),
+    }
+}
+
+/// Returns true if a path is the same as another or somewhere below it
+fn is_within(path: &str, ancestor: &str) -> bool {
+    path == ancestor
+        || ancestor == "/"
+        || (path.starts_with(ancestor) && path[ancestor.len()..].starts_with("/"))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::from(format!("{}\n", code)));
+    *resp.status_mut() = code;
+    resp
+}
+
+fn xml_response(code: StatusCode, body: String) -> Result<Response<Body>, WebServerError> {
+    let mut resp = Response::new(Body::from(body));
+    resp.headers_mut().append(
+        "Content-Type",
+        HeaderValue::from_str("application/xml; charset=utf-8")?,
+    );
+    *resp.status_mut() = code;
+    Ok(resp)
+}
+
+impl DavServer {
+    pub fn new(
+        registry: &Arc<Registry>,
+        db_url: url::Url,
+        auth_url: url::Url,
+        ttl: Duration,
+    ) -> DavServer {
+        DavServer {
+            registry: Arc::clone(registry),
+            db_url,
+            auth_url,
+            ttl,
+            accessors: Mutex::new(TtlCache::new(usize::MAX)),
+            locks: Mutex::new(DavLocks::default()),
+        }
+    }
+
+    pub async fn house_keeping(&self) {
+        {
+            let mut lock = self.accessors.lock().await;
+            lock.iter(); // this will run the remove_expired function
+        }
+        self.release_expired().await;
+    }
+
+    pub(crate) async fn process(
+        &self,
+        server: &Server,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        match self.process_internal(server, req).await {
+            Ok(a) => Ok(a),
+            Err(err) => {
+                let mut resp = Response::new(Body::from(err.response_body()));
+                *resp.status_mut() = err.status_code();
+                if let WebServerError(WebServerErrorKind::Unauthorized, _) = err {
+                    resp.headers_mut().append(
+                        "WWW-Authenticate",
+                        HeaderValue::from_str("Basic realm=\"ate\"")?,
+                    );
+                }
+                Ok(resp)
+            }
+        }
+    }
+
+    async fn process_internal(
+        &self,
+        server: &Server,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        // Clients probe the capabilities before they authenticate
+        if req.method().as_str() == "OPTIONS" {
+            let mut resp = Response::new(Body::empty());
+            resp.headers_mut()
+                .append("DAV", HeaderValue::from_str("1, 2")?);
+            resp.headers_mut()
+                .append("MS-Author-Via", HeaderValue::from_str("DAV")?);
+            resp.headers_mut().append(
+                "Allow",
+                HeaderValue::from_str(
+                    "OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, COPY, MOVE, PROPFIND, PROPPATCH, LOCK, UNLOCK",
+                )?,
+            );
+            return Ok(resp);
+        }
+
+        let target = self.target(&req).await?;
+        match req.method().as_str() {
+            "GET" => self.process_get(server, target, false).await,
+            "HEAD" => self.process_get(server, target, true).await,
+            "PUT" => self.process_put(target, req).await,
+            "DELETE" => self.process_delete(target, req).await,
+            "MKCOL" => self.process_mkcol(target, req).await,
+            "COPY" => self.process_copy_move(target, req, false).await,
+            "MOVE" => self.process_copy_move(target, req, true).await,
+            "PROPFIND" => self.process_propfind(server, target, req).await,
+            "PROPPATCH" => self.process_proppatch(target).await,
+            "LOCK" => self.process_lock(target, req).await,
+            "UNLOCK" => self.process_unlock(target, req).await,
+            _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    /// Determines the file system, path and session from the request
+    async fn target(&self, req: &Request<Body>) -> Result<DavTarget, WebServerError> {
+        let path = match decode_path(req.uri().path()) {
+            Some(a) => a,
+            None => bail!(WebServerErrorKind::BadRequest(
+                "the path is not valid UTF-8".to_string()
+            )),
+        };
+        if path.contains("..") {
+            bail!(WebServerErrorKind::BadRequest(
+                "Accessing parent directories is forbidden".to_string()
+            ));
+        }
+
+        let mut comps = path[DAV_PREFIX.len()..].splitn(3, "/");
+        let (group, fs) = match (comps.next(), comps.next()) {
+            (Some(group), Some(fs)) if group.len() > 0 && fs.len() > 0 => (group, fs),
+            _ => bail!(WebServerErrorKind::BadRequest(
+                "the path must start with /dav/[group]/[file-system]/".to_string()
+            )),
+        };
+        let mut path = format!("/{}", comps.next().unwrap_or(""));
+        while path.len() > 1 && path.ends_with("/") {
+            path.pop();
+        }
+
+        let token = Self::token(req.headers())?;
+        let accessor = self.accessor(group, fs, token.as_str()).await?;
+        Ok(DavTarget {
+            ctx: accessor.session_context(),
+            principal: principal(&accessor.session),
+            accessor,
+            base: format!("{}{}/{}", DAV_PREFIX, group, fs),
+            path,
+        })
+    }
+
+    /// Clients either pass the token as a bearer token or as the password
+    /// of basic authentication (which is all that some of them support)
+    fn token(headers: &http::HeaderMap) -> Result<String, WebServerError> {
+        let auth = match headers.get("Authorization") {
+            Some(a) => a.to_str()?,
+            None => bail!(WebServerErrorKind::Unauthorized),
+        };
+        if let Some(token) = auth.strip_prefix("Bearer ") {
+            return Ok(token.trim().to_string());
+        }
+        if let Some(basic) = auth.strip_prefix("Basic ") {
+            let basic = base64::decode(basic.trim())
+                .ok()
+                .and_then(|a| String::from_utf8(a).ok());
+            if let Some((_, token)) = basic.as_ref().and_then(|a| a.split_once(":")) {
+                return Ok(token.trim().to_string());
+            }
+        }
+        bail!(WebServerErrorKind::Unauthorized);
+    }
+
+    async fn session(&self, group: &str, token: &str) -> Result<AteSessionType, WebServerError> {
+        let session: AteSessionType = base64::decode(token)
+            .ok()
+            .and_then(|a| SerializationFormat::MessagePack.deserialize(a).ok())
+            .ok_or(WebServerErrorKind::Unauthorized)?;
+        let inner = match session {
+            AteSessionType::Group(a) => a.inner,
+            AteSessionType::User(a) => AteSessionInner::User(a),
+            AteSessionType::Sudo(a) => AteSessionInner::Sudo(a),
+            AteSessionType::Nothing => bail!(WebServerErrorKind::Unauthorized),
+        };
+
+        // Attempt to grab additional permissions for the group (if it has any)
+        match gather_command(
+            &self.registry,
+            group.to_string(),
+            inner.clone(),
+            self.auth_url.clone(),
+        )
+        .await
+        {
+            Ok(a) => Ok(a.into()),
+            Err(err) => {
+                debug!(
+                    "group authentication failed: {} - falling back to user level authorization",
+                    err
+                );
+                Ok(inner.into())
+            }
+        }
+    }
+
+    async fn accessor(
+        &self,
+        group: &str,
+        fs: &str,
+        token: &str,
+    ) -> Result<Arc<FileAccessor>, WebServerError> {
+        let key = ChainKey::from(format!("{}/{}", group, fs));
+        let cache_key = format!("{}-{}", AteHash::from_bytes(token.as_bytes()), key);
+        {
+            let mut accessors = self.accessors.lock().await;
+            if let Some(ret) = accessors.remove(&cache_key) {
+                accessors.insert(cache_key, Arc::clone(&ret), self.ttl);
+                return Ok(ret);
+            }
+        }
+
+        let session = self.session(group, token).await?;
+        let chain = self.registry.open(&self.db_url, &key, false).await?;
+        let accessor = Arc::new(
+            FileAccessor::new(
+                chain.as_arc(),
+                Some(group.to_string()),
+                session,
+                TransactionScope::Full,
+                TransactionScope::Full,
+                false,
+                false,
+            )
+            .await,
+        );
+        accessor.init(&accessor.session_context()).await?;
+
+        let mut accessors = self.accessors.lock().await;
+        accessors.insert(cache_key, Arc::clone(&accessor), self.ttl);
+        Ok(accessor)
+    }
+
+    /// Returns true if another client holds a lock on the resource
+    async fn is_locked(&self, target: &DavTarget, path: &str, headers: &http::HeaderMap) -> bool {
+        self.release_expired().await;
+        let tokens = if_tokens(headers);
+        let locks = self.locks.lock().await;
+        locks
+            .conflict(
+                target.lock_href(path).as_str(),
+                &tokens[..],
+                target.principal.as_str(),
+            )
+            .is_some()
+    }
+
+    async fn release_expired(&self) {
+        let expired = self.locks.lock().await.drain_expired();
+        for lock in expired {
+            debug!("dav lock expired - {}", lock.href);
+            Self::release_lock(lock).await;
+        }
+    }
+
+    async fn release_lock(lock: DavLock) {
+        let ctx = lock.accessor.session_context();
+        if let Err(err) = lock
+            .accessor
+            .setlk(
+                &ctx,
+                lock.ino,
+                0,
+                lock.owner,
+                0,
+                u64::MAX,
+                F_UNLCK,
+                0,
+                false,
+            )
+            .await
+        {
+            warn!("failed to release dav lock - {}", err);
+        }
+    }
+
+    async fn process_get(
+        &self,
+        server: &Server,
+        target: DavTarget,
+        is_head: bool,
+    ) -> Result<Response<Body>, WebServerError> {
+        let attr = match target.stat(target.path.as_str()).await? {
+            Some(a) => a,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+
+        // Directories are listed so that they can be browsed
+        if attr.kind == FileKind::Directory {
+            let mut listing = String::new();
+            for (name, attr) in target.children(attr.ino).await? {
+                let href = target.href(
+                    join(target.path.as_str(), name.as_str()).as_str(),
+                    attr.kind,
+                );
+                listing.push_str(
+                    format!(
+                        "<li><a href=\"{}\">{}</a></li>\n",
+                        escape(href.as_str()),
+                        escape(name.as_str())
+                    )
+                    .as_str(),
+                );
+            }
+            let body = format!(
+                "<html><body><h1>{}</h1><ul>\n{}</ul></body></html>\n",
+                escape(target.path.as_str()),
+                listing
+            );
+            let mut resp = match is_head {
+                true => Response::new(Body::empty()),
+                false => Response::new(Body::from(body)),
+            };
+            resp.headers_mut().append(
+                "Content-Type",
+                HeaderValue::from_str("text/html; charset=utf-8")?,
+            );
+            return Ok(resp);
+        }
+
+        let mut resp = if is_head {
+            Response::new(Body::empty())
+        } else {
+            let open = target
+                .accessor
+                .open(&target.ctx, attr.ino, O_RDONLY as u32)
+                .await?;
+
+            // Stream the file back in blocks so that large files are not
+            // held in memory all at once
+            let state = (
+                Arc::clone(&target.accessor),
+                target.ctx,
+                attr.ino,
+                open.fh,
+                0u64,
+            );
+            let stream = futures::stream::unfold(Some(state), |state| async move {
+                let (accessor, ctx, ino, fh, offset) = state?;
+                let ret = accessor.read(&ctx, ino, fh, offset, DAV_READ_SIZE).await;
+                match ret {
+                    Ok(data) if data.len() > 0 => {
+                        let offset = offset + data.len() as u64;
+                        Some((Ok(data), Some((accessor, ctx, ino, fh, offset))))
+                    }
+                    ret => {
+                        let _ = accessor.release(&ctx, ino, fh, 0, 0, false).await;
+                        match ret {
+                            Err(err) => Some((
+                                Err(std::io::Error::new(
+                                    std::io::ErrorKind::Other,
+                                    err.to_string(),
+                                )),
+                                None,
+                            )),
+                            Ok(_) => None,
+                        }
+                    }
+                }
+            });
+            Response::new(Body::wrap_stream(stream))
+        };
+
+        resp.headers_mut().append(
+            "Content-Length",
+            HeaderValue::from_str(attr.size.to_string().as_str())?,
+        );
+        resp.headers_mut()
+            .append("ETag", HeaderValue::from_str(etag(&attr).as_str())?);
+        server.apply_mime(target.path.as_str(), &mut resp)?;
+        Ok(resp)
+    }
+
+    async fn process_put(
+        &self,
+        target: DavTarget,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        if target.path == "/" {
+            return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        if self
+            .is_locked(&target, target.path.as_str(), req.headers())
+            .await
+        {
+            return Ok(status(StatusCode::LOCKED));
+        }
+
+        // Web site configuration is checked before it is saved so that
+        // mistakes are reported to whoever made them
+        let req = match target.is_web_conf(target.path.as_str()) {
+            true => {
+                let (parts, body) = req.into_parts();
+                let data = hyper::body::to_bytes(body)
+                    .await
+                    .map_err(|err| WebServerErrorKind::BadRequest(err.to_string()))?;
+                if let Err(err) = WebConf::parse(&String::from_utf8_lossy(&data[..])) {
+                    let mut resp = Response::new(Body::from(err.response_body()));
+                    *resp.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+                    return Ok(resp);
+                }
+                Request::from_parts(parts, Body::from(data))
+            }
+            false => req,
+        };
+
+        let (parent, name) = split_parent(target.path.as_str());
+        let parent = match target.stat(parent.as_str()).await? {
+            Some(a) if a.kind == FileKind::Directory => a,
+            _ => return Ok(status(StatusCode::CONFLICT)),
+        };
+
+        let existing = target
+            .accessor
+            .lookup(&target.ctx, parent.ino, name.as_str())
+            .await?;
+        let (ino, fh) = match existing.as_ref() {
+            Some(a) if a.kind == FileKind::Directory => {
+                return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+            }
+            Some(a) => {
+                let flags = (O_RDWR as u32) | (O_TRUNC as u32);
+                let open = target.accessor.open(&target.ctx, a.ino, flags).await?;
+                (a.ino, open.fh)
+            }
+            None => {
+                let open = target
+                    .accessor
+                    .create(&target.ctx, parent.ino, name.as_str(), 0o660)
+                    .await?;
+                (open.inode, open.fh)
+            }
+        };
+
+        // Write the data as it arrives
+        let mut body = req.into_body();
+        let mut offset = 0u64;
+        while let Some(data) = body.data().await {
+            let data = match data {
+                Ok(a) => a,
+                Err(err) => {
+                    let _ = target
+                        .accessor
+                        .release(&target.ctx, ino, fh, 0, 0, false)
+                        .await;
+                    bail!(WebServerErrorKind::BadRequest(err.to_string()));
+                }
+            };
+            let written = target
+                .accessor
+                .write(&target.ctx, ino, fh, offset, &data[..], 0)
+                .await?;
+            offset += written;
+        }
+        target
+            .accessor
+            .release(&target.ctx, ino, fh, 0, 0, true)
+            .await?;
+
+        Ok(match existing {
+            Some(_) => status(StatusCode::NO_CONTENT),
+            None => status(StatusCode::CREATED),
+        })
+    }
+
+    async fn process_delete(
+        &self,
+        target: DavTarget,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        if target.path == "/" {
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+        if self
+            .is_locked(&target, target.path.as_str(), req.headers())
+            .await
+        {
+            return Ok(status(StatusCode::LOCKED));
+        }
+        let attr = match target.stat(target.path.as_str()).await? {
+            Some(a) => a,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+        target.remove(target.path.as_str(), &attr).await?;
+        Ok(status(StatusCode::NO_CONTENT))
+    }
+
+    async fn process_mkcol(
+        &self,
+        target: DavTarget,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        if self
+            .is_locked(&target, target.path.as_str(), req.headers())
+            .await
+        {
+            return Ok(status(StatusCode::LOCKED));
+        }
+        let body = hyper::body::to_bytes(req.into_body())
+            .await
+            .map_err(|err| WebServerErrorKind::BadRequest(err.to_string()))?;
+        if body.len() > 0 {
+            return Ok(status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        }
+        if target.stat(target.path.as_str()).await?.is_some() {
+            return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+        }
+
+        let (parent, name) = split_parent(target.path.as_str());
+        let parent = match target.stat(parent.as_str()).await? {
+            Some(a) if a.kind == FileKind::Directory => a,
+            _ => return Ok(status(StatusCode::CONFLICT)),
+        };
+        target
+            .accessor
+            .mkdir(&target.ctx, parent.ino, name.as_str(), 0o770)
+            .await?;
+        Ok(status(StatusCode::CREATED))
+    }
+
+    async fn process_copy_move(
+        &self,
+        target: DavTarget,
+        req: Request<Body>,
+        is_move: bool,
+    ) -> Result<Response<Body>, WebServerError> {
+        // The destination must be on the same file system
+        let dest = match req.headers().get("Destination") {
+            Some(a) => a.to_str()?.to_string(),
+            None => return Ok(status(StatusCode::BAD_REQUEST)),
+        };
+        let dest = match url::Url::parse(dest.as_str()) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => dest,
+        };
+        let dest = match decode_path(dest.as_str()) {
+            Some(a) => a,
+            None => return Ok(status(StatusCode::BAD_REQUEST)),
+        };
+        let mut dest = match dest.strip_prefix(target.base.as_str()) {
+            Some(a) if a.len() == 0 => "/".to_string(),
+            Some(a) if a.starts_with("/") => a.to_string(),
+            _ => return Ok(status(StatusCode::BAD_GATEWAY)),
+        };
+        while dest.len() > 1 && dest.ends_with("/") {
+            dest.pop();
+        }
+        if dest == "/" || dest.contains("..") {
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+
+        // A collection can not be copied or moved into itself and neither can
+        // it replace one of its ancestors (which would remove the source)
+        if is_within(dest.as_str(), target.path.as_str())
+            || is_within(target.path.as_str(), dest.as_str())
+        {
+            return Ok(status(StatusCode::FORBIDDEN));
+        }
+
+        if self.is_locked(&target, dest.as_str(), req.headers()).await
+            || (is_move
+                && self
+                    .is_locked(&target, target.path.as_str(), req.headers())
+                    .await)
+        {
+            return Ok(status(StatusCode::LOCKED));
+        }
+
+        let src = match target.stat(target.path.as_str()).await? {
+            Some(a) => a,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+        let (dst_parent, dst_name) = split_parent(dest.as_str());
+        let dst_parent = match target.stat(dst_parent.as_str()).await? {
+            Some(a) if a.kind == FileKind::Directory => a,
+            _ => return Ok(status(StatusCode::CONFLICT)),
+        };
+
+        // Anything that is already at the destination is replaced (unless
+        // the client asked us not to)
+        let overwrite = req
+            .headers()
+            .get("Overwrite")
+            .map(|a| a.as_bytes() != b"F")
+            .unwrap_or(true);
+        let existing = target.stat(dest.as_str()).await?;
+        if let Some(existing) = existing.as_ref() {
+            if overwrite == false {
+                return Ok(status(StatusCode::PRECONDITION_FAILED));
+            }
+            target.remove(dest.as_str(), existing).await?;
+        }
+
+        if is_move {
+            let (src_parent, src_name) = split_parent(target.path.as_str());
+            let src_parent = match target.stat(src_parent.as_str()).await? {
+                Some(a) => a,
+                None => return Ok(status(StatusCode::NOT_FOUND)),
+            };
+            target
+                .accessor
+                .rename(
+                    &target.ctx,
+                    src_parent.ino,
+                    src_name.as_str(),
+                    dst_parent.ino,
+                    dst_name.as_str(),
+                )
+                .await?;
+        } else {
+            let deep = req
+                .headers()
+                .get("Depth")
+                .map(|a| a.as_bytes() != b"0")
+                .unwrap_or(true);
+            target
+                .copy(&src, dst_parent.ino, dst_name.as_str(), deep)
+                .await?;
+        }
+
+        Ok(match existing {
+            Some(_) => status(StatusCode::NO_CONTENT),
+            None => status(StatusCode::CREATED),
+        })
+    }
+
+    async fn process_propfind(
+        &self,
+        server: &Server,
+        target: DavTarget,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        let attr = match target.stat(target.path.as_str()).await? {
+            Some(a) => a,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+
+        // All the properties are always returned (infinite depth is treated
+        // as a depth of one to avoid walking entire file systems)
+        let depth = req
+            .headers()
+            .get("Depth")
+            .map(|a| a.as_bytes() != b"0")
+            .unwrap_or(true);
+
+        let mut ret = MultiStatus::new();
+        let (_, name) = split_parent(target.path.as_str());
+        let href = target.href(target.path.as_str(), attr.kind);
+        ret.add_props(
+            href.as_str(),
+            name.as_str(),
+            &attr,
+            server.mime_type(target.path.as_str()),
+        );
+        if depth && attr.kind == FileKind::Directory {
+            for (name, attr) in target.children(attr.ino).await? {
+                let path = join(target.path.as_str(), name.as_str());
+                let href = target.href(path.as_str(), attr.kind);
+                ret.add_props(
+                    href.as_str(),
+                    name.as_str(),
+                    &attr,
+                    server.mime_type(path.as_str()),
+                );
+            }
+        }
+        xml_response(StatusCode::MULTI_STATUS, ret.finish())
+    }
+
+    async fn process_proppatch(&self, target: DavTarget) -> Result<Response<Body>, WebServerError> {
+        // Dead properties are not stored so the changes are refused
+        let attr = match target.stat(target.path.as_str()).await? {
+            Some(a) => a,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+        let mut ret = MultiStatus::new();
+        let href = target.href(target.path.as_str(), attr.kind);
+        ret.add_status(href.as_str(), StatusCode::FORBIDDEN);
+        xml_response(StatusCode::MULTI_STATUS, ret.finish())
+    }
+
+    async fn process_lock(
+        &self,
+        target: DavTarget,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        self.release_expired().await;
+
+        let timeout = req
+            .headers()
+            .get("Timeout")
+            .and_then(|a| a.to_str().ok())
+            .and_then(|a| {
+                a.split(",")
+                    .filter_map(|a| a.trim().strip_prefix("Second-"))
+                    .filter_map(|a| a.parse::<u64>().ok())
+                    .next()
+            })
+            .unwrap_or(DAV_LOCK_TIMEOUT)
+            .min(DAV_LOCK_TIMEOUT_MAX);
+        let infinite = req
+            .headers()
+            .get("Depth")
+            .map(|a| a.as_bytes() != b"0")
+            .unwrap_or(true);
+        let tokens = if_tokens(req.headers());
+        let href = target.lock_href(target.path.as_str());
+
+        let body = hyper::body::to_bytes(req.into_body())
+            .await
+            .map_err(|err| WebServerErrorKind::BadRequest(err.to_string()))?;
+
+        // An empty body refreshes a lock the client already holds
+        if body.len() <= 0 {
+            let mut locks = self.locks.lock().await;
+            for token in tokens.iter() {
+                if let Some(lock) = locks.get_mut(token.as_str(), target.principal.as_str()) {
+                    if lock.covers(href.as_str()) {
+                        lock.expires = Instant::now() + Duration::from_secs(timeout);
+                        let depth = if lock.infinite { "infinity" } else { "0" };
+                        let body = lock_discovery(
+                            encode_path(lock.href.as_str()).as_str(),
+                            lock.token.as_str(),
+                            timeout,
+                            depth,
+                        );
+                        return xml_response(StatusCode::OK, body);
+                    }
+                }
+            }
+            return Ok(status(StatusCode::PRECONDITION_FAILED));
+        }
+
+        if self
+            .locks
+            .lock()
+            .await
+            .conflict(href.as_str(), &tokens[..], target.principal.as_str())
+            .is_some()
+        {
+            return Ok(status(StatusCode::LOCKED));
+        }
+
+        // Locking a path that does not exist yet creates an empty file
+        let (attr, created) = match target.stat(target.path.as_str()).await? {
+            Some(a) => (a, false),
+            None => {
+                let (parent, _) = split_parent(target.path.as_str());
+                match target.stat(parent.as_str()).await? {
+                    Some(a) if a.kind == FileKind::Directory => {}
+                    _ => return Ok(status(StatusCode::CONFLICT)),
+                }
+                (
+                    target
+                        .accessor
+                        .touch(&target.ctx, target.path.as_str())
+                        .await?,
+                    true,
+                )
+            }
+        };
+
+        // The lock is also taken on the file system itself so that it
+        // holds against other servers and mounts
+        let token = new_token()?;
+        let owner = fastrand::u64(1..);
+        target
+            .accessor
+            .setlk(
+                &target.ctx,
+                attr.ino,
+                0,
+                owner,
+                0,
+                u64::MAX,
+                F_WRLCK,
+                0,
+                false,
+            )
+            .await?;
+
+        let infinite = infinite && attr.kind == FileKind::Directory;
+        let depth = if infinite { "infinity" } else { "0" };
+        let body = lock_discovery(
+            target.href(target.path.as_str(), attr.kind).as_str(),
+            token.as_str(),
+            timeout,
+            depth,
+        );
+        self.locks.lock().await.insert(DavLock {
+            token: token.clone(),
+            principal: target.principal.clone(),
+            href,
+            infinite,
+            owner,
+            ino: attr.ino,
+            accessor: Arc::clone(&target.accessor),
+            expires: Instant::now() + Duration::from_secs(timeout),
+        });
+
+        let code = match created {
+            true => StatusCode::CREATED,
+            false => StatusCode::OK,
+        };
+        let mut resp = xml_response(code, body)?;
+        resp.headers_mut().append(
+            "Lock-Token",
+            HeaderValue::from_str(format!("<{}>", token).as_str())?,
+        );
+        Ok(resp)
+    }
+
+    async fn process_unlock(
+        &self,
+        target: DavTarget,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, WebServerError> {
+        let token = match req.headers().get("Lock-Token") {
+            Some(a) => a
+                .to_str()?
+                .trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string(),
+            None => return Ok(status(StatusCode::BAD_REQUEST)),
+        };
+        let href = target.lock_href(target.path.as_str());
+
+        let lock = {
+            let mut locks = self.locks.lock().await;
+            let covers = locks
+                .get_mut(token.as_str(), target.principal.as_str())
+                .map(|a| a.covers(href.as_str()))
+                .unwrap_or(false);
+            match covers {
+                true => locks.remove(token.as_str(), target.principal.as_str()),
+                false => None,
+            }
+        };
+        match lock {
+            Some(lock) => {
+                Self::release_lock(lock).await;
+                Ok(status(StatusCode::NO_CONTENT))
+            }
+            None => Ok(status(StatusCode::CONFLICT)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_target() -> DavTarget {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_dav_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let accessor = Arc::new(
+            FileAccessor::new(
+                chain,
+                None,
+                session.into(),
+                TransactionScope::Full,
+                TransactionScope::Full,
+                false,
+                false,
+            )
+            .await,
+        );
+        accessor.init(&accessor.session_context()).await.unwrap();
+
+        // Builds /a/c/file.txt
+        let ctx = accessor.session_context();
+        let a = accessor.mkdir(&ctx, 1, "a", 0o770).await.unwrap();
+        let c = accessor.mkdir(&ctx, a.ino, "c", 0o770).await.unwrap();
+        let file = accessor
+            .create(&ctx, c.ino, "file.txt", 0o644)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, file.inode, file.fh, 0, 0, false)
+            .await
+            .unwrap();
+
+        DavTarget {
+            ctx,
+            principal: principal(&accessor.session),
+            accessor,
+            base: "/dav/group/fs".to_string(),
+            path: "/".to_string(),
+        }
+    }
+
+    async fn copy_move(target: &DavTarget, method: &str, src: &str, dest: &str) -> StatusCode {
+        let cfg_ate = ConfAte::default();
+        let registry = Arc::new(Registry::new(&cfg_ate).await);
+        let url = url::Url::parse("ws://localhost/").unwrap();
+        let server = DavServer::new(&registry, url.clone(), url, Duration::from_secs(60));
+
+        let req = Request::builder()
+            .method(method)
+            .uri(format!("http://localhost{}{}", target.base, src))
+            .header(
+                "Destination",
+                format!("http://localhost{}{}", target.base, dest),
+            )
+            .header("Overwrite", "T")
+            .body(Body::empty())
+            .unwrap();
+        let target = DavTarget {
+            accessor: Arc::clone(&target.accessor),
+            ctx: target.ctx,
+            principal: target.principal.clone(),
+            base: target.base.clone(),
+            path: src.to_string(),
+        };
+        server
+            .process_copy_move(target, req, method == "MOVE")
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn test_is_within() {
+        assert!(is_within("/a", "/a"));
+        assert!(is_within("/a/b", "/a"));
+        assert!(is_within("/a/b", "/"));
+        assert!(is_within("/ab", "/a") == false);
+        assert!(is_within("/a", "/a/b") == false);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_copy_into_descendant_is_forbidden() {
+        ate::utils::bootstrap_test_env();
+        let target = test_target().await;
+
+        let ret = copy_move(&target, "COPY", "/a", "/a/c/copy").await;
+        assert_eq!(ret, StatusCode::FORBIDDEN);
+        assert!(target.stat("/a/c/copy").await.unwrap().is_none());
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_move_into_descendant_is_forbidden() {
+        ate::utils::bootstrap_test_env();
+        let target = test_target().await;
+
+        let ret = copy_move(&target, "MOVE", "/a", "/a/c/moved").await;
+        assert_eq!(ret, StatusCode::FORBIDDEN);
+        assert!(target.stat("/a/c/file.txt").await.unwrap().is_some());
+        assert!(target.stat("/a/c/moved").await.unwrap().is_none());
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_copy_or_move_onto_ancestor_is_forbidden() {
+        ate::utils::bootstrap_test_env();
+        let target = test_target().await;
+
+        // Overwriting the ancestor would remove the source along with it
+        for method in ["COPY", "MOVE"] {
+            let ret = copy_move(&target, method, "/a/c", "/a").await;
+            assert_eq!(ret, StatusCode::FORBIDDEN);
+            assert!(target.stat("/a/c/file.txt").await.unwrap().is_some());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use ate_files::prelude::*;
+use chrono::TimeZone;
+
+use crate::helper::escape;
+
+fn http_date(ms: u64) -> String {
+    chrono::Utc
+        .timestamp_millis(ms as i64)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn iso_date(ms: u64) -> String {
+    chrono::Utc
+        .timestamp_millis(ms as i64)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+/// Entity tag of a file which changes whenever its contents do
+pub fn etag(attr: &FileAttr) -> String {
+    format!("\"{:x}-{:x}-{:x}\"", attr.ino, attr.updated, attr.size)
+}
+
+/// Builds the body of a 207 (Multi-Status) response
+pub struct MultiStatus {
+    body: String,
+}
+
+impl MultiStatus {
+    pub fn new() -> MultiStatus {
+        MultiStatus {
+            body: "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n"
+                .to_string(),
+        }
+    }
+
+    /// Adds the properties of a file or directory
+    pub fn add_props(&mut self, href: &str, name: &str, attr: &FileAttr, mime: Option<&str>) {
+        let mut props = String::new();
+        props.push_str(format!("<D:displayname>{}</D:displayname>", escape(name)).as_str());
+        props.push_str(
+            format!(
+                "<D:creationdate>{}</D:creationdate>",
+                iso_date(attr.created)
+            )
+            .as_str(),
+        );
+        props.push_str(
+            format!(
+                "<D:getlastmodified>{}</D:getlastmodified>",
+                http_date(attr.updated)
+            )
+            .as_str(),
+        );
+        match attr.kind {
+            FileKind::Directory => {
+                props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+            }
+            _ => {
+                props.push_str("<D:resourcetype/>");
+                props.push_str(
+                    format!("<D:getcontentlength>{}</D:getcontentlength>", attr.size).as_str(),
+                );
+                props.push_str(
+                    format!("<D:getetag>{}</D:getetag>", escape(etag(attr).as_str())).as_str(),
+                );
+                if let Some(mime) = mime {
+                    props.push_str(
+                        format!("<D:getcontenttype>{}</D:getcontenttype>", escape(mime)).as_str(),
+                    );
+                }
+            }
+        }
+        props.push_str(
+            "<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope>\
+             <D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>",
+        );
+
+        self.body.push_str(
+            format!(
+                "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop>\
+                 <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+                escape(href),
+                props
+            )
+            .as_str(),
+        );
+    }
+
+    /// Adds a resource that could not be processed
+    pub fn add_status(&mut self, href: &str, status: http::StatusCode) {
+        self.body.push_str(
+            format!(
+                "<D:response><D:href>{}</D:href><D:status>HTTP/1.1 {}</D:status></D:response>\n",
+                escape(href),
+                status
+            )
+            .as_str(),
+        );
+    }
+
+    pub fn finish(mut self) -> String {
+        self.body.push_str("</D:multistatus>\n");
+        self.body
+    }
+}
+
+/// Body returned by a successful LOCK request
+pub fn lock_discovery(href: &str, token: &str, timeout: u64, depth: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+         <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:depth>{}</D:depth><D:timeout>Second-{}</D:timeout>\
+         <D:locktoken><D:href>{}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot>\
+         </D:activelock></D:lockdiscovery></D:prop>\n",
+        depth,
+        timeout,
+        escape(token),
+        escape(href)
+    )
+}
@@ -21,6 +21,7 @@ error_chain! {
         HeaderValueError(http::header::InvalidHeaderValue);
         TokioTungsteniteError(tokio_tungstenite::tungstenite::error::ProtocolError);
         HyperTungsteniteError(hyper_tungstenite::tungstenite::error::ProtocolError);
+        Crypto(ring::error::Unspecified);
     }
     errors {
         BadHost(host: String) {
@@ -39,6 +40,10 @@ error_chain! {
             description("Unknown Host"),
             display("Unknown Host"),
         }
+        Unauthorized {
+            description("Unauthorized"),
+            display("Unauthorized - a valid token must be supplied"),
+        }
     }
 }
 
@@ -48,10 +53,24 @@ impl WebServerError {
             WebServerError(WebServerErrorKind::BadHost(_), _) => StatusCode::BAD_GATEWAY,
             WebServerError(WebServerErrorKind::BadRequest(_), _) => StatusCode::BAD_REQUEST,
             WebServerError(WebServerErrorKind::UnknownHost, _) => StatusCode::BAD_REQUEST,
-            WebServerError(
-                WebServerErrorKind::FileSystemError(FileSystemErrorKind::DoesNotExist),
-                _,
-            ) => StatusCode::NOT_FOUND,
+            WebServerError(WebServerErrorKind::Unauthorized, _) => StatusCode::UNAUTHORIZED,
+            WebServerError(WebServerErrorKind::FileSystemError(err), _) => match err {
+                FileSystemErrorKind::DoesNotExist | FileSystemErrorKind::NoEntry => {
+                    StatusCode::NOT_FOUND
+                }
+                FileSystemErrorKind::NoAccess
+                | FileSystemErrorKind::PermissionDenied
+                | FileSystemErrorKind::ReadOnly => StatusCode::FORBIDDEN,
+                FileSystemErrorKind::AlreadyExists => StatusCode::METHOD_NOT_ALLOWED,
+                FileSystemErrorKind::NotDirectory | FileSystemErrorKind::IsDirectory => {
+                    StatusCode::CONFLICT
+                }
+                FileSystemErrorKind::WouldBlock => StatusCode::LOCKED,
+                FileSystemErrorKind::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+                FileSystemErrorKind::InvalidArguments => StatusCode::BAD_REQUEST,
+                FileSystemErrorKind::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -102,6 +121,10 @@ error_chain! {
             description("authorization failed too many times"),
             display("authorization for {0} failed too many times", domain)
         }
+        NoDnsProvider {
+            description("no dns provider"),
+            display("dns-01 challenges need a dns provider")
+        }
     }
 }
 
@@ -158,6 +181,18 @@ error_chain! {
             description("no tls alpn 01 challenge"),
             display("no tls alpn 01 challenge")
         }
+        NoHttp01Challenge {
+            description("no http 01 challenge"),
+            display("no http 01 challenge")
+        }
+        NoDns01Challenge {
+            description("no dns 01 challenge"),
+            display("no dns 01 challenge")
+        }
+        DnsProvider(err: String) {
+            description("dns provider failed"),
+            display("dns provider failed - {}", err)
+        }
     }
 }
 
@@ -0,0 +1,215 @@
+use error_chain::bail;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use ttl_cache::TtlCache;
+use wasmer_auth::cmd::gather_command;
+use wasmer_auth::cmd::gather_token_command;
+use wasmer_auth::cmd::login_command;
+use wasmer_auth::cmd::login_token_command;
+use wasmer_auth::cmd::token_check_command;
+use wasmer_auth::helper::b64_to_scoped_token;
+use wasmer_auth::helper::SCOPED_TOKEN_PREFIX;
+use wasmer_auth::model::TokenScope;
+
+use ate::prelude::*;
+
+use super::error::WebServerError;
+use super::error::WebServerErrorKind;
+use super::model::WebProtected;
+
+/// Checks the credentials of requests for protected paths against
+/// wasmer-auth (those that pass are remembered for a while so that the
+/// authentication server is not asked on every request)
+pub struct PathGuard {
+    registry: Arc<Registry>,
+    auth_url: url::Url,
+    ttl: Duration,
+    allowed: Mutex<TtlCache<String, ()>>,
+}
+
+impl PathGuard {
+    pub fn new(registry: &Arc<Registry>, auth_url: url::Url, ttl: Duration) -> PathGuard {
+        PathGuard {
+            registry: Arc::clone(registry),
+            auth_url,
+            ttl,
+            allowed: Mutex::new(TtlCache::new(usize::MAX)),
+        }
+    }
+
+    pub async fn house_keeping(&self) {
+        let mut lock = self.allowed.lock().await;
+        lock.iter(); // this will run the remove_expired function
+    }
+
+    /// Returns an error unless the request carries credentials that are
+    /// allowed to access the protected path
+    pub async fn check(
+        &self,
+        headers: &http::HeaderMap,
+        rule: &WebProtected,
+    ) -> Result<(), WebServerError> {
+        let auth = match headers.get("Authorization") {
+            Some(a) => a.to_str()?.trim().to_string(),
+            None => bail!(WebServerErrorKind::Unauthorized),
+        };
+        let cache_key = AteHash::from_bytes(
+            format!(
+                "{}:{}:{}:{}",
+                auth,
+                rule.path,
+                rule.group.as_ref().map(|a| a.as_str()).unwrap_or(""),
+                rule.users.join(",")
+            )
+            .as_bytes(),
+        )
+        .to_string();
+        {
+            let lock = self.allowed.lock().await;
+            if lock.contains_key(&cache_key) {
+                return Ok(());
+            }
+        }
+
+        let (session, identity, scope) = self.session(auth.as_str()).await?;
+
+        // Tokens only reach the group that they were minted for (those
+        // without a group carry the session of the user that minted them)
+        // and were already exchanged for their roles within it
+        if let Some(scope) = scope.as_ref() {
+            if let Some(scoped) = scope.group.as_ref() {
+                if rule.group.as_ref() != Some(scoped) {
+                    debug!(
+                        "access to {} denied - token is scoped to {}",
+                        rule.path, scoped
+                    );
+                    bail!(WebServerErrorKind::Unauthorized);
+                }
+            }
+        }
+        let gathered = scope.as_ref().map(|a| a.group.is_some()).unwrap_or(false);
+        if let Some(group) = rule.group.as_ref().filter(|_| gathered == false) {
+            if let Err(err) = gather_command(
+                &self.registry,
+                group.clone(),
+                session.clone(),
+                self.auth_url.clone(),
+            )
+            .await
+            {
+                debug!("access to {} denied - {}", rule.path, err);
+                bail!(WebServerErrorKind::Unauthorized);
+            }
+        }
+        if rule.users.len() > 0 && rule.users.iter().any(|a| *a == identity) == false {
+            debug!(
+                "access to {} denied - {} is not allowed",
+                rule.path, identity
+            );
+            bail!(WebServerErrorKind::Unauthorized);
+        }
+
+        let mut lock = self.allowed.lock().await;
+        lock.insert(cache_key, (), self.ttl);
+        Ok(())
+    }
+
+    /// Reads the session out of the credentials along with the identity it
+    /// belongs to, both of which have been confirmed by the authentication
+    /// server (tokens also return the scope that the server holds for them)
+    async fn session(
+        &self,
+        auth: &str,
+    ) -> Result<(AteSessionInner, String, Option<TokenScope>), WebServerError> {
+        if let Some(token) = auth.strip_prefix("Bearer ") {
+            return self.token(token.trim()).await;
+        }
+
+        let basic = auth
+            .strip_prefix("Basic ")
+            .and_then(|a| base64::decode(a.trim()).ok())
+            .and_then(|a| String::from_utf8(a).ok());
+        let (username, password) = match basic.as_ref().and_then(|a| a.split_once(":")) {
+            Some(a) => a,
+            None => bail!(WebServerErrorKind::Unauthorized),
+        };
+
+        // Clients that only support basic authentication may pass a token
+        // as the password (in the same way as WebDAV)
+        if password.trim().starts_with(SCOPED_TOKEN_PREFIX) {
+            return self.token(password.trim()).await;
+        }
+        match login_command(
+            &self.registry,
+            username.to_string(),
+            password.to_string(),
+            None,
+            self.auth_url.clone(),
+            false,
+        )
+        .await
+        {
+            Ok(a) => {
+                let identity = a.identity.clone();
+                Ok((AteSessionInner::User(a), identity, None))
+            }
+            Err(err) => {
+                debug!("login failed for {} - {}", username, err);
+                bail!(WebServerErrorKind::Unauthorized);
+            }
+        }
+    }
+
+    /// Only scoped tokens are accepted as the authentication server can look
+    /// them up (bare sessions can be forged by anyone and are refused)
+    async fn token(
+        &self,
+        token: &str,
+    ) -> Result<(AteSessionInner, String, Option<TokenScope>), WebServerError> {
+        let token = match b64_to_scoped_token(token) {
+            Some(a) => a,
+            None => {
+                debug!("access denied - credentials are not a scoped token");
+                bail!(WebServerErrorKind::Unauthorized);
+            }
+        };
+        let checked = match token_check_command(&self.registry, &token, self.auth_url.clone()).await
+        {
+            Ok(a) => a,
+            Err(err) => {
+                debug!("access denied - {}", err);
+                bail!(WebServerErrorKind::Unauthorized);
+            }
+        };
+
+        // The session of the token is sealed so it is exchanged with the
+        // authentication server for the rights that it holds
+        let session = match checked.scope.group.clone() {
+            Some(group) => {
+                match gather_token_command(&self.registry, group, &token, self.auth_url.clone())
+                    .await
+                {
+                    Ok(_) => AteSessionInner::Nothing,
+                    Err(err) => {
+                        debug!("access denied - {}", err);
+                        bail!(WebServerErrorKind::Unauthorized);
+                    }
+                }
+            }
+            None => match login_token_command(&self.registry, &token, self.auth_url.clone()).await
+            {
+                Ok(AteSessionType::User(a)) => AteSessionInner::User(a),
+                Ok(AteSessionType::Sudo(a)) => AteSessionInner::Sudo(a),
+                Ok(_) => bail!(WebServerErrorKind::Unauthorized),
+                Err(err) => {
+                    debug!("access denied - {}", err);
+                    bail!(WebServerErrorKind::Unauthorized);
+                }
+            },
+        };
+        Ok((session, token.identity, Some(checked.scope)))
+    }
+}
@@ -15,3 +15,164 @@ pub fn redirect_body(target: &str) -> String {
         target, target, target
     )
 }
+
+/// Escapes text so that it can be placed within an XML (or HTML) element
+pub fn escape(val: &str) -> String {
+    let mut ret = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Percent encodes a path so that it can be used as a href (the slashes
+/// that separate the components are left alone)
+pub fn encode_path(path: &str) -> String {
+    let mut ret = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                ret.push(b as char)
+            }
+            b => ret.push_str(format!("%{:02X}", b).as_str()),
+        }
+    }
+    ret
+}
+
+/// Decodes a percent encoded path (returns None if it is not valid UTF-8)
+pub fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut n = 0usize;
+    while n < bytes.len() {
+        if bytes[n] == b'%' && n + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[n + 1..n + 3]).ok()?;
+            ret.push(u8::from_str_radix(hex, 16).ok()?);
+            n += 3;
+            continue;
+        }
+        ret.push(bytes[n]);
+        n += 1;
+    }
+    String::from_utf8(ret).ok()
+}
+
+/// Puts a request path into the form that the file system looks it up in
+/// (percent decoded without any empty or `.` components) so that the rules
+/// of a site see the same path as the file that is served. Paths that climb
+/// into a parent directory or that do not decode are refused
+pub fn normalize_path(path: &str) -> Option<String> {
+    let decoded = decode_path(path)?;
+    let mut ret = String::with_capacity(decoded.len());
+    for comp in decoded.split("/") {
+        match comp {
+            "" | "." => continue,
+            ".." => return None,
+            comp => {
+                ret.push('/');
+                ret.push_str(comp);
+            }
+        }
+    }
+    if ret.is_empty() || decoded.ends_with("/") {
+        ret.push('/');
+    }
+    Some(ret)
+}
+
+/// Page that lists the entries in a directory (each entry is named along
+/// with whether it is a directory itself)
+pub fn listing_body(path: &str, entries: &[(String, bool)]) -> String {
+    let mut base = path.to_string();
+    if base.ends_with("/") == false {
+        base.push('/');
+    }
+
+    let mut listing = String::new();
+    if let Some((parent, _)) = base[..base.len() - 1].rsplit_once("/") {
+        let href = encode_path(format!("{}/", parent).as_str());
+        listing
+            .push_str(format!("<li><a href=\"{}\">../</a></li>\n", escape(href.as_str())).as_str());
+    }
+    for (name, is_dir) in entries {
+        let mut name = name.clone();
+        if *is_dir {
+            name.push('/');
+        }
+        let href = encode_path(format!("{}{}", base, name).as_str());
+        listing.push_str(
+            format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape(href.as_str()),
+                escape(name.as_str())
+            )
+            .as_str(),
+        );
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Index of {}</title>
+  </head>
+  <body>
+    <h1>Index of {}</h1>
+    <ul>
+{}    </ul>
+  </body>
+</html>
+"#,
+        escape(base.as_str()),
+        escape(base.as_str()),
+        listing
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/").as_deref(), Some("/"));
+        assert_eq!(normalize_path("").as_deref(), Some("/"));
+        assert_eq!(
+            normalize_path("/index.html").as_deref(),
+            Some("/index.html")
+        );
+        assert_eq!(normalize_path("/docs/").as_deref(), Some("/docs/"));
+        assert_eq!(
+            normalize_path("//admin//secret.html").as_deref(),
+            Some("/admin/secret.html")
+        );
+        assert_eq!(
+            normalize_path("/./admin/./secret.html").as_deref(),
+            Some("/admin/secret.html")
+        );
+        assert_eq!(
+            normalize_path("/%61dmin/secret.html").as_deref(),
+            Some("/admin/secret.html")
+        );
+        assert_eq!(
+            normalize_path("/%2Fadmin%2Fsecret.html").as_deref(),
+            Some("/admin/secret.html")
+        );
+        assert_eq!(
+            normalize_path("/my%20notes.txt").as_deref(),
+            Some("/my notes.txt")
+        );
+
+        assert_eq!(normalize_path("/public/../admin/secret.html"), None);
+        assert_eq!(normalize_path("/public/%2E%2E/admin/secret.html"), None);
+        assert_eq!(normalize_path("/%zz"), None);
+        assert_eq!(normalize_path("/%ff"), None);
+    }
+}
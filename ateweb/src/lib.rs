@@ -1,6 +1,9 @@
 pub mod builder;
 pub mod conf;
+pub mod content;
+pub mod dav;
 pub mod error;
+pub mod guard;
 pub mod helper;
 pub mod model;
 pub mod opt;
@@ -1,5 +1,7 @@
+mod pattern;
 mod web_conf;
 
+pub use pattern::*;
 pub use web_conf::*;
 
 pub const WEB_CONF_FILES: &'static str = ".conf/";
@@ -8,3 +10,4 @@ pub const WEB_CONF_FILES_WEB_CERT: &'static str = ".conf/cert.pem";
 pub const WEB_CONF_FILES_WEB_KEY: &'static str = ".conf/key.pem";
 pub const WEB_CONF_FILES_ALPN_CERT: &'static str = ".conf/alpn/cert.pem";
 pub const WEB_CONF_FILES_ALPN_KEY: &'static str = ".conf/alpn/key.pem";
+pub const WEB_CONF_FILES_ACME: &'static str = ".conf/acme/";
@@ -0,0 +1,108 @@
+/// Matches a path against a pattern where each `*` stands for any run of
+/// characters (including `/`), returning what each `*` matched
+pub fn match_path<'a>(pattern: &str, path: &'a str) -> Option<Vec<&'a str>> {
+    let parts = pattern.split("*").collect::<Vec<_>>();
+    if parts.len() == 1 {
+        return match pattern == path {
+            true => Some(Vec::new()),
+            false => None,
+        };
+    }
+
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if path.len() < first.len() + last.len()
+        || path.starts_with(first) == false
+        || path.ends_with(last) == false
+    {
+        return None;
+    }
+
+    // Every wildcard except the last matches as little as it can
+    let mut rest = &path[first.len()..path.len() - last.len()];
+    let mut ret = Vec::with_capacity(parts.len() - 1);
+    for part in parts[1..parts.len() - 1].iter() {
+        let n = rest.find(part)?;
+        ret.push(&rest[..n]);
+        rest = &rest[n + part.len()..];
+    }
+    ret.push(rest);
+    Some(ret)
+}
+
+/// Builds a path from a target by replacing each `*` with what the `*` in
+/// the same position of the pattern matched
+pub fn expand_path(target: &str, captures: &[&str]) -> String {
+    let mut ret = String::with_capacity(target.len());
+    let mut captures = captures.iter();
+    for (n, part) in target.split("*").enumerate() {
+        if n > 0 {
+            ret.push_str(captures.next().map(|a| *a).unwrap_or(""));
+        }
+        ret.push_str(part);
+    }
+    ret
+}
+
+/// Number of wildcards in a pattern
+pub fn wildcards(pattern: &str) -> usize {
+    pattern.matches("*").count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_path() {
+        assert_eq!(match_path("/about.html", "/about.html"), Some(vec![]));
+        assert_eq!(match_path("/about.html", "/about.htm"), None);
+
+        assert_eq!(
+            match_path("/admin/*", "/admin/secret.html"),
+            Some(vec!["secret.html"])
+        );
+        assert_eq!(
+            match_path("/admin/*", "/admin/a/b.html"),
+            Some(vec!["a/b.html"])
+        );
+        assert_eq!(match_path("/admin/*", "/admin/"), Some(vec![""]));
+        assert_eq!(match_path("/admin/*", "/admin"), None);
+        assert_eq!(match_path("/admin/*", "/public/admin/a.html"), None);
+
+        // Paths are matched as they are given which is why requests are
+        // normalized before they are compared with any rule
+        assert_eq!(match_path("/admin/*", "//admin/secret.html"), None);
+
+        assert_eq!(match_path("/*.html", "/docs/a.html"), Some(vec!["docs/a"]));
+        assert_eq!(
+            match_path("/blog/*/post/*", "/blog/2021/post/hello"),
+            Some(vec!["2021", "hello"])
+        );
+        assert_eq!(match_path("/blog/*/post/*", "/blog/2021/hello"), None);
+
+        // The text around the wildcards must not overlap
+        assert_eq!(match_path("/a*a", "/a"), None);
+        assert_eq!(match_path("/a*a", "/aa"), Some(vec![""]));
+    }
+
+    #[test]
+    fn test_expand_path() {
+        assert_eq!(expand_path("/new/*", &["x.html"]), "/new/x.html");
+        assert_eq!(expand_path("/*/to/*", &["a", "b"]), "/a/to/b");
+        assert_eq!(expand_path("/index.html", &["ignored"]), "/index.html");
+        assert_eq!(expand_path("/*/*", &["a"]), "/a/");
+
+        let captures = match_path("/blog/*/post/*", "/blog/2021/post/hello").unwrap();
+        assert_eq!(
+            expand_path("/posts/*-*.html", &captures[..]),
+            "/posts/2021-hello.html"
+        );
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(wildcards("/index.html"), 0);
+        assert_eq!(wildcards("/blog/*/post/*"), 2);
+    }
+}
@@ -1,6 +1,10 @@
 use fxhash::FxHashMap;
 use serde::*;
 
+use super::pattern::*;
+use crate::error::WebServerError;
+use crate::error::WebServerErrorKind;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WebConf {
     /// Forces the host to be redirected to a new URL
@@ -24,6 +28,80 @@ pub struct WebConf {
     /// List of the domains that this domain will reverse proxy for cors
     #[serde(default)]
     pub cors_proxy: Vec<String>,
+    /// Redirects the paths that match a pattern somewhere else
+    #[serde(default)]
+    pub redirects: Vec<WebRedirect>,
+    /// Serves another file for the paths that match a pattern
+    #[serde(default)]
+    pub rewrites: Vec<WebRewrite>,
+    /// Adds headers (e.g. CSP, HSTS or caching) to the responses of the paths
+    /// that match a pattern
+    #[serde(default)]
+    pub headers: Vec<WebHeaders>,
+    /// Paths that may only be accessed by users that authenticate
+    #[serde(default)]
+    pub protected: Vec<WebProtected>,
+    /// Page that is served for missing paths (for single page applications)
+    #[serde(default)]
+    pub spa_fallback: Option<String>,
+    /// Lists the contents of directories that have no default page
+    #[serde(default)]
+    pub directory_listing: bool,
+}
+
+/// Redirects the paths that match a pattern (`*` matches anything)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebRedirect {
+    /// Pattern that the path must match (e.g. /blog/*)
+    pub from: String,
+    /// Path or URL to redirect to where each `*` is replaced by what was
+    /// matched by the pattern (e.g. https://blog.example.com/*)
+    pub to: String,
+    /// Status code of the redirect (301, 302, 303, 307 or 308)
+    #[serde(default = "default_redirect_status")]
+    pub status: u16,
+}
+
+fn default_redirect_status() -> u16 {
+    308
+}
+
+/// Serves another file for the paths that match a pattern (`*` matches anything)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebRewrite {
+    /// Pattern that the path must match (e.g. /docs/*)
+    pub from: String,
+    /// Path that is served instead where each `*` is replaced by what was
+    /// matched by the pattern (e.g. /documentation/*)
+    pub to: String,
+}
+
+/// Headers that are added to the responses of the paths that match a pattern
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebHeaders {
+    /// Pattern that the path must match (e.g. /assets/*)
+    pub path: String,
+    /// Headers that are added (replacing any that are already there)
+    #[serde(default)]
+    pub values: FxHashMap<String, String>,
+}
+
+/// Paths that may only be accessed by users that authenticate with
+/// wasmer-auth, either by basic authentication or with a token
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebProtected {
+    /// Pattern that the path must match (e.g. /admin/*)
+    pub path: String,
+    /// Users must be a member of this group (as confirmed by the
+    /// authentication server)
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Users that are allowed in (when empty anyone that authenticates is)
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Realm reported to browsers when they ask for credentials
+    #[serde(default)]
+    pub realm: Option<String>,
 }
 
 impl Default for WebConf {
@@ -36,6 +114,270 @@ impl Default for WebConf {
             default_page: None,
             status_pages: FxHashMap::default(),
             cors_proxy: Vec::new(),
+            redirects: Vec::new(),
+            rewrites: Vec::new(),
+            headers: Vec::new(),
+            protected: Vec::new(),
+            spa_fallback: None,
+            directory_listing: false,
+        }
+    }
+}
+
+impl WebConf {
+    /// Reads the configuration from YAML and checks that it is valid
+    pub fn parse(data: &str) -> Result<WebConf, WebServerError> {
+        let ret = serde_yaml::from_str::<WebConf>(data)
+            .map_err(|err| WebServerErrorKind::BadConfiguration(err.to_string()))?;
+        ret.validate()?;
+        Ok(ret)
+    }
+
+    /// Checks the configuration for mistakes (all of them are reported at
+    /// once so that they can be fixed in one go)
+    pub fn validate(&self) -> Result<(), WebServerError> {
+        let mut errors = Vec::new();
+
+        if let Some(redirect) = self.redirect.as_ref() {
+            if redirect.parse::<http::uri::Authority>().is_err() {
+                errors.push(format!("redirect '{}' is not a valid host", redirect));
+            }
+        }
+        if let Some(page) = self.default_page.as_ref() {
+            if page.len() <= 0 || page.contains("..") {
+                errors.push(format!("default_page '{}' must be a file name", page));
+            }
+        }
+        for (code, page) in self.status_pages.iter() {
+            if http::StatusCode::from_u16(*code).is_err() {
+                errors.push(format!(
+                    "status_pages has an invalid status code ({})",
+                    code
+                ));
+            }
+            check_parent(&mut errors, format!("status_pages.{}", code), page);
+        }
+        for (n, host) in self.cors_proxy.iter().enumerate() {
+            if host.parse::<http::uri::Authority>().is_err() {
+                errors.push(format!("cors_proxy[{}] '{}' is not a valid host", n, host));
+            }
+        }
+
+        for (n, rule) in self.redirects.iter().enumerate() {
+            check_pattern(&mut errors, format!("redirects[{}].from", n), &rule.from);
+            let field = format!("redirects[{}].to", n);
+            if rule.to.starts_with("/") == false
+                && rule.to.starts_with("http://") == false
+                && rule.to.starts_with("https://") == false
+            {
+                errors.push(format!(
+                    "{} '{}' must be a path or an http(s) URL",
+                    field, rule.to
+                ));
+            }
+            check_wildcards(&mut errors, field, &rule.from, &rule.to);
+            match rule.status {
+                301 | 302 | 303 | 307 | 308 => {}
+                status => errors.push(format!(
+                    "redirects[{}].status ({}) must be 301, 302, 303, 307 or 308",
+                    n, status
+                )),
+            }
+        }
+
+        for (n, rule) in self.rewrites.iter().enumerate() {
+            check_pattern(&mut errors, format!("rewrites[{}].from", n), &rule.from);
+            let field = format!("rewrites[{}].to", n);
+            check_path(&mut errors, field.clone(), &rule.to);
+            check_wildcards(&mut errors, field, &rule.from, &rule.to);
+        }
+
+        for (n, rule) in self.headers.iter().enumerate() {
+            check_pattern(&mut errors, format!("headers[{}].path", n), &rule.path);
+            for (name, value) in rule.values.iter() {
+                if http::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    errors.push(format!(
+                        "headers[{}].values '{}' is not a valid header name",
+                        n, name
+                    ));
+                }
+                if http::header::HeaderValue::from_str(value.as_str()).is_err() {
+                    errors.push(format!(
+                        "headers[{}].values.{} is not a valid header value",
+                        n, name
+                    ));
+                }
+            }
         }
+
+        for (n, rule) in self.protected.iter().enumerate() {
+            check_pattern(&mut errors, format!("protected[{}].path", n), &rule.path);
+            if rule.group.is_none() && rule.users.len() <= 0 {
+                errors.push(format!(
+                    "protected[{}] must name a group or a list of users",
+                    n
+                ));
+            }
+            if let Some(realm) = rule.realm.as_ref() {
+                if realm.contains("\"") {
+                    errors.push(format!("protected[{}].realm must not contain quotes", n));
+                }
+            }
+        }
+
+        if let Some(page) = self.spa_fallback.as_ref() {
+            check_parent(&mut errors, "spa_fallback".to_string(), page);
+        }
+
+        if errors.len() > 0 {
+            return Err(WebServerErrorKind::BadConfiguration(errors.join("; ")).into());
+        }
+        Ok(())
+    }
+
+    /// Returns the first redirect that applies to a path and where it goes
+    pub fn find_redirect(&self, path: &str) -> Option<(&WebRedirect, String)> {
+        self.redirects.iter().find_map(|rule| {
+            match_path(rule.from.as_str(), path)
+                .map(|captures| (rule, expand_path(rule.to.as_str(), &captures[..])))
+        })
+    }
+
+    /// Returns the path that should be served for a path
+    pub fn rewrite(&self, path: &str) -> Option<String> {
+        self.rewrites.iter().find_map(|rule| {
+            match_path(rule.from.as_str(), path)
+                .map(|captures| expand_path(rule.to.as_str(), &captures[..]))
+        })
+    }
+
+    /// Returns the first protection rule that applies to a path (a rule on
+    /// everything in a directory such as `/admin/*` also protects the
+    /// directory itself as its default page is served for `/admin`)
+    pub fn find_protected(&self, path: &str) -> Option<&WebProtected> {
+        let dir = format!("{}/", path.trim_end_matches('/'));
+        self.protected.iter().find(|rule| {
+            match_path(rule.path.as_str(), path).is_some()
+                || match_path(rule.path.as_str(), dir.as_str()).is_some()
+        })
+    }
+
+    /// Returns all the headers that apply to a path (later rules win)
+    pub fn find_headers(&self, path: &str) -> Vec<(&String, &String)> {
+        self.headers
+            .iter()
+            .filter(|rule| match_path(rule.path.as_str(), path).is_some())
+            .flat_map(|rule| rule.values.iter())
+            .collect()
+    }
+}
+
+fn check_parent(errors: &mut Vec<String>, field: String, path: &str) {
+    if path.contains("..") {
+        errors.push(format!("{} '{}' must not contain '..'", field, path));
+    }
+}
+
+fn check_path(errors: &mut Vec<String>, field: String, path: &str) {
+    if path.starts_with("/") == false {
+        errors.push(format!("{} '{}' must start with '/'", field, path));
+    }
+    check_parent(errors, field, path);
+}
+
+fn check_pattern(errors: &mut Vec<String>, field: String, pattern: &str) {
+    check_path(errors, field.clone(), pattern);
+    if pattern.contains("**") {
+        errors.push(format!(
+            "{} '{}' must not have wildcards next to each other",
+            field, pattern
+        ));
+    }
+}
+
+fn check_wildcards(errors: &mut Vec<String>, field: String, from: &str, to: &str) {
+    if wildcards(to) > wildcards(from) {
+        errors.push(format!(
+            "{} '{}' has more wildcards than the pattern '{}'",
+            field, to, from
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::normalize_path;
+
+    const CONF: &'static str = r#"
+redirects:
+  - from: /old/*
+    to: /new/*
+rewrites:
+  - from: /app/*
+    to: /index.html
+protected:
+  - path: /admin/*
+    group: example.com
+"#;
+
+    fn protected(conf: &WebConf, path: &str) -> bool {
+        match normalize_path(path) {
+            Some(path) => conf.find_protected(path.as_str()).is_some(),
+            None => true,
+        }
+    }
+
+    #[test]
+    fn test_protected_paths_can_not_be_bypassed() {
+        let conf = WebConf::parse(CONF).unwrap();
+        assert!(protected(&conf, "/admin/secret.html"));
+        assert!(protected(&conf, "//admin/secret.html"));
+        assert!(protected(&conf, "/admin//secret.html"));
+        assert!(protected(&conf, "/./admin/secret.html"));
+        assert!(protected(&conf, "/%61dmin/secret.html"));
+        assert!(protected(&conf, "/%2Fadmin/secret.html"));
+        assert!(protected(&conf, "/public/../admin/secret.html"));
+
+        // The directory itself serves its default page (or a listing)
+        assert!(protected(&conf, "/admin"));
+        assert!(protected(&conf, "/admin/"));
+        assert!(protected(&conf, "//admin"));
+        assert!(protected(&conf, "/index.html") == false);
+        assert!(protected(&conf, "/administrator.html") == false);
+    }
+
+    #[test]
+    fn test_redirects_and_rewrites() {
+        let conf = WebConf::parse(CONF).unwrap();
+        let (rule, target) = conf.find_redirect("/old/a/b.html").unwrap();
+        assert_eq!(rule.status, 308);
+        assert_eq!(target, "/new/a/b.html");
+        assert!(conf.find_redirect("/new/a.html").is_none());
+
+        assert_eq!(
+            conf.rewrite("/app/settings").as_deref(),
+            Some("/index.html")
+        );
+        assert_eq!(conf.rewrite("/about.html"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let err = WebConf::parse(
+            r#"
+rewrites:
+  - from: admin/*
+    to: /x/*/*
+protected:
+  - path: /a/**
+"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("rewrites[0].from 'admin/*' must start with '/'"));
+        assert!(err.contains("has more wildcards than the pattern"));
+        assert!(err.contains("must not have wildcards next to each other"));
+        assert!(err.contains("protected[0] must name a group or a list of users"));
     }
 }
@@ -6,6 +6,7 @@ use url::Url;
 use clap::Parser;
 
 use super::OptsAuth;
+use crate::acme::AcmeChallenge;
 
 #[derive(Parser)]
 #[clap(version = "1.6", author = "John S. <johnathan.sharratt@gmail.com>")]
@@ -68,6 +69,22 @@ pub struct OptsWeb {
     /// Location where all the websites will be cached
     #[clap(long, default_value = "/tmp/www")]
     pub log_path: String,
+    /// Serves the file systems over WebDAV under /dav/[group]/[file-system]/
+    /// (clients authenticate with a token either as a bearer token or as the
+    /// password of basic authentication)
+    #[clap(long)]
+    pub dav: bool,
+    /// Challenge used to prove that the domains are controlled by this server
+    /// when certificates are ordered (tls-alpn-01, http-01 or dns-01)
+    #[clap(long, default_value = "tls-alpn-01")]
+    pub acme_challenge: AcmeChallenge,
+    /// Command that adds and removes the TXT records of dns-01 challenges
+    /// (it is run as `[command] add|remove [name] [value]`)
+    #[clap(long)]
+    pub acme_dns_hook: Option<String>,
+    /// Directory of the certificate authority that certificates are ordered from
+    #[clap(long, default_value = "https://acme-v02.api.letsencrypt.org/directory")]
+    pub acme_directory: String,
 }
 
 /// Runs a web server that will serve content from a Wasmer file system
@@ -120,6 +137,22 @@ pub struct OptsAll {
     /// Ensures that this authentication server runs as a specific node_id
     #[clap(short, long)]
     pub node_id: Option<u32>,
+    /// Serves the file systems over WebDAV under /dav/[group]/[file-system]/
+    /// (clients authenticate with a token either as a bearer token or as the
+    /// password of basic authentication)
+    #[clap(long)]
+    pub dav: bool,
+    /// Challenge used to prove that the domains are controlled by this server
+    /// when certificates are ordered (tls-alpn-01, http-01 or dns-01)
+    #[clap(long, default_value = "tls-alpn-01")]
+    pub acme_challenge: AcmeChallenge,
+    /// Command that adds and removes the TXT records of dns-01 challenges
+    /// (it is run as `[command] add|remove [name] [value]`)
+    #[clap(long)]
+    pub acme_dns_hook: Option<String>,
+    /// Directory of the certificate authority that certificates are ordered from
+    #[clap(long, default_value = "https://acme-v02.api.letsencrypt.org/directory")]
+    pub acme_directory: String,
 }
 
 #[derive(Parser)]
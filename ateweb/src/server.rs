@@ -35,10 +35,16 @@ use crate::model::WebConf;
 
 use super::acceptor::*;
 use super::acme::AcmeResolver;
+use super::acme::DnsProvider;
+use super::acme::ACME_HTTP_CHALLENGE_PREFIX;
 use super::builder::*;
 use super::conf::*;
+use super::content::*;
+use super::dav::*;
 use super::error::WebServerError;
 use super::error::WebServerErrorKind;
+use super::guard::PathGuard;
+use super::helper::normalize_path;
 use super::model::*;
 use super::stream::*;
 
@@ -88,6 +94,9 @@ pub struct Server {
     server_conf: ServerConf,
     callback: Option<Arc<dyn ServerCallback + 'static>>,
     mime: FxHashMap<String, String>,
+    dav: Option<DavServer>,
+    guard: PathGuard,
+    dns_provider: Option<Arc<dyn DnsProvider>>,
 }
 
 async fn process(
@@ -134,34 +143,57 @@ impl Server {
         // Now we are ready
         let registry = Arc::new(Registry::new(&builder.conf.cfg_ate).await);
 
-        let session_factory = SessionFactory {
-            auth_url: builder.auth_url.clone(),
-            registry: registry.clone(),
-            master_key: builder.web_master_key.clone(),
+        let session_factory = match builder.session_factory.take() {
+            Some(a) => a,
+            None => Box::new(SessionFactory {
+                auth_url: builder.auth_url.clone(),
+                registry: registry.clone(),
+                master_key: builder.web_master_key.clone(),
+            }),
         };
 
         let repo = Repository::new(
             &registry,
             builder.remote.clone(),
             builder.auth_url.clone(),
-            Box::new(session_factory),
+            session_factory,
             builder.conf.ttl,
         )
         .await?;
 
+        let dav = match builder.conf.dav {
+            true => Some(DavServer::new(
+                &registry,
+                builder.remote.clone(),
+                builder.auth_url.clone(),
+                builder.conf.ttl,
+            )),
+            false => None,
+        };
+        let guard = PathGuard::new(&registry, builder.auth_url.clone(), builder.conf.ttl);
+
         Ok(Arc::new(Server {
             repo,
             web_conf: Mutex::new(FxHashMap::default()),
             server_conf: builder.conf,
             callback: builder.callback,
             mime: Server::init_mime(),
+            dav,
+            guard,
+            dns_provider: builder.dns_provider,
         }))
     }
 
     pub async fn run(self: &Arc<Self>) -> Result<(), Box<dyn std::error::Error>> {
         trace!("running web server");
 
-        let acme = AcmeResolver::new(&self.repo).await?;
+        let acme = AcmeResolver::new(
+            &self.repo,
+            self.server_conf.acme_directory.as_str(),
+            self.server_conf.acme_challenge,
+            self.dns_provider.clone(),
+        )
+        .await?;
 
         let mut joins = Vec::new();
         for listen in self.server_conf.listen.iter() {
@@ -229,6 +261,10 @@ impl Server {
 
     async fn house_keeping(&self) {
         self.repo.house_keeping().await;
+        self.guard.house_keeping().await;
+        if let Some(dav) = &self.dav {
+            dav.house_keeping().await;
+        }
     }
 
     pub(crate) fn get_host(&self, req: &Request<Body>) -> Result<String, WebServerError> {
@@ -274,10 +310,9 @@ impl Server {
             {
                 Some(data) => {
                     let data = String::from_utf8_lossy(&data[..]);
-                    serde_yaml::from_str::<WebConf>(&data).map_err(|err| {
-                        WebServerError::from_kind(WebServerErrorKind::BadConfiguration(
-                            err.to_string(),
-                        ))
+                    WebConf::parse(&data).map_err(|err| {
+                        warn!("invalid {} for {} - {}", WEB_CONF_FILES_CONF, host, err);
+                        err
                     })?
                 }
                 None => {
@@ -369,14 +404,51 @@ impl Server {
     pub(crate) async fn process_redirect(
         &self,
         uri: &str,
+    ) -> Result<Response<Body>, WebServerError> {
+        self.process_redirect_with_status(uri, StatusCode::PERMANENT_REDIRECT)
+            .await
+    }
+
+    pub(crate) async fn process_redirect_with_status(
+        &self,
+        uri: &str,
+        status: StatusCode,
     ) -> Result<Response<Body>, WebServerError> {
         let mut resp = Response::new(Body::from(crate::helper::redirect_body(uri)));
         resp.headers_mut()
             .append("Location", HeaderValue::from_str(uri)?);
-        *resp.status_mut() = StatusCode::PERMANENT_REDIRECT;
+        *resp.status_mut() = status;
         return Ok(resp);
     }
 
+    pub(crate) async fn process_acme_challenge(
+        &self,
+        host: &str,
+        token: &str,
+    ) -> Result<Response<Body>, WebServerError> {
+        let valid = token.len() > 0
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        let key = ChainKey::from(format!("{}/www", host));
+        let path = format!("{}{}", WEB_CONF_FILES_ACME, token);
+        let key_auth = match valid {
+            true => self.repo.get_file(&key, host, path.as_str()).await?,
+            false => None,
+        };
+        let mut resp = match key_auth {
+            Some(data) => Response::new(Body::from(data)),
+            None => {
+                let mut resp = Response::new(Body::from(StatusCode::NOT_FOUND.as_str()));
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+                resp
+            }
+        };
+        resp.headers_mut()
+            .append("Content-Type", HeaderValue::from_str("text/plain")?);
+        Ok(resp)
+    }
+
     pub(crate) fn sanitize(&self, mut path: &str) -> Result<(), WebServerError> {
         while path.starts_with("/") {
             path = &path[1..];
@@ -399,38 +471,187 @@ impl Server {
         host: &str,
         path: &str,
         is_head: bool,
+        headers: &http::HeaderMap,
         conf: &WebConf,
     ) -> Result<Option<Response<Body>>, WebServerError> {
         self.sanitize(path)?;
         let key = ChainKey::from(format!("{}/www", host));
-        trace!("perf-checkpoint: get_file (path={})", path);
-        if let Some(data) = self.repo.get_file(&key, host, path).await? {
-            let len_str = data.len().to_string();
-            trace!("perf-checkpoint: got_file (data_len={})", len_str);
-
-            let mut resp = if is_head {
-                Response::new(Body::empty())
-            } else {
-                Response::new(Body::from(data))
-            };
-            resp.headers_mut()
-                .append("Content-Length", HeaderValue::from_str(len_str.as_str())?);
-            self.apply_mime(path, &mut resp)?;
-            if conf.coop {
-                resp.headers_mut().append(
-                    "Cross-Origin-Embedder-Policy",
-                    HeaderValue::from_str("require-corp")?,
-                );
-                resp.headers_mut().append(
-                    "Cross-Origin-Opener-Policy",
-                    HeaderValue::from_str("same-origin")?,
-                );
+        trace!("perf-checkpoint: stat_file (path={})", path);
+        let file = match self.repo.stat_file(&key, host, path).await? {
+            Some(a) => a,
+            None => {
+                return Ok(None);
             }
-            *resp.status_mut() = StatusCode::OK;
-            Ok(Some(resp))
+        };
+        let len = file.attr.size;
+        let hash = file.hash.to_hex_string();
+        let mime = self.mime_type(path);
+        let compressible = is_compressible(mime);
+        trace!("perf-checkpoint: got_file (len={})", len);
+
+        // Compressed bodies are only sent when the whole file is requested
+        let range = parse_range(headers, len, hash.as_str());
+        let mut encoding = match range {
+            ByteRange::Full if compressible && len <= COMPRESS_MAX => negotiate_encoding(headers),
+            _ => None,
+        };
+
+        let mut resp = if not_modified(headers, hash.as_str(), file.attr.updated) {
+            let mut resp = Response::new(Body::empty());
+            *resp.status_mut() = StatusCode::NOT_MODIFIED;
+            resp
         } else {
-            Ok(None)
+            match range {
+                ByteRange::Unsatisfiable => {
+                    let mut resp = Response::new(Body::empty());
+                    resp.headers_mut().append(
+                        "Content-Range",
+                        HeaderValue::from_str(format!("bytes */{}", len).as_str())?,
+                    );
+                    *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    resp
+                }
+                ByteRange::Partial { start, end } => {
+                    let mut resp = match is_head {
+                        true => Response::new(Body::empty()),
+                        false => Response::new(self.stream_file(
+                            &key,
+                            host,
+                            file.attr.ino,
+                            start,
+                            end + 1,
+                        )),
+                    };
+                    resp.headers_mut().append(
+                        "Content-Range",
+                        HeaderValue::from_str(format!("bytes {}-{}/{}", start, end, len).as_str())?,
+                    );
+                    resp.headers_mut().append(
+                        "Content-Length",
+                        HeaderValue::from_str((end + 1 - start).to_string().as_str())?,
+                    );
+                    *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    resp
+                }
+                ByteRange::Full => {
+                    // Compressing needs the whole file in memory (which is
+                    // why only smaller files are compressed)
+                    let compressed = match (encoding, is_head) {
+                        (Some(enc), false) => {
+                            let data = self
+                                .repo
+                                .read_file(&key, host, file.attr.ino, 0, len as u32)
+                                .await?;
+                            match enc.compress(&data[..]).await {
+                                Ok(a) => Some(a),
+                                Err(err) => {
+                                    warn!("failed to compress {} - {}", path, err);
+                                    encoding = None;
+                                    None
+                                }
+                            }
+                        }
+                        _ => None,
+                    };
+
+                    let mut resp = match compressed {
+                        Some(data) => {
+                            let len_str = data.len().to_string();
+                            let mut resp = Response::new(Body::from(data));
+                            resp.headers_mut()
+                                .append("Content-Length", HeaderValue::from_str(len_str.as_str())?);
+                            resp
+                        }
+                        None if encoding.is_some() => Response::new(Body::empty()),
+                        None => {
+                            let mut resp = match is_head {
+                                true => Response::new(Body::empty()),
+                                false => Response::new(self.stream_file(
+                                    &key,
+                                    host,
+                                    file.attr.ino,
+                                    0,
+                                    len,
+                                )),
+                            };
+                            resp.headers_mut().append(
+                                "Content-Length",
+                                HeaderValue::from_str(len.to_string().as_str())?,
+                            );
+                            resp
+                        }
+                    };
+                    if let Some(encoding) = encoding {
+                        resp.headers_mut()
+                            .append("Content-Encoding", HeaderValue::from_str(encoding.name())?);
+                    }
+                    *resp.status_mut() = StatusCode::OK;
+                    resp
+                }
+            }
+        };
+
+        resp.headers_mut().append(
+            "ETag",
+            HeaderValue::from_str(etag(hash.as_str(), encoding).as_str())?,
+        );
+        resp.headers_mut().append(
+            "Last-Modified",
+            HeaderValue::from_str(http_date(file.attr.updated).as_str())?,
+        );
+        resp.headers_mut()
+            .append("Accept-Ranges", HeaderValue::from_str("bytes")?);
+        resp.headers_mut()
+            .append("Cache-Control", HeaderValue::from_str("no-cache")?);
+        if compressible {
+            resp.headers_mut()
+                .append("Vary", HeaderValue::from_str("Accept-Encoding")?);
         }
+        self.apply_mime(path, &mut resp)?;
+        if conf.coop {
+            resp.headers_mut().append(
+                "Cross-Origin-Embedder-Policy",
+                HeaderValue::from_str("require-corp")?,
+            );
+            resp.headers_mut().append(
+                "Cross-Origin-Opener-Policy",
+                HeaderValue::from_str("same-origin")?,
+            );
+        }
+        Ok(Some(resp))
+    }
+
+    /// Streams part of a file back in blocks so that only the pages that
+    /// hold the range are read (and large files are not held in memory)
+    fn stream_file(&self, key: &ChainKey, host: &str, ino: u64, start: u64, end: u64) -> Body {
+        let state = (Arc::clone(&self.repo), key.clone(), host.to_string(), start);
+        let stream = futures::stream::unfold(Some(state), move |state| async move {
+            let (repo, key, host, offset) = state?;
+            if offset >= end {
+                return None;
+            }
+            let size = (end - offset).min(READ_SIZE as u64) as u32;
+            match repo.read_file(&key, host.as_str(), ino, offset, size).await {
+                Ok(data) if data.len() > 0 => {
+                    let offset = offset + data.len() as u64;
+                    Some((Ok(data), Some((repo, key, host, offset))))
+                }
+                Ok(_) => None,
+                Err(err) => Some((
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        err.to_string(),
+                    )),
+                    None,
+                )),
+            }
+        });
+        Body::wrap_stream(stream)
+    }
+
+    pub(crate) fn mime_type(&self, path: &str) -> Option<&str> {
+        let ext = path.split(".").collect::<Vec<_>>().into_iter().rev().next()?;
+        self.mime.get(ext).map(|a| a.as_str())
     }
 
     pub(crate) fn apply_mime(
@@ -438,12 +659,9 @@ impl Server {
         path: &str,
         resp: &mut Response<Body>,
     ) -> Result<(), WebServerError> {
-        if let Some(ext) = path.split(".").collect::<Vec<_>>().into_iter().rev().next() {
-            let ext = ext.to_string();
-            if let Some(mime) = self.mime.get(&ext) {
-                resp.headers_mut()
-                    .append("Content-Type", HeaderValue::from_str(mime.as_str())?);
-            }
+        if let Some(mime) = self.mime_type(path) {
+            resp.headers_mut()
+                .append("Content-Type", HeaderValue::from_str(mime)?);
         }
         Ok(())
     }
@@ -564,11 +782,44 @@ impl Server {
         ret
     }
 
+    /// Checks the credentials of a request against the protection rule that
+    /// applies to a path (if any), returning the response that refuses it
+    async fn check_protected(
+        &self,
+        host: &str,
+        path: &str,
+        headers: &http::HeaderMap,
+        conf: &WebConf,
+    ) -> Result<Option<Response<Body>>, WebServerError> {
+        let rule = match conf.find_protected(path) {
+            Some(a) => a,
+            None => return Ok(None),
+        };
+        trace!("perf-checkpoint: protected path");
+        if let Err(err) = self.guard.check(headers, rule).await {
+            let mut resp = Response::new(Body::from(err.response_body()));
+            *resp.status_mut() = err.status_code();
+            if let WebServerError(WebServerErrorKind::Unauthorized, _) = err {
+                let realm = rule.realm.as_ref().map(|a| a.as_str()).unwrap_or(host);
+                resp.headers_mut().append(
+                    "WWW-Authenticate",
+                    HeaderValue::from_str(format!("Basic realm=\"{}\"", realm).as_str())?,
+                );
+            }
+            return Ok(Some(resp));
+        }
+        Ok(None)
+    }
+
+    /// Serves a path along with the default page or listing of directories
+    /// and the fallback of single page applications (every path that ends
+    /// up being served is checked against the protection rules)
     pub(crate) async fn process_get_with_default(
         &self,
         host: &str,
         path: &str,
         is_head: bool,
+        headers: &http::HeaderMap,
         conf: &WebConf,
     ) -> Result<Response<Body>, WebServerError> {
         self.sanitize(path)?;
@@ -580,9 +831,19 @@ impl Server {
             path.to_string()
         };
 
+        if let Some(resp) = self
+            .check_protected(host, path.as_str(), headers, conf)
+            .await?
+        {
+            return Ok(resp);
+        }
+
         // Attempt to get the file
         trace!("perf-checkpoint: process_get");
-        match self.process_get(host, path.as_str(), is_head, conf).await? {
+        match self
+            .process_get(host, path.as_str(), is_head, headers, conf)
+            .await?
+        {
             Some(a) => {
                 return Ok(a);
             }
@@ -595,13 +856,56 @@ impl Server {
                     } else {
                         format!("{}{}", path, default_page)
                     };
-                    if let Some(ret) = self.process_get(host, path.as_str(), is_head, conf).await? {
+                    if let Some(resp) = self
+                        .check_protected(host, path.as_str(), headers, conf)
+                        .await?
+                    {
+                        return Ok(resp);
+                    }
+                    if let Some(ret) = self
+                        .process_get(host, path.as_str(), is_head, headers, conf)
+                        .await?
+                    {
                         return Ok(ret);
                     }
                 }
             }
         }
 
+        // Directories that have no default page may be listed instead
+        if conf.directory_listing {
+            trace!("perf-checkpoint: list_dir");
+            let key = ChainKey::from(format!("{}/www", host));
+            if let Some(entries) = self.repo.list_dir(&key, host, path.as_str()).await? {
+                return self.process_listing(path.as_str(), entries, is_head);
+            }
+        }
+
+        // Single page applications handle their own routes (paths that
+        // look like files are still reported as missing)
+        if let Some(page) = conf.spa_fallback.as_ref() {
+            let is_route = path
+                .rsplit("/")
+                .next()
+                .map(|a| a.contains(".") == false)
+                .unwrap_or(true);
+            if is_route {
+                trace!("perf-checkpoint: spa_fallback");
+                if let Some(resp) = self
+                    .check_protected(host, page.as_str(), headers, conf)
+                    .await?
+                {
+                    return Ok(resp);
+                }
+                if let Some(ret) = self
+                    .process_get(host, page.as_str(), is_head, headers, conf)
+                    .await?
+                {
+                    return Ok(ret);
+                }
+            }
+        }
+
         trace!("perf-checkpoint: response from data");
         let data = format!("File Not Found - {}\n", path);
         let mut resp = Response::new(Body::from(data));
@@ -609,6 +913,45 @@ impl Server {
         Ok(resp)
     }
 
+    fn process_listing(
+        &self,
+        path: &str,
+        entries: Vec<(String, FileAttr)>,
+        is_head: bool,
+    ) -> Result<Response<Body>, WebServerError> {
+        // Hidden entries (which includes the configuration files) are not listed
+        let mut entries = entries
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(".") == false)
+            .map(|(name, attr)| (name, attr.kind == FileKind::Directory))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut resp = match is_head {
+            true => Response::new(Body::empty()),
+            false => Response::new(Body::from(crate::helper::listing_body(path, &entries[..]))),
+        };
+        resp.headers_mut().append(
+            "Content-Type",
+            HeaderValue::from_str("text/html; charset=utf-8")?,
+        );
+        resp.headers_mut()
+            .append("Cache-Control", HeaderValue::from_str("no-cache")?);
+        Ok(resp)
+    }
+
+    /// Adds the headers that the configuration has for this path
+    fn apply_headers(&self, path: &str, conf: &WebConf, resp: &mut Response<Body>) {
+        for (name, value) in conf.find_headers(path) {
+            if let (Ok(name), Ok(value)) = (
+                http::header::HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value.as_str()),
+            ) {
+                resp.headers_mut().insert(name, value);
+            }
+        }
+    }
+
     pub(crate) async fn process(
         &self,
         req: Request<Body>,
@@ -625,6 +968,17 @@ impl Server {
         let uri = req.uri().clone();
         let method = req.method().clone();
 
+        if let Some(dav) = &self.dav {
+            if uri.path().starts_with(DAV_PREFIX) {
+                trace!("perf-checkpoint: dav");
+                let ret = dav.process(self, req).await;
+                if let Ok(a) = ret.as_ref() {
+                    info!("dav peer={} method={} path={} - {}", sock_addr, method, uri, a.status());
+                }
+                return ret;
+            }
+        }
+
         if method == Method::POST || method == Method::PUT {
             trace!("perf-checkpoint: put/post");
 
@@ -665,6 +1019,14 @@ impl Server {
 
         let is_head = method == Method::HEAD;
         let host = self.get_host(&req)?;
+
+        // Challenges are answered before anything else (including redirects)
+        // as the certificate authority only ever asks for them over HTTP
+        if let Some(token) = uri.path().strip_prefix(ACME_HTTP_CHALLENGE_PREFIX) {
+            trace!("perf-checkpoint: acme challenge");
+            return self.process_acme_challenge(host.as_str(), token).await;
+        }
+
         let conf = self.get_conf(host.as_str()).await?;
 
         let ret = self.process_internal(req, listen, &conf).await;
@@ -682,7 +1044,13 @@ impl Server {
                 if let Some(page) = page {
                     trace!("perf-checkpoint: load error page");
                     if let Some(ret) = self
-                        .process_get(host.as_str(), page.as_str(), is_head, &conf)
+                        .process_get(
+                            host.as_str(),
+                            page.as_str(),
+                            is_head,
+                            &http::HeaderMap::new(),
+                            &conf,
+                        )
                         .await?
                     {
                         return Ok(ret);
@@ -844,13 +1212,59 @@ impl Server {
         let host = self.get_host(&req)?;
         let is_head = req.method() == Method::HEAD || req.method() == Method::OPTIONS;
 
-        let path = req.uri().path();
+        // Redirects, rewrites and protected paths are all matched against the
+        // path in the form that the file system looks it up in so that paths
+        // such as `//admin` or `/%61dmin` can not be used to get around them
+        let path = match normalize_path(req.uri().path()) {
+            Some(a) => a,
+            None => bail!(WebServerErrorKind::BadRequest(
+                "Accessing parent directories is forbidden".to_string()
+            )),
+        };
+        let path = path.as_str();
         match req.method() {
             &Method::OPTIONS | &Method::HEAD | &Method::GET => {
                 trace!("perf-checkpoint: options/head/get");
                 self.sanitize(path)?;
-                self.process_get_with_default(host.as_str(), path, is_head, conf)
-                    .await
+
+                if let Some((rule, target)) = conf.find_redirect(path) {
+                    trace!("perf-checkpoint: redirect rule");
+                    let mut target = target;
+                    if let Some(query) = req.uri().query() {
+                        if target.contains("?") == false {
+                            target.push('?');
+                            target.push_str(query);
+                        }
+                    }
+                    let status =
+                        StatusCode::from_u16(rule.status).unwrap_or(StatusCode::PERMANENT_REDIRECT);
+                    return self
+                        .process_redirect_with_status(target.as_str(), status)
+                        .await;
+                }
+
+                // The path that is actually served is also checked (when the
+                // content is fetched) so that rewrites can not be used to get
+                // around the protection
+                if let Some(resp) = self
+                    .check_protected(host.as_str(), path, req.headers(), conf)
+                    .await?
+                {
+                    return Ok(resp);
+                }
+                let served = conf.rewrite(path);
+
+                let mut resp = self
+                    .process_get_with_default(
+                        host.as_str(),
+                        served.as_ref().map(|a| a.as_str()).unwrap_or(path),
+                        is_head,
+                        req.headers(),
+                        conf,
+                    )
+                    .await?;
+                self.apply_headers(path, conf, &mut resp);
+                Ok(resp)
             }
             _ => {
                 trace!("perf-checkpoint: method_not_allowed");
@@ -899,4 +1313,216 @@ for SessionFactory
         )
         .await?))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acme::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    const HOST: &'static str = "example.test";
+
+    /// Reads the web sites with a fixed session rather than gathering the
+    /// permissions of each host from wasmer-auth
+    struct TestSessionFactory {
+        session: AteSessionUser,
+    }
+
+    #[async_trait]
+    impl RepositorySessionFactory for TestSessionFactory {
+        async fn create(&self, _sni: String, _key: ChainKey) -> Result<AteSessionType, AteError> {
+            Ok(AteSessionType::User(self.session.clone()))
+        }
+    }
+
+    fn test_account() -> Account {
+        let alg = &ECDSA_P256_SHA256_FIXED_SIGNING;
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(alg, &SystemRandom::new()).unwrap();
+        Account {
+            key_pair: EcdsaKeyPair::from_pkcs8(alg, pkcs8.as_ref()).unwrap(),
+            directory: Directory {
+                new_nonce: String::new(),
+                new_account: String::new(),
+                new_order: String::new(),
+                insecure: true,
+            },
+            kid: String::new(),
+        }
+    }
+
+    async fn get(port: u16, path: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .uri(format!("http://127.0.0.1:{}{}", port, path))
+            .header("Host", HOST)
+            .body(Body::empty())
+            .unwrap();
+        let resp = hyper::Client::new().request(req).await.unwrap();
+        let status = resp.status();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Starts a web server that reads its web sites out of a local database
+    /// server (which has to be kept alive for as long as the web server)
+    async fn test_server() -> (Arc<Server>, u16, Arc<ate::mesh::MeshRoot>) {
+        // Web sites are read out of a local database server
+        let cfg_ate = ConfAte::default();
+        let cert = PrivateEncryptKey::generate(KeySize::Bit192);
+        ate::mesh::add_global_certificate(&cert.hash());
+        let port_offset = fastrand::u16(..1000);
+        let db_url =
+            url::Url::parse(format!("ws://localhost:{}/db", 6000 + port_offset).as_str()).unwrap();
+        let mut cfg_mesh = ConfMesh::solo_from_url(
+            &cfg_ate,
+            &db_url,
+            &IpAddr::from_str("::1").unwrap(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        cfg_mesh.wire_protocol = StreamProtocol::WebSocket;
+        cfg_mesh.listen_certificate = Some(cert);
+        let db = create_ethereal_centralized_server(&cfg_ate, &cfg_mesh)
+            .await
+            .unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let web_port = 7000 + port_offset;
+        let server = ServerBuilder::new(db_url.clone(), db_url.clone())
+            .with_conf(&cfg_ate)
+            .with_session_factory(TestSessionFactory { session })
+            .with_acme_challenge(AcmeChallenge::Http01)
+            .add_listener(IpAddr::from_str("127.0.0.1").unwrap(), web_port, false)
+            .build()
+            .await
+            .unwrap();
+        {
+            let server = Arc::clone(&server);
+            TaskEngine::spawn(async move {
+                let _ = server.run().await;
+            });
+        }
+        (server, web_port, db)
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_http_01_challenge_is_served_by_the_listener() {
+        ate::utils::bootstrap_test_env();
+        let (server, web_port, _db) = test_server().await;
+
+        // The web site needs a file system for the challenge to be saved in
+        let key = ChainKey::from(format!("{}/www", HOST));
+        let accessor = server.repo.get_accessor(&key, HOST).await.unwrap();
+        accessor.init(&RequestContext::default()).await.unwrap();
+
+        // Prepare the challenge in the same way as when a certificate is ordered
+        let resolver = AcmeResolver::new(
+            &server.repo,
+            server.server_conf.acme_directory.as_str(),
+            AcmeChallenge::Http01,
+            None,
+        )
+        .await
+        .unwrap();
+        let account = test_account();
+        let challenges = vec![Challenge {
+            typ: ChallengeType::Http01,
+            url: String::new(),
+            token: "Xj3_token-123".to_string(),
+        }];
+        let challenge = resolver
+            .prepare(&account, HOST, false, &challenges)
+            .await
+            .unwrap();
+        assert_eq!(challenge.token, challenges[0].token);
+        let (_, key_auth) = account.http_01(&challenges).unwrap();
+
+        // The certificate authority fetches it over plain HTTP
+        let path = format!("{}{}", ACME_HTTP_CHALLENGE_PREFIX, challenge.token);
+        let mut served = None;
+        for _ in 0..50 {
+            match hyper::Client::new()
+                .get(format!("http://127.0.0.1:{}/", web_port).parse().unwrap())
+                .await
+            {
+                Ok(_) => {
+                    served = Some(get(web_port, path.as_str()).await);
+                    break;
+                }
+                Err(_) => ate::engine::sleep(Duration::from_millis(100)).await,
+            }
+        }
+        assert_eq!(served, Some((StatusCode::OK, key_auth)));
+
+        // Unknown and malformed tokens are not found
+        let unknown = format!("{}{}", ACME_HTTP_CHALLENGE_PREFIX, "unknown");
+        assert_eq!(
+            get(web_port, unknown.as_str()).await.0,
+            StatusCode::NOT_FOUND
+        );
+        let malformed = format!("{}{}", ACME_HTTP_CHALLENGE_PREFIX, "..%2Fweb.yaml");
+        assert_eq!(
+            get(web_port, malformed.as_str()).await.0,
+            StatusCode::NOT_FOUND
+        );
+
+        // Once the authorization is over the answer is removed
+        resolver.cleanup(&account, HOST, &challenge).await.unwrap();
+        assert_eq!(get(web_port, path.as_str()).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_protected_directory_is_not_served_without_its_slash() {
+        ate::utils::bootstrap_test_env();
+        let (server, web_port, _db) = test_server().await;
+
+        let key = ChainKey::from(format!("{}/www", HOST));
+        let accessor = server.repo.get_accessor(&key, HOST).await.unwrap();
+        accessor.init(&RequestContext::default()).await.unwrap();
+        let conf = r#"
+default_page: index.html
+directory_listing: true
+protected:
+  - path: /admin/*
+    group: example.com
+"#;
+        server
+            .repo
+            .set_file(&key, HOST, WEB_CONF_FILES_CONF, conf.as_bytes())
+            .await
+            .unwrap();
+        server
+            .repo
+            .set_file(&key, HOST, "admin/index.html", b"secret")
+            .await
+            .unwrap();
+
+        for _ in 0..50 {
+            match hyper::Client::new()
+                .get(format!("http://127.0.0.1:{}/", web_port).parse().unwrap())
+                .await
+            {
+                Ok(_) => break,
+                Err(_) => ate::engine::sleep(Duration::from_millis(100)).await,
+            }
+        }
+
+        // The default page of the directory is behind the same protection
+        // as the directory whether or not the path ends with a slash
+        for path in ["/admin", "/admin/", "/admin/index.html", "//admin"] {
+            let (status, body) = get(web_port, path).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", path);
+            assert!(body.contains("secret") == false, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,651 @@
+use async_trait::async_trait;
+use base64::URL_SAFE_NO_PAD;
+use fxhash::FxHashMap;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use ring::digest::{digest, SHA256};
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use ateweb::acme::*;
+use ateweb::error::*;
+
+/// Validity of the certificates that the stand-in issues (short enough that
+/// they are always due for renewal)
+const ISSUED_DAYS: i64 = 10;
+
+/// TXT records that DNS-01 challenges are checked against
+#[derive(Default)]
+struct FakeDns {
+    records: Mutex<FxHashMap<String, Vec<String>>>,
+}
+
+impl FakeDns {
+    fn get(&self, name: &str) -> Vec<String> {
+        let records = self.records.lock().unwrap();
+        records.get(name).cloned().unwrap_or_default()
+    }
+
+    fn len(&self) -> usize {
+        let records = self.records.lock().unwrap();
+        records.values().map(|a| a.len()).sum()
+    }
+}
+
+#[async_trait]
+impl DnsProvider for FakeDns {
+    async fn add_txt(&self, name: &str, value: &str) -> Result<(), AcmeError> {
+        let mut records = self.records.lock().unwrap();
+        records
+            .entry(name.to_string())
+            .or_default()
+            .push(value.to_string());
+        Ok(())
+    }
+
+    async fn remove_txt(&self, name: &str, value: &str) -> Result<(), AcmeError> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(values) = records.get_mut(name) {
+            values.retain(|a| a != value);
+            if values.len() <= 0 {
+                records.remove(name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Answers HTTP-01 challenges from a local HTTP server
+struct HttpResponder {
+    tokens: Arc<Mutex<FxHashMap<String, String>>>,
+}
+
+#[async_trait]
+impl AcmeResponder for HttpResponder {
+    async fn prepare(
+        &self,
+        account: &Account,
+        _domain: &str,
+        _wildcard: bool,
+        challenges: &Vec<Challenge>,
+    ) -> Result<Challenge, OrderError> {
+        let (challenge, key_auth) = account.http_01(challenges)?;
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.insert(challenge.token.clone(), key_auth);
+        Ok(challenge.clone())
+    }
+
+    async fn cleanup(
+        &self,
+        _account: &Account,
+        _domain: &str,
+        challenge: &Challenge,
+    ) -> Result<(), OrderError> {
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.remove(&challenge.token);
+        Ok(())
+    }
+}
+
+/// Answers DNS-01 challenges through a DNS provider
+struct DnsResponder {
+    dns: Arc<FakeDns>,
+    wildcards: Mutex<u32>,
+}
+
+#[async_trait]
+impl AcmeResponder for DnsResponder {
+    async fn prepare(
+        &self,
+        account: &Account,
+        domain: &str,
+        wildcard: bool,
+        challenges: &Vec<Challenge>,
+    ) -> Result<Challenge, OrderError> {
+        if wildcard {
+            *self.wildcards.lock().unwrap() += 1;
+        }
+        let (challenge, value) = account.dns_01(challenges)?;
+        let name = format!("{}{}", ACME_DNS_CHALLENGE_PREFIX, domain);
+        self.dns.add_txt(name.as_str(), value.as_str()).await?;
+        Ok(challenge.clone())
+    }
+
+    async fn cleanup(
+        &self,
+        account: &Account,
+        domain: &str,
+        challenge: &Challenge,
+    ) -> Result<(), OrderError> {
+        let (_, value) = account.dns_01(&vec![challenge.clone()])?;
+        let name = format!("{}{}", ACME_DNS_CHALLENGE_PREFIX, domain);
+        self.dns.remove_txt(name.as_str(), value.as_str()).await?;
+        Ok(())
+    }
+}
+
+struct StandInAuthz {
+    domain: String,
+    wildcard: bool,
+    token: String,
+    valid: bool,
+}
+
+struct StandInOrder {
+    kid: String,
+    domains: Vec<String>,
+    authzs: Vec<usize>,
+    cert: Option<String>,
+}
+
+#[derive(Default)]
+struct StandInState {
+    nonce: u64,
+    accounts: Vec<(Vec<u8>, String)>,
+    orders: Vec<StandInOrder>,
+    authzs: Vec<StandInAuthz>,
+    issued: u32,
+}
+
+/// Stand-in for an ACME server (such as LetsEncrypt) that checks the
+/// signatures of the requests and validates the challenges for real
+struct StandIn {
+    base: String,
+    http_addr: SocketAddr,
+    dns: Arc<FakeDns>,
+    state: Mutex<StandInState>,
+}
+
+fn b64(data: impl AsRef<[u8]>) -> String {
+    base64::encode_config(data, URL_SAFE_NO_PAD)
+}
+
+fn unb64(data: &str) -> Vec<u8> {
+    base64::decode_config(data, URL_SAFE_NO_PAD).unwrap_or_default()
+}
+
+fn reply(status: StatusCode, body: String) -> Response<Body> {
+    let mut resp = Response::new(Body::from(body));
+    *resp.status_mut() = status;
+    resp
+}
+
+impl StandIn {
+    fn directory(&self) -> String {
+        format!("{}/dir", self.base)
+    }
+
+    fn issued(&self) -> u32 {
+        self.state.lock().unwrap().issued
+    }
+
+    async fn handle(self: Arc<Self>, req: Request<Body>) -> Response<Body> {
+        let path = req.uri().path().to_string();
+        let url = format!("{}{}", self.base, path);
+        if req.method() == Method::GET && path == "/dir" {
+            let dir = json!({
+                "newNonce": format!("{}/nonce", self.base),
+                "newAccount": format!("{}/account", self.base),
+                "newOrder": format!("{}/order", self.base),
+            });
+            return reply(StatusCode::OK, dir.to_string());
+        }
+        if path == "/nonce" {
+            let nonce = {
+                let mut state = self.state.lock().unwrap();
+                state.nonce += 1;
+                format!("nonce{}", state.nonce)
+            };
+            let mut resp = reply(StatusCode::OK, String::new());
+            resp.headers_mut()
+                .insert("Replay-Nonce", nonce.parse().unwrap());
+            return resp;
+        }
+
+        // Everything else is a signed request
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        let (kid, payload) = match self.verify(&body[..], url.as_str()) {
+            Some(a) => a,
+            None => return reply(StatusCode::UNAUTHORIZED, "bad signature".to_string()),
+        };
+        let mut comps = path[1..].split("/");
+        let ret = match (comps.next(), comps.next(), comps.next()) {
+            (Some("account"), None, _) => Some(self.new_account(kid)),
+            (Some("order"), None, _) => Some(self.new_order(kid, payload)),
+            (Some("order"), Some(n), _) => n.parse().ok().map(|n| self.order(n)),
+            (Some("authz"), Some(n), _) => n.parse().ok().map(|n| self.authz(n)),
+            (Some("chall"), Some(n), Some(typ)) => match n.parse() {
+                Ok(n) => Some(self.challenge(n, typ).await),
+                Err(_) => None,
+            },
+            (Some("finalize"), Some(n), _) => n.parse().ok().map(|n| self.finalize(n, payload)),
+            (Some("cert"), Some(n), _) => n.parse().ok().map(|n| self.cert(n)),
+            _ => None,
+        };
+        ret.unwrap_or_else(|| reply(StatusCode::NOT_FOUND, "not found".to_string()))
+    }
+
+    /// Checks the JWS signature returning the account (or the public key of
+    /// a new account) and the payload
+    fn verify(&self, body: &[u8], url: &str) -> Option<(String, Value)> {
+        let body: Value = serde_json::from_slice(body).ok()?;
+        let protected = body["protected"].as_str()?;
+        let payload = body["payload"].as_str()?;
+        let signature = unb64(body["signature"].as_str()?);
+        let header: Value = serde_json::from_slice(&unb64(protected)[..]).ok()?;
+        if header["url"].as_str()? != url || header["alg"].as_str()? != "ES256" {
+            return None;
+        }
+
+        let (kid, public_key) = match header["kid"].as_str() {
+            Some(kid) => {
+                let state = self.state.lock().unwrap();
+                let public_key = state
+                    .accounts
+                    .iter()
+                    .enumerate()
+                    .find(|(n, _)| format!("{}/acct/{}", self.base, n) == kid)
+                    .map(|(_, (public_key, _))| public_key.clone())?;
+                (kid.to_string(), public_key)
+            }
+            None => {
+                let jwk = &header["jwk"];
+                let mut public_key = vec![4u8];
+                public_key.extend(unb64(jwk["x"].as_str()?));
+                public_key.extend(unb64(jwk["y"].as_str()?));
+                let thumb = json!({
+                    "crv": jwk["crv"],
+                    "kty": jwk["kty"],
+                    "x": jwk["x"],
+                    "y": jwk["y"],
+                });
+                let thumb = b64(digest(&SHA256, thumb.to_string().as_bytes()));
+                (format!("{}.{}", b64(&public_key), thumb), public_key)
+            }
+        };
+
+        let signed = format!("{}.{}", protected, payload);
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+            .verify(signed.as_bytes(), &signature[..])
+            .ok()?;
+
+        let payload = match payload.len() {
+            0 => Value::Null,
+            _ => serde_json::from_slice(&unb64(payload)[..]).ok()?,
+        };
+        Some((kid, payload))
+    }
+
+    fn thumbprint(&self, kid: &str) -> String {
+        let state = self.state.lock().unwrap();
+        state
+            .accounts
+            .iter()
+            .enumerate()
+            .find(|(n, _)| format!("{}/acct/{}", self.base, n) == kid)
+            .map(|(_, (_, thumb))| thumb.clone())
+            .unwrap_or_default()
+    }
+
+    fn new_account(&self, key: String) -> Response<Body> {
+        if key.starts_with(self.base.as_str()) {
+            return reply(StatusCode::BAD_REQUEST, "account exists".to_string());
+        }
+        let (public_key, thumb) = match key.split_once(".") {
+            Some((public_key, thumb)) => (unb64(public_key), thumb.to_string()),
+            None => return reply(StatusCode::BAD_REQUEST, "bad key".to_string()),
+        };
+        let mut state = self.state.lock().unwrap();
+        let kid = format!("{}/acct/{}", self.base, state.accounts.len());
+        state.accounts.push((public_key, thumb));
+
+        let mut resp = reply(StatusCode::CREATED, json!({"status": "valid"}).to_string());
+        resp.headers_mut().insert("Location", kid.parse().unwrap());
+        resp
+    }
+
+    fn new_order(&self, kid: String, payload: Value) -> Response<Body> {
+        let domains = payload["identifiers"]
+            .as_array()
+            .map(|a| {
+                a.iter()
+                    .filter_map(|i| i["value"].as_str())
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let n = {
+            let mut state = self.state.lock().unwrap();
+            let mut authzs = Vec::new();
+            for domain in domains.iter() {
+                let (domain, wildcard) = match domain.strip_prefix("*.") {
+                    Some(a) => (a.to_string(), true),
+                    None => (domain.clone(), false),
+                };
+                authzs.push(state.authzs.len());
+                let token = b64(fastrand::u64(..).to_be_bytes());
+                state.authzs.push(StandInAuthz {
+                    domain,
+                    wildcard,
+                    token,
+                    valid: false,
+                });
+            }
+            state.orders.push(StandInOrder {
+                kid,
+                domains,
+                authzs,
+                cert: None,
+            });
+            state.orders.len() - 1
+        };
+
+        let mut resp = self.order(n);
+        *resp.status_mut() = StatusCode::CREATED;
+        resp.headers_mut().insert(
+            "Location",
+            format!("{}/order/{}", self.base, n).parse().unwrap(),
+        );
+        resp
+    }
+
+    fn order(&self, n: usize) -> Response<Body> {
+        let state = self.state.lock().unwrap();
+        let order = match state.orders.get(n) {
+            Some(a) => a,
+            None => return reply(StatusCode::NOT_FOUND, "no order".to_string()),
+        };
+        let ready = order.authzs.iter().all(|a| state.authzs[*a].valid);
+        let ret = match (order.cert.is_some(), ready) {
+            (true, _) => json!({
+                "status": "valid",
+                "certificate": format!("{}/cert/{}", self.base, n),
+            }),
+            (false, true) => json!({
+                "status": "ready",
+                "finalize": format!("{}/finalize/{}", self.base, n),
+            }),
+            (false, false) => json!({
+                "status": "pending",
+                "authorizations": order
+                    .authzs
+                    .iter()
+                    .map(|a| format!("{}/authz/{}", self.base, a))
+                    .collect::<Vec<_>>(),
+                "finalize": format!("{}/finalize/{}", self.base, n),
+            }),
+        };
+        reply(StatusCode::OK, ret.to_string())
+    }
+
+    fn authz(&self, n: usize) -> Response<Body> {
+        let state = self.state.lock().unwrap();
+        let authz = match state.authzs.get(n) {
+            Some(a) => a,
+            None => return reply(StatusCode::NOT_FOUND, "no authz".to_string()),
+        };
+
+        // Wildcards can only be proven with DNS
+        let mut challenges = vec![json!({
+            "type": "dns-01",
+            "url": format!("{}/chall/{}/dns", self.base, n),
+            "token": authz.token,
+        })];
+        if authz.wildcard == false {
+            challenges.push(json!({
+                "type": "http-01",
+                "url": format!("{}/chall/{}/http", self.base, n),
+                "token": authz.token,
+            }));
+        }
+        let ret = json!({
+            "status": if authz.valid { "valid" } else { "pending" },
+            "identifier": { "type": "dns", "value": authz.domain },
+            "challenges": challenges,
+            "wildcard": authz.wildcard,
+        });
+        reply(StatusCode::OK, ret.to_string())
+    }
+
+    async fn challenge(&self, n: usize, typ: &str) -> Response<Body> {
+        let (domain, token, kid) = {
+            let state = self.state.lock().unwrap();
+            let authz = match state.authzs.get(n) {
+                Some(a) => a,
+                None => return reply(StatusCode::NOT_FOUND, "no authz".to_string()),
+            };
+            let kid = state
+                .orders
+                .iter()
+                .find(|o| o.authzs.contains(&n))
+                .map(|o| o.kid.clone())
+                .unwrap_or_default();
+            (authz.domain.clone(), authz.token.clone(), kid)
+        };
+        let key_auth = format!("{}.{}", token, self.thumbprint(kid.as_str()));
+
+        let valid = match typ {
+            "http" => {
+                let uri = format!(
+                    "http://{}{}{}",
+                    self.http_addr, ACME_HTTP_CHALLENGE_PREFIX, token
+                );
+                let req = Request::builder()
+                    .uri(uri)
+                    .header("Host", domain.as_str())
+                    .body(Body::empty())
+                    .unwrap();
+                match hyper::Client::new().request(req).await {
+                    Ok(resp) if resp.status() == StatusCode::OK => {
+                        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                        &body[..] == key_auth.as_bytes()
+                    }
+                    _ => false,
+                }
+            }
+            "dns" => {
+                let expected = b64(digest(&SHA256, key_auth.as_bytes()));
+                let name = format!("{}{}", ACME_DNS_CHALLENGE_PREFIX, domain);
+                self.dns.get(name.as_str()).contains(&expected)
+            }
+            _ => false,
+        };
+        if valid {
+            let mut state = self.state.lock().unwrap();
+            state.authzs[n].valid = true;
+        }
+        let ret = json!({
+            "type": format!("{}-01", typ),
+            "status": if valid { "valid" } else { "pending" },
+            "token": token,
+        });
+        reply(StatusCode::OK, ret.to_string())
+    }
+
+    fn finalize(&self, n: usize, payload: Value) -> Response<Body> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let ready = match state.orders.get(n) {
+                Some(order) => order.authzs.iter().all(|a| state.authzs[*a].valid),
+                None => false,
+            };
+            if ready == false || payload["csr"].as_str().map(|a| a.len()).unwrap_or(0) <= 0 {
+                return reply(StatusCode::FORBIDDEN, "order is not ready".to_string());
+            }
+
+            let mut params = rcgen::CertificateParams::new(state.orders[n].domains.clone());
+            params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+            params.not_before = chrono::Utc::now() - chrono::Duration::days(1);
+            params.not_after = chrono::Utc::now() + chrono::Duration::days(ISSUED_DAYS);
+            let cert = rcgen::Certificate::from_params(params).unwrap();
+            state.orders[n].cert = Some(cert.serialize_pem().unwrap());
+            state.issued += 1;
+        }
+        self.order(n)
+    }
+
+    fn cert(&self, n: usize) -> Response<Body> {
+        let state = self.state.lock().unwrap();
+        match state.orders.get(n).and_then(|o| o.cert.clone()) {
+            Some(pem) => reply(StatusCode::OK, pem),
+            None => reply(StatusCode::NOT_FOUND, "no certificate".to_string()),
+        }
+    }
+}
+
+fn serve<F, R>(listener: std::net::TcpListener, handler: F)
+where
+    F: Fn(Request<Body>) -> R + Clone + Send + Sync + 'static,
+    R: std::future::Future<Output = Response<Body>> + Send + 'static,
+{
+    let make_service = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let ret = handler(req);
+                async move { Ok::<_, Infallible>(ret.await) }
+            }))
+        }
+    });
+    let server = hyper::Server::from_tcp(listener)
+        .unwrap()
+        .serve(make_service);
+    tokio::spawn(server);
+}
+
+fn bind() -> (std::net::TcpListener, SocketAddr) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+/// Starts the stand-in along with the web server that answers HTTP-01
+async fn setup() -> (
+    Arc<StandIn>,
+    Arc<Mutex<FxHashMap<String, String>>>,
+    Arc<FakeDns>,
+) {
+    let tokens: Arc<Mutex<FxHashMap<String, String>>> = Default::default();
+    let (listener, http_addr) = bind();
+    {
+        let tokens = Arc::clone(&tokens);
+        serve(listener, move |req: Request<Body>| {
+            let tokens = Arc::clone(&tokens);
+            async move {
+                let token = req
+                    .uri()
+                    .path()
+                    .strip_prefix(ACME_HTTP_CHALLENGE_PREFIX)
+                    .unwrap_or("")
+                    .to_string();
+                let key_auth = tokens.lock().unwrap().get(&token).cloned();
+                match key_auth {
+                    Some(key_auth) => reply(StatusCode::OK, key_auth),
+                    None => reply(StatusCode::NOT_FOUND, String::new()),
+                }
+            }
+        });
+    }
+
+    let dns: Arc<FakeDns> = Default::default();
+    let (listener, addr) = bind();
+    let stand_in = Arc::new(StandIn {
+        base: format!("http://{}", addr),
+        http_addr,
+        dns: Arc::clone(&dns),
+        state: Mutex::new(StandInState::default()),
+    });
+    {
+        let stand_in = Arc::clone(&stand_in);
+        serve(listener, move |req: Request<Body>| {
+            Arc::clone(&stand_in).handle(req)
+        });
+    }
+    (stand_in, tokens, dns)
+}
+
+#[tokio::test]
+async fn acme_http_01_order_and_renew() {
+    let (stand_in, tokens, _) = setup().await;
+    let responder = HttpResponder {
+        tokens: Arc::clone(&tokens),
+    };
+    let domains = vec!["www.example.test".to_string()];
+
+    let (cert, cert_pem, key_pem) = order_certificate(
+        stand_in.directory().as_str(),
+        domains.clone(),
+        chrono::Duration::days(90),
+        &responder,
+    )
+    .await
+    .unwrap();
+    assert!(cert.cert.len() >= 1);
+    assert!(cert_pem.contains("BEGIN CERTIFICATE"));
+    assert!(key_pem.contains("PRIVATE KEY"));
+    assert_eq!(stand_in.issued(), 1);
+    assert_eq!(tokens.lock().unwrap().len(), 0);
+
+    // The certificate expires within the renewal period so it is renewed
+    assert!(duration_until_renewal(&cert, chrono::Duration::days(1)).as_secs() > 0);
+    assert_eq!(
+        duration_until_renewal(&cert, chrono::Duration::days(30)).as_secs(),
+        0
+    );
+    let (renewed, renewed_pem, _) = order_certificate(
+        stand_in.directory().as_str(),
+        domains,
+        chrono::Duration::days(90),
+        &responder,
+    )
+    .await
+    .unwrap();
+    assert!(renewed.cert.len() >= 1);
+    assert_ne!(renewed_pem, cert_pem);
+    assert_eq!(stand_in.issued(), 2);
+    assert_eq!(tokens.lock().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn acme_dns_01_wildcard() {
+    let (stand_in, _, dns) = setup().await;
+    let responder = DnsResponder {
+        dns: Arc::clone(&dns),
+        wildcards: Mutex::new(0),
+    };
+    let domains = vec!["example.test".to_string(), "*.example.test".to_string()];
+
+    let (cert, cert_pem, _) = order_certificate(
+        stand_in.directory().as_str(),
+        domains,
+        chrono::Duration::days(90),
+        &responder,
+    )
+    .await
+    .unwrap();
+    assert!(cert.cert.len() >= 1);
+    assert!(cert_pem.contains("BEGIN CERTIFICATE"));
+    assert_eq!(stand_in.issued(), 1);
+    assert_eq!(*responder.wildcards.lock().unwrap(), 1);
+    assert_eq!(dns.len(), 0);
+}
+
+#[test]
+fn acme_challenge_names() {
+    for challenge in [
+        AcmeChallenge::TlsAlpn01,
+        AcmeChallenge::Http01,
+        AcmeChallenge::Dns01,
+    ] {
+        assert_eq!(
+            challenge.to_string().parse::<AcmeChallenge>(),
+            Ok(challenge)
+        );
+    }
+    assert!("http-02".parse::<AcmeChallenge>().is_err());
+}
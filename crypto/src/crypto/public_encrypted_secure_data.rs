@@ -187,18 +187,30 @@ where
         match self.members.get(&referrer.hash().to_hex_string()) {
             Some(a) => {
                 let shared_key = a.unwrap(referrer)?;
-                let index = encrypt_key.hash().to_hex_string();
-                self.members.insert(
-                    index.clone(),
-                    PublicEncryptedSecureData::new(encrypt_key, shared_key)?,
-                );
-                self.metadata.insert(index, meta);
+                self.add_ext(encrypt_key, meta, shared_key)?;
                 Ok(true)
             }
             None => Ok(false),
         }
     }
 
+    /// Adds a member using the shared key directly (which must be the same
+    /// key that was used when the data was created)
+    pub fn add_ext(
+        &mut self,
+        encrypt_key: &PublicEncryptKey,
+        meta: String,
+        shared_key: EncryptKey,
+    ) -> Result<(), std::io::Error> {
+        let index = encrypt_key.hash().to_hex_string();
+        self.members.insert(
+            index.clone(),
+            PublicEncryptedSecureData::new(encrypt_key, shared_key)?,
+        );
+        self.metadata.insert(index, meta);
+        Ok(())
+    }
+
     pub fn remove(&mut self, what: &AteHash) -> bool {
         let index = what.to_hex_string();
         let ret = self.members.remove(&index).is_some();
@@ -219,4 +231,11 @@ where
     pub fn meta_list<'a>(&'a self) -> impl Iterator<Item = &'a String> {
         self.metadata.values()
     }
+
+    /// Returns the hash of the public key of every member along with its metadata
+    pub fn member_list<'a>(&'a self) -> impl Iterator<Item = (AteHash, Option<&'a String>)> {
+        self.members
+            .iter()
+            .map(move |(index, a)| (a.ek_hash(), self.metadata.get(index)))
+    }
 }
\ No newline at end of file
@@ -114,6 +114,24 @@ for MyFileSystem
         self.read_metadata(path).await
     }
 
+    async fn read_link(&self, _path: String) -> FsResult<String> {
+        FsResult::Err(FsError::InvalidInput)
+    }
+
+    async fn hard_link(&self, _from: String, _to: String) -> FsResult<Metadata> {
+        FsResult::Err(FsError::PermissionDenied)
+    }
+
+    async fn read_fs_stats(&self, _path: String) -> FsResult<api::FsStats> {
+        FsResult::Ok(api::FsStats {
+            block_size: 4096,
+            blocks: 1,
+            files: 2,
+            name_max: 255,
+            ..Default::default()
+        })
+    }
+
     async fn open(&self, path: String, _options: api::OpenOptions) -> Result<Arc<dyn api::OpenedFile>, BusError> {
         if path == "/readme.md" {
             Result::Ok(Arc::new(MyFile::default()))
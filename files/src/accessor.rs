@@ -20,13 +20,21 @@ use ::ate::prelude::*;
 use ::ate::{crypto::DerivedEncryptKey, prelude::TransactionScope};
 
 use super::api::*;
+use super::chunk::reconcile_chunks;
+use super::chunk::ChunkRelease;
 use super::codes::*;
+use super::conflict::release_revisions;
 use super::error::*;
 use super::handle::*;
+use super::lock::*;
 use super::model::*;
 use super::prelude::*;
 
 use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+
+/// Seconds that the number of inodes reported by `statfs` may be out of date
+const INODE_COUNT_SECS: u64 = 10;
 
 #[derive(Debug)]
 pub struct FileAccessor
@@ -47,6 +55,24 @@ where
     pub commit_lock: tokio::sync::Mutex<()>,
     pub impersonate_uid: bool,
     pub force_sudo: bool,
+    pub quota: Option<u64>,
+    /// Name of the snapshot that is presented (read only) instead of the
+    /// live file system
+    pub snapshot: Option<String>,
+    pub locks: AsyncMutex<LockTable>,
+    /// Whether the chain could reach its remote when it was last checked
+    pub connected: seqlock::SeqLock<bool>,
+    /// Files that were committed while the chain was disconnected
+    pub offline: Mutex<FxHashSet<u64>>,
+    /// Quotas that writes are checked against (refreshed every second)
+    pub quotas: Mutex<QuotaIndex>,
+    /// Bytes charged to the quotas of each file ahead of its writes
+    pub quota_reserved: Mutex<FxHashMap<u64, u64>>,
+    /// Quotas that were charged since their deltas were last folded
+    pub quota_touched: Mutex<FxHashSet<QuotaTarget>>,
+    /// Inodes that were last counted for `statfs` along with the size of the
+    /// chain and the second that they were counted at
+    pub inode_cache: Mutex<Option<(u64, u64, u64)>>,
     pub init_flag: AsyncMutex<bool>,
 }
 
@@ -93,6 +119,15 @@ impl FileAccessor {
             commit_lock: tokio::sync::Mutex::new(()),
             impersonate_uid,
             force_sudo: false,
+            quota: None,
+            snapshot: None,
+            locks: AsyncMutex::new(LockTable::default()),
+            connected: seqlock::SeqLock::new(true),
+            offline: Mutex::new(FxHashSet::default()),
+            quotas: Mutex::new(QuotaIndex::default()),
+            quota_reserved: Mutex::new(FxHashMap::default()),
+            quota_touched: Mutex::new(FxHashSet::default()),
+            inode_cache: Mutex::new(None),
             init_flag: AsyncMutex::new(false),
         }
     }
@@ -102,6 +137,30 @@ impl FileAccessor {
         self
     }
 
+    pub fn with_quota(mut self, val: Option<u64>) -> Self {
+        self.quota = val;
+        self
+    }
+
+    /// Presents the named snapshot (read-only) instead of the live file system
+    /// by reading the chain as it was at the moment the snapshot was taken
+    pub async fn with_snapshot(mut self, val: Option<String>) -> Result<Self> {
+        if let Some(name) = val.as_ref() {
+            let at = self.snapshot_at(name.as_str()).await?;
+            self.dio = self.chain.dio_at(&self.session, at).await;
+        }
+        self.snapshot = val;
+        Ok(self)
+    }
+
+    /// Snapshots are read only so any attempt to change them fails
+    pub fn check_writable(&self) -> Result<()> {
+        if self.snapshot.is_some() {
+            bail!(FileSystemErrorKind::ReadOnly);
+        }
+        Ok(())
+    }
+
     pub fn session_context(&self) -> RequestContext
     {
         RequestContext {
@@ -110,7 +169,14 @@ impl FileAccessor {
         }
     }
 
-    pub async fn init(&self, req: &RequestContext) -> Result<DaoMut<Inode>> {
+    pub async fn init(&self, req: &RequestContext) -> Result<Dao<Inode>> {
+        // Snapshots are only ever read
+        if self.snapshot.is_some() {
+            let root = self.dio.load::<Inode>(&PrimaryKey::from(1)).await?;
+            self.load_quotas().await?;
+            return Ok(root);
+        }
+
         let dio = self.dio_mut_meta().await;
         let root = match dio.load::<Inode>(&PrimaryKey::from(1)).await
         {
@@ -146,7 +212,8 @@ impl FileAccessor {
 
         // Disable any more root nodes from being created (only the single root node is allowed)
         self.chain.single().await.disable_new_roots();
-        Ok(root)
+        self.load_quotas().await?;
+        Ok(root.into())
     }
 
     pub fn get_group_read_key<'a>(&'a self, gid: u32) -> Option<&'a EncryptKey> {
@@ -224,6 +291,28 @@ impl FileAccessor {
         Ok(dao)
     }
 
+    /// Returns a hash that changes whenever the contents of a file do (the
+    /// chunks are addressed by their contents so their keys are hashed)
+    pub async fn content_hash(&self, inode: u64) -> Result<AteHash> {
+        let dao = self.load(inode).await?;
+        let mut data = Vec::with_capacity(16 + dao.chunks.len() * 20);
+        data.extend_from_slice(&dao.size.to_be_bytes());
+        for chunk in dao.chunks.iter() {
+            data.extend_from_slice(&chunk.offset.to_be_bytes());
+            data.extend_from_slice(&chunk.key.as_u64().to_be_bytes());
+        }
+        if dao.chunks.is_empty() {
+            // Files written before chunking rewrite their bundles in place
+            // so the time they were updated is also needed
+            data.extend_from_slice(&dao.when_updated().to_be_bytes());
+            for bundle in dao.bundles.iter() {
+                let bundle = bundle.map(|a| a.as_u64()).unwrap_or(0);
+                data.extend_from_slice(&bundle.to_be_bytes());
+            }
+        }
+        Ok(AteHash::from_bytes(&data[..]))
+    }
+
     pub async fn load_mut(&self, inode: u64) -> Result<DaoMut<Inode>> {
         let dio = self.dio.trans(self.scope_meta).await;
         let dao = dio.load::<Inode>(&PrimaryKey::from(inode)).await?;
@@ -255,7 +344,7 @@ impl FileAccessor {
         let data = self.load(inode).await?;
         let created = data.when_created();
         let updated = data.when_updated();
-        let read_only = flags & O_RDONLY != 0;
+        let read_only = flags & O_RDONLY != 0 || self.snapshot.is_some();
 
         let uid = data.dentry.uid;
         let gid = data.dentry.gid;
@@ -269,20 +358,34 @@ impl FileAccessor {
                 .gid(gid)
                 .created(created)
                 .updated(updated);
-            children.push(FileSpec::FixedFile(fixed));
+            children.push((".".to_string(), FileSpec::FixedFile(fixed)));
 
             let fixed = FixedFile::new(data.key().as_u64(), "..".to_string(), FileKind::Directory)
                 .uid(uid)
                 .gid(gid)
                 .created(created)
                 .updated(updated);
-            children.push(FileSpec::FixedFile(fixed));
+            children.push(("..".to_string(), FileSpec::FixedFile(fixed)));
 
             match writable {
                 true => {
                     let mut data = self.load_mut_io(inode).await?;
                     let mut data = data.as_mut();
                     for child in data.children.iter_mut_ext(true, true).await? {
+                        if child.orphaned {
+                            continue;
+                        }
+                        let name = child.dentry.name.clone();
+
+                        // Hard links are presented as the inode they refer to
+                        let child = match child.hard_link {
+                            Some(target) => match self.load_mut_io(target).await {
+                                Ok(a) => a,
+                                Err(FileSystemError(FileSystemErrorKind::NoEntry, _)) => continue,
+                                Err(err) => return Err(err),
+                            },
+                            None => child,
+                        };
                         let child_spec = Inode::as_file_spec_mut(
                             child.key().as_u64(),
                             child.when_created(),
@@ -290,11 +393,25 @@ impl FileAccessor {
                             child,
                         )
                         .await;
-                        children.push(child_spec);
+                        children.push((name, child_spec));
                     }
                 }
                 false => {
                     for child in data.children.iter_ext(true, true).await? {
+                        if child.orphaned {
+                            continue;
+                        }
+                        let name = child.dentry.name.clone();
+
+                        // Hard links are presented as the inode they refer to
+                        let child = match child.hard_link {
+                            Some(target) => match self.load(target).await {
+                                Ok(a) => a,
+                                Err(FileSystemError(FileSystemErrorKind::NoEntry, _)) => continue,
+                                Err(err) => return Err(err),
+                            },
+                            None => child,
+                        };
                         let child_spec = Inode::as_file_spec(
                             child.key().as_u64(),
                             child.when_created(),
@@ -302,7 +419,7 @@ impl FileAccessor {
                             child,
                         )
                         .await;
-                        children.push(child_spec);
+                        children.push((name, child_spec));
                     }
                 }
             }
@@ -316,7 +433,9 @@ impl FileAccessor {
             false => Inode::as_file_spec(data.key().as_u64(), created, updated, data).await,
         };
         if flags & O_TRUNC != 0 {
+            let size = spec.size();
             spec.fallocate(0).await?;
+            self.quota_charge_file(inode, -(size as i64)).await?;
             dirty = true;
         }
 
@@ -331,7 +450,7 @@ impl FileAccessor {
             dirty: seqlock::SeqLock::new(dirty),
         };
 
-        for child in children.into_iter() {
+        for (name, child) in children.into_iter() {
             let (uid, gid) = match self.impersonate_uid {
                 true => {
                     let uid = self.reverse_uid(child.uid(), req);
@@ -340,7 +459,7 @@ impl FileAccessor {
                 }
                 false => (child.uid(), child.gid()),
             };
-            open.add_child(&child, uid, gid);
+            open.add_child_named(name, &child, uid, gid);
         }
 
         Ok(open)
@@ -380,7 +499,7 @@ impl FileAccessor {
             .children
             .iter()
             .await?
-            .filter(|c| *c.dentry.name == *name)
+            .filter(|c| c.orphaned == false && *c.dentry.name == *name)
             .next()
         {
             trace!("create parent={} name={}: already-exists", parent, name);
@@ -392,17 +511,141 @@ impl FileAccessor {
         let child = Inode::new(name.to_string(), mode, uid, gid, FileKind::RegularFile);
 
         let mut child = data.as_mut().children.push(child)?;
+        self.quota_charge(&dio, Some(key), &QuotaOwner::of(uid, gid), 0, 1)
+            .await?;
+        self.inodes_changed(1);
         self.updwasmer_auth(mode, uid, gid, child.auth_mut())?;
         return Ok(child);
     }
 
+    /// Removes a directory entry - hard links release their reference on the
+    /// inode they point to while inodes that are still linked from elsewhere
+    /// are hidden rather than deleted. The chunks of deleted inodes are added
+    /// to `release` which must be applied after the transaction commits.
+    ///
+    /// When the entry was the last link to an inode that had already been
+    /// unlinked then that (now deleted) inode is returned with the directory
+    /// that still held it as its parent (or none if it was moved out of a
+    /// removed tree)
+    pub async fn remove_dentry(
+        &self,
+        dio: &Arc<DioMut>,
+        key: &PrimaryKey,
+        release: &mut ChunkRelease,
+    ) -> Result<Option<Inode>> {
+        let mut entry = dio.load::<Inode>(key).await?;
+        if let Some(target) = entry.hard_link {
+            dio.delete(key).await?;
+
+            let mut target = match dio.load::<Inode>(&PrimaryKey::from(target)).await {
+                Ok(a) => a,
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                    return Ok(None);
+                }
+                Err(err) => {
+                    bail!(err);
+                }
+            };
+            if target.links <= 1 && target.orphaned {
+                let target_key = target.key().clone();
+                let target_parent = target
+                    .parent()
+                    .filter(|a| a.collection_id != 0)
+                    .map(|a| a.parent_id.as_u64());
+                release.add_inode(&target);
+                let mut freed = target.take();
+                freed.dentry.parent = target_parent;
+                release_revisions(dio, &target_key, release).await?;
+                dio.delete(&target_key).await?;
+                self.inodes_changed(-1);
+                return Ok(Some(freed));
+            } else {
+                let mut target = target.as_mut();
+                target.links = target.links.saturating_sub(1);
+            }
+        } else if entry.links > 0 {
+            entry.as_mut().orphaned = true;
+        } else {
+            let is_file = entry.kind == FileKind::RegularFile;
+            release.add_inode(&entry);
+            drop(entry);
+            if is_file {
+                release_revisions(dio, key, release).await?;
+            }
+            dio.delete(key).await?;
+            self.inodes_changed(-1);
+        }
+        Ok(None)
+    }
+
+    /// Removes an entry and everything below it. Inodes that are still hard
+    /// linked from outside of the tree are moved out of it (and hidden)
+    /// rather than deleted so that the other links keep their data
+    pub async fn remove_tree(
+        &self,
+        dio: &Arc<DioMut>,
+        key: &PrimaryKey,
+        release: &mut ChunkRelease,
+    ) -> Result<()> {
+        let mut tree = Vec::new();
+        let mut stack = vec![key.clone()];
+        while let Some(key) = stack.pop() {
+            let dao = dio.load::<Inode>(&key).await?;
+            for child in dao.children.iter().await? {
+                stack.push(child.key().clone());
+            }
+            tree.push(key);
+        }
+
+        // Entries are released deepest first so that any hard links within
+        // the tree have dropped their references before their targets are
+        // looked at
+        for child in tree.iter().rev() {
+            let mut entry = match dio.load::<Inode>(child).await {
+                Ok(a) => a,
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => continue,
+                Err(err) => bail!(err),
+            };
+            if entry.hard_link.is_none() && entry.links > 0 {
+                entry.detach()?;
+                entry.attach_orphaned(&PrimaryKey::from(1))?;
+                entry.as_mut().orphaned = true;
+            } else {
+                drop(entry);
+                self.remove_dentry(dio, child, release).await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn tick(&self) -> Result<()> {
         let secs = self.elapsed.elapsed().as_secs();
         if secs > self.last_elapsed.read() {
             let _ = self.commit_lock.lock().await;
             if secs > self.last_elapsed.read() {
                 *self.last_elapsed.lock_write() = secs;
+
+                let connected = self.chain.is_connected().await;
+                if connected != self.connected.read() {
+                    *self.connected.lock_write() = connected;
+                }
+
                 self.commit_internal().await?;
+                self.load_quotas().await?;
+                if connected {
+                    // References that were taken or released while offline
+                    match reconcile_chunks(&self.dio).await {
+                        Ok(0) => {}
+                        Ok(n) => info!("reconciled {} offline chunk change(s)", n),
+                        Err(err) => warn!("failed to reconcile offline chunk changes - {}", err),
+                    }
+                    if let Err(err) = self.quota_fold().await {
+                        warn!("failed to fold quota usage - {}", err);
+                    }
+                }
+                if connected && self.offline.lock().unwrap().is_empty() == false {
+                    self.reconnected().await?;
+                }
             }
         }
         Ok(())
@@ -434,7 +677,19 @@ impl FileAccessor {
                 .collect::<Vec<_>>()
         };
         for open in open_handles {
-            open.spec.commit().await?;
+            self.commit_handle(&open).await?;
+        }
+        Ok(())
+    }
+
+    /// Commits the changes made through an open handle - files that are
+    /// committed while the chain is disconnected are checked for conflicting
+    /// edits once it reconnects
+    async fn commit_handle(&self, open: &OpenHandle) -> Result<()> {
+        open.spec.commit().await?;
+        self.quota_settle(open.inode).await?;
+        if self.chain.is_connected().await == false {
+            self.offline.lock().unwrap().insert(open.inode);
         }
         Ok(())
     }
@@ -582,6 +837,10 @@ impl FileAccessor {
         self.tick().await?;
         trace!("access inode={} mask={:#02x}", inode, mask);
 
+        if (mask & 0o2) != 0 {
+            self.check_writable()?;
+        }
+
         let dao = self.load(inode).await?;
         if (dao.dentry.mode & mask) != 0 {
             trace!("access mode={:#02x} - ok", dao.dentry.mode);
@@ -655,11 +914,14 @@ impl FileAccessor {
         set_attr: SetAttr,
     ) -> Result<FileAttr> {
         self.tick().await?;
+        self.check_writable()?;
         trace!("setattr inode={}", inode);
 
+        self.quota_settle(inode).await?;
         let key = PrimaryKey::from(inode);
         let dio = self.dio_mut_meta().await;
         let mut dao = dio.load::<Inode>(&key).await?;
+        let owners = QuotaOwner::of(dao.dentry.uid, dao.dentry.gid);
 
         let mut changed = false;
         if let Some(uid) = set_attr.uid {
@@ -688,6 +950,25 @@ impl FileAccessor {
         }
 
         if changed == true {
+            // Entries that change hands move between the quotas of their owners
+            let new_owners = QuotaOwner::of(dao.dentry.uid, dao.dentry.gid);
+            if new_owners != owners {
+                let bytes = match dao.kind == FileKind::RegularFile && dao.hard_link.is_none() {
+                    true => dao.size as i64,
+                    false => 0,
+                };
+                let from = owners
+                    .into_iter()
+                    .filter(|a| new_owners.contains(a) == false)
+                    .collect::<Vec<_>>();
+                let to = new_owners
+                    .into_iter()
+                    .filter(|a| owners.contains(a) == false)
+                    .collect::<Vec<_>>();
+                self.quota_charge(&dio, None, &from, -bytes, -1).await?;
+                self.quota_charge(&dio, None, &to, bytes, 1).await?;
+            }
+
             self.updwasmer_auth(
                 dao.dentry.mode,
                 dao.dentry.uid,
@@ -853,12 +1134,15 @@ impl FileAccessor {
         _req: &RequestContext,
         inode: u64,
         fh: u64,
-        _lock_owner: u64,
+        lock_owner: u64,
     ) -> Result<()> {
         self.tick().await?;
         self.commit().await?;
         debug!("wasmer-dfs::flush inode={}", inode);
 
+        // Closing a file drops the POSIX locks of its owner
+        self.release_locks(inode, lock_owner).await?;
+
         let open = {
             let lock = self.open_handles.lock().unwrap();
             match lock.get(&fh) {
@@ -867,7 +1151,7 @@ impl FileAccessor {
             }
         };
         if let Some(open) = open {
-            open.spec.commit().await?
+            self.commit_handle(&open).await?;
         }
 
         self.chain.flush().await?;
@@ -898,6 +1182,7 @@ impl FileAccessor {
         mode: u32,
     ) -> Result<FileAttr> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::mkdir parent={}", parent);
 
         let dio = self.dio.trans(self.scope_meta).await;
@@ -913,7 +1198,11 @@ impl FileAccessor {
 
         let mut child = data.as_mut().children.push(child)?;
         self.updwasmer_auth(mode, uid, gid, child.auth_mut())?;
+        let owners = QuotaOwner::of(uid, gid);
+        self.quota_charge(&dio, Some(data.key().clone()), &owners, 0, 1)
+            .await?;
         dio.commit().await?;
+        self.inodes_changed(1);
 
         let child_spec = Inode::as_file_spec(
             child.key().as_u64(),
@@ -927,6 +1216,7 @@ impl FileAccessor {
 
     pub async fn rmdir(&self, req: &RequestContext, parent: u64, name: &str) -> Result<()> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::rmdir parent={}", parent);
 
         let open = self.create_open_handle(parent, req, O_RDONLY).await?;
@@ -944,9 +1234,15 @@ impl FileAccessor {
         {
             debug!("wasmer-dfs::rmdir parent={} name={}: found", parent, name);
 
+            self.quota_settle_all().await?;
             let dio = self.dio.trans(self.scope_meta).await;
-            dio.delete(&PrimaryKey::from(entry.inode)).await?;
+            let key = PrimaryKey::from(entry.inode);
+            self.quota_release_tree(&dio, PrimaryKey::from(parent), &key)
+                .await?;
+            let mut release = ChunkRelease::default();
+            self.remove_tree(&dio, &key, &mut release).await?;
             dio.commit().await?;
+            release.apply(&self.dio).await?;
             return Ok(());
         }
 
@@ -969,6 +1265,7 @@ impl FileAccessor {
         mode: u32,
     ) -> Result<FileAttr> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::mknod parent={} name={}", parent, name);
 
         let dao = self.mknod_internal(&req, parent, name, mode).await?;
@@ -992,6 +1289,7 @@ impl FileAccessor {
         mode: u32,
     ) -> Result<Arc<OpenHandle>> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::create parent={} name={}", parent, name);
 
         let data = self.mknod_internal(req, parent, name, mode).await?;
@@ -1028,6 +1326,7 @@ impl FileAccessor {
 
     pub async fn unlink(&self, _req: &RequestContext, parent: u64, name: &str) -> Result<()> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::unlink parent={} name={}", parent, name);
 
         let parent_key = PrimaryKey::from(parent);
@@ -1043,7 +1342,7 @@ impl FileAccessor {
             .children
             .iter()
             .await?
-            .filter(|c| c.dentry.name.as_str() == name)
+            .filter(|c| c.orphaned == false && c.dentry.name.as_str() == name)
             .next()
         {
             if data.kind == FileKind::Directory {
@@ -1054,9 +1353,14 @@ impl FileAccessor {
                 bail!(FileSystemErrorKind::IsDirectory);
             }
 
+            self.quota_settle(data.key().as_u64()).await?;
             let dio = self.dio_mut_meta().await;
-            dio.delete(&data.key()).await?;
+            let mut release = ChunkRelease::default();
+            let freed = self.remove_dentry(&dio, data.key(), &mut release).await?;
+            self.quota_release(&dio, parent_key, &data, freed.as_ref())
+                .await?;
             dio.commit().await?;
+            release.apply(&self.dio).await?;
 
             return Ok(());
         }
@@ -1072,8 +1376,10 @@ impl FileAccessor {
         new_name: &str,
     ) -> Result<()> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::rename name={} new_name={}", name, new_name);
 
+        self.quota_settle_all().await?;
         let mut parent_data = self.load_mut(parent).await?;
         if parent_data.kind != FileKind::Directory {
             debug!("wasmer-dfs::rename parent={} not-a-directory", parent);
@@ -1086,9 +1392,13 @@ impl FileAccessor {
             .children
             .iter_mut()
             .await?
-            .filter(|c| c.dentry.name.as_str() == name)
+            .filter(|c| c.orphaned == false && c.dentry.name.as_str() == name)
             .next()
         {
+            let mut moved_to = None;
+            let mut replaced = None;
+            let mut release = ChunkRelease::default();
+
             // If the parent has changed then move it
             if parent != new_parent {
                 let new_parent_key = PrimaryKey::from(new_parent);
@@ -1103,29 +1413,56 @@ impl FileAccessor {
                     .children
                     .iter()
                     .await?
-                    .filter(|c| c.dentry.name.as_str() == new_name)
+                    .filter(|c| c.orphaned == false && c.dentry.name.as_str() == new_name)
                     .next()
                 {
-                    dio.delete(existing.key()).await?;
+                    let freed = self
+                        .remove_dentry(&dio, existing.key(), &mut release)
+                        .await?;
+                    replaced = Some((new_parent_key.clone(), existing.take(), freed));
                 }
                 data.detach()?;
                 data.attach(&new_parent_data, &new_parent_data.children)?;
+                data.as_mut().dentry.parent = Some(new_parent_key.as_u64());
+                moved_to = Some(new_parent_key);
             } else {
                 if let Some(existing) = parent_data
                     .children
                     .iter()
                     .await?
-                    .filter(|c| c.dentry.name.as_str() == new_name)
+                    .filter(|c| c.orphaned == false && c.dentry.name.as_str() == new_name)
                     .next()
                 {
-                    dio.delete(existing.key()).await?;
+                    let freed = self
+                        .remove_dentry(&dio, existing.key(), &mut release)
+                        .await?;
+                    replaced = Some((PrimaryKey::from(parent), existing.take(), freed));
                 }
             }
 
             data.as_mut().dentry.name = new_name.to_string();
+            let key = data.key().clone();
             drop(parent_data);
 
+            // Directory quotas follow the entry (and everything below it)
+            if self.has_quotas() {
+                if let Some((parent_key, entry, freed)) = replaced {
+                    self.quota_release(&dio, parent_key, &entry, freed.as_ref())
+                        .await?;
+                }
+                if let Some(new_parent_key) = moved_to {
+                    let usage = self.quota_subtree(&key).await?;
+                    let (bytes, inodes) = (usage.bytes as i64, usage.inodes as i64);
+                    let parent_key = Some(PrimaryKey::from(parent));
+                    self.quota_charge(&dio, parent_key, &[], -bytes, -inodes)
+                        .await?;
+                    self.quota_charge(&dio, Some(new_parent_key), &[], bytes, inodes)
+                        .await?;
+                }
+            }
+
             dio.commit().await?;
+            release.apply(&self.dio).await?;
             return Ok(());
         }
         bail!(FileSystemErrorKind::NoEntry);
@@ -1161,15 +1498,17 @@ impl FileAccessor {
         inode: u64,
         fh: u64,
         _flags: u32,
-        _lock_owner: u64,
+        lock_owner: u64,
         flush: bool,
     ) -> Result<()> {
         self.tick().await?;
         debug!("wasmer-dfs::release inode={}", inode);
 
+        self.release_locks(inode, lock_owner).await?;
+
         let open = self.open_handles.lock().unwrap().remove(&fh);
         if let Some(open) = open {
-            open.spec.commit().await?
+            self.commit_handle(&open).await?;
         }
 
         if flush {
@@ -1231,6 +1570,7 @@ impl FileAccessor {
         _flags: u32,
     ) -> Result<u64> {
         self.tick().await?;
+        self.check_writable()?;
         debug!(
             "wasmer-dfs::write inode={} offset={} size={}",
             inode,
@@ -1258,6 +1598,10 @@ impl FileAccessor {
             bail!(FileSystemErrorKind::ReadOnly);
         }
 
+        // Only the bytes that grow the file count towards its quotas
+        let grow = (offset + data.len() as u64).saturating_sub(open.spec.size());
+        self.quota_charge_file(inode, grow as i64).await?;
+
         let wrote = open.spec.write(offset, data).await?;
         if open.dirty.read() == false {
             *open.dirty.lock_write() = true;
@@ -1270,6 +1614,63 @@ impl FileAccessor {
         Ok(wrote)
     }
 
+    /// Copies a range of one open file into another, whole chunks are shared
+    /// between the two files rather than copied
+    pub async fn copy_file_range(
+        &self,
+        _req: &RequestContext,
+        inode_in: u64,
+        fh_in: u64,
+        off_in: u64,
+        inode_out: u64,
+        fh_out: u64,
+        off_out: u64,
+        length: u64,
+    ) -> Result<u64> {
+        self.tick().await?;
+        self.check_writable()?;
+        debug!(
+            "wasmer-dfs::copy_file_range inode_in={} inode_out={} length={}",
+            inode_in, inode_out, length
+        );
+
+        let (open_in, open_out) = {
+            let lock = self.open_handles.lock().unwrap();
+            match (lock.get(&fh_in), lock.get(&fh_out)) {
+                (Some(a), Some(b)) => (Arc::clone(a), Arc::clone(b)),
+                _ => {
+                    bail!(FileSystemErrorKind::NotImplemented);
+                }
+            }
+        };
+
+        if open_out.read_only {
+            bail!(FileSystemErrorKind::ReadOnly);
+        }
+
+        let length = length.min(open_in.spec.size().saturating_sub(off_in));
+        let grow = (off_out + length).saturating_sub(open_out.spec.size());
+        self.quota_charge_file(inode_out, grow as i64).await?;
+
+        let copied = match (&open_in.spec, &open_out.spec) {
+            (FileSpec::RegularFile(src), FileSpec::RegularFile(dst)) => {
+                dst.copy_from(src, off_in, off_out, length).await?
+            }
+            _ => {
+                bail!(FileSystemErrorKind::InvalidArguments);
+            }
+        };
+        if open_out.dirty.read() == false {
+            *open_out.dirty.lock_write() = true;
+        }
+
+        debug!(
+            "wasmer-dfs::copied inode_in={} inode_out={} size={}",
+            inode_in, inode_out, copied
+        );
+        Ok(copied)
+    }
+
     pub async fn fallocate(
         &self,
         _req: &RequestContext,
@@ -1280,6 +1681,7 @@ impl FileAccessor {
         _mode: u32,
     ) -> Result<()> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::fallocate inode={}", inode);
 
         if fh > 0 {
@@ -1295,6 +1697,9 @@ impl FileAccessor {
                     bail!(FileSystemErrorKind::ReadOnly);
                 }
 
+                let grow = (offset + length).saturating_sub(open.spec.size());
+                self.quota_charge_file(inode, grow as i64).await?;
+
                 open.spec.fallocate(offset + length).await?;
                 if open.dirty.read() == false {
                     *open.dirty.lock_write() = true;
@@ -1304,8 +1709,11 @@ impl FileAccessor {
         }
 
         let mut dao = self.load_mut(inode).await?;
+        let delta = (offset + length) as i64 - dao.size as i64;
+        self.quota_charge_file(inode, delta).await?;
         dao.as_mut().size = offset + length;
         dao.trans().commit().await?;
+        self.quota_settle(inode).await?;
 
         return Ok(());
     }
@@ -1342,6 +1750,269 @@ impl FileAccessor {
         Ok(offset)
     }
 
+    pub async fn getlk(
+        &self,
+        _req: &RequestContext,
+        inode: u64,
+        _fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: u32,
+        pid: u32,
+    ) -> Result<FileLock> {
+        self.tick().await?;
+        debug!(
+            "wasmer-dfs::getlk inode={} start={} end={}",
+            inode, start, end
+        );
+
+        let lock = FileLock {
+            owner: lock_owner,
+            start,
+            end,
+            typ,
+            pid,
+        };
+
+        let locks = self.locks.lock().await;
+        if let Some(conflict) = locks.conflict(inode, &lock) {
+            return Ok(conflict);
+        }
+
+        // Probe the mesh to see if another machine holds locks on this file
+        // (writers hold the gate and all the read slots while readers hold
+        // one of the slots)
+        let hold = locks.mesh_hold(inode);
+        if hold == Some(MeshHold::Exclusive) {
+            return Ok(FileLock::unlocked(start, end));
+        }
+        let key = PrimaryKey::from(inode);
+        let dio = self.dio_mut_meta().await;
+        if hold.is_none() {
+            let gate = LockTable::mesh_key(&key);
+            if dio.try_lock(gate).await? == false {
+                return Ok(FileLock {
+                    owner: 0,
+                    start: 0,
+                    end: u64::MAX,
+                    typ: F_WRLCK,
+                    pid: 0,
+                });
+            }
+            dio.unlock(gate).await?;
+        }
+        if typ == F_WRLCK {
+            for slot in 0..MESH_READ_SLOTS {
+                if hold == Some(MeshHold::Shared(slot)) {
+                    continue;
+                }
+                let slot = LockTable::mesh_slot_key(&key, slot);
+                if dio.try_lock(slot).await? == false {
+                    return Ok(FileLock {
+                        owner: 0,
+                        start: 0,
+                        end: u64::MAX,
+                        typ: F_RDLCK,
+                        pid: 0,
+                    });
+                }
+                dio.unlock(slot).await?;
+            }
+        }
+        Ok(FileLock::unlocked(start, end))
+    }
+
+    pub async fn setlk(
+        &self,
+        _req: &RequestContext,
+        inode: u64,
+        _fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: u32,
+        pid: u32,
+        block: bool,
+    ) -> Result<()> {
+        self.tick().await?;
+        debug!(
+            "wasmer-dfs::setlk inode={} start={} end={} type={}",
+            inode, start, end, typ
+        );
+
+        if start > end || typ > F_UNLCK {
+            bail!(FileSystemErrorKind::InvalidArguments);
+        }
+        let lock = FileLock {
+            owner: lock_owner,
+            start,
+            end,
+            typ,
+            pid,
+        };
+        if typ == F_UNLCK {
+            let mut locks = self.locks.lock().await;
+            if locks.is_held(inode) {
+                locks.apply(inode, lock);
+                self.sync_mesh(&mut locks, inode).await?;
+            }
+            return Ok(());
+        }
+
+        // Use an exponential backoff while we wait for the lock
+        let mut max_wait = 0u64;
+        loop {
+            if self.try_setlk(inode, lock).await? {
+                return Ok(());
+            }
+            if block == false {
+                bail!(FileSystemErrorKind::WouldBlock);
+            }
+
+            max_wait = ((max_wait * 12u64) / 10u64) + 5u64;
+            max_wait = max_wait.min(500u64);
+            let min_wait = max_wait / 2u64;
+            let random_wait = fastrand::u64(min_wait..max_wait);
+            ate::engine::sleep(std::time::Duration::from_millis(random_wait)).await;
+        }
+    }
+
+    /// Applies a `flock` lock, which is a whole-file lock held by the open
+    /// file rather than by a process - it goes through the same mesh gate
+    /// and read slots as `fcntl` locks so it holds across machines
+    pub async fn flock(
+        &self,
+        req: &RequestContext,
+        inode: u64,
+        fh: u64,
+        lock_owner: u64,
+        typ: u32,
+        block: bool,
+    ) -> Result<()> {
+        self.setlk(req, inode, fh, lock_owner, 0, u64::MAX, typ, 0, block)
+            .await
+    }
+
+    async fn try_setlk(&self, inode: u64, lock: FileLock) -> Result<bool> {
+        let mut locks = self.locks.lock().await;
+        if locks.conflict(inode, &lock).is_some() {
+            return Ok(false);
+        }
+
+        // The mesh locks must also be won so that the lock holds against
+        // other machines mounting the same chain
+        let exclusive = lock.typ == F_WRLCK || locks.mesh_needed(inode) == Some(F_WRLCK);
+        let hold = match (locks.mesh_hold(inode), exclusive) {
+            (Some(MeshHold::Exclusive), _) => MeshHold::Exclusive,
+            (Some(MeshHold::Shared(slot)), false) => MeshHold::Shared(slot),
+            (held, true) => match self.lock_mesh_exclusive(inode, held).await? {
+                true => MeshHold::Exclusive,
+                false => {
+                    return Ok(false);
+                }
+            },
+            (None, false) => match self.lock_mesh_shared(inode).await? {
+                Some(slot) => MeshHold::Shared(slot),
+                None => {
+                    return Ok(false);
+                }
+            },
+        };
+        locks.set_mesh_hold(inode, Some(hold));
+
+        locks.apply(inode, lock);
+        self.sync_mesh(&mut locks, inode).await?;
+        Ok(true)
+    }
+
+    /// Drops all the advisory locks that an owner holds on a file
+    async fn release_locks(&self, inode: u64, lock_owner: u64) -> Result<()> {
+        let mut locks = self.locks.lock().await;
+        locks.release(inode, lock_owner);
+        self.sync_mesh(&mut locks, inode).await?;
+        Ok(())
+    }
+
+    /// Takes one of the read slots of a file, starting from a random one so
+    /// that readers on different machines rarely collide
+    async fn lock_mesh_shared(&self, inode: u64) -> Result<Option<u32>> {
+        let key = PrimaryKey::from(inode);
+        let dio = self.dio_mut_meta().await;
+        let first = fastrand::u32(0..MESH_READ_SLOTS);
+        for n in 0..MESH_READ_SLOTS {
+            let slot = (first + n) % MESH_READ_SLOTS;
+            if dio.try_lock(LockTable::mesh_slot_key(&key, slot)).await? {
+                return Ok(Some(slot));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Takes the write gate and every read slot of a file (other than the
+    /// slot this mount already holds) or nothing at all
+    async fn lock_mesh_exclusive(&self, inode: u64, held: Option<MeshHold>) -> Result<bool> {
+        let key = PrimaryKey::from(inode);
+        let dio = self.dio_mut_meta().await;
+        let gate = LockTable::mesh_key(&key);
+        if dio.try_lock(gate).await? == false {
+            return Ok(false);
+        }
+
+        let mut taken = Vec::new();
+        for slot in 0..MESH_READ_SLOTS {
+            if held == Some(MeshHold::Shared(slot)) {
+                continue;
+            }
+            let slot = LockTable::mesh_slot_key(&key, slot);
+            if dio.try_lock(slot).await? == false {
+                for slot in taken {
+                    dio.unlock(slot).await?;
+                }
+                dio.unlock(gate).await?;
+                return Ok(false);
+            }
+            taken.push(slot);
+        }
+        Ok(true)
+    }
+
+    /// Releases the mesh locks of a file that its remaining advisory locks
+    /// no longer need (all of them once the last lock goes, or all but one
+    /// read slot when the last write lock goes)
+    async fn sync_mesh(&self, locks: &mut LockTable, inode: u64) -> Result<()> {
+        let hold = match locks.mesh_hold(inode) {
+            Some(a) => a,
+            None => {
+                return Ok(());
+            }
+        };
+        let key = PrimaryKey::from(inode);
+        let dio = self.dio_mut_meta().await;
+        match (locks.mesh_needed(inode), hold) {
+            (None, MeshHold::Shared(slot)) => {
+                dio.unlock(LockTable::mesh_slot_key(&key, slot)).await?;
+                locks.set_mesh_hold(inode, None);
+            }
+            (None, MeshHold::Exclusive) => {
+                for slot in 0..MESH_READ_SLOTS {
+                    dio.unlock(LockTable::mesh_slot_key(&key, slot)).await?;
+                }
+                dio.unlock(LockTable::mesh_key(&key)).await?;
+                locks.set_mesh_hold(inode, None);
+            }
+            (Some(F_RDLCK), MeshHold::Exclusive) => {
+                for slot in 1..MESH_READ_SLOTS {
+                    dio.unlock(LockTable::mesh_slot_key(&key, slot)).await?;
+                }
+                dio.unlock(LockTable::mesh_key(&key)).await?;
+                locks.set_mesh_hold(inode, Some(MeshHold::Shared(0)));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub async fn symlink(
         &self,
         req: &RequestContext,
@@ -1350,6 +2021,7 @@ impl FileAccessor {
         link: &str,
     ) -> Result<FileAttr> {
         self.tick().await?;
+        self.check_writable()?;
         debug!(
             "wasmer-dfs::symlink parent={}, name={}, link={}",
             parent, name, link
@@ -1377,6 +2049,136 @@ impl FileAccessor {
         Ok(self.spec_as_attr_reverse(&spec, &req))
     }
 
+    pub async fn readlink(&self, _req: &RequestContext, inode: u64) -> Result<String> {
+        self.tick().await?;
+        debug!("wasmer-dfs::readlink inode={}", inode);
+
+        let dao = self.load(inode).await?;
+        if dao.kind != FileKind::SymLink {
+            debug!("wasmer-dfs::readlink inode={} not-a-symlink", inode);
+            bail!(FileSystemErrorKind::InvalidArguments);
+        }
+        match dao.link.clone() {
+            Some(a) => Ok(a),
+            None => bail!(FileSystemErrorKind::NoEntry),
+        }
+    }
+
+    pub async fn link(
+        &self,
+        req: &RequestContext,
+        inode: u64,
+        new_parent: u64,
+        new_name: &str,
+    ) -> Result<FileAttr> {
+        self.tick().await?;
+        self.check_writable()?;
+        debug!(
+            "wasmer-dfs::link inode={}, new_parent={}, new_name={}",
+            inode, new_parent, new_name
+        );
+
+        let dio = self.dio_mut_meta().await;
+        let mut target = dio.load::<Inode>(&PrimaryKey::from(inode)).await?;
+        if target.kind == FileKind::Directory {
+            debug!("wasmer-dfs::link inode={} is-a-directory", inode);
+            bail!(FileSystemErrorKind::IsDirectory);
+        }
+
+        let mut parent = dio.load::<Inode>(&PrimaryKey::from(new_parent)).await?;
+        if parent.kind != FileKind::Directory {
+            debug!("wasmer-dfs::link new_parent={} not-a-directory", new_parent);
+            bail!(FileSystemErrorKind::NotDirectory);
+        }
+        if let Some(_) = parent
+            .children
+            .iter()
+            .await?
+            .filter(|c| c.orphaned == false && c.dentry.name.as_str() == new_name)
+            .next()
+        {
+            debug!("wasmer-dfs::link new_parent={} name={}: already-exists", new_parent, new_name);
+            bail!(FileSystemErrorKind::AlreadyExists);
+        }
+
+        // The new entry only refers to the inode (which keeps all the data)
+        let mode = target.dentry.mode;
+        let uid = target.dentry.uid;
+        let gid = target.dentry.gid;
+        let mut entry = Inode::new(new_name.to_string(), mode, uid, gid, target.kind);
+        entry.hard_link = Some(inode);
+
+        let mut entry = parent.as_mut().children.push(entry)?;
+        self.updwasmer_auth(mode, uid, gid, entry.auth_mut())?;
+        target.as_mut().links += 1;
+        dio.commit().await?;
+
+        let spec = Inode::as_file_spec(
+            inode,
+            target.when_created(),
+            target.when_updated(),
+            target.into(),
+        )
+        .await;
+        Ok(self.spec_as_attr_reverse(&spec, &req))
+    }
+
+    pub async fn statfs(&self, req: &RequestContext, inode: u64) -> Result<FsStats> {
+        self.tick().await?;
+        debug!("wasmer-dfs::statfs inode={}", inode);
+
+        if let Some((limit, usage)) = self.quota_of(req, inode).await? {
+            return Ok(FsStats::from_quota(&limit, &usage));
+        }
+
+        let used = self.chain.size().await;
+        let files = self.inode_count(used).await?;
+        Ok(FsStats::new(used, files, self.quota))
+    }
+
+    /// Returns the number of inodes in the file system - they are only
+    /// counted again once the chain has changed and the last count is more
+    /// than `INODE_COUNT_SECS` old so busy file systems do not walk every
+    /// inode whenever they are asked
+    async fn inode_count(&self, size: u64) -> Result<u64> {
+        let secs = self.elapsed.elapsed().as_secs();
+        if let Some((at_size, at_secs, count)) = *self.inode_cache.lock().unwrap() {
+            if at_size == size || secs < at_secs + INODE_COUNT_SECS {
+                return Ok(count);
+            }
+        }
+        let count = self.count_inodes().await?;
+        *self.inode_cache.lock().unwrap() = Some((size, secs, count));
+        Ok(count)
+    }
+
+    /// Keeps the number of inodes reported by `statfs` in step with the
+    /// inodes that are created and deleted through this accessor
+    pub(crate) fn inodes_changed(&self, delta: i64) {
+        if let Some((_, _, count)) = self.inode_cache.lock().unwrap().as_mut() {
+            *count = (*count as i64).saturating_add(delta).max(1) as u64;
+        }
+    }
+
+    /// Counts the inodes in the file system (hard links share the inode they
+    /// refer to while orphaned entries still hold theirs)
+    async fn count_inodes(&self) -> Result<u64> {
+        let mut ret = 1u64;
+        let mut stack = vec![PrimaryKey::from(1u64)];
+        while let Some(key) = stack.pop() {
+            let dao = self.dio.load::<Inode>(&key).await?;
+            for child in dao.children.iter().await? {
+                if child.hard_link.is_none() {
+                    ret += 1;
+                }
+                if child.kind == FileKind::Directory {
+                    stack.push(child.key().clone());
+                }
+            }
+        }
+        Ok(ret)
+    }
+
     pub async fn setxattr(
         &self,
         req: &RequestContext,
@@ -1385,6 +2187,7 @@ impl FileAccessor {
         value: &str,
     ) -> Result<()> {
         self.tick().await?;
+        self.check_writable()?;
 
         let flags = O_RDWR;
         let mut open = self.create_open_handle(inode, &req, flags).await?;
@@ -1394,6 +2197,7 @@ impl FileAccessor {
     /// remove an extended attribute.
     pub async fn removexattr(&self, req: &RequestContext, inode: u64, name: &str) -> Result<bool> {
         self.tick().await?;
+        self.check_writable()?;
         debug!("wasmer-dfs::removexattr not-implemented");
 
         let flags = O_RDWR;
@@ -1427,3 +2231,197 @@ impl FileAccessor {
         open.spec.list_xattr().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_accessor() -> FileAccessor {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_accessor_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let accessor = FileAccessor::new(
+            chain,
+            None,
+            session.into(),
+            TransactionScope::Full,
+            TransactionScope::Full,
+            false,
+            false,
+        )
+        .await;
+        accessor.init(&accessor.session_context()).await.unwrap();
+        accessor
+    }
+
+    async fn write_file(accessor: &FileAccessor, parent: u64, name: &str, data: &[u8]) -> u64 {
+        let ctx = accessor.session_context();
+        let handle = accessor.create(&ctx, parent, name, 0o644).await.unwrap();
+        accessor
+            .write(&ctx, handle.inode, handle.fh, 0, data, 0)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        handle.inode
+    }
+
+    async fn read_file(accessor: &FileAccessor, inode: u64) -> Bytes {
+        let ctx = accessor.session_context();
+        let handle = accessor.open(&ctx, inode, O_RDONLY as u32).await.unwrap();
+        let ret = accessor
+            .read(&ctx, handle.inode, handle.fh, 0, 4096)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        ret
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_hard_links_share_the_inode() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let dir = accessor.mkdir(&ctx, 1, "home", 0o770).await.unwrap().ino;
+        let ino = write_file(&accessor, 1, "a.txt", b"hello").await;
+
+        // The link is presented as the inode it refers to
+        let attr = accessor.link(&ctx, ino, dir, "b.txt").await.unwrap();
+        assert_eq!(attr.ino, ino);
+        assert_eq!(attr.nlink, 2);
+        let found = accessor.lookup(&ctx, dir, "b.txt").await.unwrap().unwrap();
+        assert_eq!(found.ino, ino);
+
+        // Names that are already taken and directories can not be linked
+        assert!(matches!(
+            accessor.link(&ctx, ino, dir, "b.txt").await,
+            Err(FileSystemError(FileSystemErrorKind::AlreadyExists, _))
+        ));
+        assert!(matches!(
+            accessor.link(&ctx, dir, 1, "c").await,
+            Err(FileSystemError(FileSystemErrorKind::IsDirectory, _))
+        ));
+
+        // The data outlives the original name while a link still refers to it
+        accessor.unlink(&ctx, 1, "a.txt").await.unwrap();
+        assert!(accessor.lookup(&ctx, 1, "a.txt").await.unwrap().is_none());
+        let found = accessor.lookup(&ctx, dir, "b.txt").await.unwrap().unwrap();
+        assert_eq!(found.nlink, 1);
+        assert_eq!(read_file(&accessor, ino).await.as_ref(), b"hello");
+
+        accessor.unlink(&ctx, dir, "b.txt").await.unwrap();
+        assert!(accessor.lookup(&ctx, dir, "b.txt").await.unwrap().is_none());
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_readlink() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let ino = write_file(&accessor, 1, "a.txt", b"hello").await;
+
+        let attr = accessor
+            .symlink(&ctx, 1, "b.txt", "/a.txt")
+            .await
+            .unwrap();
+        assert!(attr.kind == FileKind::SymLink);
+        assert_eq!(accessor.readlink(&ctx, attr.ino).await.unwrap(), "/a.txt");
+
+        // Only symbolic links can be read as one
+        assert!(matches!(
+            accessor.readlink(&ctx, ino).await,
+            Err(FileSystemError(FileSystemErrorKind::InvalidArguments, _))
+        ));
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_statfs_counts_inodes() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+
+        let stats = accessor.statfs(&ctx, 1).await.unwrap();
+        assert_eq!(stats.files - stats.ffree, 1);
+        assert_eq!(stats.ffree, crate::stats::DEFAULT_FREE_INODES);
+
+        // Hard links do not use up another inode
+        let dir = accessor.mkdir(&ctx, 1, "home", 0o770).await.unwrap().ino;
+        let ino = write_file(&accessor, dir, "a.txt", b"hello").await;
+        accessor.link(&ctx, ino, 1, "b.txt").await.unwrap();
+        let stats = accessor.statfs(&ctx, 1).await.unwrap();
+        assert_eq!(stats.files - stats.ffree, 3);
+        assert!(stats.blocks >= stats.bfree);
+        assert_eq!(stats.bavail, stats.bfree);
+
+        // Nor does the original name once it is only held by the link
+        accessor.unlink(&ctx, dir, "a.txt").await.unwrap();
+        let stats = accessor.statfs(&ctx, 1).await.unwrap();
+        assert_eq!(stats.files - stats.ffree, 3);
+        accessor.unlink(&ctx, 1, "b.txt").await.unwrap();
+        let stats = accessor.statfs(&ctx, 1).await.unwrap();
+        assert_eq!(stats.files - stats.ffree, 2);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_flock_holds_across_mounts() {
+        ate::utils::bootstrap_test_env();
+        let a = test_accessor().await;
+        let ctx = a.session_context();
+        let ino = write_file(&a, 1, "a.txt", b"hello").await;
+
+        // A second mount of the same chain
+        let b = FileAccessor::new(
+            Arc::clone(&a.chain),
+            None,
+            a.session.clone(),
+            TransactionScope::Full,
+            TransactionScope::Full,
+            false,
+            false,
+        )
+        .await;
+
+        let fa = a.open(&ctx, ino, O_RDWR as u32).await.unwrap();
+        let fb = b.open(&ctx, ino, O_RDWR as u32).await.unwrap();
+
+        // Exclusive locks keep the other mount out until they are dropped
+        a.flock(&ctx, ino, fa.fh, 1, F_WRLCK, false).await.unwrap();
+        for typ in [F_RDLCK, F_WRLCK] {
+            assert!(matches!(
+                b.flock(&ctx, ino, fb.fh, 2, typ, false).await,
+                Err(FileSystemError(FileSystemErrorKind::WouldBlock, _))
+            ));
+        }
+        a.flock(&ctx, ino, fa.fh, 1, F_UNLCK, false).await.unwrap();
+
+        // Shared locks are held by both mounts through their read slots
+        a.flock(&ctx, ino, fa.fh, 1, F_RDLCK, false).await.unwrap();
+        b.flock(&ctx, ino, fb.fh, 2, F_RDLCK, false).await.unwrap();
+        assert!(matches!(
+            b.flock(&ctx, ino, fb.fh, 2, F_WRLCK, false).await,
+            Err(FileSystemError(FileSystemErrorKind::WouldBlock, _))
+        ));
+
+        // Closing the file drops its lock so the other mount can upgrade
+        a.release(&ctx, ino, fa.fh, 0, 1, false).await.unwrap();
+        b.flock(&ctx, ino, fb.fh, 2, F_WRLCK, false).await.unwrap();
+        b.release(&ctx, ino, fb.fh, 0, 2, false).await.unwrap();
+    }
+}
@@ -52,6 +52,10 @@ pub trait FileApi {
         0
     }
 
+    fn nlink(&self) -> u32 {
+        1
+    }
+
     fn mode(&self) -> u32 {
         0
     }
@@ -10,6 +10,7 @@ pub struct FileAttr {
     pub created: u64,
     pub kind: FileKind,
     pub mode: u32,
+    pub nlink: u32,
     pub uid: u32,
     pub gid: u32,
 }
@@ -27,6 +28,7 @@ impl FileAttr {
             created: spec.created(),
             kind: spec.kind(),
             mode: spec.mode(),
+            nlink: spec.nlink(),
             uid,
             gid,
             blksize: blksize as u32,
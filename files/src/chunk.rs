@@ -0,0 +1,1181 @@
+use ate::meta::MetaAuthorization;
+use ate::prelude::*;
+use bytes::Bytes;
+use fxhash::FxHashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::conflict::host_name;
+use super::error::*;
+use super::model::*;
+
+pub const CHUNK_MIN: usize = 16384; // Smallest chunk that is cut (unless the file ends first)
+pub const CHUNK_AVG: usize = 65536; // Size that the chunker normalizes towards
+pub const CHUNK_MAX: usize = 262144; // Chunks are always cut at this size
+const CACHED_CHUNKS: usize = 32; // Number of cached chunks per open file
+const OVERLAY_MAX: usize = 8388608; // Pending writes are flushed when they reach this size
+const CHUNK_LOCK_TIMEOUT: Duration = Duration::from_secs(30); // Longest wait for the lock of a chunk
+
+// Masks used for normalized chunking - before the average size a boundary is
+// harder to find and after it becomes easier, which narrows the distribution
+const MASK_S: u64 = !(u64::MAX >> 18);
+const MASK_L: u64 = !(u64::MAX >> 14);
+
+/// Table of random values that the rolling gear hash is built from
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut ret = [0u64; 256];
+    let mut seed = 0x9e3779b97f4a7c15u64;
+    let mut n = 0;
+    while n < 256 {
+        // splitmix64
+        seed = seed.wrapping_add(0x9e3779b97f4a7c15u64);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9u64);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111ebu64);
+        ret[n] = z ^ (z >> 31);
+        n += 1;
+    }
+    ret
+}
+
+/// Finds the next content-defined boundary in the buffer, returning `None`
+/// when the buffer ends before a boundary could be found
+pub fn find_boundary(buf: &[u8]) -> Option<usize> {
+    if buf.len() <= CHUNK_MIN {
+        return None;
+    }
+
+    let end = buf.len().min(CHUNK_MAX);
+    let mut hash = 0u64;
+    for n in CHUNK_MIN..end {
+        hash = (hash << 1).wrapping_add(GEAR[buf[n] as usize]);
+        let mask = if n < CHUNK_AVG { MASK_S } else { MASK_L };
+        if hash & mask == 0 {
+            return Some(n + 1);
+        }
+    }
+
+    match end {
+        CHUNK_MAX => Some(CHUNK_MAX),
+        _ => None,
+    }
+}
+
+/// Chunks are only shared between files that are readable and writable by
+/// the same keys, otherwise one user could learn what another has stored
+fn chunk_scope(auth: &MetaAuthorization) -> String {
+    let read = match &auth.read {
+        ReadOption::Specific(hash, _) => hash.to_string(),
+        ReadOption::Everyone(_) => "everyone".to_string(),
+        ReadOption::Inherit => "inherit".to_string(),
+    };
+    format!("{}/{}", read, auth.write)
+}
+
+/// Returns the offset that a rewrite of `pos` must start from so that it
+/// replaces whole chunks
+fn align_down(refs: &[ChunkRef], pos: u64) -> u64 {
+    match refs.iter().find(|c| c.offset <= pos && c.end() > pos) {
+        Some(c) => c.offset,
+        None => pos,
+    }
+}
+
+/// Returns the offset that a rewrite ending at `pos` must extend to so that
+/// it replaces whole chunks
+fn align_up(refs: &[ChunkRef], pos: u64) -> u64 {
+    match refs.iter().find(|c| c.offset < pos && c.end() > pos) {
+        Some(c) => c.end(),
+        None => pos,
+    }
+}
+
+/// Writes that have been made to a file but not yet cut into chunks
+#[derive(Debug)]
+pub struct ChunkOverlay {
+    pub start: u64,
+    pub buf: Vec<u8>,
+}
+
+impl ChunkOverlay {
+    pub fn end(&self) -> u64 {
+        self.start + self.buf.len() as u64
+    }
+}
+
+/// Chunks that were recently read by an open file and any pending writes
+pub struct ChunkState {
+    cache: Box<[Option<(PrimaryKey, Bytes)>; CACHED_CHUNKS]>,
+    overlay: Option<ChunkOverlay>,
+}
+
+impl ChunkState {
+    pub fn new() -> ChunkState {
+        ChunkState {
+            cache: Box::new(array_init::array_init(|_| None)),
+            overlay: None,
+        }
+    }
+
+    async fn load(&mut self, dio: &Arc<Dio>, key: &PrimaryKey) -> Result<Bytes> {
+        // Use the cache-line to load the chunk
+        let cache_index = key.as_u64() as usize % CACHED_CHUNKS;
+        let cache_line = &mut self.cache[cache_index];
+        match cache_line {
+            Some((k, b)) if k == key => Ok(b.clone()),
+            _ => {
+                let dao = dio.load::<Chunk>(key).await?;
+                let buf = Bytes::from(dao.take().buf);
+                cache_line.replace((key.clone(), buf.clone()));
+                Ok(buf)
+            }
+        }
+    }
+
+    /// Reads a range of the committed chunks (holes are read as zeros)
+    async fn read_base(
+        &mut self,
+        dio: &Arc<Dio>,
+        refs: &[ChunkRef],
+        offset: u64,
+        size: u64,
+    ) -> Result<Vec<u8>> {
+        let mut ret = vec![0u8; size as usize];
+        let end = offset + size;
+        for c in refs.iter().filter(|c| c.offset < end && c.end() > offset) {
+            let buf = self.load(dio, &c.key).await?;
+            let from = c.offset.max(offset);
+            let to = c.end().min(end).min(c.offset + buf.len() as u64);
+            if to > from {
+                ret[(from - offset) as usize..(to - offset) as usize]
+                    .copy_from_slice(&buf[(from - c.offset) as usize..(to - c.offset) as usize]);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Reads a range of the file including any writes that are still pending
+    pub async fn read(
+        &mut self,
+        dio: &Arc<Dio>,
+        refs: &[ChunkRef],
+        offset: u64,
+        size: u64,
+    ) -> Result<Vec<u8>> {
+        let mut ret = self.read_base(dio, refs, offset, size).await?;
+        if let Some(overlay) = self.overlay.as_ref() {
+            let from = overlay.start.max(offset);
+            let to = overlay.end().min(offset + size);
+            if to > from {
+                ret[(from - offset) as usize..(to - offset) as usize].copy_from_slice(
+                    &overlay.buf[(from - overlay.start) as usize..(to - overlay.start) as usize],
+                );
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Writes data into the pending overlay which will be cut into chunks when
+    /// the file is committed (or the overlay grows too large)
+    pub async fn write(
+        &mut self,
+        inode: &mut DaoMut<Inode>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<()> {
+        // Writes that are not next to the pending ones are flushed separately
+        let end = offset + data.len() as u64;
+        if let Some(overlay) = self.overlay.as_ref() {
+            if offset > overlay.end() || end < overlay.start {
+                self.flush_overlay(inode, true).await?;
+            }
+        }
+
+        // Extend the overlay so that it covers whole chunks
+        let dio = inode.dio().clone();
+        let start = align_down(&inode.chunks, offset);
+        let stop = align_up(&inode.chunks, end);
+        let mut overlay = match self.overlay.take() {
+            Some(mut overlay) => {
+                if start < overlay.start {
+                    let mut buf = self
+                        .read_base(&dio, &inode.chunks, start, overlay.start - start)
+                        .await?;
+                    buf.extend_from_slice(&overlay.buf[..]);
+                    overlay.buf = buf;
+                    overlay.start = start;
+                }
+                if stop > overlay.end() {
+                    let buf = self
+                        .read_base(&dio, &inode.chunks, overlay.end(), stop - overlay.end())
+                        .await?;
+                    overlay.buf.extend_from_slice(&buf[..]);
+                }
+                overlay
+            }
+            None => ChunkOverlay {
+                start,
+                buf: self
+                    .read_base(&dio, &inode.chunks, start, stop - start)
+                    .await?,
+            },
+        };
+
+        // Apply the write
+        let pos = (offset - overlay.start) as usize;
+        overlay.buf[pos..pos + data.len()].copy_from_slice(data);
+        let full = overlay.buf.len() >= OVERLAY_MAX;
+        self.overlay.replace(overlay);
+
+        if full {
+            self.flush_overlay(inode, false).await?;
+        }
+        Ok(())
+    }
+
+    /// Cuts all the pending writes into chunks and drops any chunks that are
+    /// beyond the end of the file
+    pub async fn flush(&mut self, inode: &mut DaoMut<Inode>) -> Result<()> {
+        self.flush_overlay(inode, true).await?;
+
+        // A chunk that straddles the end of the file is rewritten without its tail
+        let size = inode.size;
+        let straddle = inode
+            .chunks
+            .iter()
+            .filter(|c| c.offset < size && c.end() > size)
+            .next()
+            .map(|c| c.offset);
+        if let Some(offset) = straddle {
+            let dio = inode.dio().clone();
+            let buf = self
+                .read_base(&dio, &inode.chunks, offset, size - offset)
+                .await?;
+            self.overlay.replace(ChunkOverlay { start: offset, buf });
+            self.flush_overlay(inode, true).await?;
+        }
+
+        // Release any chunks that are past the end of the file
+        if inode.chunks.iter().any(|c| c.offset >= size) {
+            let dio = inode.trans();
+            let mut release = ChunkRelease::default();
+            for c in inode.chunks.iter().filter(|c| c.offset >= size) {
+                release.add(c);
+            }
+            inode.as_mut().chunks.retain(|c| c.offset < size);
+            dio.commit().await?;
+            release.apply(inode.dio()).await?;
+        }
+        Ok(())
+    }
+
+    /// Replaces a range of the file with chunks that are shared with another
+    /// file - the range starts at the first chunk and ends after the last one
+    pub async fn share(&mut self, inode: &mut DaoMut<Inode>, refs: Vec<ChunkRef>) -> Result<()> {
+        let (start, end) = match (refs.first(), refs.last()) {
+            (Some(first), Some(last)) => (first.offset, last.end()),
+            _ => {
+                return Ok(());
+            }
+        };
+
+        // The chunks of this file must have boundaries at both ends of the range
+        self.flush(inode).await?;
+        self.split(inode, start).await?;
+        self.split(inode, end).await?;
+
+        // Take a reference on the shared chunks, the ones they replace are
+        // only released once the inode no longer refers to them
+        share_chunks(inode.dio(), inode.auth(), &refs).await?;
+        let dio = inode.trans();
+        let mut release = ChunkRelease::default();
+        for c in inode
+            .chunks
+            .iter()
+            .filter(|c| c.offset >= start && c.end() <= end)
+        {
+            release.add(c);
+        }
+
+        {
+            let mut guard = inode.as_mut();
+            let mut chunks = Vec::with_capacity(guard.chunks.len() + refs.len());
+            chunks.extend(guard.chunks.iter().filter(|c| c.end() <= start).cloned());
+            chunks.extend(refs.into_iter());
+            chunks.extend(guard.chunks.iter().filter(|c| c.offset >= end).cloned());
+            guard.chunks = chunks;
+            if guard.size < end {
+                guard.size = end;
+            }
+        }
+        dio.commit().await?;
+        release.apply(inode.dio()).await
+    }
+
+    /// Splits the chunk that straddles an offset so that there is a boundary there
+    async fn split(&mut self, inode: &mut DaoMut<Inode>, pos: u64) -> Result<()> {
+        let chunk = match inode
+            .chunks
+            .iter()
+            .filter(|c| c.offset < pos && c.end() > pos)
+            .next()
+        {
+            Some(a) => a.clone(),
+            None => {
+                return Ok(());
+            }
+        };
+
+        let dio = inode.dio().clone();
+        let mut head = self
+            .read_base(&dio, &inode.chunks, chunk.offset, chunk.size as u64)
+            .await?;
+        let tail = head.split_off((pos - chunk.offset) as usize);
+
+        self.overlay.replace(ChunkOverlay {
+            start: chunk.offset,
+            buf: head,
+        });
+        self.flush_overlay(inode, true).await?;
+        self.overlay.replace(ChunkOverlay {
+            start: pos,
+            buf: tail,
+        });
+        self.flush_overlay(inode, true).await
+    }
+
+    /// Cuts the overlay into content-defined chunks and stores them, when
+    /// `all` is false the tail after the last boundary is left pending so
+    /// that the next write can continue it
+    async fn flush_overlay(&mut self, inode: &mut DaoMut<Inode>, all: bool) -> Result<()> {
+        let overlay = match self.overlay.take() {
+            Some(a) => a,
+            None => {
+                return Ok(());
+            }
+        };
+        let start = overlay.start;
+        let end = overlay.end();
+
+        // Anything beyond the end of the file is discarded
+        let mut buf = overlay.buf;
+        let size = inode.size;
+        buf.truncate(size.saturating_sub(start).min(buf.len() as u64) as usize);
+
+        // Find the boundaries
+        let mut cuts = Vec::new();
+        let mut pos = 0usize;
+        while pos < buf.len() {
+            match find_boundary(&buf[pos..]) {
+                Some(n) => {
+                    cuts.push((pos, pos + n));
+                    pos += n;
+                }
+                None if all => {
+                    cuts.push((pos, buf.len()));
+                    pos = buf.len();
+                }
+                None => break,
+            }
+        }
+
+        // Chunks are addressed by a hash of their contents (except for those
+        // cut while the chain is disconnected which get keys of their own as
+        // shared chunks can only be referenced while holding their locks)
+        let dio = inode.dio().clone();
+        let scope = chunk_scope(inode.auth());
+        let online = dio.chain().is_connected().await;
+        let mut deltas = FxHashMap::default();
+        let mut created = FxHashMap::default();
+        let mut refs = Vec::with_capacity(cuts.len());
+        for (from, to) in cuts {
+            let data = &buf[from..to];
+            let hash = AteHash::from_bytes(data);
+            let mut key = match online {
+                true => {
+                    PrimaryKey::from(AteHash::from_bytes_twice(scope.as_bytes(), hash.as_bytes()))
+                }
+                false => PrimaryKey::generate(),
+            };
+            if deltas.contains_key(&key) == false {
+                let shared = match dio.load::<Chunk>(&key).await {
+                    Ok(existing) => existing.hash == hash,
+                    Err(LoadError(LoadErrorKind::NotFound(_), _)) => true,
+                    Err(_) => false,
+                };
+                if shared == false {
+                    // The key is taken by something else so this chunk can not be shared
+                    key = PrimaryKey::generate();
+                }
+
+                // The data is kept even when the chunk already exists so that
+                // it can be stored again if another mount deletes it before
+                // the reference is taken
+                created.insert(
+                    key,
+                    Chunk {
+                        hash,
+                        buf: data.to_vec(),
+                        refs: 0,
+                    },
+                );
+            }
+            *deltas.entry(key).or_insert(0i64) += 1;
+            refs.push(ChunkRef {
+                offset: start + from as u64,
+                size: (to - from) as u32,
+                key,
+            });
+        }
+
+        // The new chunks are referenced before the inode points at them while
+        // the chunks that were rewritten are released after it no longer does
+        update_refs(&dio, Some(inode.auth()), deltas, created).await?;
+        let mut release = ChunkRelease::default();
+        for c in inode
+            .chunks
+            .iter()
+            .filter(|c| c.offset < end && c.end() > start)
+        {
+            release.add(c);
+        }
+
+        // Splice the new chunks into the inode
+        let trans = inode.trans();
+        {
+            let mut guard = inode.as_mut();
+            let mut chunks = Vec::with_capacity(guard.chunks.len() + refs.len());
+            chunks.extend(guard.chunks.iter().filter(|c| c.end() <= start).cloned());
+            chunks.extend(refs.into_iter());
+            chunks.extend(guard.chunks.iter().filter(|c| c.offset >= end).cloned());
+            guard.chunks = chunks;
+        }
+        trans.commit().await?;
+        release.apply(&dio).await?;
+
+        // Whatever was not cut stays pending
+        if pos < buf.len() {
+            let buf = buf.split_off(pos);
+            self.overlay.replace(ChunkOverlay {
+                start: start + pos as u64,
+                buf,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Key of the mesh lock that guards the reference count of a chunk
+fn chunk_lock_key(key: &PrimaryKey) -> PrimaryKey {
+    PrimaryKey::from(AteHash::from_bytes_twice(
+        b"chunk-refs",
+        &key.as_u64().to_be_bytes(),
+    ))
+}
+
+/// Waits for a mesh lock - every mount of the chain changes the reference
+/// counts so without the locks concurrent updates would be lost. Returns
+/// false if the chain is (or becomes) disconnected while waiting
+pub(crate) async fn wait_for_lock(trans: &Arc<DioMut>, lock: PrimaryKey) -> Result<bool> {
+    let timer = Instant::now();
+    let mut max_wait = 0u64;
+    while trans.try_lock(lock).await? == false {
+        if trans.chain().is_connected().await == false {
+            return Ok(false);
+        }
+        if timer.elapsed() > CHUNK_LOCK_TIMEOUT {
+            debug!("lock {} is held by someone else", lock);
+            bail!(FileSystemErrorKind::WouldBlock);
+        }
+
+        // Use an exponential backoff
+        max_wait = (((max_wait * 12u64) / 10u64) + 5u64).min(500u64);
+        let random_wait = fastrand::u64(max_wait / 2u64..max_wait);
+        ate::engine::sleep(Duration::from_millis(random_wait)).await;
+    }
+    Ok(true)
+}
+
+/// Takes the mesh locks of a set of chunks (in order of their keys so that
+/// two mounts never wait on each other). Returns `None` without holding any
+/// of the locks if the chain is disconnected
+async fn lock_chunks(trans: &Arc<DioMut>, keys: &[PrimaryKey]) -> Result<Option<Vec<PrimaryKey>>> {
+    let mut locks = Vec::with_capacity(keys.len());
+    for key in keys {
+        let lock = chunk_lock_key(key);
+        match wait_for_lock(trans, lock).await {
+            Ok(true) => locks.push(lock),
+            ret => {
+                unlock_all(trans, locks).await?;
+                return ret.map(|_| None);
+            }
+        }
+    }
+    Ok(Some(locks))
+}
+
+/// Releases mesh locks (all of them are released even if one fails)
+async fn unlock_all(trans: &Arc<DioMut>, locks: Vec<PrimaryKey>) -> Result<()> {
+    let mut ret = Ok(());
+    for lock in locks {
+        if let Err(err) = trans.unlock(lock).await {
+            ret = Err(err.into());
+        }
+    }
+    ret
+}
+
+/// Applies reference count changes to chunks. The chunks are reloaded and
+/// updated in a single transaction while holding all of their mesh locks.
+/// New chunks are stored with the same authorization as the file and chunks
+/// that are no longer referenced are deleted so that compaction can remove
+/// them. While the chain is disconnected the changes are journaled instead
+async fn update_refs(
+    dio: &Arc<Dio>,
+    auth: Option<&MetaAuthorization>,
+    deltas: FxHashMap<PrimaryKey, i64>,
+    mut created: FxHashMap<PrimaryKey, Chunk>,
+) -> Result<()> {
+    let mut keys = deltas
+        .iter()
+        .filter(|(_, delta)| **delta != 0)
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        return Ok(());
+    }
+    keys.sort();
+
+    let trans = dio.trans(TransactionScope::Full).await;
+    trans.auto_cancel();
+    let locks = match lock_chunks(&trans, &keys[..]).await? {
+        Some(a) => a,
+        None => {
+            return journal_refs(&trans, auth, deltas, created).await;
+        }
+    };
+
+    let mut ret = Ok(());
+    for key in keys {
+        let delta = deltas[&key];
+        ret = update_ref(&trans, auth, key, delta, created.remove(&key)).await;
+        if ret.is_err() {
+            break;
+        }
+    }
+    if ret.is_ok() {
+        ret = trans.commit().await.map_err(|err| err.into());
+    }
+    unlock_all(&trans, locks).await?;
+    ret
+}
+
+/// Records reference count changes while the chain is disconnected. Chunks
+/// that exist are left as they are and the changes are kept in a
+/// `ChunkJournal` for `reconcile_chunks` to apply once the chain reconnects.
+/// Chunks that do not exist yet (those cut while offline get keys of their
+/// own) are stored with the references that were taken on them
+async fn journal_refs(
+    trans: &Arc<DioMut>,
+    auth: Option<&MetaAuthorization>,
+    deltas: FxHashMap<PrimaryKey, i64>,
+    mut created: FxHashMap<PrimaryKey, Chunk>,
+) -> Result<()> {
+    let mut journal = Vec::new();
+    for (key, delta) in deltas {
+        if delta == 0 {
+            continue;
+        }
+        if trans.exists(&key).await {
+            journal.push((key, delta));
+        } else {
+            update_ref(trans, auth, key, delta, created.remove(&key)).await?;
+        }
+    }
+
+    if journal.is_empty() == false {
+        debug!(
+            "journaled {} chunk reference(s) while offline",
+            journal.len()
+        );
+        let entry = ChunkJournal {
+            host: host_name().to_string(),
+            deltas: journal,
+        };
+        let mut entries = DaoVec::<ChunkJournal>::new_orphaned_mut(
+            trans,
+            PrimaryKey::from(1),
+            CHUNK_JOURNAL_VEC_ID,
+        );
+        entries.push(entry)?;
+    }
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Applies the reference count changes that this host journaled while the
+/// chain was disconnected and returns how many journal entries were applied.
+/// Each entry is applied while holding its own mesh lock (along with those of
+/// its chunks) and is deleted in the same transaction so that it is only ever
+/// applied once. Chunks that other mounts deleted in the meantime are read
+/// back from the history of the chain
+pub async fn reconcile_chunks(dio: &Arc<Dio>) -> Result<usize> {
+    let entries =
+        DaoVec::<ChunkJournal>::new_orphaned(dio, PrimaryKey::from(1), CHUNK_JOURNAL_VEC_ID)
+            .iter()
+            .await?
+            .filter(|e| e.host.as_str() == host_name())
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+
+    let mut ret = 0usize;
+    for key in entries {
+        let trans = dio.trans(TransactionScope::Full).await;
+        trans.auto_cancel();
+        if wait_for_lock(&trans, key).await? == false {
+            // The rest waits until the chain reconnects again
+            break;
+        }
+        let applied = reconcile_entry(dio, &trans, &key).await;
+        trans.unlock(key).await?;
+        if applied? {
+            ret += 1;
+        }
+    }
+    Ok(ret)
+}
+
+async fn reconcile_entry(
+    dio: &Arc<Dio>,
+    trans: &Arc<DioMut>,
+    entry_key: &PrimaryKey,
+) -> Result<bool> {
+    // Another mount on this host may have applied it already
+    let entry = match trans.load::<ChunkJournal>(entry_key).await {
+        Ok(a) => a.take(),
+        Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+            return Ok(false);
+        }
+        Err(err) => {
+            return Err(err.into());
+        }
+    };
+    let mut deltas = FxHashMap::default();
+    for (key, delta) in entry.deltas {
+        *deltas.entry(key).or_insert(0i64) += delta;
+    }
+    let mut keys = deltas
+        .iter()
+        .filter(|(_, delta)| **delta != 0)
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    let locks = match lock_chunks(trans, &keys[..]).await? {
+        Some(a) => a,
+        None => {
+            return Ok(false);
+        }
+    };
+    let mut ret = Ok(());
+    for key in keys {
+        let delta = deltas[&key];
+        ret = reconcile_ref(dio, trans, key, delta).await;
+        if ret.is_err() {
+            break;
+        }
+    }
+    if ret.is_ok() {
+        ret = match trans.delete(entry_key).await {
+            Ok(()) => trans.commit().await.map_err(|err| err.into()),
+            Err(err) => Err(err.into()),
+        };
+    }
+    unlock_all(trans, locks).await?;
+    ret.map(|_| true)
+}
+
+/// Applies a journaled change to the reference count of a chunk (the caller
+/// holds its lock)
+async fn reconcile_ref(
+    dio: &Arc<Dio>,
+    trans: &Arc<DioMut>,
+    key: PrimaryKey,
+    delta: i64,
+) -> Result<()> {
+    if delta > 0 && trans.exists(&key).await == false {
+        if let Some(restored) = dio.load_versions::<Chunk>(&key).await?.pop() {
+            debug!("chunk {} was deleted while offline", key);
+            let auth = restored.auth().clone();
+            return update_ref(trans, Some(&auth), key, delta, Some(restored.take())).await;
+        }
+    }
+    update_ref(trans, None, key, delta, None).await
+}
+
+/// Changes the reference count of a single chunk (the caller holds its lock
+/// unless the chunk is new and has a key of its own)
+async fn update_ref(
+    trans: &Arc<DioMut>,
+    auth: Option<&MetaAuthorization>,
+    key: PrimaryKey,
+    delta: i64,
+    created: Option<Chunk>,
+) -> Result<()> {
+    match trans.load::<Chunk>(&key).await {
+        Ok(mut dao) => {
+            // Another mount may have stored the same chunk in the meantime
+            let refs = dao.refs as i64 + delta;
+            if refs <= 0 {
+                drop(dao);
+                trans.delete(&key).await?;
+            } else {
+                dao.as_mut().refs = refs as u64;
+            }
+        }
+        Err(LoadError(LoadErrorKind::NotFound(_), _)) => match (created, auth) {
+            (Some(mut chunk), Some(auth)) if delta > 0 => {
+                chunk.refs = delta as u64;
+
+                // Chunks are shared between files so they hang off the root rather than an inode
+                let mut dao = trans.store_with_key(chunk, key)?;
+                dao.attach_orphaned(&PrimaryKey::from(1))?;
+                let mut guard = dao.auth_mut();
+                guard.read = auth.read.clone();
+                guard.write = auth.write.clone();
+                guard.commit()?;
+            }
+            _ if delta > 0 => {
+                // Referencing a chunk that no longer exists would leave the
+                // file pointing at data that is gone
+                error!("chunk {} is missing", key);
+                bail!(FileSystemErrorKind::MissingChunk(key.to_string()));
+            }
+            _ => {
+                // There is nothing left to release
+                debug!("chunk {} was already released", key);
+            }
+        },
+        Err(err) => {
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+/// References to chunks that are released once the change that stopped using
+/// them has been committed - releasing them any earlier would delete chunks
+/// that are still in use if that commit were to fail
+#[derive(Debug, Default)]
+#[must_use = "the chunks are only released when `apply` is called"]
+pub struct ChunkRelease {
+    deltas: FxHashMap<PrimaryKey, i64>,
+}
+
+impl ChunkRelease {
+    /// Releases one reference on a chunk
+    pub fn add(&mut self, chunk: &ChunkRef) {
+        *self.deltas.entry(chunk.key).or_insert(0i64) -= 1;
+    }
+
+    /// Releases the references that a file holds on its chunks (used when the
+    /// inode itself is deleted)
+    pub fn add_inode(&mut self, inode: &Inode) {
+        for c in inode.chunks.iter() {
+            self.add(c);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub async fn apply(self, dio: &Arc<Dio>) -> Result<()> {
+        update_refs(dio, None, self.deltas, FxHashMap::default()).await
+    }
+}
+
+/// Takes a reference on each of the chunks (used when they are shared with
+/// another inode) - this must happen before that inode is committed
+pub async fn share_chunks(
+    dio: &Arc<Dio>,
+    auth: &MetaAuthorization,
+    chunks: &[ChunkRef],
+) -> Result<()> {
+    share_chunks_from(dio, dio, auth, chunks).await
+}
+
+/// Takes a reference on each of the chunks of a file that is copied out of an
+/// older view of the file system (i.e. a snapshot) - chunks that have been
+/// deleted since then are brought back from that view
+pub async fn share_chunks_from(
+    dio: &Arc<Dio>,
+    from: &Arc<Dio>,
+    auth: &MetaAuthorization,
+    chunks: &[ChunkRef],
+) -> Result<()> {
+    let mut deltas = FxHashMap::default();
+    let mut created = FxHashMap::default();
+    for c in chunks.iter() {
+        *deltas.entry(c.key).or_insert(0i64) += 1;
+
+        // The data is kept so that the chunk can be stored again if it is
+        // deleted before the reference is taken
+        if created.contains_key(&c.key) == false {
+            let chunk = match dio.load_and_take::<Chunk>(&c.key).await {
+                Ok(a) => a,
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                    from.load_and_take::<Chunk>(&c.key).await?
+                }
+                Err(err) => {
+                    return Err(err.into());
+                }
+            };
+            created.insert(c.key, chunk);
+        }
+    }
+    update_refs(dio, Some(auth), deltas, created).await
+}
+
+/// Chunks can only be shared between files that are protected by the same keys
+pub fn can_share(a: &MetaAuthorization, b: &MetaAuthorization) -> bool {
+    chunk_scope(a) == chunk_scope(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fxhash::FxHashSet;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    fn random_data(len: usize, seed: u64) -> Vec<u8> {
+        let rng = fastrand::Rng::with_seed(seed);
+        (0..len).map(|_| rng.u8(..)).collect()
+    }
+
+    /// Returns the offsets at which the buffer is cut into chunks
+    fn cuts(buf: &[u8]) -> Vec<usize> {
+        let mut ret = Vec::new();
+        let mut pos = 0usize;
+        while let Some(n) = find_boundary(&buf[pos..]) {
+            pos += n;
+            ret.push(pos);
+        }
+        ret
+    }
+
+    #[test]
+    fn test_find_boundary_limits() {
+        // Nothing is cut until there is more than the minimum
+        assert_eq!(find_boundary(&[]), None);
+        assert_eq!(find_boundary(&vec![0u8; CHUNK_MIN]), None);
+
+        // Data is always cut by the maximum
+        assert!(matches!(
+            find_boundary(&vec![0u8; CHUNK_MAX * 2]),
+            Some(n) if n > CHUNK_MIN && n <= CHUNK_MAX
+        ));
+
+        let data = random_data(CHUNK_MAX * 8, 1);
+        let mut last = 0usize;
+        for cut in cuts(&data) {
+            assert!(cut - last > CHUNK_MIN);
+            assert!(cut - last <= CHUNK_MAX);
+            last = cut;
+        }
+    }
+
+    #[test]
+    fn test_find_boundary_is_content_defined() {
+        let data = random_data(CHUNK_MAX * 8, 2);
+        let before = cuts(&data);
+        assert!(before.len() > 4);
+
+        // Inserting data at the front only moves the first boundaries, the
+        // rest are found at the same place in the content
+        let mut shifted = vec![7u8; 100];
+        shifted.extend_from_slice(&data[..]);
+        let after = cuts(&shifted)
+            .into_iter()
+            .filter_map(|a| a.checked_sub(100))
+            .collect::<FxHashSet<_>>();
+        let same = before.iter().filter(|a| after.contains(a)).count();
+        assert!(same >= before.len() - 2);
+    }
+
+    async fn test_dio() -> Arc<Dio> {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_chunks_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        chain.dio(&session).await
+    }
+
+    async fn write_file(dio: &Arc<Dio>, name: &str, data: &[u8]) -> DaoMut<Inode> {
+        let trans = dio.trans(TransactionScope::Full).await;
+        let mut inode = trans
+            .store(Inode::new(
+                name.to_string(),
+                0o644,
+                0,
+                0,
+                FileKind::RegularFile,
+            ))
+            .unwrap();
+        inode.as_mut().size = data.len() as u64;
+        trans.commit().await.unwrap();
+
+        let mut state = ChunkState::new();
+        state.write(&mut inode, 0, data).await.unwrap();
+        state.flush(&mut inode).await.unwrap();
+        inode
+    }
+
+    async fn refs(dio: &Arc<Dio>, key: &PrimaryKey) -> Option<u64> {
+        match dio.load::<Chunk>(key).await {
+            Ok(a) => Some(a.refs),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    async fn journal(dio: &Arc<Dio>) -> Vec<(PrimaryKey, i64)> {
+        DaoVec::<ChunkJournal>::new_orphaned(dio, PrimaryKey::from(1), CHUNK_JOURNAL_VEC_ID)
+            .iter()
+            .await
+            .unwrap()
+            .flat_map(|e| e.take().deltas)
+            .collect()
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_chunks_are_reference_counted() {
+        ate::utils::bootstrap_test_env();
+        let dio = test_dio().await;
+
+        // Files with the same contents share their chunks
+        let data = random_data(CHUNK_MIN, 3);
+        let a = write_file(&dio, "a", &data[..]).await;
+        let b = write_file(&dio, "b", &data[..]).await;
+        assert_eq!(a.chunks.len(), 1);
+        assert_eq!(a.chunks, b.chunks);
+        let key = a.chunks[0].key;
+        assert_eq!(refs(&dio, &key).await, Some(2));
+
+        // Sharing from several tasks at once does not lose any updates
+        let chunks = a.chunks.clone();
+        let (r1, r2) = tokio::join!(
+            share_chunks(&dio, a.auth(), &chunks[..]),
+            share_chunks(&dio, a.auth(), &chunks[..])
+        );
+        r1.unwrap();
+        r2.unwrap();
+        assert_eq!(refs(&dio, &key).await, Some(4));
+
+        // The chunk is only deleted once the last reference is released
+        let mut release = ChunkRelease::default();
+        release.add_inode(&a);
+        release.add_inode(&a);
+        release.add_inode(&a);
+        release.apply(&dio).await.unwrap();
+        assert_eq!(refs(&dio, &key).await, Some(1));
+
+        let mut release = ChunkRelease::default();
+        release.add_inode(&b);
+        release.apply(&dio).await.unwrap();
+        assert_eq!(refs(&dio, &key).await, None);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_rewrites_release_old_chunks() {
+        ate::utils::bootstrap_test_env();
+        let dio = test_dio().await;
+
+        let mut a = write_file(&dio, "a", &random_data(CHUNK_MIN, 4)[..]).await;
+        let old = a.chunks[0].key;
+        assert_eq!(refs(&dio, &old).await, Some(1));
+
+        // Overwriting the file moves it onto a new chunk
+        let mut state = ChunkState::new();
+        state
+            .write(&mut a, 0, &random_data(CHUNK_MIN, 5)[..])
+            .await
+            .unwrap();
+        state.flush(&mut a).await.unwrap();
+        assert_eq!(refs(&dio, &old).await, None);
+        assert_eq!(refs(&dio, &a.chunks[0].key).await, Some(1));
+
+        // Truncating the file releases the chunks past the end
+        let new = a.chunks[0].key;
+        a.as_mut().size = 0;
+        state.flush(&mut a).await.unwrap();
+        assert!(a.chunks.is_empty());
+        assert_eq!(refs(&dio, &new).await, None);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_deleted_chunks_are_stored_again() {
+        ate::utils::bootstrap_test_env();
+        let dio = test_dio().await;
+
+        let data = random_data(CHUNK_MIN, 6);
+        let a = write_file(&dio, "a", &data[..]).await;
+        let key = a.chunks[0].key;
+        let chunk = dio.load_and_take::<Chunk>(&key).await.unwrap();
+
+        // Another mount releases the last reference after the chunk was found
+        let mut release = ChunkRelease::default();
+        release.add_inode(&a);
+        release.apply(&dio).await.unwrap();
+        assert_eq!(refs(&dio, &key).await, None);
+
+        // The reference that is taken afterwards brings the data back
+        let mut deltas = FxHashMap::default();
+        deltas.insert(key, 1i64);
+        let mut created = FxHashMap::default();
+        created.insert(key, chunk);
+        update_refs(&dio, Some(a.auth()), deltas, created)
+            .await
+            .unwrap();
+        assert_eq!(refs(&dio, &key).await, Some(1));
+
+        // Without the data the reference fails rather than pointing at nothing
+        let mut release = ChunkRelease::default();
+        release.add_inode(&a);
+        release.apply(&dio).await.unwrap();
+        let mut deltas = FxHashMap::default();
+        deltas.insert(key, 1i64);
+        let ret = update_refs(&dio, Some(a.auth()), deltas, FxHashMap::default()).await;
+        assert!(matches!(
+            ret,
+            Err(FileSystemError(FileSystemErrorKind::MissingChunk(_), _))
+        ));
+
+        // Releasing a chunk that is already gone is not an error
+        let mut release = ChunkRelease::default();
+        release.add_inode(&a);
+        release.apply(&dio).await.unwrap();
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_offline_refs_are_journaled() {
+        ate::utils::bootstrap_test_env();
+        let mut cfg_ate = ConfAte::default();
+        cfg_ate.recovery_mode = RecoveryMode::Async;
+
+        // The chain is hosted by a local mesh server that can be taken away
+        let cert = PrivateEncryptKey::generate(KeySize::Bit192);
+        ate::mesh::add_global_certificate(&cert.hash());
+        let port = 6000 + fastrand::u16(..1000);
+        let url = url::Url::parse(format!("ws://localhost:{}/", port).as_str()).unwrap();
+        let mut cfg_mesh = ConfMesh::solo_from_url(
+            &cfg_ate,
+            &url,
+            &IpAddr::from_str("::1").unwrap(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        cfg_mesh.wire_protocol = StreamProtocol::WebSocket;
+        cfg_mesh.listen_certificate = Some(cert);
+        let server = create_server(&cfg_mesh).await.unwrap();
+        server
+            .add_route(all_ethereal_distributed().await, &cfg_ate)
+            .await
+            .unwrap();
+
+        let client = create_temporal_client(&cfg_ate, &cfg_mesh);
+        let key = ChainKey::from(format!("test_chunks_{}", fastrand::u64(..)));
+        let chain = client.open(&url, &key).await.unwrap();
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let dio = chain.dio(&session).await;
+
+        let data = random_data(CHUNK_MIN, 7);
+        let a = write_file(&dio, "a", &data[..]).await;
+        let key = a.chunks[0].key;
+        assert_eq!(refs(&dio, &key).await, Some(1));
+
+        // Take the server away so that the chunk locks can not be reached
+        server.shutdown().await;
+        drop(server);
+        let timer = Instant::now();
+        while dio.chain().is_connected().await {
+            assert!(timer.elapsed() < Duration::from_secs(10));
+            ate::engine::sleep(Duration::from_millis(50)).await;
+        }
+
+        // Shared chunks are journaled rather than waiting for their locks
+        let timer = Instant::now();
+        share_chunks(&dio, a.auth(), &a.chunks[..]).await.unwrap();
+        let b = write_file(&dio, "b", &data[..]).await;
+        assert!(timer.elapsed() < CHUNK_LOCK_TIMEOUT);
+        assert_eq!(refs(&dio, &key).await, Some(1));
+        assert_eq!(journal(&dio).await, vec![(key, 1i64)]);
+
+        // Chunks that are cut while offline get keys of their own
+        assert_eq!(b.chunks.len(), 1);
+        assert!(b.chunks[0].key != key);
+        assert_eq!(refs(&dio, &b.chunks[0].key).await, Some(1));
+
+        // Releases are journaled as well (nothing is deleted while offline)
+        let mut release = ChunkRelease::default();
+        release.add_inode(&a);
+        release.apply(&dio).await.unwrap();
+        assert_eq!(refs(&dio, &key).await, Some(1));
+        assert_eq!(journal(&dio).await.len(), 2);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_journaled_refs_are_reconciled() {
+        ate::utils::bootstrap_test_env();
+        let dio = test_dio().await;
+
+        let a = write_file(&dio, "a", &random_data(CHUNK_MIN, 8)[..]).await;
+        let b = write_file(&dio, "b", &random_data(CHUNK_MIN, 9)[..]).await;
+        let (ka, kb) = (a.chunks[0].key, b.chunks[0].key);
+
+        // References that were taken offline on a chunk that another mount
+        // deleted in the meantime
+        let trans = dio.trans(TransactionScope::Full).await;
+        let mut deltas = FxHashMap::default();
+        deltas.insert(ka, 1i64);
+        deltas.insert(kb, 1i64);
+        journal_refs(&trans, None, deltas, FxHashMap::default())
+            .await
+            .unwrap();
+        let mut release = ChunkRelease::default();
+        release.add_inode(&b);
+        release.apply(&dio).await.unwrap();
+        assert_eq!(refs(&dio, &ka).await, Some(1));
+        assert_eq!(refs(&dio, &kb).await, None);
+
+        // The journal is applied once (bringing back what was deleted)
+        assert_eq!(reconcile_chunks(&dio).await.unwrap(), 1);
+        assert_eq!(refs(&dio, &ka).await, Some(2));
+        assert_eq!(refs(&dio, &kb).await, Some(1));
+        assert!(journal(&dio).await.is_empty());
+        assert_eq!(reconcile_chunks(&dio).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,712 @@
+use ate::prelude::*;
+use ate::time::ChainTimestamp;
+use error_chain::bail;
+use fxhash::FxHashSet;
+use once_cell::sync::Lazy;
+use std::ops::Deref;
+use std::sync::Arc;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::accessor::FileAccessor;
+use super::api::FileKind;
+use super::chunk::share_chunks;
+use super::chunk::share_chunks_from;
+use super::chunk::ChunkRelease;
+use super::error::*;
+use super::model::*;
+use super::snapshot::copy_auth;
+
+/// Extended attribute that lists the conflicted copies made of a file
+pub const CONFLICTS_XATTR: &'static str = "user.ate.conflicts";
+
+/// Host that conflicted copies are named after when the edits were made by a
+/// mount that was online (and hence did not record where it was running)
+const REMOTE_HOST: &'static str = "remote";
+
+/// Conflicted copy of a file as it is listed
+#[derive(Debug, Clone)]
+pub struct ConflictInfo {
+    /// Path of the file that the conflict was detected on
+    pub path: String,
+    /// Name of the conflicted copy (it sits next to the file)
+    pub name: String,
+    /// Host that made the edits that were kept in the copy
+    pub host: String,
+    /// Chain timestamp (in milliseconds) that the conflict was detected at
+    pub detected: u64,
+}
+
+static HOST_NAME: Lazy<String> = Lazy::new(|| {
+    if let Ok(name) = std::env::var("HOSTNAME") {
+        if name.is_empty() == false {
+            return name;
+        }
+    }
+    match std::fs::read_to_string("/etc/hostname") {
+        Ok(name) if name.trim().is_empty() == false => name.trim().to_string(),
+        _ => "unknown".to_string(),
+    }
+});
+
+/// Name of the machine that offline edits are recorded against
+pub fn host_name() -> &'static str {
+    HOST_NAME.as_str()
+}
+
+/// Formats a timestamp (in milliseconds) as a calendar date
+fn civil_date(ms: u64) -> String {
+    let z = (ms / 86_400_000) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Name given to the conflicted copy of a file (the extension is kept so
+/// that the copy still opens with the same application)
+fn conflicted_name(name: &str, host: &str, date: &str, attempt: u32) -> String {
+    let suffix = match attempt {
+        0 => format!(" (conflicted copy {} {})", host, date),
+        n => format!(" (conflicted copy {} {} {})", host, date, n + 1),
+    };
+    match name.rfind('.') {
+        Some(n) if n > 0 => format!("{}{}{}", &name[..n], suffix, &name[n..]),
+        _ => format!("{}{}", name, suffix),
+    }
+}
+
+/// Gives the contents of a file that is about to be committed a new revision.
+///
+/// Commits made while the mount is offline also keep the contents in a
+/// `Revision` (the first one stores it and later ones update it in place)
+/// so that they can be compared with what other mounts wrote once the mount
+/// reconnects. The chunks that the revision no longer refers to are returned
+/// and must be released after the commit
+pub(crate) async fn record_revision(
+    inode: &mut DaoMut<Inode>,
+    offline: bool,
+) -> Result<ChunkRelease> {
+    let mut release = ChunkRelease::default();
+    if offline == false {
+        inode.as_mut().rev = fastrand::u64(1..);
+        return Ok(release);
+    }
+
+    // The revision holds its own reference on the chunks (which is journaled
+    // until the chain reconnects as the chunk locks can not be reached)
+    let dio = inode.trans();
+    share_chunks(inode.dio(), inode.auth(), &inode.chunks).await?;
+    if inode.rev != 0 {
+        match dio.load::<Revision>(&PrimaryKey::from(inode.rev)).await {
+            Ok(mut revision) => {
+                for c in revision.chunks.iter() {
+                    release.add(c);
+                }
+                let mut revision = revision.as_mut();
+                revision.size = inode.size;
+                revision.chunks = inode.chunks.clone();
+                return Ok(release);
+            }
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {}
+            Err(err) => {
+                bail!(err);
+            }
+        }
+    }
+
+    let id = fastrand::u64(1..);
+    let revision = Revision {
+        id,
+        base: inode.rev,
+        base_at: inode.when_updated(),
+        host: host_name().to_string(),
+        size: inode.size,
+        chunks: inode.chunks.clone(),
+    };
+    let mut revs =
+        DaoVec::<Revision>::new_orphaned_mut(&dio, inode.key().clone(), REVISIONS_VEC_ID);
+    revs.push_with_key(revision, PrimaryKey::from(id))?;
+    inode.as_mut().rev = id;
+    Ok(release)
+}
+
+/// Deletes the revisions kept for an inode (along with their references on
+/// the chunks, which are added to `release`)
+pub(crate) async fn release_revisions(
+    dio: &Arc<DioMut>,
+    inode: &PrimaryKey,
+    release: &mut ChunkRelease,
+) -> Result<()> {
+    let revs = DaoVec::<Revision>::new_orphaned_mut(dio, inode.clone(), REVISIONS_VEC_ID)
+        .iter()
+        .await?
+        .collect::<Vec<_>>();
+    for r in revs {
+        for c in r.chunks.iter() {
+            release.add(c);
+        }
+        dio.delete(r.key()).await?;
+    }
+    Ok(())
+}
+
+/// Divergent contents of a file that are kept as a conflicted copy
+struct Fork {
+    host: String,
+    when: u64,
+    size: u64,
+    chunks: Vec<ChunkRef>,
+    /// Point in time that the contents can be read at if they are no longer
+    /// referenced by a revision of this mount
+    at: Option<ChainTimestamp>,
+}
+
+impl FileAccessor {
+    /// Checks the files that were edited while this mount was disconnected
+    /// for edits that other mounts made to them in the meantime
+    pub(crate) async fn reconnected(&self) -> Result<()> {
+        let offline = {
+            let mut lock = self.offline.lock().unwrap();
+            lock.drain().collect::<Vec<_>>()
+        };
+        if offline.is_empty() {
+            return Ok(());
+        }
+
+        // Wait for the events that other mounts wrote in the meantime
+        if let Err(err) = self.chain.sync().await {
+            warn!("failed to sync after reconnecting - {}", err);
+            self.offline.lock().unwrap().extend(offline);
+            *self.connected.lock_write() = false;
+            return Ok(());
+        }
+
+        for inode in offline {
+            match self.detect_conflicts(inode).await {
+                Ok(0) => {}
+                Ok(n) => info!("inode {} had {} conflicting edit(s)", inode, n),
+                Err(err) => warn!("failed to check inode {} for conflicts - {}", inode, err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the offline edits of a file with the edits that other mounts
+    /// made to it and keeps whichever of them lost as a conflicted copy next
+    /// to the file. The revisions of the offline edits are released afterwards
+    pub(crate) async fn detect_conflicts(&self, inode: u64) -> Result<usize> {
+        let key = PrimaryKey::from(inode);
+        let revs = DaoVec::<Revision>::new_orphaned(&self.dio, key.clone(), REVISIONS_VEC_ID)
+            .iter()
+            .await?
+            .filter(|r| r.host.as_str() == host_name())
+            .collect::<Vec<_>>();
+        if revs.is_empty() {
+            return Ok(0);
+        }
+
+        let dio = self.dio_mut_meta().await;
+        let mut release = ChunkRelease::default();
+        for r in revs.iter() {
+            for c in r.chunks.iter() {
+                release.add(c);
+            }
+            dio.delete(r.key()).await?;
+        }
+
+        let forks = match dio.load::<Inode>(&key).await {
+            Ok(dao) if dao.kind == FileKind::RegularFile && dao.orphaned == false => {
+                self.detect_conflicts_with(&dio, dao, &revs[..]).await?
+            }
+            Ok(_) | Err(LoadError(LoadErrorKind::NotFound(_), _)) => 0,
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        dio.commit().await?;
+        release.apply(&self.dio).await?;
+        Ok(forks)
+    }
+
+    async fn detect_conflicts_with(
+        &self,
+        dio: &Arc<DioMut>,
+        mut dao: DaoMut<Inode>,
+        revs: &[Dao<Revision>],
+    ) -> Result<usize> {
+        let inode = dao.key().as_u64();
+        let parent = match dao.dentry.parent {
+            Some(a) => a,
+            None => {
+                return Ok(0);
+            }
+        };
+        let ids = revs.iter().map(|r| r.id).collect::<FxHashSet<_>>();
+        let bases = revs.iter().map(|r| r.base).collect::<FxHashSet<_>>();
+        let base_at = revs.iter().map(|r| r.base_at).min().unwrap_or_default();
+
+        let mut forks = Vec::new();
+        if ids.contains(&dao.rev) == false {
+            // Another mount committed the file after the offline edits so
+            // they are the ones that are kept aside
+            if let Some(head) = revs.iter().max_by_key(|r| r.when_updated()) {
+                forks.push(Fork {
+                    host: head.host.clone(),
+                    when: head.when_updated(),
+                    size: head.size,
+                    chunks: head.chunks.clone(),
+                    at: None,
+                });
+            }
+        } else {
+            // The offline edits were committed last which hides any edits
+            // that other mounts made in the meantime, these are still in the
+            // history of the chain (content changes always get a new revision)
+            let theirs = self
+                .dio
+                .load_versions::<Inode>(dao.key())
+                .await?
+                .into_iter()
+                .filter(|v| v.when_updated() > base_at)
+                .filter(|v| ids.contains(&v.rev) == false && bases.contains(&v.rev) == false)
+                .last();
+            if let Some(theirs) = theirs {
+                forks.push(Fork {
+                    host: REMOTE_HOST.to_string(),
+                    when: theirs.when_updated(),
+                    size: theirs.size,
+                    chunks: theirs.chunks.clone(),
+                    at: Some(ChainTimestamp::from(theirs.when_updated())),
+                });
+            }
+        }
+        if forks.is_empty() {
+            return Ok(0);
+        }
+
+        let path = self.path_of(inode).await?;
+        let mut parent = dio.load::<Inode>(&PrimaryKey::from(parent)).await?;
+        let mut names = parent
+            .children
+            .iter()
+            .await?
+            .filter(|c| c.orphaned == false)
+            .map(|c| c.dentry.name.clone())
+            .collect::<FxHashSet<_>>();
+        let mut copies = match dao.xattr.get(&CONFLICTS_XATTR.to_string()).await? {
+            Some(a) => a.deref().clone(),
+            None => String::new(),
+        };
+        let mut conflicts =
+            DaoVec::<Conflict>::new_orphaned_mut(dio, PrimaryKey::from(1), CONFLICTS_VEC_ID);
+
+        for fork in forks.iter() {
+            let date = civil_date(fork.when);
+            let mut attempt = 0u32;
+            let name = loop {
+                let name = conflicted_name(
+                    dao.dentry.name.as_str(),
+                    fork.host.as_str(),
+                    date.as_str(),
+                    attempt,
+                );
+                if names.contains(&name) == false {
+                    break name;
+                }
+                attempt += 1;
+            };
+            names.insert(name.clone());
+
+            let mut copy = Inode::new(
+                name.clone(),
+                dao.dentry.mode,
+                dao.dentry.uid,
+                dao.dentry.gid,
+                FileKind::RegularFile,
+            );
+            copy.dentry.parent = Some(parent.key().as_u64());
+            copy.dentry.xattr = dao.dentry.xattr.clone();
+            copy.size = fork.size;
+            copy.chunks = fork.chunks.clone();
+            copy.rev = fastrand::u64(1..);
+            let mut copy = parent.as_mut().children.push(copy)?;
+            copy_auth(dao.auth(), copy.auth_mut())?;
+            self.inodes_changed(1);
+
+            // Chunks that were only referenced by hidden edits may be gone
+            let from = match fork.at {
+                Some(at) => self.chain.dio_at(&self.session, at).await,
+                None => Arc::clone(&self.dio),
+            };
+            share_chunks_from(&self.dio, &from, dao.auth(), &copy.chunks).await?;
+
+            conflicts.push(Conflict {
+                inode,
+                path: path.clone(),
+                copy: copy.key().as_u64(),
+                name: name.clone(),
+                host: fork.host.clone(),
+            })?;
+
+            if copies.is_empty() == false {
+                copies.push('\n');
+            }
+            copies.push_str(name.as_str());
+            warn!("conflicting edit of {} kept as '{}'", path, name);
+        }
+
+        dao.as_mut()
+            .xattr
+            .insert(CONFLICTS_XATTR.to_string(), copies)
+            .await?;
+        Ok(forks.len())
+    }
+
+    /// Returns the path of an inode relative to the root of the file system
+    pub(crate) async fn path_of(&self, inode: u64) -> Result<String> {
+        let mut parts = Vec::new();
+        let mut next = Some(inode);
+        while let Some(inode) = next {
+            if inode == 1 {
+                break;
+            }
+            let dao = self.dio.load::<Inode>(&PrimaryKey::from(inode)).await?;
+            parts.push(dao.dentry.name.clone());
+            next = dao.dentry.parent;
+        }
+        parts.reverse();
+        Ok(format!("/{}", parts.join("/")))
+    }
+
+    /// Lists the conflicted copies that still exist (deleting a copy is how
+    /// a conflict is resolved)
+    pub async fn conflicts(&self) -> Result<Vec<ConflictInfo>> {
+        let conflicts =
+            DaoVec::<Conflict>::new_orphaned(&self.dio, PrimaryKey::from(1), CONFLICTS_VEC_ID);
+
+        let mut ret = Vec::new();
+        for conflict in conflicts.iter().await? {
+            let exists = match self
+                .dio
+                .load::<Inode>(&PrimaryKey::from(conflict.copy))
+                .await
+            {
+                Ok(a) => a.orphaned == false,
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => false,
+                Err(err) => {
+                    bail!(err);
+                }
+            };
+            if exists {
+                ret.push(ConflictInfo {
+                    path: conflict.path.clone(),
+                    name: conflict.name.clone(),
+                    host: conflict.host.clone(),
+                    detected: conflict.when_created(),
+                });
+            }
+        }
+        ret.sort_by_key(|c| c.detected);
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkState;
+    use crate::codes::*;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(951_782_400_000), "2000-02-29");
+        assert_eq!(civil_date(1_735_603_200_000 + 86_399_999), "2024-12-31");
+        assert_eq!(civil_date(1_735_603_200_000 + 86_400_000), "2025-01-01");
+    }
+
+    #[test]
+    fn test_conflicted_name() {
+        assert_eq!(
+            conflicted_name("report.txt", "laptop", "2024-12-31", 0),
+            "report (conflicted copy laptop 2024-12-31).txt"
+        );
+        assert_eq!(
+            conflicted_name("report.txt", "laptop", "2024-12-31", 1),
+            "report (conflicted copy laptop 2024-12-31 2).txt"
+        );
+        assert_eq!(
+            conflicted_name("archive.tar.gz", "laptop", "2024-12-31", 0),
+            "archive.tar (conflicted copy laptop 2024-12-31).gz"
+        );
+        assert_eq!(
+            conflicted_name(".bashrc", "laptop", "2024-12-31", 0),
+            ".bashrc (conflicted copy laptop 2024-12-31)"
+        );
+        assert_eq!(
+            conflicted_name("Makefile", "laptop", "2024-12-31", 0),
+            "Makefile (conflicted copy laptop 2024-12-31)"
+        );
+    }
+
+    async fn test_accessor() -> FileAccessor {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_conflicts_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let accessor = FileAccessor::new(
+            chain,
+            None,
+            session.into(),
+            TransactionScope::Full,
+            TransactionScope::Full,
+            false,
+            false,
+        )
+        .await;
+        accessor.init(&accessor.session_context()).await.unwrap();
+        accessor
+    }
+
+    async fn pause() {
+        ate::engine::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    async fn open(accessor: &FileAccessor, key: &PrimaryKey) -> DaoMut<Inode> {
+        let dio = accessor.dio.trans(TransactionScope::Full).await;
+        dio.load::<Inode>(key).await.unwrap()
+    }
+
+    async fn edit(inode: &mut DaoMut<Inode>, data: &[u8], offline: bool) {
+        inode.as_mut().size = data.len() as u64;
+        let mut state = ChunkState::new();
+        state.write(inode, 0, data).await.unwrap();
+        state.flush(inode).await.unwrap();
+        let release = record_revision(inode, offline).await.unwrap();
+        inode.trans().commit().await.unwrap();
+        release.apply(inode.dio()).await.unwrap();
+    }
+
+    async fn contents(accessor: &FileAccessor, path: &str) -> Vec<u8> {
+        let ctx = accessor.session_context();
+        let attr = accessor.search(&ctx, path).await.unwrap().unwrap();
+        let dao = accessor
+            .dio
+            .load::<Inode>(&PrimaryKey::from(attr.ino))
+            .await
+            .unwrap();
+        ChunkState::new()
+            .read(&accessor.dio, &dao.chunks[..], 0, dao.size)
+            .await
+            .unwrap()
+    }
+
+    async fn revisions(accessor: &FileAccessor, key: &PrimaryKey) -> usize {
+        DaoVec::<Revision>::new_orphaned(&accessor.dio, key.clone(), REVISIONS_VEC_ID)
+            .iter()
+            .await
+            .unwrap()
+            .count()
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_offline_edit_that_lost_is_kept_as_a_copy() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let ino = accessor.touch(&ctx, "/notes.txt").await.unwrap().ino;
+        let key = PrimaryKey::from(ino);
+        edit(&mut open(&accessor, &key).await, b"original", false).await;
+        pause().await;
+
+        // Both mounts start from the same version and the online edit comes last
+        let mut ours = open(&accessor, &key).await;
+        let mut theirs = open(&accessor, &key).await;
+        edit(&mut ours, b"offline edit", true).await;
+        assert_eq!(revisions(&accessor, &key).await, 1);
+        edit(&mut ours, b"second offline edit", true).await;
+        assert_eq!(revisions(&accessor, &key).await, 1);
+        pause().await;
+        edit(&mut theirs, b"online edit", false).await;
+        assert_eq!(revisions(&accessor, &key).await, 1);
+
+        assert_eq!(accessor.detect_conflicts(ino).await.unwrap(), 1);
+        assert_eq!(contents(&accessor, "/notes.txt").await, b"online edit");
+        let conflicts = accessor.conflicts().await.unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "/notes.txt");
+        assert_eq!(conflicts[0].host, host_name());
+        assert!(conflicts[0].name.starts_with("notes (conflicted copy "));
+        assert!(conflicts[0].name.ends_with(").txt"));
+        let copy = format!("/{}", conflicts[0].name);
+        assert_eq!(
+            contents(&accessor, copy.as_str()).await,
+            b"second offline edit"
+        );
+
+        // The revision is released once the file has been checked
+        assert_eq!(revisions(&accessor, &key).await, 0);
+        assert_eq!(accessor.detect_conflicts(ino).await.unwrap(), 0);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_online_edit_hidden_by_offline_edit_is_kept_as_a_copy() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let ino = accessor.touch(&ctx, "/plan.txt").await.unwrap().ino;
+        let key = PrimaryKey::from(ino);
+        edit(&mut open(&accessor, &key).await, b"original", false).await;
+        pause().await;
+
+        // Both mounts start from the same version and the offline edit comes last
+        let mut ours = open(&accessor, &key).await;
+        let mut theirs = open(&accessor, &key).await;
+        edit(&mut theirs, b"online edit", false).await;
+        pause().await;
+        edit(&mut ours, b"offline edit", true).await;
+
+        assert_eq!(accessor.detect_conflicts(ino).await.unwrap(), 1);
+        assert_eq!(contents(&accessor, "/plan.txt").await, b"offline edit");
+        let conflicts = accessor.conflicts().await.unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].host, REMOTE_HOST);
+        let copy = format!("/{}", conflicts[0].name);
+        assert_eq!(contents(&accessor, copy.as_str()).await, b"online edit");
+        assert_eq!(revisions(&accessor, &key).await, 0);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_offline_edit_without_other_edits_is_not_a_conflict() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let ino = accessor.touch(&ctx, "/solo.txt").await.unwrap().ino;
+        let key = PrimaryKey::from(ino);
+        edit(&mut open(&accessor, &key).await, b"original", false).await;
+        pause().await;
+
+        edit(&mut open(&accessor, &key).await, b"offline edit", true).await;
+        assert_eq!(accessor.detect_conflicts(ino).await.unwrap(), 0);
+        assert_eq!(contents(&accessor, "/solo.txt").await, b"offline edit");
+        assert!(accessor.conflicts().await.unwrap().is_empty());
+        assert_eq!(revisions(&accessor, &key).await, 0);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_revisions_are_released_with_the_file() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let ino = accessor.touch(&ctx, "/gone.txt").await.unwrap().ino;
+        let key = PrimaryKey::from(ino);
+
+        let mut inode = open(&accessor, &key).await;
+        edit(&mut inode, b"offline edit", true).await;
+        let chunk = inode.chunks[0].key;
+        assert_eq!(revisions(&accessor, &key).await, 1);
+
+        accessor.unlink(&ctx, 1, "gone.txt").await.unwrap();
+        assert_eq!(revisions(&accessor, &key).await, 0);
+        assert!(accessor.dio.exists(&chunk).await == false);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_offline_edits_are_written_on_a_disconnected_mesh() {
+        ate::utils::bootstrap_test_env();
+        let mut cfg_ate = ConfAte::default();
+        cfg_ate.recovery_mode = RecoveryMode::Async;
+
+        // The chain is hosted by a local mesh server that can be taken away
+        let cert = PrivateEncryptKey::generate(KeySize::Bit192);
+        ate::mesh::add_global_certificate(&cert.hash());
+        let port = 7000 + fastrand::u16(..1000);
+        let url = url::Url::parse(format!("ws://localhost:{}/", port).as_str()).unwrap();
+        let mut cfg_mesh = ConfMesh::solo_from_url(
+            &cfg_ate,
+            &url,
+            &IpAddr::from_str("::1").unwrap(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        cfg_mesh.wire_protocol = StreamProtocol::WebSocket;
+        cfg_mesh.listen_certificate = Some(cert);
+        let server = create_server(&cfg_mesh).await.unwrap();
+        server
+            .add_route(all_ethereal_distributed().await, &cfg_ate)
+            .await
+            .unwrap();
+
+        let client = create_temporal_client(&cfg_ate, &cfg_mesh);
+        let key = ChainKey::from(format!("test_conflicts_{}", fastrand::u64(..)));
+        let chain = client.open(&url, &key).await.unwrap();
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let accessor = FileAccessor::new(
+            chain,
+            None,
+            session.into(),
+            TransactionScope::Full,
+            TransactionScope::Full,
+            false,
+            false,
+        )
+        .await;
+        let ctx = accessor.session_context();
+        accessor.init(&ctx).await.unwrap();
+        let ino = accessor.touch(&ctx, "/notes.txt").await.unwrap().ino;
+        let key = PrimaryKey::from(ino);
+        edit(&mut open(&accessor, &key).await, b"original", false).await;
+
+        server.shutdown().await;
+        drop(server);
+        let timer = Instant::now();
+        while accessor.chain.is_connected().await {
+            assert!(timer.elapsed() < Duration::from_secs(10));
+            pause().await;
+        }
+
+        // The edit is committed with a revision (rather than waiting for the
+        // locks of its chunks) and checked for conflicts once reconnected
+        let timer = Instant::now();
+        let handle = accessor.open(&ctx, ino, O_RDWR as u32).await.unwrap();
+        accessor
+            .write(&ctx, ino, handle.fh, 0, b"offline edit", 0)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, ino, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        assert!(timer.elapsed() < Duration::from_secs(10));
+        assert_eq!(revisions(&accessor, &key).await, 1);
+        assert!(accessor.offline.lock().unwrap().contains(&ino));
+        assert_eq!(contents(&accessor, "/notes.txt").await, b"offline edit");
+    }
+}
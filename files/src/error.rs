@@ -55,6 +55,18 @@ error_chain! {
             description("the function is not implemented"),
             display("the function is not implemented")
         }
+        WouldBlock {
+            description("the entry is locked by someone else"),
+            display("the entry is locked by someone else")
+        }
+        QuotaExceeded {
+            description("the quota has been exceeded"),
+            display("the quota has been exceeded")
+        }
+        MissingChunk(key: String) {
+            description("a chunk of the file no longer exists"),
+            display("chunk {} of the file no longer exists", key)
+        }
     }
 }
 
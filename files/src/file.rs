@@ -1,8 +1,13 @@
 #![allow(dead_code)]
 use super::api::FileKind;
+use super::chunk::can_share;
+use super::chunk::ChunkRelease;
+use super::chunk::ChunkState;
+use super::conflict::record_revision;
 use super::model::*;
 use crate::api::FileApi;
 use async_trait::async_trait;
+use ate::meta::MetaAuthorization;
 use ate::prelude::*;
 use bytes::Bytes;
 use error_chain::bail;
@@ -28,6 +33,7 @@ pub struct RegularFile {
     pub gid: u32,
     pub mode: u32,
     pub name: String,
+    pub nlink: u32,
     pub size: SeqLock<u64>,
     pub state: Mutex<FileState>,
 }
@@ -40,6 +46,7 @@ impl RegularFile {
             mode: inode.dentry.mode,
             name: inode.dentry.name.clone(),
             ino: inode.key().as_u64(),
+            nlink: inode.nlink(),
             size: SeqLock::new(inode.size),
             created,
             updated,
@@ -47,6 +54,7 @@ impl RegularFile {
                 inode,
                 bundles: Box::new(array_init::array_init(|_| None)),
                 pages: Box::new(array_init::array_init(|_| None)),
+                chunks: ChunkState::new(),
             }),
         }
     }
@@ -58,6 +66,7 @@ impl RegularFile {
             mode: inode.dentry.mode,
             name: inode.dentry.name.clone(),
             ino: inode.key().as_u64(),
+            nlink: inode.nlink(),
             size: SeqLock::new(inode.size),
             created,
             updated,
@@ -66,6 +75,7 @@ impl RegularFile {
                 dirty: false,
                 bundles: Box::new(array_init::array_init(|_| None)),
                 pages: Box::new(array_init::array_init(|_| None)),
+                chunks: ChunkState::new(),
             }),
         }
     }
@@ -76,12 +86,14 @@ pub enum FileState {
         inode: Dao<Inode>,
         bundles: Box<[Option<Dao<PageBundle>>; CACHED_BUNDLES]>,
         pages: Box<[Option<Dao<Page>>; CACHED_PAGES]>,
+        chunks: ChunkState,
     },
     Mutable {
         dirty: bool,
         inode: DaoMut<Inode>,
         bundles: Box<[Option<DaoMut<PageBundle>>; CACHED_BUNDLES]>,
         pages: Box<[Option<DaoMutGuardOwned<Page>>; CACHED_PAGES]>,
+        chunks: ChunkState,
     },
 }
 
@@ -92,12 +104,14 @@ impl FileState {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => inode.deref(),
             FileState::Mutable {
                 dirty: _,
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => inode.deref(),
         }
     }
@@ -113,11 +127,13 @@ impl FileState {
                 inode,
                 bundles,
                 pages,
+                chunks: _,
             } => (dirty, inode, bundles, pages),
             FileState::Immutable {
                 inode: _,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 bail!(FileSystemErrorKind::NoAccess);
             }
@@ -134,6 +150,29 @@ impl FileState {
         mut size: u64,
         ret: &mut Cursor<&mut Vec<u8>>,
     ) -> Result<()> {
+        // Files without any bundles are stored as content-defined chunks
+        if self.__inode().bundles.is_empty() {
+            let (dio, inode, chunks) = match self {
+                FileState::Mutable {
+                    dirty: _,
+                    inode,
+                    bundles: _,
+                    pages: _,
+                    chunks,
+                } => (inode.dio().clone(), inode.deref(), chunks),
+                FileState::Immutable {
+                    inode,
+                    bundles: _,
+                    pages: _,
+                    chunks,
+                } => (inode.dio().clone(), inode.deref(), chunks),
+            };
+            let buf = chunks.read(&dio, &inode.chunks, offset, size).await?;
+            let mut reader = Cursor::new(&buf[..]);
+            tokio::io::copy(&mut reader, ret).await?;
+            return Ok(());
+        }
+
         // Compute the strides
         let stride_page = super::model::PAGE_SIZE as u64;
         let stride_bundle = super::model::PAGES_PER_BUNDLE as u64 * stride_page;
@@ -163,6 +202,7 @@ impl FileState {
                 inode,
                 bundles,
                 pages,
+                chunks: _,
             } => {
                 // Use the cache-line to load the bundle
                 let dio = inode.trans();
@@ -229,6 +269,7 @@ impl FileState {
                 inode,
                 bundles,
                 pages,
+                chunks: _,
             } => {
                 // Use the cache-line to load the bundle
                 let dio = inode.dio();
@@ -312,17 +353,19 @@ impl FileState {
     }
 
     pub async fn write_page(&mut self, mut offset: u64, reader: &mut Cursor<&[u8]>) -> Result<()> {
-        let (dirty, inode, bundles, pages) = match self {
+        let (dirty, inode, bundles, pages, chunks) = match self {
             FileState::Mutable {
                 dirty,
                 inode,
                 bundles,
                 pages,
-            } => (dirty, inode, bundles, pages),
+                chunks,
+            } => (dirty, inode, bundles, pages, chunks),
             FileState::Immutable {
                 inode: _,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 bail!(FileSystemErrorKind::NoAccess);
             }
@@ -330,6 +373,13 @@ impl FileState {
 
         *dirty = true;
 
+        // Files without any bundles are stored as content-defined chunks
+        if inode.bundles.is_empty() {
+            let data = &reader.get_ref()[reader.position() as usize..];
+            chunks.write(inode, offset, data).await?;
+            return Ok(());
+        }
+
         // Compute the strides
         let dio = inode.trans();
         let inode_key = inode.key().clone();
@@ -456,33 +506,112 @@ impl FileState {
     }
 
     pub async fn commit(&mut self) -> Result<()> {
-        let (dirty, inode, _, pages) = match self {
+        let (dirty, inode, _, pages, chunks) = match self {
             FileState::Mutable {
                 dirty,
                 inode,
                 bundles,
                 pages,
-            } => (dirty, inode, bundles, pages),
+                chunks,
+            } => (dirty, inode, bundles, pages, chunks),
             FileState::Immutable {
                 inode: _,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 return Ok(());
             }
         };
 
         if *dirty {
+            let mut release = ChunkRelease::default();
+            if inode.bundles.is_empty() {
+                chunks.flush(inode).await?;
+                let offline = inode.dio().chain().is_connected().await == false;
+                release = record_revision(inode, offline).await?;
+            }
             for page in pages.iter_mut() {
                 page.take();
             }
             let dio = inode.trans();
             dio.commit().await?;
+            release.apply(inode.dio()).await?;
             *dirty = false;
         }
         Ok(())
     }
 
+    /// Returns the chunks that lie entirely within a range of the file along
+    /// with the authorization of the file (files stored in bundles have none)
+    pub async fn shareable_chunks(
+        &mut self,
+        start: u64,
+        end: u64,
+    ) -> Result<Option<(MetaAuthorization, Vec<ChunkRef>)>> {
+        self.commit().await?;
+
+        let auth = match self {
+            FileState::Mutable {
+                dirty: _,
+                inode,
+                bundles: _,
+                pages: _,
+                chunks: _,
+            } => inode.auth().clone(),
+            FileState::Immutable {
+                inode,
+                bundles: _,
+                pages: _,
+                chunks: _,
+            } => inode.auth().clone(),
+        };
+
+        let inode = self.__inode();
+        if inode.bundles.is_empty() == false {
+            return Ok(None);
+        }
+        let refs = inode
+            .chunks
+            .iter()
+            .filter(|c| c.offset >= start && c.end() <= end)
+            .cloned()
+            .collect();
+        Ok(Some((auth, refs)))
+    }
+
+    /// Places chunks from another file into this one, returns false if the
+    /// chunks can not be shared and thus the data must be copied instead
+    pub async fn share_chunks(
+        &mut self,
+        auth: &MetaAuthorization,
+        refs: Vec<ChunkRef>,
+    ) -> Result<bool> {
+        let (inode, chunks) = match self {
+            FileState::Mutable {
+                dirty: _,
+                inode,
+                bundles: _,
+                pages: _,
+                chunks,
+            } => (inode, chunks),
+            FileState::Immutable {
+                inode: _,
+                bundles: _,
+                pages: _,
+                chunks: _,
+            } => {
+                bail!(FileSystemErrorKind::NoAccess);
+            }
+        };
+
+        if inode.bundles.is_empty() == false || can_share(inode.auth(), auth) == false {
+            return Ok(false);
+        }
+        chunks.share(inode, refs).await?;
+        Ok(true)
+    }
+
     pub async fn set_xattr(&mut self, name: &str, value: &str) -> Result<()> {
         match self {
             FileState::Mutable {
@@ -490,6 +619,7 @@ impl FileState {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 inode
                     .as_mut()
@@ -501,6 +631,7 @@ impl FileState {
                 inode: _,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 bail!(FileSystemErrorKind::NoAccess);
             }
@@ -516,11 +647,13 @@ impl FileState {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => inode.as_mut().xattr.delete(&name).await?,
             FileState::Immutable {
                 inode: _,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 bail!(FileSystemErrorKind::NoAccess);
             }
@@ -536,11 +669,13 @@ impl FileState {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => inode.xattr.get(&name).await?.map(|a| a.deref().clone()),
             FileState::Immutable {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => inode.xattr.get(&name).await?.map(|a| a.deref().clone()),
         };
         Ok(ret)
@@ -554,6 +689,7 @@ impl FileState {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 for (k, v) in inode.xattr.iter().await? {
                     ret.insert(k, v.deref().clone());
@@ -563,6 +699,7 @@ impl FileState {
                 inode,
                 bundles: _,
                 pages: _,
+                chunks: _,
             } => {
                 for (k, v) in inode.xattr.iter().await? {
                     ret.insert(k, v.deref().clone());
@@ -573,6 +710,92 @@ impl FileState {
     }
 }
 
+impl RegularFile {
+    /// Copies a range of another file into this one - chunks that lie entirely
+    /// within the range are shared with the source rather than copied
+    pub async fn copy_from(
+        &self,
+        src: &RegularFile,
+        off_in: u64,
+        off_out: u64,
+        len: u64,
+    ) -> Result<u64> {
+        let src_size = src.size();
+        if off_in >= src_size {
+            return Ok(0);
+        }
+        let len = len.min(src_size - off_in);
+        let end = off_in + len;
+
+        // Find the chunks that can be shared
+        let shared = match self.ino == src.ino {
+            true => None,
+            false => {
+                let mut state = src.state.lock().await;
+                state.shareable_chunks(off_in, end).await?
+            }
+        };
+        let (shared_start, shared_end) = match shared {
+            Some((auth, refs)) if refs.len() > 0 => {
+                let shared_start = refs.first().unwrap().offset;
+                let shared_end = refs.last().unwrap().end();
+                let refs = refs
+                    .into_iter()
+                    .map(|c| ChunkRef {
+                        offset: c.offset - off_in + off_out,
+                        size: c.size,
+                        key: c.key,
+                    })
+                    .collect();
+
+                let mut state = self.state.lock().await;
+                if state.share_chunks(&auth, refs).await? {
+                    *self.size.lock_write() = state.get_size()?;
+                    (shared_start, shared_end)
+                } else {
+                    (end, end)
+                }
+            }
+            _ => (end, end),
+        };
+
+        // Whatever is left over is copied
+        self.copy_bytes(src, off_in, off_out, shared_start - off_in)
+            .await?;
+        self.copy_bytes(
+            src,
+            shared_end,
+            shared_end - off_in + off_out,
+            end - shared_end,
+        )
+        .await?;
+        Ok(len)
+    }
+
+    async fn copy_bytes(
+        &self,
+        src: &RegularFile,
+        mut off_in: u64,
+        mut off_out: u64,
+        mut len: u64,
+    ) -> Result<()> {
+        let stride = super::model::PAGE_SIZE as u64;
+        while len > 0 {
+            let sub_len = len.min(stride);
+            let data = src.read(off_in, sub_len).await?;
+            if data.len() <= 0 {
+                break;
+            }
+            self.write(off_out, &data[..]).await?;
+
+            len = len - data.len() as u64;
+            off_in = off_in + data.len() as u64;
+            off_out = off_out + data.len() as u64;
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl FileApi for RegularFile {
     fn kind(&self) -> FileKind {
@@ -595,6 +818,10 @@ impl FileApi for RegularFile {
         self.size.read()
     }
 
+    fn nlink(&self) -> u32 {
+        self.nlink
+    }
+
     fn mode(&self) -> u32 {
         self.mode
     }
@@ -39,10 +39,16 @@ where
 
 impl OpenHandle {
     pub fn add_child(&mut self, spec: &FileSpec, uid: u32, gid: u32) {
+        self.add_child_named(spec.name(), spec, uid, gid);
+    }
+
+    /// Adds a child under a different name to the inode it refers to (used
+    /// by hard links)
+    pub fn add_child_named(&mut self, name: String, spec: &FileSpec, uid: u32, gid: u32) {
         self.children.push(DirectoryEntry {
             inode: spec.ino(),
             kind: spec.kind(),
-            name: spec.name(),
+            name,
             attr: FileAttr::new(spec, uid, gid),
             uid,
             gid,
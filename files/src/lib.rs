@@ -1,13 +1,20 @@
 pub mod accessor;
 pub mod api;
 pub mod attr;
+pub mod chunk;
 pub mod codes;
+pub mod conflict;
 pub mod dir;
 pub mod error;
 pub mod file;
 pub mod fixed;
 pub mod handle;
+pub mod lock;
 pub mod model;
 pub mod prelude;
+pub mod quota;
+pub mod stats;
 pub mod symlink;
+pub mod watch;
 pub mod repo;
+pub mod snapshot;
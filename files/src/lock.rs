@@ -0,0 +1,277 @@
+use ate::prelude::*;
+use fxhash::FxHashMap;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Shared (read) advisory lock
+pub const F_RDLCK: u32 = 0;
+/// Exclusive (write) advisory lock
+pub const F_WRLCK: u32 = 1;
+/// Removes an advisory lock
+pub const F_UNLCK: u32 = 2;
+
+/// Byte range advisory lock held by an owner on this mount (the range is
+/// inclusive and whole-file locks cover `0..=u64::MAX`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileLock {
+    pub owner: u64,
+    pub start: u64,
+    pub end: u64,
+    pub typ: u32,
+    pub pid: u32,
+}
+
+impl FileLock {
+    pub fn unlocked(start: u64, end: u64) -> FileLock {
+        FileLock {
+            owner: 0,
+            start,
+            end,
+            typ: F_UNLCK,
+            pid: 0,
+        }
+    }
+
+    fn overlaps(&self, other: &FileLock) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    fn conflicts(&self, other: &FileLock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other)
+            && (self.typ == F_WRLCK || other.typ == F_WRLCK)
+    }
+}
+
+/// Number of mesh locks that readers spread over, which caps how many
+/// machines can hold shared locks on the same file at the same time
+pub const MESH_READ_SLOTS: u32 = 8;
+
+/// Mesh locks that this mount holds for an inode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshHold {
+    /// One of the read slots (other machines may hold the other slots)
+    Shared(u32),
+    /// The write gate and every read slot
+    Exclusive,
+}
+
+/// Advisory locks held by this mount grouped by inode.
+///
+/// Byte ranges are arbitrated locally while other machines are kept out by
+/// mesh locks that are held for as long as any lock remains on the inode,
+/// hence the servers release them for us if this mount disconnects. The
+/// mesh only offers exclusive locks so shared locks are built from them -
+/// a reader holds any one of the `MESH_READ_SLOTS` slots of the inode while
+/// a writer holds the write gate and all of the slots.
+///
+/// Both `fcntl` (POSIX) and `flock` locks end up here - the kernel passes
+/// `flock` locks on as whole-file locks owned by the open file.
+#[derive(Debug, Default)]
+pub struct LockTable {
+    files: FxHashMap<u64, Vec<FileLock>>,
+    mesh: FxHashMap<u64, MeshHold>,
+}
+
+impl LockTable {
+    /// Key of the mesh lock that writers take before the read slots of an
+    /// inode
+    pub fn mesh_key(key: &PrimaryKey) -> PrimaryKey {
+        PrimaryKey::from(AteHash::from_bytes_twice(
+            b"advisory-lock",
+            &key.as_u64().to_be_bytes(),
+        ))
+    }
+
+    /// Key of one of the mesh locks that readers of an inode spread over
+    pub fn mesh_slot_key(key: &PrimaryKey, slot: u32) -> PrimaryKey {
+        let mut data = key.as_u64().to_be_bytes().to_vec();
+        data.extend_from_slice(&slot.to_be_bytes());
+        PrimaryKey::from(AteHash::from_bytes_twice(b"advisory-lock-read", &data))
+    }
+
+    /// Returns true if this mount holds any locks on the inode
+    pub fn is_held(&self, inode: u64) -> bool {
+        self.files.contains_key(&inode)
+    }
+
+    /// Returns the mesh locks this mount holds for the inode
+    pub fn mesh_hold(&self, inode: u64) -> Option<MeshHold> {
+        self.mesh.get(&inode).map(|a| *a)
+    }
+
+    pub fn set_mesh_hold(&mut self, inode: u64, hold: Option<MeshHold>) {
+        match hold {
+            Some(hold) => self.mesh.insert(inode, hold),
+            None => self.mesh.remove(&inode),
+        };
+    }
+
+    /// Returns the kind of mesh lock (`F_RDLCK` or `F_WRLCK`) that the locks
+    /// held on the inode need, or `None` once there are none left
+    pub fn mesh_needed(&self, inode: u64) -> Option<u32> {
+        let locks = self.files.get(&inode)?;
+        match locks.iter().any(|a| a.typ == F_WRLCK) {
+            true => Some(F_WRLCK),
+            false => Some(F_RDLCK),
+        }
+    }
+
+    /// Returns the first local lock that would block the supplied lock
+    pub fn conflict(&self, inode: u64, lock: &FileLock) -> Option<FileLock> {
+        self.files
+            .get(&inode)
+            .and_then(|locks| locks.iter().filter(|a| a.conflicts(lock)).next())
+            .map(|a| a.clone())
+    }
+
+    /// Applies (or removes for `F_UNLCK`) a lock of an owner, splitting any
+    /// of its existing locks that partially overlap, and returns true if the
+    /// inode no longer has any locks on it
+    pub fn apply(&mut self, inode: u64, lock: FileLock) -> bool {
+        let existing = self.files.remove(&inode).unwrap_or_default();
+
+        let mut locks = Vec::with_capacity(existing.len() + 1);
+        for a in existing {
+            if a.owner != lock.owner || a.overlaps(&lock) == false {
+                locks.push(a);
+                continue;
+            }
+            if a.start < lock.start {
+                locks.push(FileLock {
+                    end: lock.start - 1,
+                    ..a
+                });
+            }
+            if a.end > lock.end {
+                locks.push(FileLock {
+                    start: lock.end + 1,
+                    ..a
+                });
+            }
+        }
+        if lock.typ != F_UNLCK {
+            locks.push(lock);
+        }
+
+        if locks.is_empty() {
+            return true;
+        }
+        self.files.insert(inode, locks);
+        false
+    }
+
+    /// Drops every lock an owner holds on an inode and returns true if the
+    /// inode no longer has any locks on it
+    pub fn release(&mut self, inode: u64, owner: u64) -> bool {
+        match self.files.get_mut(&inode) {
+            Some(locks) => {
+                locks.retain(|a| a.owner != owner);
+                if locks.is_empty() {
+                    self.files.remove(&inode);
+                    return true;
+                }
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(owner: u64, start: u64, end: u64, typ: u32) -> FileLock {
+        FileLock {
+            owner,
+            start,
+            end,
+            typ,
+            pid: 0,
+        }
+    }
+
+    #[test]
+    fn test_shared_locks_do_not_conflict() {
+        let mut table = LockTable::default();
+        assert_eq!(table.apply(1, lock(1, 0, 99, F_RDLCK)), false);
+        assert_eq!(table.conflict(1, &lock(2, 50, 150, F_RDLCK)), None);
+        assert_eq!(table.apply(1, lock(2, 50, 150, F_RDLCK)), false);
+        assert_eq!(table.mesh_needed(1), Some(F_RDLCK));
+
+        let blocker = table.conflict(1, &lock(3, 120, 130, F_WRLCK));
+        assert_eq!(blocker, Some(lock(2, 50, 150, F_RDLCK)));
+        assert_eq!(table.conflict(1, &lock(3, 151, 200, F_WRLCK)), None);
+    }
+
+    #[test]
+    fn test_owner_never_conflicts_with_itself() {
+        let mut table = LockTable::default();
+        table.apply(1, lock(1, 0, u64::MAX, F_WRLCK));
+        assert_eq!(table.conflict(1, &lock(1, 10, 20, F_WRLCK)), None);
+        assert!(table.conflict(1, &lock(2, 10, 20, F_RDLCK)).is_some());
+        assert_eq!(table.conflict(2, &lock(2, 10, 20, F_WRLCK)), None);
+    }
+
+    #[test]
+    fn test_unlock_splits_range() {
+        let mut table = LockTable::default();
+        table.apply(1, lock(1, 0, 99, F_WRLCK));
+        assert_eq!(table.apply(1, lock(1, 40, 59, F_UNLCK)), false);
+
+        assert!(table.conflict(1, &lock(2, 0, 39, F_RDLCK)).is_some());
+        assert_eq!(table.conflict(1, &lock(2, 40, 59, F_WRLCK)), None);
+        assert!(table.conflict(1, &lock(2, 60, 99, F_RDLCK)).is_some());
+
+        assert_eq!(table.apply(1, lock(1, 0, 39, F_UNLCK)), false);
+        assert_eq!(table.apply(1, lock(1, 60, 99, F_UNLCK)), true);
+        assert_eq!(table.is_held(1), false);
+    }
+
+    #[test]
+    fn test_downgrade_replaces_range() {
+        let mut table = LockTable::default();
+        table.apply(1, lock(1, 0, 99, F_WRLCK));
+        assert_eq!(table.mesh_needed(1), Some(F_WRLCK));
+
+        // Relocking the same range as shared downgrades it
+        table.apply(1, lock(1, 0, 99, F_RDLCK));
+        assert_eq!(table.mesh_needed(1), Some(F_RDLCK));
+        assert_eq!(table.conflict(1, &lock(2, 0, 99, F_RDLCK)), None);
+
+        // Part of it can be upgraded again
+        table.apply(1, lock(1, 10, 19, F_WRLCK));
+        assert_eq!(table.mesh_needed(1), Some(F_WRLCK));
+        assert!(table.conflict(1, &lock(2, 15, 15, F_RDLCK)).is_some());
+        assert_eq!(table.conflict(1, &lock(2, 20, 99, F_RDLCK)), None);
+    }
+
+    #[test]
+    fn test_release_drops_only_the_owner() {
+        let mut table = LockTable::default();
+        table.apply(1, lock(1, 0, 9, F_RDLCK));
+        table.apply(1, lock(2, 0, 9, F_RDLCK));
+        table.apply(2, lock(1, 0, 9, F_WRLCK));
+
+        assert_eq!(table.release(1, 1), false);
+        assert_eq!(table.release(2, 1), true);
+        assert_eq!(table.mesh_needed(2), None);
+        assert_eq!(table.release(1, 2), true);
+        assert_eq!(table.release(1, 2), false);
+    }
+
+    #[test]
+    fn test_mesh_keys_are_distinct() {
+        let key = PrimaryKey::from(7u64);
+        let gate = LockTable::mesh_key(&key);
+        let mut slots = (0..MESH_READ_SLOTS)
+            .map(|slot| LockTable::mesh_slot_key(&key, slot))
+            .collect::<Vec<_>>();
+        slots.sort();
+        slots.dedup();
+        assert_eq!(slots.len(), MESH_READ_SLOTS as usize);
+        assert!(slots.contains(&gate) == false);
+        assert!(slots.contains(&LockTable::mesh_slot_key(&PrimaryKey::from(8u64), 0)) == false);
+    }
+}
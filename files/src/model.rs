@@ -10,6 +10,12 @@ use serde::*;
 pub const PAGES_PER_BUNDLE: usize = 1024;
 pub const PAGE_SIZE: usize = 131072;
 pub const WEB_CONFIG_ID: u64 = 0xb709d79e5cf6dd64u64;
+pub const SNAPSHOTS_ID: u64 = 0x6c1e8f03a9d45b27u64;
+pub const REVISIONS_VEC_ID: u64 = 0x3f5a9c0e7b21d846u64;
+pub const CONFLICTS_VEC_ID: u64 = 0x91d4e6a2c7f03b58u64;
+pub const QUOTAS_ID: u64 = 0x2b8e74d1f60a9c35u64;
+pub const CHUNK_JOURNAL_VEC_ID: u64 = 0xd83a51c6e09f7b42u64;
+pub const QUOTA_DELTAS_VEC_ID: u64 = 0x5e0b93f7a41c2d68u64;
 
 /// Represents a block of data
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +29,129 @@ pub struct PageBundle {
     pub pages: Vec<Option<PrimaryKey>>,
 }
 
+/// Represents a content-defined chunk of file data that is addressed by the
+/// hash of its contents and shared by every file that contains it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Chunk {
+    pub hash: AteHash,
+    pub buf: Vec<u8>,
+    /// Number of file ranges that currently reference this chunk
+    pub refs: u64,
+}
+
+/// Reference from a file to the chunk that holds a range of its data
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef {
+    pub offset: u64,
+    pub size: u32,
+    pub key: PrimaryKey,
+}
+
+impl ChunkRef {
+    pub fn end(&self) -> u64 {
+        self.offset + self.size as u64
+    }
+}
+
+/// Reference count changes that a mount made to chunks while it was
+/// disconnected from the chain (the counts of shared chunks are only changed
+/// while holding their mesh locks) which are applied once it reconnects
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkJournal {
+    pub host: String,
+    pub deltas: Vec<(PrimaryKey, i64)>,
+}
+
+/// List of the snapshots that have been taken of the file system
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotIndex {
+    pub snapshots: DaoVec<Snapshot>,
+}
+
+/// Named point in time that the file system was frozen at - the snapshot
+/// holds a `ChainPin` that keeps the history of the chain needed to read the
+/// inode tree (and file data) as it was at the moment the pin was written
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    pub pin: PrimaryKey,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Contents of a file that a mount committed while it was disconnected from
+/// the chain. It holds a reference on its chunks until the mount reconnects
+/// and checks whether other mounts edited the file in the meantime
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Revision {
+    pub id: u64,
+    /// Revision of the file that the offline edits were made on top of
+    pub base: u64,
+    /// When the version of the inode that the edits were made on top of was
+    /// written (in milliseconds)
+    #[serde(default)]
+    pub base_at: u64,
+    pub host: String,
+    pub size: u64,
+    pub chunks: Vec<ChunkRef>,
+}
+
+/// Divergent edit of a file that was kept as a separate conflicted copy
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Conflict {
+    pub inode: u64,
+    pub path: String,
+    pub copy: u64,
+    pub name: String,
+    pub host: String,
+}
+
+/// Limits on the number of bytes and inodes (`None` means unlimited)
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaLimit {
+    pub bytes: Option<u64>,
+    pub inodes: Option<u64>,
+}
+
+/// Bytes and inodes that currently count towards a quota
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub bytes: u64,
+    pub inodes: u64,
+}
+
+/// Change in the usage of a quota - every charge is written as a record of
+/// its own (rather than by updating the usage in place) so that mounts which
+/// charge the same quota at the same time never lose each others changes
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct QuotaDelta {
+    pub bytes: i64,
+    pub inodes: i64,
+}
+
+/// Owner of the files that count towards a quota
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaOwner {
+    User(u32),
+    Group(u32),
+}
+
+/// Quota on everything that is owned by a user or group
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OwnerQuota {
+    pub owner: QuotaOwner,
+    pub limit: QuotaLimit,
+    pub usage: QuotaUsage,
+}
+
+/// Directories and owners that have quotas placed on them (so that writes
+/// to file systems without any quotas skip the accounting)
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct QuotaIndex {
+    pub dirs: Vec<u64>,
+    pub owners: Vec<QuotaOwner>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Dentry {
     pub parent: Option<u64>,
@@ -39,9 +168,34 @@ pub struct Inode {
     pub dentry: Dentry,
     pub size: u64,
     pub bundles: Vec<Option<PrimaryKey>>,
+    /// Content-defined chunks ordered by offset (files written before chunking
+    /// was introduced keep using the bundles above)
+    #[serde(default)]
+    pub chunks: Vec<ChunkRef>,
     pub children: DaoVec<Inode>,
     pub link: Option<String>,
     pub xattr: DaoMap<String, String>,
+    /// Number of hard link entries (besides the original entry) that refer
+    /// to this inode, use `nlink()` for the total number of names
+    #[serde(default)]
+    pub links: u32,
+    /// When set this entry is a hard link to the inode with this key
+    #[serde(default)]
+    pub hard_link: Option<u64>,
+    /// The original entry was unlinked but hard links still refer to it
+    #[serde(default)]
+    pub orphaned: bool,
+    /// Revision of the contents that this version of the inode holds (it
+    /// changes on every commit of the contents)
+    #[serde(default)]
+    pub rev: u64,
+    /// Limits placed on the subtree below this directory
+    #[serde(default)]
+    pub quota: Option<QuotaLimit>,
+    /// Usage of the subtree below this directory (only kept up to date while
+    /// it has a quota)
+    #[serde(default)]
+    pub usage: QuotaUsage,
 }
 
 impl Inode {
@@ -58,9 +212,24 @@ impl Inode {
             },
             size: 0,
             bundles: Vec::default(),
+            chunks: Vec::default(),
             children: DaoVec::new(),
             link: None,
             xattr: DaoMap::default(),
+            links: 0,
+            hard_link: None,
+            orphaned: false,
+            rev: 0,
+            quota: None,
+            usage: QuotaUsage::default(),
+        }
+    }
+
+    /// Number of names that currently refer to this inode
+    pub fn nlink(&self) -> u32 {
+        match self.orphaned {
+            true => self.links,
+            false => self.links + 1,
         }
     }
 
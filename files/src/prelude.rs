@@ -9,11 +9,21 @@ pub use crate::attr::SetAttr;
 
 pub use crate::accessor::FileAccessor;
 pub use crate::accessor::RequestContext;
+pub use crate::conflict::ConflictInfo;
 pub use crate::dir::Directory;
 pub use crate::file::FileState;
 pub use crate::file::RegularFile;
 pub use crate::fixed::FixedFile;
 pub use crate::handle::DirectoryEntry;
 pub use crate::handle::OpenHandle;
+pub use crate::lock::FileLock;
 pub use crate::model::*;
+pub use crate::quota::QuotaInfo;
+pub use crate::quota::QuotaTarget;
+pub use crate::snapshot::SnapshotChange;
+pub use crate::snapshot::SnapshotInfo;
+pub use crate::stats::FsStats;
 pub use crate::symlink::SymLink;
+pub use crate::watch::FileWatch;
+pub use crate::watch::WatchEvent;
+pub use crate::watch::WatchEventKind;
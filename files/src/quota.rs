@@ -0,0 +1,973 @@
+use ate::prelude::*;
+use error_chain::bail;
+use fxhash::FxHashMap;
+use std::sync::Arc;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::accessor::FileAccessor;
+use super::accessor::RequestContext;
+use super::api::FileKind;
+use super::chunk::wait_for_lock;
+use super::error::*;
+use super::model::*;
+
+/// Bytes that a growing file is charged for ahead of its writes
+const QUOTA_RESERVE: u64 = 4 * 1024 * 1024;
+
+/// Something that a quota can be placed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaTarget {
+    /// Everything below a directory (by inode)
+    Directory(u64),
+    /// Everything owned by a user or group
+    Owner(QuotaOwner),
+}
+
+/// Quota as it is listed
+#[derive(Debug, Clone)]
+pub struct QuotaInfo {
+    /// Path of the directory or the owner (e.g. `uid:1000`) of the quota
+    pub target: String,
+    pub limit: QuotaLimit,
+    pub usage: QuotaUsage,
+}
+
+impl QuotaLimit {
+    /// Returns true if the usage can grow by these amounts without going
+    /// over the limit (shrinking is always allowed)
+    pub fn allows(&self, usage: &QuotaUsage, bytes: i64, inodes: i64) -> bool {
+        let over = |limit: Option<u64>, used: u64, delta: i64| match limit {
+            Some(limit) if delta > 0 => used.saturating_add(delta as u64) > limit,
+            _ => false,
+        };
+        over(self.bytes, usage.bytes, bytes) == false
+            && over(self.inodes, usage.inodes, inodes) == false
+    }
+}
+
+impl QuotaUsage {
+    pub fn apply(&mut self, bytes: i64, inodes: i64) {
+        self.bytes = (self.bytes as i64).saturating_add(bytes).max(0) as u64;
+        self.inodes = (self.inodes as i64).saturating_add(inodes).max(0) as u64;
+    }
+}
+
+impl QuotaOwner {
+    /// The user and group quotas that an entry counts towards
+    pub fn of(uid: u32, gid: u32) -> [QuotaOwner; 2] {
+        [QuotaOwner::User(uid), QuotaOwner::Group(gid)]
+    }
+
+    /// Key of the record that holds the quota of this owner
+    pub fn key(&self) -> PrimaryKey {
+        let (kind, id) = match self {
+            QuotaOwner::User(a) => (b"quota-uid", a),
+            QuotaOwner::Group(a) => (b"quota-gid", a),
+        };
+        PrimaryKey::from(AteHash::from_bytes_twice(kind, &id.to_be_bytes()))
+    }
+}
+
+impl QuotaTarget {
+    /// Key of the record that holds the usage of this quota (its deltas are
+    /// stored below it)
+    pub fn key(&self) -> PrimaryKey {
+        match self {
+            QuotaTarget::Directory(a) => PrimaryKey::from(*a),
+            QuotaTarget::Owner(a) => a.key(),
+        }
+    }
+
+    /// Key of the mesh lock that is held while the deltas of this quota are
+    /// folded or reset
+    fn lock_key(&self) -> PrimaryKey {
+        PrimaryKey::from(AteHash::from_bytes_twice(
+            b"quota-usage",
+            &self.key().as_u64().to_be_bytes(),
+        ))
+    }
+}
+
+/// Adds the deltas that were charged to a quota to the usage that it was
+/// last counted at
+async fn quota_total(deltas: &DaoVec<QuotaDelta>, base: QuotaUsage) -> Result<QuotaUsage> {
+    let (mut bytes, mut inodes) = (0i64, 0i64);
+    for delta in deltas.iter().await? {
+        bytes = bytes.saturating_add(delta.bytes);
+        inodes = inodes.saturating_add(delta.inodes);
+    }
+    let mut ret = base;
+    ret.apply(bytes, inodes);
+    Ok(ret)
+}
+
+/// Replaces the deltas of a quota with a single delta that holds their sum
+async fn quota_fold_deltas(trans: &Arc<DioMut>, target: &QuotaTarget) -> Result<()> {
+    let mut deltas =
+        DaoVec::<QuotaDelta>::new_orphaned_mut(trans, target.key(), QUOTA_DELTAS_VEC_ID);
+    let existing = deltas.iter_mut().await?.collect::<Vec<_>>();
+    if existing.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut sum = QuotaDelta::default();
+    for delta in existing {
+        sum.bytes = sum.bytes.saturating_add(delta.bytes);
+        sum.inodes = sum.inodes.saturating_add(delta.inodes);
+        delta.delete()?;
+    }
+    if sum.bytes != 0 || sum.inodes != 0 {
+        deltas.push(sum)?;
+    }
+    trans.commit().await?;
+    Ok(())
+}
+
+/// Removes all the deltas of a quota (its usage has just been recounted)
+async fn quota_reset_deltas(dio: &Arc<DioMut>, target: &QuotaTarget) -> Result<()> {
+    let mut deltas = DaoVec::<QuotaDelta>::new_orphaned_mut(dio, target.key(), QUOTA_DELTAS_VEC_ID);
+    for delta in deltas.iter_mut().await? {
+        delta.delete()?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for QuotaOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            QuotaOwner::User(a) => write!(f, "uid:{}", a),
+            QuotaOwner::Group(a) => write!(f, "gid:{}", a),
+        }
+    }
+}
+
+impl FileAccessor {
+    /// Refreshes the list of quotas that writes are checked against
+    pub(crate) async fn load_quotas(&self) -> Result<()> {
+        let index = match self
+            .dio
+            .load::<QuotaIndex>(&PrimaryKey::from(QUOTAS_ID))
+            .await
+        {
+            Ok(a) => a.take(),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => QuotaIndex::default(),
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        *self.quotas.lock().unwrap() = index;
+        Ok(())
+    }
+
+    async fn quota_index(&self, dio: &Arc<DioMut>) -> Result<DaoMut<QuotaIndex>> {
+        let key = PrimaryKey::from(QUOTAS_ID);
+        match dio.load::<QuotaIndex>(&key).await {
+            Ok(a) => Ok(a),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                let mut index = dio.store_with_key(QuotaIndex::default(), key)?;
+                index.attach_orphaned(&PrimaryKey::from(1))?;
+                Ok(index)
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        }
+    }
+
+    /// Checks that every quota that applies to an entry in a directory can
+    /// absorb a change in usage and then records it in the transaction
+    /// (returns `QuotaExceeded` without changing anything if one can not)
+    pub(crate) async fn quota_charge(
+        &self,
+        dio: &Arc<DioMut>,
+        parent: Option<PrimaryKey>,
+        owners: &[QuotaOwner],
+        bytes: i64,
+        inodes: i64,
+    ) -> Result<()> {
+        let index = self.quotas.lock().unwrap().clone();
+        if (bytes == 0 && inodes == 0) || (index.dirs.is_empty() && index.owners.is_empty()) {
+            return Ok(());
+        }
+
+        let mut dirs = Vec::new();
+        let mut next = match index.dirs.is_empty() {
+            true => None,
+            false => parent,
+        };
+        while let Some(key) = next {
+            let dao = match dio.load::<Inode>(&key).await {
+                Ok(a) => a,
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => break,
+                Err(err) => {
+                    bail!(err);
+                }
+            };
+            next = match key.as_u64() {
+                1 => None,
+                _ => dao.parent_id(),
+            };
+            if dao.quota.is_some() && index.dirs.contains(&key.as_u64()) {
+                dirs.push(dao);
+            }
+        }
+
+        let mut records = Vec::new();
+        for owner in owners.iter().filter(|o| index.owners.contains(o)) {
+            match dio.load::<OwnerQuota>(&owner.key()).await {
+                Ok(a) => records.push(a),
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => {}
+                Err(err) => {
+                    bail!(err);
+                }
+            }
+        }
+
+        let mut charges = Vec::new();
+        for dir in dirs.iter() {
+            if let Some(limit) = dir.quota {
+                let target = QuotaTarget::Directory(dir.key().as_u64());
+                let deltas = DaoVec::new_orphaned_mut(dio, target.key(), QUOTA_DELTAS_VEC_ID);
+                let usage = quota_total(&deltas, dir.usage).await?;
+                if limit.allows(&usage, bytes, inodes) == false {
+                    debug!("quota of inode {} exceeded", dir.key());
+                    bail!(FileSystemErrorKind::QuotaExceeded);
+                }
+                charges.push((target, deltas));
+            }
+        }
+        for record in records.iter() {
+            let target = QuotaTarget::Owner(record.owner);
+            let deltas = DaoVec::new_orphaned_mut(dio, target.key(), QUOTA_DELTAS_VEC_ID);
+            let usage = quota_total(&deltas, record.usage).await?;
+            if record.limit.allows(&usage, bytes, inodes) == false {
+                debug!("quota of {} exceeded", record.owner);
+                bail!(FileSystemErrorKind::QuotaExceeded);
+            }
+            charges.push((target, deltas));
+        }
+
+        // The usage itself is never written here (mounts that charge the same
+        // quota at the same time would otherwise overwrite each other) which
+        // means that concurrent writes can go slightly over a limit
+        let mut touched = self.quota_touched.lock().unwrap();
+        for (target, mut deltas) in charges {
+            deltas.push(QuotaDelta { bytes, inodes })?;
+            touched.insert(target);
+        }
+        Ok(())
+    }
+
+    /// Folds the deltas that this mount charged to its quotas into a single
+    /// delta (while holding the mesh lock of the quota) so that the usage
+    /// stays quick to add up. This waits until the chain is connected
+    pub(crate) async fn quota_fold(&self) -> Result<()> {
+        let targets = std::mem::take(&mut *self.quota_touched.lock().unwrap());
+        let mut targets = targets.into_iter().collect::<Vec<_>>();
+        while let Some(target) = targets.pop() {
+            let trans = self.dio_mut_meta().await;
+            let lock = target.lock_key();
+            let ret = match wait_for_lock(&trans, lock).await {
+                Ok(true) => {
+                    let ret = quota_fold_deltas(&trans, &target).await;
+                    trans.unlock(lock).await?;
+                    ret.map(|_| true)
+                }
+                ret => ret,
+            };
+            if let Ok(true) = ret {
+                continue;
+            }
+
+            // The rest is folded the next time around
+            let mut touched = self.quota_touched.lock().unwrap();
+            touched.insert(target);
+            touched.extend(targets);
+            return ret.map(|_| ());
+        }
+        Ok(())
+    }
+
+    /// Charges (or refunds) a change in the size of a file to its quotas.
+    ///
+    /// Growing files are charged `QUOTA_RESERVE` bytes ahead of time (or
+    /// just what they need when that would go over a quota) and shrinking
+    /// files hand their bytes back to the same pool, so most writes do not
+    /// touch the chain. Whatever is left over is refunded when the file is
+    /// committed by `quota_settle`
+    pub(crate) async fn quota_charge_file(&self, inode: u64, bytes: i64) -> Result<()> {
+        if bytes == 0 || self.has_quotas() == false {
+            return Ok(());
+        }
+
+        let reserved = {
+            let mut lock = self.quota_reserved.lock().unwrap();
+            let reserved = lock.entry(inode).or_default();
+            if bytes < 0 {
+                *reserved += bytes.unsigned_abs();
+                return Ok(());
+            }
+            if *reserved >= bytes as u64 {
+                *reserved -= bytes as u64;
+                return Ok(());
+            }
+            std::mem::take(reserved)
+        };
+
+        let need = bytes as u64 - reserved;
+        let ahead = need.max(QUOTA_RESERVE);
+        let charged = match self.quota_charge_inode(inode, ahead as i64).await {
+            Ok(()) => ahead,
+            Err(FileSystemError(FileSystemErrorKind::QuotaExceeded, _)) if ahead > need => {
+                match self.quota_charge_inode(inode, need as i64).await {
+                    Ok(()) => need,
+                    Err(err) => {
+                        self.quota_unreserve(inode, reserved);
+                        return Err(err);
+                    }
+                }
+            }
+            Err(err) => {
+                self.quota_unreserve(inode, reserved);
+                return Err(err);
+            }
+        };
+        self.quota_unreserve(inode, charged - need);
+        Ok(())
+    }
+
+    fn quota_unreserve(&self, inode: u64, bytes: u64) {
+        if bytes > 0 {
+            let mut lock = self.quota_reserved.lock().unwrap();
+            *lock.entry(inode).or_default() += bytes;
+        }
+    }
+
+    async fn quota_charge_inode(&self, inode: u64, bytes: i64) -> Result<()> {
+        let dao = self.dio.load::<Inode>(&PrimaryKey::from(inode)).await?;
+        let owners = QuotaOwner::of(dao.dentry.uid, dao.dentry.gid);
+        let dio = self.dio_mut_meta().await;
+        self.quota_charge(&dio, dao.parent_id(), &owners, bytes, 0)
+            .await?;
+        dio.commit().await?;
+        Ok(())
+    }
+
+    /// Refunds the bytes that were charged to the quotas of a file ahead of
+    /// its writes but never used (this must happen before the file moves,
+    /// changes hands or is removed)
+    pub(crate) async fn quota_settle(&self, inode: u64) -> Result<()> {
+        let reserved = self.quota_reserved.lock().unwrap().remove(&inode);
+        match reserved {
+            Some(bytes) if bytes > 0 => {
+                if let Err(err) = self.quota_charge_inode(inode, -(bytes as i64)).await {
+                    self.quota_unreserve(inode, bytes);
+                    return Err(err);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Refunds every file that still holds bytes charged ahead of its writes
+    pub(crate) async fn quota_settle_all(&self) -> Result<()> {
+        let inodes = self
+            .quota_reserved
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for inode in inodes {
+            self.quota_settle(inode).await?;
+        }
+        Ok(())
+    }
+
+    /// Refunds the quotas of an entry that was removed from a directory. Hard
+    /// links do not use an inode of their own so the inode (and its data)
+    /// only counts as freed once the last link to it is gone - `freed` is the
+    /// inode that `remove_dentry` deleted along with the last link (if any)
+    pub(crate) async fn quota_release(
+        &self,
+        dio: &Arc<DioMut>,
+        parent: PrimaryKey,
+        entry: &Inode,
+        freed: Option<&Inode>,
+    ) -> Result<()> {
+        let (parent, entry) = match (entry.hard_link, freed) {
+            (Some(_), Some(target)) => (target.dentry.parent.map(PrimaryKey::from), target),
+            (None, _) if entry.links == 0 => (Some(parent), entry),
+            _ => return Ok(()),
+        };
+        let bytes = match entry.kind == FileKind::RegularFile {
+            true => entry.size as i64,
+            false => 0,
+        };
+        let owners = QuotaOwner::of(entry.dentry.uid, entry.dentry.gid);
+        self.quota_charge(dio, parent, &owners, -bytes, -1).await
+    }
+
+    /// Refunds the quotas of a directory that is about to be removed along
+    /// with everything below it (each owner gets back what they had in it)
+    pub(crate) async fn quota_release_tree(
+        &self,
+        dio: &Arc<DioMut>,
+        parent: PrimaryKey,
+        key: &PrimaryKey,
+    ) -> Result<()> {
+        if self.has_quotas() == false {
+            return Ok(());
+        }
+
+        let mut total = QuotaUsage::default();
+        let mut owners = FxHashMap::<QuotaOwner, QuotaUsage>::default();
+        let mut stack = vec![self.dio.load::<Inode>(key).await?];
+        while let Some(dao) = stack.pop() {
+            let (bytes, inodes) = match dao.hard_link {
+                None if dao.kind == FileKind::RegularFile => (dao.size as i64, 1),
+                None => (0, 1),
+                Some(_) => (0, 0),
+            };
+            total.apply(bytes, inodes);
+
+            // Inodes that are still linked from outside of the tree keep
+            // counting towards their owners
+            if dao.links == 0 {
+                for owner in QuotaOwner::of(dao.dentry.uid, dao.dentry.gid) {
+                    owners.entry(owner).or_default().apply(bytes, inodes);
+                }
+            }
+
+            if dao.kind == FileKind::Directory {
+                for child in dao.children.iter().await? {
+                    stack.push(child);
+                }
+            }
+        }
+
+        let (bytes, inodes) = (total.bytes as i64, total.inodes as i64);
+        self.quota_charge(dio, Some(parent), &[], -bytes, -inodes)
+            .await?;
+        for (owner, usage) in owners {
+            let (bytes, inodes) = (usage.bytes as i64, usage.inodes as i64);
+            self.quota_charge(dio, None, &[owner], -bytes, -inodes)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn has_quotas(&self) -> bool {
+        let index = self.quotas.lock().unwrap();
+        index.dirs.is_empty() == false || index.owners.is_empty() == false
+    }
+
+    /// Counts the bytes and inodes below a directory (only those that belong
+    /// to an owner if one is supplied). Hard links share the inode they refer
+    /// to while unlinked inodes that are still held by a link keep theirs
+    async fn quota_usage(&self, root: PrimaryKey, owner: Option<QuotaOwner>) -> Result<QuotaUsage> {
+        let mut ret = QuotaUsage::default();
+        let mut stack = vec![root];
+        while let Some(key) = stack.pop() {
+            let dao = self.dio.load::<Inode>(&key).await?;
+            for child in dao.children.iter().await? {
+                let counts = match owner {
+                    Some(QuotaOwner::User(uid)) => child.dentry.uid == uid,
+                    Some(QuotaOwner::Group(gid)) => child.dentry.gid == gid,
+                    None => true,
+                };
+                if counts && child.hard_link.is_none() {
+                    ret.inodes += 1;
+                    if child.kind == FileKind::RegularFile {
+                        ret.bytes += child.size;
+                    }
+                }
+                if child.kind == FileKind::Directory {
+                    stack.push(child.key().clone());
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Usage of an entry and everything below it (used when it moves to
+    /// another directory)
+    pub(crate) async fn quota_subtree(&self, key: &PrimaryKey) -> Result<QuotaUsage> {
+        let dao = self.dio.load::<Inode>(key).await?;
+        let mut ret = match dao.kind {
+            FileKind::Directory => self.quota_usage(key.clone(), None).await?,
+            _ => QuotaUsage::default(),
+        };
+        if dao.hard_link.is_none() {
+            ret.inodes += 1;
+            if dao.kind == FileKind::RegularFile {
+                ret.bytes += dao.size;
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Places (or with `None` removes) a quota - the current usage is counted
+    /// when the quota is set and then maintained as the files change
+    pub async fn quota_set(&self, target: QuotaTarget, limit: Option<QuotaLimit>) -> Result<()> {
+        self.check_writable()?;
+        self.quota_settle_all().await?;
+        let dio = self.dio_mut_meta().await;
+
+        // Mounts that fold the deltas of the quota wait for it to be recounted
+        let lock = target.lock_key();
+        let locked = wait_for_lock(&dio, lock).await?;
+        let ret = self.quota_set_internal(&dio, target, limit).await;
+        if locked {
+            dio.unlock(lock).await?;
+        }
+        *self.quotas.lock().unwrap() = ret?;
+        Ok(())
+    }
+
+    async fn quota_set_internal(
+        &self,
+        dio: &Arc<DioMut>,
+        target: QuotaTarget,
+        limit: Option<QuotaLimit>,
+    ) -> Result<QuotaIndex> {
+        let mut index = self.quota_index(dio).await?;
+        quota_reset_deltas(dio, &target).await?;
+
+        match target {
+            QuotaTarget::Directory(inode) => {
+                let key = PrimaryKey::from(inode);
+                let mut dao = dio.load::<Inode>(&key).await?;
+                if dao.kind != FileKind::Directory {
+                    bail!(FileSystemErrorKind::NotDirectory);
+                }
+                let usage = match limit {
+                    Some(_) => self.quota_usage(key.clone(), None).await?,
+                    None => QuotaUsage::default(),
+                };
+                {
+                    let mut dao = dao.as_mut();
+                    dao.quota = limit;
+                    dao.usage = usage;
+                }
+
+                let mut index = index.as_mut();
+                index.dirs.retain(|a| *a != key.as_u64());
+                if limit.is_some() {
+                    index.dirs.push(key.as_u64());
+                }
+            }
+            QuotaTarget::Owner(owner) => {
+                let key = owner.key();
+                match limit {
+                    Some(limit) => {
+                        let usage = self.quota_usage(PrimaryKey::from(1), Some(owner)).await?;
+                        match dio.load::<OwnerQuota>(&key).await {
+                            Ok(mut a) => {
+                                let mut a = a.as_mut();
+                                a.limit = limit;
+                                a.usage = usage;
+                            }
+                            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                                let record = OwnerQuota {
+                                    owner,
+                                    limit,
+                                    usage,
+                                };
+                                let mut record = dio.store_with_key(record, key)?;
+                                record.attach_orphaned(&PrimaryKey::from(1))?;
+                            }
+                            Err(err) => {
+                                bail!(err);
+                            }
+                        }
+                    }
+                    None => {
+                        if dio.exists(&key).await {
+                            dio.delete(&key).await?;
+                        }
+                    }
+                }
+
+                let mut index = index.as_mut();
+                index.owners.retain(|a| *a != owner);
+                if limit.is_some() {
+                    index.owners.push(owner);
+                }
+            }
+        }
+
+        let index = index.take();
+        dio.commit().await?;
+        Ok(index)
+    }
+
+    /// Current usage of a quota (the usage it was counted at plus the deltas
+    /// that were charged to it since)
+    async fn quota_usage_of(&self, target: &QuotaTarget, base: QuotaUsage) -> Result<QuotaUsage> {
+        let deltas = DaoVec::new_orphaned(&self.dio, target.key(), QUOTA_DELTAS_VEC_ID);
+        quota_total(&deltas, base).await
+    }
+
+    /// Lists all the quotas along with their current usage
+    pub async fn quota_list(&self) -> Result<Vec<QuotaInfo>> {
+        self.load_quotas().await?;
+        let index = self.quotas.lock().unwrap().clone();
+
+        let mut ret = Vec::new();
+        for inode in index.dirs {
+            let dao = match self.dio.load::<Inode>(&PrimaryKey::from(inode)).await {
+                Ok(a) => a,
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => continue,
+                Err(err) => {
+                    bail!(err);
+                }
+            };
+            if let Some(limit) = dao.quota {
+                let target = QuotaTarget::Directory(inode);
+                ret.push(QuotaInfo {
+                    target: self.path_of(inode).await?,
+                    limit,
+                    usage: self.quota_usage_of(&target, dao.usage).await?,
+                });
+            }
+        }
+        for owner in index.owners {
+            match self.dio.load::<OwnerQuota>(&owner.key()).await {
+                Ok(a) => {
+                    let target = QuotaTarget::Owner(owner);
+                    ret.push(QuotaInfo {
+                        target: owner.to_string(),
+                        limit: a.limit,
+                        usage: self.quota_usage_of(&target, a.usage).await?,
+                    })
+                }
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => {}
+                Err(err) => {
+                    bail!(err);
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Returns the quota that is reported for an inode - the closest directory
+    /// quota above it or otherwise the quota of the user or group
+    pub(crate) async fn quota_of(
+        &self,
+        req: &RequestContext,
+        inode: u64,
+    ) -> Result<Option<(QuotaLimit, QuotaUsage)>> {
+        let index = self.quotas.lock().unwrap().clone();
+        if index.dirs.is_empty() == false {
+            let mut next = Some(PrimaryKey::from(inode));
+            while let Some(key) = next {
+                let dao = match self.dio.load::<Inode>(&key).await {
+                    Ok(a) => a,
+                    Err(LoadError(LoadErrorKind::NotFound(_), _)) => break,
+                    Err(err) => {
+                        bail!(err);
+                    }
+                };
+                if let Some(limit) = dao.quota {
+                    let target = QuotaTarget::Directory(key.as_u64());
+                    let usage = self.quota_usage_of(&target, dao.usage).await?;
+                    return Ok(Some((limit, usage)));
+                }
+                next = match key.as_u64() {
+                    1 => None,
+                    _ => dao.parent_id(),
+                };
+            }
+        }
+
+        let uid = self.translate_uid(req.uid, req);
+        let gid = self.translate_gid(req.gid, req);
+        for owner in QuotaOwner::of(uid, gid) {
+            if index.owners.contains(&owner) == false {
+                continue;
+            }
+            match self.dio.load::<OwnerQuota>(&owner.key()).await {
+                Ok(a) => {
+                    let usage = self
+                        .quota_usage_of(&QuotaTarget::Owner(owner), a.usage)
+                        .await?;
+                    return Ok(Some((a.limit, usage)));
+                }
+                Err(LoadError(LoadErrorKind::NotFound(_), _)) => {}
+                Err(err) => {
+                    bail!(err);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes::*;
+
+    async fn test_accessor() -> FileAccessor {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_quotas_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let accessor = FileAccessor::new(
+            chain,
+            None,
+            session.into(),
+            TransactionScope::Full,
+            TransactionScope::Full,
+            false,
+            false,
+        )
+        .await;
+        accessor.init(&accessor.session_context()).await.unwrap();
+        accessor
+    }
+
+    async fn usage(accessor: &FileAccessor, target: &str) -> QuotaUsage {
+        accessor
+            .quota_list()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|a| a.target == target)
+            .unwrap()
+            .usage
+    }
+
+    async fn write_file(accessor: &FileAccessor, parent: u64, name: &str, data: &[u8]) -> u64 {
+        let ctx = accessor.session_context();
+        let handle = accessor.create(&ctx, parent, name, 0o644).await.unwrap();
+        accessor
+            .write(&ctx, handle.inode, handle.fh, 0, data, 0)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        handle.inode
+    }
+
+    #[test]
+    fn test_limit_allows() {
+        let limit = QuotaLimit {
+            bytes: Some(100),
+            inodes: None,
+        };
+        let usage = QuotaUsage {
+            bytes: 90,
+            inodes: 1000,
+        };
+        assert!(limit.allows(&usage, 10, 1));
+        assert!(limit.allows(&usage, 11, 0) == false);
+        assert!(limit.allows(&usage, -90, -1));
+
+        let mut usage = usage;
+        usage.apply(-200, -1);
+        assert_eq!(usage.bytes, 0);
+        assert_eq!(usage.inodes, 999);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_writes_are_charged_and_truncates_refunded() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let dir = accessor.mkdir(&ctx, 1, "home", 0o770).await.unwrap().ino;
+        let limit = QuotaLimit {
+            bytes: Some(1000),
+            inodes: Some(10),
+        };
+        accessor
+            .quota_set(QuotaTarget::Directory(dir), Some(limit))
+            .await
+            .unwrap();
+
+        // Only what was written counts once the file is committed
+        let ino = write_file(&accessor, dir, "a.bin", &[1u8; 600]).await;
+        assert_eq!(
+            usage(&accessor, "/home").await,
+            QuotaUsage {
+                bytes: 600,
+                inodes: 1
+            }
+        );
+        assert!(accessor.quota_reserved.lock().unwrap().is_empty());
+
+        // Writes that would go over the quota fail without charging anything
+        let handle = accessor.create(&ctx, dir, "b.bin", 0o644).await.unwrap();
+        let ret = accessor
+            .write(&ctx, handle.inode, handle.fh, 0, &[2u8; 401], 0)
+            .await;
+        assert!(matches!(
+            ret,
+            Err(FileSystemError(FileSystemErrorKind::QuotaExceeded, _))
+        ));
+        for n in 0..4 {
+            accessor
+                .write(&ctx, handle.inode, handle.fh, n * 100, &[2u8; 100], 0)
+                .await
+                .unwrap();
+        }
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        assert_eq!(usage(&accessor, "/home").await.bytes, 1000);
+
+        // Opening with O_TRUNC refunds the bytes that were dropped
+        let handle = accessor
+            .open(&ctx, ino, (O_WRONLY | O_TRUNC) as u32)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            usage(&accessor, "/home").await,
+            QuotaUsage {
+                bytes: 400,
+                inodes: 2
+            }
+        );
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_hard_links_share_the_inode_they_refer_to() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let dir = accessor.mkdir(&ctx, 1, "home", 0o770).await.unwrap().ino;
+        let limit = QuotaLimit {
+            bytes: Some(1000),
+            inodes: Some(10),
+        };
+        accessor
+            .quota_set(QuotaTarget::Directory(dir), Some(limit))
+            .await
+            .unwrap();
+        let held = QuotaUsage {
+            bytes: 100,
+            inodes: 1,
+        };
+
+        // Links do not use up another inode (just like in statfs)
+        let ino = write_file(&accessor, dir, "a.bin", &[1u8; 100]).await;
+        accessor.link(&ctx, ino, dir, "b.bin").await.unwrap();
+        assert_eq!(usage(&accessor, "/home").await, held);
+
+        // The inode is only freed along with the last link to it
+        accessor.unlink(&ctx, dir, "a.bin").await.unwrap();
+        assert_eq!(usage(&accessor, "/home").await, held);
+        accessor.unlink(&ctx, dir, "b.bin").await.unwrap();
+        assert_eq!(usage(&accessor, "/home").await, QuotaUsage::default());
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_rmdir_refunds_the_whole_tree() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let home = accessor.mkdir(&ctx, 1, "home", 0o770).await.unwrap();
+        let limit = QuotaLimit {
+            bytes: Some(1 << 20),
+            inodes: Some(100),
+        };
+        let owner = QuotaOwner::User(home.uid);
+        accessor
+            .quota_set(QuotaTarget::Directory(home.ino), Some(limit))
+            .await
+            .unwrap();
+        accessor
+            .quota_set(QuotaTarget::Owner(owner), Some(limit))
+            .await
+            .unwrap();
+        let before = usage(&accessor, owner.to_string().as_str()).await;
+
+        let tree = accessor.mkdir(&ctx, home.ino, "tree", 0o770).await.unwrap();
+        let sub = accessor.mkdir(&ctx, tree.ino, "sub", 0o770).await.unwrap();
+        write_file(&accessor, tree.ino, "a.bin", &[1u8; 300]).await;
+        write_file(&accessor, sub.ino, "b.bin", &[2u8; 200]).await;
+        write_file(&accessor, home.ino, "kept.bin", &[3u8; 100]).await;
+        assert_eq!(
+            usage(&accessor, "/home").await,
+            QuotaUsage {
+                bytes: 600,
+                inodes: 5
+            }
+        );
+
+        accessor.rmdir(&ctx, home.ino, "tree").await.unwrap();
+        assert_eq!(
+            usage(&accessor, "/home").await,
+            QuotaUsage {
+                bytes: 100,
+                inodes: 1
+            }
+        );
+        let after = usage(&accessor, owner.to_string().as_str()).await;
+        assert_eq!(after.bytes, before.bytes + 100);
+        assert_eq!(after.inodes, before.inodes + 1);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_concurrent_charges_are_not_lost() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let dir = accessor.mkdir(&ctx, 1, "home", 0o770).await.unwrap().ino;
+        let limit = QuotaLimit {
+            bytes: Some(1000),
+            inodes: None,
+        };
+        let target = QuotaTarget::Directory(dir);
+        accessor.quota_set(target, Some(limit)).await.unwrap();
+
+        // Both transactions read the same usage before either commits
+        let a = accessor.dio_mut_meta().await;
+        let b = accessor.dio_mut_meta().await;
+        let parent = Some(PrimaryKey::from(dir));
+        accessor
+            .quota_charge(&a, parent, &[], 100, 0)
+            .await
+            .unwrap();
+        accessor
+            .quota_charge(&b, parent, &[], 200, 0)
+            .await
+            .unwrap();
+        a.commit().await.unwrap();
+        b.commit().await.unwrap();
+        assert_eq!(usage(&accessor, "/home").await.bytes, 300);
+
+        // Folding leaves a single delta behind without changing the usage
+        accessor.quota_fold().await.unwrap();
+        assert_eq!(usage(&accessor, "/home").await.bytes, 300);
+        let deltas =
+            DaoVec::<QuotaDelta>::new_orphaned(&accessor.dio, target.key(), QUOTA_DELTAS_VEC_ID);
+        assert_eq!(deltas.iter().await.unwrap().count(), 1);
+
+        // Recounting the usage drops the deltas
+        accessor.quota_set(target, Some(limit)).await.unwrap();
+        assert_eq!(usage(&accessor, "/home").await.bytes, 0);
+        assert_eq!(deltas.iter().await.unwrap().count(), 0);
+    }
+}
@@ -148,6 +148,71 @@ impl Repository {
         })
     }
 
+    /// Looks up a file without reading it (directories are not returned)
+    pub async fn stat_file(&self, key: &ChainKey, sni: &str, path: &str) -> Result<Option<RepositoryFile>, FileSystemError> {
+        let context = RequestContext::default();
+        let chain = self.get_accessor(key, sni).await?;
+
+        trace!("perf-checkpoint: search (path={})", path);
+        let attr = match chain.search(&context, path).await {
+            Ok(Some(a)) if a.kind != FileKind::Directory => a,
+            Ok(_)
+            | Err(FileSystemError(FileSystemErrorKind::IsDirectory, _))
+            | Err(FileSystemError(FileSystemErrorKind::DoesNotExist, _))
+            | Err(FileSystemError(FileSystemErrorKind::NoEntry, _)) => {
+                return Ok(None);
+            }
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+        let hash = chain.content_hash(attr.ino).await?;
+        Ok(Some(RepositoryFile { attr, hash }))
+    }
+
+    /// Lists the entries in a directory (returns None if it is not a directory)
+    pub async fn list_dir(&self, key: &ChainKey, sni: &str, path: &str) -> Result<Option<Vec<(String, FileAttr)>>, FileSystemError> {
+        let context = RequestContext::default();
+        let chain = self.get_accessor(key, sni).await?;
+
+        let attr = match chain.search(&context, path).await {
+            Ok(Some(a)) if a.kind == FileKind::Directory => a,
+            Ok(_)
+            | Err(FileSystemError(FileSystemErrorKind::NotDirectory, _))
+            | Err(FileSystemError(FileSystemErrorKind::DoesNotExist, _))
+            | Err(FileSystemError(FileSystemErrorKind::NoEntry, _)) => {
+                return Ok(None);
+            }
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+
+        let flags = crate::codes::O_RDONLY as u32;
+        let open = chain.opendir(&context, attr.ino, flags).await?;
+        let ret = open
+            .children
+            .iter()
+            .filter(|a| a.name != "." && a.name != "..")
+            .map(|a| (a.name.clone(), a.attr.clone()))
+            .collect::<Vec<_>>();
+        let _ = chain.releasedir(&context, attr.ino, open.fh, 0).await;
+        Ok(Some(ret))
+    }
+
+    /// Reads a range of a file (only the pages that hold the range are loaded)
+    pub async fn read_file(&self, key: &ChainKey, sni: &str, ino: u64, offset: u64, size: u32) -> Result<Bytes, FileSystemError> {
+        let context = RequestContext::default();
+        let chain = self.get_accessor(key, sni).await?;
+
+        let flags = crate::codes::O_RDONLY as u32;
+        trace!("perf-checkpoint: open (ino={}, flags={})", ino, flags);
+        let oh = chain.open(&context, ino, flags).await?;
+        let ret = chain.read(&context, ino, oh.fh, offset, size).await;
+        let _ = chain.release(&context, ino, oh.fh, flags, 0, false).await;
+        ret
+    }
+
     pub async fn set_file(
         &self,
         key: &ChainKey,
@@ -172,12 +237,47 @@ impl Repository {
         Ok(written)
     }
 
+    /// Removes a file (it is not an error if the file does not exist)
+    pub async fn remove_file(&self, key: &ChainKey, sni: &str, path: &str) -> Result<(), FileSystemError> {
+        let context = RequestContext::default();
+        let chain = self.get_accessor(key, sni).await?;
+
+        let (parent, name) = match path.trim_end_matches("/").rsplit_once("/") {
+            Some((parent, name)) => (parent, name),
+            None => ("/", path),
+        };
+        let parent = match chain.search(&context, parent).await {
+            Ok(Some(a)) => a,
+            Ok(None)
+            | Err(FileSystemError(FileSystemErrorKind::DoesNotExist, _))
+            | Err(FileSystemError(FileSystemErrorKind::NoEntry, _)) => {
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(err.into());
+            }
+        };
+        match chain.unlink(&context, parent.ino, name).await {
+            Ok(_)
+            | Err(FileSystemError(FileSystemErrorKind::DoesNotExist, _))
+            | Err(FileSystemError(FileSystemErrorKind::NoEntry, _)) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub async fn house_keeping(&self) {
         let mut lock = self.chains.lock().await;
         lock.iter(); // this will run the remove_expired function
     }
 }
 
+/// File that was found in a repository along with a hash of its contents
+#[derive(Debug, Clone)]
+pub struct RepositoryFile {
+    pub attr: FileAttr,
+    pub hash: AteHash,
+}
+
 #[async_trait]
 pub trait RepositorySessionFactory
 where Self: Send + Sync
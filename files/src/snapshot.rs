@@ -0,0 +1,737 @@
+use ate::meta::MetaAuthorization;
+use ate::prelude::*;
+use ate::time::ChainTimestamp;
+use error_chain::bail;
+use fxhash::FxHashMap;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::Arc;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::accessor::FileAccessor;
+use super::api::FileApi;
+use super::api::FileKind;
+use super::chunk::share_chunks_from;
+use super::chunk::ChunkRelease;
+use super::error::*;
+use super::file::RegularFile;
+use super::model::*;
+
+/// Summary of a snapshot as it is listed
+#[derive(Debug, Clone)]
+pub struct SnapshotInfo {
+    pub name: String,
+    /// Chain timestamp (in milliseconds) that the snapshot was taken at
+    pub created: u64,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Entry that differs between two versions of the file system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotChange {
+    Added(String),
+    Removed(String),
+    Modified(String),
+}
+
+impl std::fmt::Display for SnapshotChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SnapshotChange::Added(path) => write!(f, "+ {}", path),
+            SnapshotChange::Removed(path) => write!(f, "- {}", path),
+            SnapshotChange::Modified(path) => write!(f, "M {}", path),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TreeStats {
+    files: u64,
+    size: u64,
+}
+
+/// Attributes of an entry that are compared when two trees are diffed
+#[derive(Debug)]
+struct DiffEntry {
+    kind: FileKind,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    size: u64,
+    link: Option<String>,
+    /// Position, length and hash of each chunk (or page) of the file data
+    content: Vec<(u64, u32, AteHash)>,
+}
+
+impl DiffEntry {
+    fn same(&self, other: &DiffEntry) -> bool {
+        self.kind == other.kind
+            && self.mode == other.mode
+            && self.uid == other.uid
+            && self.gid == other.gid
+            && self.size == other.size
+            && self.link == other.link
+            && self.content == other.content
+    }
+}
+
+/// Creates an unattached copy of an inode (children, extended attributes and
+/// file data are copied separately)
+fn clone_inode(src: &Inode) -> Inode {
+    let mut ret = Inode::new(
+        src.dentry.name.clone(),
+        src.dentry.mode,
+        src.dentry.uid,
+        src.dentry.gid,
+        src.kind,
+    );
+    ret.dentry.xattr = src.dentry.xattr.clone();
+    ret.size = src.size;
+    ret.link = src.link.clone();
+    if src.bundles.is_empty() {
+        ret.chunks = src.chunks.clone();
+    }
+    ret
+}
+
+pub(crate) fn copy_auth(from: &MetaAuthorization, mut to: DaoAuthGuard<'_>) -> Result<()> {
+    to.read = from.read.clone();
+    to.write = from.write.clone();
+    to.commit()?;
+    Ok(())
+}
+
+impl FileAccessor {
+    async fn snapshot_index(&self, dio: &Arc<DioMut>) -> Result<DaoMut<SnapshotIndex>> {
+        let key = PrimaryKey::from(SNAPSHOTS_ID);
+        match dio.load::<SnapshotIndex>(&key).await {
+            Ok(a) => Ok(a),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                let mut index = dio.store_with_key(
+                    SnapshotIndex {
+                        snapshots: DaoVec::new(),
+                    },
+                    key,
+                )?;
+                index.attach_orphaned(&PrimaryKey::from(1))?;
+                Ok(index)
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        }
+    }
+
+    async fn snapshot_find(&self, name: &str) -> Result<Option<Dao<Snapshot>>> {
+        let index = match self
+            .dio
+            .load::<SnapshotIndex>(&PrimaryKey::from(SNAPSHOTS_ID))
+            .await
+        {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Ok(None);
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        Ok(index
+            .snapshots
+            .iter()
+            .await?
+            .filter(|s| s.name.as_str() == name)
+            .next())
+    }
+
+    /// Returns the point in time that a snapshot froze the file system at
+    /// (which is when its pin was written to the chain)
+    pub async fn snapshot_at(&self, name: &str) -> Result<ChainTimestamp> {
+        let snapshot = match self.snapshot_find(name).await? {
+            Some(a) => a,
+            None => {
+                bail!(FileSystemErrorKind::NoEntry);
+            }
+        };
+        let pin = self.dio.load::<ChainPin>(&snapshot.pin).await?;
+        Ok(ChainTimestamp::from(pin.when_created()))
+    }
+
+    /// Opens a read only view of the file system as it was when a snapshot
+    /// was taken
+    async fn snapshot_dio(&self, name: &str) -> Result<Arc<Dio>> {
+        let at = self.snapshot_at(name).await?;
+        Ok(self.chain.dio_at(&self.session, at).await)
+    }
+
+    pub async fn snapshot_list(&self) -> Result<Vec<SnapshotInfo>> {
+        let index = match self
+            .dio
+            .load::<SnapshotIndex>(&PrimaryKey::from(SNAPSHOTS_ID))
+            .await
+        {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Ok(Vec::new());
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+
+        let mut ret = Vec::new();
+        for s in index.snapshots.iter().await? {
+            let pin = self.dio.load::<ChainPin>(&s.pin).await?;
+            ret.push(SnapshotInfo {
+                name: s.name.clone(),
+                created: pin.when_created(),
+                files: s.files,
+                size: s.size,
+            });
+        }
+        ret.sort_by_key(|s| s.created);
+        Ok(ret)
+    }
+
+    /// Freezes the file system under a name. Nothing is copied - the snapshot
+    /// pins the chain at the moment it is taken so that the inode tree can
+    /// later be read exactly as it was then
+    pub async fn snapshot_create(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        if self.snapshot_find(name).await?.is_some() {
+            bail!(FileSystemErrorKind::AlreadyExists);
+        }
+
+        let dio = self.dio_mut_meta().await;
+        let mut index = self.snapshot_index(&dio).await?;
+        let mut snapshot = index.as_mut().snapshots.push(Snapshot {
+            name: name.to_string(),
+            pin: PrimaryKey::default(),
+            files: 0,
+            size: 0,
+        })?;
+        let snapshot_key = snapshot.key().clone();
+
+        let mut pin = dio.store(ChainPin {
+            label: name.to_string(),
+        })?;
+        pin.attach_orphaned(&snapshot_key)?;
+        snapshot.as_mut().pin = pin.key().clone();
+        dio.commit().await?;
+
+        // The totals are counted from the frozen tree itself
+        let frozen = self.snapshot_dio(name).await?;
+        let stats = tree_stats(&frozen).await?;
+        let dio = self.dio_mut_meta().await;
+        let mut snapshot = dio.load::<Snapshot>(&snapshot_key).await?;
+        {
+            let mut snapshot = snapshot.as_mut();
+            snapshot.files = stats.files;
+            snapshot.size = stats.size;
+        }
+        dio.commit().await?;
+
+        info!(
+            "snapshot '{}' created ({} files, {} bytes)",
+            name, stats.files, stats.size
+        );
+        Ok(())
+    }
+
+    /// Deletes a snapshot which releases its pin so that the history it held
+    /// on to can be compacted away
+    pub async fn snapshot_delete(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        let snapshot = match self.snapshot_find(name).await? {
+            Some(a) => a,
+            None => {
+                bail!(FileSystemErrorKind::NoEntry);
+            }
+        };
+
+        let dio = self.dio_mut_meta().await;
+        dio.delete(&snapshot.pin).await?;
+        dio.delete(snapshot.key()).await?;
+        dio.commit().await?;
+        Ok(())
+    }
+
+    /// Replaces the contents of the file system with those of a snapshot
+    pub async fn snapshot_restore(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        let frozen = self.snapshot_dio(name).await?;
+
+        let dio = self.dio_mut_meta().await;
+        let root = dio.load::<Inode>(&PrimaryKey::from(1)).await?;
+        let children = root
+            .children
+            .iter()
+            .await?
+            .map(|c| c.key().clone())
+            .collect::<Vec<_>>();
+        let mut release = ChunkRelease::default();
+        for child in children {
+            self.remove_tree(&dio, &child, &mut release).await?;
+        }
+
+        let src = frozen.load::<Inode>(&PrimaryKey::from(1)).await?;
+        self.clone_into(&dio, &frozen, src, root).await?;
+        dio.commit().await?;
+        release.apply(&self.dio).await?;
+        *self.inode_cache.lock().unwrap() = None;
+        Ok(())
+    }
+
+    /// Creates a writable copy of a snapshot as a new directory in the root
+    /// of the file system
+    pub async fn snapshot_clone(&self, name: &str, new_name: &str) -> Result<u64> {
+        self.check_writable()?;
+        let frozen = self.snapshot_dio(name).await?;
+
+        let dio = self.dio_mut_meta().await;
+        let mut root = dio.load::<Inode>(&PrimaryKey::from(1)).await?;
+        if root
+            .children
+            .iter()
+            .await?
+            .any(|c| c.orphaned == false && c.dentry.name.as_str() == new_name)
+        {
+            bail!(FileSystemErrorKind::AlreadyExists);
+        }
+
+        let src = frozen.load::<Inode>(&PrimaryKey::from(1)).await?;
+        let mut copy = clone_inode(&src);
+        copy.dentry.name = new_name.to_string();
+        copy.dentry.parent = Some(1);
+        let mut copy = root.as_mut().children.push(copy)?;
+        copy_auth(src.auth(), copy.auth_mut())?;
+        let ret = copy.key().as_u64();
+
+        self.clone_into(&dio, &frozen, src, copy).await?;
+        dio.commit().await?;
+        *self.inode_cache.lock().unwrap() = None;
+        Ok(ret)
+    }
+
+    /// Lists the entries that differ between a snapshot and either another
+    /// snapshot or the live file system
+    pub async fn snapshot_diff(&self, from: &str, to: Option<&str>) -> Result<Vec<SnapshotChange>> {
+        let from = self.snapshot_dio(from).await?;
+        let to = match to {
+            Some(a) => self.snapshot_dio(a).await?,
+            None => Arc::clone(&self.dio),
+        };
+
+        let from = collect_tree(&from).await?;
+        let to = collect_tree(&to).await?;
+
+        let mut ret = Vec::new();
+        for (path, entry) in from.iter() {
+            match to.get(path) {
+                Some(other) if entry.same(other) => {}
+                Some(_) => ret.push(SnapshotChange::Modified(path.clone())),
+                None => ret.push(SnapshotChange::Removed(path.clone())),
+            }
+        }
+        for path in to.keys() {
+            if from.contains_key(path) == false {
+                ret.push(SnapshotChange::Added(path.clone()));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Copies the children of a directory in an older view of the file system
+    /// (`from`) into a live directory. Regular files share their chunks with
+    /// the source and inodes that are hard linked are only copied once
+    async fn clone_into(
+        &self,
+        dio: &Arc<DioMut>,
+        from: &Arc<Dio>,
+        src: Dao<Inode>,
+        dst: DaoMut<Inode>,
+    ) -> Result<()> {
+        // Copies of the inodes that hard links refer to (by their original key)
+        let mut targets = FxHashMap::default();
+        let mut links = Vec::new();
+
+        let mut stack = vec![(src, dst)];
+        while let Some((src, mut dst)) = stack.pop() {
+            for child in src.children.iter().await? {
+                if child.orphaned {
+                    continue;
+                }
+
+                let mut copy = clone_inode(&child);
+                copy.dentry.parent = Some(dst.key().as_u64());
+                let mut copy = dst.as_mut().children.push(copy)?;
+                copy_auth(child.auth(), copy.auth_mut())?;
+
+                // Hard links are connected once all their targets are copied
+                if let Some(target) = child.hard_link {
+                    links.push((copy.key().clone(), target));
+                    continue;
+                }
+                if child.links > 0 {
+                    targets.insert(child.key().as_u64(), copy.key().clone());
+                }
+                self.clone_contents(from, child, copy, &mut stack).await?;
+            }
+        }
+
+        for (link, target) in links {
+            let target_copy = match targets.get(&target) {
+                Some(a) => a.clone(),
+                None => {
+                    // The original entry of the target was unlinked so it is
+                    // copied as a hidden inode
+                    let target_src = match from.load::<Inode>(&PrimaryKey::from(target)).await {
+                        Ok(a) => a,
+                        Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                            dio.delete(&link).await?;
+                            continue;
+                        }
+                        Err(err) => {
+                            bail!(err);
+                        }
+                    };
+                    let mut copy = clone_inode(&target_src);
+                    copy.orphaned = true;
+                    let mut copy = dio.store(copy)?;
+                    copy.attach_orphaned(&PrimaryKey::from(1))?;
+                    copy_auth(target_src.auth(), copy.auth_mut())?;
+                    let key = copy.key().clone();
+                    self.clone_contents(from, target_src, copy, &mut stack)
+                        .await?;
+                    targets.insert(target, key.clone());
+                    key
+                }
+            };
+
+            let mut target_copy = dio.load::<Inode>(&target_copy).await?;
+            target_copy.as_mut().links += 1;
+            let mut link = dio.load::<Inode>(&link).await?;
+            link.as_mut().hard_link = Some(target_copy.key().as_u64());
+        }
+        Ok(())
+    }
+
+    /// Copies the extended attributes and data of an inode into its copy
+    /// (directories are pushed onto the stack so their children get copied)
+    async fn clone_contents(
+        &self,
+        from: &Arc<Dio>,
+        src: Dao<Inode>,
+        mut copy: DaoMut<Inode>,
+        stack: &mut Vec<(Dao<Inode>, DaoMut<Inode>)>,
+    ) -> Result<()> {
+        for (k, v) in src.xattr.iter().await? {
+            copy.as_mut().xattr.insert(k, v.deref().clone()).await?;
+        }
+
+        match src.kind {
+            FileKind::Directory => stack.push((src, copy)),
+            FileKind::RegularFile => {
+                if src.bundles.is_empty() {
+                    share_chunks_from(&self.dio, from, src.auth(), &src.chunks).await?;
+                } else {
+                    // The pages of files stored in bundles are changed in place
+                    // so they can not be shared and their data is copied
+                    let from = RegularFile::new(src, 0, 0).await;
+                    let to = RegularFile::new_mut(copy, 0, 0).await;
+                    to.copy_from(&from, 0, 0, from.size()).await?;
+                    to.commit().await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Counts the regular files (and their bytes) in a view of the file system
+async fn tree_stats(dio: &Arc<Dio>) -> Result<TreeStats> {
+    let mut ret = TreeStats::default();
+    let mut stack = vec![PrimaryKey::from(1)];
+    while let Some(key) = stack.pop() {
+        let dao = dio.load::<Inode>(&key).await?;
+        for child in dao.children.iter().await? {
+            if child.orphaned || child.hard_link.is_some() {
+                continue;
+            }
+            match child.kind {
+                FileKind::Directory => stack.push(child.key().clone()),
+                FileKind::RegularFile => {
+                    ret.files += 1;
+                    ret.size += child.size;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(ret)
+}
+
+async fn collect_tree(dio: &Arc<Dio>) -> Result<BTreeMap<String, DiffEntry>> {
+    let mut ret = BTreeMap::new();
+    let mut stack = vec![(PrimaryKey::from(1), String::new())];
+    while let Some((key, path)) = stack.pop() {
+        let dao = dio.load::<Inode>(&key).await?;
+        for child in dao.children.iter().await? {
+            if child.orphaned {
+                continue;
+            }
+            let child_path = format!("{}/{}", path, child.dentry.name);
+            let child = match child.hard_link {
+                Some(target) => match dio.load::<Inode>(&PrimaryKey::from(target)).await {
+                    Ok(a) => a,
+                    Err(LoadError(LoadErrorKind::NotFound(_), _)) => continue,
+                    Err(err) => {
+                        bail!(err);
+                    }
+                },
+                None => child,
+            };
+
+            if child.kind == FileKind::Directory {
+                stack.push((child.key().clone(), child_path.clone()));
+            }
+            ret.insert(
+                child_path,
+                DiffEntry {
+                    kind: child.kind,
+                    mode: child.dentry.mode,
+                    uid: child.dentry.uid,
+                    gid: child.dentry.gid,
+                    size: child.size,
+                    link: child.link.clone(),
+                    content: content_hashes(dio, child.deref()).await?,
+                },
+            );
+        }
+    }
+    Ok(ret)
+}
+
+/// Hashes the data of a file - chunks carry the hash of their contents while
+/// pages in bundles are changed in place so they are hashed as they are read
+async fn content_hashes(dio: &Arc<Dio>, inode: &Inode) -> Result<Vec<(u64, u32, AteHash)>> {
+    let mut ret = Vec::new();
+    if inode.kind != FileKind::RegularFile {
+        return Ok(ret);
+    }
+
+    if inode.bundles.is_empty() {
+        for c in inode.chunks.iter() {
+            let chunk = dio.load::<Chunk>(&c.key).await?;
+            ret.push((c.offset, c.size, chunk.hash));
+        }
+        return Ok(ret);
+    }
+
+    for (n, bundle) in inode.bundles.iter().enumerate() {
+        let bundle = match bundle {
+            Some(a) => dio.load::<PageBundle>(a).await?,
+            None => continue,
+        };
+        for (m, page) in bundle.pages.iter().enumerate() {
+            let page = match page {
+                Some(a) => dio.load::<Page>(a).await?,
+                None => continue,
+            };
+            let offset = ((n * PAGES_PER_BUNDLE + m) * PAGE_SIZE) as u64;
+            ret.push((offset, page.buf.len() as u32, AteHash::from_bytes(&page.buf[..])));
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_MIN;
+    use crate::codes::*;
+    use bytes::Bytes;
+
+    async fn test_accessor() -> FileAccessor {
+        let builder = ChainBuilder::new(&ConfAte::default())
+            .await
+            .temporal(true)
+            .build();
+        let key = ChainKey::from(format!("test_snapshots_{}", fastrand::u64(..)));
+        let chain = builder.open(&key).await.unwrap();
+
+        let mut session = AteSessionUser::new();
+        session
+            .user
+            .add_write_key(&PrivateSignKey::generate(KeySize::Bit192));
+        let accessor = FileAccessor::new(
+            chain,
+            None,
+            session.into(),
+            TransactionScope::Full,
+            TransactionScope::Full,
+            false,
+            false,
+        )
+        .await;
+        accessor.init(&accessor.session_context()).await.unwrap();
+        accessor
+    }
+
+    async fn write_file(accessor: &FileAccessor, parent: u64, name: &str, data: &[u8]) -> u64 {
+        let ctx = accessor.session_context();
+        let handle = accessor.create(&ctx, parent, name, 0o644).await.unwrap();
+        accessor
+            .write(&ctx, handle.inode, handle.fh, 0, data, 0)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        handle.inode
+    }
+
+    async fn overwrite_file(accessor: &FileAccessor, inode: u64, data: &[u8]) {
+        let ctx = accessor.session_context();
+        let handle = accessor.open(&ctx, inode, O_WRONLY as u32).await.unwrap();
+        accessor
+            .write(&ctx, handle.inode, handle.fh, 0, data, 0)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+    }
+
+    async fn read_path(accessor: &FileAccessor, parent: u64, name: &str) -> Option<Bytes> {
+        let ctx = accessor.session_context();
+        let attr = accessor.lookup(&ctx, parent, name).await.unwrap()?;
+        let handle = accessor.open(&ctx, attr.ino, O_RDONLY as u32).await.unwrap();
+        let ret = accessor
+            .read(&ctx, handle.inode, handle.fh, 0, attr.size as u32)
+            .await
+            .unwrap();
+        accessor
+            .release(&ctx, handle.inode, handle.fh, 0, 0, false)
+            .await
+            .unwrap();
+        Some(ret)
+    }
+
+    fn entry(size: u64, content: Vec<(u64, u32, AteHash)>) -> DiffEntry {
+        DiffEntry {
+            kind: FileKind::RegularFile,
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            size,
+            link: None,
+            content,
+        }
+    }
+
+    #[test]
+    fn test_diff_entries_compare_contents() {
+        let a = AteHash::from_bytes(b"one");
+        let b = AteHash::from_bytes(b"two");
+        assert!(entry(3, vec![(0, 3, a)]).same(&entry(3, vec![(0, 3, a)])));
+
+        // Data that changed in place is found even though the size is the same
+        assert!(entry(3, vec![(0, 3, a)]).same(&entry(3, vec![(0, 3, b)])) == false);
+        let split = entry(6, vec![(0, 3, a), (3, 3, b)]);
+        assert!(split.same(&entry(6, vec![(0, 3, a)])) == false);
+        assert!(entry(3, vec![(0, 3, a)]).same(&entry(4, vec![(0, 3, a)])) == false);
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_snapshot_diff_restore_and_clone() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let a = write_file(&accessor, 1, "a.txt", b"one").await;
+
+        accessor.snapshot_create("s1").await.unwrap();
+        assert!(matches!(
+            accessor.snapshot_create("s1").await,
+            Err(FileSystemError(FileSystemErrorKind::AlreadyExists, _))
+        ));
+        let list = accessor.snapshot_list().await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "s1");
+        assert_eq!((list[0].files, list[0].size), (1, 3));
+
+        // Changes made after the snapshot show up in the diff (even those
+        // that leave the size unchanged)
+        overwrite_file(&accessor, a, b"two").await;
+        write_file(&accessor, 1, "b.txt", b"three").await;
+        assert_eq!(
+            accessor.snapshot_diff("s1", None).await.unwrap(),
+            vec![
+                SnapshotChange::Modified("/a.txt".to_string()),
+                SnapshotChange::Added("/b.txt".to_string()),
+            ]
+        );
+
+        // A clone is a writable copy of the snapshot next to the live files
+        let dir = accessor.snapshot_clone("s1", "old").await.unwrap();
+        assert_eq!(read_path(&accessor, dir, "a.txt").await.unwrap().as_ref(), b"one");
+        assert!(read_path(&accessor, dir, "b.txt").await.is_none());
+        assert_eq!(read_path(&accessor, 1, "a.txt").await.unwrap().as_ref(), b"two");
+        let copy = accessor.lookup(&ctx, dir, "a.txt").await.unwrap().unwrap();
+        overwrite_file(&accessor, copy.ino, b"new").await;
+        assert_eq!(read_path(&accessor, 1, "a.txt").await.unwrap().as_ref(), b"two");
+
+        // Restoring puts everything back the way it was
+        accessor.snapshot_restore("s1").await.unwrap();
+        assert_eq!(read_path(&accessor, 1, "a.txt").await.unwrap().as_ref(), b"one");
+        assert!(read_path(&accessor, 1, "b.txt").await.is_none());
+        assert!(accessor.lookup(&ctx, 1, "old").await.unwrap().is_none());
+        assert!(accessor.snapshot_diff("s1", None).await.unwrap().is_empty());
+
+        accessor.snapshot_delete("s1").await.unwrap();
+        assert!(accessor.snapshot_list().await.unwrap().is_empty());
+        assert!(matches!(
+            accessor.snapshot_restore("s1").await,
+            Err(FileSystemError(FileSystemErrorKind::NoEntry, _))
+        ));
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    #[test]
+    async fn test_copy_file_range_shares_chunks() {
+        ate::utils::bootstrap_test_env();
+        let accessor = test_accessor().await;
+        let ctx = accessor.session_context();
+        let rng = fastrand::Rng::with_seed(7);
+        let data = (0..CHUNK_MIN * 4).map(|_| rng.u8(..)).collect::<Vec<_>>();
+        let a = write_file(&accessor, 1, "a.bin", &data[..]).await;
+        let b = write_file(&accessor, 1, "b.bin", &[]).await;
+
+        let from = accessor.open(&ctx, a, O_RDONLY as u32).await.unwrap();
+        let to = accessor.open(&ctx, b, O_RDWR as u32).await.unwrap();
+        let copied = accessor
+            .copy_file_range(&ctx, a, from.fh, 0, b, to.fh, 0, data.len() as u64 * 2)
+            .await
+            .unwrap();
+        assert_eq!(copied, data.len() as u64);
+        accessor.release(&ctx, a, from.fh, 0, 0, false).await.unwrap();
+        accessor.release(&ctx, b, to.fh, 0, 0, false).await.unwrap();
+        assert_eq!(read_path(&accessor, 1, "b.bin").await.unwrap().as_ref(), &data[..]);
+
+        // The copy refers to the same chunks rather than duplicating them
+        let a = accessor.dio.load::<Inode>(&PrimaryKey::from(a)).await.unwrap();
+        let b = accessor.dio.load::<Inode>(&PrimaryKey::from(b)).await.unwrap();
+        assert!(a.chunks.is_empty() == false);
+        assert_eq!(a.chunks, b.chunks);
+        for c in a.chunks.iter() {
+            let chunk = accessor.dio.load::<Chunk>(&c.key).await.unwrap();
+            assert!(chunk.refs >= 2);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::model::*;
+
+/// Maximum length of a file name in the file system
+pub const MAX_NAME_LEN: u32 = 255;
+
+/// Capacity that is reported for volumes that have no quota
+pub const DEFAULT_CAPACITY: u64 = 1024 * 1024 * 1024 * 1024;
+
+/// Free inodes that are reported for volumes that have no inode limit
+pub const DEFAULT_FREE_INODES: u64 = u32::MAX as u64;
+
+#[derive(Debug, Clone, Default)]
+pub struct FsStats {
+    /// total blocks in the file system
+    pub blocks: u64,
+    /// free blocks in the file system
+    pub bfree: u64,
+    /// free blocks available to unprivileged users
+    pub bavail: u64,
+    /// total inodes in the file system
+    pub files: u64,
+    /// free inodes in the file system
+    pub ffree: u64,
+    /// block size
+    pub bsize: u32,
+    /// maximum length of a file name
+    pub namelen: u32,
+    /// fragment size
+    pub frsize: u32,
+}
+
+impl FsStats {
+    /// Computes the statistics from the number of bytes used by the chain,
+    /// the number of inodes and the quota (if there is one)
+    pub fn new(used: u64, files: u64, quota: Option<u64>) -> FsStats {
+        let bsize = PAGE_SIZE as u64;
+        let capacity = match quota {
+            Some(a) => a.max(used),
+            None => used + DEFAULT_CAPACITY,
+        };
+        let blocks = (capacity + bsize - 1) / bsize;
+        let bfree = (capacity - used) / bsize;
+
+        FsStats {
+            blocks,
+            bfree,
+            bavail: bfree,
+            files: files + DEFAULT_FREE_INODES,
+            ffree: DEFAULT_FREE_INODES,
+            bsize: bsize as u32,
+            namelen: MAX_NAME_LEN,
+            frsize: bsize as u32,
+        }
+    }
+
+    /// Computes the statistics of a directory or owner that has a quota (any
+    /// limit that is not set is reported the same as a volume without a quota)
+    pub fn from_quota(limit: &QuotaLimit, usage: &QuotaUsage) -> FsStats {
+        let mut ret = FsStats::new(usage.bytes, usage.inodes, limit.bytes);
+        if let Some(inodes) = limit.inodes {
+            let inodes = inodes.max(usage.inodes);
+            ret.files = inodes;
+            ret.ffree = inodes - usage.inodes;
+        }
+        ret
+    }
+}
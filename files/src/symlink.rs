@@ -13,6 +13,7 @@ pub struct SymLink {
     pub gid: u32,
     pub mode: u32,
     pub name: String,
+    pub nlink: u32,
     pub link: Option<String>,
 }
 
@@ -24,6 +25,7 @@ impl SymLink {
             mode: inode.dentry.mode,
             name: inode.dentry.name.clone(),
             ino: inode.key().as_u64(),
+            nlink: inode.nlink(),
             link: inode.link.clone(),
             created,
             updated,
@@ -37,6 +39,7 @@ impl SymLink {
             mode: inode.dentry.mode,
             name: inode.dentry.name.clone(),
             ino: inode.key().as_u64(),
+            nlink: inode.nlink(),
             link: inode.link.clone(),
             created,
             updated,
@@ -86,6 +89,10 @@ impl FileApi for SymLink {
         self.link.clone()
     }
 
+    fn nlink(&self) -> u32 {
+        self.nlink
+    }
+
     fn size(&self) -> u64 {
         match &self.link {
             Some(a) => a.len() as u64,
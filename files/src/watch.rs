@@ -0,0 +1,298 @@
+use ate::prelude::*;
+use error_chain::bail;
+use fxhash::FxHashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::accessor::FileAccessor;
+use super::accessor::RequestContext;
+use super::api::FileKind;
+use super::error::*;
+use super::model::*;
+
+/// What happened to the entry that an event refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEventKind {
+    Create,
+    Modify,
+    Delete,
+    Rename,
+}
+
+/// Change made to an entry within a watched directory
+#[derive(Debug, Clone)]
+pub struct WatchEvent {
+    pub kind: WatchEventKind,
+    /// Inode of the entry that changed
+    pub inode: u64,
+    /// Inode of the directory the entry is (or was) in
+    pub parent: Option<u64>,
+    /// Path of the entry relative to the root of the file system
+    pub path: String,
+    /// Directory the entry was in before it was renamed
+    pub old_parent: Option<u64>,
+    /// Path the entry had before it was renamed
+    pub old_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct WatchEntry {
+    parent: Option<u64>,
+    name: String,
+    kind: FileKind,
+}
+
+/// Stream of changes made to a path (including those made by other mounts)
+/// which are derived from the events that are written to the chain
+pub struct FileWatch {
+    chain: Arc<Chain>,
+    session: AteSessionType,
+    receiver: mpsc::Receiver<ChainEvent>,
+    root: u64,
+    path: String,
+    recursive: bool,
+    entries: FxHashMap<u64, WatchEntry>,
+}
+
+impl FileWatch {
+    /// Waits for the next change (returns None when the chain is closed)
+    pub async fn recv(&mut self) -> Option<WatchEvent> {
+        loop {
+            let evt = self.receiver.recv().await?;
+            match self.process(evt).await {
+                Ok(Some(a)) => return Some(a),
+                Ok(None) => continue,
+                Err(err) => {
+                    debug!("watch event skipped - {}", err);
+                    continue;
+                }
+            }
+        }
+    }
+
+    async fn process(&mut self, evt: ChainEvent) -> Result<Option<WatchEvent>> {
+        let inode = evt.key.as_u64();
+        if let Some(type_name) = evt.type_name.as_ref() {
+            if type_name.as_str() != std::any::type_name::<Inode>() {
+                return Ok(None);
+            }
+        }
+
+        if evt.deleted {
+            return Ok(self.removed(inode));
+        }
+
+        // Only entries that are (or were) within the watched tree matter
+        let known = self.entries.contains_key(&inode);
+        if known == false && self.in_scope(evt.parent.map(|p| p.as_u64())) == false {
+            return Ok(None);
+        }
+
+        // Load the latest version of the entry (a fresh DIO is used so that
+        // nothing stale is read from a cache)
+        let dio = self.chain.dio(&self.session).await;
+        let dao = match dio.load::<Inode>(&evt.key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Ok(self.removed(inode));
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        if dao.orphaned {
+            return Ok(self.removed(inode));
+        }
+
+        let entry = WatchEntry {
+            parent: dao.dentry.parent,
+            name: dao.dentry.name.clone(),
+            kind: dao.kind,
+        };
+        if inode != self.root && self.in_scope(entry.parent) == false {
+            // It was moved somewhere that is not being watched
+            return Ok(self.removed(inode));
+        }
+
+        let old = self.entries.insert(inode, entry.clone());
+        let path = self.path_of(inode);
+        let ret = match old {
+            None => WatchEvent {
+                kind: WatchEventKind::Create,
+                inode,
+                parent: entry.parent,
+                path,
+                old_parent: None,
+                old_path: None,
+            },
+            Some(old) if old.parent != entry.parent || old.name != entry.name => {
+                let mut old_path = self.path_of(old.parent.unwrap_or(self.root));
+                if old_path.ends_with("/") == false {
+                    old_path.push('/');
+                }
+                old_path.push_str(old.name.as_str());
+                WatchEvent {
+                    kind: WatchEventKind::Rename,
+                    inode,
+                    parent: entry.parent,
+                    path,
+                    old_parent: old.parent,
+                    old_path: Some(old_path),
+                }
+            }
+            Some(_) => WatchEvent {
+                kind: WatchEventKind::Modify,
+                inode,
+                parent: entry.parent,
+                path,
+                old_parent: None,
+                old_path: None,
+            },
+        };
+        Ok(Some(ret))
+    }
+
+    /// Returns true if entries within this parent are being watched
+    fn in_scope(&self, parent: Option<u64>) -> bool {
+        match parent {
+            Some(p) if self.recursive => self
+                .entries
+                .get(&p)
+                .map(|e| e.kind == FileKind::Directory)
+                .unwrap_or(false),
+            Some(p) => p == self.root,
+            None => false,
+        }
+    }
+
+    /// Forgets an entry (and everything below it) returning the event that
+    /// reports that it was deleted
+    fn removed(&mut self, inode: u64) -> Option<WatchEvent> {
+        let entry = self.entries.get(&inode)?.clone();
+        let path = self.path_of(inode);
+
+        let mut gone = vec![inode];
+        while let Some(next) = gone.pop() {
+            self.entries.remove(&next);
+            gone.extend(
+                self.entries
+                    .iter()
+                    .filter(|(_, e)| e.parent == Some(next))
+                    .map(|(k, _)| *k),
+            );
+        }
+
+        Some(WatchEvent {
+            kind: WatchEventKind::Delete,
+            inode,
+            parent: entry.parent,
+            path,
+            old_parent: None,
+            old_path: None,
+        })
+    }
+
+    /// Builds the path of a known entry from the names that were cached
+    fn path_of(&self, inode: u64) -> String {
+        let mut parts = Vec::new();
+        let mut next = inode;
+        while next != self.root {
+            match self.entries.get(&next) {
+                Some(e) => {
+                    parts.push(e.name.as_str());
+                    match e.parent {
+                        Some(p) => next = p,
+                        None => break,
+                    }
+                }
+                None => break,
+            }
+        }
+        parts.reverse();
+
+        let mut ret = self.path.clone();
+        for part in parts {
+            if ret.ends_with("/") == false {
+                ret.push('/');
+            }
+            ret.push_str(part);
+        }
+        ret
+    }
+}
+
+impl FileAccessor {
+    /// Watches a path for entries that are created, modified, deleted or
+    /// renamed (when recursive the whole subtree below it is watched)
+    pub async fn watch(
+        &self,
+        req: &RequestContext,
+        path: &str,
+        recursive: bool,
+    ) -> Result<FileWatch> {
+        let attr = match self.search(req, path).await? {
+            Some(a) => a,
+            None => {
+                bail!(FileSystemErrorKind::DoesNotExist);
+            }
+        };
+        let root = attr.ino;
+
+        // Subscribe before the tree is scanned so that nothing is missed
+        let receiver = self.chain.watch().await;
+
+        let mut entries = FxHashMap::default();
+        entries.insert(
+            root,
+            WatchEntry {
+                parent: None,
+                name: String::new(),
+                kind: attr.kind,
+            },
+        );
+        let mut pending = vec![root];
+        while let Some(next) = pending.pop() {
+            let dao = self.dio.load::<Inode>(&PrimaryKey::from(next)).await?;
+            if dao.kind != FileKind::Directory {
+                continue;
+            }
+            for child in dao.children.iter().await? {
+                if child.orphaned {
+                    continue;
+                }
+                let inode = child.key().as_u64();
+                entries.insert(
+                    inode,
+                    WatchEntry {
+                        parent: Some(next),
+                        name: child.dentry.name.clone(),
+                        kind: child.kind,
+                    },
+                );
+                if recursive && child.kind == FileKind::Directory {
+                    pending.push(inode);
+                }
+            }
+        }
+
+        let mut path = path.to_string();
+        if path.starts_with("/") == false {
+            path.insert(0, '/');
+        }
+        if path.len() > 1 && path.ends_with("/") {
+            path.pop();
+        }
+
+        Ok(FileWatch {
+            chain: Arc::clone(&self.chain),
+            session: self.session.clone(),
+            receiver,
+            root,
+            path,
+            recursive,
+            entries,
+        })
+    }
+}
@@ -14,6 +14,7 @@ use crate::transaction::*;
 use std::sync::Arc;
 use std::sync::RwLock as StdRwLock;
 use tokio::sync::broadcast;
+use tokio::sync::mpsc;
 use tokio::sync::RwLock;
 
 use crate::conf::ConfAte;
@@ -88,6 +89,21 @@ impl<'a> Chain {
         self.remote_addr.as_ref()
     }
 
+    /// Returns true if the chain can currently reach its remote (local
+    /// chains are always considered to be connected)
+    pub async fn is_connected(&'a self) -> bool {
+        self.pipe.is_connected().await
+    }
+
+    /// Subscribes to a summary of every event that is written to the chain
+    /// (the subscription ends when the receiver is dropped)
+    pub async fn watch(&'a self) -> mpsc::Receiver<ChainEvent> {
+        let (tx, rx) = mpsc::channel(1000);
+        let mut lock = self.inside_async.write().await;
+        lock.watchers.push(ChainWatcher { sender: tx });
+        rx
+    }
+
     pub async fn single(&'a self) -> ChainSingleUser<'a> {
         ChainSingleUser::new(self).await
     }
@@ -108,6 +124,11 @@ impl<'a> Chain {
         self.inside_async.read().await.chain.redo.count()
     }
 
+    /// Returns the number of bytes the chain occupies in its redo log
+    pub async fn size(&'a self) -> u64 {
+        self.inside_async.read().await.chain.redo.size()
+    }
+
     pub async fn flush(&'a self) -> Result<(), tokio::io::Error> {
         Ok(self.inside_async.write().await.chain.flush().await?)
     }
@@ -2,6 +2,7 @@
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
 use crate::event::*;
+use crate::header::PrimaryKey;
 
 use tokio::sync::mpsc;
 
@@ -10,3 +11,22 @@ pub(crate) struct ChainListener {
     pub(crate) id: u64,
     pub(crate) sender: mpsc::Sender<EventWeakData>,
 }
+
+/// Summary of an event that was written to the chain, watchers receive
+/// these for every row that changes regardless of which collection it is in
+#[derive(Debug, Clone)]
+pub struct ChainEvent {
+    /// Key of the row that was written or removed
+    pub key: PrimaryKey,
+    /// Row that owns the collection that this row belongs to
+    pub parent: Option<PrimaryKey>,
+    /// Name of the type that the row was serialized from
+    pub type_name: Option<String>,
+    /// True if the row was removed from the chain
+    pub deleted: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct ChainWatcher {
+    pub(crate) sender: mpsc::Sender<ChainEvent>,
+}
@@ -12,6 +12,7 @@ mod workers;
 
 pub use self::core::*;
 pub use compact::*;
+pub use listener::ChainEvent;
 pub(crate) use listener::*;
 pub use new::*;
 pub(crate) use protected_async::*;
@@ -180,6 +180,7 @@ impl<'a> Chain {
             disable_new_roots: false,
             sync_tolerance: builder.cfg_ate.sync_tolerance,
             listeners: MultiMap::new(),
+            watchers: Vec::new(),
             is_shutdown: false,
             integrity: load_integrity,
         };
@@ -14,6 +14,7 @@ use std::sync::Arc;
 use std::sync::RwLock as StdRwLock;
 use std::sync::RwLockWriteGuard as StdRwLockWriteGuard;
 use std::time::Duration;
+use tokio::sync::mpsc;
 use tokio::sync::RwLock;
 
 use crate::meta::*;
@@ -30,6 +31,7 @@ pub(crate) struct ChainProtectedAsync {
     pub(crate) disable_new_roots: bool,
     pub(crate) sync_tolerance: Duration,
     pub(crate) listeners: MultiMap<MetaCollection, ChainListener>,
+    pub(crate) watchers: Vec<ChainWatcher>,
     pub(crate) is_shutdown: bool,
     pub(crate) integrity: TrustMode,
 }
@@ -185,6 +187,41 @@ impl ChainProtectedAsync {
     }
 
     pub(crate) async fn notify(lock: Arc<RwLock<ChainProtectedAsync>>, evts: Vec<EventWeakData>) {
+        // Watchers see a summary of every event (slow watchers miss events
+        // rather than holding up the chain)
+        let summaries = evts
+            .iter()
+            .filter_map(|evt| {
+                let key = evt.meta.get_data_key()?;
+                Some(ChainEvent {
+                    key,
+                    parent: evt.meta.get_parent().map(|p| p.vec.parent_id),
+                    type_name: evt.meta.get_type_name().map(|t| t.type_name.clone()),
+                    deleted: evt.meta.get_tombstone().is_some(),
+                })
+            })
+            .collect::<Vec<_>>();
+        if summaries.is_empty() == false {
+            let mut closed = false;
+            {
+                let lock = lock.read().await;
+                for watcher in lock.watchers.iter() {
+                    for evt in summaries.iter() {
+                        if let Err(mpsc::error::TrySendError::Closed(_)) =
+                            watcher.sender.try_send(evt.clone())
+                        {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if closed {
+                let mut lock = lock.write().await;
+                lock.watchers.retain(|a| a.sender.is_closed() == false);
+            }
+        }
+
         // Build a map of event parents that will be used in the BUS notifications
         let mut notify_map = MultiMap::new();
         for evt in evts {
@@ -75,6 +75,7 @@ where
         Ok(Tx {
             direction: TxDirection::Upcast(upstream),
             hello_path: hello_path.clone(),
+            peer_addr: None,
             wire_format: conf.cfg_mesh.wire_format,
             relay: None,
             metrics: Arc::clone(&metrics),
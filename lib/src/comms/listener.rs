@@ -326,6 +326,7 @@ where
         group.all.insert(node_id, Arc::downgrade(&tx));
         let tx = Tx {
             hello_path: hello.path.clone(),
+            peer_addr: Some(sock_addr),
             wire_format,
             direction: TxDirection::Downcast(TxGroupSpecific {
                 me_id: node_id,
@@ -1,6 +1,7 @@
 use fxhash::FxHashMap;
 use rand::seq::SliceRandom;
 use serde::{de::DeserializeOwned, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
 use std::sync::Weak;
@@ -40,6 +41,9 @@ pub(crate) struct TxRelay {
 #[derive(Debug)]
 pub(crate) struct Tx {
     pub hello_path: String,
+    /// Address of the peer on the other end of the connection (only known
+    /// when the connection was accepted by this node)
+    pub peer_addr: Option<SocketAddr>,
     pub(crate) direction: TxDirection,
     pub wire_format: SerializationFormat,
     pub(crate) relay: Option<TxRelay>,
@@ -163,6 +167,7 @@ impl Tx {
 
         let ret = Tx {
             hello_path: self.hello_path.clone(),
+            peer_addr: self.peer_addr,
             direction,
             wire_format: self.wire_format.clone(),
             relay: None,
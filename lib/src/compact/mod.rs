@@ -3,6 +3,7 @@ pub mod compact_state;
 pub mod cut_off_compactor;
 pub mod event_compactor;
 pub mod indecisive_compactor;
+pub mod pin_compactor;
 pub mod public_key_compactor;
 pub mod remove_duplicates;
 pub mod sig_compactor;
@@ -15,6 +16,7 @@ pub use compact_mode::*;
 pub use cut_off_compactor::*;
 pub use event_compactor::*;
 pub use indecisive_compactor::*;
+pub use pin_compactor::*;
 pub use public_key_compactor::*;
 pub use remove_duplicates::*;
 pub use sig_compactor::*;
@@ -0,0 +1,120 @@
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+use crate::event::*;
+use crate::header::*;
+use crate::time::ChainTimestamp;
+
+use super::*;
+
+/// Record that pins the state of a chain at the moment it was written so that
+/// the chain can still be read as it was then (see `Chain::dio_at`) after it
+/// has been compacted - deleting the record releases the pin
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChainPin {
+    pub label: String,
+}
+
+/// Keeps the versions of every record that were current when a `ChainPin`
+/// was written (and any tombstones that later removed them)
+#[derive(Default, Clone)]
+pub struct PinCompactor {
+    pins: BTreeSet<ChainTimestamp>,
+    released: FxHashSet<PrimaryKey>,
+    versions: FxHashMap<PrimaryKey, BTreeSet<ChainTimestamp>>,
+}
+
+impl PinCompactor {
+    fn is_pin(header: &EventHeader) -> bool {
+        match header.meta.get_type_name() {
+            Some(t) => t.type_name.as_str() == std::any::type_name::<ChainPin>(),
+            None => false,
+        }
+    }
+
+    /// Returns true if a pin was written after this version of the record
+    /// and before the record was next changed
+    fn is_pinned(&self, key: &PrimaryKey, when: ChainTimestamp) -> bool {
+        let next = self
+            .versions
+            .get(key)
+            .and_then(|v| v.range((Bound::Excluded(when), Bound::Unbounded)).next());
+        match next {
+            Some(next) => self.pins.range(when..*next).next().is_some(),
+            None => self.pins.range(when..).next().is_some(),
+        }
+    }
+}
+
+impl EventCompactor for PinCompactor {
+    fn clone_compactor(&self) -> Option<Box<dyn EventCompactor>> {
+        Some(Box::new(Self::default()))
+    }
+
+    fn relevance(&self, header: &EventHeader) -> EventRelevance {
+        let when = match header.meta.get_timestamp() {
+            Some(a) => *a,
+            None => {
+                return EventRelevance::Abstain;
+            }
+        };
+
+        // Tombstones must be kept while an older version of the record is
+        // pinned as otherwise the record would come back to life
+        if let Some(key) = header.meta.get_tombstone() {
+            let pinned = match self.versions.get(&key) {
+                Some(versions) => versions.range(..when).any(|v| self.is_pinned(&key, *v)),
+                None => false,
+            };
+            return match pinned {
+                true => EventRelevance::ForceKeep,
+                false => EventRelevance::Abstain,
+            };
+        }
+
+        let key = match header.meta.get_data_key() {
+            Some(key) => key,
+            None => {
+                return EventRelevance::Abstain;
+            }
+        };
+        if Self::is_pin(header) && self.released.contains(&key) == false {
+            return EventRelevance::ForceKeep;
+        }
+        match self.is_pinned(&key, when) {
+            true => EventRelevance::ForceKeep,
+            false => EventRelevance::Abstain,
+        }
+    }
+
+    fn feed(&mut self, header: &EventHeader, _keep: bool) {
+        let when = match header.meta.get_timestamp() {
+            Some(a) => *a,
+            None => {
+                return;
+            }
+        };
+
+        // Events are fed from newest to oldest so a pin that was deleted is
+        // released before the pin itself is seen
+        if let Some(key) = header.meta.get_tombstone() {
+            self.released.insert(key);
+            self.versions.entry(key).or_default().insert(when);
+            return;
+        }
+
+        if let Some(key) = header.meta.get_data_key() {
+            if Self::is_pin(header) && self.released.contains(&key) == false {
+                self.pins.insert(when);
+            }
+            self.versions.entry(key).or_default().insert(when);
+        }
+    }
+
+    fn name(&self) -> &str {
+        "pin-compactor"
+    }
+}
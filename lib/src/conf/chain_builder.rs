@@ -150,6 +150,7 @@ impl ChainBuilder {
             .push(Box::new(RemoveDuplicatesCompactor::default()));
         self.compactors
             .push(Box::new(TombstoneCompactor::default()));
+        self.compactors.push(Box::new(PinCompactor::default()));
         self.plugins.push(Box::new(AntiReplayPlugin::default()));
 
         match self.configured_for {
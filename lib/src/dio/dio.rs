@@ -229,6 +229,23 @@ impl Dio {
         self.multi.lookup_primary(key).await.is_some()
     }
 
+    /// Loads every version of a record that is still in the history of the
+    /// chain (oldest first) - older versions are only kept until the chain
+    /// is compacted unless they have been pinned with a `ChainPin`
+    pub async fn load_versions<D>(
+        self: &Arc<Self>,
+        key: &PrimaryKey,
+    ) -> Result<Vec<Dao<D>>, LoadError>
+    where
+        D: DeserializeOwned,
+    {
+        let mut ret = Vec::new();
+        for leaf in self.multi.versions_of(key).await {
+            ret.push(self.load_from_entry(leaf).await?);
+        }
+        Ok(ret)
+    }
+
     pub(crate) async fn load_from_entry<D>(
         self: &Arc<Self>,
         leaf: EventLeaf,
@@ -324,9 +341,7 @@ impl Dio {
     }
 
     pub async fn __all_keys(self: &Arc<Self>) -> Vec<PrimaryKey> {
-        let guard = self.multi.inside_async.read().await;
-        let keys = guard.chain.timeline.pointers.all_keys();
-        keys.map(|a| a.clone()).collect::<Vec<_>>()
+        self.multi.all_keys().await
     }
 
     pub async fn children<D>(
@@ -476,10 +491,12 @@ impl Dio {
                     continue;
                 }
 
-                to_load.push(match inside_async.chain.lookup_primary(&key) {
-                    Some(a) => a,
-                    None => continue,
-                });
+                to_load.push(
+                    match self.multi.lookup_primary_locked(&inside_async, &key) {
+                        Some(a) => a,
+                        None => continue,
+                    },
+                );
             }
             to_load
         };
@@ -732,6 +749,33 @@ impl Chain {
         ret.run_decache(decache);
         ret
     }
+
+    /// Opens a read only data access layer that sees the chain exactly as it was
+    /// at a particular point in time (events that came afterwards are invisible)
+    ///
+    /// The history is only available until the chain is compacted unless it has
+    /// been pinned with a `ChainPin`
+    pub async fn dio_at(
+        self: &Arc<Chain>,
+        session: &'_ dyn AteSession,
+        at: ChainTimestamp,
+    ) -> Arc<Dio> {
+        let decache = self.decache.subscribe();
+        let multi = ChainMultiUser::new_at(self, at).await;
+        let ret = Dio {
+            chain: Arc::clone(self),
+            state: StdMutex::new(DioState {
+                cache_load: FxHashMap::default(),
+            }),
+            session: StdRwLock::new(session.clone_session()),
+            log_format: Some(multi.default_format.clone()),
+            multi,
+            time: Arc::clone(&self.time),
+        };
+        let ret = Arc::new(ret);
+        ret.run_decache(decache);
+        ret
+    }
 }
 
 impl Dio {
@@ -49,6 +49,7 @@ pub(crate) struct DioMutState {
     pub(super) deleted: FxHashSet<PrimaryKey>,
     pub(super) pipe_unlock: FxHashSet<PrimaryKey>,
     pub(super) auto_cancel: bool,
+    pub(super) default_read: ReadOption,
 }
 
 impl DioMutState {
@@ -148,6 +149,7 @@ impl DioMutState {
             deleted: FxHashSet::default(),
             pipe_unlock: FxHashSet::default(),
             auto_cancel: true,
+            default_read: ReadOption::Inherit,
         }
     }
 
@@ -311,11 +313,12 @@ impl DioMut {
         let _pop2 = PrimaryKeyScope::new(key);
         let data = data.clone();
 
+        let read = self.state.lock().unwrap().default_read.clone();
         let row_header = RowHeader {
             key: key.clone(),
             parent: None,
             auth: MetaAuthorization {
-                read: ReadOption::Inherit,
+                read,
                 write: WriteOption::Inherit,
             },
         };
@@ -419,6 +422,13 @@ impl DioMut {
         state.auto_cancel = false;
     }
 
+    /// Sets who may read the new records that are stored by this DIO (by
+    /// default they inherit the readability of their parent)
+    pub fn set_default_read(&self, read: ReadOption) {
+        let mut state = self.state.lock().unwrap();
+        state.default_read = read;
+    }
+
     pub(crate) fn default_format(&self) -> MessageFormat {
         self.dio.multi.default_format.clone()
     }
@@ -436,6 +446,11 @@ impl DioMut {
                 return Ok(());
             }
 
+            // Views that are frozen in time can not be changed
+            if self.multi.is_frozen() {
+                bail!(CommitErrorKind::ReadOnly);
+            }
+
             // Grab the rows from the state datachain
             let rows = state
                 .store_ordered
@@ -802,6 +817,83 @@ impl DioMut {
         self.run_async(self.dio.__load_raw(key)).await
     }
 
+    /// Changes the authorization of a data object without needing to know its
+    /// type (e.g. to move records onto keys that have been rotated) where the
+    /// closure returns the new authorization or None if nothing should change
+    pub async fn reauthorize<F>(
+        self: &Arc<Self>,
+        key: &PrimaryKey,
+        change: F,
+    ) -> Result<bool, LoadError>
+    where
+        F: FnOnce(&MetaAuthorization) -> Option<MetaAuthorization>,
+    {
+        {
+            let state = self.state.lock().unwrap();
+            if state.is_locked(key) {
+                bail!(LoadErrorKind::ObjectStillLocked(key.clone()));
+            }
+            if state.deleted.contains(&key) {
+                bail!(LoadErrorKind::AlreadyDeleted(key.clone()));
+            }
+        }
+
+        let leaf = match self.multi.lookup_primary(key).await {
+            Some(a) => a,
+            None => bail!(LoadErrorKind::NotFound(key.clone())),
+        };
+        let evt = self.multi.load(leaf).await?;
+        let header = evt.header.as_header()?;
+
+        let auth = match header.meta.get_authorization() {
+            Some(a) => a.clone(),
+            None => MetaAuthorization::default(),
+        };
+        let auth = match change(&auth) {
+            Some(a) => a,
+            None => return Ok(false),
+        };
+
+        // The data is decrypted with the old keys and will be encrypted again
+        // with the new keys when the transaction is committed
+        let data = match evt.data.data_bytes {
+            Some(data) => {
+                let session = self.session();
+                self.multi
+                    .data_as_overlay(&header.meta, data, session.deref())?
+            }
+            None => return Ok(false),
+        };
+        let parent = header.meta.get_parent().map(|a| a.clone());
+        let row = RowData {
+            key: key.clone(),
+            type_name: header
+                .meta
+                .get_type_name()
+                .map(|a| a.type_name.clone())
+                .unwrap_or_default(),
+            format: evt.data.format,
+            data_hash: AteHash::from_bytes(&data[..]),
+            data,
+            collections: header.meta.get_collections().into_iter().collect(),
+            created: leaf.created,
+            updated: leaf.updated,
+            extra_meta: Vec::new(),
+            parent: parent.clone(),
+            auth: auth.clone(),
+            is_new: false,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.dirty_header(RowHeader {
+            key: key.clone(),
+            parent,
+            auth,
+        });
+        state.dirty_row(row);
+        Ok(true)
+    }
+
     pub async fn exists(&self, key: &PrimaryKey) -> bool {
         {
             let state = self.state.lock().unwrap();
@@ -965,10 +1057,12 @@ impl DioMut {
                     continue;
                 }
 
-                to_load.push(match inside_async.chain.lookup_primary(&key) {
-                    Some(a) => a,
-                    None => continue,
-                });
+                to_load.push(
+                    match self.multi.lookup_primary_locked(&inside_async, &key) {
+                        Some(a) => a,
+                        None => continue,
+                    },
+                );
             }
 
             to_load
@@ -7,6 +7,7 @@ use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 use crate::crypto::*;
 use crate::dio::*;
 use crate::prelude::*;
+use crate::time::ChainTimestamp;
 
 #[cfg(test)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -261,3 +262,181 @@ async fn test_dio() -> Result<(), AteError> {
 
     Ok(())
 }
+
+#[tokio::main(flavor = "current_thread")]
+#[test]
+async fn test_dio_reauthorize() -> Result<(), AteError> {
+    crate::utils::bootstrap_test_env();
+
+    let write_key = PrivateSignKey::generate(KeySize::Bit192);
+    let old_key = EncryptKey::generate(KeySize::Bit192);
+    let new_key = EncryptKey::generate(KeySize::Bit192);
+
+    let mut session_old = AteSessionUser::new();
+    session_old.user.add_write_key(&write_key);
+    session_old.user.add_read_key(&old_key);
+    let mut session_both = session_old.clone();
+    session_both.user.add_read_key(&new_key);
+    let mut session_new = AteSessionUser::new();
+    session_new.user.add_write_key(&write_key);
+    session_new.user.add_read_key(&new_key);
+
+    let chain_name = format!(
+        "test_dio_reauthorize_{}",
+        PrimaryKey::generate().to_string()
+    );
+    let mut mock_cfg = crate::conf::tests::mock_test_config();
+    let (chain, _builder) = crate::trust::create_test_chain(
+        &mut mock_cfg,
+        chain_name,
+        false,
+        false,
+        Some(write_key.as_public_key()),
+    )
+    .await;
+
+    // Store a record that only the old key can read
+    let key = {
+        let dio = chain.dio_mut(&session_old).await;
+        let mut dao = dio.store("secret".to_string())?;
+        dao.auth_mut().read = ReadOption::from_key(&old_key);
+        let key = dao.key().clone();
+        dio.commit().await?;
+        key
+    };
+
+    // Move it onto the new key
+    {
+        let dio = chain.dio_mut(&session_both).await;
+        let changed = dio
+            .reauthorize(&key, |auth| match &auth.read {
+                ReadOption::Specific(hash, derived) if *hash == old_key.hash() => {
+                    let mut derived = derived.clone();
+                    derived.change(&old_key, &new_key).ok()?;
+                    let mut auth = auth.clone();
+                    auth.read = ReadOption::Specific(new_key.hash(), derived);
+                    Some(auth)
+                }
+                _ => None,
+            })
+            .await?;
+        assert!(changed);
+        assert!(dio.reauthorize(&key, |_| None).await? == false);
+        dio.commit().await?;
+    }
+
+    // Only the new key can read it now
+    let dio = chain.dio(&session_new).await;
+    assert_eq!(*dio.load::<String>(&key).await?, "secret".to_string());
+    let dio = chain.dio(&session_old).await;
+    dio.load::<String>(&key)
+        .await
+        .expect_err("The old key should no longer read the record");
+
+    chain.single().await.destroy().await.unwrap();
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+#[test]
+async fn test_dio_at_survives_compaction() -> Result<(), AteError> {
+    crate::utils::bootstrap_test_env();
+
+    let write_key = PrivateSignKey::generate(KeySize::Bit192);
+    let read_key = EncryptKey::generate(KeySize::Bit192);
+    let mut session = AteSessionUser::new();
+    session.user.add_write_key(&write_key);
+    session.user.add_read_key(&read_key);
+
+    let chain_name = format!("test_dio_at_{}", PrimaryKey::generate().to_string());
+    let mut mock_cfg = crate::conf::tests::mock_test_config();
+    mock_cfg.sync_tolerance = std::time::Duration::from_millis(0);
+    let (chain, _builder) = crate::trust::create_test_chain(
+        &mut mock_cfg,
+        chain_name,
+        false,
+        false,
+        Some(write_key.as_public_key()),
+    )
+    .await;
+    let pause = || crate::engine::sleep(std::time::Duration::from_millis(10));
+
+    // Write two records and then pin the chain
+    let (key, gone) = {
+        let dio = chain.dio_mut(&session).await;
+        let key = dio.store("before".to_string())?.key().clone();
+        let gone = dio.store("gone".to_string())?.key().clone();
+        dio.commit().await?;
+        (key, gone)
+    };
+    pause().await;
+    let pin = {
+        let dio = chain.dio_mut(&session).await;
+        let pin = dio.store(ChainPin::default())?.key().clone();
+        dio.commit().await?;
+        pin
+    };
+    let at = ChainTimestamp::from(
+        chain
+            .dio(&session)
+            .await
+            .load::<ChainPin>(&pin)
+            .await?
+            .when_created(),
+    );
+    pause().await;
+
+    // Change one record and delete the other
+    {
+        let dio = chain.dio_mut(&session).await;
+        let mut dao = dio.load::<String>(&key).await?;
+        *dao.as_mut() = "after".to_string();
+        dio.delete(&gone).await?;
+        dio.commit().await?;
+    }
+    pause().await;
+
+    // The pinned view still sees the old state after a compaction
+    chain.compact().await?;
+    let dio = chain.dio(&session).await;
+    assert_eq!(*dio.load::<String>(&key).await?, "after".to_string());
+    assert!(dio.exists(&gone).await == false);
+    let frozen = chain.dio_at(&session, at).await;
+    assert_eq!(*frozen.load::<String>(&key).await?, "before".to_string());
+    assert_eq!(*frozen.load::<String>(&gone).await?, "gone".to_string());
+    let versions = chain
+        .dio(&session)
+        .await
+        .load_versions::<String>(&key)
+        .await?
+        .into_iter()
+        .map(|a| a.take())
+        .collect::<Vec<_>>();
+    assert_eq!(versions, vec!["before".to_string(), "after".to_string()]);
+
+    // Frozen views can not be changed
+    let dio = frozen.as_mut().await;
+    dio.store("nope".to_string())?;
+    dio.commit()
+        .await
+        .expect_err("A frozen view should be read only");
+    dio.cancel();
+
+    // Once the pin is removed the old versions are compacted away
+    {
+        let dio = chain.dio_mut(&session).await;
+        dio.delete(&pin).await?;
+        dio.commit().await?;
+    }
+    pause().await;
+    chain.compact().await?;
+    let frozen = chain.dio_at(&session, at).await;
+    assert!(frozen.exists(&key).await == false);
+    assert!(frozen.exists(&gone).await == false);
+    let dio = chain.dio(&session).await;
+    assert_eq!(*dio.load::<String>(&key).await?, "after".to_string());
+    assert_eq!(dio.load_versions::<String>(&key).await?.len(), 1);
+
+    chain.single().await.destroy().await.unwrap();
+    Ok(())
+}
@@ -12,6 +12,7 @@ use super::crypto::PublicSignKey;
 use super::error::ChainCreationError;
 use super::spec::TrustMode;
 use crate::crypto::KeySize;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub type MessageOfTheDay = Option<String>;
@@ -48,6 +49,18 @@ where
         wire_encryption: Option<KeySize>,
     ) -> Result<OpenAction, ChainCreationError>;
 
+    /// Opens a chain for a peer that connected from a particular address, flows
+    /// that do not care where their clients are just open the chain as normal
+    async fn open_from(
+        &self,
+        builder: ChainBuilder,
+        key: &ChainKey,
+        wire_encryption: Option<KeySize>,
+        _peer_addr: Option<SocketAddr>,
+    ) -> Result<OpenAction, ChainCreationError> {
+        self.open(builder, key, wire_encryption).await
+    }
+
     async fn message_of_the_day(
         &self,
         chain: &Arc<Chain>,
@@ -471,7 +471,7 @@ async fn open_internal<'b>(
         debug!("open_flow: {}", route.flow_type);
         match route
             .flow
-            .open(builder, &route_chain.chain, wire_encryption)
+            .open_from(builder, &route_chain.chain, wire_encryption, tx.peer_addr)
             .await?
         {
             OpenAction::PrivateChain { chain, session } => {
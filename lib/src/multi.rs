@@ -18,6 +18,7 @@ use super::meta::*;
 use super::pipe::*;
 use super::spec::MessageFormat;
 use super::transaction::*;
+use super::time::ChainTimestamp;
 use super::trust::*;
 use super::event::MessageBytes;
 
@@ -45,6 +46,10 @@ pub struct ChainMultiUser {
     #[derivative(Debug = "ignore")]
     pub(super) pipe: Arc<Box<dyn EventPipe>>,
     pub(super) default_format: MessageFormat,
+    /// When set the lookups are answered from an index of the chain as it
+    /// was at a particular point in time rather than from the live index
+    #[derivative(Debug = "ignore")]
+    pub(super) frozen: Option<Arc<BinaryTreeIndexer>>,
 }
 
 impl ChainMultiUser {
@@ -54,6 +59,18 @@ impl ChainMultiUser {
             inside_sync: Arc::clone(&chain.inside_sync),
             pipe: Arc::clone(&chain.pipe),
             default_format: chain.default_format,
+            frozen: None,
+        }
+    }
+
+    pub(crate) async fn new_at(chain: &Chain, at: ChainTimestamp) -> ChainMultiUser {
+        let frozen = chain.inside_async.read().await.chain.timeline.index_at(at);
+        ChainMultiUser {
+            inside_async: Arc::clone(&chain.inside_async),
+            inside_sync: Arc::clone(&chain.inside_sync),
+            pipe: Arc::clone(&chain.pipe),
+            default_format: chain.default_format,
+            frozen: Some(Arc::new(frozen)),
         }
     }
 
@@ -68,6 +85,7 @@ impl ChainMultiUser {
             inside_sync: Arc::clone(inside_sync),
             pipe: Arc::clone(pipe),
             default_format: guard.default_format,
+            frozen: None,
         }
     }
 
@@ -149,14 +167,35 @@ impl ChainMultiUser {
     }
 
     pub async fn lookup_primary(&self, key: &PrimaryKey) -> Option<EventLeaf> {
+        if let Some(frozen) = self.frozen.as_ref() {
+            return frozen.lookup_primary(key);
+        }
         self.inside_async.read().await.chain.lookup_primary(key)
     }
 
+    /// Looks up a primary key while the caller already holds the chain lock
+    pub(crate) fn lookup_primary_locked(
+        &self,
+        guard: &ChainProtectedAsync,
+        key: &PrimaryKey,
+    ) -> Option<EventLeaf> {
+        match self.frozen.as_ref() {
+            Some(frozen) => frozen.lookup_primary(key),
+            None => guard.chain.lookup_primary(key),
+        }
+    }
+
     pub async fn lookup_secondary(&self, key: &MetaCollection) -> Option<Vec<EventLeaf>> {
+        if let Some(frozen) = self.frozen.as_ref() {
+            return frozen.lookup_secondary(key);
+        }
         self.inside_async.read().await.chain.lookup_secondary(key)
     }
 
     pub async fn lookup_secondary_raw(&self, key: &MetaCollection) -> Option<Vec<PrimaryKey>> {
+        if let Some(frozen) = self.frozen.as_ref() {
+            return frozen.lookup_secondary_raw(key);
+        }
         self.inside_async
             .read()
             .await
@@ -165,10 +204,16 @@ impl ChainMultiUser {
     }
 
     pub async fn lookup_parent(&self, key: &PrimaryKey) -> Option<MetaParent> {
+        if let Some(frozen) = self.frozen.as_ref() {
+            return frozen.lookup_parent(key);
+        }
         self.inside_async.read().await.chain.lookup_parent(key)
     }
 
     pub async fn roots_raw(&self) -> Vec<PrimaryKey> {
+        if let Some(frozen) = self.frozen.as_ref() {
+            return frozen.roots_raw();
+        }
         self.inside_async
             .read()
             .await
@@ -176,6 +221,29 @@ impl ChainMultiUser {
             .roots_raw()
     }
 
+    pub async fn versions_of(&self, key: &PrimaryKey) -> Vec<EventLeaf> {
+        self.inside_async
+            .read()
+            .await
+            .chain
+            .timeline
+            .versions_of(key)
+    }
+
+    pub async fn all_keys(&self) -> Vec<PrimaryKey> {
+        if let Some(frozen) = self.frozen.as_ref() {
+            return frozen.all_keys().map(|a| a.clone()).collect();
+        }
+        let guard = self.inside_async.read().await;
+        let keys = guard.chain.timeline.pointers.all_keys();
+        keys.map(|a| a.clone()).collect()
+    }
+
+    /// Returns true if this view is frozen at a point in time
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+
     #[allow(dead_code)]
     pub(crate) fn metadata_lint_many<'a>(
         &self,
@@ -1,3 +1,4 @@
+pub use crate::compact::ChainPin;
 pub use crate::compact::CompactMode;
 pub use crate::conf::ConfAte as AteConfig;
 pub use crate::conf::ConfAte;
@@ -49,6 +50,7 @@ pub use crate::utils::chain_key_16hex;
 pub use crate::utils::chain_key_4hex;
 
 pub use crate::chain::Chain;
+pub use crate::chain::ChainEvent;
 pub use crate::conf::ChainBuilder;
 pub use crate::mesh::ChainGuard;
 pub use crate::trust::ChainKey;
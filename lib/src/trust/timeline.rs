@@ -59,6 +59,41 @@ impl<'a> ChainTimeline {
         }
     }
 
+    /// Builds an index of the chain as it was at a particular point in time by
+    /// replaying all the events that happened up until then
+    pub(crate) fn index_at(&self, at: ChainTimestamp) -> BinaryTreeIndexer {
+        let mut ret = BinaryTreeIndexer::default();
+        for (_, raw) in self.history.range(..=at) {
+            if let Ok(header) = raw.as_header() {
+                ret.feed(&header);
+            }
+        }
+        ret
+    }
+
+    /// Returns every version of a record that is still in the history of the
+    /// chain (oldest first) - this walks the whole history
+    pub(crate) fn versions_of(&self, key: &PrimaryKey) -> Vec<EventLeaf> {
+        let mut ret = Vec::new();
+        let mut created = None;
+        for (when, raw) in self.history.iter() {
+            if raw.data_hash.is_none() {
+                continue;
+            }
+            match raw.as_header() {
+                Ok(header) if header.meta.get_data_key() == Some(key.clone()) => {}
+                _ => continue,
+            }
+            let updated = when.time_since_epoch_ms;
+            ret.push(EventLeaf {
+                record: raw.event_hash.clone(),
+                created: *created.get_or_insert(updated),
+                updated,
+            });
+        }
+        ret
+    }
+
     #[allow(dead_code)]
     pub(crate) fn start(&self) -> ChainTimestamp {
         let last = self.history.iter().next();
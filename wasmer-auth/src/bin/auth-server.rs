@@ -1,10 +1,14 @@
 use ate::prelude::*;
 use ate::utils::load_node_list;
 use clap::Parser;
+use std::net::SocketAddr;
+use std::sync::Arc;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
 use wasmer_auth::helper::*;
+use wasmer_auth::mail::*;
+use wasmer_auth::oidc::*;
 use wasmer_auth::prelude::*;
 
 #[derive(Parser)]
@@ -46,6 +50,10 @@ struct Run {
     /// Path to the secret key that grants access to the contracts
     #[clap(long, default_value = "~/wasmer/contract.key")]
     contract_key_path: String,
+    /// Path to the secret key shared with the SSH servers that are trusted to log users in
+    /// with their SSH keys (when the key does not exist SSH key logins are disabled)
+    #[clap(long, default_value = "~/wasmer/ssh-login.key")]
+    ssh_login_key_path: String,
     /// Path to the certificate file that will be used by an listening servers
     /// (there must be TXT records in the host domain servers for this cert)
     #[clap(long, default_value = "~/wasmer/cert")]
@@ -66,6 +74,43 @@ struct Run {
     /// Ensures that this authentication server runs as a specific node_id
     #[clap(short, long)]
     node_id: Option<u32>,
+    /// Address that the OpenID Connect provider will listen on (when not
+    /// supplied the provider is disabled)
+    #[clap(long)]
+    oidc_listen: Option<SocketAddr>,
+    /// Public URL of the OpenID Connect provider which is used as the issuer
+    /// of the ID tokens
+    #[clap(long)]
+    oidc_issuer: Option<url::Url>,
+    /// Path to the key that signs the ID tokens issued by the OpenID Connect provider
+    #[clap(long, default_value = "~/wasmer/oidc.key")]
+    oidc_key_path: String,
+    /// Web application that may sign in users via the OpenID Connect provider
+    /// in the form 'client_id=redirect_uri' (can be supplied multiple times)
+    #[clap(long)]
+    oidc_client: Vec<OidcClient>,
+    /// Address of the SMTP relay that verification and recovery emails are sent
+    /// through (when not supplied users can not recover their accounts by email)
+    #[clap(long)]
+    smtp_server: Option<String>,
+    /// Address that the verification and recovery emails are sent from
+    #[clap(long, default_value = "noreply@localhost")]
+    mail_from: String,
+    /// Web page that the links in the verification and recovery emails point to
+    #[clap(long, default_value = "https://localhost/recover")]
+    mail_link_url: url::Url,
+    /// Number of seconds that the emailed codes remain valid for
+    #[clap(long, default_value = "900")]
+    mail_code_lifetime: u64,
+    /// Address of the database servers that hold the databases of the groups, when
+    /// the keys of a group are rotated the records in its databases are moved onto
+    /// the new keys (when not supplied the records are left as they are)
+    #[clap(long)]
+    db_url: Option<url::Url>,
+    /// Group whose owners must approve every elevation to sudo (break-glass), while
+    /// it is supplied users can no longer sudo without such an approved elevation
+    #[clap(long)]
+    break_glass: Option<String>,
 }
 
 /// Generates the secret key that helps protect key operations like creating users and resetting passwords
@@ -104,6 +149,8 @@ async fn main() -> Result<(), AteError> {
             let web_key: EncryptKey = load_key(run.web_key_path.clone(), ".read");
             let edge_key: EncryptKey = load_key(run.edge_key_path.clone(), ".read");
             let contract_key: EncryptKey = load_key(run.contract_key_path.clone(), ".read");
+            let ssh_login_key: Option<EncryptKey> =
+                try_load_key(format!("{}.read", run.ssh_login_key_path));
 
             // Build a session for service
             let mut cfg_ate = conf_auth();
@@ -118,7 +165,15 @@ async fn main() -> Result<(), AteError> {
             session.user.add_read_key(&root_read_key);
             session.user.add_write_key(&root_write_key);
 
-            // Create the server and listen
+            // Emails can only be sent when there is a mail relay
+            let mail = run.smtp_server.as_ref().map(|smtp_server| {
+                let sender = SmtpMailSender::new(smtp_server.as_str(), run.mail_from.as_str());
+                let mut mail = MailConf::new(Arc::new(sender), run.mail_link_url.clone());
+                mail.code_lifetime = std::time::Duration::from_secs(run.mail_code_lifetime);
+                mail
+            });
+
+            // Create the flow that serves the command chains
             let mut flow = ChainFlow::new(
                 &cfg_ate,
                 root_write_key,
@@ -129,6 +184,43 @@ async fn main() -> Result<(), AteError> {
                 &run.url,
             );
             flow.terms_and_conditions = Some(wasmer_auth::GENERIC_TERMS_AND_CONDITIONS.to_string());
+            flow.ssh_key = ssh_login_key;
+            flow.mail = mail;
+            flow.db_url = run.db_url.clone();
+            flow.break_glass = run.break_glass.clone();
+
+            // Start the OpenID Connect provider (if it has been enabled)
+            if let Some(oidc_listen) = run.oidc_listen {
+                let issuer = match run.oidc_issuer.clone() {
+                    Some(a) => a,
+                    None => {
+                        eprintln!(
+                            "The OpenID Connect provider requires an issuer URL (--oidc-issuer)"
+                        );
+                        std::process::exit(1);
+                    }
+                };
+                let oidc_key: Vec<u8> = load_key(run.oidc_key_path.clone(), "");
+                let oidc_key = match OidcSigningKey::from_pkcs8(&oidc_key[..]) {
+                    Ok(a) => a,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        std::process::exit(1);
+                    }
+                };
+                // Logins through the provider count towards the same throttles
+                // as those made over the command chains
+                let service = flow.service().await?;
+                let oidc_conf = OidcConf::new(issuer, run.oidc_client.clone());
+                let oidc = OidcServer::new(oidc_conf, oidc_key, service);
+                tokio::spawn(async move {
+                    if let Err(err) = oidc.listen(oidc_listen).await {
+                        error!("OpenID Connect provider failed - {}", err);
+                    }
+                });
+            }
+
+            // Create the server and listen
             let mut cfg_mesh =
                 ConfMesh::solo_from_url(&cfg_ate, &run.url, &run.listen, None, run.node_id).await?;
             cfg_mesh.wire_protocol = StreamProtocol::parse(&run.url)?;
@@ -170,6 +262,12 @@ async fn main() -> Result<(), AteError> {
 
             let contract_key = EncryptKey::generate(generate.strength);
             save_key(key_path.clone(), contract_key, "contract.key.read");
+
+            let ssh_login_key = EncryptKey::generate(generate.strength);
+            save_key(key_path.clone(), ssh_login_key, "ssh-login.key.read");
+
+            let oidc_key = OidcSigningKey::generate();
+            save_key(key_path.clone(), oidc_key, "oidc.key");
         }
     }
 
@@ -0,0 +1,55 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use error_chain::bail;
+use std::io::stdout;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use url::Url;
+
+use crate::cmd::*;
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::opt::*;
+use crate::prelude::*;
+use crate::request::*;
+
+pub async fn audit_list_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    limit: usize,
+    auth: Url,
+) -> Result<AuditListResponse, AuditError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let list = AuditListRequest {
+        session: session.clone(),
+        limit,
+    };
+    let response: Result<AuditListResponse, AuditListFailed> = chain.invoke(list).await?;
+    Ok(response?)
+}
+
+pub async fn main_audit(
+    session: AteSessionInner,
+    limit: usize,
+    auth: Url,
+) -> Result<(), AuditError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = audit_list_command(&registry, &session, limit, auth).await?;
+
+    println!("# Audit Log");
+    println!("");
+    for event in result.events {
+        match event.source {
+            Some(source) => println!(
+                "{} {} from {} - {}",
+                event.when, event.action, source, event.outcome
+            ),
+            None => println!("{} {} - {}", event.when, event.action, event.outcome),
+        }
+    }
+    Ok(())
+}
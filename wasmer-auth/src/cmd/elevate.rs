@@ -0,0 +1,193 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use error_chain::bail;
+use std::io::stdout;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use url::Url;
+
+use crate::cmd::*;
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::opt::*;
+use crate::prelude::*;
+use crate::request::*;
+
+pub async fn elevate_command(
+    registry: &Registry,
+    session: &AteSessionUser,
+    authenticator_code: String,
+    minutes: u32,
+    scope: TokenScope,
+    approval: Option<String>,
+    reason: String,
+    auth: Url,
+) -> Result<ElevateResponse, ElevationError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the elevate request and fire it over to the authentication server
+    let elevate = ElevateRequest {
+        session: session.clone(),
+        authenticator_code,
+        minutes,
+        scope,
+        approval,
+        reason,
+    };
+    let response: Result<ElevateResponse, ElevationFailed> = chain.invoke(elevate).await?;
+    let result = response?;
+    debug!("id: {}", result.elevation.id);
+    Ok(result)
+}
+
+pub async fn elevation_approve_command(
+    registry: &Registry,
+    session: &AteSessionGroup,
+    identity: String,
+    id: AteHash,
+    auth: Url,
+) -> Result<ElevationApproveResponse, ElevationError> {
+    // Open a command chain
+    let group = session.identity().to_string();
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the approve request and fire it over to the authentication server
+    let approve = ElevationApproveRequest {
+        group,
+        session: session.clone(),
+        identity,
+        id,
+    };
+    let response: Result<ElevationApproveResponse, ElevationFailed> = chain.invoke(approve).await?;
+    Ok(response?)
+}
+
+pub async fn elevation_redeem_command(
+    registry: &Registry,
+    session: &AteSessionUser,
+    authenticator_code: String,
+    id: AteHash,
+    auth: Url,
+) -> Result<ElevateResponse, ElevationError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the redeem request and fire it over to the authentication server
+    let redeem = ElevationRedeemRequest {
+        session: session.clone(),
+        authenticator_code,
+        id,
+    };
+    let response: Result<ElevateResponse, ElevationFailed> = chain.invoke(redeem).await?;
+    Ok(response?)
+}
+
+/// Elevates the user (or redeems an elevation that was approved) and prints
+/// the scoped token that holds the elevated session
+pub async fn main_elevate(
+    session: AteSessionUser,
+    code: Option<String>,
+    minutes: u32,
+    scope: TokenScope,
+    approval: Option<String>,
+    reason: String,
+    redeem: Option<AteHash>,
+    auth: Url,
+    hint_group: &str,
+) -> Result<(), ElevationError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+
+    // Elevations need the same authenticator code as a normal sudo
+    let code = match code {
+        Some(a) => a,
+        None => {
+            if !is_tty_stdin() {
+                bail!(ElevationErrorKind::InvalidArguments);
+            }
+
+            // When no code is supplied we will ask for it
+            eprint!("Authenticator Code: ");
+            stdout().lock().flush()?;
+            let mut s = String::new();
+            std::io::stdin()
+                .read_line(&mut s)
+                .expect("Did not enter a valid code");
+            s.trim().to_string()
+        }
+    };
+
+    let response = match redeem {
+        Some(id) => elevation_redeem_command(&registry, &session, code, id, auth.clone()).await?,
+        None => {
+            elevate_command(
+                &registry,
+                &session,
+                code,
+                minutes,
+                scope,
+                approval,
+                reason,
+                auth.clone(),
+            )
+            .await?
+        }
+    };
+    let elevation = response.elevation;
+
+    // Break-glass elevations must first be approved by an owner of the group
+    let token = match response.token {
+        Some(a) => a,
+        None => {
+            eprintln!(
+                "The elevation (id={}) is waiting for an owner of the {} ({}) to approve it:",
+                elevation.id,
+                hint_group.to_lowercase(),
+                elevation.approval.clone().unwrap_or_default()
+            );
+            eprintln!(
+                "  group approve-elevation {} {} {}",
+                elevation.approval.clone().unwrap_or_default(),
+                elevation.identity,
+                elevation.id
+            );
+            eprintln!("Once it is approved it can be redeemed with:");
+            eprintln!("  token elevate --redeem {}", elevation.id);
+            return Ok(());
+        }
+    };
+
+    // The sudo session is sealed within the token and never leaves the server,
+    // which checks the token every time it is sent along with a group command
+    if is_tty_stdout() {
+        eprintln!(
+            "The token string below may be used for group commands until {}.\n",
+            token
+                .token
+                .expires
+                .map(|a| a.to_string())
+                .unwrap_or_default()
+        );
+    }
+    println!("{}", scoped_token_to_b64(&token)?);
+    Ok(())
+}
+
+pub async fn main_elevation_approve(
+    session: &AteSessionGroup,
+    username: String,
+    id: AteHash,
+    auth: Url,
+    hint_group: &str,
+) -> Result<(), ElevationError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = elevation_approve_command(&registry, session, username, id, auth).await?;
+
+    println!(
+        "{} elevation approved (id={}, user={})",
+        hint_group, result.elevation.id, result.elevation.identity
+    );
+    Ok(())
+}
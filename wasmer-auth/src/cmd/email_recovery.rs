@@ -0,0 +1,197 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use error_chain::bail;
+use std::io::stdout;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use url::Url;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::opt::*;
+use crate::prelude::*;
+use crate::request::*;
+
+pub async fn email_recovery_command(
+    registry: &Registry,
+    email: String,
+    auth: Url,
+) -> Result<EmailRecoveryResponse, EmailRecoveryError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Ask for the recovery code to be emailed
+    let request = EmailRecoveryRequest { email };
+    let response: Result<EmailRecoveryResponse, EmailRecoveryFailed> =
+        chain.invoke(request).await?;
+    let result = response?;
+    Ok(result)
+}
+
+pub async fn email_recovery_enrol_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    enable: bool,
+    auth: Url,
+) -> Result<EmailRecoveryEnrolResponse, EmailRecoveryError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Opt in (or out) of recovery by email
+    let request = EmailRecoveryEnrolRequest {
+        session: session.clone(),
+        enable,
+    };
+    let response: Result<EmailRecoveryEnrolResponse, EmailRecoveryFailed> =
+        chain.invoke(request).await?;
+    let result = response?;
+    Ok(result)
+}
+
+pub async fn main_email_recovery_enrol(
+    session: AteSessionInner,
+    enable: bool,
+    auth: Url,
+) -> Result<EmailRecoveryEnrolResponse, EmailRecoveryError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = email_recovery_enrol_command(&registry, &session, enable, auth).await?;
+
+    match result.enabled {
+        true => println!(
+            r#"Recovery by email is enabled for this account
+
+The authentication server now keeps a copy of the key that opens your account
+(which only the server can read) so that it can be recovered with a code that
+is emailed to you along with a code from your authenticator."#
+        ),
+        false => println!(
+            r#"Recovery by email is disabled for this account (the server no longer keeps a
+copy of the key that opens it)"#
+        ),
+    }
+    Ok(result)
+}
+
+pub async fn email_reset_command(
+    registry: &Registry,
+    email: String,
+    code: String,
+    sudo_code: String,
+    new_password: String,
+    auth: Url,
+) -> Result<EmailResetResponse, EmailRecoveryError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Generate a read-key using the password and some seed data
+    // (this read-key will be mixed with entropy on the server side to decrypt the row
+    //  which means that neither the client nor the server can get at the data alone)
+    let prefix = format!("remote-login:{}:", email);
+    let new_secret = password_to_read_key(&prefix, &new_password, 15, KeySize::Bit192);
+
+    // Create the reset command
+    let request = EmailResetRequest {
+        email,
+        code,
+        sudo_code,
+        new_secret,
+    };
+    let response: Result<EmailResetResponse, EmailResetFailed> = chain.invoke(request).await?;
+    let result = response?;
+    Ok(result)
+}
+
+pub async fn main_email_reset(
+    username: Option<String>,
+    code: Option<String>,
+    sudo_code: Option<String>,
+    new_password: Option<String>,
+    auth: Url,
+) -> Result<EmailResetResponse, EmailRecoveryError> {
+    let username = match username {
+        Some(a) => a,
+        None => {
+            print!("Username: ");
+            stdout().lock().flush()?;
+            let mut s = String::new();
+            std::io::stdin()
+                .read_line(&mut s)
+                .expect("Did not enter a valid username");
+            s.trim().to_string()
+        }
+    };
+
+    // If we do not have a code yet then we need to ask for one to be emailed
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let code = match code {
+        Some(a) => a,
+        None => {
+            let response =
+                email_recovery_command(&registry, username.clone(), auth.clone()).await?;
+            eprintln!(
+                r#"# Account Recovery by Email
+
+If the account has an email address that can be used for recovery then a code
+has been sent to it which is valid for the next {} minutes. You will also need
+an 'authenticator code' from your mobile app.
+"#,
+                (response.expires_in.as_secs() / 60).max(1)
+            );
+
+            print!("Emailed Code: ");
+            stdout().lock().flush()?;
+            let mut s = String::new();
+            std::io::stdin()
+                .read_line(&mut s)
+                .expect("Did not enter a valid code");
+            s.trim().to_string()
+        }
+    };
+
+    let new_password = match new_password {
+        Some(a) => a,
+        None => {
+            let ret1 = rpassword_wasi::prompt_password("New Password: ").unwrap();
+            let ret2 = rpassword_wasi::prompt_password("New Password Again: ").unwrap();
+            if ret1 != ret2 {
+                bail!(EmailRecoveryErrorKind::PasswordMismatch);
+            }
+
+            ret2
+        }
+    };
+
+    let sudo_code = match sudo_code {
+        Some(a) => a,
+        None => {
+            print!("Authenticator Code: ");
+            stdout().lock().flush()?;
+            let mut s = String::new();
+            std::io::stdin()
+                .read_line(&mut s)
+                .expect("Did not enter a valid authenticator code");
+            s.trim().to_string()
+        }
+    };
+
+    let result =
+        email_reset_command(&registry, username, code, sudo_code, new_password, auth).await?;
+
+    if is_tty_stdout() {
+        println!("Account reset (id={})", result.key);
+
+        // Display the new recovery code
+        println!("");
+        if let Some(message_of_the_day) = &result.message_of_the_day {
+            println!("{}", message_of_the_day.as_str());
+            println!("");
+        }
+        println!("Your old recovery code no longer works - below is your new recovery code which");
+        println!("you should save somewhere safe.");
+        println!("");
+        println!("Recovery Code: {}", result.recovery_code);
+    }
+
+    Ok(result)
+}
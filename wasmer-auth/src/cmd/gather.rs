@@ -48,6 +48,7 @@ pub async fn gather_command(
     let gather = GatherRequest {
         group: group.clone(),
         session,
+        token: None,
     };
 
     // Attempt the gather request with a 10 second timeout
@@ -56,6 +57,29 @@ pub async fn gather_command(
     Ok(result.authority)
 }
 
+/// Exchanges a scoped token for the roles it holds in a group, the server
+/// checks the token is still valid before it hands them out
+pub async fn gather_token_command(
+    registry: &Registry,
+    group: String,
+    token: &ScopedToken,
+    auth: Url,
+) -> Result<AteSessionGroup, GatherError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Create the gather command
+    let gather = GatherRequest {
+        group,
+        session: AteSessionInner::Nothing,
+        token: Some(token.clone()),
+    };
+
+    let response: Result<GatherResponse, GatherFailed> = chain.invoke(gather).await?;
+    let result = response?;
+    Ok(result.authority)
+}
+
 pub async fn main_session_group(
     token_string: Option<String>,
     token_file_path: Option<String>,
@@ -68,6 +92,34 @@ pub async fn main_session_group(
     main_session_group_ext(token_string, token_file_path, group, sudo, code, auth_url, hint_group, false).await
 }
 
+/// Privileged commands that are supplied an elevation send it along to the
+/// server instead of gathering the roles of the group
+pub async fn main_session_group_elevated(
+    token_string: Option<String>,
+    token_file_path: Option<String>,
+    group: String,
+    auth_url: Option<url::Url>,
+    hint_group: &str,
+) -> Result<(AteSessionGroup, Option<ScopedToken>), GatherError> {
+    if let Some(elevation) = main_elevation(token_string.clone(), token_file_path.clone()).await {
+        return Ok((
+            AteSessionGroup::new(AteSessionInner::Nothing, group),
+            Some(elevation),
+        ));
+    }
+    let session = main_session_group(
+        token_string,
+        token_file_path,
+        group,
+        true,
+        None,
+        auth_url,
+        hint_group,
+    )
+    .await?;
+    Ok((session, None))
+}
+
 pub async fn main_session_group_ext(
     token_string: Option<String>,
     token_file_path: Option<String>,
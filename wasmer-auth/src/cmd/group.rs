@@ -21,6 +21,12 @@ pub async fn main_opts_group(
     auth: url::Url,
     hint_group: &str,
 ) -> Result<(), AteError> {
+    main_check_operation(
+        token.clone(),
+        token_path.clone(),
+        opts_group.action.operation(),
+    )
+    .await?;
     match opts_group.action {
         GroupAction::Create(action) => {
             let session =
@@ -34,12 +40,10 @@ pub async fn main_opts_group(
             .await?;
         }
         GroupAction::AddUser(action) => {
-            let session = main_session_group(
+            let (session, elevation) = main_session_group_elevated(
                 token.clone(),
                 token_path.clone(),
                 action.group.clone(),
-                true,
-                None,
                 Some(auth.clone()),
                 hint_group,
             )
@@ -49,17 +53,16 @@ pub async fn main_opts_group(
                 Some(action.username),
                 auth,
                 &session,
+                elevation.as_ref(),
                 hint_group,
             )
             .await?;
         }
         GroupAction::RemoveUser(action) => {
-            let session = main_session_group(
+            let (session, elevation) = main_session_group_elevated(
                 token.clone(),
                 token_path.clone(),
                 action.group.clone(),
-                true,
-                None,
                 Some(auth.clone()),
                 hint_group,
             )
@@ -69,22 +72,21 @@ pub async fn main_opts_group(
                 Some(action.username),
                 auth,
                 &session,
+                elevation.as_ref(),
                 hint_group,
             )
             .await?;
         }
         GroupAction::RemoveGroup(action) => {
-            let session = main_session_group(
+            let (session, elevation) = main_session_group_elevated(
                 token.clone(),
                 token_path.clone(),
                 action.group.clone(),
-                true,
-                None,
                 Some(auth.clone()),
                 hint_group,
             )
             .await?;
-            main_group_remove(auth, &session, hint_group).await?;
+            main_group_remove(auth, &session, elevation.as_ref(), hint_group).await?;
         }
         GroupAction::Details(action) => {
             if token.is_some() || token_path.is_some() {
@@ -103,6 +105,29 @@ pub async fn main_opts_group(
                 main_group_details(Some(action.group), auth, None, hint_group).await?;
             }
         }
+        GroupAction::Role(action) => {
+            main_opts_group_role(action, token, token_path, auth, hint_group).await?;
+        }
+        GroupAction::ApproveElevation(action) => {
+            let id = match AteHash::from_hex_string(action.id.as_str()) {
+                Some(a) => a,
+                None => {
+                    eprintln!("The elevation identifier ({}) is not valid", action.id);
+                    std::process::exit(1);
+                }
+            };
+            let session = main_session_group(
+                token.clone(),
+                token_path.clone(),
+                action.group.clone(),
+                true,
+                None,
+                Some(auth.clone()),
+                hint_group,
+            )
+            .await?;
+            main_elevation_approve(&session, action.username, id, auth, hint_group).await?;
+        }
     }
     Ok(())
 }
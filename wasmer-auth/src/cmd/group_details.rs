@@ -26,6 +26,33 @@ pub async fn group_details_command(
     let create = GroupDetailsRequest {
         group,
         session: session.map(|s| s.clone()),
+        database: None,
+    };
+
+    let response: Result<GroupDetailsResponse, GroupDetailsFailed> = chain.invoke(create).await?;
+    let result = response?;
+    debug!("key: {}", result.key);
+    Ok(result)
+}
+
+/// Tells the authentication server that a member of the group is using one of
+/// its databases so that the records within it are moved onto the new keys of
+/// the group whenever they are rotated
+pub async fn group_database_register_command(
+    registry: &Registry,
+    group: String,
+    database: String,
+    auth: Url,
+    session: &AteSessionGroup,
+) -> Result<GroupDetailsResponse, GroupDetailsError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the request and fire it over to the authentication server
+    let create = GroupDetailsRequest {
+        group,
+        session: Some(session.clone()),
+        database: Some(database),
     };
 
     let response: Result<GroupDetailsResponse, GroupDetailsFailed> = chain.invoke(create).await?;
@@ -82,5 +109,18 @@ pub async fn main_group_details(
         }
         println!("");
     }
+    if result.bindings.len() > 0 {
+        println!("# Bindings");
+        println!("");
+        for binding in result.bindings {
+            let access = match (binding.read, binding.write) {
+                (true, true) => "read-write",
+                (true, false) => "read",
+                _ => "write",
+            };
+            println!("- {} => {} ({})", binding.purpose, binding.chain, access);
+        }
+        println!("");
+    }
     Ok(())
 }
@@ -17,6 +17,7 @@ use crate::request::*;
 pub async fn group_remove_command(
     registry: &Registry,
     session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
     auth: Url,
 ) -> Result<GroupRemoveResponse, GroupRemoveError> {
     // Open a command chain
@@ -27,6 +28,7 @@ pub async fn group_remove_command(
     let create = GroupRemoveRequest {
         group,
         session: session.clone(),
+        elevation: elevation.cloned(),
     };
 
     let response: Result<GroupRemoveResponse, GroupRemoveFailed> = chain.invoke(create).await?;
@@ -38,11 +40,12 @@ pub async fn group_remove_command(
 pub async fn main_group_remove(
     auth: Url,
     session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
     hint_group: &str,
 ) -> Result<(), GroupRemoveError> {
     // Remove a user from a group using the authentication server
     let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
-    let result = group_remove_command(&registry, &session, auth).await?;
+    let result = group_remove_command(&registry, &session, elevation, auth).await?;
 
     println!("{} removed (id={})", hint_group, result.key);
 
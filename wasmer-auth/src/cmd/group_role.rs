@@ -0,0 +1,203 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use error_chain::bail;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use url::Url;
+
+use crate::cmd::*;
+use crate::error::*;
+use crate::helper::*;
+use crate::opt::*;
+use crate::prelude::*;
+use crate::request::*;
+
+/// Built-in roles are referred to by their usual names while everything else
+/// is the name of a custom role
+pub fn parse_role_purpose(name: &str) -> AteRolePurpose {
+    match AteRolePurpose::from_str(name) {
+        Ok(a) => a,
+        Err(_) => AteRolePurpose::Other(name.to_string()),
+    }
+}
+
+pub async fn group_role_create_command(
+    registry: &Registry,
+    session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
+    purpose: AteRolePurpose,
+    auth: Url,
+) -> Result<GroupRoleCreateResponse, GroupRoleError> {
+    // Open a command chain
+    let group = session.identity().to_string();
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the create request and fire it over to the authentication server
+    let create = GroupRoleCreateRequest {
+        group,
+        session: session.clone(),
+        elevation: elevation.cloned(),
+        purpose,
+    };
+
+    let response: Result<GroupRoleCreateResponse, GroupRoleFailed> = chain.invoke(create).await?;
+    let result = response?;
+    debug!("key: {}", result.key);
+    Ok(result)
+}
+
+pub async fn group_role_remove_command(
+    registry: &Registry,
+    session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
+    purpose: AteRolePurpose,
+    auth: Url,
+) -> Result<GroupRoleRemoveResponse, GroupRoleError> {
+    // Open a command chain
+    let group = session.identity().to_string();
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the remove request and fire it over to the authentication server
+    let remove = GroupRoleRemoveRequest {
+        group,
+        session: session.clone(),
+        elevation: elevation.cloned(),
+        purpose,
+    };
+
+    let response: Result<GroupRoleRemoveResponse, GroupRoleFailed> = chain.invoke(remove).await?;
+    let result = response?;
+    debug!("key: {}", result.key);
+    Ok(result)
+}
+
+pub async fn group_role_bind_command(
+    registry: &Registry,
+    session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
+    purpose: AteRolePurpose,
+    database: String,
+    read: bool,
+    write: bool,
+    auth: Url,
+) -> Result<GroupRoleBindResponse, GroupRoleError> {
+    // Open a command chain
+    let group = session.identity().to_string();
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Make the bind request and fire it over to the authentication server
+    let bind = GroupRoleBindRequest {
+        group,
+        session: session.clone(),
+        elevation: elevation.cloned(),
+        purpose,
+        chain: database,
+        read,
+        write,
+    };
+
+    let response: Result<GroupRoleBindResponse, GroupRoleFailed> = chain.invoke(bind).await?;
+    let result = response?;
+    debug!("key: {}", result.key);
+    Ok(result)
+}
+
+pub async fn main_opts_group_role(
+    opts: OptsGroupRole,
+    token: Option<String>,
+    token_path: Option<String>,
+    auth: url::Url,
+    hint_group: &str,
+) -> Result<(), GroupRoleError> {
+    let group = match &opts.action {
+        GroupRoleAction::Create(a) => a.group.clone(),
+        GroupRoleAction::Remove(a) => a.group.clone(),
+        GroupRoleAction::Grant(a) => a.group.clone(),
+        GroupRoleAction::Revoke(a) => a.group.clone(),
+        GroupRoleAction::Bind(a) => a.group.clone(),
+        GroupRoleAction::Unbind(a) => a.group.clone(),
+    };
+    let (session, elevation) =
+        main_session_group_elevated(token, token_path, group, Some(auth.clone()), hint_group)
+            .await?;
+
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    match opts.action {
+        GroupRoleAction::Create(action) => {
+            let purpose = AteRolePurpose::Other(action.role);
+            let result =
+                group_role_create_command(&registry, &session, elevation.as_ref(), purpose, auth)
+                    .await?;
+            println!("{} role created (id={})", hint_group, result.key);
+        }
+        GroupRoleAction::Remove(action) => {
+            let purpose = AteRolePurpose::Other(action.role);
+            let result =
+                group_role_remove_command(&registry, &session, elevation.as_ref(), purpose, auth)
+                    .await?;
+            println!("{} role removed (id={})", hint_group, result.key);
+        }
+        GroupRoleAction::Grant(action) => {
+            for role in action.roles {
+                main_group_user_add(
+                    Some(parse_role_purpose(role.as_str())),
+                    Some(action.username.clone()),
+                    auth.clone(),
+                    &session,
+                    elevation.as_ref(),
+                    hint_group,
+                )
+                .await?;
+            }
+        }
+        GroupRoleAction::Revoke(action) => {
+            for role in action.roles {
+                main_group_user_remove(
+                    Some(parse_role_purpose(role.as_str())),
+                    Some(action.username.clone()),
+                    auth.clone(),
+                    &session,
+                    elevation.as_ref(),
+                    hint_group,
+                )
+                .await?;
+            }
+        }
+        GroupRoleAction::Bind(action) => {
+            let (read, write) = match (action.read, action.write) {
+                (false, false) => (true, true),
+                a => a,
+            };
+            let purpose = parse_role_purpose(action.role.as_str());
+            let result = group_role_bind_command(
+                &registry,
+                &session,
+                elevation.as_ref(),
+                purpose,
+                action.database,
+                read,
+                write,
+                auth,
+            )
+            .await?;
+            println!("{} role bound (id={})", hint_group, result.key);
+        }
+        GroupRoleAction::Unbind(action) => {
+            let purpose = parse_role_purpose(action.role.as_str());
+            let result = group_role_bind_command(
+                &registry,
+                &session,
+                elevation.as_ref(),
+                purpose,
+                action.database,
+                false,
+                false,
+                auth,
+            )
+            .await?;
+            println!("{} role unbound (id={})", hint_group, result.key);
+        }
+    }
+    Ok(())
+}
@@ -17,6 +17,7 @@ use crate::request::*;
 pub async fn group_user_remove_command(
     registry: &Registry,
     session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
     purpose: AteRolePurpose,
     username: String,
     auth: Url,
@@ -38,6 +39,7 @@ pub async fn group_user_remove_command(
     let create = GroupUserRemoveRequest {
         group,
         session: session.clone(),
+        elevation: elevation.cloned(),
         who: who.hash(),
         purpose,
     };
@@ -54,6 +56,7 @@ pub async fn main_group_user_remove(
     username: Option<String>,
     auth: Url,
     session: &AteSessionGroup,
+    elevation: Option<&ScopedToken>,
     hint_group: &str,
 ) -> Result<(), GroupUserRemoveError> {
     let purpose = match purpose {
@@ -89,7 +92,8 @@ pub async fn main_group_user_remove(
 
     // Remove a user from a group using the authentication server
     let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
-    let result = group_user_remove_command(&registry, &session, purpose, username, auth).await?;
+    let result =
+        group_user_remove_command(&registry, &session, elevation, purpose, username, auth).await?;
 
     println!("{} user removed (id={})", hint_group, result.key);
 
@@ -10,10 +10,13 @@ use url::Url;
 
 use crate::error::*;
 use crate::helper::*;
+use crate::model::*;
 use crate::opt::*;
 use crate::prelude::*;
 use crate::request::*;
 
+use super::*;
+
 pub async fn login_command(
     registry: &Registry,
     username: String,
@@ -36,6 +39,7 @@ pub async fn login_command(
         email: username.clone(),
         secret: read_key,
         verification_code,
+        token: None,
     };
 
     // Attempt the login request with a 10 second timeout
@@ -56,6 +60,152 @@ pub async fn login_command(
     Ok(result.authority)
 }
 
+pub async fn token_check_command(
+    registry: &Registry,
+    token: &ScopedToken,
+    auth: Url,
+) -> Result<AccessToken, LoginError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Ask the authentication server if the token is still valid (the sealed
+    // session proves that we hold the token)
+    let check = TokenCheckRequest {
+        identity: token.identity.clone(),
+        id: token.token.id.clone(),
+        sealed: token.sealed.clone(),
+    };
+    let response: Result<TokenCheckResponse, TokenFailed> = chain.invoke(check).await?;
+    match response {
+        Ok(a) => Ok(a.token),
+        Err(TokenFailed::Expired(id)) => Err(LoginErrorKind::TokenExpired(id).into()),
+        Err(TokenFailed::Revoked(id)) | Err(TokenFailed::NotFound(id)) => {
+            Err(LoginErrorKind::TokenRevoked(id).into())
+        }
+        Err(TokenFailed::NoAccess) => {
+            Err(LoginErrorKind::TokenRevoked(token.token.id.clone()).into())
+        }
+        Err(TokenFailed::NoMasterKey) => Err(LoginErrorKind::NoMasterKey.into()),
+        Err(TokenFailed::InternalError(code)) => Err(LoginErrorKind::InternalError(code).into()),
+    }
+}
+
+/// Exchanges a scoped token for a fresh session of the user that minted it,
+/// the server checks the token is still valid before it opens the account
+pub async fn login_token_command(
+    registry: &Registry,
+    token: &ScopedToken,
+    auth: Url,
+) -> Result<AteSessionType, LoginError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let login = LoginRequest {
+        email: token.identity.clone(),
+        secret: EncryptKey::generate(KeySize::Bit192),
+        verification_code: None,
+        token: Some(token.clone()),
+    };
+    let response: Result<LoginResponse, LoginFailed> = chain.invoke(login).await?;
+    let result = response?;
+    Ok(match result.sudo {
+        Some(a) => AteSessionType::Sudo(a),
+        None => AteSessionType::User(result.authority),
+    })
+}
+
+/// Converts a token string into the session that it holds, the session of a
+/// scoped token is sealed so it can only be had from the authentication
+/// server (which checks that the token has neither expired nor been revoked)
+pub(crate) async fn token_to_session(
+    token: String,
+    auth_url: Option<&url::Url>,
+) -> Result<AteSessionType, LoginError> {
+    let token = match b64_to_scoped_token(token.as_str()) {
+        Some(a) => a,
+        None => {
+            return b64_to_session(token);
+        }
+    };
+    if token.token.is_expired() {
+        bail!(LoginErrorKind::TokenExpired(token.token.id));
+    }
+    let auth = match auth_url {
+        Some(a) => a.clone(),
+        None => bail!(LoginErrorKind::TokenNotChecked),
+    };
+
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    match token.token.scope.group.clone() {
+        Some(group) => match gather_token_command(&registry, group, &token, auth).await {
+            Ok(a) => Ok(AteSessionType::Group(a)),
+            Err(GatherError(GatherErrorKind::NoAccess, _)) => {
+                bail!(LoginErrorKind::TokenRevoked(token.token.id))
+            }
+            Err(GatherError(GatherErrorKind::NoMasterKey, _)) => {
+                bail!(LoginErrorKind::NoMasterKey)
+            }
+            Err(GatherError(GatherErrorKind::InternalError(code), _)) => {
+                bail!(LoginErrorKind::InternalError(code))
+            }
+            Err(err) => Err(AteError::from(err).into()),
+        },
+        None => login_token_command(&registry, &token, auth).await,
+    }
+}
+
+/// Reads the token that was supplied either as a string or within a file
+async fn main_read_token(
+    token_string: Option<String>,
+    token_file_path: Option<String>,
+) -> Option<String> {
+    let mut token = token_string;
+    if token.is_none() {
+        if let Some(path) = token_file_path {
+            let path = shellexpand::tilde(path.as_str()).to_string();
+            #[cfg(feature = "enable_full")]
+            if let Ok(a) = tokio::fs::read_to_string(path).await {
+                token = Some(a);
+            }
+            #[cfg(not(feature = "enable_full"))]
+            if let Ok(a) = std::fs::read_to_string(path) {
+                token = Some(a);
+            }
+        }
+    }
+    token
+}
+
+/// Fails when the token that was supplied is a scoped token which may not be
+/// used for a particular command (plain sessions may be used for anything),
+/// this only saves a round trip as the server also checks elevations
+pub async fn main_check_operation(
+    token_string: Option<String>,
+    token_file_path: Option<String>,
+    operation: &str,
+) -> Result<(), LoginError> {
+    let token = main_read_token(token_string, token_file_path).await;
+    if let Some(token) = token.as_ref().and_then(|a| b64_to_scoped_token(a.as_str())) {
+        if token.token.scope.allows_operation(operation) == false {
+            bail!(LoginErrorKind::OperationNotAllowed(operation.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the elevation that was supplied (if the token is one), elevations
+/// are never exchanged for a session and are instead sent along with every
+/// privileged command so that the server checks them each time
+pub async fn main_elevation(
+    token_string: Option<String>,
+    token_file_path: Option<String>,
+) -> Option<ScopedToken> {
+    main_read_token(token_string, token_file_path)
+        .await
+        .and_then(|a| b64_to_scoped_token(a.trim()))
+        .filter(|a| a.token.elevated)
+}
+
 pub(crate) async fn main_session_start(
     token_string: Option<String>,
     token_file_path: Option<String>,
@@ -72,11 +222,11 @@ pub(crate) async fn main_session_start(
             let path = shellexpand::tilde(path.as_str()).to_string();
             #[cfg(feature = "enable_full")]
             if let Ok(token) = tokio::fs::read_to_string(path).await {
-                session = Some(b64_to_session(token));
+                session = Some(token_to_session(token, auth_url.as_ref()).await?);
             }
             #[cfg(not(feature = "enable_full"))]
             if let Ok(token) = std::fs::read_to_string(path) {
-                session = Some(b64_to_session(token));
+                session = Some(token_to_session(token, auth_url.as_ref()).await?);
             }
         }
     }
@@ -84,7 +234,7 @@ pub(crate) async fn main_session_start(
     // The session might be supplied as a base64 string
     if session.is_none() {
         if let Some(token) = token_string {
-            session = Some(b64_to_session(token));
+            session = Some(token_to_session(token, auth_url.as_ref()).await?);
         }
     }
 
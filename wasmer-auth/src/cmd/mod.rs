@@ -1,31 +1,41 @@
+pub mod audit;
 pub mod create_group;
 pub mod create_user;
 pub mod database;
+pub mod elevate;
+pub mod email_recovery;
 pub mod gather;
 pub mod group;
 pub mod group_details;
 pub mod group_remove;
+pub mod group_role;
 pub mod group_user_add;
 pub mod group_user_remove;
 pub mod login;
 pub mod query;
 pub mod reset;
+pub mod ssh_key;
 pub mod sudo;
 pub mod token;
 pub mod user;
 
+pub use audit::*;
 pub use create_group::*;
 pub use create_user::*;
 pub use database::*;
+pub use elevate::*;
+pub use email_recovery::*;
 pub use gather::*;
 pub use group::*;
 pub use group_details::*;
 pub use group_remove::*;
+pub use group_role::*;
 pub use group_user_add::*;
 pub use group_user_remove::*;
 pub use login::*;
 pub use query::*;
 pub use reset::*;
+pub use ssh_key::*;
 pub use sudo::*;
 pub use token::*;
 pub use user::*;
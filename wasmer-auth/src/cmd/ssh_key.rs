@@ -0,0 +1,159 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use error_chain::bail;
+use std::io::stdout;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use url::Url;
+
+use crate::cmd::*;
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::opt::*;
+use crate::prelude::*;
+use crate::request::*;
+
+pub async fn ssh_key_add_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    key: SshKey,
+    auth: Url,
+) -> Result<SshKeyAddResponse, SshKeyError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let add = SshKeyAddRequest {
+        session: session.clone(),
+        key,
+    };
+    let response: Result<SshKeyAddResponse, SshKeyFailed> = chain.invoke(add).await?;
+    Ok(response?)
+}
+
+pub async fn ssh_key_list_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    auth: Url,
+) -> Result<SshKeyListResponse, SshKeyError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let list = SshKeyListRequest {
+        session: session.clone(),
+    };
+    let response: Result<SshKeyListResponse, SshKeyFailed> = chain.invoke(list).await?;
+    Ok(response?)
+}
+
+pub async fn ssh_key_remove_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    public_key: String,
+    auth: Url,
+) -> Result<SshKeyRemoveResponse, SshKeyError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let remove = SshKeyRemoveRequest {
+        session: session.clone(),
+        public_key,
+    };
+    let response: Result<SshKeyRemoveResponse, SshKeyFailed> = chain.invoke(remove).await?;
+    Ok(response?)
+}
+
+/// Reads an OpenSSH public key from a file or, if no such file exists, treats
+/// the argument as the key itself
+fn read_ssh_key(key: &str) -> String {
+    let path = shellexpand::tilde(key).to_string();
+    match std::fs::read_to_string(path) {
+        Ok(a) => a,
+        Err(_) => key.to_string(),
+    }
+}
+
+pub async fn main_add_ssh_key(
+    session: AteSessionInner,
+    key: String,
+    comment: Option<String>,
+    auth: Url,
+) -> Result<(), SshKeyError> {
+    let mut key = match SshKey::from_openssh(read_ssh_key(key.as_str()).as_str()) {
+        Some(a) => a,
+        None => {
+            bail!(SshKeyErrorKind::InvalidKey);
+        }
+    };
+    if let Some(comment) = comment {
+        key.comment = comment;
+    }
+
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = ssh_key_add_command(&registry, &session, key, auth).await?;
+
+    println!("SSH key added ({})", result.key.comment);
+    Ok(())
+}
+
+pub async fn main_list_ssh_keys(session: AteSessionInner, auth: Url) -> Result<(), SshKeyError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = ssh_key_list_command(&registry, &session, auth).await?;
+
+    println!("# SSH Keys");
+    println!("");
+    for key in result.keys {
+        let key_type = key
+            .key_type()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let fingerprint = key.fingerprint().unwrap_or_default();
+        println!(
+            "{} {} {} (added {})",
+            key_type, fingerprint, key.comment, key.added
+        );
+    }
+    Ok(())
+}
+
+pub async fn main_remove_ssh_key(
+    session: AteSessionInner,
+    key: String,
+    auth: Url,
+) -> Result<(), SshKeyError> {
+    // The key can be supplied as a file, an OpenSSH key or its fingerprint
+    let key = read_ssh_key(key.as_str());
+    let public_key = match SshKey::from_openssh(key.as_str()) {
+        Some(a) => a.public_key().unwrap_or_default().to_string(),
+        None => key.trim().to_string(),
+    };
+
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = ssh_key_remove_command(&registry, &session, public_key, auth).await?;
+
+    println!("SSH key removed ({})", result.key.comment);
+    Ok(())
+}
+
+pub async fn main_opts_ssh_key(
+    opts_ssh_key: OptsSshKey,
+    token: Option<String>,
+    token_path: Option<String>,
+    auth: url::Url,
+) -> Result<(), AteError> {
+    let session = main_session_user(token, token_path, Some(auth.clone())).await?;
+    let session = AteSessionInner::User(session);
+    match opts_ssh_key.action {
+        SshKeyAction::Add(action) => {
+            main_add_ssh_key(session, action.key, action.comment, auth).await?;
+        }
+        SshKeyAction::List(_action) => {
+            main_list_ssh_keys(session, auth).await?;
+        }
+        SshKeyAction::Remove(action) => {
+            main_remove_ssh_key(session, action.key, auth).await?;
+        }
+    }
+    Ok(())
+}
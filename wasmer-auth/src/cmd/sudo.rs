@@ -138,6 +138,13 @@ pub async fn main_sudo(
             );
             std::process::exit(1);
         }
+        Err(SudoError(SudoErrorKind::BreakGlass(group), _)) => {
+            eprintln!(
+                "Sudo is disabled - request an elevation that an owner of the group ({}) approves instead.",
+                group
+            );
+            std::process::exit(1);
+        }
         Err(err) => {
             bail!(err);
         }
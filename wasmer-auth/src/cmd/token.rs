@@ -10,10 +10,154 @@ use url::Url;
 use crate::cmd::*;
 use crate::error::*;
 use crate::helper::*;
+use crate::model::*;
 use crate::opt::*;
 use crate::prelude::*;
 use crate::request::*;
 
+pub async fn token_create_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    token: AccessToken,
+    grant: Option<AteSessionType>,
+    auth: Url,
+) -> Result<TokenCreateResponse, TokenError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    // Record the token with the authentication server so that it can be revoked
+    // (the server seals the session that the token hands out)
+    let create = TokenCreateRequest {
+        session: session.clone(),
+        token,
+        grant,
+    };
+    let response: Result<TokenCreateResponse, TokenFailed> = chain.invoke(create).await?;
+    let result = response?;
+    debug!("id: {}", result.id);
+    Ok(result)
+}
+
+pub async fn token_list_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    auth: Url,
+) -> Result<TokenListResponse, TokenError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let list = TokenListRequest {
+        session: session.clone(),
+    };
+    let response: Result<TokenListResponse, TokenFailed> = chain.invoke(list).await?;
+    Ok(response?)
+}
+
+pub async fn token_revoke_command(
+    registry: &Registry,
+    session: &AteSessionInner,
+    id: AteHash,
+    auth: Url,
+) -> Result<TokenRevokeResponse, TokenError> {
+    // Open a command chain
+    let chain = registry.open_cmd(&auth).await?;
+
+    let revoke = TokenRevokeRequest {
+        session: session.clone(),
+        id,
+    };
+    let response: Result<TokenRevokeResponse, TokenFailed> = chain.invoke(revoke).await?;
+    Ok(response?)
+}
+
+pub async fn main_mint_token(
+    name: String,
+    user: AteSessionInner,
+    session: AteSessionType,
+    scope: TokenScope,
+    expires: Option<chrono::DateTime<chrono::Utc>>,
+    auth: Url,
+) -> Result<(), TokenError> {
+    let token = AccessToken {
+        id: AteHash::generate(),
+        name,
+        issued: chrono::Utc::now(),
+        expires,
+        scope,
+        revoked: false,
+        elevated: false,
+    };
+
+    // Record the token before it is handed out
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = token_create_command(&registry, &user, token.clone(), Some(session), auth).await?;
+    let sealed = match result.sealed {
+        Some(a) => a,
+        None => bail!(TokenErrorKind::NoAccess),
+    };
+
+    let token = ScopedToken {
+        identity: user.identity().to_string(),
+        token,
+        sealed,
+    };
+
+    if is_tty_stdout() {
+        eprintln!("The token string below can be used to secure your file system.\n");
+    }
+    println!("{}", scoped_token_to_b64(&token)?);
+    Ok(())
+}
+
+pub async fn main_list_tokens(session: AteSessionInner, auth: Url) -> Result<(), TokenError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = token_list_command(&registry, &session, auth).await?;
+
+    println!("# Access Tokens");
+    println!("");
+    for token in result.tokens {
+        println!("## {}", token.name);
+        println!("");
+        println!("id: {}", token.id);
+        println!("issued: {}", token.issued);
+        match token.expires {
+            Some(a) => println!("expires: {}", a),
+            None => println!("expires: never"),
+        }
+        if let Some(group) = token.scope.group.as_ref() {
+            println!("group: {}", group);
+        }
+        for role in token.scope.roles.iter() {
+            println!("role: {}", role);
+        }
+        for chain in token.scope.chains.iter() {
+            println!("chain: {}", chain);
+        }
+        for operation in token.scope.operations.iter() {
+            println!("operation: {}", operation);
+        }
+        if token.revoked {
+            println!("[revoked]");
+        } else if token.is_expired() {
+            println!("[expired]");
+        }
+        println!("");
+    }
+    Ok(())
+}
+
+pub async fn main_revoke_token(
+    session: AteSessionInner,
+    id: AteHash,
+    auth: Url,
+) -> Result<(), TokenError> {
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let result = token_revoke_command(&registry, &session, id, auth).await?;
+
+    println!("Token revoked (id={})", result.id);
+    Ok(())
+}
+
 pub async fn main_opts_token(
     opts_token: OptsToken,
     token: Option<String>,
@@ -21,6 +165,12 @@ pub async fn main_opts_token(
     auth: url::Url,
     hint_group: &str,
 ) -> Result<(), AteError> {
+    main_check_operation(
+        token.clone(),
+        token_path.clone(),
+        opts_token.action.operation(),
+    )
+    .await?;
     match opts_token.action {
         TokenAction::Generate(action) => {
             let session = main_login(action.email, action.password, auth).await?;
@@ -74,6 +224,99 @@ pub async fn main_opts_token(
             eprintln!("The token contains the following claims.\n");
             println!("{}", session);
         }
+        TokenAction::Mint(action) => {
+            let scope = TokenScope {
+                group: action.group.clone(),
+                roles: action.roles,
+                chains: Vec::new(),
+                operations: action.operations,
+            };
+            let expires = action
+                .expires
+                .map(|hours| chrono::Utc::now() + chrono::Duration::hours(hours as i64));
+
+            // Tokens that are limited to a group only hold the roles within that group
+            // while the user session is only used to record the token
+            let (user, session) = match action.group {
+                Some(group) => {
+                    let session = main_session_group(
+                        token.clone(),
+                        token_path.clone(),
+                        group,
+                        action.sudo,
+                        None,
+                        Some(auth.clone()),
+                        hint_group,
+                    )
+                    .await?;
+                    let user = session.inner.clone();
+                    let session = scope_group_session(session, &scope);
+                    (user, AteSessionType::Group(session))
+                }
+                None => {
+                    let session =
+                        main_session_user(token.clone(), token_path.clone(), Some(auth.clone()))
+                            .await?;
+                    let session = match action.sudo {
+                        true => {
+                            AteSessionInner::Sudo(main_sudo(session, None, auth.clone()).await?)
+                        }
+                        false => AteSessionInner::User(session),
+                    };
+                    (session.clone(), session.into())
+                }
+            };
+
+            main_mint_token(action.name, user, session, scope, expires, auth).await?;
+        }
+        TokenAction::List(_action) => {
+            let session =
+                main_session_user(token.clone(), token_path.clone(), Some(auth.clone())).await?;
+            main_list_tokens(AteSessionInner::User(session), auth).await?;
+        }
+        TokenAction::Revoke(action) => {
+            let id = match AteHash::from_hex_string(action.id.as_str()) {
+                Some(a) => a,
+                None => {
+                    eprintln!("The token identifier ({}) is not valid", action.id);
+                    std::process::exit(1);
+                }
+            };
+            let session =
+                main_session_user(token.clone(), token_path.clone(), Some(auth.clone())).await?;
+            main_revoke_token(AteSessionInner::User(session), id, auth).await?;
+        }
+        TokenAction::Elevate(action) => {
+            let redeem = match action.redeem {
+                Some(id) => match AteHash::from_hex_string(id.as_str()) {
+                    Some(a) => Some(a),
+                    None => {
+                        eprintln!("The elevation identifier ({}) is not valid", id);
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
+            let scope = TokenScope {
+                group: action.group,
+                roles: action.roles,
+                chains: Vec::new(),
+                operations: action.operations,
+            };
+            let session = main_login(action.email, action.password, auth.clone()).await?;
+            main_elevate(
+                session,
+                action.code,
+                action.minutes,
+                scope,
+                action.approval,
+                action.reason,
+                redeem,
+                auth,
+                hint_group,
+            )
+            .await?;
+        }
     }
     Ok(())
 }
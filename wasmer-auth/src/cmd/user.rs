@@ -20,6 +20,12 @@ pub async fn main_opts_user(
     token_path: Option<String>,
     auth: url::Url,
 ) -> Result<(), AteError> {
+    main_check_operation(
+        token.clone(),
+        token_path.clone(),
+        opts_user.action.operation(),
+    )
+    .await?;
     match opts_user.action {
         UserAction::Create(action) => {
             let _session = main_create_user(action.email, action.password, auth).await?;
@@ -40,6 +46,30 @@ pub async fn main_opts_user(
             )
             .await?;
         }
+        UserAction::RecoverEmail(action) => {
+            let _session = main_email_reset(
+                action.email,
+                action.code,
+                action.auth_code,
+                action.new_password,
+                auth,
+            )
+            .await?;
+        }
+        UserAction::EnrolEmail(action) => {
+            let session =
+                main_session_user(token.clone(), token_path.clone(), Some(auth.clone())).await?;
+            main_email_recovery_enrol(AteSessionInner::User(session), !action.disable, auth)
+                .await?;
+        }
+        UserAction::SshKey(action) => {
+            main_opts_ssh_key(action, token, token_path, auth).await?;
+        }
+        UserAction::Audit(action) => {
+            let session =
+                main_session_user(token.clone(), token_path.clone(), Some(auth.clone())).await?;
+            main_audit(AteSessionInner::User(session), action.limit, auth).await?;
+        }
     }
     Ok(())
 }
@@ -0,0 +1,49 @@
+use error_chain::error_chain;
+
+use crate::request::*;
+use ::ate::prelude::*;
+
+error_chain! {
+    types {
+        AuditError, AuditErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+        ChainCreationError(::ate::error::ChainCreationError, ::ate::error::ChainCreationErrorKind);
+        SerializationError(::ate::error::SerializationError, ::ate::error::SerializationErrorKind);
+        InvokeError(::ate::error::InvokeError, ::ate::error::InvokeErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        NoAccess {
+            description("audit query failed as the session does not belong to the identity")
+            display("audit query failed as the session does not belong to the identity")
+        }
+        NoMasterKey {
+            description("audit query failed as the server has not been properly initialized")
+            display("audit query failed as the server has not been properly initialized")
+        }
+        InternalError(code: u16) {
+            description("audit query failed as the server experienced an internal error")
+            display("audit query failed as the server experienced an internal error - code={}", code)
+        }
+    }
+}
+
+impl From<AuditError> for AteError {
+    fn from(err: AuditError) -> AteError {
+        AteErrorKind::ServiceError(err.to_string()).into()
+    }
+}
+
+impl From<AuditListFailed> for AuditError {
+    fn from(err: AuditListFailed) -> AuditError {
+        match err {
+            AuditListFailed::NoAccess => AuditErrorKind::NoAccess.into(),
+            AuditListFailed::NoMasterKey => AuditErrorKind::NoMasterKey.into(),
+            AuditListFailed::InternalError(code) => AuditErrorKind::InternalError(code).into(),
+        }
+    }
+}
@@ -101,6 +101,12 @@ impl From<CreateGroupFailed> for CreateError {
     }
 }
 
+impl std::fmt::Display for CreateGroupFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", CreateError::from(self.clone()))
+    }
+}
+
 impl From<CreateUserFailed> for CreateError {
     fn from(err: CreateUserFailed) -> CreateError {
         match err {
@@ -0,0 +1,112 @@
+use error_chain::error_chain;
+
+use crate::request::*;
+use ::ate::prelude::*;
+
+error_chain! {
+    types {
+        ElevationError, ElevationErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+        ChainCreationError(::ate::error::ChainCreationError, ::ate::error::ChainCreationErrorKind);
+        SerializationError(::ate::error::SerializationError, ::ate::error::SerializationErrorKind);
+        InvokeError(::ate::error::InvokeError, ::ate::error::InvokeErrorKind);
+        SudoError(super::SudoError, super::SudoErrorKind);
+        GatherError(super::GatherError, super::GatherErrorKind);
+        QueryError(super::QueryError, super::QueryErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        NotFound(id: AteHash) {
+            description("the elevation does not exist"),
+            display("the elevation ({}) does not exist", id),
+        }
+        NotApproved(id: AteHash) {
+            description("the elevation has not yet been approved"),
+            display("the elevation ({}) has not yet been approved", id),
+        }
+        AlreadyRedeemed(id: AteHash) {
+            description("the elevation has already been redeemed"),
+            display("the elevation ({}) has already been redeemed", id),
+        }
+        Expired(id: AteHash) {
+            description("the elevation has expired"),
+            display("the elevation ({}) has expired", id),
+        }
+        InvalidDuration(minutes: u32) {
+            description("the elevation period is not valid"),
+            display("the elevation period ({} minutes) is not valid", minutes),
+        }
+        OutOfScope(operation: String) {
+            description("the elevation may not be used for this operation"),
+            display("the elevation may not be used for this operation ({})", operation),
+        }
+        UnsupportedScope {
+            description("the elevation can not be limited to chains"),
+            display("the elevation can not be limited to chains"),
+        }
+        InvalidArguments {
+            description("you did not provide the right type or quantity of arguments")
+            display("you did not provide the right type or quantity of arguments")
+        }
+        SelfApproval {
+            description("an elevation must be approved by someone else"),
+            display("an elevation must be approved by someone else"),
+        }
+        GroupNotFound {
+            description("the group that approves the elevation does not exist"),
+            display("the group that approves the elevation does not exist"),
+        }
+        NoAccess {
+            description("only an owner of the group may approve the elevation"),
+            display("only an owner of the group may approve the elevation"),
+        }
+        NoMasterKey {
+            description("the elevation failed as the server has not been properly initialized"),
+            display("the elevation failed as the server has not been properly initialized"),
+        }
+        InternalError(code: u16) {
+            description("the elevation failed as the server experienced an internal error")
+            display("the elevation failed as the server experienced an internal error - code={}", code)
+        }
+    }
+}
+
+impl From<ElevationError> for AteError {
+    fn from(err: ElevationError) -> AteError {
+        AteErrorKind::ServiceError(err.to_string()).into()
+    }
+}
+
+impl From<ElevationFailed> for ElevationError {
+    fn from(err: ElevationFailed) -> ElevationError {
+        match err {
+            ElevationFailed::NotFound(id) => ElevationErrorKind::NotFound(id).into(),
+            ElevationFailed::NotApproved(id) => ElevationErrorKind::NotApproved(id).into(),
+            ElevationFailed::AlreadyRedeemed(id) => ElevationErrorKind::AlreadyRedeemed(id).into(),
+            ElevationFailed::Expired(id) => ElevationErrorKind::Expired(id).into(),
+            ElevationFailed::InvalidDuration(minutes) => {
+                ElevationErrorKind::InvalidDuration(minutes).into()
+            }
+            ElevationFailed::OutOfScope(operation) => {
+                ElevationErrorKind::OutOfScope(operation).into()
+            }
+            ElevationFailed::UnsupportedScope => ElevationErrorKind::UnsupportedScope.into(),
+            ElevationFailed::SelfApproval => ElevationErrorKind::SelfApproval.into(),
+            ElevationFailed::GroupNotFound => ElevationErrorKind::GroupNotFound.into(),
+            ElevationFailed::NoAccess => ElevationErrorKind::NoAccess.into(),
+            ElevationFailed::NoMasterKey => ElevationErrorKind::NoMasterKey.into(),
+            ElevationFailed::SudoFailed(err) => super::SudoError::from(err).into(),
+            ElevationFailed::InternalError(code) => ElevationErrorKind::InternalError(code).into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ElevationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", ElevationError::from(self.clone()))
+    }
+}
@@ -0,0 +1,108 @@
+use error_chain::error_chain;
+
+use crate::request::*;
+use ::ate::prelude::*;
+
+error_chain! {
+    types {
+        EmailRecoveryError, EmailRecoveryErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+        ChainCreationError(::ate::error::ChainCreationError, ::ate::error::ChainCreationErrorKind);
+        SerializationError(::ate::error::SerializationError, ::ate::error::SerializationErrorKind);
+        InvokeError(::ate::error::InvokeError, ::ate::error::InvokeErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        MailDisabled {
+            description("recovery by email is not enabled on this server")
+            display("recovery by email is not enabled on this server")
+        }
+        MissingToken {
+            description("the session does not hold the token that is needed to enrol for recovery by email")
+            display("the session does not hold the token that is needed to enrol for recovery by email")
+        }
+        NoAccess {
+            description("the session does not have access to this account")
+            display("the session does not have access to this account")
+        }
+        PasswordMismatch {
+            description("recovery failed as the passwords did not match")
+            display("recovery failed as the passwords did not match")
+        }
+        InvalidCode {
+            description("the emailed code is not valid or has expired")
+            display("the emailed code is not valid or has expired")
+        }
+        InvalidAuthenticatorCode {
+            description("the supplied authenticator code was not valid")
+            display("the supplied authenticator code was not valid")
+        }
+        RecoveryImpossible {
+            description("recovery of this account is impossible")
+            display("recovery of this account is impossible")
+        }
+        NoMasterKey {
+            description("recovery failed as the server has not been properly initialized")
+            display("recovery failed as the server has not been properly initialized")
+        }
+        InternalError(code: u16) {
+            description("recovery failed as the server experienced an internal error")
+            display("recovery failed as the server experienced an internal error - code={}", code)
+        }
+    }
+}
+
+impl From<EmailRecoveryError> for AteError {
+    fn from(err: EmailRecoveryError) -> AteError {
+        AteErrorKind::ServiceError(err.to_string()).into()
+    }
+}
+
+impl From<EmailRecoveryFailed> for EmailRecoveryError {
+    fn from(err: EmailRecoveryFailed) -> EmailRecoveryError {
+        match err {
+            EmailRecoveryFailed::MailDisabled => EmailRecoveryErrorKind::MailDisabled.into(),
+            EmailRecoveryFailed::MissingToken => EmailRecoveryErrorKind::MissingToken.into(),
+            EmailRecoveryFailed::NoAccess => EmailRecoveryErrorKind::NoAccess.into(),
+            EmailRecoveryFailed::NoMasterKey => EmailRecoveryErrorKind::NoMasterKey.into(),
+            EmailRecoveryFailed::InternalError(code) => {
+                EmailRecoveryErrorKind::InternalError(code).into()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for EmailRecoveryFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", EmailRecoveryError::from(self.clone()))
+    }
+}
+
+impl From<EmailResetFailed> for EmailRecoveryError {
+    fn from(err: EmailResetFailed) -> EmailRecoveryError {
+        match err {
+            EmailResetFailed::MailDisabled => EmailRecoveryErrorKind::MailDisabled.into(),
+            EmailResetFailed::InvalidCode => EmailRecoveryErrorKind::InvalidCode.into(),
+            EmailResetFailed::InvalidAuthenticatorCode => {
+                EmailRecoveryErrorKind::InvalidAuthenticatorCode.into()
+            }
+            EmailResetFailed::RecoveryImpossible => {
+                EmailRecoveryErrorKind::RecoveryImpossible.into()
+            }
+            EmailResetFailed::NoMasterKey => EmailRecoveryErrorKind::NoMasterKey.into(),
+            EmailResetFailed::InternalError(code) => {
+                EmailRecoveryErrorKind::InternalError(code).into()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for EmailResetFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", EmailRecoveryError::from(self.clone()))
+    }
+}
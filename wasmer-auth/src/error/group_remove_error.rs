@@ -56,3 +56,9 @@ impl From<GroupRemoveFailed> for GroupRemoveError {
         }
     }
 }
+
+impl std::fmt::Display for GroupRemoveFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", GroupRemoveError::from(self.clone()))
+    }
+}
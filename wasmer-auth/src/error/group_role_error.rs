@@ -0,0 +1,89 @@
+use error_chain::error_chain;
+
+use crate::request::*;
+use ::ate::prelude::*;
+
+error_chain! {
+    types {
+        GroupRoleError, GroupRoleErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+        ChainCreationError(::ate::error::ChainCreationError, ::ate::error::ChainCreationErrorKind);
+        SerializationError(::ate::error::SerializationError, ::ate::error::SerializationErrorKind);
+        InvokeError(::ate::error::InvokeError, ::ate::error::InvokeErrorKind);
+        GatherError(super::GatherError, super::GatherErrorKind);
+        GroupDetailsError(super::GroupDetailsError, super::GroupDetailsErrorKind);
+        GroupUserAddError(super::GroupUserAddError, super::GroupUserAddErrorKind);
+        GroupUserRemoveError(super::GroupUserRemoveError, super::GroupUserRemoveErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        GroupNotFound {
+            description("group role operation failed as the group does not exist")
+            display("group role operation failed as the group does not exist")
+        }
+        RoleNotFound {
+            description("group role operation failed as the group role does not exist")
+            display("group role operation failed as the group role does not exist")
+        }
+        RoleExists {
+            description("group role operation failed as the group role already exists")
+            display("group role operation failed as the group role already exists")
+        }
+        BuiltInRole {
+            description("group role operation failed as built-in roles can not be created or removed")
+            display("group role operation failed as built-in roles can not be created or removed")
+        }
+        InvalidRole {
+            description("group role operation failed as the role name is invalid")
+            display("group role operation failed as the role name is invalid (only letters, digits, '_' and '-' are allowed)")
+        }
+        InvalidChain {
+            description("group role operation failed as the database name is invalid")
+            display("group role operation failed as the database name is invalid (only letters, digits and '_' are allowed)")
+        }
+        NoAccess {
+            description("group role operation failed as the referrer has no access to this group")
+            display("group role operation failed as the referrer has no access to this group")
+        }
+        NoMasterKey {
+            description("group role operation failed as the server has not been properly initialized")
+            display("group role operation failed as the server has not been properly initialized")
+        }
+        InternalError(code: u16) {
+            description("group role operation failed as the server experienced an internal error")
+            display("group role operation failed as the server experienced an internal error - code={}", code)
+        }
+    }
+}
+
+impl From<GroupRoleError> for AteError {
+    fn from(err: GroupRoleError) -> AteError {
+        AteErrorKind::ServiceError(err.to_string()).into()
+    }
+}
+
+impl From<GroupRoleFailed> for GroupRoleError {
+    fn from(err: GroupRoleFailed) -> GroupRoleError {
+        match err {
+            GroupRoleFailed::GroupNotFound => GroupRoleErrorKind::GroupNotFound.into(),
+            GroupRoleFailed::RoleNotFound => GroupRoleErrorKind::RoleNotFound.into(),
+            GroupRoleFailed::RoleExists => GroupRoleErrorKind::RoleExists.into(),
+            GroupRoleFailed::BuiltInRole => GroupRoleErrorKind::BuiltInRole.into(),
+            GroupRoleFailed::InvalidRole => GroupRoleErrorKind::InvalidRole.into(),
+            GroupRoleFailed::InvalidChain => GroupRoleErrorKind::InvalidChain.into(),
+            GroupRoleFailed::NoAccess => GroupRoleErrorKind::NoAccess.into(),
+            GroupRoleFailed::NoMasterKey => GroupRoleErrorKind::NoMasterKey.into(),
+            GroupRoleFailed::InternalError(code) => GroupRoleErrorKind::InternalError(code).into(),
+        }
+    }
+}
+
+impl std::fmt::Display for GroupRoleFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", GroupRoleError::from(self.clone()))
+    }
+}
@@ -66,3 +66,9 @@ impl From<GroupUserAddFailed> for GroupUserAddError {
         }
     }
 }
+
+impl std::fmt::Display for GroupUserAddFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", GroupUserAddError::from(self.clone()))
+    }
+}
@@ -43,6 +43,10 @@ error_chain! {
             description("group user remove failed as the user is not a member of this group role")
             display("group user remove failed as the user is not a member of this group role")
         }
+        MemberNotFound(identity: String) {
+            description("group user remove failed as the keys of one of the remaining members could not be found"),
+            display("group user remove failed as the keys of the remaining member ({}) could not be found - remove them first", identity),
+        }
         InternalError(code: u16) {
             description("group user remove failed as the server experienced an internal error")
             display("group user remove failed as the server experienced an internal error - code={}", code)
@@ -66,9 +70,18 @@ impl From<GroupUserRemoveFailed> for GroupUserRemoveError {
                 GroupUserRemoveErrorKind::NothingToRemove.into()
             }
             GroupUserRemoveFailed::RoleNotFound => GroupUserRemoveErrorKind::RoleNotFound.into(),
+            GroupUserRemoveFailed::MemberNotFound(identity) => {
+                GroupUserRemoveErrorKind::MemberNotFound(identity).into()
+            }
             GroupUserRemoveFailed::InternalError(code) => {
                 GroupUserRemoveErrorKind::InternalError(code).into()
             }
         }
     }
 }
+
+impl std::fmt::Display for GroupUserRemoveFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", GroupUserRemoveError::from(self.clone()))
+    }
+}
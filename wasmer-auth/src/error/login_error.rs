@@ -46,6 +46,22 @@ error_chain! {
             description("login failed due to an incorrect password")
             display("login failed due to an incorrect password")
         }
+        TokenRevoked(id: AteHash) {
+            description("login failed as the access token has been revoked"),
+            display("login failed as the access token ({}) has been revoked", id),
+        }
+        TokenExpired(id: AteHash) {
+            description("login failed as the access token has expired"),
+            display("login failed as the access token ({}) has expired", id),
+        }
+        TokenNotChecked {
+            description("the access token must be checked by the authentication server before it is used"),
+            display("the access token must be checked by the authentication server before it is used"),
+        }
+        OperationNotAllowed(operation: String) {
+            description("the access token is not allowed to be used for this command"),
+            display("the access token is not allowed to be used for this command ({})", operation),
+        }
         InternalError(code: u16) {
             description("login failed as the server experienced an internal error")
             display("login failed as the server experienced an internal error - code={}", code)
@@ -65,9 +81,17 @@ impl From<LoginFailed> for LoginError {
             LoginFailed::AccountLocked(duration) => LoginErrorKind::AccountLocked(duration).into(),
             LoginFailed::NoMasterKey => LoginErrorKind::NoMasterKey.into(),
             LoginFailed::Unverified(username) => LoginErrorKind::Unverified(username).into(),
+            LoginFailed::TokenRevoked(id) => LoginErrorKind::TokenRevoked(id).into(),
+            LoginFailed::TokenExpired(id) => LoginErrorKind::TokenExpired(id).into(),
             LoginFailed::UserNotFound(username) => LoginErrorKind::NotFound(username).into(),
             LoginFailed::WrongPassword => LoginErrorKind::WrongPassword.into(),
             LoginFailed::InternalError(code) => LoginErrorKind::InternalError(code).into(),
         }
     }
 }
+
+impl std::fmt::Display for LoginFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", LoginError::from(self.clone()))
+    }
+}
@@ -0,0 +1,28 @@
+use error_chain::error_chain;
+
+error_chain! {
+    types {
+        MailError, MailErrorKind, ResultExt, Result;
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        InvalidHeader(value: String) {
+            description("the mail could not be sent as one of its headers is invalid"),
+            display("the mail could not be sent as one of its headers is invalid ({})", value),
+        }
+        Rejected(code: u16, reply: String) {
+            description("the mail server rejected the mail"),
+            display("the mail server rejected the mail - {} {}", code, reply),
+        }
+        InvalidReply(reply: String) {
+            description("the mail server sent a reply that could not be understood"),
+            display("the mail server sent a reply that could not be understood ({})", reply),
+        }
+        Timeout {
+            description("the mail server took too long to respond")
+            display("the mail server took too long to respond")
+        }
+    }
+}
@@ -1,31 +1,58 @@
+mod audit_error;
 mod create_error;
+mod elevation_error;
+mod email_recovery_error;
 mod gather_error;
 mod group_details_error;
 mod group_remove_error;
+mod group_role_error;
 mod group_user_add_error;
 mod group_user_remove_error;
 mod login_error;
+mod mail_error;
+#[cfg(feature = "server")]
+mod oidc_error;
 mod query_error;
 mod reset_error;
+mod ssh_key_error;
 mod sudo_error;
+mod token_error;
 
+pub use audit_error::AuditError;
+pub use audit_error::AuditErrorKind;
 pub use create_error::CreateError;
 pub use create_error::CreateErrorKind;
+pub use elevation_error::ElevationError;
+pub use elevation_error::ElevationErrorKind;
+pub use email_recovery_error::EmailRecoveryError;
+pub use email_recovery_error::EmailRecoveryErrorKind;
 pub use gather_error::GatherError;
 pub use gather_error::GatherErrorKind;
 pub use group_details_error::GroupDetailsError;
 pub use group_details_error::GroupDetailsErrorKind;
 pub use group_remove_error::GroupRemoveError;
 pub use group_remove_error::GroupRemoveErrorKind;
+pub use group_role_error::GroupRoleError;
+pub use group_role_error::GroupRoleErrorKind;
 pub use group_user_add_error::GroupUserAddError;
 pub use group_user_add_error::GroupUserAddErrorKind;
 pub use group_user_remove_error::GroupUserRemoveError;
 pub use group_user_remove_error::GroupUserRemoveErrorKind;
 pub use login_error::LoginError;
 pub use login_error::LoginErrorKind;
+pub use mail_error::MailError;
+pub use mail_error::MailErrorKind;
+#[cfg(feature = "server")]
+pub use oidc_error::OidcError;
+#[cfg(feature = "server")]
+pub use oidc_error::OidcErrorKind;
 pub use query_error::QueryError;
 pub use query_error::QueryErrorKind;
 pub use reset_error::ResetError;
 pub use reset_error::ResetErrorKind;
+pub use ssh_key_error::SshKeyError;
+pub use ssh_key_error::SshKeyErrorKind;
 pub use sudo_error::SudoError;
 pub use sudo_error::SudoErrorKind;
+pub use token_error::TokenError;
+pub use token_error::TokenErrorKind;
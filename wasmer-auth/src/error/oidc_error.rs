@@ -0,0 +1,61 @@
+use error_chain::error_chain;
+
+error_chain! {
+    types {
+        OidcError, OidcErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+        Hyper(hyper::Error);
+        Http(hyper::http::Error);
+        SerdeJson(serde_json::Error);
+    }
+    errors {
+        NotFound {
+            description("the requested endpoint does not exist")
+            display("the requested endpoint does not exist")
+        }
+        InvalidRequest(reason: String) {
+            description("the request is missing a parameter or is otherwise malformed"),
+            display("the request is invalid - {}", reason),
+        }
+        InvalidClient {
+            description("the client or its redirect address is not registered")
+            display("the client or its redirect address is not registered")
+        }
+        InvalidGrant(reason: String) {
+            description("the authorization code is invalid, expired or has already been used"),
+            display("the authorization code is not valid - {}", reason),
+        }
+        UnsupportedGrantType(grant_type: String) {
+            description("the grant type is not supported"),
+            display("the grant type ({}) is not supported", grant_type),
+        }
+        InvalidToken {
+            description("the access token is invalid or has expired")
+            display("the access token is invalid or has expired")
+        }
+        BadKey(reason: String) {
+            description("the signing key could not be used"),
+            display("the signing key could not be used - {}", reason),
+        }
+    }
+}
+
+impl OidcErrorKind {
+    /// Error code as defined by the OAuth 2.0 specification
+    pub fn code(&self) -> &'static str {
+        match self {
+            OidcErrorKind::NotFound => "not_found",
+            OidcErrorKind::InvalidRequest(_) => "invalid_request",
+            OidcErrorKind::InvalidClient => "invalid_client",
+            OidcErrorKind::InvalidGrant(_) => "invalid_grant",
+            OidcErrorKind::UnsupportedGrantType(_) => "unsupported_grant_type",
+            OidcErrorKind::InvalidToken => "invalid_token",
+            _ => "server_error",
+        }
+    }
+}
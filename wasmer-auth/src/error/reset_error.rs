@@ -74,3 +74,9 @@ impl From<ResetFailed> for ResetError {
         }
     }
 }
+
+impl std::fmt::Display for ResetFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", ResetError::from(self.clone()))
+    }
+}
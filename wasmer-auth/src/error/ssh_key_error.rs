@@ -0,0 +1,92 @@
+use error_chain::error_chain;
+
+use crate::request::*;
+use ::ate::prelude::*;
+
+error_chain! {
+    types {
+        SshKeyError, SshKeyErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+        ChainCreationError(::ate::error::ChainCreationError, ::ate::error::ChainCreationErrorKind);
+        SerializationError(::ate::error::SerializationError, ::ate::error::SerializationErrorKind);
+        InvokeError(::ate::error::InvokeError, ::ate::error::InvokeErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        NotFound(key: String) {
+            description("the SSH key is not registered"),
+            display("the SSH key ({}) is not registered", key),
+        }
+        InvalidKey {
+            description("the SSH public key is not in the OpenSSH format")
+            display("the SSH public key is not in the OpenSSH format")
+        }
+        UnknownKey {
+            description("login failed as the SSH key is not registered with this account")
+            display("login failed as the SSH key is not registered with this account")
+        }
+        MissingToken {
+            description("ssh key operation failed as the session does not hold a login token")
+            display("ssh key operation failed as the session does not hold a login token")
+        }
+        NotSupported {
+            description("login with SSH keys is not enabled on the authentication server")
+            display("login with SSH keys is not enabled on the authentication server")
+        }
+        AccountLocked(duration: std::time::Duration) {
+            description("login failed as the account is locked"),
+            display("login failed as the account is locked for {} hours", (duration.as_secs() as f32 / 3600f32)),
+        }
+        Unverified(username: String) {
+            description("login failed as the account is not yet verified"),
+            display("login failed for {} as the account is not yet verified", username),
+        }
+        NoAccess {
+            description("ssh key operation failed as the session does not belong to the identity")
+            display("ssh key operation failed as the session does not belong to the identity")
+        }
+        NoMasterKey {
+            description("ssh key operation failed as the server has not been properly initialized")
+            display("ssh key operation failed as the server has not been properly initialized")
+        }
+        InternalError(code: u16) {
+            description("ssh key operation failed as the server experienced an internal error")
+            display("ssh key operation failed as the server experienced an internal error - code={}", code)
+        }
+    }
+}
+
+impl From<SshKeyError> for AteError {
+    fn from(err: SshKeyError) -> AteError {
+        AteErrorKind::ServiceError(err.to_string()).into()
+    }
+}
+
+impl From<SshKeyFailed> for SshKeyError {
+    fn from(err: SshKeyFailed) -> SshKeyError {
+        match err {
+            SshKeyFailed::NotFound(key) => SshKeyErrorKind::NotFound(key).into(),
+            SshKeyFailed::InvalidKey => SshKeyErrorKind::InvalidKey.into(),
+            SshKeyFailed::UnknownKey => SshKeyErrorKind::UnknownKey.into(),
+            SshKeyFailed::MissingToken => SshKeyErrorKind::MissingToken.into(),
+            SshKeyFailed::NotSupported => SshKeyErrorKind::NotSupported.into(),
+            SshKeyFailed::AccountLocked(duration) => {
+                SshKeyErrorKind::AccountLocked(duration).into()
+            }
+            SshKeyFailed::Unverified(username) => SshKeyErrorKind::Unverified(username).into(),
+            SshKeyFailed::NoAccess => SshKeyErrorKind::NoAccess.into(),
+            SshKeyFailed::NoMasterKey => SshKeyErrorKind::NoMasterKey.into(),
+            SshKeyFailed::InternalError(code) => SshKeyErrorKind::InternalError(code).into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SshKeyFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", SshKeyError::from(self.clone()))
+    }
+}
@@ -51,6 +51,10 @@ error_chain! {
             description("login failed due to an incorrect authentication code")
             display("login failed due to an incorrect authentication code")
         }
+        BreakGlass(group: String) {
+            description("sudo is disabled as elevations must be approved by an owner of the break-glass group")
+            display("sudo is disabled as elevations must be approved by an owner of the break-glass group ({})", group)
+        }
         InternalError(code: u16) {
             description("login failed as the server experienced an internal error")
             display("login failed as the server experienced an internal error - code={}", code)
@@ -73,7 +77,14 @@ impl From<SudoFailed> for SudoError {
             SudoFailed::Unverified(username) => SudoErrorKind::Unverified(username).into(),
             SudoFailed::UserNotFound(username) => SudoErrorKind::NotFound(username).into(),
             SudoFailed::WrongCode => SudoErrorKind::WrongCode.into(),
+            SudoFailed::BreakGlass(group) => SudoErrorKind::BreakGlass(group).into(),
             SudoFailed::InternalError(code) => SudoErrorKind::InternalError(code).into(),
         }
     }
 }
+
+impl std::fmt::Display for SudoFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", SudoError::from(self.clone()))
+    }
+}
@@ -0,0 +1,72 @@
+use error_chain::error_chain;
+
+use crate::request::*;
+use ::ate::prelude::*;
+
+error_chain! {
+    types {
+        TokenError, TokenErrorKind, ResultExt, Result;
+    }
+    links {
+        AteError(::ate::error::AteError, ::ate::error::AteErrorKind);
+        ChainCreationError(::ate::error::ChainCreationError, ::ate::error::ChainCreationErrorKind);
+        SerializationError(::ate::error::SerializationError, ::ate::error::SerializationErrorKind);
+        InvokeError(::ate::error::InvokeError, ::ate::error::InvokeErrorKind);
+        LoginError(super::LoginError, super::LoginErrorKind);
+        GatherError(super::GatherError, super::GatherErrorKind);
+        SudoError(super::SudoError, super::SudoErrorKind);
+    }
+    foreign_links {
+        IO(tokio::io::Error);
+    }
+    errors {
+        NotFound(id: AteHash) {
+            description("the access token does not exist"),
+            display("the access token ({}) does not exist", id),
+        }
+        Revoked(id: AteHash) {
+            description("the access token has been revoked"),
+            display("the access token ({}) has been revoked", id),
+        }
+        Expired(id: AteHash) {
+            description("the access token has expired"),
+            display("the access token ({}) has expired", id),
+        }
+        NoAccess {
+            description("token operation failed as the session does not belong to the identity")
+            display("token operation failed as the session does not belong to the identity")
+        }
+        NoMasterKey {
+            description("token operation failed as the server has not been properly initialized")
+            display("token operation failed as the server has not been properly initialized")
+        }
+        UnsupportedScope {
+            description("token operation failed as tokens can not be limited to chains"),
+            display("token operation failed as tokens can not be limited to chains"),
+        }
+        InternalError(code: u16) {
+            description("token operation failed as the server experienced an internal error")
+            display("token operation failed as the server experienced an internal error - code={}", code)
+        }
+    }
+}
+
+impl From<TokenError> for AteError {
+    fn from(err: TokenError) -> AteError {
+        AteErrorKind::ServiceError(err.to_string()).into()
+    }
+}
+
+impl From<TokenFailed> for TokenError {
+    fn from(err: TokenFailed) -> TokenError {
+        match err {
+            TokenFailed::NotFound(id) => TokenErrorKind::NotFound(id).into(),
+            TokenFailed::Revoked(id) => TokenErrorKind::Revoked(id).into(),
+            TokenFailed::Expired(id) => TokenErrorKind::Expired(id).into(),
+            TokenFailed::NoAccess => TokenErrorKind::NoAccess.into(),
+            TokenFailed::NoMasterKey => TokenErrorKind::NoMasterKey.into(),
+            TokenFailed::UnsupportedScope => TokenErrorKind::UnsupportedScope.into(),
+            TokenFailed::InternalError(code) => TokenErrorKind::InternalError(code).into(),
+        }
+    }
+}
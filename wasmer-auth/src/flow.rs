@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
@@ -5,9 +6,12 @@ use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 use async_trait::async_trait;
 use ate::crypto::EncryptKey;
 use ate::crypto::KeySize;
+use ate::error::TimeError;
 use ate::{error::ChainCreationError, prelude::*};
 use regex::Regex;
+use tokio::sync::Mutex;
 
+use crate::mail::*;
 use crate::service::*;
 
 pub struct ChainFlow {
@@ -21,6 +25,11 @@ pub struct ChainFlow {
     regex_cmd: Regex,
     session: AteSessionUser,
     pub terms_and_conditions: Option<String>,
+    pub ssh_key: Option<EncryptKey>,
+    pub mail: Option<MailConf>,
+    pub db_url: Option<url::Url>,
+    pub break_glass: Option<String>,
+    service: Mutex<Option<Arc<AuthService>>>,
 }
 
 impl ChainFlow {
@@ -44,8 +53,39 @@ impl ChainFlow {
             edge_key,
             contract_key,
             terms_and_conditions: None,
+            ssh_key: None,
+            mail: None,
+            db_url: None,
+            break_glass: None,
+            service: Mutex::new(None),
         }
     }
+
+    /// Returns the service that processes the commands of every command chain
+    /// (it is created when first needed) so that the login throttles are
+    /// shared by all the connections and anything else that serves them
+    pub async fn service(&self) -> Result<Arc<AuthService>, TimeError> {
+        let mut lock = self.service.lock().await;
+        if let Some(service) = lock.as_ref() {
+            return Ok(Arc::clone(service));
+        }
+        let service = AuthService::new(
+            &self.cfg,
+            self.auth_url.clone(),
+            self.session.clone(),
+            self.web_key.clone(),
+            self.edge_key.clone(),
+            self.contract_key.clone(),
+            self.terms_and_conditions.clone(),
+            self.ssh_key.clone(),
+            self.mail.clone(),
+            self.db_url.clone(),
+            self.break_glass.clone(),
+        )
+        .await?;
+        *lock = Some(Arc::clone(&service));
+        Ok(service)
+    }
 }
 
 #[async_trait]
@@ -62,10 +102,20 @@ impl OpenFlow for ChainFlow {
     }
 
     async fn open(
+        &self,
+        builder: ChainBuilder,
+        key: &ChainKey,
+        wire_encryption: Option<KeySize>,
+    ) -> Result<OpenAction, ChainCreationError> {
+        self.open_from(builder, key, wire_encryption, None).await
+    }
+
+    async fn open_from(
         &self,
         mut builder: ChainBuilder,
         key: &ChainKey,
         wire_encryption: Option<KeySize>,
+        peer_addr: Option<SocketAddr>,
     ) -> Result<OpenAction, ChainCreationError> {
         debug!("open_auth: {}", key);
 
@@ -109,19 +159,11 @@ impl OpenFlow for ChainFlow {
 
             let chain = builder.build().open(key).await?;
 
-            // Add the services to this chain
-            service_auth_handlers(
-                &self.cfg,
-                cmd_session.clone(),
-                self.auth_url.clone(),
-                self.session.clone(),
-                self.web_key.clone(),
-                self.edge_key.clone(),
-                self.contract_key.clone(),
-                self.terms_and_conditions.clone(),
-                &Arc::clone(&chain),
-            )
-            .await?;
+            // Add the services to this chain (command chains are private to
+            // the connection that opened them so logins are tied to its address)
+            let service = self.service().await?;
+            let source = peer_addr.map(|a| a.ip().to_string());
+            service_auth_handlers(&cmd_session, &service, &chain, source);
 
             // Return the chain to the caller
             return Ok(OpenAction::PrivateChain {
@@ -121,9 +121,74 @@ pub(crate) fn complete_group_auth(
         roles = next;
     }
 
+    // Add the keys that the roles had before they were rotated so that records
+    // which have not yet been moved onto the new keys can still be accessed
+    for role in group.roles.iter() {
+        let previous = match role.previous.as_ref() {
+            Some(a) => a,
+            None => continue,
+        };
+        let read_key = session
+            .get_group_role(&role.purpose)
+            .iter()
+            .flat_map(|r| r.read_keys())
+            .filter(|k| k.hash() == role.read)
+            .map(|k| k.clone())
+            .next();
+        let read_key = match read_key {
+            Some(a) => a,
+            None => continue,
+        };
+
+        let b = session.get_or_create_group_role(&previous_role_purpose(&role.purpose));
+        for a in previous.unwrap(&read_key)? {
+            b.add_read_key(&a.read);
+            b.add_private_read_key(&a.private_read);
+            b.add_write_key(&a.write);
+        }
+    }
+
+    // Add the read keys of the databases that the roles held by the session
+    // have been bound to (the current key is added first)
+    let super_keys = session
+        .private_read_keys(AteSessionKeyCategory::AllKeys)
+        .map(|a| a.clone())
+        .collect::<Vec<_>>();
+    for chain_keys in group.chain_keys.iter() {
+        for read_key in super_keys.iter() {
+            if let Some(keys) = chain_keys.access.unwrap(&read_key)? {
+                let b = session.get_or_create_group_role(&chain_role_purpose(&chain_keys.chain));
+                for key in keys.iter() {
+                    b.add_read_key(key);
+                }
+                b.add_gid(group.gid);
+                break;
+            }
+        }
+    }
+
     Ok(session)
 }
 
+/// Returns the role that holds the keys a role had before they were rotated
+pub fn previous_role_purpose(purpose: &AteRolePurpose) -> AteRolePurpose {
+    AteRolePurpose::Other(format!("previous-{}", purpose))
+}
+
+/// Returns the role that holds the read keys of a database of the group
+pub fn chain_role_purpose(chain: &str) -> AteRolePurpose {
+    AteRolePurpose::Other(format!("chain-{}", chain))
+}
+
+/// Returns who may read the new records of a database of the group, which is
+/// anyone holding its current read key if roles were bound to read it
+pub fn chain_read_option(session: &dyn AteSession, chain: &str) -> Option<ReadOption> {
+    session
+        .role(&chain_role_purpose(chain))
+        .and_then(|r| r.read_keys().next())
+        .map(|k| ReadOption::from_key(k))
+}
+
 pub async fn load_credentials(
     registry: &Registry,
     username: String,
@@ -7,8 +7,9 @@ use ate::prelude::*;
 
 use crate::cmd::gather_command;
 use crate::cmd::main_session_prompt;
+use crate::cmd::token_to_session;
 use crate::error::*;
-use crate::helper::b64_to_session;
+use crate::helper::chain_read_option;
 
 pub struct DioBuilder {
     cfg_ate: ConfAte,
@@ -53,19 +54,20 @@ impl DioBuilder {
         self
     }
 
-    pub fn with_token_string(mut self, token: String) -> Self {
-        self.session = Box::new(b64_to_session(token));
-        self
+    /// Scoped tokens are exchanged with the authentication server (set with
+    /// `with_url_auth` beforehand) which checks they are still valid
+    pub async fn with_token_string(mut self, token: String) -> Result<Self, LoginError> {
+        self.session = Box::new(token_to_session(token, Some(&self.url_auth)).await?);
+        Ok(self)
     }
 
-    pub async fn with_token_path(mut self, path: String) -> Result<Self, LoginError> {
+    pub async fn with_token_path(self, path: String) -> Result<Self, LoginError> {
         let path = shellexpand::tilde(path.as_str()).to_string();
         #[cfg(feature = "enable_full")]
         let token = tokio::fs::read_to_string(path).await?;
         #[cfg(not(feature = "enable_full"))]
         let token = std::fs::read_to_string(path)?;
-        self.session = Box::new(b64_to_session(token));
-        Ok(self)
+        self.with_token_string(token).await
     }
 
     pub fn with_registry(mut self, registry: Registry) -> Self {
@@ -125,6 +127,15 @@ impl DioBuilder {
         let key = ChainKey::new(self.generate_key(name));
         let registry = self.get_registry().await;
         let chain = registry.open(&self.url_db, &key, true).await?;
-        Ok(chain.dio_mut(self.session.deref()).await)
+        let dio = chain.dio_mut(self.session.deref()).await;
+
+        // If the roles we hold were bound to read this database then new
+        // records are encrypted with its current read key
+        if self.group.is_some() {
+            if let Some(read) = chain_read_option(self.session.deref(), name) {
+                dio.set_default_read(read);
+            }
+        }
+        Ok(dio)
     }
 }
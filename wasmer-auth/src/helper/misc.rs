@@ -3,6 +3,9 @@ use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
 use ::ate::crypto::EncryptKey;
 use ::ate::prelude::*;
+use error_chain::bail;
+
+use crate::error::*;
 
 pub fn password_to_read_key(
     seed: &String,
@@ -37,11 +40,17 @@ pub fn session_to_b64(session: AteSessionType) -> Result<String, SerializationEr
     Ok(base64::encode(bytes))
 }
 
-pub fn b64_to_session(val: String) -> AteSessionType {
+/// Converts a plain session back from its base64 form, scoped tokens are
+/// rejected as their session is sealed until the authentication server has
+/// checked the token (see `token_to_session`)
+pub fn b64_to_session(val: String) -> Result<AteSessionType, LoginError> {
+    if val.trim().starts_with(super::SCOPED_TOKEN_PREFIX) {
+        bail!(LoginErrorKind::TokenNotChecked);
+    }
     let val = val.trim().to_string();
     let format = SerializationFormat::MessagePack;
-    let bytes = base64::decode(val).unwrap();
-    format.deserialize(bytes).unwrap()
+    let bytes = base64::decode(val).map_err(|_| LoginErrorKind::InvalidArguments)?;
+    Ok(format.deserialize(bytes)?)
 }
 
 #[allow(dead_code)]
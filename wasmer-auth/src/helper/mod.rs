@@ -3,9 +3,15 @@ mod builder;
 mod conf;
 mod keys;
 mod misc;
+mod reencrypt;
+mod ssh;
+mod token;
 
 pub use auth::*;
 pub use builder::*;
 pub use conf::*;
 pub use keys::*;
 pub use misc::*;
+pub use reencrypt::*;
+pub use ssh::*;
+pub use token::*;
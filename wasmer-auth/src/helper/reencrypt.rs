@@ -0,0 +1,214 @@
+use fxhash::FxHashMap;
+use fxhash::FxHashSet;
+use std::sync::Arc;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ::ate::crypto::EncryptKey;
+use ::ate::prelude::*;
+
+use super::previous_role_purpose;
+
+/// Number of records that are moved before the transaction is committed
+const REENCRYPT_BATCH: usize = 100;
+
+/// Moves the records of a chain that are still protected by keys that have
+/// since been rotated onto the current keys of the group (the session must
+/// hold both the old and new keys) returning the number of records that were
+/// moved. Every record that is moved gets a new inner key so anyone that kept
+/// the old one can not read what is written to it from then on, hence the
+/// children that inherit the key are encrypted again along with it
+pub async fn reencrypt_chain(
+    chain: &Arc<Chain>,
+    session: &AteSessionGroup,
+) -> Result<usize, AteError> {
+    // Map the keys that each role had before onto the keys that it has now
+    let mut read_map: Vec<(AteHash, EncryptKey)> = Vec::new();
+    let mut write_map: Vec<(AteHash, AteHash)> = Vec::new();
+    for role in session.group.roles.iter() {
+        let previous = match session.get_group_role(&previous_role_purpose(&role.purpose)) {
+            Some(a) => a,
+            None => continue,
+        };
+        if let Some(new) = role.read_keys().next() {
+            for old in previous.read_keys().filter(|k| k.hash() != new.hash()) {
+                read_map.push((old.hash(), new.clone()));
+            }
+        }
+        if let Some(new) = role.write_keys().next() {
+            for old in previous.write_keys().filter(|k| k.hash() != new.hash()) {
+                write_map.push((old.hash(), new.hash()));
+            }
+        }
+    }
+    if read_map.len() <= 0 && write_map.len() <= 0 {
+        return Ok(0);
+    }
+    let map_read = |hash: &AteHash| {
+        read_map
+            .iter()
+            .filter(|(old, _)| old == hash)
+            .map(|(_, new)| new)
+            .next()
+    };
+    let map_write = |hash: &AteHash| {
+        write_map
+            .iter()
+            .filter(|(old, _)| old == hash)
+            .map(|(_, new)| new.clone())
+            .next()
+    };
+    let remap_write = |write: &WriteOption| match write {
+        WriteOption::Specific(hash) => map_write(hash).map(WriteOption::Specific),
+        WriteOption::Any(hashes) if hashes.iter().any(|h| map_write(h).is_some()) => {
+            Some(WriteOption::Any(
+                hashes
+                    .iter()
+                    .map(|h| map_write(h).unwrap_or_else(|| h.clone()))
+                    .collect(),
+            ))
+        }
+        _ => None,
+    };
+
+    // Find the records that need to move and the children that inherit the
+    // read key of their parent
+    let keys = chain.dio(session).await.all_keys().await;
+    debug!(
+        "reencrypting {} records of chain {}",
+        keys.len(),
+        chain.key()
+    );
+    let dio = chain.dio_full(session).await;
+    let mut inherits = FxHashMap::<PrimaryKey, Vec<PrimaryKey>>::default();
+    let mut rotates = Vec::new();
+    let mut rewrites = Vec::new();
+    for key in keys {
+        let meta = match dio.load_raw(&key).await {
+            Ok(a) => a.meta,
+            Err(err) => {
+                trace!("skipping record {} - {}", key, err);
+                continue;
+            }
+        };
+        let read = meta
+            .get_authorization()
+            .map(|a| a.read.clone())
+            .unwrap_or_default();
+        match read {
+            ReadOption::Inherit => {
+                if let Some(parent) = meta.get_parent() {
+                    if parent.vec.parent_id != key {
+                        inherits.entry(parent.vec.parent_id).or_default().push(key);
+                    }
+                }
+            }
+            ReadOption::Specific(hash, _) if map_read(&hash).is_some() => {
+                rotates.push(key);
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(auth) = meta.get_authorization() {
+            if remap_write(&auth.write).is_some() {
+                rewrites.push(key);
+            }
+        }
+    }
+
+    // Records that get a new read key go first so that the children which
+    // inherit it are moved with them rather than on their own
+    let moves = rotates.into_iter().chain(rewrites.into_iter()).collect::<Vec<_>>();
+
+    let mut moved = 0usize;
+    let mut pending = 0usize;
+    let mut done = FxHashSet::default();
+    for key in moves {
+        if done.contains(&key) {
+            continue;
+        }
+
+        // The record is given a fresh inner key under the new read key of its role
+        let mut rotated = false;
+        let ret = dio
+            .reauthorize(&key, |auth| {
+                let mut auth = auth.clone();
+                let mut changed = false;
+                if let ReadOption::Specific(hash, _) = &auth.read {
+                    if let Some(new) = map_read(hash) {
+                        auth.read = ReadOption::from_key(new);
+                        rotated = true;
+                        changed = true;
+                    }
+                }
+                if let Some(write) = remap_write(&auth.write) {
+                    auth.write = write;
+                    changed = true;
+                }
+                match changed {
+                    true => Some(auth),
+                    false => None,
+                }
+            })
+            .await;
+        match ret {
+            Ok(true) => {
+                moved += 1;
+                pending += 1;
+                done.insert(key.clone());
+            }
+            Ok(false) => continue,
+            Err(err) => {
+                trace!("skipping record {} - {}", key, err);
+                continue;
+            }
+        }
+
+        // Everything that inherits the inner key of the record is encrypted
+        // again in the same transaction (after its parent so that it picks up
+        // the new key when it is committed)
+        if rotated {
+            let mut stack = inherits.get(&key).cloned().unwrap_or_default();
+            while let Some(child) = stack.pop() {
+                if done.contains(&child) {
+                    continue;
+                }
+                let ret = dio
+                    .reauthorize(&child, |auth| {
+                        let mut auth = auth.clone();
+                        if let Some(write) = remap_write(&auth.write) {
+                            auth.write = write;
+                        }
+                        Some(auth)
+                    })
+                    .await;
+                match ret {
+                    Ok(_) => {
+                        moved += 1;
+                        pending += 1;
+                        done.insert(child.clone());
+                        if let Some(next) = inherits.get(&child) {
+                            stack.extend(next.iter().cloned());
+                        }
+                    }
+                    Err(err) => {
+                        // A child that can not be moved with its parent would
+                        // become unreadable so the parent is left as it was
+                        warn!("failed to reencrypt record {} - {}", child, err);
+                        dio.cancel();
+                        return Err(err.into());
+                    }
+                }
+            }
+        }
+
+        if pending >= REENCRYPT_BATCH {
+            dio.commit().await?;
+            pending = 0;
+        }
+    }
+    dio.commit().await?;
+
+    info!("reencrypted {} records of chain {}", moved, chain.key());
+    Ok(moved)
+}
@@ -0,0 +1,24 @@
+use ate::crypto::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Computes the proof that an SSH server attaches to a login request so that
+/// the authentication server knows it came from a server holding the SSH
+/// login key (anyone can know a public key so it can not be trusted alone),
+/// the proof also covers the address of the client the server vouches for
+pub fn ssh_login_proof(
+    key: &EncryptKey,
+    email: &str,
+    public_key: &str,
+    timestamp: i64,
+    source: Option<&str>,
+) -> AteHash {
+    let data = format!(
+        "ssh-login:{}:{}:{}:{}",
+        email,
+        public_key,
+        timestamp,
+        source.unwrap_or_default()
+    );
+    AteHash::from_bytes_twice(&key.value()[..], data.as_bytes())
+}
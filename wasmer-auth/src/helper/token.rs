@@ -0,0 +1,50 @@
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ::ate::prelude::*;
+
+use crate::model::*;
+
+/// Prefix that marks a token string as a scoped access token rather than a
+/// plain session (the dash can never appear in a base64 string)
+pub const SCOPED_TOKEN_PREFIX: &'static str = "ate-token.";
+
+/// Access token that carries a session which has been cut down to the scope
+/// of the token along with the claims the token was minted with, the session
+/// is sealed so it must be exchanged with the authentication server (which
+/// checks that the token is still valid) before it can be used
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScopedToken {
+    pub identity: String,
+    pub token: AccessToken,
+    pub sealed: EncryptedSecureData<TokenSeal>,
+}
+
+pub fn scoped_token_to_b64(token: &ScopedToken) -> Result<String, SerializationError> {
+    let format = SerializationFormat::MessagePack;
+    let bytes = format.serialize(token)?;
+    Ok(format!("{}{}", SCOPED_TOKEN_PREFIX, base64::encode(bytes)))
+}
+
+/// Returns the scoped token held within the string or none if the string is
+/// a plain session (or is not a token at all)
+pub fn b64_to_scoped_token(val: &str) -> Option<ScopedToken> {
+    let val = val.trim().strip_prefix(SCOPED_TOKEN_PREFIX)?;
+    let format = SerializationFormat::MessagePack;
+    let bytes = base64::decode(val).ok()?;
+    format.deserialize(bytes).ok()
+}
+
+/// Removes everything from a group session that falls outside the scope of a
+/// token, including the session of the user that gathered it
+pub fn scope_group_session(mut session: AteSessionGroup, scope: &TokenScope) -> AteSessionGroup {
+    if scope.roles.len() > 0 {
+        session
+            .group
+            .roles
+            .retain(|r| scope.roles.iter().any(|p| *p == r.purpose));
+    }
+    session.inner = AteSessionInner::Nothing;
+    session
+}
@@ -3,7 +3,10 @@ pub mod error;
 #[cfg(all(feature = "server"))]
 pub mod flow;
 pub mod helper;
+pub mod mail;
 pub mod model;
+#[cfg(all(feature = "server"))]
+pub mod oidc;
 pub mod opt;
 pub mod prelude;
 pub mod request;
@@ -0,0 +1,7 @@
+mod sender;
+#[cfg(feature = "server")]
+mod smtp;
+
+pub use sender::*;
+#[cfg(feature = "server")]
+pub use smtp::*;
@@ -0,0 +1,53 @@
+#![allow(unused_imports)]
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use crate::error::*;
+
+/// Plain text email that is sent to a user
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails to users, implement this trait to deliver them by some
+/// other means than SMTP (for instance a transactional email API)
+#[async_trait]
+pub trait MailSender: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Configuration of the emails that the authentication server sends to its users
+#[derive(Clone)]
+pub struct MailConf {
+    pub sender: Arc<dyn MailSender>,
+    /// Web page that users are sent to (the email address and code are added
+    /// to it as query parameters)
+    pub link_url: url::Url,
+    /// How long the codes that are emailed remain valid
+    pub code_lifetime: Duration,
+}
+
+impl MailConf {
+    pub fn new(sender: Arc<dyn MailSender>, link_url: url::Url) -> MailConf {
+        MailConf {
+            sender,
+            link_url,
+            code_lifetime: Duration::from_secs(900),
+        }
+    }
+
+    /// Builds the link that is placed in an email
+    pub fn link(&self, action: &str, email: &str, code: &str) -> url::Url {
+        let mut ret = self.link_url.clone();
+        ret.query_pairs_mut()
+            .append_pair("action", action)
+            .append_pair("email", email)
+            .append_pair("code", code);
+        ret
+    }
+}
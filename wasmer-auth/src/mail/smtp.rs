@@ -0,0 +1,131 @@
+#![allow(unused_imports)]
+use async_trait::async_trait;
+use error_chain::bail;
+use std::time::Duration;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::*;
+use crate::error::*;
+
+/// Sends emails to a mail relay using plain SMTP (the relay is expected to be
+/// a local or otherwise trusted server that takes care of the onward delivery)
+pub struct SmtpMailSender {
+    /// Address of the mail relay (e.g. localhost:25)
+    pub server: String,
+    /// Address that the emails are sent from
+    pub from: String,
+    /// Name that this server introduces itself as
+    pub hello: String,
+    pub timeout: Duration,
+}
+
+impl SmtpMailSender {
+    pub fn new(server: &str, from: &str) -> SmtpMailSender {
+        SmtpMailSender {
+            server: server.to_string(),
+            from: from.to_string(),
+            hello: "localhost".to_string(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    async fn send_internal(&self, mail: Mail) -> Result<(), MailError> {
+        for header in [&self.from, &mail.to, &mail.subject] {
+            if header.contains('\r') || header.contains('\n') {
+                bail!(MailErrorKind::InvalidHeader(header.clone()));
+            }
+        }
+
+        let mut stream = BufReader::new(TcpStream::connect(self.server.as_str()).await?);
+        smtp_reply(&mut stream, &[220]).await?;
+        smtp_command(&mut stream, format!("EHLO {}", self.hello), &[250]).await?;
+        smtp_command(&mut stream, format!("MAIL FROM:<{}>", self.from), &[250]).await?;
+        smtp_command(&mut stream, format!("RCPT TO:<{}>", mail.to), &[250, 251]).await?;
+        smtp_command(&mut stream, "DATA".to_string(), &[354]).await?;
+
+        let mut data = String::new();
+        data.push_str(format!("From: <{}>\r\n", self.from).as_str());
+        data.push_str(format!("To: <{}>\r\n", mail.to).as_str());
+        data.push_str(format!("Subject: {}\r\n", mail.subject).as_str());
+        data.push_str(format!("Date: {}\r\n", chrono::Utc::now().to_rfc2822()).as_str());
+        data.push_str("MIME-Version: 1.0\r\n");
+        data.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+        data.push_str("Content-Transfer-Encoding: 8bit\r\n");
+        data.push_str("\r\n");
+        for line in mail.body.lines() {
+            // Lines that start with a dot must be escaped so they are not
+            // mistaken for the end of the data
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".");
+        smtp_command(&mut stream, data, &[250]).await?;
+
+        // The mail has been accepted so failing to say goodbye does not matter
+        let _ = smtp_command(&mut stream, "QUIT".to_string(), &[221]).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MailSender for SmtpMailSender {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        debug!("sending mail to {} via {}", mail.to, self.server);
+        match tokio::time::timeout(self.timeout, self.send_internal(mail)).await {
+            Ok(ret) => ret,
+            Err(_) => Err(MailErrorKind::Timeout.into()),
+        }
+    }
+}
+
+async fn smtp_command(
+    stream: &mut BufReader<TcpStream>,
+    command: String,
+    expected: &[u16],
+) -> Result<String, MailError> {
+    stream.write_all(command.as_bytes()).await?;
+    stream.write_all(b"\r\n").await?;
+    stream.flush().await?;
+    smtp_reply(stream, expected).await
+}
+
+/// Reads a reply from the server (which may span multiple lines) and checks
+/// that its code is one of the codes that were expected
+async fn smtp_reply(
+    stream: &mut BufReader<TcpStream>,
+    expected: &[u16],
+) -> Result<String, MailError> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            bail!(MailErrorKind::InvalidReply(reply));
+        }
+        let line = line.trim_end();
+        if line.len() < 3 || line.is_char_boundary(3) == false {
+            bail!(MailErrorKind::InvalidReply(line.to_string()));
+        }
+        let code = match line[..3].parse::<u16>() {
+            Ok(a) => a,
+            Err(_) => bail!(MailErrorKind::InvalidReply(line.to_string())),
+        };
+        if reply.len() > 0 {
+            reply.push('\n');
+        }
+        reply.push_str(line[3..].trim_start_matches(|c: char| c == '-' || c == ' '));
+
+        if line[3..].starts_with('-') == false {
+            if expected.contains(&code) == false {
+                bail!(MailErrorKind::Rejected(code, reply));
+            }
+            return Ok(reply);
+        }
+    }
+}
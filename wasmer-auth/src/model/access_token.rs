@@ -0,0 +1,72 @@
+use ate::crypto::AteHash;
+use ate::session::AteRolePurpose;
+use ate::session::AteSessionType;
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Limits what the holder of an access token is able to do
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TokenScope {
+    /// Group the token was gathered for (when none is set the token carries
+    /// the session of the user that minted it)
+    pub group: Option<String>,
+    /// Roles within the group that the token holds (empty means all of them)
+    pub roles: Vec<AteRolePurpose>,
+    /// Chains that the token may be used to open - chains are opened with the
+    /// keys that the token holds so the server can not enforce this and it
+    /// refuses to mint tokens that set it (empty means any of them)
+    pub chains: Vec<String>,
+    /// Commands that the token may be used for, e.g. 'group.add-user' (empty
+    /// means any of them)
+    #[serde(default)]
+    pub operations: Vec<String>,
+}
+
+impl TokenScope {
+    pub fn allows_operation(&self, operation: &str) -> bool {
+        self.operations.len() <= 0 || self.operations.iter().any(|a| a == operation)
+    }
+}
+
+/// Record of an access token that was minted by a user, the token itself is
+/// held by whoever it was handed to and is never stored on the server
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AccessToken {
+    pub id: AteHash,
+    pub name: String,
+    pub issued: chrono::DateTime<chrono::Utc>,
+    pub expires: Option<chrono::DateTime<chrono::Utc>>,
+    pub scope: TokenScope,
+    pub revoked: bool,
+    /// Elevations are never exchanged for the session they hold, instead the
+    /// server opens it on every privileged operation that they are used for
+    #[serde(default)]
+    pub elevated: bool,
+}
+
+impl AccessToken {
+    pub fn is_expired(&self) -> bool {
+        match self.expires {
+            Some(expires) => expires <= chrono::Utc::now(),
+            None => false,
+        }
+    }
+}
+
+/// Session that a scoped token hands out, it is sealed with the master key so
+/// that only the authentication server can open it and it only does so while
+/// the token has not expired nor been revoked
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenSeal {
+    pub id: AteHash,
+    pub identity: String,
+    pub session: AteSessionType,
+}
+
+/// All the access tokens that a particular user has minted
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AccessTokens {
+    pub identity: String,
+    pub tokens: Vec<AccessToken>,
+}
@@ -0,0 +1,172 @@
+use ate::prelude::*;
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Security relevant action that was performed by (or against) an identity
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum AuditAction {
+    Login,
+    SshLogin,
+    Sudo,
+    Reset,
+    EmailReset,
+    Elevate {
+        id: AteHash,
+        minutes: u32,
+        approval: Option<String>,
+    },
+    ElevationApprove {
+        id: AteHash,
+        requester: String,
+        approver: String,
+    },
+    ElevationRedeem {
+        id: AteHash,
+    },
+    CreateGroup {
+        group: String,
+    },
+    GroupUserAdd {
+        group: String,
+        who: String,
+        purpose: AteRolePurpose,
+    },
+    GroupUserRemove {
+        group: String,
+        who: AteHash,
+        purpose: AteRolePurpose,
+    },
+    RemoveGroup {
+        group: String,
+    },
+    GroupRoleCreate {
+        group: String,
+        purpose: AteRolePurpose,
+    },
+    GroupRoleRemove {
+        group: String,
+        purpose: AteRolePurpose,
+    },
+    GroupRoleBind {
+        group: String,
+        purpose: AteRolePurpose,
+        chain: String,
+        read: bool,
+        write: bool,
+    },
+    EmailRecoveryEnrol {
+        enabled: bool,
+    },
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditAction::Login => write!(f, "login"),
+            AuditAction::SshLogin => write!(f, "ssh-login"),
+            AuditAction::Sudo => write!(f, "sudo"),
+            AuditAction::Reset => write!(f, "reset"),
+            AuditAction::EmailReset => write!(f, "email-reset"),
+            AuditAction::Elevate {
+                id,
+                minutes,
+                approval,
+            } => match approval {
+                Some(group) => write!(
+                    f,
+                    "elevate ({} for {} minutes, approval by {})",
+                    id, minutes, group
+                ),
+                None => write!(f, "elevate ({} for {} minutes)", id, minutes),
+            },
+            AuditAction::ElevationApprove {
+                id,
+                requester,
+                approver,
+            } => write!(
+                f,
+                "elevation-approve ({} of {} by {})",
+                id, requester, approver
+            ),
+            AuditAction::ElevationRedeem { id } => write!(f, "elevation-redeem ({})", id),
+            AuditAction::CreateGroup { group } => write!(f, "create-group ({})", group),
+            AuditAction::GroupUserAdd {
+                group,
+                who,
+                purpose,
+            } => write!(f, "group-user-add ({} {} as {})", group, who, purpose),
+            AuditAction::GroupUserRemove {
+                group,
+                who,
+                purpose,
+            } => write!(f, "group-user-remove ({} {} as {})", group, who, purpose),
+            AuditAction::RemoveGroup { group } => write!(f, "remove-group ({})", group),
+            AuditAction::GroupRoleCreate { group, purpose } => {
+                write!(f, "group-role-create ({} {})", group, purpose)
+            }
+            AuditAction::GroupRoleRemove { group, purpose } => {
+                write!(f, "group-role-remove ({} {})", group, purpose)
+            }
+            AuditAction::GroupRoleBind {
+                group,
+                purpose,
+                chain,
+                read,
+                write,
+            } => write!(
+                f,
+                "group-role-bind ({} {} to {} read={} write={})",
+                group, purpose, chain, read, write
+            ),
+            AuditAction::EmailRecoveryEnrol { enabled } => {
+                write!(f, "email-recovery-enrol (enabled={})", enabled)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum AuditOutcome {
+    Success,
+    Failed(String),
+}
+
+impl AuditOutcome {
+    /// Failures are recorded with the same message that the caller is shown
+    pub fn from_result<T, E>(ret: &Result<T, E>) -> AuditOutcome
+    where
+        E: std::fmt::Display,
+    {
+        match ret {
+            Ok(_) => AuditOutcome::Success,
+            Err(err) => AuditOutcome::Failed(err.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for AuditOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditOutcome::Success => write!(f, "success"),
+            AuditOutcome::Failed(reason) => write!(f, "failed - {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEvent {
+    pub when: chrono::DateTime<chrono::Utc>,
+    pub action: AuditAction,
+    /// Address of the client as reported by the server that relayed the request
+    pub source: Option<String>,
+    pub outcome: AuditOutcome,
+}
+
+/// Append-only record of the security events of an identity, only the
+/// authentication server is able to write to it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditLog {
+    pub identity: String,
+    pub events: DaoVec<AuditEvent>,
+}
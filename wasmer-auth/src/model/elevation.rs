@@ -0,0 +1,66 @@
+use ate::crypto::AteHash;
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::*;
+
+/// Longest period that a session may be elevated for (in minutes)
+pub const MAX_ELEVATION_MINUTES: u32 = 24 * 60;
+
+/// Period in which an elevation must be approved and redeemed (in minutes)
+pub const ELEVATION_APPROVAL_MINUTES: i64 = 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ElevationStatus {
+    /// Waiting for an owner of the approval group to approve it
+    Pending,
+    Approved {
+        by: String,
+        when: chrono::DateTime<chrono::Utc>,
+    },
+    /// The elevated session has been handed out (it can only happen once)
+    Redeemed,
+}
+
+impl std::fmt::Display for ElevationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ElevationStatus::Pending => write!(f, "pending"),
+            ElevationStatus::Approved { by, .. } => write!(f, "approved by {}", by),
+            ElevationStatus::Redeemed => write!(f, "redeemed"),
+        }
+    }
+}
+
+/// Request to elevate a session to sudo for a limited period of time and
+/// scope, the elevated session is handed out as an access token with the
+/// same identifier so that it expires and can be revoked
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Elevation {
+    pub id: AteHash,
+    pub identity: String,
+    pub reason: String,
+    pub requested: chrono::DateTime<chrono::Utc>,
+    /// Number of minutes that the elevated session lasts once it is redeemed
+    pub minutes: u32,
+    pub scope: TokenScope,
+    /// Group that one of the owners of must approve the elevation (break-glass)
+    pub approval: Option<String>,
+    pub status: ElevationStatus,
+}
+
+impl Elevation {
+    /// Elevations that were not approved and redeemed in time can no longer be used
+    pub fn is_stale(&self) -> bool {
+        self.requested + chrono::Duration::minutes(ELEVATION_APPROVAL_MINUTES) <= chrono::Utc::now()
+    }
+}
+
+/// Recent elevations of a particular user, only the authentication server is
+/// able to read and write them
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Elevations {
+    pub identity: String,
+    pub elevations: Vec<Elevation>,
+}
@@ -0,0 +1,14 @@
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::prelude::*;
+
+/// Allows the authentication server to open the account of a user who lost
+/// their password once they prove they own their email address and their
+/// authenticator (this record is only readable with the master key)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailRecovery {
+    pub email: String,
+    pub super_key: EncryptKey,
+}
@@ -1,8 +1,49 @@
+use ate::prelude::*;
 use serde::*;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+/// Reason that a code was emailed to a user
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EmailPurpose {
+    Verify,
+    Recover,
+}
+
+impl EmailPurpose {
+    pub fn key(&self, email: &str) -> PrimaryKey {
+        PrimaryKey::from(format!("email-code:{}:{}", self, email))
+    }
+}
+
+impl std::fmt::Display for EmailPurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmailPurpose::Verify => write!(f, "verify"),
+            EmailPurpose::Recover => write!(f, "recover"),
+        }
+    }
+}
+
+/// Time limited code that was emailed to a user to prove they own the address,
+/// only the hash of the code is kept and it is deleted once it has been used
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EmailVerification {
-    pub code: String,
+    pub email: String,
+    pub purpose: EmailPurpose,
+    pub code_hash: AteHash,
+    pub issued: chrono::DateTime<chrono::Utc>,
+    pub expires: chrono::DateTime<chrono::Utc>,
+    pub failed_attempts: u32,
+}
+
+impl EmailVerification {
+    pub fn hash_code(email: &str, code: &str) -> AteHash {
+        let entropy = format!("email-code:{}:{}", email, code.trim().to_lowercase());
+        AteHash::from_bytes(entropy.as_bytes())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires <= chrono::Utc::now()
+    }
 }
@@ -13,4 +13,14 @@ pub struct Group {
     pub foreign: DaoForeign,
     pub broker_read: PrivateEncryptKey,
     pub broker_write: PrivateSignKey,
+    /// Databases of the group that the roles have been bound to
+    #[serde(default)]
+    pub bindings: Vec<RoleBinding>,
+    /// Read keys of the databases that have roles bound to read them
+    #[serde(default)]
+    pub chain_keys: Vec<ChainKeys>,
+    /// Databases of the group that members have used (their records are moved
+    /// onto the new keys whenever the keys of the group are rotated)
+    #[serde(default)]
+    pub databases: Vec<String>,
 }
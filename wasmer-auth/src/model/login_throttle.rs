@@ -0,0 +1,47 @@
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::*;
+
+/// Counts the failed login attempts of an account (or of a source address) so
+/// that repeated failures lock it out for longer and longer periods of time
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LoginThrottle {
+    pub failed_attempts: u32,
+    pub status: UserStatus,
+}
+
+impl LoginThrottle {
+    /// Returns how much longer the account remains locked (if it is locked)
+    pub fn locked_for(&self) -> Option<std::time::Duration> {
+        match &self.status {
+            UserStatus::Locked(until) => {
+                let utc_now = chrono::Utc::now();
+                match *until > utc_now {
+                    true => (*until - utc_now).to_std().ok(),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Records a failed attempt, once the free attempts are used up every
+    /// further failure doubles the lockout from 30 seconds up to a day
+    pub fn failed(&mut self, free_attempts: u32) {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        if self.failed_attempts > free_attempts {
+            let exponent = (self.failed_attempts - free_attempts - 1).min(12);
+            let ban_time = chrono::Duration::seconds((30i64 << exponent).min(86400));
+            if let Some(utc_ban) = chrono::Utc::now().checked_add_signed(ban_time) {
+                self.status = UserStatus::Locked(utc_ban);
+            }
+        }
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failed_attempts = 0;
+        self.status = UserStatus::Nominal;
+    }
+}
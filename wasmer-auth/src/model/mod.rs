@@ -1,14 +1,21 @@
 mod accepted_terms;
+mod access_token;
 mod advert;
+mod audit;
 mod authentication_method;
 mod authorization;
 mod company;
+mod elevation;
+mod email_recovery;
 mod email_verification;
 mod gender;
 mod group;
+mod login_throttle;
 mod person;
 mod role;
+mod role_binding;
 mod sms_verification;
+mod ssh_key;
 mod ssh_key_type;
 mod sudo;
 mod user;
@@ -17,16 +24,23 @@ mod user_role;
 mod user_status;
 
 pub use accepted_terms::*;
+pub use access_token::*;
 pub use advert::*;
+pub use audit::*;
 pub use authentication_method::*;
 pub use authorization::*;
 pub use company::*;
+pub use elevation::*;
+pub use email_recovery::*;
 pub use email_verification::*;
 pub use gender::*;
 pub use group::*;
+pub use login_throttle::*;
 pub use person::*;
 pub use role::*;
+pub use role_binding::*;
 pub use sms_verification::*;
+pub use ssh_key::*;
 pub use ssh_key_type::*;
 pub use sudo::*;
 pub use user::*;
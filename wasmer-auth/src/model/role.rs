@@ -13,4 +13,9 @@ pub struct Role {
     pub private_read: PublicEncryptKey,
     pub write: PublicSignKey,
     pub access: MultiEncryptedSecureData<Authorization>,
+    /// Keys that the role had before they were rotated (encrypted with the
+    /// current read key) so that members can still access the records that
+    /// have not yet been moved onto the new keys
+    #[serde(default)]
+    pub previous: Option<EncryptedSecureData<Vec<Authorization>>>,
 }
@@ -0,0 +1,28 @@
+use ate::crypto::*;
+use ate::prelude::*;
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Grants the members of a group role access to one of the databases that
+/// belong to the group (i.e. the chain named `[group]/[chain]`)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RoleBinding {
+    pub purpose: AteRolePurpose,
+    pub chain: String,
+    /// Members of the role are given the keys needed to read the database
+    pub read: bool,
+    /// The database accepts records that are signed by the write key of the role
+    pub write: bool,
+}
+
+/// Keys that encrypt the records of a particular database of the group, they
+/// are only shared with the delegates and the roles that are bound to read it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChainKeys {
+    pub chain: String,
+    /// Hash of the key that new records should be encrypted with
+    pub read: AteHash,
+    /// Current key followed by the keys it replaced when readers were unbound
+    pub access: MultiEncryptedSecureData<Vec<EncryptKey>>,
+}
@@ -0,0 +1,82 @@
+use ate::crypto::*;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::*;
+
+/// Public key that a user has registered so that they can login over SSH
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKey {
+    pub comment: String,
+    pub added: DateTime<Utc>,
+    /// Always 'WithSshKey' where the secret holds the base64 encoded public
+    /// key (as it appears in an authorized_keys file)
+    pub auth: AuthenticationMethod,
+}
+
+impl SshKey {
+    pub fn new(key_type: SshKeyType, public_key: String, comment: String) -> SshKey {
+        SshKey {
+            comment,
+            added: Utc::now(),
+            auth: AuthenticationMethod::WithSshKey {
+                key_type,
+                secret: public_key,
+            },
+        }
+    }
+
+    /// Parses a public key in the OpenSSH format (e.g. the contents of '~/.ssh/id_ed25519.pub')
+    pub fn from_openssh(line: &str) -> Option<SshKey> {
+        let mut parts = line.trim().splitn(3, char::is_whitespace);
+        let key_type = SshKeyType::from_openssh(parts.next()?)?;
+        let public_key = parts.next()?.trim();
+        if public_key.len() <= 0 || base64::decode(public_key).is_err() {
+            return None;
+        }
+        let comment = parts.next().unwrap_or_default().trim();
+        Some(SshKey::new(
+            key_type,
+            public_key.to_string(),
+            comment.to_string(),
+        ))
+    }
+
+    pub fn key_type(&self) -> Option<SshKeyType> {
+        match &self.auth {
+            AuthenticationMethod::WithSshKey { key_type, .. } => Some(*key_type),
+            _ => None,
+        }
+    }
+
+    pub fn public_key(&self) -> Option<&str> {
+        match &self.auth {
+            AuthenticationMethod::WithSshKey { secret, .. } => Some(secret.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Fingerprint of the key in the same form that 'ssh-keygen -l' prints it
+    /// (e.g. 'SHA256:...')
+    pub fn fingerprint(&self) -> Option<String> {
+        use sha2::Digest;
+        let blob = base64::decode(self.public_key()?).ok()?;
+        let hash = sha2::Sha256::digest(&blob[..]);
+        Some(format!(
+            "SHA256:{}",
+            base64::encode_config(hash, base64::STANDARD_NO_PAD)
+        ))
+    }
+}
+
+/// SSH keys registered by a user along with the login token that lets the
+/// authentication server open their account when one of the keys is used
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeys {
+    pub email: String,
+    pub token: EncryptedSecureData<EncryptKey>,
+    pub keys: Vec<SshKey>,
+}
@@ -2,10 +2,35 @@ use serde::*;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum SshKeyType {
     DSA,
     RSA,
     ED25519,
     ECDSA,
 }
+
+impl SshKeyType {
+    /// Parses the algorithm name that prefixes an OpenSSH public key
+    /// (e.g. 'ssh-ed25519')
+    pub fn from_openssh(name: &str) -> Option<SshKeyType> {
+        match name {
+            "ssh-dss" => Some(SshKeyType::DSA),
+            "ssh-rsa" | "rsa-sha2-256" | "rsa-sha2-512" => Some(SshKeyType::RSA),
+            "ssh-ed25519" => Some(SshKeyType::ED25519),
+            a if a.starts_with("ecdsa-sha2-") => Some(SshKeyType::ECDSA),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SshKeyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SshKeyType::DSA => write!(f, "dsa"),
+            SshKeyType::RSA => write!(f, "rsa"),
+            SshKeyType::ED25519 => write!(f, "ed25519"),
+            SshKeyType::ECDSA => write!(f, "ecdsa"),
+        }
+    }
+}
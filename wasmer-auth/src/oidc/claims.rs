@@ -0,0 +1,35 @@
+use serde::*;
+use std::collections::BTreeMap;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Facts about a user that were established when they signed in
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserClaims {
+    /// Identifier of the user (their uid) which stays the same even when
+    /// their email address changes
+    pub sub: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub uid: Option<u32>,
+    /// Methods the user authenticated with ('pwd' and when they supplied
+    /// their authenticator code also 'otp')
+    pub amr: Vec<String>,
+    /// Roles the user holds within the groups that were requested in the
+    /// scope (e.g. 'group:mygroup')
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub auth_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub user: UserClaims,
+}
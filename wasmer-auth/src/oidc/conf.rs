@@ -0,0 +1,61 @@
+use std::time::Duration;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Web application that is allowed to authenticate its users against the
+/// OpenID Connect provider
+#[derive(Debug, Clone)]
+pub struct OidcClient {
+    pub client_id: String,
+    pub redirect_uri: String,
+}
+
+impl std::str::FromStr for OidcClient {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((client_id, redirect_uri)) if client_id.len() > 0 && redirect_uri.len() > 0 => {
+                Ok(OidcClient {
+                    client_id: client_id.to_string(),
+                    redirect_uri: redirect_uri.to_string(),
+                })
+            }
+            _ => Err("clients must be supplied in the form 'client_id=redirect_uri'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcConf {
+    /// Public URL of the provider which is also the issuer of the ID tokens
+    pub issuer: url::Url,
+    /// List of the clients that may use the provider
+    pub clients: Vec<OidcClient>,
+    /// How long an authorization code may be exchanged for tokens
+    pub code_ttl: Duration,
+    /// How long the ID tokens and access tokens remain valid
+    pub token_ttl: Duration,
+}
+
+impl OidcConf {
+    pub fn new(issuer: url::Url, clients: Vec<OidcClient>) -> OidcConf {
+        OidcConf {
+            issuer,
+            clients,
+            code_ttl: Duration::from_secs(60),
+            token_ttl: Duration::from_secs(3600),
+        }
+    }
+
+    pub fn client(&self, client_id: &str, redirect_uri: &str) -> Option<&OidcClient> {
+        self.clients
+            .iter()
+            .find(|a| a.client_id == client_id && a.redirect_uri == redirect_uri)
+    }
+
+    /// Returns the full URL of one of the endpoints of the provider
+    pub fn endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.issuer.as_str().trim_end_matches('/'), path)
+    }
+}
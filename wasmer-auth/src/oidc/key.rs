@@ -0,0 +1,86 @@
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::*;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::crypto::AteHash;
+
+use crate::error::*;
+
+/// Key that the OpenID Connect provider signs its ID tokens with (ES256)
+pub struct OidcSigningKey {
+    pair: EcdsaKeyPair,
+    kid: String,
+    rng: SystemRandom,
+}
+
+impl OidcSigningKey {
+    /// Generates a new key and returns it in PKCS#8 format
+    pub fn generate() -> Vec<u8> {
+        let rng = SystemRandom::new();
+        EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .expect("failed to generate the OpenID Connect signing key")
+            .as_ref()
+            .to_vec()
+    }
+
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<OidcSigningKey, OidcError> {
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8)
+            .map_err(|err| OidcErrorKind::BadKey(err.to_string()))?;
+        let kid = AteHash::from_bytes(pair.public_key().as_ref()).to_8hex();
+        Ok(OidcSigningKey {
+            pair,
+            kid,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        self.kid.as_str()
+    }
+
+    /// Signs the claims and returns them as a compact JWT
+    pub fn sign<T>(&self, claims: &T) -> Result<String, OidcError>
+    where
+        T: Serialize,
+    {
+        let header = serde_json::json!({
+            "alg": "ES256",
+            "typ": "JWT",
+            "kid": self.kid,
+        });
+        let header = b64url(serde_json::to_vec(&header)?);
+        let claims = b64url(serde_json::to_vec(claims)?);
+        let message = format!("{}.{}", header, claims);
+        let signature = self
+            .pair
+            .sign(&self.rng, message.as_bytes())
+            .map_err(|err| OidcErrorKind::BadKey(err.to_string()))?;
+        Ok(format!("{}.{}", message, b64url(signature.as_ref())))
+    }
+
+    /// Returns the public half of the key as a JSON Web Key Set
+    pub fn jwks(&self) -> serde_json::Value {
+        // The public key is an uncompressed point (0x04 | x | y)
+        let public = self.pair.public_key().as_ref();
+        serde_json::json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "use": "sig",
+                "alg": "ES256",
+                "kid": self.kid,
+                "x": b64url(&public[1..33]),
+                "y": b64url(&public[33..65]),
+            }]
+        })
+    }
+}
+
+pub(crate) fn b64url<T>(data: T) -> String
+where
+    T: AsRef<[u8]>,
+{
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
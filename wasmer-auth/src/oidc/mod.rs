@@ -0,0 +1,9 @@
+mod claims;
+mod conf;
+mod key;
+mod server;
+
+pub use claims::*;
+pub use conf::*;
+pub use key::*;
+pub use server::*;
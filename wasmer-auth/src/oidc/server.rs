@@ -0,0 +1,722 @@
+use error_chain::bail;
+use hyper::header::{
+    HeaderValue, AUTHORIZATION, CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, COOKIE,
+    LOCATION, SET_COOKIE, X_FRAME_OPTIONS,
+};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use ring::digest;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::prelude::*;
+
+use super::*;
+use crate::error::*;
+use crate::helper::password_to_read_key;
+use crate::request::*;
+use crate::service::AuthService;
+
+/// Name of the cookie that holds the anti-forgery token of the sign in form
+const CSRF_COOKIE: &'static str = "oidc_csrf";
+/// How long the sign in form may be left open before it must be reloaded
+const CSRF_TTL: Duration = Duration::from_secs(900);
+
+/// Authorization code that is waiting to be exchanged for tokens
+struct AuthCode {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: String,
+    nonce: Option<String>,
+    scope: String,
+    auth_time: i64,
+    user: UserClaims,
+    expires: Instant,
+}
+
+/// Access token that was handed out along with an ID token
+struct AccessGrant {
+    user: UserClaims,
+    expires: Instant,
+}
+
+/// Parameters of an authorization request which are carried through the
+/// sign in form as hidden fields
+#[derive(Debug, Clone, Default)]
+struct AuthorizeParams {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    scope: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: String,
+    code_challenge_method: String,
+}
+
+impl AuthorizeParams {
+    fn parse(conf: &OidcConf, mut params: HashMap<String, String>) -> Result<Self, OidcError> {
+        let ret = AuthorizeParams {
+            response_type: params.remove("response_type").unwrap_or_default(),
+            client_id: params.remove("client_id").unwrap_or_default(),
+            redirect_uri: params.remove("redirect_uri").unwrap_or_default(),
+            scope: params.remove("scope").unwrap_or_default(),
+            state: params.remove("state"),
+            nonce: params.remove("nonce"),
+            code_challenge: params.remove("code_challenge").unwrap_or_default(),
+            code_challenge_method: params.remove("code_challenge_method").unwrap_or_default(),
+        };
+        if conf
+            .client(ret.client_id.as_str(), ret.redirect_uri.as_str())
+            .is_none()
+        {
+            bail!(OidcErrorKind::InvalidClient);
+        }
+        if ret.response_type != "code" {
+            bail!(OidcErrorKind::InvalidRequest(
+                "only the 'code' response type is supported".to_string()
+            ));
+        }
+        if ret.scope.split_whitespace().any(|a| a == "openid") == false {
+            bail!(OidcErrorKind::InvalidRequest(
+                "the 'openid' scope is required".to_string()
+            ));
+        }
+        if ret.code_challenge.len() <= 0 || ret.code_challenge_method != "S256" {
+            bail!(OidcErrorKind::InvalidRequest(
+                "a PKCE code challenge using 'S256' is required".to_string()
+            ));
+        }
+        Ok(ret)
+    }
+
+    fn fields(&self) -> Vec<(&'static str, &str)> {
+        let mut ret = vec![
+            ("response_type", self.response_type.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("scope", self.scope.as_str()),
+            ("code_challenge", self.code_challenge.as_str()),
+            ("code_challenge_method", self.code_challenge_method.as_str()),
+        ];
+        if let Some(state) = self.state.as_ref() {
+            ret.push(("state", state.as_str()));
+        }
+        if let Some(nonce) = self.nonce.as_ref() {
+            ret.push(("nonce", nonce.as_str()));
+        }
+        ret
+    }
+}
+
+/// OpenID Connect provider that lets other web applications sign their users
+/// in using the accounts held by the authentication server
+pub struct OidcServer {
+    conf: OidcConf,
+    key: OidcSigningKey,
+    service: Arc<AuthService>,
+    codes: Mutex<HashMap<String, AuthCode>>,
+    grants: Mutex<HashMap<String, AccessGrant>>,
+    /// Anti-forgery tokens of the sign in forms that were handed out
+    csrf: Mutex<HashMap<String, Instant>>,
+}
+
+impl OidcServer {
+    pub fn new(conf: OidcConf, key: OidcSigningKey, service: Arc<AuthService>) -> Arc<OidcServer> {
+        Arc::new(OidcServer {
+            conf,
+            key,
+            service,
+            codes: Mutex::new(HashMap::new()),
+            grants: Mutex::new(HashMap::new()),
+            csrf: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn listen(self: &Arc<Self>, addr: SocketAddr) -> Result<(), OidcError> {
+        let make_service = {
+            let server = Arc::clone(self);
+            make_service_fn(move |conn: &AddrStream| {
+                let server = server.clone();
+                let remote = conn.remote_addr();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req| server.clone().process(req, remote)))
+                }
+            })
+        };
+        info!("OpenID Connect provider listening on {}", addr);
+        hyper::Server::try_bind(&addr)?.serve(make_service).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn process(
+        self: Arc<Self>,
+        req: Request<Body>,
+        remote: SocketAddr,
+    ) -> Result<Response<Body>, Infallible> {
+        let mut ret = match self.route(req, remote).await {
+            Ok(a) => a,
+            Err(err) => {
+                debug!("oidc request failed - {}", err);
+                error_response(err).unwrap_or_else(|_| {
+                    let mut resp = Response::new(Body::empty());
+                    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    resp
+                })
+            }
+        };
+
+        // None of the pages may be framed by another site (which would let it
+        // trick the user into signing in)
+        ret.headers_mut()
+            .insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        ret.headers_mut().insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("frame-ancestors 'none'"),
+        );
+        Ok(ret)
+    }
+
+    async fn route(
+        &self,
+        req: Request<Body>,
+        remote: SocketAddr,
+    ) -> Result<Response<Body>, OidcError> {
+        // The provider may be hosted under a sub-path of the issuer
+        let prefix = self.conf.issuer.path().trim_end_matches('/');
+        let path = req.uri().path();
+        let path = path.strip_prefix(prefix).unwrap_or(path).to_string();
+
+        match (req.method(), path.as_str()) {
+            (&Method::GET, "/.well-known/openid-configuration") => self.discovery(),
+            (&Method::GET, "/jwks.json") => json_response(StatusCode::OK, self.key.jwks()),
+            (&Method::GET, "/authorize") => {
+                let params = parse_params(req.uri().query().unwrap_or_default());
+                let params = AuthorizeParams::parse(&self.conf, params)?;
+                self.sign_in_page(&params, None)
+            }
+            (&Method::POST, "/authorize") => self.authorize(req, remote).await,
+            (&Method::POST, "/token") => self.token(req).await,
+            (&Method::GET, "/userinfo") | (&Method::POST, "/userinfo") => self.userinfo(req),
+            _ => Err(OidcErrorKind::NotFound.into()),
+        }
+    }
+
+    fn discovery(&self) -> Result<Response<Body>, OidcError> {
+        let ret = serde_json::json!({
+            "issuer": self.conf.endpoint(""),
+            "authorization_endpoint": self.conf.endpoint("/authorize"),
+            "token_endpoint": self.conf.endpoint("/token"),
+            "userinfo_endpoint": self.conf.endpoint("/userinfo"),
+            "jwks_uri": self.conf.endpoint("/jwks.json"),
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["ES256"],
+            "token_endpoint_auth_methods_supported": ["none"],
+            "code_challenge_methods_supported": ["S256"],
+            "scopes_supported": ["openid", "email"],
+            "claims_supported": ["sub", "email", "email_verified", "uid", "amr", "groups", "nonce"],
+        });
+        json_response(StatusCode::OK, ret)
+    }
+
+    /// Signs the user in with their password (and optionally their authenticator
+    /// code) and then hands an authorization code back to the client
+    async fn authorize(
+        &self,
+        req: Request<Body>,
+        remote: SocketAddr,
+    ) -> Result<Response<Body>, OidcError> {
+        let cookie = req
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|a| a.to_str().ok())
+            .flat_map(|a| a.split(';'))
+            .filter_map(|a| a.trim().strip_prefix(CSRF_COOKIE))
+            .filter_map(|a| a.strip_prefix('='))
+            .map(|a| a.to_string())
+            .next();
+        let body = hyper::body::to_bytes(req.into_body()).await?;
+        let mut form = parse_params(String::from_utf8_lossy(&body[..]).as_ref());
+        let email = form.remove("email").unwrap_or_default().trim().to_string();
+        let password = form.remove("password").unwrap_or_default();
+        let code = form.remove("code").unwrap_or_default().trim().to_string();
+        let csrf = form.remove("csrf").unwrap_or_default();
+        let params = AuthorizeParams::parse(&self.conf, form)?;
+
+        // The form must have been handed out by us to the same browser (which
+        // stops other sites from signing the user into an account of theirs)
+        if self.redeem_csrf(csrf.as_str(), cookie.as_ref()) == false {
+            warn!("oidc sign in denied ({}) - invalid csrf token", email);
+            return self.sign_in_page(
+                &params,
+                Some("The sign in form has expired, please try again"),
+            );
+        }
+
+        // Login using the same secret that the command line tools would compute
+        let prefix = format!("remote-login:{}:", email);
+        let secret = password_to_read_key(&prefix, &password, 15, KeySize::Bit192);
+        let login = LoginRequest {
+            email: email.clone(),
+            secret,
+            verification_code: None,
+            token: None,
+        };
+        let source = Some(remote.ip().to_string());
+        let session = match self.service.clone().process_login_from(login, source).await {
+            Ok(a) => a.authority,
+            Err(LoginFailed::AccountLocked(_)) => {
+                return self.sign_in_page(&params, Some("This account is currently locked"));
+            }
+            Err(LoginFailed::Unverified(_)) => {
+                return self.sign_in_page(&params, Some("This account has not yet been verified"));
+            }
+            Err(_) => {
+                return self.sign_in_page(&params, Some("The email or password is incorrect"));
+            }
+        };
+        let uid = match session.user.uid() {
+            Some(a) => a,
+            None => {
+                return self.sign_in_page(&params, Some("This account can not be used to sign in"));
+            }
+        };
+
+        // The authenticator code elevates the session to sudo
+        let (session, amr) = match code.len() {
+            0 => (AteSessionInner::User(session), vec!["pwd".to_string()]),
+            _ => {
+                let sudo = SudoRequest {
+                    session,
+                    authenticator_code: code,
+                };
+                match self.service.clone().process_sudo(sudo).await {
+                    Ok(a) => (
+                        AteSessionInner::Sudo(a.authority),
+                        vec!["pwd".to_string(), "otp".to_string()],
+                    ),
+                    Err(SudoFailed::BreakGlass(_)) => {
+                        return self.sign_in_page(
+                            &params,
+                            Some("Sudo is disabled, please sign in without the authenticator code"),
+                        );
+                    }
+                    Err(_) => {
+                        return self
+                            .sign_in_page(&params, Some("The authenticator code is incorrect"));
+                    }
+                }
+            }
+        };
+
+        // Gather the roles the user holds in any of the groups that were requested
+        let mut groups = BTreeMap::new();
+        for group in params
+            .scope
+            .split_whitespace()
+            .filter_map(|a| a.strip_prefix("group:"))
+        {
+            let gather = GatherRequest {
+                session: session.clone(),
+                group: group.to_string(),
+                token: None,
+            };
+            if let Ok(a) = self.service.clone().process_gather(gather).await {
+                let roles = a
+                    .authority
+                    .group
+                    .roles
+                    .iter()
+                    .filter(|r| match &r.purpose {
+                        AteRolePurpose::Other(a) => {
+                            a.starts_with("previous-") == false && a.starts_with("chain-") == false
+                        }
+                        _ => true,
+                    })
+                    .map(|r| r.purpose.to_string())
+                    .collect();
+                groups.insert(group.to_string(), roles);
+            }
+        }
+
+        let user = UserClaims {
+            sub: uid.to_string(),
+            email: email.clone(),
+            email_verified: true,
+            uid: Some(uid),
+            amr,
+            groups,
+        };
+        info!("oidc sign in accepted ({}) for {}", email, params.client_id);
+
+        // Hand out a code that the client can exchange for the tokens
+        let now = Instant::now();
+        let code = random_code();
+        {
+            let mut codes = self.codes.lock().unwrap();
+            codes.retain(|_, a| a.expires > now);
+            codes.insert(
+                code.clone(),
+                AuthCode {
+                    client_id: params.client_id.clone(),
+                    redirect_uri: params.redirect_uri.clone(),
+                    code_challenge: params.code_challenge.clone(),
+                    nonce: params.nonce.clone(),
+                    scope: params.scope.clone(),
+                    auth_time: chrono::Utc::now().timestamp(),
+                    user,
+                    expires: now + self.conf.code_ttl,
+                },
+            );
+        }
+
+        let mut redirect = url::Url::parse(params.redirect_uri.as_str())
+            .map_err(|_| OidcErrorKind::InvalidClient)?;
+        redirect
+            .query_pairs_mut()
+            .append_pair("code", code.as_str());
+        if let Some(state) = params.state.as_ref() {
+            redirect
+                .query_pairs_mut()
+                .append_pair("state", state.as_str());
+        }
+        let mut resp = Response::new(Body::empty());
+        *resp.status_mut() = StatusCode::FOUND;
+        resp.headers_mut()
+            .insert(LOCATION, HeaderValue::from_str(redirect.as_str()).unwrap());
+        Ok(resp)
+    }
+
+    /// Exchanges an authorization code for an ID token and access token
+    async fn token(&self, req: Request<Body>) -> Result<Response<Body>, OidcError> {
+        let body = hyper::body::to_bytes(req.into_body()).await?;
+        let mut form = parse_params(String::from_utf8_lossy(&body[..]).as_ref());
+        let grant_type = form.remove("grant_type").unwrap_or_default();
+        if grant_type != "authorization_code" {
+            bail!(OidcErrorKind::UnsupportedGrantType(grant_type));
+        }
+        let code = form.remove("code").unwrap_or_default();
+        let verifier = form.remove("code_verifier").unwrap_or_default();
+
+        // Codes can only be used once
+        let code = match self.codes.lock().unwrap().remove(&code) {
+            Some(a) => a,
+            None => {
+                bail!(OidcErrorKind::InvalidGrant("unknown code".to_string()));
+            }
+        };
+        if code.expires <= Instant::now() {
+            bail!(OidcErrorKind::InvalidGrant(
+                "the code has expired".to_string()
+            ));
+        }
+        if form.get("client_id") != Some(&code.client_id)
+            || form.get("redirect_uri") != Some(&code.redirect_uri)
+        {
+            bail!(OidcErrorKind::InvalidGrant(
+                "the code was issued to another client".to_string()
+            ));
+        }
+        if pkce_challenge(verifier.as_str()) != code.code_challenge {
+            bail!(OidcErrorKind::InvalidGrant(
+                "the code verifier does not match the challenge".to_string()
+            ));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let ttl = self.conf.token_ttl;
+        let id_token = self.key.sign(&IdTokenClaims {
+            iss: self.conf.endpoint(""),
+            aud: code.client_id.clone(),
+            iat: now,
+            exp: now + ttl.as_secs() as i64,
+            auth_time: code.auth_time,
+            nonce: code.nonce.clone(),
+            user: code.user.clone(),
+        })?;
+
+        let access_token = random_code();
+        {
+            let now = Instant::now();
+            let mut grants = self.grants.lock().unwrap();
+            grants.retain(|_, a| a.expires > now);
+            grants.insert(
+                access_token.clone(),
+                AccessGrant {
+                    user: code.user,
+                    expires: now + ttl,
+                },
+            );
+        }
+
+        let ret = serde_json::json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": ttl.as_secs(),
+            "id_token": id_token,
+            "scope": code.scope,
+        });
+        json_response(StatusCode::OK, ret)
+    }
+
+    fn userinfo(&self, req: Request<Body>) -> Result<Response<Body>, OidcError> {
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|a| a.to_str().ok())
+            .and_then(|a| a.strip_prefix("Bearer "))
+            .ok_or(OidcErrorKind::InvalidToken)?;
+
+        let user = {
+            let grants = self.grants.lock().unwrap();
+            match grants.get(token.trim()) {
+                Some(a) if a.expires > Instant::now() => a.user.clone(),
+                _ => {
+                    bail!(OidcErrorKind::InvalidToken);
+                }
+            }
+        };
+        json_response(StatusCode::OK, serde_json::to_value(user)?)
+    }
+
+    /// Hands out a new anti-forgery token for a sign in form
+    fn issue_csrf(&self) -> String {
+        let now = Instant::now();
+        let csrf = random_code();
+        let mut tokens = self.csrf.lock().unwrap();
+        tokens.retain(|_, a| *a > now);
+        tokens.insert(csrf.clone(), now + CSRF_TTL);
+        csrf
+    }
+
+    /// Anti-forgery tokens can only be used once and must match the cookie
+    /// that was set along with the form
+    fn redeem_csrf(&self, csrf: &str, cookie: Option<&String>) -> bool {
+        if csrf.len() <= 0 || cookie.map(|a| a.as_str()) != Some(csrf) {
+            return false;
+        }
+        match self.csrf.lock().unwrap().remove(csrf) {
+            Some(expires) => expires > Instant::now(),
+            None => false,
+        }
+    }
+
+    fn sign_in_page(
+        &self,
+        params: &AuthorizeParams,
+        error: Option<&str>,
+    ) -> Result<Response<Body>, OidcError> {
+        let csrf = self.issue_csrf();
+        let mut hidden = format!(
+            "<input type=\"hidden\" name=\"csrf\" value=\"{}\">\n",
+            escape_html(csrf.as_str())
+        );
+        for (name, value) in params.fields() {
+            hidden.push_str(
+                format!(
+                    "<input type=\"hidden\" name=\"{}\" value=\"{}\">\n",
+                    name,
+                    escape_html(value)
+                )
+                .as_str(),
+            );
+        }
+        let error = match error {
+            Some(a) => format!("<p class=\"error\">{}</p>\n", escape_html(a)),
+            None => String::new(),
+        };
+        let html = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Sign in</title></head>
+<body>
+<h1>Sign in to {client}</h1>
+{error}<form method="post" action="{action}">
+{hidden}<label>Email <input type="email" name="email" required autofocus></label><br>
+<label>Password <input type="password" name="password" required></label><br>
+<label>Authenticator code (optional) <input type="text" name="code" autocomplete="one-time-code"></label><br>
+<button type="submit">Sign in</button>
+</form>
+</body>
+</html>
+"#,
+            client = escape_html(params.client_id.as_str()),
+            action = escape_html(self.conf.endpoint("/authorize").as_str()),
+            error = error,
+            hidden = hidden
+        );
+
+        let mut resp = Response::new(Body::from(html));
+        *resp.status_mut() = match error.len() {
+            0 => StatusCode::OK,
+            _ => StatusCode::UNAUTHORIZED,
+        };
+        resp.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        resp.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        let cookie = format!(
+            "{}={}; Path={}/authorize; Max-Age={}; HttpOnly; Secure; SameSite=Strict",
+            CSRF_COOKIE,
+            csrf,
+            self.conf.issuer.path().trim_end_matches('/'),
+            CSRF_TTL.as_secs()
+        );
+        resp.headers_mut().insert(
+            SET_COOKIE,
+            HeaderValue::from_str(cookie.as_str()).map_err(hyper::http::Error::from)?,
+        );
+        Ok(resp)
+    }
+}
+
+/// Computes the S256 code challenge for a PKCE code verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    b64url(digest::digest(&digest::SHA256, verifier.as_bytes()))
+}
+
+fn random_code() -> String {
+    format!(
+        "{}{}",
+        AteHash::generate().to_hex_string(),
+        AteHash::generate().to_hex_string()
+    )
+}
+
+fn parse_params(query: &str) -> HashMap<String, String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+fn escape_html(val: &str) -> String {
+    val.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn json_response(
+    status: StatusCode,
+    value: serde_json::Value,
+) -> Result<Response<Body>, OidcError> {
+    let resp = Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CACHE_CONTROL, "no-store")
+        .body(Body::from(serde_json::to_vec(&value)?))?;
+    Ok(resp)
+}
+
+fn error_response(err: OidcError) -> Result<Response<Body>, OidcError> {
+    let status = match err.kind() {
+        OidcErrorKind::NotFound => StatusCode::NOT_FOUND,
+        OidcErrorKind::InvalidClient | OidcErrorKind::InvalidToken => StatusCode::UNAUTHORIZED,
+        OidcErrorKind::InvalidRequest(_)
+        | OidcErrorKind::InvalidGrant(_)
+        | OidcErrorKind::UnsupportedGrantType(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let mut resp = json_response(
+        status,
+        serde_json::json!({
+            "error": err.kind().code(),
+            "error_description": err.to_string(),
+        }),
+    )?;
+    if let OidcErrorKind::InvalidToken = err.kind() {
+        resp.headers_mut().insert(
+            hyper::header::WWW_AUTHENTICATE,
+            HeaderValue::from_static("Bearer error=\"invalid_token\""),
+        );
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conf() -> OidcConf {
+        OidcConf::new(
+            url::Url::parse("https://localhost/oidc").unwrap(),
+            vec!["web=https://localhost/callback".parse().unwrap()],
+        )
+    }
+
+    fn test_params() -> HashMap<String, String> {
+        let mut ret = HashMap::new();
+        ret.insert("response_type".to_string(), "code".to_string());
+        ret.insert("client_id".to_string(), "web".to_string());
+        ret.insert(
+            "redirect_uri".to_string(),
+            "https://localhost/callback".to_string(),
+        );
+        ret.insert("scope".to_string(), "openid email".to_string());
+        ret.insert("state".to_string(), "xyz".to_string());
+        ret.insert("code_challenge".to_string(), "abc".to_string());
+        ret.insert("code_challenge_method".to_string(), "S256".to_string());
+        ret
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // Example from RFC 7636 (appendix B)
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert!(pkce_challenge("a") != pkce_challenge("b"));
+    }
+
+    #[test]
+    fn test_authorize_params() {
+        let conf = test_conf();
+        let params = AuthorizeParams::parse(&conf, test_params()).unwrap();
+        assert_eq!(params.client_id, "web");
+        assert_eq!(params.state.as_deref(), Some("xyz"));
+        assert!(params.fields().contains(&("state", "xyz")));
+        assert!(params.fields().iter().any(|(name, _)| *name == "nonce") == false);
+
+        let mut unknown = test_params();
+        unknown.insert("redirect_uri".to_string(), "https://evil/".to_string());
+        assert!(matches!(
+            AuthorizeParams::parse(&conf, unknown).unwrap_err().kind(),
+            OidcErrorKind::InvalidClient
+        ));
+
+        let mut implicit = test_params();
+        implicit.insert("response_type".to_string(), "token".to_string());
+        assert!(matches!(
+            AuthorizeParams::parse(&conf, implicit).unwrap_err().kind(),
+            OidcErrorKind::InvalidRequest(_)
+        ));
+
+        let mut no_openid = test_params();
+        no_openid.insert("scope".to_string(), "email".to_string());
+        assert!(AuthorizeParams::parse(&conf, no_openid).is_err());
+
+        let mut plain = test_params();
+        plain.insert("code_challenge_method".to_string(), "plain".to_string());
+        assert!(AuthorizeParams::parse(&conf, plain).is_err());
+
+        let mut no_challenge = test_params();
+        no_challenge.remove("code_challenge");
+        assert!(AuthorizeParams::parse(&conf, no_challenge).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+use clap::Parser;
+
+/// Registers an SSH public key that can then be used to login over SSH
+#[derive(Parser)]
+pub struct AddSshKey {
+    /// Path to the public key file (or the public key itself) in the OpenSSH format
+    #[clap(index = 1, default_value = "~/.ssh/id_ed25519.pub")]
+    pub key: String,
+    /// Comment that helps identify the key (defaults to the comment within the key)
+    #[clap(short, long)]
+    pub comment: Option<String>,
+}
@@ -0,0 +1,9 @@
+use clap::Parser;
+
+/// Lists the most recent logins, sudo attempts, recoveries and group changes of the user
+#[derive(Parser)]
+pub struct AuditUser {
+    /// Maximum number of events that will be listed
+    #[clap(short, long, default_value = "50")]
+    pub limit: usize,
+}
@@ -0,0 +1,40 @@
+use ate::prelude::*;
+use clap::Parser;
+
+/// Elevates the user to sudo for a limited period of time and only within the supplied scope,
+/// optionally waiting for an owner of a group to approve it first
+#[derive(Parser)]
+pub struct ElevateToken {
+    /// Email address that you wish to login using
+    #[clap(index = 1)]
+    pub email: Option<String>,
+    /// Password associated with this account
+    #[clap(index = 2)]
+    pub password: Option<String>,
+    /// Authenticator code from your google authenticator
+    #[clap(long)]
+    pub code: Option<String>,
+    /// Number of minutes until the elevated token expires
+    #[clap(long, default_value = "15")]
+    pub minutes: u32,
+    /// Reason for the elevation which is recorded in the audit log
+    #[clap(long, default_value = "")]
+    pub reason: String,
+    /// Group that the elevation will be limited to (when no group is supplied the token will
+    /// hold the sudo session of the user)
+    #[clap(short, long)]
+    pub group: Option<String>,
+    /// Role within the group that the elevated token will hold, can be used multiple times
+    #[clap(long = "role", requires = "group")]
+    pub roles: Vec<AteRolePurpose>,
+    /// Command that the elevated token may be used for (e.g. 'group.add-user'), can be used
+    /// multiple times
+    #[clap(long = "operation")]
+    pub operations: Vec<String>,
+    /// Group whose owners must approve the elevation before it can be redeemed (break-glass)
+    #[clap(long, conflicts_with = "redeem")]
+    pub approval: Option<String>,
+    /// Redeems an elevation that has been approved using its identifier
+    #[clap(long)]
+    pub redeem: Option<String>,
+}
@@ -0,0 +1,10 @@
+use clap::Parser;
+
+/// Opts in to recovering the account by email, while enabled the authentication server keeps a
+/// copy of the key that opens the account (which only the server itself is able to read)
+#[derive(Parser)]
+pub struct EnrolUserEmail {
+    /// Opts out of recovery by email and removes the copy of the key held by the server
+    #[clap(long)]
+    pub disable: bool,
+}
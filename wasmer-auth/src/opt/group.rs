@@ -27,4 +27,25 @@ pub enum GroupAction {
     /// Display the details about a particular group (token is required to see role membership)
     #[clap()]
     Details(GroupDetails),
+    /// Manages the custom roles of a group and binds roles to its databases
+    #[clap()]
+    Role(OptsGroupRole),
+    /// Approves an elevation that is waiting on an owner of the group
+    #[clap()]
+    ApproveElevation(GroupApproveElevation),
+}
+
+impl GroupAction {
+    /// Name of the command which access tokens can be limited to
+    pub fn operation(&self) -> &'static str {
+        match self {
+            GroupAction::Create(_) => "group.create",
+            GroupAction::RemoveGroup(_) => "group.remove-group",
+            GroupAction::AddUser(_) => "group.add-user",
+            GroupAction::RemoveUser(_) => "group.remove-user",
+            GroupAction::Details(_) => "group.details",
+            GroupAction::Role(_) => "group.role",
+            GroupAction::ApproveElevation(_) => "group.approve-elevation",
+        }
+    }
 }
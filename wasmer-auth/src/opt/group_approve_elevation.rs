@@ -0,0 +1,15 @@
+use clap::Parser;
+
+/// Approves an elevation that a user requested which must be approved by an owner of the group
+#[derive(Parser)]
+pub struct GroupApproveElevation {
+    /// Name of the group that the elevation is waiting on
+    #[clap(index = 1)]
+    pub group: String,
+    /// Username that requested the elevation
+    #[clap(index = 2)]
+    pub username: String,
+    /// Identifier of the elevation that will be approved
+    #[clap(index = 3)]
+    pub id: String,
+}
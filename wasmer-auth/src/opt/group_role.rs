@@ -0,0 +1,32 @@
+use clap::Parser;
+
+use super::*;
+
+#[derive(Parser)]
+#[clap()]
+pub struct OptsGroupRole {
+    #[clap(subcommand)]
+    pub action: GroupRoleAction,
+}
+
+#[derive(Parser)]
+pub enum GroupRoleAction {
+    /// Creates a custom role within a group
+    #[clap()]
+    Create(GroupRoleCreate),
+    /// Removes a custom role from a group along with all of its bindings
+    #[clap()]
+    Remove(GroupRoleRemove),
+    /// Grants one or more roles of a group to a user
+    #[clap()]
+    Grant(GroupRoleGrant),
+    /// Revokes one or more roles of a group from a user
+    #[clap()]
+    Revoke(GroupRoleRevoke),
+    /// Binds a role to one of the databases of the group
+    #[clap()]
+    Bind(GroupRoleBind),
+    /// Removes the binding between a role and a database of the group
+    #[clap()]
+    Unbind(GroupRoleUnbind),
+}
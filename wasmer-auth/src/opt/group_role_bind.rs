@@ -0,0 +1,22 @@
+use clap::Parser;
+
+/// Binds a role to one of the databases of a group ('[group]/[database]')
+#[derive(Parser)]
+pub struct GroupRoleBind {
+    /// Name of the group that holds the role
+    #[clap(index = 1)]
+    pub group: String,
+    /// Role that will be bound to the database
+    #[clap(index = 2)]
+    pub role: String,
+    /// Name of the database within the group
+    #[clap(index = 3)]
+    pub database: String,
+    /// Members of the role will be able to read the database
+    #[clap(long)]
+    pub read: bool,
+    /// Members of the role will be able to write to the database (when neither
+    /// this nor read are given the role may both read and write)
+    #[clap(long)]
+    pub write: bool,
+}
@@ -0,0 +1,12 @@
+use clap::Parser;
+
+/// Creates a custom role within a group
+#[derive(Parser)]
+pub struct GroupRoleCreate {
+    /// Name of the group that the role will be created in
+    #[clap(index = 1)]
+    pub group: String,
+    /// Name of the role (letters, digits, '_' and '-' only)
+    #[clap(index = 2)]
+    pub role: String,
+}
@@ -0,0 +1,16 @@
+use clap::Parser;
+
+/// Grants roles of a group to a particular user
+#[derive(Parser)]
+pub struct GroupRoleGrant {
+    /// Name of the group that holds the roles
+    #[clap(index = 1)]
+    pub group: String,
+    /// Username that will be granted the roles
+    #[clap(index = 2)]
+    pub username: String,
+    /// Roles that will be granted, either built-in roles or custom roles
+    /// that were created with 'group role create'
+    #[clap(index = 3, required = true)]
+    pub roles: Vec<String>,
+}
@@ -0,0 +1,12 @@
+use clap::Parser;
+
+/// Removes a custom role from a group
+#[derive(Parser)]
+pub struct GroupRoleRemove {
+    /// Name of the group that the role will be removed from
+    #[clap(index = 1)]
+    pub group: String,
+    /// Name of the custom role to be removed
+    #[clap(index = 2)]
+    pub role: String,
+}
@@ -0,0 +1,15 @@
+use clap::Parser;
+
+/// Revokes roles of a group from a particular user
+#[derive(Parser)]
+pub struct GroupRoleRevoke {
+    /// Name of the group that holds the roles
+    #[clap(index = 1)]
+    pub group: String,
+    /// Username that the roles will be revoked from
+    #[clap(index = 2)]
+    pub username: String,
+    /// Roles that will be revoked
+    #[clap(index = 3, required = true)]
+    pub roles: Vec<String>,
+}
@@ -0,0 +1,15 @@
+use clap::Parser;
+
+/// Removes the binding between a role and a database of a group
+#[derive(Parser)]
+pub struct GroupRoleUnbind {
+    /// Name of the group that holds the role
+    #[clap(index = 1)]
+    pub group: String,
+    /// Role that will be unbound from the database
+    #[clap(index = 2)]
+    pub role: String,
+    /// Name of the database within the group
+    #[clap(index = 3)]
+    pub database: String,
+}
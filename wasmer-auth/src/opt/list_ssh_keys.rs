@@ -0,0 +1,5 @@
+use clap::Parser;
+
+/// Lists all the SSH keys registered with the account
+#[derive(Parser)]
+pub struct ListSshKeys {}
@@ -0,0 +1,5 @@
+use clap::Parser;
+
+/// Lists all the access tokens that have been minted by the user
+#[derive(Parser)]
+pub struct ListTokens {}
@@ -0,0 +1,29 @@
+use ate::prelude::*;
+use clap::Parser;
+
+/// Mints an access token that is limited in scope and time and which can later be revoked
+#[derive(Parser)]
+pub struct MintToken {
+    /// Name of the token which helps identify it when the tokens are listed
+    #[clap(index = 1)]
+    pub name: String,
+    /// Group that the token will be limited to (when no group is supplied the token will hold
+    /// the session of the user)
+    #[clap(short, long)]
+    pub group: Option<String>,
+    /// Role within the group that the token will hold, can be used multiple times (when no roles
+    /// are supplied the token will hold all the roles that the user has within the group)
+    #[clap(long = "role", requires = "group")]
+    pub roles: Vec<AteRolePurpose>,
+    /// Command that the token may be used for (e.g. 'group.add-user'), can be used multiple
+    /// times (when no operations are supplied the token may be used for any command)
+    #[clap(long = "operation")]
+    pub operations: Vec<String>,
+    /// Number of hours until the token expires (when not supplied the token remains valid until
+    /// it is revoked)
+    #[clap(long)]
+    pub expires: Option<u32>,
+    /// Determines if sudo permissions should be held by the token
+    #[clap(long)]
+    pub sudo: bool,
+}
@@ -1,37 +1,75 @@
+mod add_ssh_key;
+mod audit_user;
 mod core;
 mod create_group;
 mod create_user;
 mod database;
 mod database_details;
 mod database_truncate;
+mod elevate_token;
+mod enrol_user_email;
 mod gather_permissions;
 mod generate_token;
 mod generate_token_sudo;
 mod group;
 mod group_add_user;
+mod group_approve_elevation;
 mod group_details;
 mod group_remove;
 mod group_remove_user;
+mod group_role;
+mod group_role_bind;
+mod group_role_create;
+mod group_role_grant;
+mod group_role_remove;
+mod group_role_revoke;
+mod group_role_unbind;
+mod list_ssh_keys;
+mod list_tokens;
+mod mint_token;
+mod recover_user_email;
+mod remove_ssh_key;
 mod reset_user;
+mod revoke_token;
+mod ssh_key;
 mod token;
 mod user;
 mod view_token;
 
 pub use self::core::*;
+pub use add_ssh_key::*;
+pub use audit_user::*;
 pub use create_group::*;
 pub use create_user::*;
 pub use database::*;
 pub use database_details::*;
 pub use database_truncate::*;
+pub use elevate_token::*;
+pub use enrol_user_email::*;
 pub use gather_permissions::*;
 pub use generate_token::*;
 pub use generate_token_sudo::*;
 pub use group::*;
 pub use group_add_user::*;
+pub use group_approve_elevation::*;
 pub use group_details::*;
 pub use group_remove::*;
 pub use group_remove_user::*;
+pub use group_role::*;
+pub use group_role_bind::*;
+pub use group_role_create::*;
+pub use group_role_grant::*;
+pub use group_role_remove::*;
+pub use group_role_revoke::*;
+pub use group_role_unbind::*;
+pub use list_ssh_keys::*;
+pub use list_tokens::*;
+pub use mint_token::*;
+pub use recover_user_email::*;
+pub use remove_ssh_key::*;
 pub use reset_user::*;
+pub use revoke_token::*;
+pub use ssh_key::*;
 pub use token::*;
 pub use user::*;
 pub use view_token::*;
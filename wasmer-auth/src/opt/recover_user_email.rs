@@ -0,0 +1,18 @@
+use clap::Parser;
+
+/// Recovers a lost account using a code that is emailed to you along with your authenticator
+#[derive(Parser)]
+pub struct RecoverUserEmail {
+    /// Email address of the user to be recovered
+    #[clap(index = 1)]
+    pub email: Option<String>,
+    /// Code that was emailed to you (when not supplied a new one will be emailed)
+    #[clap(index = 2)]
+    pub code: Option<String>,
+    /// New password for the user
+    #[clap(index = 3)]
+    pub new_password: Option<String>,
+    /// The authenticator code from your mobile authenticator
+    #[clap(index = 4)]
+    pub auth_code: Option<String>,
+}
@@ -0,0 +1,10 @@
+use clap::Parser;
+
+/// Removes an SSH key so that it can no longer be used to login
+#[derive(Parser)]
+pub struct RemoveSshKey {
+    /// Path to the public key file, the public key itself or its fingerprint (as
+    /// shown by 'user ssh-key list')
+    #[clap(index = 1)]
+    pub key: String,
+}
@@ -0,0 +1,9 @@
+use clap::Parser;
+
+/// Revokes an access token so that it can no longer be used
+#[derive(Parser)]
+pub struct RevokeToken {
+    /// Identifier of the token that will be revoked (as shown when the tokens are listed)
+    #[clap(index = 1)]
+    pub id: String,
+}
@@ -0,0 +1,23 @@
+use clap::Parser;
+
+use super::*;
+
+#[derive(Parser)]
+#[clap()]
+pub struct OptsSshKey {
+    #[clap(subcommand)]
+    pub action: SshKeyAction,
+}
+
+#[derive(Parser)]
+pub enum SshKeyAction {
+    /// Registers an SSH public key that can then be used to login over SSH
+    #[clap()]
+    Add(AddSshKey),
+    /// Lists all the SSH keys registered with the account
+    #[clap()]
+    List(ListSshKeys),
+    /// Removes an SSH key so that it can no longer be used to login
+    #[clap()]
+    Remove(RemoveSshKey),
+}
@@ -24,4 +24,34 @@ pub enum TokenAction {
     /// Views the contents of the supplied token
     #[clap()]
     View(ViewToken),
+    /// Mints an access token that is limited to a group, a set of roles or operations and that
+    /// expires after a period of time
+    #[clap()]
+    Mint(MintToken),
+    /// Lists all the access tokens that have been minted by the user
+    #[clap()]
+    List(ListTokens),
+    /// Revokes an access token so that it can no longer be used
+    #[clap()]
+    Revoke(RevokeToken),
+    /// Elevates to sudo for a limited period of time within a limited scope, optionally only
+    /// after an owner of a group has approved it
+    #[clap()]
+    Elevate(ElevateToken),
+}
+
+impl TokenAction {
+    /// Name of the command which access tokens can be limited to
+    pub fn operation(&self) -> &'static str {
+        match self {
+            TokenAction::Generate(_) => "token.generate",
+            TokenAction::Sudo(_) => "token.sudo",
+            TokenAction::Gather(_) => "token.gather",
+            TokenAction::View(_) => "token.view",
+            TokenAction::Mint(_) => "token.mint",
+            TokenAction::List(_) => "token.list",
+            TokenAction::Revoke(_) => "token.revoke",
+            TokenAction::Elevate(_) => "token.elevate",
+        }
+    }
 }
@@ -20,4 +20,32 @@ pub enum UserAction {
     /// Recovers a lost account using your recovery code
     #[clap()]
     Recover(ResetUser),
+    /// Recovers a lost account using a code that is emailed to you
+    #[clap()]
+    RecoverEmail(RecoverUserEmail),
+    /// Opts in (or out) of recovering the account by email
+    #[clap()]
+    EnrolEmail(EnrolUserEmail),
+    /// Manages the SSH keys that can be used to login over SSH
+    #[clap()]
+    SshKey(OptsSshKey),
+    /// Lists the most recent security events of the user (logins, sudo, recoveries and group
+    /// changes) along with their outcome
+    #[clap()]
+    Audit(AuditUser),
+}
+
+impl UserAction {
+    /// Name of the command which access tokens can be limited to
+    pub fn operation(&self) -> &'static str {
+        match self {
+            UserAction::Create(_) => "user.create",
+            UserAction::Details => "user.details",
+            UserAction::Recover(_) => "user.recover",
+            UserAction::RecoverEmail(_) => "user.recover-email",
+            UserAction::EnrolEmail(_) => "user.enrol-email",
+            UserAction::SshKey(_) => "user.ssh-key",
+            UserAction::Audit(_) => "user.audit",
+        }
+    }
 }
@@ -0,0 +1,33 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use serde::*;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use crate::model::AuditEvent;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditListRequest {
+    pub session: AteSessionInner,
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditListResponse {
+    pub events: Vec<AuditEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum AuditListFailed {
+    NoAccess,
+    NoMasterKey,
+    InternalError(u16),
+}
+
+impl<E> From<E> for AuditListFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        AuditListFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
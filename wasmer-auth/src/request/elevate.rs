@@ -0,0 +1,75 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use serde::*;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use super::SudoFailed;
+use crate::helper::ScopedToken;
+use crate::model::Elevation;
+use crate::model::TokenScope;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ElevateRequest {
+    pub session: AteSessionUser,
+    pub authenticator_code: String,
+    pub minutes: u32,
+    pub scope: TokenScope,
+    /// Group that one of the owners of must approve the elevation first
+    pub approval: Option<String>,
+    pub reason: String,
+}
+
+/// The elevation token is only returned once the elevation is redeemed,
+/// which happens straight away when no approval is needed (the sudo session
+/// stays sealed within the token and is only opened by the server)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ElevateResponse {
+    pub elevation: Elevation,
+    pub token: Option<ScopedToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ElevationApproveRequest {
+    pub group: String,
+    pub session: AteSessionGroup,
+    pub identity: String,
+    pub id: AteHash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ElevationApproveResponse {
+    pub elevation: Elevation,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ElevationRedeemRequest {
+    pub session: AteSessionUser,
+    pub authenticator_code: String,
+    pub id: AteHash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ElevationFailed {
+    NotFound(AteHash),
+    NotApproved(AteHash),
+    AlreadyRedeemed(AteHash),
+    Expired(AteHash),
+    InvalidDuration(u32),
+    OutOfScope(String),
+    UnsupportedScope,
+    SelfApproval,
+    GroupNotFound,
+    NoAccess,
+    NoMasterKey,
+    SudoFailed(SudoFailed),
+    InternalError(u16),
+}
+
+impl<E> From<E> for ElevationFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        ElevationFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
@@ -0,0 +1,87 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use serde::*;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+/// Asks the server to email a time limited recovery code to the user (the
+/// response is the same whether or not the account exists)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailRecoveryRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailRecoveryResponse {
+    /// How long the emailed code remains valid for
+    pub expires_in: std::time::Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum EmailRecoveryFailed {
+    MailDisabled,
+    MissingToken,
+    NoAccess,
+    NoMasterKey,
+    InternalError(u16),
+}
+
+impl<E> From<E> for EmailRecoveryFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        EmailRecoveryFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
+
+/// Opts the user in (or out) of recovering their account by email, while
+/// they are enrolled the server keeps a copy of the key that opens their
+/// account which only the master key of the server can read
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailRecoveryEnrolRequest {
+    pub session: AteSessionInner,
+    pub enable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailRecoveryEnrolResponse {
+    pub enabled: bool,
+}
+
+/// Resets the login secret of a user who proved they own both their email
+/// address (with the emailed code) and their authenticator
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailResetRequest {
+    pub email: String,
+    pub code: String,
+    pub sudo_code: String,
+    pub new_secret: EncryptKey,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailResetResponse {
+    pub key: PrimaryKey,
+    /// The old recovery code can no longer be used so a new one is issued
+    pub recovery_code: String,
+    pub authority: AteSessionUser,
+    pub message_of_the_day: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum EmailResetFailed {
+    MailDisabled,
+    InvalidCode,
+    InvalidAuthenticatorCode,
+    RecoveryImpossible,
+    NoMasterKey,
+    InternalError(u16),
+}
+
+impl<E> From<E> for EmailResetFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        EmailResetFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
@@ -3,10 +3,16 @@ use ate::prelude::*;
 use serde::*;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use crate::helper::ScopedToken;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GatherRequest {
     pub session: AteSessionInner,
     pub group: String,
+    /// Scoped token that is exchanged for the roles it holds in the group
+    /// (the session is ignored when a token is supplied)
+    #[serde(default)]
+    pub token: Option<ScopedToken>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -3,10 +3,17 @@ use ate::prelude::*;
 use serde::*;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use crate::model::RoleBinding;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupDetailsRequest {
     pub group: String,
     pub session: Option<AteSessionGroup>,
+    /// Database of the group (i.e. the chain named `[group]/[database]`) that
+    /// a member of the group is using, it is recorded so that its records are
+    /// moved onto the new keys when the keys of the group are rotated
+    #[serde(default)]
+    pub database: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,6 +33,10 @@ pub struct GroupDetailsResponse {
     pub name: String,
     pub roles: Vec<GroupDetailsRoleResponse>,
     pub gid: u32,
+    /// Databases of the group that the roles are bound to (these are public
+    /// as the database servers need them to decide who may write)
+    #[serde(default)]
+    pub bindings: Vec<RoleBinding>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -3,10 +3,15 @@ use ate::prelude::*;
 use serde::*;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use crate::helper::ScopedToken;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupRemoveRequest {
     pub group: String,
     pub session: AteSessionGroup,
+    /// Elevation to act with instead of the session
+    #[serde(default)]
+    pub elevation: Option<ScopedToken>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
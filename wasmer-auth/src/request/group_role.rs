@@ -0,0 +1,79 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use serde::*;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use crate::helper::ScopedToken;
+use crate::model::RoleBinding;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupRoleCreateRequest {
+    pub group: String,
+    pub session: AteSessionGroup,
+    pub purpose: AteRolePurpose,
+    /// Elevation that the request acts with instead of the session (the server
+    /// checks it is still valid and covers the operation)
+    #[serde(default)]
+    pub elevation: Option<ScopedToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupRoleCreateResponse {
+    pub key: PrimaryKey,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupRoleRemoveRequest {
+    pub group: String,
+    pub session: AteSessionGroup,
+    pub purpose: AteRolePurpose,
+    #[serde(default)]
+    pub elevation: Option<ScopedToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupRoleRemoveResponse {
+    pub key: PrimaryKey,
+}
+
+/// Binds a role to one of the databases of the group (when both read and
+/// write are false the role is unbound from the database instead)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupRoleBindRequest {
+    pub group: String,
+    pub session: AteSessionGroup,
+    pub purpose: AteRolePurpose,
+    pub chain: String,
+    pub read: bool,
+    pub write: bool,
+    #[serde(default)]
+    pub elevation: Option<ScopedToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupRoleBindResponse {
+    pub key: PrimaryKey,
+    pub bindings: Vec<RoleBinding>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum GroupRoleFailed {
+    GroupNotFound,
+    RoleNotFound,
+    RoleExists,
+    BuiltInRole,
+    InvalidRole,
+    InvalidChain,
+    NoMasterKey,
+    NoAccess,
+    InternalError(u16),
+}
+
+impl<E> From<E> for GroupRoleFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        GroupRoleFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
@@ -3,6 +3,8 @@ use ate::prelude::*;
 use serde::*;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use crate::helper::ScopedToken;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupUserAddRequest {
     pub group: String,
@@ -10,6 +12,10 @@ pub struct GroupUserAddRequest {
     pub who_key: PublicEncryptKey,
     pub who_name: String,
     pub purpose: AteRolePurpose,
+    /// Elevation that the request acts with instead of the session (the server
+    /// checks it is still valid and covers the operation)
+    #[serde(default)]
+    pub elevation: Option<ScopedToken>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
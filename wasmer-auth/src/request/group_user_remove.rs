@@ -3,12 +3,17 @@ use ate::prelude::*;
 use serde::*;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use crate::helper::ScopedToken;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GroupUserRemoveRequest {
     pub group: String,
     pub session: AteSessionGroup,
     pub who: AteHash,
     pub purpose: AteRolePurpose,
+    /// Elevation to act with instead of the session
+    #[serde(default)]
+    pub elevation: Option<ScopedToken>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +28,7 @@ pub enum GroupUserRemoveFailed {
     NothingToRemove,
     NoMasterKey,
     NoAccess,
+    MemberNotFound(String),
     InternalError(u16),
 }
 
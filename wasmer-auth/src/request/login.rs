@@ -4,11 +4,17 @@ use serde::*;
 use std::time::Duration;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use crate::helper::ScopedToken;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LoginRequest {
     pub email: String,
     pub secret: EncryptKey,
     pub verification_code: Option<String>,
+    /// Scoped token that is exchanged for the session it holds (the secret is
+    /// ignored when a token is supplied)
+    #[serde(default)]
+    pub token: Option<ScopedToken>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,6 +26,9 @@ pub struct LoginResponse {
     pub sudo_write: PublicSignKey,
     pub authority: AteSessionUser,
     pub message_of_the_day: Option<String>,
+    /// Sudo rights held by the scoped token that the session was exchanged for
+    #[serde(default)]
+    pub sudo: Option<AteSessionSudo>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,6 +37,8 @@ pub enum LoginFailed {
     WrongPassword,
     AccountLocked(Duration),
     Unverified(String),
+    TokenRevoked(AteHash),
+    TokenExpired(AteHash),
     NoMasterKey,
     InternalError(u16),
 }
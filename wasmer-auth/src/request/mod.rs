@@ -1,23 +1,35 @@
+mod audit;
 mod create_group;
 mod create_user;
+mod elevate;
+mod email_recovery;
 mod gather;
 mod group_details;
 mod group_remove;
+mod group_role;
 mod group_user_add;
 mod group_user_remove;
 mod login;
 mod query;
 mod reset;
+mod ssh_key;
 mod sudo;
+mod token;
 
+pub use audit::*;
 pub use create_group::*;
 pub use create_user::*;
+pub use elevate::*;
+pub use email_recovery::*;
 pub use gather::*;
 pub use group_details::*;
 pub use group_remove::*;
+pub use group_role::*;
 pub use group_user_add::*;
 pub use group_user_remove::*;
 pub use login::*;
 pub use query::*;
 pub use reset::*;
+pub use ssh_key::*;
 pub use sudo::*;
+pub use token::*;
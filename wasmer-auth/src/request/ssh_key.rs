@@ -0,0 +1,79 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use serde::*;
+use std::time::Duration;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use crate::model::SshKey;
+use crate::model::SshKeyType;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeyAddRequest {
+    pub session: AteSessionInner,
+    pub key: SshKey,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeyAddResponse {
+    pub key: SshKey,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeyListRequest {
+    pub session: AteSessionInner,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeyListResponse {
+    pub keys: Vec<SshKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeyRemoveRequest {
+    pub session: AteSessionInner,
+    /// Public key to remove or its fingerprint (see `SshKey::fingerprint`)
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshKeyRemoveResponse {
+    pub key: SshKey,
+}
+
+/// Sent by an SSH server once a client has proven that it holds the private
+/// half of one of the keys registered by the user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SshLoginRequest {
+    pub email: String,
+    pub key_type: SshKeyType,
+    pub public_key: String,
+    pub timestamp: i64,
+    /// Address of the client that connected to the SSH server
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Proves that the request came from a trusted SSH server (see `ssh_login_proof`)
+    pub proof: AteHash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum SshKeyFailed {
+    NotFound(String),
+    InvalidKey,
+    UnknownKey,
+    MissingToken,
+    NotSupported,
+    AccountLocked(Duration),
+    Unverified(String),
+    NoAccess,
+    NoMasterKey,
+    InternalError(u16),
+}
+
+impl<E> From<E> for SshKeyFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        SshKeyFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
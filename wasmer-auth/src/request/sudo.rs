@@ -22,6 +22,7 @@ pub enum SudoFailed {
     WrongCode,
     AccountLocked(Duration),
     Unverified(String),
+    BreakGlass(String),
     NoMasterKey,
     InternalError(u16),
 }
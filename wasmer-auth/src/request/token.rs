@@ -0,0 +1,78 @@
+#![allow(unused_imports)]
+use ate::prelude::*;
+use serde::*;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use crate::model::AccessToken;
+use crate::model::TokenSeal;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenCreateRequest {
+    pub session: AteSessionInner,
+    pub token: AccessToken,
+    /// Session that the token hands out (it is cut down to the scope of the
+    /// token and sealed by the server)
+    #[serde(default)]
+    pub grant: Option<AteSessionType>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenCreateResponse {
+    pub id: AteHash,
+    #[serde(default)]
+    pub sealed: Option<EncryptedSecureData<TokenSeal>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenListRequest {
+    pub session: AteSessionInner,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenListResponse {
+    pub tokens: Vec<AccessToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenRevokeRequest {
+    pub session: AteSessionInner,
+    pub id: AteHash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenRevokeResponse {
+    pub id: AteHash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenCheckRequest {
+    pub identity: String,
+    pub id: AteHash,
+    /// Sealed session of the token which proves that the caller holds it
+    pub sealed: EncryptedSecureData<TokenSeal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenCheckResponse {
+    pub token: AccessToken,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum TokenFailed {
+    NotFound(AteHash),
+    Revoked(AteHash),
+    Expired(AteHash),
+    NoAccess,
+    NoMasterKey,
+    UnsupportedScope,
+    InternalError(u16),
+}
+
+impl<E> From<E> for TokenFailed
+where
+    E: std::error::Error + Sized,
+{
+    fn from(err: E) -> Self {
+        TokenFailed::InternalError(ate::utils::obscure_error(err))
+    }
+}
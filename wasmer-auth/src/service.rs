@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
@@ -10,6 +11,7 @@ use ::ate::prelude::*;
 use ::ate::time::TimeKeeper;
 
 use crate::helper::*;
+use crate::mail::*;
 use crate::model::*;
 use crate::request::*;
 
@@ -22,6 +24,24 @@ pub struct AuthService {
     pub time_keeper: TimeKeeper,
     pub terms_and_conditions: Option<String>,
     pub registry: Arc<Registry>,
+    /// Key shared with the SSH servers that are trusted to vouch for users
+    /// logging in with their SSH keys (when missing SSH key logins are disabled)
+    pub ssh_key: Option<EncryptKey>,
+    /// Failed logins of the source addresses that recently attempted to login
+    pub source_throttle: Mutex<HashMap<String, LoginThrottle>>,
+    /// Failed logins of accounts that do not exist, these lock out the same
+    /// way as real accounts so the lock out does not reveal who has an account
+    pub unknown_throttle: Mutex<HashMap<String, LoginThrottle>>,
+    /// Sends the verification and recovery emails (when missing users can
+    /// only recover their accounts with their recovery code)
+    pub mail: Option<MailConf>,
+    /// Address of the database servers that hold the databases of the groups
+    /// (when missing the records are not moved onto the keys of a group when
+    /// they are rotated)
+    pub db_url: Option<url::Url>,
+    /// Group whose owners must approve every elevation (break-glass), while
+    /// it is set users can no longer sudo other than through an elevation
+    pub break_glass: Option<String>,
 }
 
 impl AuthService {
@@ -33,6 +53,10 @@ impl AuthService {
         edge_key: EncryptKey,
         contract_key: EncryptKey,
         terms_and_conditions: Option<String>,
+        ssh_key: Option<EncryptKey>,
+        mail: Option<MailConf>,
+        db_url: Option<url::Url>,
+        break_glass: Option<String>,
     ) -> Result<Arc<AuthService>, TimeError> {
         let service = Arc::new(AuthService {
             auth_url,
@@ -47,66 +71,156 @@ impl AuthService {
                 .keep_alive(Duration::from_secs(60))
                 .cement(),
             terms_and_conditions,
+            ssh_key,
+            source_throttle: Mutex::new(HashMap::new()),
+            unknown_throttle: Mutex::new(HashMap::new()),
+            mail,
+            db_url,
+            break_glass,
         });
         Ok(service)
     }
 }
 
-pub async fn service_auth_handlers(
-    cfg: &ConfAte,
-    cmd_session: AteSessionUser,
-    auth_url: url::Url,
-    auth_session: AteSessionUser,
-    web_key: EncryptKey,
-    edge_key: EncryptKey,
-    contract_key: EncryptKey,
-    terms_and_conditions: Option<String>,
+/// Adds the handlers of the commands to a command chain, every chain shares
+/// the same service so that state such as the login throttles is not split
+/// while the logins are throttled by the address the chain was opened from
+pub fn service_auth_handlers(
+    cmd_session: &AteSessionUser,
+    service: &Arc<AuthService>,
     chain: &Arc<Chain>,
-) -> Result<(), TimeError> {
-    let service = AuthService::new(
-        cfg,
-        auth_url,
-        auth_session,
-        web_key,
-        edge_key,
-        contract_key,
-        terms_and_conditions,
-    )
-    .await?;
-    chain.add_service(&cmd_session, service.clone(), AuthService::process_login);
-    chain.add_service(&cmd_session, service.clone(), AuthService::process_sudo);
-    chain.add_service(&cmd_session, service.clone(), AuthService::process_reset);
-    chain.add_service(
-        &cmd_session,
+    source: Option<String>,
+) {
+    {
+        let source = source.clone();
+        chain.add_service(
+            cmd_session,
+            service.clone(),
+            move |service: Arc<AuthService>, request: LoginRequest| {
+                service.process_login_from(request, source.clone())
+            },
+        );
+    }
+    chain.add_service(cmd_session, service.clone(), AuthService::process_sudo);
+    chain.add_service(cmd_session, service.clone(), AuthService::process_reset);
+    chain.add_service(
+        cmd_session,
         service.clone(),
         AuthService::process_create_user,
     );
     chain.add_service(
-        &cmd_session,
+        cmd_session,
         service.clone(),
         AuthService::process_create_group,
     );
-    chain.add_service(&cmd_session, service.clone(), AuthService::process_query);
-    chain.add_service(&cmd_session, service.clone(), AuthService::process_gather);
+    chain.add_service(cmd_session, service.clone(), AuthService::process_query);
+    chain.add_service(cmd_session, service.clone(), AuthService::process_gather);
     chain.add_service(
-        &cmd_session,
+        cmd_session,
         service.clone(),
         AuthService::process_group_user_add,
     );
     chain.add_service(
-        &cmd_session,
+        cmd_session,
         service.clone(),
         AuthService::process_group_user_remove,
     );
     chain.add_service(
-        &cmd_session,
+        cmd_session,
         service.clone(),
         AuthService::process_group_details,
     );
     chain.add_service(
-        &cmd_session,
+        cmd_session,
         service.clone(),
         AuthService::process_group_remove,
     );
-    Ok(())
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_token_create,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_token_list,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_token_revoke,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_token_check,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_ssh_key_add,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_ssh_key_list,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_ssh_key_remove,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        move |service: Arc<AuthService>, request: SshLoginRequest| {
+            service.process_ssh_login_from(request, source.clone())
+        },
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_audit_list,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_email_recovery,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_email_reset,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_email_recovery_enrol,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_group_role_create,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_group_role_remove,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_group_role_bind,
+    );
+    chain.add_service(cmd_session, service.clone(), AuthService::process_elevate);
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_elevation_approve,
+    );
+    chain.add_service(
+        cmd_session,
+        service.clone(),
+        AuthService::process_elevation_redeem,
+    );
 }
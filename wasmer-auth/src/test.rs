@@ -2,12 +2,29 @@
 use crate::prelude::*;
 use ate::prelude::*;
 use ate::time::TimeKeeper;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 use url::Url;
 
 use crate::cmd::*;
+use crate::error::*;
+use crate::helper::*;
+use crate::mail::*;
+use crate::model::*;
+use crate::oidc::*;
+use crate::request::*;
 use crate::prelude::*;
+use crate::service::AuthService;
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+/// Record with children that inherit its read key
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TestLedger {
+    name: String,
+    entries: DaoVec<String>,
+}
 
 #[tokio::main(flavor = "current_thread")]
 #[test]
@@ -42,7 +59,8 @@ pub async fn test_create_user_and_group() {
     let port_offset = fastrand::u16(..1000);
     let port = 5000 + port_offset;
     let auth = Url::parse(format!("ws://localhost:{}/auth", port).as_str()).unwrap();
-    let flow = ChainFlow::new(
+    let root_session = session.clone();
+    let mut flow = ChainFlow::new(
         &cfg_ate,
         root_write_key,
         session,
@@ -52,6 +70,21 @@ pub async fn test_create_user_and_group() {
         &auth,
     );
 
+    // Emails are delivered to a local SMTP sink so that the test can read them
+    info!("starting the smtp sink");
+    let (mail_tx, mut mail_rx) = tokio::sync::mpsc::unbounded_channel();
+    let sink = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let sink_addr = sink.local_addr().unwrap().to_string();
+    tokio::spawn(smtp_sink(sink, mail_tx));
+    let sender = SmtpMailSender::new(sink_addr.as_str(), "noreply@nowhere.com");
+    let link_url = Url::parse("https://localhost/recover").unwrap();
+    let mail_conf = MailConf::new(Arc::new(sender), link_url);
+    flow.mail = Some(mail_conf.clone());
+
+    // SSH servers that hold this key may vouch for users logging in with keys
+    let ssh_login_key = EncryptKey::generate(KeySize::Bit192);
+    flow.ssh_key = Some(ssh_login_key.clone());
+
     // Create the server and listen on port 5000
     info!("creating server and listening on ports with routes");
     let mut cfg_mesh = ConfMesh::solo_from_url(
@@ -75,6 +108,7 @@ pub async fn test_create_user_and_group() {
     let response = main_create_user(Some(username.clone()), Some(password.clone()), auth.clone())
         .await
         .unwrap();
+    let qr_secret = response.qr_secret.clone();
     let session = response.authority;
 
     // Get the read key for the user
@@ -188,6 +222,45 @@ pub async fn test_create_user_and_group() {
         "The user should have had this role"
     );
 
+    // Unknown users must fail in the same way as a wrong password
+    info!("login with an unknown user");
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let response = login_command(
+        &registry,
+        "nobody@nowhere.com".to_string(),
+        password.clone(),
+        None,
+        auth.clone(),
+        false,
+    )
+    .await;
+    assert!(
+        matches!(response, Err(LoginError(LoginErrorKind::WrongPassword, _))),
+        "Unknown users should look like a wrong password"
+    );
+
+    // The logins should have been recorded in the audit log of the user
+    info!("check the audit log of 'joe.blogs'");
+    let user = main_login(Some(username.clone()), Some(password.clone()), auth.clone())
+        .await
+        .unwrap();
+    let audit = audit_list_command(
+        &registry,
+        &AteSessionInner::User(user),
+        100,
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    assert!(
+        audit
+            .events
+            .iter()
+            .any(|a| matches!(a.action, AuditAction::Login)
+                && matches!(a.outcome, AuditOutcome::Success)),
+        "The logins should have been audited"
+    );
+
     // Create a friend and add it to the new group we just added
     info!("create a friend account 'myfriend'");
     let friend_username = "myfriend@nowhere.come".to_string();
@@ -198,6 +271,7 @@ pub async fn test_create_user_and_group() {
     )
     .await
     .unwrap();
+    let friend_qr_secret = friend.qr_secret.clone();
     let friend_session = friend.authority;
 
     info!("add friend to the group 'mygroup'");
@@ -206,6 +280,7 @@ pub async fn test_create_user_and_group() {
         Some(friend_username.clone()),
         auth.clone(),
         &session,
+        None,
         "Group",
     )
     .await
@@ -246,12 +321,14 @@ pub async fn test_create_user_and_group() {
         .unwrap();
 
     // Remove user the role
+    let departed = friend.clone();
     info!("remove the 'friend' from the group");
     main_group_user_remove(
         Some(AteRolePurpose::Contributor),
         Some(friend_username.clone()),
         auth.clone(),
         &session,
+        None,
         "Group",
     )
     .await
@@ -273,4 +350,1334 @@ pub async fn test_create_user_and_group() {
             .is_none(),
         "The user should have had this role removed"
     );
+
+    // The keys of the role were rotated so the keys that the friend kept can not
+    // read anything that is written with the new keys
+    info!("check the old keys can not read records written after the removal");
+    let rotated = main_gather(
+        Some(group.clone()),
+        session.inner.clone(),
+        auth.clone(),
+        "Group",
+    )
+    .await
+    .unwrap();
+    let old_key = departed
+        .get_group_role(&AteRolePurpose::Contributor)
+        .and_then(|r| r.read_keys().next())
+        .expect("The friend should have held the contributor read key")
+        .clone();
+    let new_key = rotated
+        .get_group_role(&AteRolePurpose::Contributor)
+        .and_then(|r| r.read_keys().next())
+        .expect("The delegate should hold the new contributor read key")
+        .clone();
+    assert!(
+        old_key.hash() != new_key.hash(),
+        "The contributor read key should have been rotated"
+    );
+
+    let ledger = ChainBuilder::new(&cfg_ate)
+        .await
+        .temporal(true)
+        .build()
+        .open(&ChainKey::from(format!("test_rotation_{}", fastrand::u64(..))))
+        .await
+        .unwrap();
+    let mut writer = AteSessionGroup::new(rotated.inner.clone(), group.clone());
+    writer.add_group_read_key(&AteRolePurpose::Contributor, &new_key);
+    writer.add_group_read_key(
+        &previous_role_purpose(&AteRolePurpose::Contributor),
+        &old_key,
+    );
+    let (before, entry, after) = {
+        let dio = ledger.dio_mut(&writer).await;
+        let mut before = dio
+            .store(TestLedger {
+                name: "before".to_string(),
+                entries: DaoVec::new(),
+            })
+            .unwrap();
+        before.auth_mut().read = ReadOption::from_key(&old_key);
+        let entry = before.as_mut().entries.push("entry".to_string()).unwrap();
+        let mut after = dio
+            .store(TestLedger {
+                name: "after".to_string(),
+                entries: DaoVec::new(),
+            })
+            .unwrap();
+        after.auth_mut().read = ReadOption::from_key(&new_key);
+        let keys = (
+            before.key().clone(),
+            entry.key().clone(),
+            after.key().clone(),
+        );
+        dio.commit().await.unwrap();
+        keys
+    };
+    let dio = ledger.dio(&departed).await;
+    assert!(
+        dio.load::<TestLedger>(&after).await.is_err(),
+        "The old keys should not read records written with the new keys"
+    );
+    assert!(
+        dio.load::<TestLedger>(&before).await.is_ok(),
+        "The old keys should still read records that have not been moved"
+    );
+
+    // Once the records are moved onto the new keys (with a fresh inner key
+    // that the entries inherit) the old keys can not read any of them
+    info!("move the records onto the new keys");
+    let moved = reencrypt_chain(&ledger, &writer).await.unwrap();
+    assert_eq!(moved, 2, "The record and its entry should have been moved");
+    let dio = ledger.dio(&departed).await;
+    assert!(
+        dio.load::<TestLedger>(&before).await.is_err(),
+        "The old keys should not read records that were moved"
+    );
+    assert!(
+        dio.load::<String>(&entry).await.is_err(),
+        "The old keys should not read the children of records that were moved"
+    );
+    let mut current = AteSessionGroup::new(rotated.inner.clone(), group.clone());
+    current.add_group_read_key(&AteRolePurpose::Contributor, &new_key);
+    let dio = ledger.dio(&current).await;
+    assert_eq!(dio.load::<TestLedger>(&before).await.unwrap().name, "before");
+    assert_eq!(
+        dio.load::<String>(&entry).await.unwrap().as_str(),
+        "entry"
+    );
+
+    // The server moves the databases of the group in the background and then
+    // forgets the keys that were rotated
+    info!("wait for the rotated keys to be removed from the group");
+    let mut pruned = false;
+    for _ in 0..50u32 {
+        let check = main_gather(
+            Some(group.clone()),
+            session.inner.clone(),
+            auth.clone(),
+            "Group",
+        )
+        .await
+        .unwrap();
+        if check
+            .get_group_role(&previous_role_purpose(&AteRolePurpose::Contributor))
+            .is_none()
+        {
+            pruned = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(pruned, "The rotated keys should have been removed from the group");
+
+    // Create a custom role that can only read the 'ledger' database
+    info!("create the custom role 'auditors' and bind it to 'ledger'");
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let auditors = AteRolePurpose::Other("auditors".to_string());
+    group_role_create_command(&registry, &session, None, auditors.clone(), auth.clone())
+        .await
+        .unwrap();
+    let bound = group_role_bind_command(
+        &registry,
+        &session,
+        None,
+        auditors.clone(),
+        "ledger".to_string(),
+        true,
+        false,
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(bound.bindings.len(), 1);
+
+    // Custom roles must exist before they can be granted
+    info!("granting a custom role that does not exist should fail");
+    let ret = group_user_add_command(
+        &registry,
+        &session,
+        None,
+        AteRolePurpose::Other("auditorz".to_string()),
+        friend_username.clone(),
+        auth.clone(),
+    )
+    .await;
+    assert!(matches!(
+        ret,
+        Err(GroupUserAddError(GroupUserAddErrorKind::InvalidPurpose, _))
+    ));
+
+    info!("grant the 'auditors' role to the friend");
+    main_group_user_add(
+        Some(auditors.clone()),
+        Some(friend_username.clone()),
+        auth.clone(),
+        &session,
+        None,
+        "Group",
+    )
+    .await
+    .unwrap();
+    let friend = main_gather(
+        Some(group.clone()),
+        friend_session.clone().into(),
+        auth.clone(),
+        "Group",
+    )
+    .await
+    .unwrap();
+    assert!(
+        friend.get_group_role(&auditors).is_some(),
+        "The user should have the custom role"
+    );
+    let ledger_key = friend
+        .get_group_role(&chain_role_purpose("ledger"))
+        .expect("Should have the read keys of the ledger database")
+        .read_keys()
+        .next()
+        .expect("Should have a read key for the ledger database")
+        .clone();
+
+    // Records that the role writes to the database are encrypted with its key
+    info!("the 'auditors' role writes a record to 'ledger'");
+    let ledger = ChainBuilder::new(&cfg_ate)
+        .await
+        .temporal(true)
+        .build()
+        .open(&ChainKey::from(format!("test_binding_{}", fastrand::u64(..))))
+        .await
+        .unwrap();
+    let (audited, audit_entry) = {
+        let dio = ledger.dio_mut(&friend).await;
+        dio.set_default_read(
+            chain_read_option(&friend, "ledger").expect("Should read the ledger database"),
+        );
+        let mut audited = dio
+            .store(TestLedger {
+                name: "audited".to_string(),
+                entries: DaoVec::new(),
+            })
+            .unwrap();
+        let entry = audited.as_mut().entries.push("entry".to_string()).unwrap();
+        let keys = (audited.key().clone(), entry.key().clone());
+        dio.commit().await.unwrap();
+        keys
+    };
+    let dio = ledger.dio(&friend).await;
+    assert_eq!(
+        dio.load::<TestLedger>(&audited).await.unwrap().name,
+        "audited"
+    );
+    let dio = ledger.dio(&friend_session).await;
+    assert!(
+        dio.load::<TestLedger>(&audited).await.is_err(),
+        "Records should be encrypted with the key of the database"
+    );
+
+    // Unbinding the role rotates the key of the database
+    info!("unbind the 'auditors' role from 'ledger'");
+    group_role_bind_command(
+        &registry,
+        &session,
+        None,
+        auditors.clone(),
+        "ledger".to_string(),
+        false,
+        false,
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let friend = main_gather(
+        Some(group.clone()),
+        friend_session.clone().into(),
+        auth.clone(),
+        "Group",
+    )
+    .await
+    .unwrap();
+    assert!(
+        friend
+            .get_group_role(&chain_role_purpose("ledger"))
+            .is_none(),
+        "The user should no longer be able to read the ledger database"
+    );
+    let delegate = main_gather(
+        Some(group.clone()),
+        session.inner.clone(),
+        auth.clone(),
+        "Group",
+    )
+    .await
+    .unwrap();
+    let current_key = delegate
+        .get_group_role(&chain_role_purpose("ledger"))
+        .expect("Delegates should keep the read keys of the ledger database")
+        .read_keys()
+        .next()
+        .expect("Should have a read key for the ledger database")
+        .clone();
+    assert!(current_key.hash() != ledger_key.hash());
+
+    info!("an unbound role can not read the 'ledger' database");
+    let dio = ledger.dio(&friend).await;
+    assert!(
+        dio.load::<TestLedger>(&audited).await.is_err(),
+        "The unbound role should not read the records of the database"
+    );
+    assert!(
+        dio.load::<String>(&audit_entry).await.is_err(),
+        "The unbound role should not read the children of the records"
+    );
+    let dio = ledger.dio(&delegate).await;
+    assert_eq!(
+        dio.load::<TestLedger>(&audited).await.unwrap().name,
+        "audited"
+    );
+    assert_eq!(
+        dio.load::<String>(&audit_entry).await.unwrap().as_str(),
+        "entry"
+    );
+
+    // Mint an access token that only holds the observer role of the group
+    info!("mint a scoped token for 'mygroup'");
+    let registry = ate::mesh::Registry::new(&conf_cmd()).await.cement();
+    let user = main_login(Some(username.clone()), Some(password.clone()), auth.clone())
+        .await
+        .unwrap();
+    let user = AteSessionInner::User(user);
+    let scope = TokenScope {
+        group: Some(group.clone()),
+        roles: vec![AteRolePurpose::Observer],
+        chains: Vec::new(),
+        operations: Vec::new(),
+    };
+    let token = AccessToken {
+        id: AteHash::generate(),
+        name: "ci".to_string(),
+        issued: chrono::Utc::now(),
+        expires: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+        scope: scope.clone(),
+        revoked: false,
+        elevated: false,
+    };
+
+    // Chains are opened with the keys that the token holds so the server
+    // refuses to mint tokens that claim to be limited to some of them
+    let mut unenforceable = token.clone();
+    unenforceable.id = AteHash::generate();
+    unenforceable.scope.chains = vec![format!("{}/data", group)];
+    match token_create_command(
+        &registry,
+        &user,
+        unenforceable,
+        Some(AteSessionType::Group(session.clone())),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(TokenError(TokenErrorKind::UnsupportedScope, _)) => {}
+        _ => panic!("Tokens should not be limited to chains that the server can not enforce"),
+    }
+
+    let created = token_create_command(
+        &registry,
+        &user,
+        token.clone(),
+        Some(AteSessionType::Group(session.clone())),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let scoped = ScopedToken {
+        identity: username.clone(),
+        token,
+        sealed: created.sealed.expect("The server should seal the session of the token"),
+    };
+    let encoded = scoped_token_to_b64(&scoped).unwrap();
+    let scoped = b64_to_scoped_token(encoded.as_str())
+        .expect("The token should survive being encoded");
+    match b64_to_session(encoded.clone()) {
+        Err(LoginError(LoginErrorKind::TokenNotChecked, _)) => {}
+        _ => panic!("Scoped tokens should only be opened by the authentication server"),
+    }
+
+    // The session of the token is only handed out by the server
+    info!("exchange the token for the roles it holds");
+    let gathered = gather_token_command(&registry, group.clone(), &scoped, auth.clone())
+        .await
+        .unwrap();
+    assert!(gathered.get_group_role(&AteRolePurpose::Observer).is_some());
+    assert!(
+        gathered.get_group_role(&AteRolePurpose::Delegate).is_none(),
+        "The token should not hold roles outside of its scope"
+    );
+    match token_to_session(encoded.clone(), Some(&auth)).await {
+        Ok(AteSessionType::Group(a)) => {
+            assert!(a.get_group_role(&AteRolePurpose::Observer).is_some())
+        }
+        _ => panic!("The token should be exchanged for a group session"),
+    }
+
+    // Tokens can not be checked (or exchanged) without the seal that proves
+    // they are held by the caller
+    let mut forged = scoped.clone();
+    forged.token.id = AteHash::generate();
+    match token_check_command(&registry, &forged, auth.clone()).await {
+        Err(LoginError(LoginErrorKind::TokenRevoked(_), _)) => {}
+        _ => panic!("Tokens without a matching seal should be rejected"),
+    }
+    let mut forged = scoped.clone();
+    forged.identity = friend_username.clone();
+    assert!(
+        gather_token_command(&registry, group.clone(), &forged, auth.clone())
+            .await
+            .is_err(),
+        "Tokens should not be exchanged under someone else's name"
+    );
+
+    // The token is valid until it is revoked
+    info!("check and revoke the token");
+    token_check_command(&registry, &scoped, auth.clone())
+        .await
+        .unwrap();
+    let tokens = token_list_command(&registry, &user, auth.clone())
+        .await
+        .unwrap();
+    assert_eq!(tokens.tokens.len(), 1);
+    token_revoke_command(&registry, &user, scoped.token.id.clone(), auth.clone())
+        .await
+        .unwrap();
+    match token_check_command(&registry, &scoped, auth.clone()).await {
+        Err(LoginError(LoginErrorKind::TokenRevoked(_), _)) => {}
+        _ => panic!("The token should have been revoked"),
+    }
+    match gather_token_command(&registry, group.clone(), &scoped, auth.clone()).await {
+        Err(GatherError(GatherErrorKind::NoAccess, _)) => {}
+        _ => panic!("Revoked tokens should not be exchanged for their roles"),
+    }
+
+    // Elevate to sudo for a few minutes and only for adding users
+    info!("elevate 'joe.blogs' for five minutes");
+    timer.wait_for_high_accuracy().await;
+    let code = google_auth
+        .get_code(
+            qr_secret.as_str(),
+            timer.current_timestamp_as_duration().unwrap().as_secs() / 30,
+        )
+        .unwrap();
+    let user = main_login(Some(username.clone()), Some(password.clone()), auth.clone())
+        .await
+        .unwrap();
+    let scope = TokenScope {
+        operations: vec!["group.add-user".to_string()],
+        ..Default::default()
+    };
+    match elevate_command(
+        &registry,
+        &user,
+        code.clone(),
+        MAX_ELEVATION_MINUTES + 1,
+        scope.clone(),
+        None,
+        "too long".to_string(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(ElevationError(ElevationErrorKind::InvalidDuration(_), _)) => {}
+        _ => panic!("Elevations longer than a day should be rejected"),
+    }
+    let unenforceable = TokenScope {
+        chains: vec![format!("{}/data", group)],
+        ..scope.clone()
+    };
+    match elevate_command(
+        &registry,
+        &user,
+        code.clone(),
+        5,
+        unenforceable,
+        None,
+        "too narrow".to_string(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(ElevationError(ElevationErrorKind::UnsupportedScope, _)) => {}
+        _ => panic!("Elevations should not be limited to chains that the server can not enforce"),
+    }
+    let elevated = elevate_command(
+        &registry,
+        &user,
+        code.clone(),
+        5,
+        scope,
+        None,
+        "hotfix".to_string(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let scoped = elevated
+        .token
+        .expect("Elevations without an approval should be redeemed straight away");
+    let expires = scoped.token.expires.expect("Elevations should always expire");
+    assert!(expires <= chrono::Utc::now() + chrono::Duration::minutes(5));
+    assert!(scoped.token.scope.allows_operation("group.add-user"));
+    assert!(scoped.token.scope.allows_operation("group.remove-group") == false);
+    token_check_command(&registry, &scoped, auth.clone())
+        .await
+        .unwrap();
+    match login_token_command(&registry, &scoped, auth.clone()).await {
+        Err(_) => {}
+        _ => panic!("Elevations should not be exchanged for a session"),
+    }
+    match gather_token_command(&registry, group.clone(), &scoped, auth.clone()).await {
+        Err(GatherError(GatherErrorKind::NoAccess, _)) => {}
+        _ => panic!("Elevations should not be exchanged for the roles of a group"),
+    }
+
+    // Instead the elevation is sent with each command and checked by the server
+    info!("add 'myfriend' as an observer of 'mygroup' using the elevation");
+    let placeholder = AteSessionGroup::new(AteSessionInner::Nothing, group.clone());
+    group_user_add_command(
+        &registry,
+        &placeholder,
+        Some(&scoped),
+        AteRolePurpose::Observer,
+        friend_username.clone(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    match group_user_remove_command(
+        &registry,
+        &placeholder,
+        Some(&scoped),
+        AteRolePurpose::Observer,
+        friend_username.clone(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(GroupUserRemoveError(GroupUserRemoveErrorKind::NoAccess, _)) => {}
+        _ => panic!("Elevations should only be used for the operations in their scope"),
+    }
+    match group_remove_command(&registry, &placeholder, Some(&scoped), auth.clone()).await {
+        Err(GroupRemoveError(GroupRemoveErrorKind::NoAccess, _)) => {}
+        _ => panic!("Elevations should only be used for the operations in their scope"),
+    }
+    token_revoke_command(
+        &registry,
+        &AteSessionInner::User(user.clone()),
+        scoped.token.id.clone(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    match group_user_add_command(
+        &registry,
+        &placeholder,
+        Some(&scoped),
+        AteRolePurpose::Observer,
+        friend_username.clone(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(GroupUserAddError(GroupUserAddErrorKind::NoAccess, _)) => {}
+        _ => panic!("Revoked elevations should not be used"),
+    }
+
+    // Break-glass elevations are held back until an owner approves them
+    info!("request an elevation for 'myfriend' that needs approval");
+    timer.wait_for_high_accuracy().await;
+    let friend_code = google_auth
+        .get_code(
+            friend_qr_secret.as_str(),
+            timer.current_timestamp_as_duration().unwrap().as_secs() / 30,
+        )
+        .unwrap();
+    let friend_user = main_login(
+        Some(friend_username.clone()),
+        Some(password.clone()),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let pending = elevate_command(
+        &registry,
+        &friend_user,
+        friend_code.clone(),
+        30,
+        TokenScope::default(),
+        Some(group.clone()),
+        "incident".to_string(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    assert!(pending.token.is_none());
+    let id = pending.elevation.id.clone();
+    match elevation_redeem_command(
+        &registry,
+        &friend_user,
+        friend_code.clone(),
+        id.clone(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(ElevationError(ElevationErrorKind::NotApproved(_), _)) => {}
+        _ => panic!("Elevations should not be redeemed before they are approved"),
+    }
+
+    info!("only an owner of 'mygroup' may approve the elevation");
+    let delegate = main_gather(
+        Some(group.clone()),
+        user.clone().into(),
+        auth.clone(),
+        "Group",
+    )
+    .await
+    .unwrap();
+    match elevation_approve_command(
+        &registry,
+        &delegate,
+        friend_username.clone(),
+        id.clone(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(ElevationError(ElevationErrorKind::NoAccess, _)) => {}
+        _ => panic!("Only owners of the group should be able to approve elevations"),
+    }
+    let sudo = main_sudo(user.clone(), Some(code.clone()), auth.clone())
+        .await
+        .unwrap();
+    let owner = main_gather(
+        Some(group.clone()),
+        sudo.clone().into(),
+        auth.clone(),
+        "Group",
+    )
+    .await
+    .unwrap();
+    elevation_approve_command(
+        &registry,
+        &owner,
+        friend_username.clone(),
+        id.clone(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let redeemed = elevation_redeem_command(
+        &registry,
+        &friend_user,
+        friend_code.clone(),
+        id.clone(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let redeemed = redeemed
+        .token
+        .expect("Approved elevations should be redeemed for a token");
+    match elevation_redeem_command(&registry, &friend_user, friend_code, id, auth.clone()).await {
+        Err(ElevationError(ElevationErrorKind::AlreadyRedeemed(_), _)) => {}
+        _ => panic!("Elevations should only be redeemed once"),
+    }
+
+    // Recover an account that lost its password using an emailed code
+    info!("create an account 'lost.user' and recover it by email");
+    let lost_username = "lost.user@nowhere.com".to_string();
+    let lost = main_create_user(
+        Some(lost_username.clone()),
+        Some(password.clone()),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+
+    // Recovery by email is opt-in so nothing is sent until the user enrols
+    email_recovery_command(&registry, lost_username.clone(), auth.clone())
+        .await
+        .unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_secs(2), mail_rx.recv())
+            .await
+            .is_err(),
+        "Accounts that did not enrol should not be sent recovery emails"
+    );
+    let enrol = email_recovery_enrol_command(
+        &registry,
+        &AteSessionInner::User(lost.authority.clone()),
+        true,
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    assert!(enrol.enabled);
+    email_recovery_command(&registry, lost_username.clone(), auth.clone())
+        .await
+        .unwrap();
+    let code = emailed_code(&mut mail_rx, &lost_username).await;
+
+    timer.wait_for_high_accuracy().await;
+    let sudo_code = google_auth
+        .get_code(
+            lost.qr_secret.as_str(),
+            timer.current_timestamp_as_duration().unwrap().as_secs() / 30,
+        )
+        .unwrap();
+    let new_password = "letmeinagain".to_string();
+    match email_reset_command(
+        &registry,
+        lost_username.clone(),
+        "0000-0000-0000".to_string(),
+        sudo_code.clone(),
+        new_password.clone(),
+        auth.clone(),
+    )
+    .await
+    {
+        Err(EmailRecoveryError(EmailRecoveryErrorKind::InvalidCode, _)) => {}
+        _ => panic!("A code that was not emailed should have been rejected"),
+    }
+    let reset = email_reset_command(
+        &registry,
+        lost_username.clone(),
+        code,
+        sudo_code,
+        new_password.clone(),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    assert!(reset.recovery_code != lost.recovery_code);
+
+    // Only the new password works once the keys have been re-wrapped
+    info!("login to 'lost.user' with the new password");
+    main_login(
+        Some(lost_username.clone()),
+        Some(new_password.clone()),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let response = login_command(
+        &registry,
+        lost_username.clone(),
+        password.clone(),
+        None,
+        auth.clone(),
+        false,
+    )
+    .await;
+    assert!(
+        matches!(response, Err(LoginError(LoginErrorKind::WrongPassword, _))),
+        "The old password should no longer work"
+    );
+
+    // Sign in to a web application through the OpenID Connect provider
+    info!("sign in to a web application with OpenID Connect");
+    let service = AuthService::new(
+        &cfg_ate,
+        auth.clone(),
+        root_session,
+        web_read_key.clone(),
+        edge_read_key.clone(),
+        contract_read_key.clone(),
+        None,
+        None,
+        Some(mail_conf.clone()),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Elevations stop working as soon as they expire
+    info!("let the elevation of 'myfriend' expire");
+    let placeholder = AteSessionGroup::new(AteSessionInner::Nothing, group.clone());
+    service
+        .elevated_session(
+            group.as_str(),
+            "group.add-user",
+            placeholder.clone(),
+            Some(redeemed.clone()),
+        )
+        .await
+        .unwrap();
+    let (dio, mut tokens) = service
+        .load_access_tokens(&AteSessionInner::User(friend_user.clone()))
+        .await
+        .unwrap();
+    for token in tokens.as_mut().tokens.iter_mut() {
+        if token.id == redeemed.token.id {
+            token.expires = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+        }
+    }
+    dio.commit().await.unwrap();
+    match service
+        .elevated_session(
+            group.as_str(),
+            "group.add-user",
+            placeholder.clone(),
+            Some(redeemed.clone()),
+        )
+        .await
+    {
+        Err(ElevationFailed::Expired(_)) => {}
+        _ => panic!("Expired elevations should not be used"),
+    }
+
+    // While break-glass is enabled sudo is disabled and every elevation must
+    // be approved by an owner of the break-glass group
+    info!("sudo and elevate while break-glass is enabled");
+    let glass = AuthService::new(
+        &cfg_ate,
+        auth.clone(),
+        service.master_session.clone(),
+        web_read_key.clone(),
+        edge_read_key.clone(),
+        contract_read_key.clone(),
+        None,
+        None,
+        None,
+        None,
+        Some(group.clone()),
+    )
+    .await
+    .unwrap();
+    timer.wait_for_high_accuracy().await;
+    let code = google_auth
+        .get_code(
+            qr_secret.as_str(),
+            timer.current_timestamp_as_duration().unwrap().as_secs() / 30,
+        )
+        .unwrap();
+    let sudo_request = SudoRequest {
+        session: user.clone(),
+        authenticator_code: code.clone(),
+    };
+    match glass.clone().process_sudo(sudo_request).await {
+        Err(SudoFailed::BreakGlass(a)) => assert_eq!(a, group),
+        _ => panic!("Sudo should be disabled while break-glass is enabled"),
+    }
+    let elevate = ElevateRequest {
+        session: user.clone(),
+        authenticator_code: code,
+        minutes: 5,
+        scope: TokenScope::default(),
+        approval: None,
+        reason: "bypass".to_string(),
+    };
+    let pending = glass.clone().process_elevate(elevate).await.unwrap();
+    assert!(pending.token.is_none());
+    assert_eq!(pending.elevation.approval, Some(group.clone()));
+    let gather = GatherRequest {
+        session: AteSessionInner::Sudo(sudo.clone()),
+        group: group.clone(),
+        token: None,
+    };
+    let gathered = glass.clone().process_gather(gather).await.unwrap();
+    assert!(
+        gathered
+            .authority
+            .get_group_role(&AteRolePurpose::Owner)
+            .is_none(),
+        "Sudo sessions should not be given the owner role while break-glass is enabled"
+    );
+
+    // The failed login of 'nobody' earlier must not have left records behind
+    let nobody = "nobody@nowhere.com";
+    let chain_key = ate::utils::chain_key_4hex(nobody, Some("redo"));
+    let chain = service
+        .registry
+        .open(&service.auth_url, &chain_key, true)
+        .await
+        .unwrap();
+    let dio = chain.dio(&service.master_session).await;
+    assert!(
+        dio.exists(&PrimaryKey::from(format!("login-throttle:{}", nobody)))
+            .await
+            == false,
+        "Unknown accounts should not be given a login throttle"
+    );
+    assert!(
+        dio.exists(&PrimaryKey::from(format!("audit:{}", nobody)))
+            .await
+            == false,
+        "Unknown accounts should not be given an audit log"
+    );
+
+    let issuer = Url::parse("https://localhost/oidc").unwrap();
+    let client: OidcClient = "web=https://localhost/callback".parse().unwrap();
+    let oidc_key = OidcSigningKey::from_pkcs8(&OidcSigningKey::generate()[..]).unwrap();
+    let oidc = OidcServer::new(
+        OidcConf::new(issuer.clone(), vec![client.clone()]),
+        oidc_key,
+        service.clone(),
+    );
+    let uid = main_login(Some(username.clone()), Some(password.clone()), auth.clone())
+        .await
+        .unwrap()
+        .user
+        .uid()
+        .expect("Users should have a uid");
+
+    // The sign in form may not be framed nor posted without its csrf token
+    let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("response_type", "code")
+        .append_pair("client_id", "web")
+        .append_pair("redirect_uri", "https://localhost/callback")
+        .append_pair("scope", "openid email group:mygroup")
+        .append_pair("state", "xyz")
+        .append_pair("code_challenge", pkce_challenge(verifier).as_str())
+        .append_pair("code_challenge_method", "S256")
+        .finish();
+    let form = oidc_request(
+        &oidc,
+        Method::GET,
+        format!("/oidc/authorize?{}", query),
+        None,
+        "",
+    )
+    .await;
+    assert_eq!(form.status(), StatusCode::OK);
+    assert_eq!(form.headers()["x-frame-options"], "DENY");
+    assert!(form.headers()["content-security-policy"]
+        .to_str()
+        .unwrap()
+        .contains("frame-ancestors 'none'"));
+    let (cookie, csrf) = oidc_csrf(form).await;
+    let credentials = url::form_urlencoded::Serializer::for_suffix(query.clone(), 0)
+        .append_pair("email", username.as_str())
+        .append_pair("password", password.as_str())
+        .finish();
+    let forged = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/authorize".to_string(),
+        None,
+        credentials.as_str(),
+    )
+    .await;
+    assert_eq!(forged.status(), StatusCode::UNAUTHORIZED);
+    let signed_in = url::form_urlencoded::Serializer::for_suffix(credentials.clone(), 0)
+        .append_pair("csrf", csrf.as_str())
+        .finish();
+    let redirect = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/authorize".to_string(),
+        Some(cookie.as_str()),
+        signed_in.as_str(),
+    )
+    .await;
+    assert_eq!(redirect.status(), StatusCode::FOUND);
+    let redirect = Url::parse(redirect.headers()["location"].to_str().unwrap()).unwrap();
+    let code = redirect
+        .query_pairs()
+        .find(|(k, _)| k == "code")
+        .map(|(_, v)| v.to_string())
+        .expect("The redirect should carry a code");
+    assert!(redirect
+        .query_pairs()
+        .any(|(k, v)| k == "state" && v == "xyz"));
+    let replayed = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/authorize".to_string(),
+        Some(cookie.as_str()),
+        signed_in.as_str(),
+    )
+    .await;
+    assert_eq!(
+        replayed.status(),
+        StatusCode::UNAUTHORIZED,
+        "The csrf token should only be used once"
+    );
+
+    // The code is exchanged once for the tokens of the user
+    info!("exchange the OpenID Connect code for tokens");
+    let exchange = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("grant_type", "authorization_code")
+        .append_pair("code", code.as_str())
+        .append_pair("code_verifier", verifier)
+        .append_pair("client_id", "web")
+        .append_pair("redirect_uri", "https://localhost/callback")
+        .finish();
+    let tokens = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/token".to_string(),
+        None,
+        exchange.as_str(),
+    )
+    .await;
+    assert_eq!(tokens.status(), StatusCode::OK);
+    let tokens = oidc_json(tokens).await;
+    let access_token = tokens["access_token"].as_str().unwrap().to_string();
+    assert!(tokens["id_token"].as_str().unwrap().split('.').count() == 3);
+    let reused = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/token".to_string(),
+        None,
+        exchange.as_str(),
+    )
+    .await;
+    assert_eq!(reused.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(oidc_json(reused).await["error"], "invalid_grant");
+
+    // The access token reads the claims of the user
+    let userinfo = Request::builder()
+        .method(Method::GET)
+        .uri("/oidc/userinfo")
+        .header("Authorization", format!("Bearer {}", access_token))
+        .body(Body::empty())
+        .unwrap();
+    let userinfo = oidc.clone().process(userinfo, oidc_remote()).await.unwrap();
+    assert_eq!(userinfo.status(), StatusCode::OK);
+    let claims = oidc_json(userinfo).await;
+    assert_eq!(claims["sub"], uid.to_string());
+    assert_eq!(claims["email"], username.as_str());
+    assert!(claims["groups"]["mygroup"].as_array().unwrap().len() > 0);
+    let userinfo = Request::builder()
+        .method(Method::GET)
+        .uri("/oidc/userinfo")
+        .header("Authorization", "Bearer nothing")
+        .body(Body::empty())
+        .unwrap();
+    let userinfo = oidc.clone().process(userinfo, oidc_remote()).await.unwrap();
+    assert_eq!(userinfo.status(), StatusCode::UNAUTHORIZED);
+
+    // Codes that are not exchanged in time are rejected
+    info!("let an OpenID Connect code expire");
+    let mut conf = OidcConf::new(issuer, vec![client]);
+    conf.code_ttl = Duration::from_millis(0);
+    let oidc_key = OidcSigningKey::from_pkcs8(&OidcSigningKey::generate()[..]).unwrap();
+    let oidc = OidcServer::new(conf, oidc_key, service);
+    let form = oidc_request(
+        &oidc,
+        Method::GET,
+        format!("/oidc/authorize?{}", query),
+        None,
+        "",
+    )
+    .await;
+    let (cookie, csrf) = oidc_csrf(form).await;
+    let signed_in = url::form_urlencoded::Serializer::for_suffix(credentials, 0)
+        .append_pair("csrf", csrf.as_str())
+        .finish();
+    let redirect = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/authorize".to_string(),
+        Some(cookie.as_str()),
+        signed_in.as_str(),
+    )
+    .await;
+    let redirect = Url::parse(redirect.headers()["location"].to_str().unwrap()).unwrap();
+    let code = redirect
+        .query_pairs()
+        .find(|(k, _)| k == "code")
+        .map(|(_, v)| v.to_string())
+        .unwrap();
+    let exchange = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("grant_type", "authorization_code")
+        .append_pair("code", code.as_str())
+        .append_pair("code_verifier", verifier)
+        .append_pair("client_id", "web")
+        .append_pair("redirect_uri", "https://localhost/callback")
+        .finish();
+    let expired = oidc_request(
+        &oidc,
+        Method::POST,
+        "/oidc/token".to_string(),
+        None,
+        exchange.as_str(),
+    )
+    .await;
+    assert_eq!(expired.status(), StatusCode::BAD_REQUEST);
+    // Register an SSH key and login with it through a trusted SSH server
+    info!("login to 'lost.user' with an ssh key");
+    let lost = main_login(
+        Some(lost_username.clone()),
+        Some(new_password.clone()),
+        auth.clone(),
+    )
+    .await
+    .unwrap();
+    let lost = AteSessionInner::User(lost);
+    let public_key = "AAAAC3NzaC1lZDI1NTE5AAAAIAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8g";
+    let other_key = "AAAAC3NzaC1lZDI1NTE5AAAAICEiIyQlJicoKSorLC0uLzAxMjM0NTY3ODk6Ozw9Pj9A";
+    let key = SshKey::from_openssh(format!("ssh-ed25519 {} laptop", public_key).as_str())
+        .expect("The key should be parsed");
+    let fingerprint = key.fingerprint().unwrap();
+    assert_eq!(
+        fingerprint,
+        "SHA256:mKqU+0K8OhKmA8bBQi9Rz0Q5l7/g160hIP+rJYSTNj4"
+    );
+    ssh_key_add_command(&registry, &lost, key, auth.clone())
+        .await
+        .unwrap();
+    ssh_login(&registry, &auth, &ssh_login_key, &lost_username, public_key)
+        .await
+        .unwrap();
+    match ssh_login(&registry, &auth, &ssh_login_key, &lost_username, other_key).await {
+        Err(SshKeyFailed::UnknownKey) => {}
+        _ => panic!("Keys that were not registered should be rejected"),
+    }
+    let untrusted = EncryptKey::generate(KeySize::Bit192);
+    match ssh_login(&registry, &auth, &untrusted, &lost_username, public_key).await {
+        Err(SshKeyFailed::NoAccess) => {}
+        _ => panic!("Only trusted SSH servers should be able to vouch for a key"),
+    }
+
+    // Failed key logins lock the account just like wrong passwords
+    info!("lock 'lost.user' out with unknown ssh keys");
+    for _ in 0..4 {
+        let _ = ssh_login(&registry, &auth, &ssh_login_key, &lost_username, other_key).await;
+    }
+    match ssh_login(&registry, &auth, &ssh_login_key, &lost_username, public_key).await {
+        Err(SshKeyFailed::AccountLocked(_)) => {}
+        _ => panic!("Repeated failed ssh logins should lock the account"),
+    }
+
+    // Accounts that do not exist are locked out just like real ones so that
+    // the lock out does not reveal who has an account
+    info!("lock out an account that does not exist");
+    let unknown = LoginRequest {
+        email: "no.one@nowhere.com".to_string(),
+        secret: EncryptKey::generate(KeySize::Bit192),
+        verification_code: None,
+        token: None,
+    };
+    for _ in 0..5 {
+        match service.clone().process_login(unknown.clone()).await {
+            Err(LoginFailed::WrongPassword) => {}
+            _ => panic!("Unknown accounts should fail like wrong passwords"),
+        }
+    }
+    match service.clone().process_login(unknown).await {
+        Err(LoginFailed::AccountLocked(_)) => {}
+        _ => panic!("Unknown accounts should be locked out like real ones"),
+    }
+
+    // Keys are removed by their fingerprint (not by their comment)
+    info!("remove the ssh key of 'lost.user'");
+    assert!(
+        ssh_key_remove_command(&registry, &lost, "laptop".to_string(), auth.clone())
+            .await
+            .is_err(),
+        "Keys should not be removed by their comment"
+    );
+    let removed = ssh_key_remove_command(&registry, &lost, fingerprint, auth.clone())
+        .await
+        .unwrap();
+    assert_eq!(removed.key.comment, "laptop");
+    let keys = ssh_key_list_command(&registry, &lost, auth.clone())
+        .await
+        .unwrap();
+    assert_eq!(keys.keys.len(), 0);
+
+    // Accounts that are not yet verified are emailed a fresh code when they
+    // login without one (but only once the last code can no longer be used)
+    info!("create an unverified account and verify it by email");
+    let unverified_username = "unverified.user@nowhere.com".to_string();
+    let prefix = format!("remote-login:{}:", unverified_username);
+    let secret = password_to_read_key(&prefix, &password, 15, KeySize::Bit192);
+    service
+        .clone()
+        .process_create_user_internal(
+            CreateUserRequest {
+                auth: auth.to_string(),
+                email: unverified_username.clone(),
+                secret: secret.clone(),
+                accepted_terms: None,
+            },
+            UserStatus::Unverified,
+        )
+        .await
+        .unwrap();
+    let first_code = emailed_code(&mut mail_rx, &unverified_username).await;
+    let login = |verification_code: Option<String>| LoginRequest {
+        email: unverified_username.clone(),
+        secret: secret.clone(),
+        verification_code,
+        token: None,
+    };
+    match service.clone().process_login(login(None)).await {
+        Err(LoginFailed::Unverified(_)) => {}
+        _ => panic!("The account should not be verified yet"),
+    }
+    assert!(
+        tokio::time::timeout(Duration::from_secs(2), mail_rx.recv())
+            .await
+            .is_err(),
+        "Codes should not be resent while the last one is still fresh"
+    );
+    for _ in 0..5 {
+        match service
+            .clone()
+            .process_login(login(Some("0000-0000-0000".to_string())))
+            .await
+        {
+            Err(LoginFailed::WrongPassword) => {}
+            _ => panic!("Wrong verification codes should be rejected"),
+        }
+    }
+    match service.clone().process_login(login(None)).await {
+        Err(LoginFailed::Unverified(_)) => {}
+        _ => panic!("The account should not be verified yet"),
+    }
+    let code = emailed_code(&mut mail_rx, &unverified_username).await;
+    assert_ne!(code, first_code);
+    service
+        .clone()
+        .process_login(login(Some(code)))
+        .await
+        .expect("The resent code should verify the account");
+}
+
+/// Logs in with an SSH key the way that a trusted SSH server would
+async fn ssh_login(
+    registry: &Registry,
+    auth: &Url,
+    ssh_login_key: &EncryptKey,
+    email: &str,
+    public_key: &str,
+) -> Result<LoginResponse, SshKeyFailed> {
+    let timestamp = chrono::Utc::now().timestamp();
+    let login = SshLoginRequest {
+        email: email.to_string(),
+        key_type: SshKeyType::ED25519,
+        public_key: public_key.to_string(),
+        timestamp,
+        source: None,
+        proof: ssh_login_proof(ssh_login_key, email, public_key, timestamp, None),
+    };
+    let chain = registry.open_cmd(auth).await.unwrap();
+    chain.invoke(login).await.unwrap()
+}
+
+fn oidc_remote() -> std::net::SocketAddr {
+    "127.0.0.1:4000".parse().unwrap()
+}
+
+/// Sends a request straight to the OpenID Connect provider
+async fn oidc_request(
+    oidc: &Arc<OidcServer>,
+    method: Method,
+    uri: String,
+    cookie: Option<&str>,
+    body: &str,
+) -> Response<Body> {
+    let mut req = Request::builder().method(method).uri(uri);
+    if let Some(cookie) = cookie {
+        req = req.header("Cookie", cookie);
+    }
+    if body.len() > 0 {
+        req = req.header("Content-Type", "application/x-www-form-urlencoded");
+    }
+    let req = req.body(Body::from(body.to_string())).unwrap();
+    oidc.clone().process(req, oidc_remote()).await.unwrap()
+}
+
+/// Reads the csrf cookie and the matching hidden field of a sign in form
+async fn oidc_csrf(resp: Response<Body>) -> (String, String) {
+    let cookie = resp.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    let body = String::from_utf8_lossy(&body[..]).to_string();
+    let csrf = body
+        .split("name=\"csrf\" value=\"")
+        .nth(1)
+        .and_then(|a| a.split('"').next())
+        .expect("The sign in form should hold a csrf token")
+        .to_string();
+    (cookie, csrf)
+}
+
+async fn oidc_json(resp: Response<Body>) -> serde_json::Value {
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    serde_json::from_slice(&body[..]).unwrap()
+}
+
+/// Minimal SMTP server that accepts every email and passes on its data
+/// Waits for the next email and returns the code that was sent in it
+async fn emailed_code(
+    mail_rx: &mut tokio::sync::mpsc::UnboundedReceiver<String>,
+    email: &str,
+) -> String {
+    let mail = tokio::time::timeout(Duration::from_secs(10), mail_rx.recv())
+        .await
+        .expect("The email should have been sent")
+        .unwrap();
+    assert!(mail.contains(format!("To: <{}>", email).as_str()));
+    mail.lines()
+        .map(|a| a.trim())
+        .find(|a| a.len() == 14 && a.matches('-').count() == 2)
+        .expect("The email should contain a code")
+        .to_string()
+}
+
+async fn smtp_sink(
+    listener: tokio::net::TcpListener,
+    tx: tokio::sync::mpsc::UnboundedSender<String>,
+) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    while let Ok((stream, _)) = listener.accept().await {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut stream = BufReader::new(stream);
+            let _ = stream.write_all(b"220 sink ESMTP\r\n").await;
+            let mut data: Option<String> = None;
+            loop {
+                let mut line = String::new();
+                match stream.read_line(&mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                if let Some(d) = data.as_mut() {
+                    if line.trim_end() != "." {
+                        d.push_str(line.as_str());
+                        continue;
+                    }
+                    let _ = tx.send(data.take().unwrap());
+                    let _ = stream.write_all(b"250 OK\r\n").await;
+                    continue;
+                }
+
+                let command = line.trim_end().to_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250-sink\r\n250 8BITMIME\r\n"
+                } else if command == "DATA" {
+                    data = Some(String::new());
+                    b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                } else if command == "QUIT" {
+                    let _ = stream.write_all(b"221 Bye\r\n").await;
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                let _ = stream.write_all(reply).await;
+            }
+        });
+    }
 }
@@ -0,0 +1,133 @@
+#![allow(unused_imports)]
+use error_chain::bail;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::error::LoadError;
+use ate::prelude::*;
+use ate::utils::chain_key_4hex;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::prelude::*;
+use crate::request::*;
+use crate::service::AuthService;
+
+impl AuthService {
+    pub async fn process_audit_list(
+        self: Arc<Self>,
+        request: AuditListRequest,
+    ) -> Result<AuditListResponse, AuditListFailed> {
+        let identity = request.session.identity().to_string();
+        debug!("audit list: {}", identity);
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(&identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        if self.session_owns_identity(&dio, &request.session).await? == false {
+            warn!("audit request denied ({}) - no access", identity);
+            return Err(AuditListFailed::NoAccess);
+        }
+
+        let log_key = PrimaryKey::from(format!("audit:{}", identity));
+        let log = match dio.load::<AuditLog>(&log_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Ok(AuditListResponse { events: Vec::new() });
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+
+        // Only the most recent events are returned
+        let mut events = Vec::new();
+        for event in log.events.iter().await? {
+            events.push(event.take());
+        }
+        events.sort_by(|a, b| a.when.cmp(&b.when));
+        if events.len() > request.limit {
+            events.drain(..events.len() - request.limit);
+        }
+        Ok(AuditListResponse { events })
+    }
+
+    /// Appends an event to the audit log of the identity, failing to record it
+    /// is logged but never fails the operation that is being audited
+    pub(crate) async fn audit(
+        &self,
+        identity: &str,
+        action: AuditAction,
+        source: Option<String>,
+        outcome: AuditOutcome,
+    ) {
+        let event = AuditEvent {
+            when: chrono::Utc::now(),
+            action,
+            source,
+            outcome,
+        };
+        debug!("audit: {} {} ({})", identity, event.action, event.outcome);
+
+        if let Err(err) = self.audit_internal(identity, event).await {
+            warn!("failed to record audit event ({}) - {:?}", identity, err);
+        }
+    }
+
+    async fn audit_internal(
+        &self,
+        identity: &str,
+        event: AuditEvent,
+    ) -> Result<(), AuditListFailed> {
+        // Get the master write key
+        let master_write_key = match self.master_session.user.write_keys().next() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(AuditListFailed::NoMasterKey);
+            }
+        };
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(AuditListFailed::NoMasterKey);
+            }
+        };
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        // Load the log or create it if this is the first event
+        let log_key = PrimaryKey::from(format!("audit:{}", identity));
+        let mut log = match dio.load::<AuditLog>(&log_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                // Identities that do not exist are not given a log (otherwise
+                // anyone could fill the chain with logs of made up accounts)
+                if dio.exists(&PrimaryKey::from(identity.to_string())).await == false {
+                    debug!("audit skipped ({}) - no such identity", identity);
+                    return Ok(());
+                }
+
+                let log = AuditLog {
+                    identity: identity.to_string(),
+                    events: DaoVec::default(),
+                };
+                let mut log = dio.store_with_key(log, log_key)?;
+                log.auth_mut().read = ReadOption::from_key(&master_key);
+                log.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+                log
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        log.as_mut().events.push(event)?;
+        dio.commit().await?;
+        Ok(())
+    }
+}
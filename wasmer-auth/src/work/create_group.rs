@@ -27,6 +27,25 @@ impl AuthService {
     pub async fn process_create_group(
         self: Arc<Self>,
         request: CreateGroupRequest,
+    ) -> Result<CreateGroupResponse, CreateGroupFailed> {
+        let identity = request.identity.clone();
+        let action = AuditAction::CreateGroup {
+            group: request.group.clone(),
+        };
+        let ret = self.clone().process_create_group_internal(request).await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_create_group_internal(
+        self: Arc<Self>,
+        request: CreateGroupRequest,
     ) -> Result<CreateGroupResponse, CreateGroupFailed> {
         info!("create group: {}", request.group);
 
@@ -200,6 +219,9 @@ impl AuthService {
             roles: Vec::new(),
             broker_read: broker_read.clone(),
             broker_write: broker_write.clone(),
+            bindings: Vec::new(),
+            chain_keys: Vec::new(),
+            databases: Vec::new(),
         };
         let mut group = dio.store_with_key(group, group_key.clone())?;
 
@@ -345,6 +367,7 @@ impl AuthService {
                     private_read: role_private_read.as_public_key().clone(),
                     write: role_write.as_public_key().clone(),
                     access,
+                    previous: None,
                 };
                 group_mut.roles.push(role);
             }
@@ -87,15 +87,7 @@ impl AuthService {
         super_session.token = Some(super_token);
 
         // Generate the recovery code
-        let recovery_code = AteHash::generate().to_hex_string().to_uppercase();
-        let recovery_code = format!(
-            "{}-{}-{}-{}-{}",
-            &recovery_code[0..4],
-            &recovery_code[4..8],
-            &recovery_code[8..12],
-            &recovery_code[12..16],
-            &recovery_code[16..20]
-        );
+        let recovery_code = generate_recovery_code();
         let recovery_prefix = format!("recover-login:{}:", request.email);
         let recovery_key =
             password_to_read_key(&recovery_prefix, &recovery_code, 15, KeySize::Bit192);
@@ -386,6 +378,12 @@ impl AuthService {
         // Save the data
         dio.commit().await?;
 
+        // Email the verification code to the user
+        if let Some(verification_code) = user.verification_code.clone() {
+            self.email_code(&request.email, EmailPurpose::Verify, &verification_code)
+                .await?;
+        }
+
         // Create the authorizations and return them
         let mut session = compute_user_auth(user.deref());
         session.token = Some(token);
@@ -404,3 +402,16 @@ impl AuthService {
         ))
     }
 }
+
+/// Generates the code that users store somewhere safe to recover their account
+pub(crate) fn generate_recovery_code() -> String {
+    let recovery_code = AteHash::generate().to_hex_string().to_uppercase();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &recovery_code[0..4],
+        &recovery_code[4..8],
+        &recovery_code[8..12],
+        &recovery_code[12..16],
+        &recovery_code[16..20]
+    )
+}
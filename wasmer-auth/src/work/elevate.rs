@@ -0,0 +1,496 @@
+#![allow(unused_imports)]
+use error_chain::bail;
+use std::ops::Deref;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::error::LoadError;
+use ate::prelude::*;
+use ate::utils::chain_key_4hex;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::prelude::*;
+use crate::request::*;
+use crate::service::AuthService;
+
+impl AuthService {
+    pub async fn process_elevate(
+        self: Arc<Self>,
+        request: ElevateRequest,
+    ) -> Result<ElevateResponse, ElevationFailed> {
+        let identity = request.session.identity().to_string();
+        let id = AteHash::generate();
+        let action = AuditAction::Elevate {
+            id: id.clone(),
+            minutes: request.minutes,
+            approval: request.approval.clone(),
+        };
+        let ret = self.clone().process_elevate_internal(id, request).await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_elevate_internal(
+        self: Arc<Self>,
+        id: AteHash,
+        request: ElevateRequest,
+    ) -> Result<ElevateResponse, ElevationFailed> {
+        let identity = request.session.identity().to_string();
+        info!(
+            "elevate attempt: {} ({} minutes)",
+            identity, request.minutes
+        );
+
+        if request.minutes == 0 || request.minutes > MAX_ELEVATION_MINUTES {
+            return Err(ElevationFailed::InvalidDuration(request.minutes));
+        }
+
+        // Chains are opened with the keys of the group rather than through
+        // the elevation so it can not be limited to some of them
+        if request.scope.chains.is_empty() == false {
+            warn!(
+                "elevate denied ({}) - elevations can not be limited to chains",
+                identity
+            );
+            return Err(ElevationFailed::UnsupportedScope);
+        }
+
+        // The elevation needs the same proof as a normal sudo
+        let sudo = self
+            .clone()
+            .process_sudo_internal(SudoRequest {
+                session: request.session.clone(),
+                authenticator_code: request.authenticator_code,
+            })
+            .await
+            .map_err(ElevationFailed::SudoFailed)?;
+
+        // While break-glass is enabled every elevation must be approved by an
+        // owner of the break-glass group
+        let approval = match self.break_glass.clone() {
+            Some(group) => Some(group),
+            None => request.approval,
+        };
+        let mut elevation = Elevation {
+            id,
+            identity: identity.clone(),
+            reason: request.reason,
+            requested: chrono::Utc::now(),
+            minutes: request.minutes,
+            scope: request.scope,
+            approval,
+            status: ElevationStatus::Pending,
+        };
+
+        // Break-glass elevations are held back until an owner of the group
+        // approves them, everything else is redeemed straight away
+        let token = match elevation.approval.is_some() {
+            true => None,
+            false => {
+                elevation.status = ElevationStatus::Redeemed;
+                let token = self
+                    .issue_elevation(&elevation, &request.session, sudo.authority)
+                    .await?;
+                Some(token)
+            }
+        };
+
+        let (dio, mut elevations) = self.load_elevations(identity.as_str()).await?;
+        {
+            let mut elevations = elevations.as_mut();
+            elevations.elevations.retain(|e| e.is_stale() == false);
+            elevations.elevations.push(elevation.clone());
+        }
+        dio.commit().await?;
+
+        Ok(ElevateResponse { elevation, token })
+    }
+
+    pub async fn process_elevation_approve(
+        self: Arc<Self>,
+        request: ElevationApproveRequest,
+    ) -> Result<ElevationApproveResponse, ElevationFailed> {
+        let approver = request.session.inner.identity().to_string();
+        let requester = request.identity.clone();
+        let action = AuditAction::ElevationApprove {
+            id: request.id.clone(),
+            requester: requester.clone(),
+            approver: approver.clone(),
+        };
+        let ret = self
+            .clone()
+            .process_elevation_approve_internal(request)
+            .await;
+
+        // The approval is recorded against both of the users
+        let outcome = AuditOutcome::from_result(&ret);
+        self.audit(approver.as_str(), action.clone(), None, outcome.clone())
+            .await;
+        if requester != approver {
+            self.audit(requester.as_str(), action, None, outcome).await;
+        }
+        ret
+    }
+
+    async fn process_elevation_approve_internal(
+        self: Arc<Self>,
+        request: ElevationApproveRequest,
+    ) -> Result<ElevationApproveResponse, ElevationFailed> {
+        let approver = request.session.inner.identity().to_string();
+        info!(
+            "elevation approve: {} of {} by {}",
+            request.id, request.identity, approver
+        );
+        if approver == request.identity {
+            return Err(ElevationFailed::SelfApproval);
+        }
+
+        // The approver must be an owner of the group
+        let group_chain_key = chain_key_4hex(&request.group, Some("redo"));
+        let chain = self
+            .registry
+            .open(&self.auth_url, &group_chain_key, true)
+            .await?;
+        let dio = chain.dio(&self.master_session).await;
+        let group_key = PrimaryKey::from(request.group.clone());
+        let group = match dio.load::<Group>(&group_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Err(ElevationFailed::GroupNotFound);
+            }
+            Err(LoadError(
+                LoadErrorKind::TransformationError(TransformErrorKind::MissingReadKey(_)),
+                _,
+            )) => {
+                return Err(ElevationFailed::NoMasterKey);
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        let owner = group
+            .roles
+            .iter()
+            .filter(|r| r.purpose == AteRolePurpose::Owner)
+            .map(|r| r.private_read.hash())
+            .next();
+        let is_owner = request
+            .session
+            .private_read_keys(AteSessionKeyCategory::AllKeys)
+            .any(|k| Some(k.hash()) == owner);
+        if is_owner == false {
+            return Err(ElevationFailed::NoAccess);
+        }
+
+        let (dio, mut elevations) = self.load_elevations(request.identity.as_str()).await?;
+        let elevation = {
+            let mut elevations = elevations.as_mut();
+            let elevation = match elevations
+                .elevations
+                .iter_mut()
+                .filter(|e| e.id == request.id)
+                .next()
+            {
+                Some(a) => a,
+                None => {
+                    return Err(ElevationFailed::NotFound(request.id));
+                }
+            };
+            if elevation.approval.as_ref() != Some(&request.group) {
+                return Err(ElevationFailed::NoAccess);
+            }
+            if elevation.status != ElevationStatus::Pending {
+                return Err(ElevationFailed::AlreadyRedeemed(request.id));
+            }
+            if elevation.is_stale() {
+                return Err(ElevationFailed::Expired(request.id));
+            }
+            elevation.status = ElevationStatus::Approved {
+                by: approver,
+                when: chrono::Utc::now(),
+            };
+            elevation.clone()
+        };
+        dio.commit().await?;
+
+        Ok(ElevationApproveResponse { elevation })
+    }
+
+    pub async fn process_elevation_redeem(
+        self: Arc<Self>,
+        request: ElevationRedeemRequest,
+    ) -> Result<ElevateResponse, ElevationFailed> {
+        let identity = request.session.identity().to_string();
+        let action = AuditAction::ElevationRedeem {
+            id: request.id.clone(),
+        };
+        let ret = self
+            .clone()
+            .process_elevation_redeem_internal(request)
+            .await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_elevation_redeem_internal(
+        self: Arc<Self>,
+        request: ElevationRedeemRequest,
+    ) -> Result<ElevateResponse, ElevationFailed> {
+        let identity = request.session.identity().to_string();
+        info!("elevation redeem: {} ({})", identity, request.id);
+
+        let sudo = self
+            .clone()
+            .process_sudo_internal(SudoRequest {
+                session: request.session.clone(),
+                authenticator_code: request.authenticator_code,
+            })
+            .await
+            .map_err(ElevationFailed::SudoFailed)?;
+
+        let (dio, mut elevations) = self.load_elevations(identity.as_str()).await?;
+        let elevation = {
+            let mut elevations = elevations.as_mut();
+            let elevation = match elevations
+                .elevations
+                .iter_mut()
+                .filter(|e| e.id == request.id)
+                .next()
+            {
+                Some(a) => a,
+                None => {
+                    return Err(ElevationFailed::NotFound(request.id));
+                }
+            };
+            match &elevation.status {
+                ElevationStatus::Pending => {
+                    return Err(ElevationFailed::NotApproved(request.id));
+                }
+                ElevationStatus::Redeemed => {
+                    return Err(ElevationFailed::AlreadyRedeemed(request.id));
+                }
+                ElevationStatus::Approved { .. } => {}
+            }
+            if elevation.is_stale() {
+                return Err(ElevationFailed::Expired(request.id));
+            }
+            elevation.status = ElevationStatus::Redeemed;
+            elevation.clone()
+        };
+        let token = self
+            .issue_elevation(&elevation, &request.session, sudo.authority)
+            .await?;
+        dio.commit().await?;
+
+        Ok(ElevateResponse {
+            elevation,
+            token: Some(token),
+        })
+    }
+
+    /// Records the access token that the elevation is handed out as so that it
+    /// expires (and can be revoked) like any other minted token, the sudo
+    /// session is sealed within the token and never leaves the server
+    async fn issue_elevation(
+        &self,
+        elevation: &Elevation,
+        session: &AteSessionUser,
+        grant: AteSessionSudo,
+    ) -> Result<ScopedToken, ElevationFailed> {
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(ElevationFailed::NoMasterKey);
+            }
+        };
+
+        let issued = chrono::Utc::now();
+        let token = AccessToken {
+            id: elevation.id.clone(),
+            name: format!("sudo - {}", elevation.reason),
+            issued,
+            expires: Some(issued + chrono::Duration::minutes(elevation.minutes as i64)),
+            scope: elevation.scope.clone(),
+            revoked: false,
+            elevated: true,
+        };
+
+        let session = AteSessionInner::User(session.clone());
+        let (dio, mut tokens) =
+            self.load_access_tokens(&session)
+                .await
+                .map_err(|err| match err {
+                    TokenFailed::NoAccess => ElevationFailed::NoAccess,
+                    TokenFailed::NoMasterKey => ElevationFailed::NoMasterKey,
+                    TokenFailed::InternalError(code) => ElevationFailed::InternalError(code),
+                    _ => ElevationFailed::InternalError(0),
+                })?;
+        tokens.as_mut().tokens.push(token.clone());
+        dio.commit().await?;
+
+        let seal = TokenSeal {
+            id: token.id.clone(),
+            identity: elevation.identity.clone(),
+            session: AteSessionType::Sudo(grant),
+        };
+        Ok(ScopedToken {
+            identity: elevation.identity.clone(),
+            token,
+            sealed: EncryptedSecureData::new(&master_key, seal)?,
+        })
+    }
+
+    /// Returns the session that a privileged request acts with, requests that
+    /// carry an elevation act with the sudo session sealed in it which is only
+    /// opened while the elevation has not expired, has not been revoked and
+    /// covers the operation (this is checked again on every operation)
+    pub(crate) async fn elevated_session(
+        &self,
+        group: &str,
+        operation: &str,
+        session: AteSessionGroup,
+        elevation: Option<ScopedToken>,
+    ) -> Result<AteSessionGroup, ElevationFailed> {
+        let elevation = match elevation {
+            Some(a) => a,
+            None => {
+                return Ok(session);
+            }
+        };
+        let identity = elevation.identity.clone();
+
+        let (token, sealed) = match self
+            .open_scoped_token(identity.as_str(), &elevation.token.id, &elevation.sealed)
+            .await
+        {
+            Ok(a) => a,
+            Err(TokenFailed::Expired(id)) => {
+                warn!("elevated {} denied ({}) - elevation expired", operation, identity);
+                return Err(ElevationFailed::Expired(id));
+            }
+            Err(TokenFailed::NoMasterKey) => {
+                return Err(ElevationFailed::NoMasterKey);
+            }
+            Err(TokenFailed::InternalError(code)) => {
+                return Err(ElevationFailed::InternalError(code));
+            }
+            Err(err) => {
+                warn!("elevated {} denied ({}) - {:?}", operation, identity, err);
+                return Err(ElevationFailed::NoAccess);
+            }
+        };
+        let sudo = match sealed {
+            AteSessionType::Sudo(a) if token.elevated => a,
+            _ => {
+                warn!("elevated {} denied ({}) - not an elevation", operation, identity);
+                return Err(ElevationFailed::NoAccess);
+            }
+        };
+        if token.scope.allows_operation(operation) == false
+            || token.scope.group.iter().any(|g| g != group)
+            || token.scope.chains.is_empty() == false
+        {
+            warn!("elevated {} denied ({}) - out of scope", operation, identity);
+            return Err(ElevationFailed::OutOfScope(operation.to_string()));
+        }
+
+        // Gather the roles that the sudo session holds in the group
+        let group_chain_key = chain_key_4hex(group, Some("redo"));
+        let chain = self
+            .registry
+            .open(&self.auth_url, &group_chain_key, true)
+            .await?;
+        let dio = chain.dio(&self.master_session).await;
+        let group_key = PrimaryKey::from(group.to_string());
+        let group = match dio.load::<Group>(&group_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Err(ElevationFailed::GroupNotFound);
+            }
+            Err(LoadError(
+                LoadErrorKind::TransformationError(TransformErrorKind::MissingReadKey(_)),
+                _,
+            )) => {
+                return Err(ElevationFailed::NoMasterKey);
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        let mut session = complete_group_auth(group.deref(), AteSessionInner::Sudo(sudo))?;
+        if token.scope.roles.len() > 0 {
+            session
+                .group
+                .roles
+                .retain(|r| token.scope.roles.iter().any(|p| *p == r.purpose));
+        }
+        Ok(session)
+    }
+
+    /// Loads (or creates) the recent elevations of a user
+    async fn load_elevations(
+        &self,
+        identity: &str,
+    ) -> Result<(Arc<DioMut>, DaoMut<Elevations>), ElevationFailed> {
+        // Get the master write key
+        let master_write_key = match self.master_session.user.write_keys().next() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(ElevationFailed::NoMasterKey);
+            }
+        };
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(ElevationFailed::NoMasterKey);
+            }
+        };
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        let elevations_key = PrimaryKey::from(format!("elevations:{}", identity));
+        let elevations = match dio.load::<Elevations>(&elevations_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                let elevations = Elevations {
+                    identity: identity.to_string(),
+                    elevations: Vec::new(),
+                };
+                let mut elevations = dio.store_with_key(elevations, elevations_key)?;
+                elevations.auth_mut().read = ReadOption::from_key(&master_key);
+                elevations.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+                elevations
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        Ok((dio, elevations))
+    }
+}
+
+/// Identity that a privileged request is recorded against, which for elevated
+/// requests is the user who was elevated
+pub(crate) fn request_identity(session: &AteSessionGroup, elevation: Option<&ScopedToken>) -> String {
+    match elevation {
+        Some(a) => a.identity.clone(),
+        None => session.inner.identity().to_string(),
+    }
+}
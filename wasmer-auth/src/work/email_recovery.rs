@@ -0,0 +1,565 @@
+#![allow(unused_imports)]
+use error_chain::bail;
+use std::ops::Deref;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::error::LoadError;
+use ate::prelude::*;
+use ate::utils::chain_key_4hex;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::mail::*;
+use crate::model::*;
+use crate::prelude::*;
+use crate::request::*;
+use crate::service::AuthService;
+
+use super::create_user::*;
+
+/// Number of wrong codes that may be entered before an emailed code is thrown away
+const EMAIL_CODE_FREE_ATTEMPTS: u32 = 5;
+/// Minimum number of seconds between two emails with the same purpose
+const EMAIL_RESEND_SECONDS: i64 = 60;
+
+impl AuthService {
+    pub async fn process_email_recovery(
+        self: Arc<Self>,
+        request: EmailRecoveryRequest,
+    ) -> Result<EmailRecoveryResponse, EmailRecoveryFailed> {
+        info!("email recovery: {}", request.email);
+        let mail = match self.mail.as_ref() {
+            Some(a) => a,
+            None => {
+                return Err(EmailRecoveryFailed::MailDisabled);
+            }
+        };
+        let response = EmailRecoveryResponse {
+            expires_in: mail.code_lifetime,
+        };
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(&request.email, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        // Only accounts that were enrolled can be recovered but the caller gets
+        // the same response so that they can not find out who has an account
+        if dio.exists(&email_recovery_key(&request.email)).await == false {
+            warn!("email recovery ignored ({}) - not enrolled", request.email);
+            return Ok(response);
+        }
+
+        let code = generate_email_code();
+        self.email_code(&request.email, EmailPurpose::Recover, &code)
+            .await?;
+        Ok(response)
+    }
+
+    pub async fn process_email_recovery_enrol(
+        self: Arc<Self>,
+        request: EmailRecoveryEnrolRequest,
+    ) -> Result<EmailRecoveryEnrolResponse, EmailRecoveryFailed> {
+        let identity = request.session.identity().to_string();
+        let action = AuditAction::EmailRecoveryEnrol {
+            enabled: request.enable,
+        };
+        let ret = self.process_email_recovery_enrol_internal(request).await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_email_recovery_enrol_internal(
+        &self,
+        request: EmailRecoveryEnrolRequest,
+    ) -> Result<EmailRecoveryEnrolResponse, EmailRecoveryFailed> {
+        let identity = request.session.identity().to_string();
+        info!(
+            "email recovery enrol: {} (enable={})",
+            identity, request.enable
+        );
+        if self.mail.is_none() {
+            return Err(EmailRecoveryFailed::MailDisabled);
+        }
+        let (master_key, master_write_key) = match (
+            self.master_key(),
+            self.master_session.user.write_keys().next(),
+        ) {
+            (Some(a), Some(b)) => (a.clone(), b.clone()),
+            _ => {
+                return Err(EmailRecoveryFailed::NoMasterKey);
+            }
+        };
+
+        // The super key is recovered from the login token of the session
+        let token = match &request.session {
+            AteSessionInner::User(a) => a.token.clone(),
+            AteSessionInner::Sudo(a) => a.inner.token.clone(),
+            AteSessionInner::Nothing => None,
+        };
+        let token = match token {
+            Some(a) => a,
+            None => {
+                warn!(
+                    "email recovery enrol denied ({}) - no token supplied",
+                    identity
+                );
+                return Err(EmailRecoveryFailed::MissingToken);
+            }
+        };
+        let super_key = token.unwrap(&master_key)?;
+
+        // Make sure the token really opens the account of this user
+        let mut super_session = self.master_session.clone();
+        super_session.user.add_read_key(&super_key);
+        let chain_key = chain_key_4hex(&identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&super_session).await;
+        if let Err(err) = dio.load::<User>(&PrimaryKey::from(identity.clone())).await {
+            warn!(
+                "email recovery enrol denied ({}) - bad token - {}",
+                identity, err
+            );
+            return Err(EmailRecoveryFailed::NoAccess);
+        }
+
+        let recovery_key = email_recovery_key(&identity);
+        match request.enable {
+            true => {
+                match dio.load::<EmailRecovery>(&recovery_key).await {
+                    Ok(mut recovery) => {
+                        recovery.as_mut().super_key = super_key.clone();
+                    }
+                    Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                        let recovery = EmailRecovery {
+                            email: identity.clone(),
+                            super_key: super_key.clone(),
+                        };
+                        let mut recovery = dio.store_with_key(recovery, recovery_key)?;
+                        recovery.auth_mut().read = ReadOption::from_key(&master_key);
+                        recovery.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+                    }
+                    Err(err) => {
+                        bail!(err);
+                    }
+                };
+            }
+            false => {
+                if dio.exists(&recovery_key).await {
+                    dio.delete(&recovery_key).await?;
+                }
+            }
+        }
+        dio.commit().await?;
+
+        Ok(EmailRecoveryEnrolResponse {
+            enabled: request.enable,
+        })
+    }
+
+    pub async fn process_email_reset(
+        self: Arc<Self>,
+        request: EmailResetRequest,
+    ) -> Result<EmailResetResponse, EmailResetFailed> {
+        let email = request.email.clone();
+        let ret = self.process_email_reset_internal(request).await;
+        self.audit(
+            email.as_str(),
+            AuditAction::EmailReset,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_email_reset_internal(
+        &self,
+        request: EmailResetRequest,
+    ) -> Result<EmailResetResponse, EmailResetFailed> {
+        info!("email reset: {}", request.email);
+        if self.mail.is_none() {
+            return Err(EmailResetFailed::MailDisabled);
+        }
+
+        // Get the master write key
+        let master_write_key = match self.master_session.user.write_keys().next() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(EmailResetFailed::NoMasterKey);
+            }
+        };
+
+        // The emailed code proves that the caller owns the email address (it
+        // can only be used once even when the rest of the recovery fails)
+        if self
+            .check_email_code(&request.email, EmailPurpose::Recover, &request.code)
+            .await?
+            == false
+        {
+            warn!("email reset denied ({}) - invalid code", request.email);
+            return Err(EmailResetFailed::InvalidCode);
+        }
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(&request.email, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        // Load the key that was enrolled for recovery by email
+        let mut recovery = match dio
+            .load::<EmailRecovery>(&email_recovery_key(&request.email))
+            .await
+        {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                warn!("email reset denied ({}) - not enrolled", request.email);
+                return Err(EmailResetFailed::RecoveryImpossible);
+            }
+            Err(err) => {
+                warn!("email reset denied ({}) - error - ", err);
+                bail!(err);
+            }
+        };
+
+        // We can now add the original encryption keys that grant us access to this account
+        let super_key = recovery.super_key.clone();
+        let (super_super_key, _) = self
+            .compute_master_key(&super_key)
+            .ok_or_else(|| EmailResetFailed::NoMasterKey)?;
+        {
+            let mut session_mut = dio.session_mut();
+            session_mut.user_mut().add_user_read_key(&super_key);
+            session_mut.user_mut().add_user_read_key(&super_super_key);
+        }
+
+        // Attempt to load the user and its sudo object
+        let user_key = PrimaryKey::from(request.email.clone());
+        let mut user = match dio.load::<User>(&user_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                warn!("email reset denied ({}) - not found", request.email);
+                return Err(EmailResetFailed::RecoveryImpossible);
+            }
+            Err(LoadError(
+                LoadErrorKind::TransformationError(TransformErrorKind::MissingReadKey(_)),
+                _,
+            )) => {
+                warn!(
+                    "email reset denied ({}) - enrolled key is out of date",
+                    request.email
+                );
+                return Err(EmailResetFailed::RecoveryImpossible);
+            }
+            Err(err) => {
+                warn!("email reset denied ({}) - error - ", err);
+                bail!(err);
+            }
+        };
+        let mut sudo = match user.as_mut().sudo.load_mut().await {
+            Ok(Some(a)) => a,
+            Ok(None) => {
+                warn!("email reset denied ({}) - sudo not found", request.email);
+                return Err(EmailResetFailed::RecoveryImpossible);
+            }
+            Err(LoadError(
+                LoadErrorKind::TransformationError(TransformErrorKind::MissingReadKey(_)),
+                _,
+            )) => {
+                warn!(
+                    "email reset denied ({}) - enrolled key is out of date",
+                    request.email
+                );
+                return Err(EmailResetFailed::RecoveryImpossible);
+            }
+            Err(err) => {
+                warn!("email reset denied ({}) - error - ", err);
+                bail!(err);
+            }
+        };
+
+        // Check the code matches the authenticator code
+        self.time_keeper.wait_for_high_accuracy().await;
+        let time = self.time_keeper.current_timestamp_as_duration()?;
+        let time = time.as_secs() / 30;
+        let google_auth = google_authenticator::GoogleAuthenticator::new();
+        if google_auth.verify_code(sudo.secret.as_str(), request.sudo_code.as_str(), 3, time) {
+            debug!("code authenticated");
+        } else {
+            warn!("email reset denied ({}) - wrong code", request.email);
+            return Err(EmailResetFailed::InvalidAuthenticatorCode);
+        }
+
+        // Compute the new super_key and super_super_key and re-wrap the user with them
+        let (new_super_key, token) = self
+            .compute_master_key(&request.new_secret)
+            .ok_or_else(|| EmailResetFailed::NoMasterKey)?;
+        let (new_super_super_key, _) = self
+            .compute_master_key(&new_super_key)
+            .ok_or_else(|| EmailResetFailed::NoMasterKey)?;
+        user.auth_mut().read = ReadOption::from_key(&new_super_key);
+        sudo.auth_mut().read = ReadOption::from_key(&new_super_super_key);
+        recovery.as_mut().super_key = new_super_key.clone();
+
+        // The old recovery code can not be read anymore so it is replaced with a new one
+        let recovery_code = generate_recovery_code();
+        let recovery_prefix = format!("recover-login:{}:", request.email);
+        let recovery_key =
+            password_to_read_key(&recovery_prefix, &recovery_code, 15, KeySize::Bit192);
+        let (super_recovery_key, _) = self
+            .compute_master_key(&recovery_key)
+            .ok_or_else(|| EmailResetFailed::NoMasterKey)?;
+        let user_recovery = UserRecovery {
+            email: request.email.clone(),
+            google_auth: sudo.google_auth.clone(),
+            sudo_secret: sudo.secret.clone(),
+            qr_code: sudo.qr_code.clone(),
+            login_secret: request.new_secret.clone(),
+        };
+        let user_recovery_key = PrimaryKey::from(format!("recovery:{}", request.email));
+        let mut user_recovery = user
+            .as_mut()
+            .recovery
+            .store_with_key(user_recovery, user_recovery_key)
+            .await?;
+        user_recovery.auth_mut().read = ReadOption::from_key(&super_recovery_key);
+        user_recovery.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+
+        // Commit the transaction
+        dio.commit().await?;
+
+        // Create the authorizations and return them
+        let mut session = compute_user_auth(user.deref());
+        session.token = Some(token);
+
+        // Return success to the caller
+        Ok(EmailResetResponse {
+            key: user.key().clone(),
+            recovery_code,
+            authority: session,
+            message_of_the_day: None,
+        })
+    }
+
+    /// Users who opted in to recovery by email (see `process_email_recovery_enrol`)
+    /// have a copy of their super key kept for them, when the key changes the
+    /// copy is updated (users who did not opt in are left alone)
+    pub(crate) async fn refresh_email_recovery(
+        &self,
+        dio: &Arc<DioMut>,
+        email: &str,
+        super_key: &EncryptKey,
+    ) -> Result<(), AteError> {
+        let recovery_key = email_recovery_key(email);
+        match dio.load::<EmailRecovery>(&recovery_key).await {
+            Ok(mut recovery) => {
+                if recovery.super_key.hash() != super_key.hash() {
+                    recovery.as_mut().super_key = super_key.clone();
+                }
+            }
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {}
+            Err(err) => {
+                bail!(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Emails a time limited code to the user (failures to deliver the email
+    /// are logged but do not fail the caller)
+    pub(crate) async fn email_code(
+        &self,
+        email: &str,
+        purpose: EmailPurpose,
+        code: &str,
+    ) -> Result<(), AteError> {
+        let mail = match self.mail.as_ref() {
+            Some(a) => a,
+            None => {
+                return Ok(());
+            }
+        };
+        if self.issue_email_code(email, purpose, code).await? == false {
+            warn!("email not sent ({}) - one was sent recently", email);
+            return Ok(());
+        }
+
+        let minutes = (mail.code_lifetime.as_secs() / 60).max(1);
+        let link = mail.link(purpose.to_string().as_str(), email, code);
+        let (subject, body) = match purpose {
+            EmailPurpose::Verify => (
+                "Verify your email address",
+                format!(
+                    "Hello,\n\n\
+                    Use the code below to verify your email address ({}) the next time that\n\
+                    you login:\n\n    \
+                    {}\n\n\
+                    or open the following link:\n\n    \
+                    {}\n\n\
+                    The code expires in {} minutes. If you did not create an account you can\n\
+                    ignore this email.\n",
+                    email, code, link, minutes
+                ),
+            ),
+            EmailPurpose::Recover => (
+                "Recover your account",
+                format!(
+                    "Hello,\n\n\
+                    Someone (hopefully you) asked to recover the account of {}. Use the code below along with\n\
+                    a code from your authenticator to choose a new password:\n\n    \
+                    {}\n\n\
+                    or open the following link:\n\n    \
+                    {}\n\n\
+                    The code expires in {} minutes. If you did not ask to recover your account you can ignore\n\
+                    this email, your password has not been changed.\n",
+                    email, code, link, minutes
+                ),
+            ),
+        };
+
+        let ret = mail
+            .sender
+            .send(Mail {
+                to: email.to_string(),
+                subject: subject.to_string(),
+                body,
+            })
+            .await;
+        if let Err(err) = ret {
+            warn!("failed to send the {} email ({}) - {}", purpose, email, err);
+        }
+        Ok(())
+    }
+
+    /// Stores the hash of a code that is about to be emailed, returns false
+    /// when a code with the same purpose was issued moments ago
+    async fn issue_email_code(
+        &self,
+        email: &str,
+        purpose: EmailPurpose,
+        code: &str,
+    ) -> Result<bool, AteError> {
+        let mail = match self.mail.as_ref() {
+            Some(a) => a,
+            None => {
+                return Ok(false);
+            }
+        };
+        let (master_key, master_write_key) = match (
+            self.master_key(),
+            self.master_session.user.write_keys().next(),
+        ) {
+            (Some(a), Some(b)) => (a.clone(), b.clone()),
+            _ => {
+                return Ok(false);
+            }
+        };
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(email, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        let now = chrono::Utc::now();
+        let expires = now
+            + chrono::Duration::from_std(mail.code_lifetime)
+                .unwrap_or_else(|_| chrono::Duration::minutes(15));
+        let code_hash = EmailVerification::hash_code(email, code);
+
+        let key = purpose.key(email);
+        match dio.load::<EmailVerification>(&key).await {
+            Ok(mut verification) => {
+                if verification.is_expired() == false
+                    && verification.issued + chrono::Duration::seconds(EMAIL_RESEND_SECONDS) > now
+                {
+                    return Ok(false);
+                }
+                let mut verification_mut = verification.as_mut();
+                verification_mut.code_hash = code_hash;
+                verification_mut.issued = now;
+                verification_mut.expires = expires;
+                verification_mut.failed_attempts = 0;
+            }
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                let verification = EmailVerification {
+                    email: email.to_string(),
+                    purpose,
+                    code_hash,
+                    issued: now,
+                    expires,
+                    failed_attempts: 0,
+                };
+                let mut verification = dio.store_with_key(verification, key)?;
+                verification.auth_mut().read = ReadOption::from_key(&master_key);
+                verification.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        }
+        dio.commit().await?;
+        Ok(true)
+    }
+
+    /// Checks a code that was emailed to the user, codes can only be used once
+    /// and are thrown away when they expire or are guessed wrong too often
+    pub(crate) async fn check_email_code(
+        &self,
+        email: &str,
+        purpose: EmailPurpose,
+        code: &str,
+    ) -> Result<bool, AteError> {
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(email, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        let key = purpose.key(email);
+        let mut verification = match dio.load::<EmailVerification>(&key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Ok(false);
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+
+        let ret = if verification.is_expired() {
+            debug!("{} code expired ({})", purpose, email);
+            dio.delete(&key).await?;
+            false
+        } else if verification.code_hash == EmailVerification::hash_code(email, code) {
+            dio.delete(&key).await?;
+            true
+        } else {
+            let failed_attempts = verification.failed_attempts + 1;
+            if failed_attempts >= EMAIL_CODE_FREE_ATTEMPTS {
+                debug!("{} code thrown away ({})", purpose, email);
+                dio.delete(&key).await?;
+            } else {
+                verification.as_mut().failed_attempts = failed_attempts;
+            }
+            false
+        };
+        dio.commit().await?;
+        Ok(ret)
+    }
+}
+
+fn email_recovery_key(email: &str) -> PrimaryKey {
+    PrimaryKey::from(format!("email-recovery:{}", email))
+}
+
+/// Generates a short code that is easy to type in from an email
+pub(crate) fn generate_email_code() -> String {
+    let v = AteHash::generate().to_hex_string().to_uppercase();
+    format!("{}-{}-{}", &v[0..4], &v[4..8], &v[8..12])
+}
@@ -63,8 +63,64 @@ impl AuthService {
             }
         };
 
-        // Now go into a loading loop on the session
-        let session = complete_group_auth(group.deref(), request.session)?;
+        // Scoped tokens are only exchanged for the roles they hold while they
+        // are still valid (the session of the request is ignored)
+        let session = match request.token {
+            Some(token) => {
+                let scope = token.token.scope.clone();
+                if let Some(scope_group) = scope.group.as_ref() {
+                    if *scope_group != request.group {
+                        warn!("gather denied ({}) - token for another group", request.group);
+                        return Err(GatherFailed::NoAccess);
+                    }
+                }
+                let sealed = match self
+                    .open_scoped_token(token.identity.as_str(), &token.token.id, &token.sealed)
+                    .await
+                {
+                    Ok((a, _)) if a.elevated => {
+                        warn!("gather denied ({}) - elevations are not exchanged", request.group);
+                        return Err(GatherFailed::NoAccess);
+                    }
+                    Ok((_, a)) => a,
+                    Err(TokenFailed::NoMasterKey) => {
+                        return Err(GatherFailed::NoMasterKey);
+                    }
+                    Err(err) => {
+                        warn!("gather denied ({}) - token rejected - {:?}", request.group, err);
+                        return Err(GatherFailed::NoAccess);
+                    }
+                };
+                match sealed {
+                    AteSessionType::Group(a) if a.group.name == request.group => a,
+                    AteSessionType::User(a) => {
+                        let session = complete_group_auth(group.deref(), AteSessionInner::User(a))?;
+                        scope_token_session(session, &scope)
+                    }
+                    AteSessionType::Sudo(a) if self.break_glass.is_some() => {
+                        let session =
+                            complete_group_auth(group.deref(), AteSessionInner::User(a.inner))?;
+                        scope_token_session(session, &scope)
+                    }
+                    AteSessionType::Sudo(a) => {
+                        let session = complete_group_auth(group.deref(), AteSessionInner::Sudo(a))?;
+                        scope_token_session(session, &scope)
+                    }
+                    _ => {
+                        return Err(GatherFailed::NoAccess);
+                    }
+                }
+            }
+
+            // Sudo rights are only used while break-glass is disabled (when it
+            // is enabled they come from elevations that an owner approved)
+            None => match request.session {
+                AteSessionInner::Sudo(a) if self.break_glass.is_some() => {
+                    complete_group_auth(group.deref(), AteSessionInner::User(a.inner))?
+                }
+                session => complete_group_auth(group.deref(), session)?,
+            },
+        };
 
         // Return the session that can be used to access this user
         Ok(GatherResponse {
@@ -75,3 +131,11 @@ impl AuthService {
         })
     }
 }
+
+/// Tokens that are limited to a group only hand out the roles they hold in it
+fn scope_token_session(session: AteSessionGroup, scope: &TokenScope) -> AteSessionGroup {
+    match scope.group.is_some() {
+        true => scope_group_session(session, scope),
+        false => session,
+    }
+}
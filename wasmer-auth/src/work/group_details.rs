@@ -77,6 +77,33 @@ impl AuthService {
             None => false,
         };
 
+        // Members of the group may record the databases that they are using
+        // so that they are moved onto new keys when the keys are rotated
+        if let (Some(database), Some(session)) = (&request.database, &request.session) {
+            let hashes = session
+                .private_read_keys(AteSessionKeyCategory::AllKeys)
+                .map(|k| k.hash())
+                .collect::<Vec<_>>();
+            let is_member = group
+                .roles
+                .iter()
+                .any(|r| hashes.iter().any(|h| r.access.exists(h)));
+            let is_valid = database.len() > 0
+                && database
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if is_member == false {
+                return Err(GroupDetailsFailed::NoAccess);
+            }
+            if is_valid && group.databases.contains(database) == false {
+                debug!("group ({}) database ({}) registered", request.group, database);
+                let dio = chain.dio_full(&self.master_session).await;
+                let mut group = dio.load::<Group>(&group_key).await?;
+                group.as_mut().databases.push(database.clone());
+                dio.commit().await?;
+            }
+        }
+
         // Build the list of roles in this group
         let mut roles = Vec::new();
         for role in group.roles.iter() {
@@ -104,6 +131,7 @@ impl AuthService {
             name: group.name.clone(),
             gid: group.gid,
             roles,
+            bindings: group.bindings.clone(),
         })
     }
 }
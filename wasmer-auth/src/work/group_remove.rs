@@ -22,15 +22,49 @@ use crate::prelude::*;
 use crate::request::*;
 use crate::service::AuthService;
 
+use super::elevate::*;
+
 impl AuthService {
     pub async fn process_group_remove(
         self: Arc<Self>,
         request: GroupRemoveRequest,
+    ) -> Result<GroupRemoveResponse, GroupRemoveFailed> {
+        let identity = request_identity(&request.session, request.elevation.as_ref());
+        let action = AuditAction::RemoveGroup {
+            group: request.group.clone(),
+        };
+        let ret = self.clone().process_group_remove_internal(request).await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_group_remove_internal(
+        self: Arc<Self>,
+        request: GroupRemoveRequest,
     ) -> Result<GroupRemoveResponse, GroupRemoveFailed> {
         info!("group ({}) remove", request.group);
 
-        // Copy the request session
-        let request_session = request.session;
+        // Copy the request session (or the one held by its elevation)
+        let request_session = self
+            .elevated_session(
+                &request.group,
+                "group.remove-group",
+                request.session,
+                request.elevation,
+            )
+            .await
+            .map_err(|err| match err {
+                ElevationFailed::GroupNotFound => GroupRemoveFailed::GroupNotFound,
+                ElevationFailed::NoMasterKey => GroupRemoveFailed::NoMasterKey,
+                ElevationFailed::InternalError(code) => GroupRemoveFailed::InternalError(code),
+                _ => GroupRemoveFailed::NoAccess,
+            })?;
 
         // Compute which chain the group should exist within
         let group_chain_key = chain_key_4hex(&request.group, Some("redo"));
@@ -0,0 +1,435 @@
+#![allow(unused_imports)]
+use error_chain::bail;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::error::LoadError;
+use ate::prelude::*;
+use ate::session::AteRolePurpose;
+use ate::utils::chain_key_4hex;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::prelude::*;
+use crate::request::*;
+use crate::service::AuthService;
+
+use super::elevate::*;
+
+impl AuthService {
+    pub async fn process_group_role_create(
+        self: Arc<Self>,
+        request: GroupRoleCreateRequest,
+    ) -> Result<GroupRoleCreateResponse, GroupRoleFailed> {
+        let identity = request_identity(&request.session, request.elevation.as_ref());
+        let action = AuditAction::GroupRoleCreate {
+            group: request.group.clone(),
+            purpose: request.purpose.clone(),
+        };
+        let ret = self
+            .clone()
+            .process_group_role_create_internal(request)
+            .await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_group_role_create_internal(
+        self: Arc<Self>,
+        request: GroupRoleCreateRequest,
+    ) -> Result<GroupRoleCreateResponse, GroupRoleFailed> {
+        info!(
+            "group ({}) role create ({})",
+            request.group, request.purpose
+        );
+        check_custom_role(&request.purpose)?;
+
+        let session = self
+            .elevated_role_session(&request.group, request.session, request.elevation)
+            .await?;
+        let (dio, mut group, delegate_write) =
+            self.load_group_for_roles(&request.group, &session).await?;
+        if group.roles.iter().any(|r| r.purpose == request.purpose) {
+            return Err(GroupRoleFailed::RoleExists);
+        }
+
+        // Generate the role keys using the same key size as the caller
+        let key_size = session
+            .read_keys(AteSessionKeyCategory::AllKeys)
+            .map(|k| k.size())
+            .next()
+            .unwrap_or_else(|| KeySize::Bit192);
+        let role_read = EncryptKey::generate(key_size);
+        let role_private_read = PrivateEncryptKey::generate(key_size);
+        let role_write = PrivateSignKey::generate(key_size);
+
+        // The new role starts with no members other than the delegates who
+        // will then grant it to users with the normal group user add
+        let referrer_identity = session.inner.identity().to_string();
+        group.as_mut().roles.push(Role {
+            purpose: request.purpose.clone(),
+            access: MultiEncryptedSecureData::new(
+                &delegate_write.as_public_key(),
+                referrer_identity,
+                Authorization {
+                    read: role_read.clone(),
+                    private_read: role_private_read.clone(),
+                    write: role_write.clone(),
+                },
+            )?,
+            read: role_read.hash(),
+            private_read: role_private_read.as_public_key().clone(),
+            write: role_write.as_public_key().clone(),
+            previous: None,
+        });
+
+        dio.commit().await?;
+
+        Ok(GroupRoleCreateResponse {
+            key: group.key().clone(),
+        })
+    }
+
+    pub async fn process_group_role_remove(
+        self: Arc<Self>,
+        request: GroupRoleRemoveRequest,
+    ) -> Result<GroupRoleRemoveResponse, GroupRoleFailed> {
+        let identity = request_identity(&request.session, request.elevation.as_ref());
+        let action = AuditAction::GroupRoleRemove {
+            group: request.group.clone(),
+            purpose: request.purpose.clone(),
+        };
+        let ret = self
+            .clone()
+            .process_group_role_remove_internal(request)
+            .await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_group_role_remove_internal(
+        self: Arc<Self>,
+        request: GroupRoleRemoveRequest,
+    ) -> Result<GroupRoleRemoveResponse, GroupRoleFailed> {
+        info!(
+            "group ({}) role remove ({})",
+            request.group, request.purpose
+        );
+        check_custom_role(&request.purpose)?;
+
+        let session = self
+            .elevated_role_session(&request.group, request.session, request.elevation)
+            .await?;
+        let (dio, mut group, delegate_write) =
+            self.load_group_for_roles(&request.group, &session).await?;
+        if group.roles.iter().any(|r| r.purpose == request.purpose) == false {
+            return Err(GroupRoleFailed::RoleNotFound);
+        }
+
+        // The members of the role know the read keys of the databases that it
+        // was bound to so those keys are rotated once the role is gone
+        let rotate = group
+            .bindings
+            .iter()
+            .filter(|b| b.purpose == request.purpose && b.read)
+            .map(|b| b.chain.clone())
+            .collect::<Vec<_>>();
+        {
+            let mut group = group.as_mut();
+            group.roles.retain(|r| r.purpose != request.purpose);
+            group.bindings.retain(|b| b.purpose != request.purpose);
+            for chain in rotate {
+                rotate_chain_keys(&mut group, chain.as_str(), &delegate_write)?;
+            }
+        }
+
+        dio.commit().await?;
+
+        Ok(GroupRoleRemoveResponse {
+            key: group.key().clone(),
+        })
+    }
+
+    pub async fn process_group_role_bind(
+        self: Arc<Self>,
+        request: GroupRoleBindRequest,
+    ) -> Result<GroupRoleBindResponse, GroupRoleFailed> {
+        let identity = request_identity(&request.session, request.elevation.as_ref());
+        let action = AuditAction::GroupRoleBind {
+            group: request.group.clone(),
+            purpose: request.purpose.clone(),
+            chain: request.chain.clone(),
+            read: request.read,
+            write: request.write,
+        };
+        let ret = self.clone().process_group_role_bind_internal(request).await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_group_role_bind_internal(
+        self: Arc<Self>,
+        request: GroupRoleBindRequest,
+    ) -> Result<GroupRoleBindResponse, GroupRoleFailed> {
+        info!(
+            "group ({}) role bind ({} to {} read={} write={})",
+            request.group, request.purpose, request.chain, request.read, request.write
+        );
+        if is_valid_chain_name(request.chain.as_str()) == false {
+            return Err(GroupRoleFailed::InvalidChain);
+        }
+
+        let session = self
+            .elevated_role_session(&request.group, request.session, request.elevation)
+            .await?;
+        let (dio, mut group, delegate_write) =
+            self.load_group_for_roles(&request.group, &session).await?;
+        if group.roles.iter().any(|r| r.purpose == request.purpose) == false {
+            return Err(GroupRoleFailed::RoleNotFound);
+        }
+
+        {
+            let mut group = group.as_mut();
+            let was_read = group
+                .bindings
+                .iter()
+                .any(|b| b.purpose == request.purpose && b.chain == request.chain && b.read);
+
+            group
+                .bindings
+                .retain(|b| b.purpose != request.purpose || b.chain != request.chain);
+            if request.read || request.write {
+                group.bindings.push(RoleBinding {
+                    purpose: request.purpose.clone(),
+                    chain: request.chain.clone(),
+                    read: request.read,
+                    write: request.write,
+                });
+            }
+
+            // Readers that are added are given the existing keys while readers
+            // that are removed force a new key for the records that follow
+            match (was_read, request.read) {
+                (false, true) => {
+                    let keys =
+                        match open_chain_keys(&group, request.chain.as_str(), &delegate_write)? {
+                            Some(a) => a,
+                            None => vec![EncryptKey::generate(delegate_write.size())],
+                        };
+                    seal_chain_keys(&mut group, request.chain.as_str(), keys)?;
+                }
+                (true, false) => {
+                    rotate_chain_keys(&mut group, request.chain.as_str(), &delegate_write)?;
+                }
+                _ => {}
+            }
+        }
+
+        dio.commit().await?;
+
+        Ok(GroupRoleBindResponse {
+            key: group.key().clone(),
+            bindings: group.bindings.clone(),
+        })
+    }
+
+    /// Custom roles are changed by delegates, which may also be done with an
+    /// elevation that covers the 'group.role' command
+    async fn elevated_role_session(
+        &self,
+        group: &str,
+        session: AteSessionGroup,
+        elevation: Option<ScopedToken>,
+    ) -> Result<AteSessionGroup, GroupRoleFailed> {
+        self.elevated_session(group, "group.role", session, elevation)
+            .await
+            .map_err(|err| match err {
+                ElevationFailed::GroupNotFound => GroupRoleFailed::GroupNotFound,
+                ElevationFailed::NoMasterKey => GroupRoleFailed::NoMasterKey,
+                ElevationFailed::InternalError(code) => GroupRoleFailed::InternalError(code),
+                _ => GroupRoleFailed::NoAccess,
+            })
+    }
+
+    /// Loads a group so that its roles can be changed, this requires the
+    /// caller to be a delegate of the group
+    async fn load_group_for_roles(
+        &self,
+        group: &str,
+        session: &AteSessionGroup,
+    ) -> Result<(Arc<DioMut>, DaoMut<Group>, PrivateEncryptKey), GroupRoleFailed> {
+        // Compute which chain the group should exist within
+        let group_chain_key = chain_key_4hex(group, Some("redo"));
+        let chain = self
+            .registry
+            .open(&self.auth_url, &group_chain_key, true)
+            .await?;
+
+        // Create the super session that has all the rights we need
+        let mut super_session = self.master_session.clone();
+        super_session.append(session.properties());
+
+        // Load the group
+        let group_key = PrimaryKey::from(group.to_string());
+        let dio = chain.dio_full(&super_session).await;
+        let group = match dio.load::<Group>(&group_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Err(GroupRoleFailed::GroupNotFound);
+            }
+            Err(LoadError(
+                LoadErrorKind::TransformationError(TransformErrorKind::MissingReadKey(_)),
+                _,
+            )) => {
+                return Err(GroupRoleFailed::NoMasterKey);
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+
+        // Only the delegates of the group may change its roles
+        let delegate_write =
+            match AuthService::get_delegate_write(session, AteRolePurpose::Delegate)? {
+                Some(a) => a,
+                None => {
+                    return Err(GroupRoleFailed::NoAccess);
+                }
+            };
+        let delegate_write_hash = delegate_write.as_public_key().hash();
+        if group
+            .roles
+            .iter()
+            .filter(|r| r.purpose == AteRolePurpose::Delegate)
+            .any(|r| r.access.exists(&delegate_write_hash))
+            == false
+        {
+            return Err(GroupRoleFailed::NoAccess);
+        }
+
+        Ok((dio, group, delegate_write))
+    }
+}
+
+/// Custom roles are the only ones that can be created and removed, they must
+/// not clash with the roles that sessions use to hold older and database keys
+fn check_custom_role(purpose: &AteRolePurpose) -> Result<(), GroupRoleFailed> {
+    let name = match purpose {
+        AteRolePurpose::Other(a) => a,
+        _ => {
+            return Err(GroupRoleFailed::BuiltInRole);
+        }
+    };
+    if name.len() <= 0
+        || name.starts_with("previous-")
+        || name.starts_with("chain-")
+        || name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            == false
+    {
+        return Err(GroupRoleFailed::InvalidRole);
+    }
+    Ok(())
+}
+
+/// Database names follow the same rules as the chains of a group ('[group]/[chain]')
+pub(crate) fn is_valid_chain_name(chain: &str) -> bool {
+    chain.len() > 0 && chain.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Opens the read keys of a database of the group (current key first)
+pub(crate) fn open_chain_keys(
+    group: &Group,
+    chain: &str,
+    key: &PrivateEncryptKey,
+) -> Result<Option<Vec<EncryptKey>>, std::io::Error> {
+    match group.chain_keys.iter().filter(|c| c.chain == chain).next() {
+        Some(a) => a.access.unwrap(key),
+        None => Ok(None),
+    }
+}
+
+/// Shares the read keys of a database with the delegates and all the roles
+/// that are currently bound to read it
+pub(crate) fn seal_chain_keys(
+    group: &mut Group,
+    chain: &str,
+    keys: Vec<EncryptKey>,
+) -> Result<(), std::io::Error> {
+    let read = match keys.first() {
+        Some(a) => a.hash(),
+        None => {
+            return Ok(());
+        }
+    };
+
+    let members = group
+        .roles
+        .iter()
+        .filter(|r| {
+            r.purpose == AteRolePurpose::Delegate
+                || group
+                    .bindings
+                    .iter()
+                    .any(|b| b.purpose == r.purpose && b.chain == chain && b.read)
+        })
+        .map(|r| (r.private_read.clone(), r.purpose.to_string()))
+        .collect::<Vec<_>>();
+    let mut members = members.into_iter();
+    let (first_key, first_meta) = match members.next() {
+        Some(a) => a,
+        None => {
+            return Ok(());
+        }
+    };
+
+    let shared_key = EncryptKey::generate(first_key.size());
+    let mut access =
+        MultiEncryptedSecureData::new_ext(&first_key, shared_key.clone(), first_meta, keys)?;
+    for (key, meta) in members {
+        access.add_ext(&key, meta, shared_key.clone())?;
+    }
+
+    group.chain_keys.retain(|c| c.chain != chain);
+    group.chain_keys.push(ChainKeys {
+        chain: chain.to_string(),
+        read,
+        access,
+    });
+    Ok(())
+}
+
+/// Generates a new read key for a database (the old keys are kept so that
+/// existing records can still be read) and shares it with the current readers
+pub(crate) fn rotate_chain_keys(
+    group: &mut Group,
+    chain: &str,
+    key: &PrivateEncryptKey,
+) -> Result<(), std::io::Error> {
+    let mut keys = match open_chain_keys(group, chain, key)? {
+        Some(a) => a,
+        None => Vec::new(),
+    };
+    keys.insert(0, EncryptKey::generate(key.size()));
+    seal_chain_keys(group, chain, keys)
+}
@@ -22,6 +22,8 @@ use crate::prelude::*;
 use crate::request::*;
 use crate::service::AuthService;
 
+use super::elevate::*;
+
 impl AuthService {
     pub fn get_delegate_write(
         request_session: &AteSessionGroup,
@@ -52,12 +54,46 @@ impl AuthService {
     pub async fn process_group_user_add(
         self: Arc<Self>,
         request: GroupUserAddRequest,
+    ) -> Result<GroupUserAddResponse, GroupUserAddFailed> {
+        let identity = request_identity(&request.session, request.elevation.as_ref());
+        let action = AuditAction::GroupUserAdd {
+            group: request.group.clone(),
+            who: request.who_name.clone(),
+            purpose: request.purpose.clone(),
+        };
+        let ret = self.clone().process_group_user_add_internal(request).await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_group_user_add_internal(
+        self: Arc<Self>,
+        request: GroupUserAddRequest,
     ) -> Result<GroupUserAddResponse, GroupUserAddFailed> {
         info!("group ({}) user add", request.group);
 
-        // Copy the request session
+        // Copy the request session (or the one held by its elevation)
         let request_purpose = request.purpose;
-        let request_session = request.session;
+        let request_session = self
+            .elevated_session(
+                &request.group,
+                "group.add-user",
+                request.session,
+                request.elevation,
+            )
+            .await
+            .map_err(|err| match err {
+                ElevationFailed::GroupNotFound => GroupUserAddFailed::GroupNotFound,
+                ElevationFailed::NoMasterKey => GroupUserAddFailed::NoMasterKey,
+                ElevationFailed::InternalError(code) => GroupUserAddFailed::InternalError(code),
+                _ => GroupUserAddFailed::NoAccess,
+            })?;
 
         // Load the master key which will be used to encrypt the group so that only
         // the authentication server can access it
@@ -109,8 +145,13 @@ impl AuthService {
             }
         };
 
-        // If the role does not exist then add it
+        // If the role does not exist then add it (custom roles must first be
+        // created by a delegate so that a typo does not create a new role)
         if group.roles.iter().any(|r| r.purpose == request_purpose) == false {
+            if let AteRolePurpose::Other(_) = &request_purpose {
+                return Err(GroupUserAddFailed::InvalidPurpose);
+            }
+
             // Get our own identity
             let referrer_identity = request_session.inner.identity().to_string();
 
@@ -134,6 +175,7 @@ impl AuthService {
                 read: role_read.hash(),
                 private_read: role_private_read.as_public_key().clone(),
                 write: role_write.as_public_key().clone(),
+                previous: None,
             })
         }
 
@@ -22,16 +22,56 @@ use crate::prelude::*;
 use crate::request::*;
 use crate::service::AuthService;
 
+use super::elevate::*;
+use super::group_role::*;
+
 impl AuthService {
     pub async fn process_group_user_remove(
         self: Arc<Self>,
         request: GroupUserRemoveRequest,
+    ) -> Result<GroupUserRemoveResponse, GroupUserRemoveFailed> {
+        let identity = request_identity(&request.session, request.elevation.as_ref());
+        let action = AuditAction::GroupUserRemove {
+            group: request.group.clone(),
+            who: request.who.clone(),
+            purpose: request.purpose.clone(),
+        };
+        let ret = self
+            .clone()
+            .process_group_user_remove_internal(request)
+            .await;
+        self.audit(
+            identity.as_str(),
+            action,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    async fn process_group_user_remove_internal(
+        self: Arc<Self>,
+        request: GroupUserRemoveRequest,
     ) -> Result<GroupUserRemoveResponse, GroupUserRemoveFailed> {
         info!("group ({}) user remove", request.group);
 
-        // Copy the request session
+        // Copy the request session (or the one held by its elevation)
         let request_purpose = request.purpose;
-        let request_session = request.session;
+        let request_session = self
+            .elevated_session(
+                &request.group,
+                "group.remove-user",
+                request.session,
+                request.elevation,
+            )
+            .await
+            .map_err(|err| match err {
+                ElevationFailed::GroupNotFound => GroupUserRemoveFailed::GroupNotFound,
+                ElevationFailed::NoMasterKey => GroupUserRemoveFailed::NoMasterKey,
+                ElevationFailed::InternalError(code) => GroupUserRemoveFailed::InternalError(code),
+                _ => GroupUserRemoveFailed::NoAccess,
+            })?;
 
         // Compute which chain the group should exist within
         let group_chain_key = chain_key_4hex(&request.group, Some("redo"));
@@ -44,6 +84,9 @@ impl AuthService {
         // Load the group
         let group_key = PrimaryKey::from(request.group.clone());
         let dio = chain.dio_full(&super_session).await;
+
+        // Nothing is written unless the removal succeeds as a whole
+        dio.auto_cancel();
         let mut group = match dio.load::<Group>(&group_key).await {
             Ok(a) => a,
             Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
@@ -105,12 +148,472 @@ impl AuthService {
             }
         }
 
+        // The departed member still holds the keys of the role (and of every role
+        // those keys open) so they are rotated and handed to the remaining members
+        let rotated = self
+            .rotate_group_keys(
+                &dio,
+                &mut group,
+                &request_purpose,
+                &request.who,
+                &request_session,
+            )
+            .await?;
+
         // Commit
         dio.commit().await?;
 
+        // The records in the databases of the group are moved onto the new keys
+        // in the background (the old keys are kept until this has finished)
+        {
+            let service = Arc::clone(&self);
+            let group_name = request.group.clone();
+            TaskEngine::spawn(async move {
+                if let Err(err) = service.reencrypt_group(group_name.as_str(), rotated).await {
+                    warn!(
+                        "group ({}) failed to move records onto the new keys - {}",
+                        group_name, err
+                    );
+                }
+            });
+        }
+
         // Return success to the caller
         Ok(GroupUserRemoveResponse {
             key: group.key().clone(),
         })
     }
+
+    /// Generates new keys for a role and for all the roles that it can reach,
+    /// then distributes them to the remaining members through their public keys
+    /// (the old keys are kept in the role, encrypted with the new read key, so
+    /// that existing records can still be read until they are re-encrypted)
+    /// returning the old and new keys of every role that was rotated
+    async fn rotate_group_keys(
+        self: &Arc<Self>,
+        dio: &Arc<DioMut>,
+        group: &mut DaoMut<Group>,
+        purpose: &AteRolePurpose,
+        who: &AteHash,
+        request_session: &AteSessionGroup,
+    ) -> Result<Vec<(AteRolePurpose, Authorization, Authorization)>, GroupUserRemoveFailed> {
+        let group_name = group.name.clone();
+
+        // Every role that has one of the reachable roles as a member is itself
+        // reachable so keep expanding until nothing more is found
+        let mut rotate = vec![purpose.clone()];
+        loop {
+            let reachable = group
+                .roles
+                .iter()
+                .filter(|r| rotate.contains(&r.purpose) == false)
+                .filter(|r| {
+                    r.access.member_list().any(|(hash, _)| {
+                        group
+                            .roles
+                            .iter()
+                            .any(|o| o.private_read.hash() == hash && rotate.contains(&o.purpose))
+                    })
+                })
+                .map(|r| r.purpose.clone())
+                .collect::<Vec<_>>();
+            if reachable.len() <= 0 {
+                break;
+            }
+            rotate.extend(reachable);
+        }
+        debug!(
+            "group ({}) rotating keys of roles: {}",
+            group_name,
+            rotate
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        // The caller must be able to open all the roles that are rotated
+        let mut old_auths = Vec::new();
+        for role in group.roles.iter().filter(|r| rotate.contains(&r.purpose)) {
+            match unwrap_role(role, request_session)? {
+                Some(a) => old_auths.push((role.purpose.clone(), a)),
+                None => {
+                    debug!(
+                        "group-user-remove-failed cannot open the {} role",
+                        role.purpose
+                    );
+                    return Err(GroupUserRemoveFailed::NoAccess);
+                }
+            }
+        }
+
+        // Generate the new keys (the finance read key is derived from the group
+        // name so that contracts can compute it hence it stays the same)
+        let new_auths = old_auths
+            .iter()
+            .map(|(purpose, old)| {
+                let key_size = old.read.size();
+                let read = match purpose {
+                    AteRolePurpose::Finance => old.read.clone(),
+                    _ => EncryptKey::generate(key_size),
+                };
+                let auth = Authorization {
+                    read,
+                    private_read: PrivateEncryptKey::generate(key_size),
+                    write: PrivateSignKey::generate(key_size),
+                };
+                (purpose.clone(), auth)
+            })
+            .collect::<Vec<_>>();
+        let new_auth = |purpose: &AteRolePurpose| {
+            new_auths
+                .iter()
+                .filter(|(p, _)| p == purpose)
+                .map(|(_, a)| a)
+                .next()
+        };
+        let public_key = |role: &Role| match new_auth(&role.purpose) {
+            Some(a) => a.private_read.as_public_key().clone(),
+            None => role.private_read.clone(),
+        };
+
+        // Find the public keys of the members that remain, roles of this group
+        // get the new keys of the role while users and other groups are looked
+        // up from the keys they advertise
+        let mut remaining = Vec::new();
+        for role in group.roles.iter().filter(|r| rotate.contains(&r.purpose)) {
+            let mut members = Vec::new();
+            for (hash, meta) in role.access.member_list() {
+                if hash == *who {
+                    continue;
+                }
+                let meta = meta.map(|a| a.clone()).unwrap_or_default();
+                if let Some(other) = group
+                    .roles
+                    .iter()
+                    .filter(|r| r.private_read.hash() == hash)
+                    .next()
+                {
+                    members.push((public_key(other), meta));
+                    continue;
+                }
+                // Members are never dropped silently, one whose keys can not
+                // be found has to be removed explicitly before anyone else
+                let key = self
+                    .find_member_key(&hash, meta.as_str())
+                    .await
+                    .map_err(|err| {
+                        warn!(
+                            "group ({}) member ({}) of the {} role could not be found - {}",
+                            group_name, meta, role.purpose, err
+                        );
+                        err
+                    })?;
+                members.push((key, meta));
+            }
+            remaining.push((role.purpose.clone(), members));
+        }
+        let owner_key = group
+            .roles
+            .iter()
+            .filter(|r| r.purpose == AteRolePurpose::Owner)
+            .map(|r| public_key(r))
+            .next();
+
+        // Rebuild the access of the roles with the new keys
+        {
+            let mut group_mut = group.as_mut();
+            for role in group_mut.roles.iter_mut() {
+                let (old, new) = match (
+                    old_auths.iter().filter(|(p, _)| *p == role.purpose).next(),
+                    new_auth(&role.purpose),
+                ) {
+                    (Some((_, old)), Some(new)) => (old, new),
+                    _ => continue,
+                };
+                let mut members = remaining
+                    .iter()
+                    .filter(|(p, _)| *p == role.purpose)
+                    .flat_map(|(_, m)| m.iter())
+                    .map(|a| a.clone())
+                    .collect::<Vec<_>>();
+                if members.len() <= 0 {
+                    if let Some(owner_key) = owner_key.as_ref() {
+                        members.push((owner_key.clone(), "owner".to_string()));
+                    }
+                }
+
+                // Web servers and edge compute derive the access key of their
+                // roles themselves so it must stay the same
+                let shared_key = match role.purpose {
+                    AteRolePurpose::WebServer => {
+                        let web_key_entropy = format!("web-read:{}", group_name);
+                        let web_key_entropy = AteHash::from_bytes(web_key_entropy.as_bytes());
+                        self.compute_web_key_from_hash(&web_key_entropy)
+                    }
+                    AteRolePurpose::EdgeCompute => {
+                        let edge_key_entropy = format!("edge-read:{}", group_name);
+                        let edge_key_entropy = AteHash::from_bytes(edge_key_entropy.as_bytes());
+                        self.compute_edge_key_from_hash(&edge_key_entropy)
+                    }
+                    _ => EncryptKey::generate(new.private_read.size()),
+                };
+                let mut members = members.into_iter();
+                let (first_key, first_meta) = match members.next() {
+                    Some(a) => a,
+                    None => {
+                        return Err(GroupUserRemoveFailed::NoAccess);
+                    }
+                };
+                let mut access = MultiEncryptedSecureData::new_ext(
+                    &first_key,
+                    shared_key.clone(),
+                    first_meta,
+                    new.clone(),
+                )?;
+                for (key, meta) in members {
+                    access.add_ext(&key, meta, shared_key.clone())?;
+                }
+
+                let mut previous = match role.previous.as_ref() {
+                    Some(a) => a.unwrap(&old.read)?,
+                    None => Vec::new(),
+                };
+                previous.push(old.clone());
+
+                role.read = new.read.hash();
+                role.private_read = new.private_read.as_public_key().clone();
+                role.write = new.write.as_public_key().clone();
+                role.access = access;
+                role.previous = Some(EncryptedSecureData::new(&new.read, previous)?);
+            }
+        }
+
+        // Databases that could be read through one of the rotated roles get a
+        // new read key that is shared using the new keys of the roles
+        {
+            let mut group_mut = group.as_mut();
+            let chains = group_mut
+                .chain_keys
+                .iter()
+                .map(|c| c.chain.clone())
+                .collect::<Vec<_>>();
+            for chain in chains {
+                let mut keys = None;
+                for (_, old) in old_auths.iter() {
+                    keys = open_chain_keys(&group_mut, chain.as_str(), &old.private_read)?;
+                    if keys.is_some() {
+                        break;
+                    }
+                }
+                if let Some(mut keys) = keys {
+                    keys.insert(0, EncryptKey::generate(keys[0].size()));
+                    seal_chain_keys(&mut group_mut, chain.as_str(), keys)?;
+                }
+            }
+        }
+
+        // The group itself may be written by the owner role
+        if let Some(owner) = new_auth(&AteRolePurpose::Owner) {
+            let master_write_key = match self.master_session.user.write_keys().next() {
+                Some(a) => a.clone(),
+                None => {
+                    return Err(GroupUserRemoveFailed::NoMasterKey);
+                }
+            };
+            group.auth_mut().write =
+                WriteOption::Any(vec![master_write_key.hash(), owner.write.hash()]);
+        }
+
+        // Update the keys that the group advertises to others (groups that this
+        // group is a member of will need to add it again with its new keys)
+        let observer = new_auth(&AteRolePurpose::Observer);
+        let contributor = new_auth(&AteRolePurpose::Contributor);
+        let owner = new_auth(&AteRolePurpose::Owner);
+        if observer.is_some() || contributor.is_some() || owner.is_some() {
+            let advert_key = PrimaryKey::from(format!("advert:{}", group_name));
+            let mut advert = dio.load::<Advert>(&advert_key).await?;
+            let mut advert = advert.as_mut();
+            if let Some(a) = observer {
+                advert.nominal_encrypt = a.private_read.as_public_key().clone();
+            }
+            if let Some(a) = contributor {
+                advert.nominal_auth = a.write.as_public_key().clone();
+            }
+            if let Some(a) = owner {
+                advert.sudo_encrypt = a.private_read.as_public_key().clone();
+                advert.sudo_auth = a.write.as_public_key().clone();
+            }
+        }
+
+        Ok(old_auths
+            .into_iter()
+            .filter_map(|(purpose, old)| {
+                new_auth(&purpose)
+                    .map(|new| new.clone())
+                    .map(|new| (purpose, old, new))
+            })
+            .collect())
+    }
+
+    /// Moves the records in the databases of a group that are protected by the
+    /// keys of roles that were rotated onto their new keys, once every database
+    /// has been moved the old keys are removed from the roles
+    pub(crate) async fn reencrypt_group(
+        self: &Arc<Self>,
+        group_name: &str,
+        rotated: Vec<(AteRolePurpose, Authorization, Authorization)>,
+    ) -> Result<(), AteError> {
+        let group_chain_key = chain_key_4hex(group_name, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &group_chain_key, true).await?;
+        let group_key = PrimaryKey::from(group_name.to_string());
+        let group = chain
+            .dio(&self.master_session)
+            .await
+            .load::<Group>(&group_key)
+            .await?;
+
+        // The session holds the new keys of the roles and the keys that they
+        // replaced (including the read keys of the databases)
+        let mut session = AteSessionGroup::new(
+            AteSessionInner::User(self.master_session.clone()),
+            group_name.to_string(),
+        );
+        for (purpose, old, new) in rotated.iter() {
+            let previous = previous_role_purpose(purpose);
+            session.add_group_read_key(purpose, &new.read);
+            session.add_group_private_read_key(purpose, &new.private_read);
+            session.add_group_write_key(purpose, &new.write);
+            session.add_group_read_key(&previous, &old.read);
+            session.add_group_private_read_key(&previous, &old.private_read);
+            session.add_group_write_key(&previous, &old.write);
+        }
+        let mut chain_keys = Vec::new();
+        for c in group.chain_keys.iter() {
+            let mut keys = None;
+            for (_, _, new) in rotated.iter() {
+                keys = open_chain_keys(group.deref(), c.chain.as_str(), &new.private_read)?;
+                if keys.is_some() {
+                    break;
+                }
+            }
+            if let Some(keys) = keys {
+                let purpose = chain_role_purpose(c.chain.as_str());
+                let previous = previous_role_purpose(&purpose);
+                for (n, key) in keys.iter().enumerate() {
+                    match n {
+                        0 => session.add_group_read_key(&purpose, key),
+                        _ => session.add_group_read_key(&previous, key),
+                    }
+                }
+                chain_keys.push((c.chain.clone(), keys));
+            }
+        }
+
+        // Move the records in all the databases that the group is known to have
+        let mut databases = group.databases.clone();
+        databases.extend(group.bindings.iter().map(|b| b.chain.clone()));
+        databases.extend(group.chain_keys.iter().map(|c| c.chain.clone()));
+        databases.sort();
+        databases.dedup();
+        if databases.len() > 0 {
+            let db_url = match self.db_url.as_ref() {
+                Some(a) => a,
+                None => {
+                    warn!(
+                        "group ({}) has databases but the server has no database address so they were not moved onto the new keys",
+                        group_name
+                    );
+                    return Ok(());
+                }
+            };
+            for database in databases {
+                let key = ChainKey::from(format!("{}/{}", group_name, database));
+                let db = self.registry.open(db_url, &key, false).await?;
+                let moved = reencrypt_chain(&db.as_arc(), &session).await?;
+                info!(
+                    "group ({}) moved {} records of database ({}) onto the new keys",
+                    group_name, moved, database
+                );
+            }
+        }
+
+        // Nothing needs the old keys anymore so they are removed (unless the
+        // keys were rotated again in the meantime)
+        let dio = chain.dio_full(&self.master_session).await;
+        let mut group = dio.load::<Group>(&group_key).await?;
+        {
+            let mut group = group.as_mut();
+            for role in group.roles.iter_mut() {
+                if rotated
+                    .iter()
+                    .any(|(p, _, new)| *p == role.purpose && new.read.hash() == role.read)
+                {
+                    role.previous = None;
+                }
+            }
+            for (chain, keys) in chain_keys {
+                let current = group
+                    .chain_keys
+                    .iter()
+                    .any(|c| c.chain == chain && c.read == keys[0].hash());
+                if current && keys.len() > 1 {
+                    seal_chain_keys(&mut group, chain.as_str(), vec![keys[0].clone()])?;
+                }
+            }
+        }
+        dio.commit().await?;
+        debug!("group ({}) removed the keys that were rotated", group_name);
+        Ok(())
+    }
+
+    /// Finds the public key of a member (a user or another group) amongst the
+    /// keys that it advertises
+    async fn find_member_key(
+        self: &Arc<Self>,
+        hash: &AteHash,
+        identity: &str,
+    ) -> Result<PublicEncryptKey, GroupUserRemoveFailed> {
+        let advert = match Arc::clone(self)
+            .process_query(QueryRequest {
+                identity: identity.to_string(),
+            })
+            .await
+        {
+            Ok(a) => a.advert,
+            Err(QueryFailed::InternalError(code)) => {
+                return Err(GroupUserRemoveFailed::InternalError(code));
+            }
+            Err(_) => {
+                return Err(GroupUserRemoveFailed::MemberNotFound(identity.to_string()));
+            }
+        };
+        vec![
+            advert.nominal_encrypt,
+            advert.sudo_encrypt,
+            advert.broker_encrypt,
+        ]
+        .into_iter()
+        .filter(|k| k.hash() == *hash)
+        .next()
+        .ok_or_else(|| GroupUserRemoveFailed::MemberNotFound(identity.to_string()))
+    }
+}
+
+/// Opens a role with the keys of a session returning its keys
+fn unwrap_role(
+    role: &Role,
+    session: &AteSessionGroup,
+) -> Result<Option<Authorization>, std::io::Error> {
+    for key in session.private_read_keys(AteSessionKeyCategory::AllKeys) {
+        if let Some(a) = role.access.unwrap(key)? {
+            return Ok(Some(a));
+        }
+    }
+    for key in session.read_keys(AteSessionKeyCategory::AllKeys) {
+        if let Some(a) = role.access.unwrap_shared(key)? {
+            return Ok(Some(a));
+        }
+    }
+    Ok(None)
 }
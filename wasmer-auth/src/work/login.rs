@@ -21,14 +21,80 @@ use crate::prelude::*;
 use crate::request::*;
 use crate::service::AuthService;
 
+use super::email_recovery::generate_email_code;
 use super::sudo::*;
 
+/// Number of failed logins an account may have before it is locked out
+const ACCOUNT_FREE_ATTEMPTS: u32 = 5;
+/// Number of failed logins a source address may have before it is backed off
+/// (this is higher than for accounts as many users can share an address)
+const SOURCE_FREE_ATTEMPTS: u32 = 20;
+/// Maximum number of source addresses (or accounts that do not exist) whose
+/// failures are remembered
+const SOURCE_MAX_TRACKED: usize = 10000;
+
 impl AuthService {
     pub async fn process_login(
         self: Arc<Self>,
         request: LoginRequest,
+    ) -> Result<LoginResponse, LoginFailed> {
+        // Logins that did not arrive over a connection only throttle the account
+        self.process_login_from(request, None).await
+    }
+
+    /// Logs in on behalf of a client whose address was taken from the
+    /// connection that it made to this server (e.g. the command chain it
+    /// opened or the OpenID Connect provider)
+    pub(crate) async fn process_login_from(
+        self: Arc<Self>,
+        request: LoginRequest,
+        source: Option<String>,
     ) -> Result<LoginResponse, LoginFailed> {
         debug!("login attempt: {}", request.email);
+        let email = request.email.clone();
+
+        // Accounts and sources that keep failing are locked out for a while
+        let ret = match self.login_throttled(email.as_str(), source.as_ref()).await {
+            Some(duration) => {
+                warn!(
+                    "login attempt denied ({}) - throttled for {}s",
+                    email,
+                    duration.as_secs()
+                );
+                Err(LoginFailed::AccountLocked(duration))
+            }
+            None => self.process_login_internal(request).await,
+        };
+        self.audit(
+            email.as_str(),
+            AuditAction::Login,
+            source.clone(),
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+
+        // Unknown accounts fail in exactly the same way as wrong passwords so
+        // that the caller can not use this to find out who has an account
+        match ret {
+            Ok(a) => {
+                self.login_attempted(email.as_str(), source, true).await;
+                Ok(a)
+            }
+            Err(LoginFailed::UserNotFound(_)) | Err(LoginFailed::WrongPassword) => {
+                self.login_attempted(email.as_str(), source, false).await;
+                Err(LoginFailed::WrongPassword)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn process_login_internal(
+        &self,
+        request: LoginRequest,
+    ) -> Result<LoginResponse, LoginFailed> {
+        if let Some(token) = request.token {
+            return self.login_with_token(request.email, token).await;
+        }
 
         // Create the super key and token
         let (super_key, token) = match self.compute_master_key(&request.secret) {
@@ -39,28 +105,254 @@ impl AuthService {
             }
         };
 
+        self.login_with_super_key(request.email, &super_key, token, request.verification_code)
+            .await
+    }
+
+    /// Exchanges a scoped token for a fresh session of the user that minted
+    /// it, the server opens the session sealed in the token (which is proof
+    /// that the caller holds it) and only if the token is still valid
+    async fn login_with_token(
+        &self,
+        email: String,
+        token: ScopedToken,
+    ) -> Result<LoginResponse, LoginFailed> {
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                warn!("login attempt denied ({}) - no master key", email);
+                return Err(LoginFailed::NoMasterKey);
+            }
+        };
+        if token.identity != email || token.token.scope.group.is_some() {
+            warn!("login attempt denied ({}) - token for someone else", email);
+            return Err(LoginFailed::WrongPassword);
+        }
+
+        let sealed = match self
+            .open_scoped_token(token.identity.as_str(), &token.token.id, &token.sealed)
+            .await
+        {
+            Ok((a, _)) if a.elevated => {
+                warn!("login attempt denied ({}) - elevations are not exchanged", email);
+                return Err(LoginFailed::WrongPassword);
+            }
+            Ok((_, a)) => a,
+            Err(TokenFailed::Expired(id)) => {
+                warn!("login attempt denied ({}) - token expired", email);
+                return Err(LoginFailed::TokenExpired(id));
+            }
+            Err(TokenFailed::Revoked(id)) | Err(TokenFailed::NotFound(id)) => {
+                warn!("login attempt denied ({}) - token revoked", email);
+                return Err(LoginFailed::TokenRevoked(id));
+            }
+            Err(TokenFailed::NoMasterKey) => {
+                return Err(LoginFailed::NoMasterKey);
+            }
+            Err(TokenFailed::NoAccess) => {
+                return Err(LoginFailed::WrongPassword);
+            }
+            Err(TokenFailed::InternalError(code)) => {
+                return Err(LoginFailed::InternalError(code));
+            }
+        };
+
+        // The super key of the user is recovered from the session that was
+        // sealed in the token so the account is opened just like a login
+        // (sudo rights are dropped while break-glass is enabled)
+        let (user, sudo) = match sealed {
+            AteSessionType::User(a) => (a, None),
+            AteSessionType::Sudo(a) if self.break_glass.is_some() => (a.inner, None),
+            AteSessionType::Sudo(a) => (a.inner, Some(a.sudo)),
+            _ => {
+                warn!("login attempt denied ({}) - token holds no user", email);
+                return Err(LoginFailed::WrongPassword);
+            }
+        };
+        let blob = match user.token {
+            Some(a) => a,
+            None => {
+                warn!("login attempt denied ({}) - token holds no user", email);
+                return Err(LoginFailed::WrongPassword);
+            }
+        };
+        let super_key = blob.unwrap(&master_key)?;
+
+        let mut ret = self
+            .login_with_super_key(email, &super_key, blob, None)
+            .await?;
+        ret.sudo = sudo.map(|sudo| AteSessionSudo {
+            inner: ret.authority.clone(),
+            sudo,
+        });
+        Ok(ret)
+    }
+
+    /// Returns how long the login must wait when either the account or the
+    /// source address has failed too many times
+    pub(crate) async fn login_throttled(
+        &self,
+        email: &str,
+        source: Option<&String>,
+    ) -> Option<std::time::Duration> {
+        let source_lock = match source {
+            Some(source) => {
+                let guard = self.source_throttle.lock().await;
+                guard.get(source).and_then(|a| a.locked_for())
+            }
+            None => None,
+        };
+
+        let account_lock = match self.load_login_throttle(email).await {
+            Ok((_, Some(a))) => a.locked_for(),
+            Ok((_, None)) => {
+                let guard = self.unknown_throttle.lock().await;
+                guard.get(email).and_then(|a| a.locked_for())
+            }
+            Err(err) => {
+                warn!("failed to load the login throttle ({}) - {:?}", email, err);
+                None
+            }
+        };
+        source_lock.max(account_lock)
+    }
+
+    /// Records the outcome of a login against both the account and the source
+    /// address, accounts that do not exist are only counted in memory
+    pub(crate) async fn login_attempted(&self, email: &str, source: Option<String>, success: bool) {
+        if let Some(source) = source {
+            let mut guard = self.source_throttle.lock().await;
+            if success {
+                guard.remove(&source);
+            } else {
+                if guard.len() >= SOURCE_MAX_TRACKED {
+                    guard.retain(|_, a| a.locked_for().is_some());
+                }
+                guard
+                    .entry(source)
+                    .or_default()
+                    .failed(SOURCE_FREE_ATTEMPTS);
+            }
+        }
+
+        if let Err(err) = self.login_attempted_internal(email, success).await {
+            warn!(
+                "failed to update the login throttle ({}) - {:?}",
+                email, err
+            );
+        }
+    }
+
+    async fn login_attempted_internal(
+        &self,
+        email: &str,
+        success: bool,
+    ) -> Result<(), LoginFailed> {
+        let (dio, throttle) = self.load_login_throttle(email).await?;
+        match (throttle, success) {
+            (Some(mut throttle), true) => {
+                if throttle.failed_attempts > 0 {
+                    throttle.as_mut().succeeded();
+                }
+            }
+            (Some(mut throttle), false) => {
+                throttle.as_mut().failed(ACCOUNT_FREE_ATTEMPTS);
+            }
+            (None, true) => {
+                self.unknown_throttle.lock().await.remove(email);
+            }
+            (None, false) => {
+                // Otherwise anyone could fill the chain with made up accounts
+                let user_key = PrimaryKey::from(email.to_string());
+                if dio.exists(&user_key).await == false {
+                    let mut guard = self.unknown_throttle.lock().await;
+                    if guard.len() >= SOURCE_MAX_TRACKED {
+                        guard.retain(|_, a| a.locked_for().is_some());
+                    }
+                    guard
+                        .entry(email.to_string())
+                        .or_default()
+                        .failed(ACCOUNT_FREE_ATTEMPTS);
+                    return Ok(());
+                }
+
+                let master_write_key = match self.master_session.user.write_keys().next() {
+                    Some(a) => a.clone(),
+                    None => {
+                        return Err(LoginFailed::NoMasterKey);
+                    }
+                };
+                let master_key = match self.master_key() {
+                    Some(a) => a.clone(),
+                    None => {
+                        return Err(LoginFailed::NoMasterKey);
+                    }
+                };
+
+                let mut throttle = LoginThrottle::default();
+                throttle.failed(ACCOUNT_FREE_ATTEMPTS);
+                let throttle_key = PrimaryKey::from(format!("login-throttle:{}", email));
+                let mut throttle = dio.store_with_key(throttle, throttle_key)?;
+                throttle.auth_mut().read = ReadOption::from_key(&master_key);
+                throttle.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+            }
+        }
+        dio.commit().await?;
+        Ok(())
+    }
+
+    /// The failed logins of an account are kept in a record that only the
+    /// authentication server can read as the password is not known yet
+    async fn load_login_throttle(
+        &self,
+        email: &str,
+    ) -> Result<(Arc<DioMut>, Option<DaoMut<LoginThrottle>>), LoginFailed> {
+        let chain_key = chain_key_4hex(email, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        let throttle_key = PrimaryKey::from(format!("login-throttle:{}", email));
+        let throttle = match dio.load::<LoginThrottle>(&throttle_key).await {
+            Ok(a) => Some(a),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => None,
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        Ok((dio, throttle))
+    }
+
+    /// Opens the account of the user with the super key that was derived from
+    /// their login secret and builds the session they will use
+    pub(crate) async fn login_with_super_key(
+        &self,
+        email: String,
+        super_key: &EncryptKey,
+        token: EncryptedSecureData<EncryptKey>,
+        verification_code: Option<String>,
+    ) -> Result<LoginResponse, LoginFailed> {
         // Create the super session
         let mut super_session = self.master_session.clone();
-        super_session.user.add_read_key(&super_key);
+        super_session.user.add_read_key(super_key);
 
         // Compute which chain the user should exist within
-        let chain_key = chain_key_4hex(request.email.as_str(), Some("redo"));
+        let chain_key = chain_key_4hex(email.as_str(), Some("redo"));
         let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
         let dio = chain.dio_full(&super_session).await;
 
         // Attempt to load the object (if it fails we will tell the caller)
-        let user_key = PrimaryKey::from(request.email.clone());
+        let user_key = PrimaryKey::from(email.clone());
         let mut user = match dio.load::<User>(&user_key).await {
             Ok(a) => a,
             Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
-                warn!("login attempt denied ({}) - not found", request.email);
-                return Err(LoginFailed::UserNotFound(request.email));
+                warn!("login attempt denied ({}) - not found", email);
+                return Err(LoginFailed::UserNotFound(email));
             }
             Err(LoadError(
                 LoadErrorKind::TransformationError(TransformErrorKind::MissingReadKey(_)),
                 _,
             )) => {
-                warn!("login attempt denied ({}) - wrong password", request.email);
+                warn!("login attempt denied ({}) - wrong password", email);
                 return Err(LoginFailed::WrongPassword);
             }
             Err(err) => {
@@ -78,17 +370,26 @@ impl AuthService {
                     let duration = until - utc_now;
                     warn!(
                         "login attempt denied ({}) - account locked until {}",
-                        request.email, until
+                        email, until
                     );
                     return Err(LoginFailed::AccountLocked(duration.to_std().unwrap()));
                 }
             }
-            UserStatus::Unverified => match request.verification_code {
+            UserStatus::Unverified => match verification_code {
                 Some(a) => {
-                    if Some(a.to_lowercase())
-                        != user.verification_code.clone().map(|a| a.to_lowercase())
-                    {
-                        warn!("login attempt denied ({}) - wrong password", request.email);
+                    // Codes that were emailed are only valid for a limited time
+                    let valid = match self.mail.is_some() {
+                        true => {
+                            self.check_email_code(&email, EmailPurpose::Verify, a.as_str())
+                                .await?
+                        }
+                        false => {
+                            Some(a.to_lowercase())
+                                == user.verification_code.clone().map(|a| a.to_lowercase())
+                        }
+                    };
+                    if valid == false {
+                        warn!("login attempt denied ({}) - wrong password", email);
                         return Err(LoginFailed::WrongPassword);
                     } else {
                         let mut user = user.as_mut();
@@ -97,12 +398,20 @@ impl AuthService {
                     }
                 }
                 None => {
-                    warn!("login attempt denied ({}) - unverified", request.email);
-                    return Err(LoginFailed::Unverified(request.email));
+                    // A fresh code is emailed in case the last one expired or
+                    // was thrown away (codes are not sent more than once a minute)
+                    self.email_code(&email, EmailPurpose::Verify, &generate_email_code())
+                        .await?;
+                    warn!("login attempt denied ({}) - unverified", email);
+                    return Err(LoginFailed::Unverified(email));
                 }
             },
             UserStatus::Nominal => {}
         };
+
+        // The copy of the key held for recovery by email (if the user opted
+        // in) must open the account with the key it is now protected by
+        self.refresh_email_recovery(&dio, &email, super_key).await?;
         dio.commit().await?;
 
         // Add all the authorizations
@@ -111,7 +420,7 @@ impl AuthService {
 
         // Return the session that can be used to access this user
         let user = user.take();
-        info!("login attempt accepted ({})", request.email);
+        info!("login attempt accepted ({})", email);
         Ok(LoginResponse {
             user_key,
             nominal_read: user.nominal_read,
@@ -120,6 +429,7 @@ impl AuthService {
             sudo_write: user.sudo_write,
             authority: session,
             message_of_the_day: None,
+            sudo: None,
         })
     }
 
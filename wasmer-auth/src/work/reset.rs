@@ -26,7 +26,16 @@ impl AuthService {
         self: Arc<Self>,
         request: ResetRequest,
     ) -> Result<ResetResponse, ResetFailed> {
-        Ok(self.process_reset_internal(request).await?.0)
+        let email = request.email.clone();
+        let ret = self.clone().process_reset_internal(request).await;
+        self.audit(
+            email.as_str(),
+            AuditAction::Reset,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        Ok(ret?.0)
     }
 
     pub async fn process_reset_internal(
@@ -216,6 +225,10 @@ impl AuthService {
             recovery_mut.qr_code = qr_code.clone();
         }
 
+        // The key that allows the account to be recovered by email has changed
+        self.refresh_email_recovery(&dio, &request.email, &super_key)
+            .await?;
+
         // Commit the transaction
         dio.commit().await?;
 
@@ -0,0 +1,318 @@
+#![allow(unused_imports)]
+use error_chain::bail;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::error::LoadError;
+use ate::prelude::*;
+use ate::utils::chain_key_4hex;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::prelude::*;
+use crate::request::*;
+use crate::service::AuthService;
+
+impl AuthService {
+    pub async fn process_ssh_key_add(
+        self: Arc<Self>,
+        request: SshKeyAddRequest,
+    ) -> Result<SshKeyAddResponse, SshKeyFailed> {
+        let identity = request.session.identity().to_string();
+        info!("ssh key add: {} ({})", identity, request.key.comment);
+
+        let public_key = match request.key.public_key() {
+            Some(a) if a.len() > 0 => a.to_string(),
+            _ => {
+                return Err(SshKeyFailed::InvalidKey);
+            }
+        };
+
+        // The login token is kept with the keys so that the account can be
+        // opened when one of them is used
+        let token = match &request.session {
+            AteSessionInner::User(a) => a.token.clone(),
+            AteSessionInner::Sudo(a) => a.inner.token.clone(),
+            AteSessionInner::Nothing => None,
+        };
+        let token = match token {
+            Some(a) => a,
+            None => {
+                warn!("ssh key add denied ({}) - no token supplied", identity);
+                return Err(SshKeyFailed::MissingToken);
+            }
+        };
+
+        // Make sure the token really opens the account of this user
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(SshKeyFailed::NoMasterKey);
+            }
+        };
+        let super_key = token.unwrap(&master_key)?;
+        let mut super_session = self.master_session.clone();
+        super_session.user.add_read_key(&super_key);
+        let chain_key = chain_key_4hex(&identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio(&super_session).await;
+        if let Err(err) = dio.load::<User>(&PrimaryKey::from(identity.clone())).await {
+            warn!("ssh key add denied ({}) - bad token - {}", identity, err);
+            return Err(SshKeyFailed::NoAccess);
+        }
+
+        let (dio, mut keys) = self.load_ssh_keys(&request.session, Some(token)).await?;
+        {
+            let mut keys = keys.as_mut();
+            keys.keys
+                .retain(|a| a.public_key() != Some(public_key.as_str()));
+            keys.keys.push(request.key.clone());
+        }
+        dio.commit().await?;
+
+        Ok(SshKeyAddResponse { key: request.key })
+    }
+
+    pub async fn process_ssh_key_list(
+        self: Arc<Self>,
+        request: SshKeyListRequest,
+    ) -> Result<SshKeyListResponse, SshKeyFailed> {
+        debug!("ssh key list: {}", request.session.identity());
+
+        let keys = match self.load_ssh_keys(&request.session, None).await {
+            Ok((_, a)) => a.take().keys,
+            Err(SshKeyFailed::NotFound(_)) => Vec::new(),
+            Err(err) => {
+                return Err(err);
+            }
+        };
+        Ok(SshKeyListResponse { keys })
+    }
+
+    pub async fn process_ssh_key_remove(
+        self: Arc<Self>,
+        request: SshKeyRemoveRequest,
+    ) -> Result<SshKeyRemoveResponse, SshKeyFailed> {
+        let identity = request.session.identity().to_string();
+        info!("ssh key remove: {}", identity);
+
+        let (dio, mut keys) = self.load_ssh_keys(&request.session, None).await?;
+        let key = {
+            let mut keys = keys.as_mut();
+            let index = keys.keys.iter().position(|a| {
+                a.public_key() == Some(request.public_key.as_str())
+                    || a.fingerprint().as_deref() == Some(request.public_key.as_str())
+            });
+            match index {
+                Some(a) => keys.keys.remove(a),
+                None => {
+                    return Err(SshKeyFailed::NotFound(request.public_key));
+                }
+            }
+        };
+        dio.commit().await?;
+
+        Ok(SshKeyRemoveResponse { key })
+    }
+
+    pub async fn process_ssh_login(
+        self: Arc<Self>,
+        request: SshLoginRequest,
+    ) -> Result<LoginResponse, SshKeyFailed> {
+        self.process_ssh_login_from(request, None).await
+    }
+
+    /// Logs in on behalf of an SSH server that connected from the `peer`
+    /// address, the address of the client is only taken from the request when
+    /// the SSH server vouched for it (otherwise anyone could pick their own)
+    pub(crate) async fn process_ssh_login_from(
+        self: Arc<Self>,
+        request: SshLoginRequest,
+        peer: Option<String>,
+    ) -> Result<LoginResponse, SshKeyFailed> {
+        let email = request.email.clone();
+        let source = match self.ssh_login_vouched(&request) {
+            true => request.source.clone().or(peer),
+            false => peer,
+        };
+
+        // Logins with keys count towards the same lock out as passwords
+        let ret = match self.login_throttled(email.as_str(), source.as_ref()).await {
+            Some(duration) => {
+                warn!(
+                    "ssh login denied ({}) - throttled for {}s",
+                    email,
+                    duration.as_secs()
+                );
+                Err(SshKeyFailed::AccountLocked(duration))
+            }
+            None => self.clone().process_ssh_login_internal(request).await,
+        };
+        self.audit(
+            email.as_str(),
+            AuditAction::SshLogin,
+            source.clone(),
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+
+        match &ret {
+            Ok(_) => self.login_attempted(email.as_str(), source, true).await,
+            Err(SshKeyFailed::UnknownKey) | Err(SshKeyFailed::NoAccess) => {
+                self.login_attempted(email.as_str(), source, false).await
+            }
+            Err(_) => {}
+        }
+        ret
+    }
+
+    /// Checks that the login request carries a recent proof from an SSH
+    /// server that holds the SSH login key
+    fn ssh_login_vouched(&self, request: &SshLoginRequest) -> bool {
+        let ssh_key = match self.ssh_key.as_ref() {
+            Some(a) => a,
+            None => {
+                return false;
+            }
+        };
+        let proof = ssh_login_proof(
+            ssh_key,
+            request.email.as_str(),
+            request.public_key.as_str(),
+            request.timestamp,
+            request.source.as_deref(),
+        );
+        let age = chrono::Utc::now().timestamp() - request.timestamp;
+        proof == request.proof && age.abs() <= 300
+    }
+
+    async fn process_ssh_login_internal(
+        self: Arc<Self>,
+        request: SshLoginRequest,
+    ) -> Result<LoginResponse, SshKeyFailed> {
+        debug!("ssh login attempt: {}", request.email);
+
+        // Only SSH servers that hold the login key are able to vouch for the
+        // client having proven it holds the private key
+        if self.ssh_key.is_none() {
+            warn!("ssh login denied ({}) - not supported", request.email);
+            return Err(SshKeyFailed::NotSupported);
+        }
+        if self.ssh_login_vouched(&request) == false {
+            warn!("ssh login denied ({}) - bad proof", request.email);
+            return Err(SshKeyFailed::NoAccess);
+        }
+
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(SshKeyFailed::NoMasterKey);
+            }
+        };
+
+        // Find the key amongst the ones the user registered
+        let chain_key = chain_key_4hex(&request.email, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio(&self.master_session).await;
+        let keys_key = PrimaryKey::from(format!("ssh-keys:{}", request.email));
+        let keys = match dio.load::<SshKeys>(&keys_key).await {
+            Ok(a) => a.take(),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                debug!("ssh login denied ({}) - no keys", request.email);
+                return Err(SshKeyFailed::UnknownKey);
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        if keys.keys.iter().any(|a| {
+            a.key_type() == Some(request.key_type)
+                && a.public_key() == Some(request.public_key.as_str())
+        }) == false
+        {
+            debug!("ssh login denied ({}) - unknown key", request.email);
+            return Err(SshKeyFailed::UnknownKey);
+        }
+
+        // Open the account using the token that was stored with the keys
+        let super_key = keys.token.unwrap(&master_key)?;
+        let ret = self
+            .login_with_super_key(request.email, &super_key, keys.token, None)
+            .await;
+        match ret {
+            Ok(a) => Ok(a),
+            Err(LoginFailed::UserNotFound(_)) | Err(LoginFailed::WrongPassword) => {
+                Err(SshKeyFailed::UnknownKey)
+            }
+            Err(LoginFailed::AccountLocked(a)) => Err(SshKeyFailed::AccountLocked(a)),
+            Err(LoginFailed::Unverified(a)) => Err(SshKeyFailed::Unverified(a)),
+            Err(LoginFailed::TokenRevoked(_)) | Err(LoginFailed::TokenExpired(_)) => {
+                Err(SshKeyFailed::UnknownKey)
+            }
+            Err(LoginFailed::NoMasterKey) => Err(SshKeyFailed::NoMasterKey),
+            Err(LoginFailed::InternalError(a)) => Err(SshKeyFailed::InternalError(a)),
+        }
+    }
+
+    /// Loads the SSH keys registered by the identity of the session, when a
+    /// login token is supplied the list is created (or its token refreshed)
+    async fn load_ssh_keys(
+        &self,
+        session: &AteSessionInner,
+        token: Option<EncryptedSecureData<EncryptKey>>,
+    ) -> Result<(Arc<DioMut>, DaoMut<SshKeys>), SshKeyFailed> {
+        let identity = session.identity().to_string();
+
+        // Get the master write key
+        let master_write_key = match self.master_session.user.write_keys().next() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(SshKeyFailed::NoMasterKey);
+            }
+        };
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(SshKeyFailed::NoMasterKey);
+            }
+        };
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(&identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        if self.session_owns_identity(&dio, session).await? == false {
+            warn!("ssh key request denied ({}) - no access", identity);
+            return Err(SshKeyFailed::NoAccess);
+        }
+
+        let keys_key = PrimaryKey::from(format!("ssh-keys:{}", identity));
+        let keys = match (dio.load::<SshKeys>(&keys_key).await, token) {
+            (Ok(mut a), Some(token)) => {
+                a.as_mut().token = token;
+                a
+            }
+            (Ok(a), None) => a,
+            (Err(LoadError(LoadErrorKind::NotFound(_), _)), Some(token)) => {
+                let keys = SshKeys {
+                    email: identity.clone(),
+                    token,
+                    keys: Vec::new(),
+                };
+                let mut keys = dio.store_with_key(keys, keys_key)?;
+                keys.auth_mut().read = ReadOption::from_key(&master_key);
+                keys.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+                keys
+            }
+            (Err(LoadError(LoadErrorKind::NotFound(_), _)), None) => {
+                return Err(SshKeyFailed::NotFound(identity));
+            }
+            (Err(err), _) => {
+                bail!(err);
+            }
+        };
+        Ok((dio, keys))
+    }
+}
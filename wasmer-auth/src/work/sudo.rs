@@ -25,6 +25,31 @@ impl AuthService {
     pub async fn process_sudo(
         self: Arc<Self>,
         request: SudoRequest,
+    ) -> Result<SudoResponse, SudoFailed> {
+        let identity = request.session.identity().to_string();
+
+        // While break-glass is enabled sudo rights are only handed out by
+        // elevations which are approved by an owner of the group
+        let ret = match self.break_glass.clone() {
+            Some(group) => {
+                warn!("sudo attempt denied ({}) - break-glass is enabled", identity);
+                Err(SudoFailed::BreakGlass(group))
+            }
+            None => self.clone().process_sudo_internal(request).await,
+        };
+        self.audit(
+            identity.as_str(),
+            AuditAction::Sudo,
+            None,
+            AuditOutcome::from_result(&ret),
+        )
+        .await;
+        ret
+    }
+
+    pub(crate) async fn process_sudo_internal(
+        self: Arc<Self>,
+        request: SudoRequest,
     ) -> Result<SudoResponse, SudoFailed> {
         info!("sudo attempt: {}", request.session.identity());
 
@@ -0,0 +1,266 @@
+#![allow(unused_imports)]
+use error_chain::bail;
+use std::sync::Arc;
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+
+use ate::error::LoadError;
+use ate::prelude::*;
+use ate::utils::chain_key_4hex;
+
+use crate::error::*;
+use crate::helper::*;
+use crate::model::*;
+use crate::prelude::*;
+use crate::request::*;
+use crate::service::AuthService;
+
+impl AuthService {
+    pub async fn process_token_create(
+        self: Arc<Self>,
+        request: TokenCreateRequest,
+    ) -> Result<TokenCreateResponse, TokenFailed> {
+        let identity = request.session.identity().to_string();
+        info!("token create: {} ({})", identity, request.token.name);
+
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(TokenFailed::NoMasterKey);
+            }
+        };
+
+        let (dio, mut tokens) = self.load_access_tokens(&request.session).await?;
+
+        // The session that the token hands out is sealed so that only this
+        // server can open it (once it has checked the token is still valid),
+        // elevations are only ever issued by the server itself and while
+        // break-glass is enabled they are the only way to hold sudo rights
+        let mut token = request.token;
+        if token.scope.chains.is_empty() == false {
+            warn!(
+                "token create denied ({}) - tokens can not be limited to chains",
+                identity
+            );
+            return Err(TokenFailed::UnsupportedScope);
+        }
+        token.revoked = false;
+        token.elevated = false;
+        let id = token.id.clone();
+        let sealed = match request.grant {
+            Some(grant) => {
+                let grant = match grant {
+                    AteSessionType::User(a) if a.identity == identity => AteSessionType::User(a),
+                    AteSessionType::Sudo(a)
+                        if a.inner.identity == identity && self.break_glass.is_none() =>
+                    {
+                        AteSessionType::Sudo(a)
+                    }
+                    AteSessionType::Group(a) if Some(&a.group.name) == token.scope.group.as_ref() => {
+                        AteSessionType::Group(scope_group_session(a, &token.scope))
+                    }
+                    _ => {
+                        warn!("token create denied ({}) - grant outside of scope", identity);
+                        return Err(TokenFailed::NoAccess);
+                    }
+                };
+                let seal = TokenSeal {
+                    id: id.clone(),
+                    identity: identity.clone(),
+                    session: grant,
+                };
+                Some(EncryptedSecureData::new(&master_key, seal)?)
+            }
+            None => None,
+        };
+        {
+            let mut tokens = tokens.as_mut();
+            tokens.tokens.retain(|a| a.id != id);
+            tokens.tokens.push(token);
+        }
+        dio.commit().await?;
+
+        Ok(TokenCreateResponse { id, sealed })
+    }
+
+    pub async fn process_token_list(
+        self: Arc<Self>,
+        request: TokenListRequest,
+    ) -> Result<TokenListResponse, TokenFailed> {
+        debug!("token list: {}", request.session.identity());
+
+        let (_, tokens) = self.load_access_tokens(&request.session).await?;
+        Ok(TokenListResponse {
+            tokens: tokens.take().tokens,
+        })
+    }
+
+    pub async fn process_token_revoke(
+        self: Arc<Self>,
+        request: TokenRevokeRequest,
+    ) -> Result<TokenRevokeResponse, TokenFailed> {
+        let identity = request.session.identity().to_string();
+        info!("token revoke: {} ({})", identity, request.id);
+
+        let (dio, mut tokens) = self.load_access_tokens(&request.session).await?;
+        {
+            let mut tokens = tokens.as_mut();
+            let token = match tokens.tokens.iter_mut().find(|a| a.id == request.id) {
+                Some(a) => a,
+                None => {
+                    return Err(TokenFailed::NotFound(request.id));
+                }
+            };
+            token.revoked = true;
+        }
+        dio.commit().await?;
+
+        Ok(TokenRevokeResponse { id: request.id })
+    }
+
+    pub async fn process_token_check(
+        self: Arc<Self>,
+        request: TokenCheckRequest,
+    ) -> Result<TokenCheckResponse, TokenFailed> {
+        debug!("token check: {} ({})", request.identity, request.id);
+
+        let (token, _) = self
+            .open_scoped_token(&request.identity, &request.id, &request.sealed)
+            .await?;
+        Ok(TokenCheckResponse { token })
+    }
+
+    /// Opens the session sealed within a scoped token, the seal proves that
+    /// the caller holds the token and the session is only handed back while
+    /// the token has neither been revoked nor expired
+    pub(crate) async fn open_scoped_token(
+        &self,
+        identity: &str,
+        id: &AteHash,
+        sealed: &EncryptedSecureData<TokenSeal>,
+    ) -> Result<(AccessToken, AteSessionType), TokenFailed> {
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(TokenFailed::NoMasterKey);
+            }
+        };
+
+        // Tokens that can not be opened are treated the same as ones that do
+        // not exist so that nothing is given away about them
+        let seal = match sealed.unwrap(&master_key) {
+            Ok(a) if a.id == *id && a.identity == identity => a,
+            _ => {
+                warn!("token check denied ({}) - invalid seal", identity);
+                return Err(TokenFailed::NotFound(id.clone()));
+            }
+        };
+
+        let chain_key = chain_key_4hex(identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio(&self.master_session).await;
+
+        // Tokens that were never recorded are treated the same as ones that
+        // do not exist as the server has no way to revoke them
+        let tokens_key = PrimaryKey::from(format!("tokens:{}", identity));
+        let tokens = match dio.load::<AccessTokens>(&tokens_key).await {
+            Ok(a) => a.take(),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Err(TokenFailed::NotFound(id.clone()));
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        let token = match tokens.tokens.into_iter().find(|a| a.id == *id) {
+            Some(a) => a,
+            None => {
+                return Err(TokenFailed::NotFound(id.clone()));
+            }
+        };
+        if token.revoked {
+            return Err(TokenFailed::Revoked(id.clone()));
+        }
+        if token.is_expired() {
+            return Err(TokenFailed::Expired(id.clone()));
+        }
+
+        Ok((token, seal.session))
+    }
+
+    /// Loads (or creates) the list of tokens minted by the identity of the
+    /// session after making sure the session really belongs to it
+    pub(crate) async fn load_access_tokens(
+        &self,
+        session: &AteSessionInner,
+    ) -> Result<(Arc<DioMut>, DaoMut<AccessTokens>), TokenFailed> {
+        let identity = session.identity().to_string();
+
+        // Get the master write key
+        let master_write_key = match self.master_session.user.write_keys().next() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(TokenFailed::NoMasterKey);
+            }
+        };
+        let master_key = match self.master_key() {
+            Some(a) => a.clone(),
+            None => {
+                return Err(TokenFailed::NoMasterKey);
+            }
+        };
+
+        // Compute which chain the user should exist within
+        let chain_key = chain_key_4hex(&identity, Some("redo"));
+        let chain = self.registry.open(&self.auth_url, &chain_key, true).await?;
+        let dio = chain.dio_full(&self.master_session).await;
+
+        if self.session_owns_identity(&dio, session).await? == false {
+            warn!("token request denied ({}) - no access", identity);
+            return Err(TokenFailed::NoAccess);
+        }
+
+        // Load the tokens or create a new list if this is the first one
+        let tokens_key = PrimaryKey::from(format!("tokens:{}", identity));
+        let tokens = match dio.load::<AccessTokens>(&tokens_key).await {
+            Ok(a) => a,
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                let tokens = AccessTokens {
+                    identity: identity.clone(),
+                    tokens: Vec::new(),
+                };
+                let mut tokens = dio.store_with_key(tokens, tokens_key)?;
+                tokens.auth_mut().read = ReadOption::from_key(&master_key);
+                tokens.auth_mut().write = WriteOption::Specific(master_write_key.hash());
+                tokens
+            }
+            Err(err) => {
+                bail!(err);
+            }
+        };
+        Ok((dio, tokens))
+    }
+
+    /// The session must hold one of the write keys that the identity advertises
+    /// otherwise it belongs to someone else
+    pub(crate) async fn session_owns_identity(
+        &self,
+        dio: &Arc<DioMut>,
+        session: &AteSessionInner,
+    ) -> Result<bool, LoadError> {
+        let identity = session.identity();
+        let advert_key = PrimaryKey::from(format!("advert:{}", identity));
+        let advert = match dio.load::<Advert>(&advert_key).await {
+            Ok(a) => a.take(),
+            Err(LoadError(LoadErrorKind::NotFound(_), _)) => {
+                return Ok(false);
+            }
+            Err(err) => {
+                return Err(err);
+            }
+        };
+        let allowed = [advert.nominal_auth.hash(), advert.sudo_auth.hash()];
+        Ok(session
+            .write_keys(AteSessionKeyCategory::AllKeys)
+            .any(|k| allowed.contains(&k.hash())))
+    }
+}
@@ -19,7 +19,24 @@ pub trait FileSystem {
     async fn remove_file(&self, path: String) -> FsResult<()>;
     async fn read_metadata(&self, path: String) -> FsResult<Metadata>;
     async fn read_symlink_metadata(&self, path: String) -> FsResult<Metadata>;
+    async fn read_link(&self, path: String) -> FsResult<String>;
+    async fn hard_link(&self, from: String, to: String) -> FsResult<Metadata>;
+    async fn read_fs_stats(&self, path: String) -> FsResult<FsStats>;
     async fn open(&self, path: String, options: OpenOptions) -> Arc<dyn OpenedFile>;
+    /// Passes every entry that is created, modified, deleted or renamed at
+    /// (or below when recursive) the path to `event` until the returned
+    /// watcher is dropped or stopped
+    async fn watch(
+        &self,
+        path: String,
+        recursive: bool,
+        event: impl Fn(WatchEvent),
+    ) -> Arc<dyn FileWatcher>;
+}
+
+#[wasmer_bus(format = "json")]
+pub trait FileWatcher {
+    async fn stop(&self);
 }
 
 #[wasmer_bus(format = "json")]
@@ -75,6 +92,17 @@ pub struct Metadata {
     pub len: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FsStats {
+    pub block_size: u64,
+    pub blocks: u64,
+    pub blocks_free: u64,
+    pub blocks_available: u64,
+    pub files: u64,
+    pub files_free: u64,
+    pub name_max: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirEntry {
     pub path: String,
@@ -86,6 +114,22 @@ pub struct Dir {
     pub data: Vec<DirEntry>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WatchKind {
+    Create,
+    Modify,
+    Delete,
+    Rename,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchEvent {
+    pub kind: WatchKind,
+    pub path: String,
+    /// Path the entry had before it was renamed
+    pub old_path: Option<String>,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum FsError {
     BaseNotDirectory,
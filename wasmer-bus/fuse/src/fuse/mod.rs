@@ -2,6 +2,7 @@
 use std::io;
 use std::path::Path;
 use std::sync::Arc;
+use tokio::sync::mpsc;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
 
@@ -10,7 +11,12 @@ use crate::api;
 pub use crate::api::Dir;
 pub use crate::api::FsError;
 pub use crate::api::FsResult;
+pub use crate::api::FsStats;
 pub use crate::api::Metadata;
+pub use crate::api::WatchEvent;
+pub use crate::api::WatchKind;
+
+const MAX_MPSC: usize = std::usize::MAX >> 3;
 
 #[derive(Clone)]
 pub struct FileSystem {
@@ -128,6 +134,45 @@ impl FileSystem {
             })?
     }
 
+    pub async fn read_link(&self, path: &Path) -> FsResult<String> {
+        trace!("read_link: path={}", path.display());
+
+        self.fs
+            .read_link(path.to_string_lossy().to_string())
+            .await
+            .map_err(|err| {
+                debug!("read_link failed - {}", err);
+                FsError::IOError
+            })?
+    }
+
+    pub async fn hard_link(&self, from: &Path, to: &Path) -> FsResult<Metadata> {
+        trace!("hard_link: from={}, to={}", from.display(), to.display());
+
+        self.fs
+            .hard_link(
+                from.to_string_lossy().to_string(),
+                to.to_string_lossy().to_string(),
+            )
+            .await
+            .map_err(|err| {
+                debug!("hard_link failed - {}", err);
+                FsError::IOError
+            })?
+    }
+
+    pub async fn fs_stats(&self, path: &Path) -> FsResult<FsStats> {
+        trace!("fs_stats: path={}", path.display());
+
+        self.fs
+            .read_fs_stats(path.to_string_lossy().to_string())
+            .await
+            .map_err(|err| {
+                debug!("fs_stats failed - {}", err);
+                FsError::IOError
+            })?
+    }
+
     pub async fn remove_file(&self, path: &Path) -> FsResult<()> {
         trace!("remove_file: path={}", path.display());
 
@@ -140,11 +185,52 @@ impl FileSystem {
             })?
     }
 
+    pub async fn watch(&self, path: &Path, recursive: bool) -> FsResult<Watcher> {
+        trace!("watch: path={}, recursive={}", path.display(), recursive);
+
+        let (tx, rx) = mpsc::channel(MAX_MPSC);
+        let watcher = self
+            .fs
+            .watch(
+                path.to_string_lossy().to_string(),
+                recursive,
+                Box::new(move |evt: WatchEvent| {
+                    wasmer_bus::task::send(&tx, evt);
+                }),
+            )
+            .await
+            .map_err(|err| {
+                debug!("watch failed - {}", err);
+                FsError::IOError
+            })?;
+        Ok(Watcher { watcher, rx })
+    }
+
     pub fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(self.clone())
     }
 }
 
+/// Changes made to a watched path (the watch ends when this is dropped)
+pub struct Watcher {
+    watcher: Arc<dyn api::FileWatcher>,
+    rx: mpsc::Receiver<WatchEvent>,
+}
+
+impl Watcher {
+    /// Waits for the next change (returns None once the watch has ended)
+    pub async fn recv(&mut self) -> Option<WatchEvent> {
+        self.rx.recv().await
+    }
+
+    pub async fn stop(&self) -> FsResult<()> {
+        self.watcher.stop().await.map_err(|err| {
+            debug!("stop failed - {}", err);
+            FsError::IOError
+        })
+    }
+}
+
 pub struct OpenOptionsConfig {
     read: bool,
     write: bool,
@@ -2,10 +2,14 @@ pub use crate::fuse::Dir;
 pub use crate::fuse::FileSystem;
 pub use crate::fuse::FsError;
 pub use crate::fuse::FsResult;
+pub use crate::fuse::FsStats;
 pub use crate::fuse::Metadata;
 pub use crate::fuse::OpenOptions;
 pub use crate::fuse::OpenOptionsConfig;
 pub use crate::fuse::VirtualFile;
+pub use crate::fuse::WatchEvent;
+pub use crate::fuse::WatchKind;
+pub use crate::fuse::Watcher;
 pub use async_trait::async_trait;
 pub use wasmer_bus;
 pub use wasmer_bus::abi::BusError;
@@ -12,6 +12,8 @@ use std::task::{
     Context,
     Poll
 };
+use std::time::Duration;
+use std::time::Instant;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
 
@@ -38,6 +40,12 @@ where
     fn error(&self, error: BusError);
 
     fn topic_hash(&self) -> u128;
+
+    /// Time by which the call must complete (sub-calls made on its
+    /// handle inherit it)
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -127,6 +135,7 @@ pub struct Call {
     #[derivative(Debug = "ignore")]
     pub(crate) callbacks: HashMap<u128, Arc<dyn Fn(Vec<u8>, SerializationFormat) -> CallbackResult + Send + Sync + 'static>>,
     pub(crate) handle: Option<CallHandle>,
+    pub(crate) deadline: Option<Instant>,
     pub(crate) state: Arc<Mutex<CallState>>,
 }
 
@@ -143,6 +152,7 @@ impl Call {
             })),
             callbacks: Default::default(),
             handle: None,
+            deadline: None,
             topic_hash,
         }
     }
@@ -158,6 +168,8 @@ impl Call {
             })),
             callbacks: Default::default(),
             handle: None,
+            // Sub-calls may not outlive the deadline of their parent
+            deadline: crate::engine::BusEngine::inherited_deadline(&parent),
             topic_hash,
         }
     }
@@ -165,6 +177,34 @@ impl Call {
     pub fn handle(&self) -> Option<CallHandle> {
         self.handle.clone()
     }
+
+    /// Returns the time by which this call must complete
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.clone()
+    }
+
+    /// Cancels the call - the callee is notified that the call was
+    /// aborted and anyone waiting on it will receive an error
+    pub fn cancel(&self) {
+        self.abort(BusError::Aborted, "call was cancelled");
+    }
+
+    fn abort(&self, err: BusError, reason: &'static str) {
+        if let Some(handle) = self.handle.clone() {
+            crate::engine::BusEngine::error(handle, err);
+            crate::abi::syscall::call_close(handle);
+            crate::engine::BusEngine::close(&handle, reason);
+        } else {
+            self.error(err);
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false
+        }
+    }
 }
 
 impl CallOps for Call {
@@ -200,6 +240,10 @@ impl CallOps for Call {
     fn topic_hash(&self) -> u128 {
         self.topic_hash
     }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline.clone()
+    }
 }
 
 #[derive(Debug)]
@@ -236,11 +280,35 @@ impl CallBuilder {
         self
     }
 
+    /// The call will fail with a timeout error if it does not complete
+    /// within this duration
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// The call will fail with a timeout error if it does not complete
+    /// before this deadline (a deadline inherited from the parent call
+    /// can only be shortened)
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        let call = self.call.as_mut().unwrap();
+        call.deadline = match call.deadline {
+            Some(existing) if existing < deadline => Some(existing),
+            _ => Some(deadline)
+        };
+        self
+    }
+
     // Invokes the call and detaches it so that it can be
     // using a contextual session
+    //
+    // Sessions live until they are closed hence detached calls
+    // never have a deadline
     pub fn detach(mut self) -> Result<CallHandle, BusError>
     {
         let mut call = self.call.take().unwrap();
+        if call.deadline.take().is_some() {
+            debug!("the deadline of a detached call is ignored");
+        }
         let handle = self.invoke_internal(&mut call)?;
         Ok(handle)
     }
@@ -260,6 +328,10 @@ impl CallBuilder {
     }
 
     fn invoke_internal(&self, call: &mut Call) -> Result<CallHandle, BusError> {
+        if call.is_expired() {
+            return Err(BusError::Timeout);
+        }
+
         let handle = match &self.request {
             Data::Prepared(req) => {
                 match &call.ctx {
@@ -316,11 +388,35 @@ impl CallBuilder {
             state.handles.insert(scope.clone());
             state.calls.insert(scope.clone(), Arc::new(call.clone()));
             call.handle.replace(scope.clone());
+            drop(state);
+
+            if let Some(deadline) = call.deadline.clone() {
+                send_deadline(scope.clone(), deadline);
+            }
         }
         handle
     }
 }
 
+/// Tells the callee how long it has to process a call, the deadline is
+/// sent as the remaining time as the two modules do not share a clock.
+/// Callees that do not understand the deadline reject it which is fine
+/// as the caller still enforces the deadline itself
+fn send_deadline(handle: CallHandle, deadline: Instant) {
+    let remaining_ms = deadline
+        .saturating_duration_since(Instant::now())
+        .as_millis() as u64;
+    let topic_hash = crate::engine::hash_topic(&crate::engine::CALL_DEADLINE_TOPIC.into());
+    if let Err(err) = crate::abi::syscall::bus_subcall(
+        handle,
+        topic_hash,
+        &remaining_ms.to_le_bytes()[..],
+        SerializationFormat::Raw,
+    ) {
+        debug!("failed to send the call deadline (handle={}) - {}", handle.id, err);
+    }
+}
+
 impl Call {
     /// Upon receiving a particular message from the service that is
     /// invoked this callback will take some action
@@ -364,7 +460,8 @@ impl Call {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 #[must_use = "this `Call` only does something when you consume it"]
 pub struct CallJoin<T>
 where
@@ -372,7 +469,20 @@ where
 {
     call: Call,
     scope: CallHandle,
-    _marker1: PhantomData<T>,
+    #[cfg(feature = "rt")]
+    #[derivative(Debug = "ignore")]
+    timer: Option<Pin<Box<tokio::time::Sleep>>>,
+    _marker1: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone
+for CallJoin<T>
+where
+    T: de::DeserializeOwned,
+{
+    fn clone(&self) -> Self {
+        CallJoin::new(self.call.clone(), self.scope.clone())
+    }
 }
 
 impl<T> CallJoin<T>
@@ -383,10 +493,27 @@ where
         CallJoin {
             call,
             scope,
+            #[cfg(feature = "rt")]
+            timer: None,
             _marker1: PhantomData,
         }
     }
 
+    /// Cancels the call so that the callee stops processing it
+    pub fn cancel(&self) {
+        self.call.cancel();
+    }
+
+    /// Returns true (and aborts the call) if the deadline has passed
+    fn check_deadline(&self) -> bool {
+        if self.call.is_expired() {
+            debug!("call has timed out (handle={})", self.scope.id);
+            self.call.abort(BusError::Timeout, "call has timed out");
+            return true;
+        }
+        false
+    }
+
     /// Waits for the call to complete and returns the response from
     /// the server    
     #[cfg(feature = "rt")]
@@ -434,6 +561,7 @@ where
                 }
             }
             Some(Err(err)) => Err(err),
+            None if self.check_deadline() => Err(BusError::Timeout),
             None => Ok(None),
         }
     }
@@ -445,7 +573,7 @@ where
 {
     type Output = Result<T, BusError>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let response = {
             let mut state = self.call.state.lock().unwrap();
             state.result.take()
@@ -461,9 +589,98 @@ where
             }
             Some(Err(err)) => Poll::Ready(Err(err)),
             None => {
+                if self.check_deadline() {
+                    return Poll::Ready(Err(BusError::Timeout));
+                }
+
+                // Make sure we are woken up when the deadline passes
+                #[cfg(feature = "rt")]
+                if let Some(deadline) = self.call.deadline.clone() {
+                    let timer = self.timer.get_or_insert_with(|| {
+                        Box::pin(tokio::time::sleep_until(deadline.into()))
+                    });
+                    if timer.as_mut().poll(cx).is_ready() {
+                        self.call.abort(BusError::Timeout, "call has timed out");
+                        return Poll::Ready(Err(BusError::Timeout));
+                    }
+                }
+
                 crate::engine::BusEngine::subscribe(&self.scope, cx);
                 Poll::Pending
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::BusEngine;
+
+    fn builder(call: Call) -> CallBuilder {
+        CallBuilder::new(call, Data::Prepared(Vec::new()), SerializationFormat::Raw)
+    }
+
+    #[test]
+    fn test_subcalls_inherit_the_inbound_deadline() {
+        let parent = CallHandle::from(1001u64);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        BusEngine::write().deadlines.insert(parent, deadline);
+
+        let call = Call::new_subcall(parent, 0);
+        assert_eq!(call.deadline(), Some(deadline));
+
+        // A later deadline can not extend it but an earlier one shortens it
+        let later = builder(call.clone()).timeout(Duration::from_secs(60));
+        assert_eq!(later.call.as_ref().unwrap().deadline(), Some(deadline));
+        let earlier_deadline = Instant::now() + Duration::from_secs(1);
+        let earlier = builder(call).deadline(earlier_deadline);
+        assert_eq!(earlier.call.as_ref().unwrap().deadline(), Some(earlier_deadline));
+
+        BusEngine::write().deadlines.remove(&parent);
+    }
+
+    #[test]
+    fn test_subcalls_inherit_the_outbound_deadline() {
+        let parent = CallHandle::from(1002u64);
+        let inbound = Instant::now() + Duration::from_secs(20);
+        let outbound = Instant::now() + Duration::from_secs(5);
+        let mut call = Call::new_call("test".into(), 0, None);
+        call.deadline = Some(outbound);
+        {
+            let mut state = BusEngine::write();
+            state.calls.insert(parent, Arc::new(call));
+            state.deadlines.insert(parent, inbound);
+        }
+
+        // The earlier of the two deadlines wins
+        assert_eq!(Call::new_subcall(parent, 0).deadline(), Some(outbound));
+
+        {
+            let mut state = BusEngine::write();
+            state.calls.remove(&parent);
+            state.deadlines.remove(&parent);
+        }
+        assert_eq!(Call::new_subcall(parent, 0).deadline(), None);
+    }
+
+    #[test]
+    fn test_expired_calls_time_out() {
+        let call = builder(Call::new_call("test".into(), 0, None))
+            .deadline(Instant::now() - Duration::from_millis(1))
+            .invoke();
+        assert!(call.is_expired());
+        let state = call.state.lock().unwrap();
+        assert!(matches!(state.result, Some(Err(BusError::Timeout))));
+    }
+
+    #[test]
+    fn test_calls_without_a_deadline_never_expire() {
+        let call = Call::new_call("test".into(), 0, None);
+        assert_eq!(call.deadline(), None);
+        assert_eq!(call.is_expired(), false);
+
+        let call = builder(call).timeout(Duration::from_secs(60)).call.unwrap();
+        assert_eq!(call.is_expired(), false);
+    }
+}
@@ -1,6 +1,8 @@
 use derivative::*;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::watch;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
 
@@ -41,3 +43,48 @@ impl Finish {
         self.handle.id
     }
 }
+
+/// Notifies a service that a call it is processing was cancelled by
+/// the caller (explicitly or because the deadline of the call passed)
+#[derive(Debug, Clone)]
+pub struct CallCancellation {
+    pub(crate) handle: CallHandle,
+    pub(crate) rx: watch::Receiver<bool>,
+}
+
+impl CallCancellation {
+    pub fn id(&self) -> u64 {
+        self.handle.id
+    }
+
+    /// Returns the time by which the caller expects the call to complete,
+    /// sub-calls made while processing it can use this as their deadline
+    pub fn deadline(&self) -> Option<Instant> {
+        crate::engine::BusEngine::deadline(&self.handle)
+    }
+
+    /// Returns true if the caller has cancelled the call
+    pub fn is_cancelled(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Waits for the caller to cancel the call, if the call finishes
+    /// normally then this will never return
+    pub async fn wait(&mut self) {
+        while *self.rx.borrow() == false {
+            if self.rx.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Returns an object that will be notified if the caller cancels an
+/// inbound call that this module is still processing
+pub fn cancellation(handle: CallHandle) -> Option<CallCancellation> {
+    crate::engine::BusEngine::cancellation(&handle)
+        .map(|rx| CallCancellation {
+            handle,
+            rx,
+        })
+}
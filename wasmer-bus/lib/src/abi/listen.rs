@@ -61,8 +61,23 @@ impl ListenService {
         let callback = Arc::clone(&self.callback);
         let res = callback.as_ref()(handle, request);
 
+        // If the caller cancels the call then we stop processing it
+        let res = match crate::abi::cancellation(handle) {
+            Some(mut cancel) => {
+                tokio::select! {
+                    res = res => res,
+                    _ = cancel.wait() => {
+                        trace!("request was cancelled by the caller (handle={})", handle.id);
+                        return;
+                    }
+                }
+            }
+            None => res.await
+        };
+        crate::engine::BusEngine::finished(&handle);
+
         let mut leak = false;
-        match res.await {
+        match res {
             ListenAction::Response(a) => {
                 crate::abi::syscall::call_reply(handle, &a[..], format);
             }
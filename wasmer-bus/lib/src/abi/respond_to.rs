@@ -82,7 +82,23 @@ impl RespondToService {
 
         let mut leak = false;
         let res = callback.as_ref()(handle, request);
-        match res.await {
+
+        // If the caller cancels the call then we stop processing it
+        let res = match crate::abi::cancellation(handle) {
+            Some(mut cancel) => {
+                tokio::select! {
+                    res = res => res,
+                    _ = cancel.wait() => {
+                        trace!("request was cancelled by the caller (handle={})", handle.id);
+                        return;
+                    }
+                }
+            }
+            None => res.await
+        };
+        crate::engine::BusEngine::finished(&handle);
+
+        match res {
             RespondAction::Response(a) => {
                 crate::abi::syscall::call_reply(handle, &a[..], format);
             }
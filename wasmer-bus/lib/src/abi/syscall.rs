@@ -28,6 +28,10 @@ fn convert_format_back(a: SerializationFormat) -> wasi::BusDataFormat {
     }
 }
 
+/// WASIX has no error code for deadlines so the bus uses the next free
+/// code, which keeps timeouts distinct from calls that were aborted
+const BUS_ERROR_TIMEOUT: wasi::BusError = BusError::Timeout as wasi::BusError;
+
 fn convert_err(val: wasi::BusError) -> BusError {
     use BusError::*;
     match val {
@@ -50,6 +54,7 @@ fn convert_err(val: wasi::BusError) -> BusError {
         wasi::BUS_ERROR_BUS_INVOCATION_FAILED => BusInvocationFailed,
         wasi::BUS_ERROR_ALREADY_CONSUMED => AlreadyConsumed,
         wasi::BUS_ERROR_MEMORY_ACCESS_VIOLATION => MemoryAccessViolation,
+        BUS_ERROR_TIMEOUT => Timeout,
         wasi::BUS_ERROR_UNKNOWN_ERROR | _ => Unknown,
     }
 }
@@ -76,6 +81,7 @@ fn convert_err_back(val: BusError) -> wasi::BusError {
         BusInvocationFailed => wasi::BUS_ERROR_BUS_INVOCATION_FAILED,
         AlreadyConsumed => wasi::BUS_ERROR_ALREADY_CONSUMED,
         MemoryAccessViolation => wasi::BUS_ERROR_MEMORY_ACCESS_VIOLATION,
+        Timeout => BUS_ERROR_TIMEOUT,
         Unknown => wasi::BUS_ERROR_UNKNOWN_ERROR
     }
 }
@@ -11,9 +11,12 @@ use std::sync::RwLockReadGuard;
 use std::sync::RwLockWriteGuard;
 use std::sync::{Arc, MutexGuard};
 use std::task::{Context, Waker};
+use std::time::Duration;
+use std::time::Instant;
 use std::{collections::HashMap, collections::HashSet, sync::Mutex};
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
+use tokio::sync::watch;
 
 use crate::abi::*;
 
@@ -24,6 +27,8 @@ pub struct BusEngineState {
     pub handles: HashSet<CallHandle>,
     pub calls: HashMap<CallHandle, Arc<dyn CallOps>>,
     pub children: HashMap<CallHandle, Vec<CallHandle>>,
+    pub deadlines: HashMap<CallHandle, Instant>,
+    pub cancellations: HashMap<CallHandle, watch::Sender<bool>>,
     pub listening: HashMap<u128, ListenService>,
     pub respond_to: HashMap<u128, RespondToService>,
     pub initialized_reactors: bool,
@@ -35,6 +40,10 @@ pub struct BusEngine {
     wakers: Mutex<HashMap<CallHandle, Waker>>,
 }
 
+/// Topic of the sub-call that a caller sends to tell the callee how long
+/// it has to process a call (the payload is the remaining milliseconds)
+pub(crate) const CALL_DEADLINE_TOPIC: &'static str = "wasmer_bus::call_deadline";
+
 // Function that hashes the topic using SHA256
 pub(crate) fn hash_topic(topic: &Cow<'static, str>) -> u128 {
     use sha2::{Sha256, Digest};
@@ -84,6 +93,14 @@ impl BusEngine {
     ) -> Result<(), BusError> {
         let state = BusEngine::read();
         if let Some(parent) = parent {
+            // The caller is telling us the deadline of a call we are processing
+            if topic_hash == hash_topic(&CALL_DEADLINE_TOPIC.into()) {
+                drop(state);
+                BusEngine::set_deadline(parent, request);
+                syscall::call_close(handle);
+                return Ok(());
+            }
+
             if let Some(parent) = state.calls.get(&parent) {
                 // If the callback is registered then process it and finish the call
                 if parent.callback(topic_hash, request, format) != CallbackResult::InvalidTopic {
@@ -102,6 +119,7 @@ impl BusEngine {
                 let mut state = BusEngine::write();
                 if state.handles.contains(&handle) == false {
                     state.handles.insert(handle);
+                    state.cancellations.insert(handle, watch::channel(false).0);
                     drop(state);
 
                     crate::task::spawn(async move {
@@ -121,6 +139,7 @@ impl BusEngine {
             let mut state = BusEngine::write();
             if state.handles.contains(&handle) == false {
                 state.handles.insert(handle);
+                state.cancellations.insert(handle, watch::channel(false).0);
                 drop(state);
 
                 crate::task::spawn(async move {
@@ -236,6 +255,78 @@ impl BusEngine {
         wakers.insert(handle.clone(), waker);
     }
 
+    /// Returns the deadline that the caller gave an inbound call that
+    /// is still being processed (if it has one)
+    pub fn deadline(handle: &CallHandle) -> Option<Instant> {
+        let state = BusEngine::read();
+        state.deadlines.get(handle).map(|a| a.clone())
+    }
+
+    /// Returns the deadline that a sub-call made on this handle inherits,
+    /// which is the earlier of the deadline the caller gave us (when the
+    /// handle is an inbound call) and that of the outbound call itself
+    pub fn inherited_deadline(handle: &CallHandle) -> Option<Instant> {
+        let state = BusEngine::read();
+        let inbound = state.deadlines.get(handle).map(|a| a.clone());
+        let outbound = state.calls.get(handle).and_then(|a| a.deadline());
+        match (inbound, outbound) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, None) => a,
+            (None, b) => b,
+        }
+    }
+
+    /// Records the deadline of an inbound call, once it passes the call
+    /// is cancelled just as if the caller had closed it
+    #[cfg(feature = "rt")]
+    fn set_deadline(handle: CallHandle, request: Vec<u8>) {
+        let remaining_ms = match request[..].try_into() {
+            Ok(a) => u64::from_le_bytes(a),
+            Err(_) => {
+                debug!("call deadline is malformed (handle={}, len={})", handle.id, request.len());
+                return;
+            }
+        };
+        let deadline = Instant::now() + Duration::from_millis(remaining_ms);
+        {
+            let mut state = BusEngine::write();
+            if state.cancellations.contains_key(&handle) == false {
+                return;
+            }
+            state.deadlines.insert(handle, deadline);
+        }
+
+        crate::task::spawn(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            let mut state = BusEngine::write();
+            if state.deadlines.get(&handle) == Some(&deadline) {
+                state.deadlines.remove(&handle);
+                if let Some(cancel) = state.cancellations.remove(&handle) {
+                    trace!(
+                        "wasmer_bus_cancel (handle={}, reason='deadline has passed')",
+                        handle.id
+                    );
+                    let _ = cancel.send(true);
+                }
+            }
+        });
+    }
+
+    /// Returns a receiver that is notified if an inbound call that is
+    /// still being processed gets cancelled by the caller
+    pub fn cancellation(handle: &CallHandle) -> Option<watch::Receiver<bool>> {
+        let state = BusEngine::read();
+        state.cancellations.get(handle).map(|tx| tx.subscribe())
+    }
+
+    /// Marks an inbound call as finished so that closing it later will
+    /// not be reported as a cancellation
+    pub fn finished(handle: &CallHandle) {
+        let mut state = BusEngine::write();
+        state.cancellations.remove(handle);
+        state.deadlines.remove(handle);
+    }
+
     pub fn add_callback(handle: CallHandle, child: CallHandle) {
         let mut state = BusEngine::write();
         let children = state.children
@@ -253,9 +344,18 @@ impl BusEngine {
             {
                 let mut state = BusEngine::write();
                 state.handles.remove(handle);
+                state.deadlines.remove(handle);
                 if let Some(mut c) = state.children.remove(handle) {
                     children.append(&mut c);
                 }
+                if let Some(cancel) = state.cancellations.remove(handle) {
+                    trace!(
+                        "wasmer_bus_cancel (handle={}, reason='{}')",
+                        handle.id,
+                        reason
+                    );
+                    let _ = cancel.send(true);
+                }
                 if let Some(drop_me) = state.calls.remove(handle) {
                     trace!(
                         "wasmer_bus_drop (handle={}, reason='{}')",
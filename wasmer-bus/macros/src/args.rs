@@ -1,5 +1,6 @@
 use proc_macro2::Span;
 use std::str::FromStr;
+use std::time::Duration;
 use syn::parse::{Parse, ParseStream, Result};
 use syn::{LitStr, Token};
 use wasmer_bus_types::SerializationFormat;
@@ -10,13 +11,21 @@ pub struct ArgsFormat {
     pub format_val: SerializationFormat,
 }
 
+pub struct ArgsTimeout {
+    pub timeout_token: kw::timeout,
+    pub eq_token: Token![=],
+    pub timeout_val: Duration,
+}
+
 #[derive(Default)]
 pub struct Args {
     pub format: Option<ArgsFormat>,
+    pub timeout: Option<ArgsTimeout>,
 }
 
 mod kw {
     syn::custom_keyword!(format);
+    syn::custom_keyword!(timeout);
 }
 
 impl Parse for Args {
@@ -28,17 +37,77 @@ impl Parse for Args {
 pub(crate) fn try_parse(input: ParseStream) -> Result<Args> {
     let mut args = Args::default();
 
-    let lookahead = input.lookahead1();
-    if lookahead.peek(kw::format) {
-        args.format = Some(ArgsFormat {
-            format_token: input.parse::<kw::format>()?,
-            eq_token: input.parse()?,
-            format_val: SerializationFormat::from_str(input.parse::<LitStr>()?.value().as_str())
-                .map_err(|e| syn::Error::new(Span::call_site(), e))?,
-        });
-    } else {
-        return Err(lookahead.error());
+    loop {
+        let lookahead = input.lookahead1();
+        if lookahead.peek(kw::format) && args.format.is_none() {
+            args.format = Some(ArgsFormat {
+                format_token: input.parse::<kw::format>()?,
+                eq_token: input.parse()?,
+                format_val: SerializationFormat::from_str(input.parse::<LitStr>()?.value().as_str())
+                    .map_err(|e| syn::Error::new(Span::call_site(), e))?,
+            });
+        } else if lookahead.peek(kw::timeout) && args.timeout.is_none() {
+            let timeout_token = input.parse::<kw::timeout>()?;
+            let eq_token = input.parse()?;
+            let timeout_lit = input.parse::<LitStr>()?;
+            args.timeout = Some(ArgsTimeout {
+                timeout_token,
+                eq_token,
+                timeout_val: parse_duration(timeout_lit.value().as_str())
+                    .map_err(|e| syn::Error::new(timeout_lit.span(), e))?,
+            });
+        } else {
+            return Err(lookahead.error());
+        }
+
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<Token![,]>()?;
     }
 
     Ok(args)
 }
+
+/// Parses a duration such as "500ms", "5s", "2m" or "1h"
+fn parse_duration(val: &str) -> std::result::Result<Duration, String> {
+    let val = val.trim();
+    let split = val
+        .find(|c: char| c.is_ascii_digit() == false)
+        .unwrap_or(val.len());
+    let (amount, unit) = val.split_at(split);
+    let amount = u64::from_str(amount)
+        .map_err(|_| format!("the timeout '{}' must start with a number", val))?;
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 3600)),
+        unit => Err(format!("the timeout unit '{}' is not supported (use ms, s, m or h)", unit)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration(" 10 s "), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_duration("0ms"), Ok(Duration::from_millis(0)));
+    }
+
+    #[test]
+    fn test_parse_duration_errors() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("-5s").is_err());
+        assert!(parse_duration("1.5s").is_err());
+    }
+}
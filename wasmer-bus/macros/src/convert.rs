@@ -20,6 +20,16 @@ pub fn convert(args: Args, input: Item) -> proc_macro::TokenStream {
         .map(|a| a.format_val)
         .unwrap_or(SerializationFormat::Json);
 
+    // Calls that return a result will fail if they do not complete within the timeout
+    let timeout = args
+        .timeout
+        .map(|a| {
+            let timeout_ms = a.timeout_val.as_millis() as u64;
+            quote! {
+                .timeout(std::time::Duration::from_millis(#timeout_ms))
+            }
+        });
+
     // Get the span
     let span = match input.clone() {
         Item::Trait(input) => input.ident.span(),
@@ -412,6 +422,7 @@ pub fn convert(args: Args, input: Item) -> proc_macro::TokenStream {
                                         request
                                     )
                                     #( #method_callbacks )*
+                                    #timeout
                                     .invoke()
                                     .join()?
                                     .await
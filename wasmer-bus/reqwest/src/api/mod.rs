@@ -1,11 +1,19 @@
 use serde::*;
+use std::sync::Arc;
 use wasmer_bus::macros::*;
 
+/// Error code returned when a request runs past its timeout
+pub const ERR_TIMED_OUT: i32 = 110;
+/// Error code returned when a request is cancelled or aborted
+pub const ERR_CONNECTION_ABORTED: i32 = 103;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ReqwestOptions
 {
     pub gzip: bool,
     pub cors_proxy: Option<String>,
+    /// Number of milliseconds the whole request may take before it is aborted
+    pub timeout_ms: Option<u64>,
 }
 
 #[wasmer_bus(format = "bincode")]
@@ -18,6 +26,29 @@ pub trait Reqwest {
         headers: Vec<(String, String)>,
         body: Option<Vec<u8>>,
     ) -> Result<Response, i32>;
+
+    /// Makes a request where the response body is streamed back one chunk at
+    /// a time - if `upload` is set then the request body is streamed up using
+    /// the returned session (after any initial `body`) until it is closed
+    async fn stream(
+        &self,
+        url: String,
+        method: String,
+        options: ReqwestOptions,
+        headers: Vec<(String, String)>,
+        body: Option<Vec<u8>>,
+        upload: bool,
+        head: impl Fn(Response),
+        receive: impl Fn(Vec<u8>),
+        finish: impl Fn(Result<(), i32>),
+    ) -> Arc<dyn ReqwestStream>;
+}
+
+#[wasmer_bus(format = "bincode")]
+pub trait ReqwestStream {
+    async fn write(&self, data: Vec<u8>) -> Result<usize, i32>;
+    async fn close(&self);
+    async fn cancel(&self);
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -7,6 +7,7 @@ pub use crate::reqwest::ClientBuilder;
 pub use crate::reqwest::Form;
 pub use crate::reqwest::Mime;
 pub use crate::reqwest::RequestBuilder;
+pub use crate::reqwest::ResponseStream;
 pub use wasmer_bus;
 pub use wasmer_bus::abi::BusError;
 pub use async_trait::async_trait;
@@ -52,6 +52,7 @@ impl Client {
         ReqwestOptions {
             gzip: self.builder.gzip,
             cors_proxy: self.builder.cors_proxy.clone(),
+            timeout_ms: self.builder.timeout.map(|a| a.as_millis() as u64),
         }
     }
 }
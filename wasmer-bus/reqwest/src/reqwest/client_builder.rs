@@ -1,10 +1,13 @@
 #![allow(dead_code)]
+use std::time::Duration;
+
 use super::*;
 
 #[derive(Debug, Default)]
 pub struct ClientBuilder {
     pub(super) gzip: bool,
     pub(super) cors_proxy: Option<String>,
+    pub(super) timeout: Option<Duration>,
 }
 
 impl ClientBuilder {
@@ -22,6 +25,12 @@ impl ClientBuilder {
         self
     }
 
+    /// Maximum amount of time a request may take before it is aborted
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     pub fn build(self) -> Result<Client, Error> {
         Ok(Client { builder: self })
     }
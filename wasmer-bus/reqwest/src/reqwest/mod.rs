@@ -8,6 +8,7 @@ mod mime;
 mod multipart;
 mod request_builder;
 mod response;
+mod stream;
 
 pub(crate) use body::*;
 pub(crate) use client::*;
@@ -27,5 +28,6 @@ pub use mime::Mime;
 pub use multipart::Form;
 pub use request_builder::RequestBuilder;
 pub use response::*;
+pub use stream::ResponseStream;
 
 pub const WAPM_NAME: &'static str = "os";
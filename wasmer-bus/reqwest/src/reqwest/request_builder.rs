@@ -3,6 +3,8 @@ use http::StatusCode;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::Write;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
 use super::*;
 use crate::api::ReqwestClient;
@@ -45,6 +47,74 @@ impl RequestBuilder {
         self.header(header::AUTHORIZATION, header_value)
     }
 
+    /// Overrides the timeout of the client for this particular request
+    pub fn timeout(mut self, timeout: Duration) -> RequestBuilder {
+        self.client.builder.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends the request and streams the response body back through the
+    /// `receive` callback one chunk at a time
+    pub fn stream<F>(self, receive: F) -> Result<ResponseStream, std::io::Error>
+    where
+        F: Fn(Vec<u8>) + Send + Sync + 'static,
+    {
+        self.open_stream(false, receive)
+    }
+
+    /// Same as `stream` except the request body is also streamed, any body
+    /// already attached is sent first and the rest is written to the returned
+    /// stream which must be closed to complete the request
+    pub fn stream_upload<F>(self, receive: F) -> Result<ResponseStream, std::io::Error>
+    where
+        F: Fn(Vec<u8>) + Send + Sync + 'static,
+    {
+        self.open_stream(true, receive)
+    }
+
+    fn open_stream<F>(self, upload: bool, receive: F) -> Result<ResponseStream, std::io::Error>
+    where
+        F: Fn(Vec<u8>) + Send + Sync + 'static,
+    {
+        let url = self.url.to_string();
+        let options = self.client.options();
+
+        let (tx_head, rx_head) = mpsc::channel(1);
+        let (tx_finish, rx_finish) = mpsc::channel(1);
+
+        let session = ReqwestClient::new(WAPM_NAME)
+            .blocking_stream(
+                url,
+                self.method.to_string(),
+                options,
+                self.headers
+                    .iter()
+                    .map(|(a, b)| (a.clone(), b.clone()))
+                    .collect(),
+                self.request
+                    .iter()
+                    .filter_map(|a| a.as_bytes())
+                    .map(|a| a.to_vec())
+                    .next(),
+                upload,
+                Box::new(move |head| {
+                    let _ = tx_head.try_send(head);
+                }),
+                Box::new(receive),
+                Box::new(move |finish| {
+                    let _ = tx_finish.try_send(finish);
+                }),
+            )
+            .map_err(|err| err.into_io_error())?;
+
+        Ok(ResponseStream {
+            session,
+            rx_head,
+            rx_finish,
+            finished: None,
+        })
+    }
+
     pub fn send(self) -> Result<Response, std::io::Error> {
         let url = self.url.to_string();
         let options = self.client.options();
@@ -0,0 +1,199 @@
+#![allow(dead_code)]
+use std::io::Error;
+use std::io::ErrorKind;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::api::ReqwestStream;
+use crate::api::Response;
+
+/// Request whose response body is delivered one chunk at a time and whose
+/// request body (when uploading) is written in pieces
+pub struct ResponseStream {
+    pub(crate) session: Arc<dyn ReqwestStream>,
+    pub(crate) rx_head: mpsc::Receiver<Response>,
+    pub(crate) rx_finish: mpsc::Receiver<Result<(), i32>>,
+    pub(crate) finished: Option<Result<(), i32>>,
+}
+
+impl ResponseStream {
+    /// Waits for the status and headers of the response to arrive
+    pub fn head(&mut self) -> Result<Response, Error> {
+        if let Some(Err(err)) = self.finished {
+            return Err(conv_error(err));
+        }
+        let rx_head = &mut self.rx_head;
+        let rx_finish = &mut self.rx_finish;
+        let ret = wasmer_bus::task::block_on(async move {
+            // The head is always sent before the finish signal thus when both
+            // are ready the head must win or it would be lost
+            tokio::select! {
+                biased;
+                head = rx_head.recv() => Ok(head),
+                finish = rx_finish.recv() => Err(finish),
+            }
+        });
+        match ret {
+            Ok(Some(head)) => Ok(head),
+            Ok(None) => Err(Error::new(
+                ErrorKind::ConnectionAborted,
+                "the request was aborted before a response was received",
+            )),
+            Err(finish) => {
+                let finish = finish.unwrap_or(Err(crate::api::ERR_CONNECTION_ABORTED));
+                self.finished = Some(finish);
+                match finish {
+                    Ok(()) => Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "the request finished without a response",
+                    )),
+                    Err(err) => Err(conv_error(err)),
+                }
+            }
+        }
+    }
+
+    /// Writes more data to the request body (only when uploading)
+    pub fn write(&self, data: &[u8]) -> Result<usize, Error> {
+        self.session
+            .blocking_write(data.to_vec())
+            .map_err(|err| err.into_io_error())?
+            .map_err(conv_error)
+    }
+
+    /// Marks the end of the request body
+    pub fn close(&self) -> Result<(), Error> {
+        self.session
+            .blocking_close()
+            .map_err(|err| err.into_io_error())
+    }
+
+    /// Aborts the request
+    pub fn cancel(&self) -> Result<(), Error> {
+        self.session
+            .blocking_cancel()
+            .map_err(|err| err.into_io_error())
+    }
+
+    /// Waits for the whole response body to be received
+    pub fn wait(mut self) -> Result<(), Error> {
+        let finish = match self.finished.take() {
+            Some(a) => Some(a),
+            None => wasmer_bus::task::block_on(self.rx_finish.recv()),
+        };
+        match finish {
+            Some(Ok(())) => Ok(()),
+            Some(Err(err)) => Err(conv_error(err)),
+            None => Err(Error::new(
+                ErrorKind::ConnectionAborted,
+                "the request was aborted",
+            )),
+        }
+    }
+}
+
+pub(crate) fn conv_error(err: i32) -> Error {
+    let kind = match err {
+        crate::api::ERR_TIMED_OUT => ErrorKind::TimedOut,
+        crate::api::ERR_CONNECTION_ABORTED => ErrorKind::ConnectionAborted,
+        _ => ErrorKind::Other,
+    };
+    Error::new(kind, format!("syscall error - code={}", err).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ReqwestStreamSimplified;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct MockSession {
+        written: Mutex<Vec<u8>>,
+        closed: Mutex<bool>,
+    }
+
+    #[wasmer_bus::async_trait]
+    impl ReqwestStreamSimplified for MockSession {
+        async fn write(&self, data: Vec<u8>) -> Result<usize, i32> {
+            let len = data.len();
+            self.written.lock().unwrap().extend(data);
+            Ok(len)
+        }
+        async fn close(&self) {
+            *self.closed.lock().unwrap() = true;
+        }
+        async fn cancel(&self) {}
+    }
+
+    fn response(status: u16) -> Response {
+        Response {
+            pos: 0,
+            data: None,
+            ok: true,
+            redirected: false,
+            status,
+            status_text: "OK".to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    fn create_stream() -> (
+        ResponseStream,
+        Arc<MockSession>,
+        mpsc::Sender<Response>,
+        mpsc::Sender<Result<(), i32>>,
+    ) {
+        let session = Arc::new(MockSession::default());
+        let (tx_head, rx_head) = mpsc::channel(1);
+        let (tx_finish, rx_finish) = mpsc::channel(1);
+        let stream = ResponseStream {
+            session: session.clone(),
+            rx_head,
+            rx_finish,
+            finished: None,
+        };
+        (stream, session, tx_head, tx_finish)
+    }
+
+    #[test]
+    fn test_head_wins_over_finish() {
+        // Small responses complete before the caller asks for the head
+        for _ in 0..100 {
+            let (mut stream, _, tx_head, tx_finish) = create_stream();
+            tx_head.try_send(response(200)).unwrap();
+            tx_finish.try_send(Ok(())).unwrap();
+            assert_eq!(stream.head().unwrap().status, 200);
+            assert!(stream.wait().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_failure_before_head() {
+        let (mut stream, _, _tx_head, tx_finish) = create_stream();
+        tx_finish.try_send(Err(crate::api::ERR_TIMED_OUT)).unwrap();
+        assert_eq!(stream.head().unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(stream.wait().unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_dropped_request_is_aborted() {
+        let (mut stream, _, tx_head, tx_finish) = create_stream();
+        drop(tx_head);
+        drop(tx_finish);
+        assert_eq!(stream.head().unwrap_err().kind(), ErrorKind::ConnectionAborted);
+    }
+
+    #[test]
+    fn test_upload_is_written_to_the_session() {
+        let (stream, session, _tx_head, tx_finish) = create_stream();
+        assert_eq!(stream.write(b"hello ").unwrap(), 6);
+        assert_eq!(stream.write(b"world").unwrap(), 5);
+        stream.close().unwrap();
+        assert_eq!(&session.written.lock().unwrap()[..], b"hello world");
+        assert!(*session.closed.lock().unwrap());
+
+        tx_finish.try_send(Ok(())).unwrap();
+        assert!(stream.wait().is_ok());
+    }
+}
@@ -24,6 +24,7 @@ pub enum BusError {
     AccessDenied = 18,
     AlreadyConsumed = 19,
     MemoryAccessViolation = 20,
+    Timeout = 21,
     Unknown = u32::MAX,
 }
 
@@ -51,6 +52,7 @@ for BusError {
             18 => AccessDenied,
             19 => AlreadyConsumed,
             20 => MemoryAccessViolation,
+            21 => Timeout,
             _ => Unknown
         }
     }
@@ -69,6 +71,10 @@ impl Into<io::Error> for BusError {
                 io::ErrorKind::ConnectionAborted,
                 format!("connection aborted - {}", self.to_string()).as_str(),
             ),
+            BusError::Timeout => io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out - {}", self.to_string()).as_str(),
+            ),
             err => io::Error::new(
                 io::ErrorKind::Other,
                 format!("wasm bus error - {}", err.to_string()).as_str(),
@@ -112,6 +118,7 @@ impl fmt::Display for BusError {
             BusError::BusInvocationFailed => write!(f, "bus invocation has failed"),
             BusError::AlreadyConsumed => write!(f, "result already consumed"),
             BusError::MemoryAccessViolation => write!(f, "memory access violation"),
+            BusError::Timeout => write!(f, "the call did not complete before its deadline."),
             BusError::Unknown => write!(f, "unknown error."),
         }
     }
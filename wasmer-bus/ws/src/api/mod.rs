@@ -1,6 +1,8 @@
 use std::sync::Arc;
 use wasmer_bus::macros::*;
 
+use crate::model::ConnectOptions;
+use crate::model::Frame;
 use crate::model::SendResult;
 use crate::model::SocketState;
 
@@ -12,11 +14,23 @@ pub trait SocketBuilder {
         state_change: impl Fn(SocketState),
         receive: impl Fn(Vec<u8>),
     ) -> Arc<dyn WebSocket>;
+
+    /// Connects with sub-protocols and extra headers, every frame that is
+    /// received (text, binary, ping, pong and close) is passed to `receive`
+    async fn connect_with(
+        &self,
+        url: String,
+        options: ConnectOptions,
+        state_change: impl Fn(SocketState),
+        receive: impl Fn(Frame),
+    ) -> Arc<dyn WebSocket>;
 }
 
 #[wasmer_bus(format = "bincode")]
 pub trait WebSocket {
     async fn send(&self, data: Vec<u8>) -> SendResult;
+    async fn send_frame(&self, frame: Frame) -> SendResult;
+    async fn protocol(&self) -> Option<String>;
 }
 
 /*
@@ -0,0 +1,14 @@
+/// Extra options used when opening a web socket
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConnectOptions {
+    /// Sub-protocols that are offered to the server (`Sec-WebSocket-Protocol`)
+    pub protocols: Vec<String>,
+    /// Additional headers sent with the upgrade request (e.g. `Authorization`)
+    pub headers: Vec<(String, String)>,
+}
+
+impl ConnectOptions {
+    pub fn is_empty(&self) -> bool {
+        self.protocols.is_empty() && self.headers.is_empty()
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt::{Display, Formatter, self};
+
+/// Close code for a normal closure of the connection
+pub const CLOSE_NORMAL: u16 = 1000;
+/// Close code used when an endpoint is going away (e.g. shutting down)
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+/// Close code used when an endpoint terminates due to a protocol error
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+/// Close code used when a message violates the policy of the endpoint
+pub const CLOSE_POLICY_VIOLATION: u16 = 1008;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    pub fn new(code: u16, reason: &str) -> CloseFrame {
+        CloseFrame {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close(Option<CloseFrame>),
+}
+
+impl Frame {
+    /// Returns the payload of a data frame (text or binary)
+    pub fn into_data(self) -> Option<Vec<u8>> {
+        match self {
+            Frame::Text(a) => Some(a.into_bytes()),
+            Frame::Binary(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Frame::Text(a) => write!(f, "text({} bytes)", a.len()),
+            Frame::Binary(a) => write!(f, "binary({} bytes)", a.len()),
+            Frame::Ping(a) => write!(f, "ping({} bytes)", a.len()),
+            Frame::Pong(a) => write!(f, "pong({} bytes)", a.len()),
+            Frame::Close(Some(a)) => write!(f, "close(code={}, reason={})", a.code, a.reason),
+            Frame::Close(None) => write!(f, "close"),
+        }
+    }
+}
@@ -1,5 +1,9 @@
+mod connect_options;
+mod frame;
 mod socket_state;
 mod send_result;
 
+pub use connect_options::*;
+pub use frame::*;
 pub use socket_state::*;
-pub use send_result::*;
\ No newline at end of file
+pub use send_result::*;
@@ -5,19 +5,38 @@ use tokio_tungstenite::MaybeTlsStream;
 use tracing::{debug, error, info, trace, warn};
 
 use super::*;
+use crate::model::ConnectOptions;
 
 pub struct SocketBuilder {
     pub(crate) url: url::Url,
+    pub(crate) options: ConnectOptions,
 }
 
 impl SocketBuilder {
     pub fn new(url: url::Url) -> SocketBuilder {
-        SocketBuilder { url }
+        SocketBuilder {
+            url,
+            options: ConnectOptions::default(),
+        }
     }
 
     pub fn new_str(url: &str) -> Result<SocketBuilder, url::ParseError> {
         let url = url::Url::parse(url)?;
-        Ok(SocketBuilder { url })
+        Ok(SocketBuilder::new(url))
+    }
+
+    /// Offers a sub-protocol to the server
+    pub fn protocol(mut self, protocol: &str) -> SocketBuilder {
+        self.options.protocols.push(protocol.to_string());
+        self
+    }
+
+    /// Adds a header to the upgrade request
+    pub fn header(mut self, name: &str, value: &str) -> SocketBuilder {
+        self.options
+            .headers
+            .push((name.to_string(), value.to_string()));
+        self
     }
 
     pub fn blocking_open(self) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, std::io::Error> {
@@ -29,6 +48,6 @@ impl SocketBuilder {
     }
 
     pub async fn open(self) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, std::io::Error> {
-        Ok(super::web_socket::connect(self.url.as_str()).await?)
+        Ok(super::web_socket::connect_with(self.url.as_str(), &self.options).await?)
     }
 }
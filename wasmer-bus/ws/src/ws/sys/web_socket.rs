@@ -15,6 +15,12 @@ use tokio::io::AsyncWrite;
 use tokio::io::AsyncRead;
 use tokio::io::ReadBuf;
 use tokio_tungstenite::{client_async_tls_with_config, tungstenite::protocol::Message};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::HeaderName;
+use tokio_tungstenite::tungstenite::http::header::HeaderValue;
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame as WsCloseFrame;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
@@ -22,7 +28,12 @@ use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 use crate::model::*;
 
 pub(crate) async fn connect(url: &str) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, io::Error> {
-    let request = url::Url::parse(url).unwrap();
+    connect_with(url, &ConnectOptions::default()).await
+}
+
+pub(crate) async fn connect_with(url: &str, options: &ConnectOptions) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, io::Error> {
+    let request = url::Url::parse(url)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
 
     let host = request
         .host()
@@ -40,11 +51,63 @@ pub(crate) async fn connect(url: &str) -> Result<WebSocket<MaybeTlsStream<TcpStr
     let socket = TcpStream::connect(addr).await?;
     socket.set_nodelay(true)?;
 
-    let (ws_stream, _) = client_async_tls_with_config(request, socket, None, None).await
+    let mut request = request.into_client_request()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    for (name, value) in options.headers.iter() {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let value = HeaderValue::from_str(value.as_str())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        request.headers_mut().append(name, value);
+    }
+    if options.protocols.len() > 0 {
+        let protocols = HeaderValue::from_str(options.protocols.join(", ").as_str())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        request.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, protocols);
+    }
+
+    let (ws_stream, response) = client_async_tls_with_config(request, socket, None, None).await
         .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let protocol = response
+        .headers()
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|a| a.to_str().ok())
+        .map(|a| a.to_string());
     let (sink, stream) = ws_stream.split();
 
-    Ok(WebSocket::new(sink, stream))
+    let mut ret = WebSocket::new(sink, stream);
+    ret.protocol = protocol;
+    Ok(ret)
+}
+
+pub(crate) fn frame_to_message(frame: Frame) -> Message {
+    match frame {
+        Frame::Text(a) => Message::Text(a),
+        Frame::Binary(a) => Message::Binary(a),
+        Frame::Ping(a) => Message::Ping(a),
+        Frame::Pong(a) => Message::Pong(a),
+        Frame::Close(a) => Message::Close(a.map(|a| WsCloseFrame {
+            code: CloseCode::from(a.code),
+            reason: a.reason.into(),
+        })),
+    }
+}
+
+pub(crate) fn message_to_frame(msg: Message) -> Option<Frame> {
+    Some(match msg {
+        Message::Text(a) => Frame::Text(a),
+        Message::Binary(a) => Frame::Binary(a),
+        Message::Ping(a) => Frame::Ping(a),
+        Message::Pong(a) => Frame::Pong(a),
+        Message::Close(a) => Frame::Close(a.map(|a| CloseFrame {
+            code: u16::from(a.code),
+            reason: a.reason.to_string(),
+        })),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return None;
+        }
+    })
 }
 
 #[derive(Debug)]
@@ -53,6 +116,7 @@ where S: AsyncRead + AsyncWrite + Unpin
 {
     sink: SplitSink<WebSocketStream<S>, Message>,
     stream: SplitStream<WebSocketStream<S>>,
+    protocol: Option<String>,
 }
 
 impl<S> WebSocket<S>
@@ -61,10 +125,16 @@ where S: AsyncRead + AsyncWrite + Unpin
     pub fn new(sink: SplitSink<WebSocketStream<S>, Message>, stream: SplitStream<WebSocketStream<S>>) -> Self {
         Self {
             sink,
-            stream
+            stream,
+            protocol: None,
         }
     }
 
+    /// Sub-protocol that the server selected during the handshake
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_ref().map(|a| a.as_str())
+    }
+
     pub fn split(self) -> (SendHalf<S>, RecvHalf<S>) {
         (
             SendHalf::new(self.sink),
@@ -100,6 +170,29 @@ where S: AsyncRead + AsyncWrite + Unpin
         Ok(())
     }
 
+    /// Sends a close frame with a particular code and reason
+    pub async fn close_with(&mut self, code: u16, reason: &str) -> io::Result<()> {
+        self.send_frame(Frame::Close(Some(CloseFrame::new(code, reason)))).await?;
+        self.close().await
+    }
+
+    pub async fn send_frame(&mut self, frame: Frame) -> io::Result<()> {
+        self.sink
+            .send(frame_to_message(frame))
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+
+    pub async fn send_text(&mut self, text: String) -> io::Result<usize> {
+        let text_len = text.len();
+        self.send_frame(Frame::Text(text)).await?;
+        Ok(text_len)
+    }
+
+    pub async fn send_ping(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.send_frame(Frame::Ping(data)).await
+    }
+
     pub async fn send(&mut self, data: Vec<u8>) -> io::Result<usize> {
         let data_len = data.len();
         self.sink
@@ -202,15 +295,45 @@ where S: AsyncRead + AsyncWrite + Unpin
     }
 
     pub async fn recv(&mut self) -> Option<Vec<u8>> {
-        match self.stream.next().await {
-            Some(Ok(Message::Binary(msg))) => {
-                Some(msg)
+        loop {
+            match self.stream.next().await {
+                Some(Ok(Message::Binary(msg))) => {
+                    return Some(msg);
+                }
+                Some(Ok(Message::Text(msg))) => {
+                    return Some(msg.into_bytes());
+                }
+                Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => {
+                    continue;
+                }
+                Some(a) => {
+                    debug!("received invalid msg: {:?}", a);
+                    return None;
+                }
+                None => {
+                    return None;
+                }
             }
-            Some(a) => {
-                debug!("received invalid msg: {:?}", a);
-                None
+        }
+    }
+
+    /// Receives the next frame including control frames (ping, pong and close)
+    pub async fn recv_frame(&mut self) -> Option<Frame> {
+        loop {
+            match self.stream.next().await {
+                Some(Ok(msg)) => {
+                    if let Some(frame) = message_to_frame(msg) {
+                        return Some(frame);
+                    }
+                }
+                Some(Err(err)) => {
+                    debug!("failed to receive frame: {}", err);
+                    return None;
+                }
+                None => {
+                    return None;
+                }
             }
-            None => None
         }
     }
 
@@ -294,3 +417,63 @@ where S: AsyncRead + AsyncWrite + Unpin
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(frame: Frame) -> Option<Frame> {
+        message_to_frame(frame_to_message(frame))
+    }
+
+    #[test]
+    fn test_data_frames_round_trip() {
+        let text = Frame::Text("hello".to_string());
+        assert_eq!(round_trip(text.clone()), Some(text));
+        let binary = Frame::Binary(vec![1, 2, 3]);
+        assert_eq!(round_trip(binary.clone()), Some(binary));
+        assert_eq!(frame_to_message(Frame::Binary(vec![4])), Message::Binary(vec![4]));
+    }
+
+    #[test]
+    fn test_control_frames_round_trip() {
+        let ping = Frame::Ping(vec![9, 8]);
+        assert_eq!(frame_to_message(ping.clone()), Message::Ping(vec![9, 8]));
+        assert_eq!(round_trip(ping.clone()), Some(ping));
+        let pong = Frame::Pong(vec![7]);
+        assert_eq!(frame_to_message(pong.clone()), Message::Pong(vec![7]));
+        assert_eq!(round_trip(pong.clone()), Some(pong));
+        assert_eq!(round_trip(Frame::Close(None)), Some(Frame::Close(None)));
+    }
+
+    #[test]
+    fn test_close_codes_are_kept() {
+        for code in [1000u16, 1001, 1008, 1011, 3000, 4999] {
+            let close = Frame::Close(Some(CloseFrame {
+                code,
+                reason: format!("reason {}", code),
+            }));
+            match frame_to_message(close.clone()) {
+                Message::Close(Some(a)) => {
+                    assert_eq!(u16::from(a.code), code);
+                    assert_eq!(a.reason, format!("reason {}", code));
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+            assert_eq!(round_trip(close.clone()), Some(close));
+        }
+
+        // Close frames received from the server are converted as well
+        let msg = Message::Close(Some(WsCloseFrame {
+            code: CloseCode::Away,
+            reason: "bye".into(),
+        }));
+        assert_eq!(
+            message_to_frame(msg),
+            Some(Frame::Close(Some(CloseFrame {
+                code: 1001,
+                reason: "bye".to_string(),
+            })))
+        );
+    }
+}
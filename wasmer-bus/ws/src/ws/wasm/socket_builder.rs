@@ -8,6 +8,8 @@ use tracing::{debug, error, info, trace, warn};
 
 use super::*;
 use crate::api;
+use crate::model::ConnectOptions;
+use crate::model::Frame;
 use crate::model::SocketState;
 use wasmer_bus::abi::*;
 
@@ -15,16 +17,34 @@ const MAX_MPSC: usize = std::usize::MAX >> 3;
 
 pub struct SocketBuilder {
     pub(crate) url: url::Url,
+    pub(crate) options: ConnectOptions,
 }
 
 impl SocketBuilder {
     pub fn new(url: url::Url) -> SocketBuilder {
-        SocketBuilder { url }
+        SocketBuilder {
+            url,
+            options: ConnectOptions::default(),
+        }
     }
 
     pub fn new_str(url: &str) -> Result<SocketBuilder, url::ParseError> {
         let url = url::Url::parse(url)?;
-        Ok(SocketBuilder { url })
+        Ok(SocketBuilder::new(url))
+    }
+
+    /// Offers a sub-protocol to the server
+    pub fn protocol(mut self, protocol: &str) -> SocketBuilder {
+        self.options.protocols.push(protocol.to_string());
+        self
+    }
+
+    /// Adds a header to the upgrade request
+    pub fn header(mut self, name: &str, value: &str) -> SocketBuilder {
+        self.options
+            .headers
+            .push((name.to_string(), value.to_string()));
+        self
     }
 
     pub fn blocking_open(self) -> Result<WebSocket, std::io::Error> {
@@ -38,18 +58,35 @@ impl SocketBuilder {
         let (tx_recv, rx_recv) = mpsc::channel(MAX_MPSC);
         let (tx_state, rx_state) = watch::channel(SocketState::Opening);
 
-        let client = api::SocketBuilderClient::new(WAPM_NAME)
-            .connect(
-                url,
-                Box::new(move |data: SocketState| {
-                    let _ = tx_state.send(data);
-                }),
-                Box::new(move |data: Vec<u8>| {
-                    wasmer_bus::task::send(&tx_recv, data);
-                }),
-            )
-            .await
-            .map_err(|err| err.into_io_error())?;
+        // Plain connections use the original call so that they still work
+        // on older operating systems that do not understand frames
+        let builder = api::SocketBuilderClient::new(WAPM_NAME);
+        let state_change = Box::new(move |data: SocketState| {
+            let _ = tx_state.send(data);
+        });
+        let client = if self.options.is_empty() {
+            builder
+                .connect(
+                    url,
+                    state_change,
+                    Box::new(move |data: Vec<u8>| {
+                        wasmer_bus::task::send(&tx_recv, Frame::Binary(data));
+                    }),
+                )
+                .await
+        } else {
+            builder
+                .connect_with(
+                    url,
+                    self.options,
+                    state_change,
+                    Box::new(move |frame: Frame| {
+                        wasmer_bus::task::send(&tx_recv, frame);
+                    }),
+                )
+                .await
+        }
+        .map_err(|err| err.into_io_error())?;
 
         Ok(WebSocket {
             client,
@@ -19,6 +19,8 @@ use tokio::sync::watch;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
 
+use crate::model::CloseFrame;
+use crate::model::Frame;
 use crate::model::SendResult;
 use crate::model::SocketState;
 use wasmer_bus::abi::*;
@@ -26,11 +28,19 @@ use wasmer_bus::abi::*;
 #[derive(Debug)]
 pub struct WebSocket {
     pub(super) client: Arc<dyn crate::api::WebSocket>,
-    pub(super) rx: mpsc::Receiver<Vec<u8>>,
+    pub(super) rx: mpsc::Receiver<Frame>,
     pub(super) state: watch::Receiver<SocketState>,
 }
 
 impl WebSocket {
+    /// Sub-protocol that the server selected during the handshake
+    pub async fn protocol(&self) -> io::Result<Option<String>> {
+        self.client
+            .protocol()
+            .await
+            .map_err(|err| err.into_io_error())
+    }
+
     pub fn split(self) -> (SendHalf, RecvHalf) {
         (
             SendHalf {
@@ -71,7 +81,40 @@ impl SendHalf {
         Ok(())
     }
 
+    /// Sends a close frame with a particular code and reason
+    pub async fn close_with(&mut self, code: u16, reason: &str) -> io::Result<()> {
+        self.send_frame(Frame::Close(Some(CloseFrame::new(code, reason))))
+            .await
+            .map(|_| ())
+    }
+
     pub async fn send(&mut self, data: Vec<u8>) -> io::Result<usize> {
+        self.check_opened().await?;
+        self.client
+            .send(data)
+            .await
+            .map_err(|err| err.into_io_error())
+            .map(conv_send_result)?
+    }
+
+    pub async fn send_frame(&mut self, frame: Frame) -> io::Result<usize> {
+        self.check_opened().await?;
+        self.client
+            .send_frame(frame)
+            .await
+            .map_err(|err| err.into_io_error())
+            .map(conv_send_result)?
+    }
+
+    pub async fn send_text(&mut self, text: String) -> io::Result<usize> {
+        self.send_frame(Frame::Text(text)).await
+    }
+
+    pub async fn send_ping(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.send_frame(Frame::Ping(data)).await.map(|_| ())
+    }
+
+    async fn check_opened(&self) -> io::Result<()> {
         let state = self.wait_till_opened().await;
         if state != SocketState::Opened {
             return Err(io::Error::new(
@@ -79,14 +122,7 @@ impl SendHalf {
                 format!("connection is not open (state={})", state).as_str(),
             ));
         }
-        self.client
-            .send(data)
-            .await
-            .map_err(|err| err.into_io_error())
-            .map(|ret| match ret {
-                SendResult::Success(a) => Ok(a),
-                SendResult::Failed(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
-            })?
+        Ok(())
     }
 
     pub fn blocking_send(&mut self, data: Vec<u8>) -> io::Result<usize> {
@@ -155,19 +191,37 @@ for SendHalf
     }
 }
 
+fn conv_send_result(ret: SendResult) -> io::Result<usize> {
+    match ret {
+        SendResult::Success(a) => Ok(a),
+        SendResult::Failed(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+    }
+}
+
 #[derive(Debug)]
 pub struct RecvHalf {
-    rx: mpsc::Receiver<Vec<u8>>,
+    rx: mpsc::Receiver<Frame>,
     buffer: Option<Bytes>,
 }
 
 impl RecvHalf {
+    /// Receives the next data frame (control frames are skipped)
     pub async fn recv(&mut self) -> Option<Vec<u8>> {
-        self.rx.recv().await
+        loop {
+            match self.rx.recv().await? {
+                Frame::Ping(_) | Frame::Pong(_) => continue,
+                frame => return frame.into_data(),
+            }
+        }
     }
 
     pub fn blocking_recv(&mut self) -> Option<Vec<u8>> {
-        wasmer_bus::task::block_on(self.rx.recv())
+        wasmer_bus::task::block_on(self.recv())
+    }
+
+    /// Receives the next frame including control frames (ping, pong and close)
+    pub async fn recv_frame(&mut self) -> Option<Frame> {
+        self.rx.recv().await
     }
 }
 
@@ -189,9 +243,24 @@ for RecvHalf
             }
             return Poll::Ready(Ok(()));
         }
-        match self.rx.poll_recv(cx) {
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(Some(data)) => {
+        let data = loop {
+            match self.rx.poll_recv(cx) {
+                Poll::Pending => {
+                    return Poll::Pending;
+                },
+                Poll::Ready(Some(Frame::Ping(_))) | Poll::Ready(Some(Frame::Pong(_))) => {
+                    continue;
+                },
+                Poll::Ready(Some(frame)) => {
+                    break frame.into_data();
+                },
+                Poll::Ready(None) => {
+                    break None;
+                }
+            }
+        };
+        match data {
+            Some(data) => {
                 if data.len() <= buf.remaining() {
                     buf.put_slice(&data[..]);
                 } else {
@@ -201,7 +270,7 @@ for RecvHalf
                 }
                 Poll::Ready(Ok(()))
             },
-            Poll::Ready(None) => {
+            None => {
                 Poll::Ready(Err(io::Error::new(io::ErrorKind::NotConnected, "web socket connection has closed")))
             }
         }
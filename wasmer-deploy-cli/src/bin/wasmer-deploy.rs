@@ -271,6 +271,7 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
         SubCommand::User(a) => match a.action {
             UserAction::Create(..) => false,
             UserAction::Recover(..) => false,
+            UserAction::RecoverEmail(..) => false,
             _ => true,
         },
         _ => true,
@@ -10,6 +10,7 @@ use wasmer_bus_fuse::api;
 use wasmer_bus_fuse::prelude::*;
 
 use super::conv_err;
+use super::file_watcher::*;
 use super::opened_file::*;
 
 #[derive(Derivative, Clone)]
@@ -325,6 +326,70 @@ impl api::FileSystemSimplified for FileSystem {
         }
     }
 
+    async fn read_link(&self, path: String) -> FsResult<String> {
+        if let Ok(Some(file)) = self.accessor.search(&self.context, path.as_str()).await {
+            self.accessor
+                .readlink(&self.context, file.ino)
+                .await
+                .map_err(|err| {
+                    debug!("read_link failed - {}", err);
+                    conv_err(err)
+                })
+        } else {
+            debug!("read_link failed - not found ({})", path);
+            FsResult::Err(FsError::EntityNotFound)
+        }
+    }
+
+    async fn hard_link(&self, from: String, to: String) -> FsResult<api::Metadata> {
+        let new_path = std::path::Path::new(&to);
+        let new_name = new_path.file_name().ok_or_else(|| FsError::InvalidInput)?;
+        let new_parent = new_path.parent().ok_or_else(|| FsError::InvalidInput)?;
+        if let Ok(Some(file)) = self.accessor.search(&self.context, from.as_str()).await {
+            if let Ok(Some(new_parent)) = self
+                .accessor
+                .search(&self.context, new_parent.to_string_lossy().as_ref())
+                .await
+            {
+                self.accessor
+                    .link(
+                        &self.context,
+                        file.ino,
+                        new_parent.ino,
+                        new_name.to_string_lossy().as_ref(),
+                    )
+                    .await
+                    .map_err(|err| {
+                        debug!("hard_link failed - {}", err);
+                        conv_err(err)
+                    })
+                    .map(super::conv_meta)
+            } else {
+                debug!("hard_link failed - new parent not found");
+                Err(FsError::EntityNotFound)
+            }
+        } else {
+            debug!("hard_link failed - not found ({})", from);
+            Err(FsError::EntityNotFound)
+        }
+    }
+
+    async fn read_fs_stats(&self, path: String) -> FsResult<api::FsStats> {
+        if let Ok(Some(file)) = self.accessor.search(&self.context, path.as_str()).await {
+            self.accessor
+                .statfs(&self.context, file.ino)
+                .await
+                .map_err(|err| {
+                    debug!("read_fs_stats failed - {}", err);
+                    conv_err(err)
+                })
+                .map(super::conv_stats)
+        } else {
+            debug!("read_fs_stats failed - not found ({})", path);
+            FsResult::Err(FsError::EntityNotFound)
+        }
+    }
+
     async fn open(
         &self,
         path: String,
@@ -333,4 +398,21 @@ impl api::FileSystemSimplified for FileSystem {
         let ret = FileSystem::open(self, path, options).await;
         Ok(ret)
     }
+
+    async fn watch(
+        &self,
+        path: String,
+        recursive: bool,
+        event: Box<dyn Fn(api::WatchEvent) + Send + Sync + 'static>,
+    ) -> Result<Arc<dyn api::FileWatcher>, BusError> {
+        let watch = self
+            .accessor
+            .watch(&self.context, path.as_str(), recursive)
+            .await
+            .map_err(|err| {
+                debug!("watch failed (path={}) - {}", path, err);
+                BusError::BadRequest
+            })?;
+        Ok(Arc::new(FileWatcher::new(path, watch, event)))
+    }
 }
\ No newline at end of file
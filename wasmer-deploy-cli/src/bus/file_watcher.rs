@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use ate_files::prelude::*;
+use derivative::*;
+use tokio::sync::watch;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+use wasmer_bus_fuse::api;
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct FileWatcher {
+    path: String,
+    #[derivative(Debug = "ignore")]
+    stop: watch::Sender<bool>,
+}
+
+impl FileWatcher {
+    /// Forwards the changes to the client until the watcher is dropped
+    /// (which happens when the client closes its handle) or stopped
+    pub fn new(
+        path: String,
+        mut changes: FileWatch,
+        event: Box<dyn Fn(api::WatchEvent) + Send + Sync + 'static>,
+    ) -> FileWatcher {
+        let (stop, mut stopped) = watch::channel(false);
+        tokio::spawn(async move {
+            loop {
+                let evt = tokio::select! {
+                    evt = changes.recv() => evt,
+                    _ = stopped.changed() => None,
+                };
+                let evt = match evt {
+                    Some(a) => a,
+                    None => break,
+                };
+                event(api::WatchEvent {
+                    kind: match evt.kind {
+                        WatchEventKind::Create => api::WatchKind::Create,
+                        WatchEventKind::Modify => api::WatchKind::Modify,
+                        WatchEventKind::Delete => api::WatchKind::Delete,
+                        WatchEventKind::Rename => api::WatchKind::Rename,
+                    },
+                    path: evt.path,
+                    old_path: evt.old_path,
+                });
+            }
+            trace!("watch has ended");
+        });
+        FileWatcher { path, stop }
+    }
+}
+
+#[async_trait]
+impl api::FileWatcherSimplified for FileWatcher {
+    async fn stop(&self) {
+        debug!("stopping watch of {}", self.path);
+        let _ = self.stop.send(true);
+    }
+}
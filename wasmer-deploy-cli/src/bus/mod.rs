@@ -1,5 +1,6 @@
 pub mod file_io;
 pub mod file_system;
+pub mod file_watcher;
 mod fuse;
 mod main;
 mod server;
@@ -38,6 +39,18 @@ fn conv_meta(file: ate_files::attr::FileAttr) -> api::Metadata {
     }
 }
 
+fn conv_stats(stats: ate_files::stats::FsStats) -> api::FsStats {
+    api::FsStats {
+        block_size: stats.bsize as u64,
+        blocks: stats.blocks,
+        blocks_free: stats.bfree,
+        blocks_available: stats.bavail,
+        files: stats.files,
+        files_free: stats.ffree,
+        name_max: stats.namelen as u64,
+    }
+}
+
 use ate_files::error::FileSystemError;
 fn conv_err(err: FileSystemError) -> api::FsError {
     use ate_files::error::FileSystemErrorKind;
@@ -53,6 +66,7 @@ fn conv_err(err: FileSystemError) -> api::FsError {
         FileSystemError(FileSystemErrorKind::InvalidArguments, _) => api::FsError::InvalidInput,
         FileSystemError(FileSystemErrorKind::NoEntry, _) => api::FsError::EntityNotFound,
         FileSystemError(FileSystemErrorKind::NotImplemented, _) => api::FsError::NoDevice,
+        FileSystemError(FileSystemErrorKind::WouldBlock, _) => api::FsError::WouldBlock,
         FileSystemError(_, _) => api::FsError::IOError,
     }
 }
@@ -11,7 +11,7 @@ use tracing::{debug, error, info, trace, warn};
 use ate_comms::StreamSecurity;
 
 use crate::error::*;
-use crate::model::{HistoricActivity, activities, InstanceHello, InstanceCommand, InstanceExport, InstanceCall};
+use crate::model::{HistoricActivity, activities, InstanceHello, InstanceCommand, InstanceExport, InstanceExportLimits, InstanceCall};
 use crate::opt::*;
 use crate::api::{DeployApi, InstanceClient};
 
@@ -285,6 +285,7 @@ pub async fn main_opts_instance_export(
     no_http: bool,
     no_https: bool,
     no_bus: bool,
+    limits: InstanceExportLimits,
 ) -> Result<(), InstanceError> {
     let (service_instance, _wallet_instance) = api.instance_action(name).await?;
 
@@ -302,6 +303,7 @@ pub async fn main_opts_instance_export(
                 https: no_https == false,
                 bus: no_bus == false,
                 pinned: None,
+                limits,
             })?;
             dio.commit().await?;
             drop(dio);
@@ -514,7 +516,11 @@ pub async fn main_opts_instance(
         OptsInstanceAction::Export(opts_export) => {
             if name.is_none() { bail!(InstanceErrorKind::InvalidInstance); }
             let name = name.unwrap();
-            main_opts_instance_export(&mut context.api, inst_url, name.as_str(), opts_export.binary.as_str(), opts_export.pinned, opts_export.no_http, opts_export.no_https, opts_export.no_bus).await?;
+            main_opts_instance_export(&mut context.api, inst_url, name.as_str(), opts_export.binary.as_str(), opts_export.pinned, opts_export.no_http, opts_export.no_https, opts_export.no_bus, InstanceExportLimits {
+                max_memory: opts_export.max_memory,
+                max_fuel: opts_export.max_fuel,
+                max_fds: opts_export.max_fds,
+            }).await?;
         }
         OptsInstanceAction::Deport(opts_deport) => {
             if name.is_none() { bail!(InstanceErrorKind::InvalidInstance); }
@@ -31,10 +31,13 @@ pub async fn main_opts_login(
         session_to_b64(session).unwrap()
     };
 
-    // Read the session
-    let session = b64_to_session(token.clone());
+    // Read the identity (the session of a scoped token stays sealed until it
+    // is exchanged with the authentication server)
     #[allow(unused)]
-    let identity = session.identity();
+    let identity = match b64_to_scoped_token(token.as_str()) {
+        Some(a) => a.identity,
+        None => b64_to_session(token.clone())?.identity().to_string(),
+    };
 
     // Save the token
     save_token(token, token_path)?;
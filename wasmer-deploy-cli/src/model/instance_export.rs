@@ -21,4 +21,22 @@ pub struct InstanceExport {
     pub bus: bool,
     /// Indicates where the service instance is currently pinned (when its stateful)
     pub pinned: Option<NodeId>,
+    /// Default resource limits applied to the processes started for this export
+    #[serde(default)]
+    pub limits: InstanceExportLimits,
+}
+
+/// Resource limits that are applied to every process that runs on behalf
+/// of an exported binary (any limit that is not set is unlimited)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct InstanceExportLimits {
+    /// Maximum amount of memory (in bytes) each process may allocate
+    #[serde(default)]
+    pub max_memory: Option<u64>,
+    /// Maximum amount of CPU fuel each process may consume on its main thread
+    #[serde(default)]
+    pub max_fuel: Option<u64>,
+    /// Maximum number of files each process may hold open
+    #[serde(default)]
+    pub max_fds: Option<u32>,
 }
\ No newline at end of file
@@ -200,6 +200,17 @@ pub struct OptsInstanceExport {
     /// Indicates if the exported endpoint will be accessible via wasmer-bus
     #[clap(long)]
     pub no_bus: bool,
+    /// Maximum amount of memory (in bytes) that the exported binary may allocate
+    #[clap(long)]
+    pub max_memory: Option<u64>,
+    /// Maximum amount of CPU fuel that each process of the exported binary may
+    /// consume (only the main thread of the process burns fuel and only when
+    /// the instance is built with the `metering` feature)
+    #[clap(long)]
+    pub max_fuel: Option<u64>,
+    /// Maximum number of files that the exported binary may hold open
+    #[clap(long)]
+    pub max_fds: Option<u32>,
 }
 
 #[derive(Parser, Clone)]
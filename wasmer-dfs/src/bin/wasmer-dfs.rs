@@ -2,7 +2,10 @@ use ate::prelude::*;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
+use wasmer_dfs::main_conflicts;
 use wasmer_dfs::main_mount;
+use wasmer_dfs::main_quota;
+use wasmer_dfs::main_snapshot;
 use wasmer_dfs::opts::*;
 
 use wasmer_auth::cmd::*;
@@ -78,76 +81,137 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         SubCommand::Logout(opts_logout) => main_opts_logout(opts_logout, opts.token_path).await?,
         SubCommand::Mount(mount) => {
-            // Derive the group from the mount address
-            let mut group = None;
-            if let Some(remote) = &mount.remote_name {
-                if let Some((group_str, _)) = remote.split_once("/") {
-                    group = Some(group_str.to_string());
-                }
-            }
+            let (group, session) = fs_session(
+                &mount.remote_name,
+                &mount.passcode,
+                opts.token,
+                token_path,
+                opts.auth.clone(),
+                opts.no_auth,
+            )
+            .await?;
 
-            let mut session: AteSessionType = AteSessionUser::default().into();
+            // Mount the file system
+            main_mount(mount, conf, group, session, opts.auth, opts.no_auth).await?;
+        }
+        SubCommand::Snapshot(snapshot) => {
+            let (group, session) = fs_session(
+                &snapshot.remote_name,
+                &snapshot.passcode,
+                opts.token,
+                token_path,
+                opts.auth,
+                opts.no_auth,
+            )
+            .await?;
+
+            main_snapshot(snapshot, conf, group, session, opts.no_auth).await?;
+        }
+        SubCommand::Conflicts(conflicts) => {
+            let (group, session) = fs_session(
+                &conflicts.remote_name,
+                &conflicts.passcode,
+                opts.token,
+                token_path,
+                opts.auth,
+                opts.no_auth,
+            )
+            .await?;
+
+            main_conflicts(conflicts, conf, group, session, opts.no_auth).await?;
+        }
+        SubCommand::Quota(quota) => {
+            let (group, session) = fs_session(
+                &quota.remote_name,
+                &quota.passcode,
+                opts.token,
+                token_path,
+                opts.auth,
+                opts.no_auth,
+            )
+            .await?;
+
+            main_quota(quota, conf, group, session, opts.no_auth).await?;
+        }
+    }
 
-            // If a passcode is supplied then use this
-            if let Some(pass) = &mount.passcode {
-                if opts.token.is_some() || token_path.is_some() {
-                    eprintln!("You can not supply both a passcode and a token, either drop the --token arguments or the --passcode argument");
-                    std::process::exit(1);
-                }
-                if mount.remote_name.is_some() {
-                    eprintln!("Using a passcode is not compatible with remotely hosted file-systems as the distributed datchain needs to make authentication checks");
-                    std::process::exit(1);
-                }
+    info!("wasmer-dfs::shutdown");
 
-                let prefix = "ate:".to_string();
-                let key = password_to_read_key(&prefix, &pass, 15, KeySize::Bit192);
+    Ok(())
+}
 
-                let mut session_user = AteSessionUser::default();
-                session_user.user.add_read_key(&key);
-                session = session_user.into();
-            } else if opts.no_auth {
-                if mount.remote_name.is_some() {
-                    eprintln!("In order to use remotely hosted file-systems you must use some form of authentication, without authentication the distributed databases will not be able to make the needed checks");
-                    std::process::exit(1);
-                }
+/// Loads the session (and group) that will be used to access a file system
+async fn fs_session(
+    remote_name: &Option<String>,
+    passcode: &Option<String>,
+    token: Option<String>,
+    token_path: Option<String>,
+    auth: url::Url,
+    no_auth: bool,
+) -> Result<(Option<String>, AteSessionType), Box<dyn std::error::Error>> {
+    // Derive the group from the mount address
+    let mut group = None;
+    if let Some(remote) = remote_name {
+        if let Some((group_str, _)) = remote.split_once("/") {
+            group = Some(group_str.to_string());
+        }
+    }
 
-                // We do not put anything in the session as no authentication method nor a passcode was supplied
-            } else {
-                // Load the session via the token or the authentication server
-                let session_user = main_session_user(
-                    opts.token.clone(),
-                    token_path.clone(),
-                    Some(opts.auth.clone()),
-                )
-                .await?;
-
-                // Attempt to grab additional permissions for the group (if it has any)
-                session = if group.is_some() {
-                    match main_gather(
-                        group.clone(),
-                        session_user.clone().into(),
-                        opts.auth,
-                        "Group",
-                    )
-                    .await
-                    {
-                        Ok(a) => a.into(),
-                        Err(err) => {
-                            debug!("Group authentication failed: {} - falling back to user level authorization", err);
-                            session_user.into()
-                        }
-                    }
-                } else {
+    let mut session: AteSessionType = AteSessionUser::default().into();
+
+    // If a passcode is supplied then use this
+    if let Some(pass) = passcode {
+        if token.is_some() || token_path.is_some() {
+            eprintln!("You can not supply both a passcode and a token, either drop the --token arguments or the --passcode argument");
+            std::process::exit(1);
+        }
+        if remote_name.is_some() {
+            eprintln!("Using a passcode is not compatible with remotely hosted file-systems as the distributed datchain needs to make authentication checks");
+            std::process::exit(1);
+        }
+
+        let prefix = "ate:".to_string();
+        let key = password_to_read_key(&prefix, &pass, 15, KeySize::Bit192);
+
+        let mut session_user = AteSessionUser::default();
+        session_user.user.add_read_key(&key);
+        session = session_user.into();
+    } else if no_auth {
+        if remote_name.is_some() {
+            eprintln!("In order to use remotely hosted file-systems you must use some form of authentication, without authentication the distributed databases will not be able to make the needed checks");
+            std::process::exit(1);
+        }
+
+        // We do not put anything in the session as no authentication method nor a passcode was supplied
+    } else {
+        // Load the session via the token or the authentication server
+        let session_user = main_session_user(
+            token.clone(),
+            token_path.clone(),
+            Some(auth.clone()),
+        )
+        .await?;
+
+        // Attempt to grab additional permissions for the group (if it has any)
+        session = if group.is_some() {
+            match main_gather(
+                group.clone(),
+                session_user.clone().into(),
+                auth,
+                "Group",
+            )
+            .await
+            {
+                Ok(a) => a.into(),
+                Err(err) => {
+                    debug!("Group authentication failed: {} - falling back to user level authorization", err);
                     session_user.into()
                 }
             }
-
-            // Mount the file system
-            main_mount(mount, conf, group, session, opts.no_auth).await?;
+        } else {
+            session_user.into()
         }
     }
 
-    info!("wasmer-dfs::shutdown");
-
-    Ok(())
+    Ok((group, session))
 }
@@ -5,6 +5,15 @@ use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 use ate_files::error::FileSystemError;
 use ate_files::error::FileSystemErrorKind;
 use fuse3::Errno;
+use std::ffi::OsStr;
+
+/// Names that are not valid UTF-8 can not be stored in the file system
+pub(crate) fn conv_name(name: &OsStr) -> std::result::Result<&str, Errno> {
+    name.to_str().ok_or_else(|| {
+        debug!("wasmer-dfs::error name is not valid utf-8 ({:?})", name);
+        libc::EINVAL.into()
+    })
+}
 
 pub(crate) fn conv_result<T>(
     r: std::result::Result<T, FileSystemError>,
@@ -28,6 +37,8 @@ pub(crate) fn conv_result<T>(
                 FileSystemError(FileSystemErrorKind::NotDirectory, _) => Err(libc::ENOTDIR.into()),
                 FileSystemError(FileSystemErrorKind::IsDirectory, _) => Err(libc::EISDIR.into()),
                 FileSystemError(FileSystemErrorKind::NotImplemented, _) => Err(libc::ENOSYS.into()),
+                FileSystemError(FileSystemErrorKind::WouldBlock, _) => Err(libc::EAGAIN.into()),
+                FileSystemError(FileSystemErrorKind::QuotaExceeded, _) => Err(libc::EDQUOT.into()),
                 FileSystemError(
                     FileSystemErrorKind::AteError(AteErrorKind::CommitError(
                         CommitErrorKind::CommsError(CommsErrorKind::Disconnected),
@@ -31,6 +31,7 @@ const FUSE_TTL: Duration = Duration::from_secs(1);
 
 use ate_files::model;
 
+use super::error::conv_name;
 use super::error::conv_result;
 use super::fuse;
 
@@ -40,6 +41,9 @@ where
 {
     accessor: FileAccessor,
     umask: u32,
+    /// Used to tell the kernel about changes made by other mounts (it comes
+    /// from the session that mounts the file system)
+    notify: Option<fuse::Notify>,
 }
 
 pub fn conv_attr(attr: &FileAttr) -> fuse::FileAttr {
@@ -56,7 +60,7 @@ pub fn conv_attr(attr: &FileAttr) -> fuse::FileAttr {
         ctime: SystemTime::UNIX_EPOCH + Duration::from_millis(attr.created),
         kind: conv_kind(attr.kind),
         perm: fuse3::perm_from_mode_and_kind(conv_kind(attr.kind), attr.mode),
-        nlink: 0,
+        nlink: attr.nlink,
         uid: attr.uid,
         gid: attr.gid,
         rdev: 0,
@@ -83,8 +87,9 @@ impl AteFS {
         no_auth: bool,
         impersonate_uid: bool,
         umask: u32,
-    ) -> AteFS {
-        AteFS {
+        snapshot: Option<String>,
+    ) -> Result<AteFS, FileSystemError> {
+        Ok(AteFS {
             accessor: FileAccessor::new(
                 chain,
                 group,
@@ -94,9 +99,19 @@ impl AteFS {
                 no_auth,
                 impersonate_uid,
             )
-            .await,
+            .await
+            .with_snapshot(snapshot)
+            .await?,
             umask,
-        }
+            notify: None,
+        })
+    }
+
+    /// Sets the channel that invalidations are sent to the kernel on (this
+    /// must be taken from the session before it mounts the file system)
+    pub fn with_notify(mut self, notify: fuse::Notify) -> Self {
+        self.notify = Some(notify);
+        self
     }
 
     pub async fn load(&self, inode: u64) -> fuse::Result<Dao<Inode>> {
@@ -126,6 +141,76 @@ impl AteFS {
     async fn tick(&self) -> fuse::Result<()> {
         conv_result(self.accessor.tick().await)
     }
+
+    /// Watches the whole file system so that the kernel caches of entries
+    /// that are changed elsewhere get invalidated
+    async fn watch(&self, req: &RequestContext) -> fuse::Result<()> {
+        if self.accessor.snapshot.is_some() {
+            return Ok(());
+        }
+        let notify = match self.notify.clone() {
+            Some(a) => a,
+            None => {
+                debug!("no notify channel - changes made elsewhere rely on the cache timeout");
+                return Ok(());
+            }
+        };
+        let mut watch = conv_result(self.accessor.watch(req, "/", true).await)?;
+
+        TaskEngine::spawn(async move {
+            while let Some(evt) = watch.recv().await {
+                let name = |path: &str| OsString::from(path.rsplit('/').next().unwrap_or(path));
+
+                let mut kinds = Vec::new();
+                match evt.kind {
+                    WatchEventKind::Modify => {}
+                    WatchEventKind::Create | WatchEventKind::Delete => {
+                        if let Some(parent) = evt.parent {
+                            kinds.push(fuse::NotifyKind::InvalidEntry {
+                                parent,
+                                name: name(evt.path.as_str()),
+                            });
+                        }
+                    }
+                    WatchEventKind::Rename => {
+                        if let (Some(parent), Some(path)) = (evt.old_parent, evt.old_path.as_ref())
+                        {
+                            kinds.push(fuse::NotifyKind::InvalidEntry {
+                                parent,
+                                name: name(path.as_str()),
+                            });
+                        }
+                        if let Some(parent) = evt.parent {
+                            kinds.push(fuse::NotifyKind::InvalidEntry {
+                                parent,
+                                name: name(evt.path.as_str()),
+                            });
+                        }
+                    }
+                }
+                // Directory listings that the entry joined or left are stale too
+                let mut inodes = vec![evt.inode];
+                if evt.kind != WatchEventKind::Modify {
+                    inodes.extend(evt.parent);
+                    inodes.extend(evt.old_parent);
+                }
+                for inode in inodes {
+                    kinds.push(fuse::NotifyKind::InvalidInode {
+                        inode,
+                        offset: 0,
+                        len: 0,
+                    });
+                }
+
+                trace!("invalidating {} ({:?})", evt.path, evt.kind);
+                for kind in kinds {
+                    let _ = notify.clone().notify(kind).await;
+                }
+            }
+            debug!("file system watch has ended");
+        });
+        Ok(())
+    }
 }
 
 fn req_ctx(req: &fuse::Request) -> RequestContext {
@@ -143,6 +228,9 @@ impl fuse::Filesystem for AteFS {
     async fn init(&self, req: fuse::Request) -> fuse::Result<()> {
         let req = req_ctx(&req);
         conv_result(self.accessor.init(&req).await)?;
+        if let Err(err) = self.watch(&req).await {
+            warn!("remote changes will not be invalidated - {}", err);
+        }
         Ok(())
     }
 
@@ -368,7 +456,7 @@ impl fuse::Filesystem for AteFS {
         name: &OsStr,
     ) -> fuse::Result<fuse::ReplyEntry> {
         let req = req_ctx(&req);
-        let name = name.to_str().unwrap();
+        let name = conv_name(name)?;
         Ok(fuse::ReplyEntry {
             ttl: FUSE_TTL,
             attr: match conv_result(self.accessor.lookup(&req, parent, name).await)? {
@@ -429,7 +517,7 @@ impl fuse::Filesystem for AteFS {
         };
         let attr = conv_result(
             self.accessor
-                .mkdir(&req, parent, name.to_str().unwrap(), mode)
+                .mkdir(&req, parent, conv_name(name)?, mode)
                 .await,
         )?;
         Ok(fuse::ReplyEntry {
@@ -443,7 +531,7 @@ impl fuse::Filesystem for AteFS {
         let req = req_ctx(&req);
         conv_result(
             self.accessor
-                .rmdir(&req, parent, name.to_str().unwrap())
+                .rmdir(&req, parent, conv_name(name)?)
                 .await,
         )
     }
@@ -469,7 +557,7 @@ impl fuse::Filesystem for AteFS {
         };
         let node = self
             .accessor
-            .mknod(&req, parent, name.to_str().unwrap(), mode)
+            .mknod(&req, parent, conv_name(name)?, mode)
             .await;
         Ok(fuse::ReplyEntry {
             ttl: FUSE_TTL,
@@ -494,7 +582,7 @@ impl fuse::Filesystem for AteFS {
         };
         let handle = conv_result(
             self.accessor
-                .create(&req, parent, name.to_str().unwrap(), mode)
+                .create(&req, parent, conv_name(name)?, mode)
                 .await,
         )?;
         Ok(fuse::ReplyCreated {
@@ -510,7 +598,7 @@ impl fuse::Filesystem for AteFS {
         let req = req_ctx(&req);
         conv_result(
             self.accessor
-                .unlink(&req, parent, name.to_str().unwrap())
+                .unlink(&req, parent, conv_name(name)?)
                 .await,
         )
     }
@@ -529,9 +617,9 @@ impl fuse::Filesystem for AteFS {
                 .rename(
                     &req,
                     parent,
-                    name.to_str().unwrap(),
+                    conv_name(name)?,
                     new_parent,
-                    new_name.to_str().unwrap(),
+                    conv_name(new_name)?,
                 )
                 .await,
         )
@@ -629,6 +717,53 @@ impl fuse::Filesystem for AteFS {
         Ok(fuse::ReplyLSeek { offset })
     }
 
+    async fn getlk(
+        &self,
+        req: fuse::Request,
+        inode: u64,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        r#type: u32,
+        pid: u32,
+    ) -> fuse::Result<fuse::ReplyLock> {
+        let req = req_ctx(&req);
+        let lock = conv_result(
+            self.accessor
+                .getlk(&req, inode, fh, lock_owner, start, end, r#type, pid)
+                .await,
+        )?;
+        Ok(fuse::ReplyLock {
+            start: lock.start,
+            end: lock.end,
+            r#type: lock.typ,
+            pid: lock.pid,
+        })
+    }
+
+    /// `flock` locks also arrive here (as whole-file locks owned by the open
+    /// file) so that they hold across machines just like `fcntl` locks
+    async fn setlk(
+        &self,
+        req: fuse::Request,
+        inode: u64,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        r#type: u32,
+        pid: u32,
+        block: bool,
+    ) -> fuse::Result<()> {
+        let req = req_ctx(&req);
+        conv_result(
+            self.accessor
+                .setlk(&req, inode, fh, lock_owner, start, end, r#type, pid, block)
+                .await,
+        )
+    }
+
     async fn symlink(
         &self,
         req: fuse::Request,
@@ -639,7 +774,7 @@ impl fuse::Filesystem for AteFS {
         let req = req_ctx(&req);
         let attr = conv_result(
             self.accessor
-                .symlink(&req, parent, name.to_str().unwrap(), link.to_str().unwrap())
+                .symlink(&req, parent, conv_name(name)?, conv_name(link)?)
                 .await,
         )?;
         Ok(fuse::ReplyEntry {
@@ -650,27 +785,49 @@ impl fuse::Filesystem for AteFS {
     }
 
     /// read symbolic link.
-    async fn readlink(&self, req: fuse::Request, _inode: u64) -> fuse::Result<fuse::ReplyData> {
-        let _req = req_ctx(&req);
-        Err(libc::ENOSYS.into())
+    async fn readlink(&self, req: fuse::Request, inode: u64) -> fuse::Result<fuse::ReplyData> {
+        let req = req_ctx(&req);
+        let link = conv_result(self.accessor.readlink(&req, inode).await)?;
+        Ok(fuse::ReplyData {
+            data: bytes::Bytes::from(link.into_bytes()),
+        })
     }
 
     /// create a hard link.
     async fn link(
         &self,
         req: fuse::Request,
-        _inode: u64,
-        _new_parent: u64,
-        _new_name: &OsStr,
+        inode: u64,
+        new_parent: u64,
+        new_name: &OsStr,
     ) -> fuse::Result<fuse::ReplyEntry> {
-        let _req = req_ctx(&req);
-        Err(libc::ENOSYS.into())
+        let req = req_ctx(&req);
+        let attr = conv_result(
+            self.accessor
+                .link(&req, inode, new_parent, conv_name(new_name)?)
+                .await,
+        )?;
+        Ok(fuse::ReplyEntry {
+            ttl: FUSE_TTL,
+            attr: conv_attr(&attr),
+            generation: 0,
+        })
     }
 
     /// get filesystem statistics.
-    async fn statsfs(&self, req: fuse::Request, _inode: u64) -> fuse::Result<fuse::ReplyStatFs> {
-        let _req = req_ctx(&req);
-        Err(libc::ENOSYS.into())
+    async fn statsfs(&self, req: fuse::Request, inode: u64) -> fuse::Result<fuse::ReplyStatFs> {
+        let req = req_ctx(&req);
+        let stats = conv_result(self.accessor.statfs(&req, inode).await)?;
+        Ok(fuse::ReplyStatFs {
+            blocks: stats.blocks,
+            bfree: stats.bfree,
+            bavail: stats.bavail,
+            files: stats.files,
+            ffree: stats.ffree,
+            bsize: stats.bsize,
+            namelen: stats.namelen,
+            frsize: stats.frsize,
+        })
     }
 
     /// set an extended attribute.
@@ -686,7 +843,7 @@ impl fuse::Filesystem for AteFS {
         let req = req_ctx(&req);
         conv_result(
             self.accessor
-                .setxattr(&req, inode, name.to_str().unwrap(), value.to_str().unwrap())
+                .setxattr(&req, inode, conv_name(name)?, conv_name(value)?)
                 .await,
         )
     }
@@ -703,7 +860,7 @@ impl fuse::Filesystem for AteFS {
         let req = req_ctx(&req);
         let ret = match conv_result(
             self.accessor
-                .getxattr(&req, inode, name.to_str().unwrap())
+                .getxattr(&req, inode, conv_name(name)?)
                 .await,
         )? {
             Some(a) => a,
@@ -753,7 +910,7 @@ impl fuse::Filesystem for AteFS {
         let req = req_ctx(&req);
         conv_result(
             self.accessor
-                .removexattr(&req, inode, name.to_str().unwrap())
+                .removexattr(&req, inode, conv_name(name)?)
                 .await,
         )?;
         Ok(())
@@ -782,11 +939,16 @@ impl fuse::Filesystem for AteFS {
         _fh: u64,
         _kh: Option<u64>,
         _flags: u32,
-        _events: u32,
+        events: u32,
         _notify: &fuse::Notify,
     ) -> fuse::Result<fuse::ReplyPoll> {
         let _req = req_ctx(&req);
-        Err(libc::ENOSYS.into())
+
+        // Files can always be read from and written to without blocking
+        let ready = (libc::POLLIN | libc::POLLOUT | libc::POLLRDNORM | libc::POLLWRNORM) as u32;
+        Ok(fuse::ReplyPoll {
+            revents: events & ready,
+        })
     }
 
     async fn notify_reply(
@@ -808,16 +970,23 @@ impl fuse::Filesystem for AteFS {
     async fn copy_file_range(
         &self,
         req: fuse::Request,
-        _inode: u64,
-        _fh_in: u64,
-        _off_in: u64,
-        _inode_out: u64,
-        _fh_out: u64,
-        _off_out: u64,
-        _length: u64,
+        inode: u64,
+        fh_in: u64,
+        off_in: u64,
+        inode_out: u64,
+        fh_out: u64,
+        off_out: u64,
+        length: u64,
         _flags: u64,
     ) -> fuse::Result<fuse::ReplyCopyFileRange> {
-        let _req = req_ctx(&req);
-        Err(libc::ENOSYS.into())
+        let req = req_ctx(&req);
+        let copied = conv_result(
+            self.accessor
+                .copy_file_range(
+                    &req, inode, fh_in, off_in, inode_out, fh_out, off_out, length,
+                )
+                .await,
+        )?;
+        Ok(fuse::ReplyCopyFileRange { copied })
     }
 }
@@ -1,4 +1,5 @@
 use ate::mesh::FatalTerminate;
+use ate::mesh::Registry;
 use ate::prelude::*;
 use ate::utils::LoadProgress;
 use error_chain::bail;
@@ -10,9 +11,13 @@ use tokio::select;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
 use crate::fs::AteFS;
+use ate_files::accessor::FileAccessor;
+use ate_files::prelude::{QuotaLimit, QuotaOwner, QuotaTarget};
 use crate::opts::*;
 use crate::umount;
 
+use url::Url;
+
 use fuse3::raw::prelude::*;
 use fuse3::MountOptions;
 
@@ -30,6 +35,7 @@ pub async fn main_mount(
     conf: ConfAte,
     group: Option<String>,
     session: AteSessionType,
+    auth: Url,
     no_auth: bool,
 ) -> Result<(), AteError> {
     let uid = match mount.uid {
@@ -49,7 +55,7 @@ pub async fn main_mount(
         .gid(gid)
         .allow_root(mount.allow_root)
         .allow_other(mount.allow_other)
-        .read_only(mount.read_only)
+        .read_only(mount.read_only || mount.snapshot.is_some())
         .write_back(mount.write_back)
         .nonempty(mount.non_empty);
 
@@ -96,78 +102,33 @@ pub async fn main_mount(
         None => info!("remote: local-only"),
     };
 
-    let builder = ChainBuilder::new(&conf).await.temporal(mount.temp);
+    let (chain, _registry) = open_chain(
+        &conf,
+        mount.remote_name.clone(),
+        &mount.remote,
+        mount.temp,
+        &mount.configured_for,
+    )
+    .await?;
 
-    // Create a progress bar loader
-    let mut progress_local = LoadProgress::new(std::io::stdout());
-    let mut progress_remote = LoadProgress::new(std::io::stdout());
-    progress_local.units = pbr::Units::Bytes;
-    progress_local.msg_done = "Downloading latest events from server...".to_string();
-    progress_remote.msg_done =
-        "Loaded the remote chain-of-trust, proceeding to mount the file system.".to_string();
-    print!("Loading the chain-of-trust...");
-
-    // We create a chain with a specific key (this is used for the file name it creates)
-    debug!("chain-init");
-    let registry;
-    let chain = match mount.remote_name {
-        None => {
-            let trust = match &mount.configured_for {
-                ConfiguredFor::BestSecurity | ConfiguredFor::SmallestSize => {
-                    TrustMode::Centralized(CentralizedRole::Client)
-                }
-                _ => TrustMode::Distributed,
-            };
-            Ok(Arc::new(
-                Chain::new_ext(
-                    builder.clone(),
-                    ChainKey::from("root"),
-                    Some(Box::new(progress_local)),
-                    true,
-                    trust,
-                    trust,
-                )
-                .await?,
-            ))
-        }
-        Some(remote) => {
-            registry = ate::mesh::Registry::new(&conf).await.temporal(mount.temp);
-
-            let guard = registry
-                .open_ext(
-                    &mount.remote,
-                    &ChainKey::from(remote),
-                    false,
-                    progress_local,
-                    progress_remote,
-                )
-                .await?;
-            Ok(guard.as_arc())
-        }
-    };
-
-    // Perform specific error handling (otherwise let it propogate up)
-    let chain = match chain {
-        Ok(a) => a,
-        Err(ChainCreationError(ChainCreationErrorKind::ServerRejected(reason), _)) => {
-            match reason {
-                FatalTerminate::Denied { reason } => {
-                    println!("Access to this file system was denied by the server");
-                    println!("---");
-                    println!("{}", reason);
-                    std::process::exit(1);
-                }
-                _ => {
-                    bail!(AteErrorKind::ChainCreationError(
-                        ChainCreationErrorKind::ServerRejected(reason)
-                    ));
-                }
+    // Let the authentication server know that the group has this database so
+    // that its records are moved onto the new keys whenever they are rotated
+    if let (AteSessionType::Group(group), Some(remote_name)) = (&session, &mount.remote_name) {
+        if let Some((_, database)) = remote_name.split_once("/") {
+            let registry = Registry::new(&wasmer_auth::helper::conf_cmd()).await.cement();
+            if let Err(err) = wasmer_auth::cmd::group_database_register_command(
+                &registry,
+                group.group.name.clone(),
+                database.to_string(),
+                auth.clone(),
+                group,
+            )
+            .await
+            {
+                warn!("failed to register the database with the group - {}", err);
             }
         }
-        Err(err) => {
-            bail!(err);
-        }
-    };
+    }
 
     // Compute the scope
     let scope_meta = match mount.recovery_mode.is_meta_sync() {
@@ -179,22 +140,25 @@ pub async fn main_mount(
         false => TransactionScope::Local,
     };
 
+    // Create the file system (snapshots are opened as they were when taken)
+    let fs = AteFS::new(
+        chain,
+        group,
+        session,
+        scope_io,
+        scope_meta,
+        no_auth,
+        mount.impersonate_uid,
+        mount.umask,
+        mount.snapshot.clone(),
+    )
+    .await?;
+
     // Create the mount point
     let mount_path = mount.mount_path.clone();
-    let mount_join = Session::new(mount_options).mount_with_unprivileged(
-        AteFS::new(
-            chain,
-            group,
-            session,
-            scope_io,
-            scope_meta,
-            no_auth,
-            mount.impersonate_uid,
-            mount.umask,
-        )
-        .await,
-        mount.mount_path,
-    );
+    let session = Session::new(mount_options);
+    let fs = fs.with_notify(session.get_notify());
+    let mount_join = session.mount_with_unprivileged(fs, mount.mount_path);
 
     // Install a ctrl-c command
     info!("mounting file-system and entering main loop");
@@ -249,3 +213,332 @@ pub async fn main_mount(
         }
     }
 }
+
+pub async fn main_snapshot(
+    opts: OptsSnapshot,
+    conf: ConfAte,
+    group: Option<String>,
+    session: AteSessionType,
+    no_auth: bool,
+) -> Result<(), AteError> {
+    let mut conf = conf.clone();
+    conf.configured_for(opts.configured_for);
+    conf.log_format.meta = opts.meta_format;
+    conf.log_format.data = opts.data_format;
+    conf.log_path = opts
+        .log_path
+        .as_ref()
+        .map(|a| shellexpand::tilde(a).to_string());
+
+    let (chain, _registry) = open_chain(
+        &conf,
+        opts.remote_name.clone(),
+        &opts.remote,
+        false,
+        &opts.configured_for,
+    )
+    .await?;
+
+    let accessor = FileAccessor::new(
+        chain,
+        group,
+        session,
+        TransactionScope::Full,
+        TransactionScope::Full,
+        no_auth,
+        false,
+    )
+    .await;
+    accessor.init(&accessor.session_context()).await?;
+
+    match opts.action {
+        SnapshotAction::Create(action) => {
+            accessor.snapshot_create(action.name.as_str()).await?;
+            println!("Snapshot '{}' created", action.name);
+        }
+        SnapshotAction::List => {
+            let snapshots = accessor.snapshot_list().await?;
+            if snapshots.len() <= 0 {
+                println!("No snapshots have been taken");
+            }
+            for snapshot in snapshots {
+                println!(
+                    "{:<24} created={}ms files={} bytes={}",
+                    snapshot.name, snapshot.created, snapshot.files, snapshot.size
+                );
+            }
+        }
+        SnapshotAction::Delete(action) => {
+            accessor.snapshot_delete(action.name.as_str()).await?;
+            println!("Snapshot '{}' deleted", action.name);
+        }
+        SnapshotAction::Restore(action) => {
+            accessor.snapshot_restore(action.name.as_str()).await?;
+            println!("Snapshot '{}' restored", action.name);
+        }
+        SnapshotAction::Diff(action) => {
+            let changes = accessor
+                .snapshot_diff(action.from.as_str(), action.to.as_ref().map(|a| a.as_str()))
+                .await?;
+            for change in changes {
+                println!("{}", change);
+            }
+        }
+        SnapshotAction::Clone(action) => {
+            accessor
+                .snapshot_clone(action.name.as_str(), action.target.as_str())
+                .await?;
+            println!(
+                "Snapshot '{}' cloned into '/{}'",
+                action.name, action.target
+            );
+        }
+    }
+
+    accessor.sync_all().await?;
+    Ok(())
+}
+
+pub async fn main_conflicts(
+    opts: OptsConflicts,
+    conf: ConfAte,
+    group: Option<String>,
+    session: AteSessionType,
+    no_auth: bool,
+) -> Result<(), AteError> {
+    let mut conf = conf.clone();
+    conf.configured_for(opts.configured_for);
+    conf.log_format.meta = opts.meta_format;
+    conf.log_format.data = opts.data_format;
+    conf.log_path = opts
+        .log_path
+        .as_ref()
+        .map(|a| shellexpand::tilde(a).to_string());
+
+    let (chain, _registry) = open_chain(
+        &conf,
+        opts.remote_name.clone(),
+        &opts.remote,
+        false,
+        &opts.configured_for,
+    )
+    .await?;
+
+    let accessor = FileAccessor::new(
+        chain,
+        group,
+        session,
+        TransactionScope::Full,
+        TransactionScope::Full,
+        no_auth,
+        false,
+    )
+    .await;
+    accessor.init(&accessor.session_context()).await?;
+
+    let conflicts = accessor.conflicts().await?;
+    if conflicts.len() <= 0 {
+        println!("No conflicts have been detected");
+    }
+    for conflict in conflicts {
+        println!(
+            "{} -> '{}' edited-on={} detected={}ms",
+            conflict.path, conflict.name, conflict.host, conflict.detected
+        );
+    }
+    Ok(())
+}
+
+pub async fn main_quota(
+    opts: OptsQuota,
+    conf: ConfAte,
+    group: Option<String>,
+    session: AteSessionType,
+    no_auth: bool,
+) -> Result<(), AteError> {
+    let mut conf = conf.clone();
+    conf.configured_for(opts.configured_for);
+    conf.log_format.meta = opts.meta_format;
+    conf.log_format.data = opts.data_format;
+    conf.log_path = opts
+        .log_path
+        .as_ref()
+        .map(|a| shellexpand::tilde(a).to_string());
+
+    let (chain, _registry) = open_chain(
+        &conf,
+        opts.remote_name.clone(),
+        &opts.remote,
+        false,
+        &opts.configured_for,
+    )
+    .await?;
+
+    let accessor = FileAccessor::new(
+        chain,
+        group,
+        session,
+        TransactionScope::Full,
+        TransactionScope::Full,
+        no_auth,
+        false,
+    )
+    .await;
+    accessor.init(&accessor.session_context()).await?;
+
+    match opts.action {
+        QuotaAction::Set(action) => {
+            let target = quota_target(&accessor, action.target.as_str()).await?;
+            let limit = QuotaLimit {
+                bytes: action.bytes,
+                inodes: action.inodes,
+            };
+            accessor.quota_set(target, Some(limit)).await?;
+            println!("Quota set on '{}'", action.target);
+        }
+        QuotaAction::Clear(action) => {
+            let target = quota_target(&accessor, action.target.as_str()).await?;
+            accessor.quota_set(target, None).await?;
+            println!("Quota removed from '{}'", action.target);
+        }
+        QuotaAction::List => {
+            let quotas = accessor.quota_list().await?;
+            if quotas.len() <= 0 {
+                println!("No quotas have been set");
+            }
+            let limit = |a: Option<u64>| match a {
+                Some(a) => a.to_string(),
+                None => "unlimited".to_string(),
+            };
+            for quota in quotas {
+                println!(
+                    "{:<24} bytes={}/{} inodes={}/{}",
+                    quota.target,
+                    quota.usage.bytes,
+                    limit(quota.limit.bytes),
+                    quota.usage.inodes,
+                    limit(quota.limit.inodes)
+                );
+            }
+        }
+    }
+
+    accessor.sync_all().await?;
+    Ok(())
+}
+
+/// Parses the target of a quota which is either a path or an owner
+async fn quota_target(accessor: &FileAccessor, target: &str) -> Result<QuotaTarget, AteError> {
+    let owner = |id: &str| {
+        id.parse::<u32>().map_err(|_| {
+            AteError::from(AteErrorKind::ServiceError(format!(
+                "invalid quota target ({})",
+                target
+            )))
+        })
+    };
+    if let Some(uid) = target.strip_prefix("uid:") {
+        return Ok(QuotaTarget::Owner(QuotaOwner::User(owner(uid)?)));
+    }
+    if let Some(gid) = target.strip_prefix("gid:") {
+        return Ok(QuotaTarget::Owner(QuotaOwner::Group(owner(gid)?)));
+    }
+    match accessor
+        .search(&accessor.session_context(), target)
+        .await?
+    {
+        Some(attr) => Ok(QuotaTarget::Directory(attr.ino)),
+        None => {
+            bail!(AteErrorKind::ServiceError(format!(
+                "the directory does not exist ({})",
+                target
+            )));
+        }
+    }
+}
+
+/// Opens the chain-of-trust that holds a file system, either from the local
+/// redo log or from a remote distributed commit log (the registry must be
+/// kept alive for as long as the chain is used)
+pub async fn open_chain(
+    conf: &ConfAte,
+    remote_name: Option<String>,
+    remote: &Url,
+    temp: bool,
+    configured_for: &ConfiguredFor,
+) -> Result<(Arc<Chain>, Option<Registry>), AteError> {
+    let builder = ChainBuilder::new(conf).await.temporal(temp);
+
+    // Create a progress bar loader
+    let mut progress_local = LoadProgress::new(std::io::stdout());
+    let mut progress_remote = LoadProgress::new(std::io::stdout());
+    progress_local.units = pbr::Units::Bytes;
+    progress_local.msg_done = "Downloading latest events from server...".to_string();
+    progress_remote.msg_done =
+        "Loaded the remote chain-of-trust, proceeding to mount the file system.".to_string();
+    print!("Loading the chain-of-trust...");
+
+    // We create a chain with a specific key (this is used for the file name it creates)
+    debug!("chain-init");
+    let mut registry = None;
+    let chain = match remote_name {
+        None => {
+            let trust = match configured_for {
+                ConfiguredFor::BestSecurity | ConfiguredFor::SmallestSize => {
+                    TrustMode::Centralized(CentralizedRole::Client)
+                }
+                _ => TrustMode::Distributed,
+            };
+            Ok(Arc::new(
+                Chain::new_ext(
+                    builder.clone(),
+                    ChainKey::from("root"),
+                    Some(Box::new(progress_local)),
+                    true,
+                    trust,
+                    trust,
+                )
+                .await?,
+            ))
+        }
+        Some(remote_name) => {
+            let reg = Registry::new(conf).await.temporal(temp);
+
+            let guard = reg
+                .open_ext(
+                    remote,
+                    &ChainKey::from(remote_name),
+                    false,
+                    progress_local,
+                    progress_remote,
+                )
+                .await?;
+            registry = Some(reg);
+            Ok(guard.as_arc())
+        }
+    };
+
+    // Perform specific error handling (otherwise let it propogate up)
+    let chain = match chain {
+        Ok(a) => a,
+        Err(ChainCreationError(ChainCreationErrorKind::ServerRejected(reason), _)) => {
+            match reason {
+                FatalTerminate::Denied { reason } => {
+                    println!("Access to this file system was denied by the server");
+                    println!("---");
+                    println!("{}", reason);
+                    std::process::exit(1);
+                }
+                _ => {
+                    bail!(AteErrorKind::ChainCreationError(
+                        ChainCreationErrorKind::ServerRejected(reason)
+                    ));
+                }
+            }
+        }
+        Err(err) => {
+            bail!(err);
+        }
+    };
+    Ok((chain, registry))
+}
@@ -5,4 +5,7 @@ pub mod helper;
 pub mod opts;
 pub mod umount;
 
+pub use helper::main_conflicts;
 pub use helper::main_mount;
+pub use helper::main_quota;
+pub use helper::main_snapshot;
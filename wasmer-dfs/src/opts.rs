@@ -101,6 +101,18 @@ pub enum SubCommand {
     /// to the service which will consume funds from the wallet.
     #[clap()]
     Mount(OptsMount),
+    /// Snapshots freeze the file system at a point in time so that it can later be
+    /// compared, restored or cloned (the file data is shared with the live files).
+    #[clap()]
+    Snapshot(OptsSnapshot),
+    /// Lists the files that were edited on more than one machine while one of them
+    /// was offline (the other version of each file is kept as a conflicted copy).
+    #[clap()]
+    Conflicts(OptsConflicts),
+    /// Quotas cap the number of bytes and files that can be written below a directory
+    /// or by a particular user or group.
+    #[clap()]
+    Quota(OptsQuota),
 }
 
 /// Mounts a particular directory as an ATE file system
@@ -154,6 +166,10 @@ pub struct OptsMount {
     /// Mount the file system in readonly mode (`ro` mount option), default is disable.
     #[clap(long)]
     pub read_only: bool,
+    /// Mounts a previously created snapshot rather than the live file system (this
+    /// implies the 'read-only' option)
+    #[clap(long)]
+    pub snapshot: Option<String>,
     /// Enable write back cache for buffered writes, default is disable.
     #[clap(short, long)]
     pub write_back: bool,
@@ -192,3 +208,195 @@ pub struct OptsMount {
     #[clap(long, default_value = "104857600")]
     pub compact_threshold_size: u64,
 }
+
+/// Manages the snapshots of an ATE file system
+#[derive(Parser)]
+pub struct OptsSnapshot {
+    /// Name of the file-system that holds the snapshots (e.g. myfs).
+    /// If this is not specified then the local chain-of-trust will be used instead
+    #[clap(long)]
+    pub remote_name: Option<String>,
+    /// URL where the data is remotely stored on a distributed commit log.
+    #[clap(short, long, default_value = "ws://wasmer.sh/db")]
+    pub remote: Url,
+    /// (Optional) Location of the local persistent redo log (e.g. ~/wasmer/fs")
+    #[clap(long)]
+    pub log_path: Option<String>,
+    /// User supplied passcode that the file-system was encrypted with (this implies
+    /// the 'no-auth' option as well)
+    #[clap(short, long)]
+    pub passcode: Option<String>,
+    /// Configure the log file for <raw>, <barebone>, <speed>, <compatibility>, <balanced> or <security>
+    #[clap(long, default_value = "speed")]
+    pub configured_for: ate::conf::ConfiguredFor,
+    /// Format of the metadata in the log file as <bincode>, <json> or <mpack>
+    #[clap(long, default_value = "bincode")]
+    pub meta_format: ate::spec::SerializationFormat,
+    /// Format of the data in the log file as <bincode>, <json> or <mpack>
+    #[clap(long, default_value = "bincode")]
+    pub data_format: ate::spec::SerializationFormat,
+    /// Action to perform on the snapshots
+    #[clap(subcommand)]
+    pub action: SnapshotAction,
+}
+
+#[derive(Parser)]
+pub enum SnapshotAction {
+    /// Creates a new snapshot of the file system as it is right now
+    #[clap()]
+    Create(OptsSnapshotCreate),
+    /// Lists all the snapshots that have been taken
+    #[clap()]
+    List,
+    /// Deletes a snapshot so that the history it holds on to can be compacted
+    #[clap()]
+    Delete(OptsSnapshotDelete),
+    /// Replaces the contents of the file system with those of a snapshot
+    #[clap()]
+    Restore(OptsSnapshotRestore),
+    /// Lists the files that differ between a snapshot and either another snapshot
+    /// or the live file system
+    #[clap()]
+    Diff(OptsSnapshotDiff),
+    /// Creates a writable copy of a snapshot as a new directory in the root of the
+    /// file system
+    #[clap()]
+    Clone(OptsSnapshotClone),
+}
+
+#[derive(Parser)]
+pub struct OptsSnapshotCreate {
+    /// Name of the snapshot
+    #[clap(index = 1)]
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct OptsSnapshotDelete {
+    /// Name of the snapshot to be deleted
+    #[clap(index = 1)]
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct OptsSnapshotRestore {
+    /// Name of the snapshot to be restored
+    #[clap(index = 1)]
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct OptsSnapshotDiff {
+    /// Name of the snapshot to compare from
+    #[clap(index = 1)]
+    pub from: String,
+    /// Name of the snapshot to compare to (if this is not specified then the live
+    /// file system is used)
+    #[clap(index = 2)]
+    pub to: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct OptsSnapshotClone {
+    /// Name of the snapshot to be cloned
+    #[clap(index = 1)]
+    pub name: String,
+    /// Name of the directory that the clone will be created as
+    #[clap(index = 2)]
+    pub target: String,
+}
+
+/// Lists the conflicted copies that exist in an ATE file system
+#[derive(Parser)]
+pub struct OptsConflicts {
+    /// Name of the file-system to be checked for conflicts (e.g. myfs).
+    /// If this is not specified then the local chain-of-trust will be used instead
+    #[clap(long)]
+    pub remote_name: Option<String>,
+    /// URL where the data is remotely stored on a distributed commit log.
+    #[clap(short, long, default_value = "ws://wasmer.sh/db")]
+    pub remote: Url,
+    /// (Optional) Location of the local persistent redo log (e.g. ~/wasmer/fs")
+    #[clap(long)]
+    pub log_path: Option<String>,
+    /// User supplied passcode that the file-system was encrypted with (this implies
+    /// the 'no-auth' option as well)
+    #[clap(short, long)]
+    pub passcode: Option<String>,
+    /// Configure the log file for <raw>, <barebone>, <speed>, <compatibility>, <balanced> or <security>
+    #[clap(long, default_value = "speed")]
+    pub configured_for: ate::conf::ConfiguredFor,
+    /// Format of the metadata in the log file as <bincode>, <json> or <mpack>
+    #[clap(long, default_value = "bincode")]
+    pub meta_format: ate::spec::SerializationFormat,
+    /// Format of the data in the log file as <bincode>, <json> or <mpack>
+    #[clap(long, default_value = "bincode")]
+    pub data_format: ate::spec::SerializationFormat,
+}
+
+/// Manages the quotas of an ATE file system
+#[derive(Parser)]
+pub struct OptsQuota {
+    /// Name of the file-system that holds the quotas (e.g. myfs).
+    /// If this is not specified then the local chain-of-trust will be used instead
+    #[clap(long)]
+    pub remote_name: Option<String>,
+    /// URL where the data is remotely stored on a distributed commit log.
+    #[clap(short, long, default_value = "ws://wasmer.sh/db")]
+    pub remote: Url,
+    /// (Optional) Location of the local persistent redo log (e.g. ~/wasmer/fs")
+    #[clap(long)]
+    pub log_path: Option<String>,
+    /// User supplied passcode that the file-system was encrypted with (this implies
+    /// the 'no-auth' option as well)
+    #[clap(short, long)]
+    pub passcode: Option<String>,
+    /// Configure the log file for <raw>, <barebone>, <speed>, <compatibility>, <balanced> or <security>
+    #[clap(long, default_value = "speed")]
+    pub configured_for: ate::conf::ConfiguredFor,
+    /// Format of the metadata in the log file as <bincode>, <json> or <mpack>
+    #[clap(long, default_value = "bincode")]
+    pub meta_format: ate::spec::SerializationFormat,
+    /// Format of the data in the log file as <bincode>, <json> or <mpack>
+    #[clap(long, default_value = "bincode")]
+    pub data_format: ate::spec::SerializationFormat,
+    /// Action to perform on the quotas
+    #[clap(subcommand)]
+    pub action: QuotaAction,
+}
+
+#[derive(Parser)]
+pub enum QuotaAction {
+    /// Places a quota on a directory or owner (the current usage is counted when the
+    /// quota is set)
+    #[clap()]
+    Set(OptsQuotaSet),
+    /// Removes the quota from a directory or owner
+    #[clap()]
+    Clear(OptsQuotaClear),
+    /// Lists all the quotas along with how much of them is used
+    #[clap()]
+    List,
+}
+
+#[derive(Parser)]
+pub struct OptsQuotaSet {
+    /// Path of the directory (e.g. /home) or the owner (e.g. uid:1000 or gid:100)
+    /// that the quota applies to
+    #[clap(index = 1)]
+    pub target: String,
+    /// Maximum number of bytes that can be stored
+    #[clap(long)]
+    pub bytes: Option<u64>,
+    /// Maximum number of files and directories that can be created
+    #[clap(long)]
+    pub inodes: Option<u64>,
+}
+
+#[derive(Parser)]
+pub struct OptsQuotaClear {
+    /// Path of the directory (e.g. /home) or the owner (e.g. uid:1000 or gid:100)
+    /// that the quota will be removed from
+    #[clap(index = 1)]
+    pub target: String,
+}
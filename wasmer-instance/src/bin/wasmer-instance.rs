@@ -93,7 +93,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>>
                     instance_authority = "wasmer.sh".to_string();
                 }
 
-                let compiled_modules = Arc::new(CachedCompiledModules::new(Some(solo.compiler_cache_path.clone())));
+                let compiled_modules = Arc::new(CachedCompiledModules::new_with_budget(Some(solo.compiler_cache_path.clone()), solo.compiler_cache_memory * 1024 * 1024).with_binary_budget(solo.binary_cache_memory * 1024 * 1024));
                 let instance_server = Server::new(
                     solo.db_url.clone(),
                     solo.auth_url.clone(),
@@ -25,6 +25,14 @@ pub struct OptsSessionServer {
     /// Location where cached compiled modules are stored
     #[clap(long, default_value = "~/wasmer/compiled")]
     pub compiler_cache_path: String,
+    /// Maximum amount of memory (in megabytes) used to hold compiled modules before
+    /// the least recently used ones are evicted (they are reloaded from the cache path)
+    #[clap(long, default_value = "512")]
+    pub compiler_cache_memory: usize,
+    /// Maximum amount of memory (in megabytes) used by each session to hold downloaded
+    /// binaries before the least recently used ones are spilled to the cache path
+    #[clap(long, default_value = "256")]
+    pub binary_cache_memory: usize,
     /// URL where the web data is remotely stored on a distributed commit log.
     #[clap(short, long, default_value = "ws://wasmer.sh/db")]
     pub db_url: url::Url,
@@ -31,6 +31,7 @@ use wasmer_os::fd::WeakFd;
 use wasmer_os::api::AsyncResult;
 use wasmer_os::fd::Fd;
 use wasmer_os::grammar::ast::Redirect;
+use wasmer_os::limits::ResourceLimits;
 
 use super::handler::SessionHandler;
 use super::handler::SessionTx;
@@ -135,7 +136,9 @@ impl Session
         // Create the job and context
         let exec_factory = self.console.exec_factory();
         let job = self.console.new_job().await?;
-        let ctx = exec_factory.create_context(self.console.new_spawn_context(&job));
+        let mut spawn = self.console.new_spawn_context(&job);
+        spawn.limits = spawn.limits.restrict(&self.export_limits(cmd.as_str()).await);
+        let ctx = exec_factory.create_context(spawn);
         let multiplexer = self.basics.multiplexer.clone();
 
         // Create the process factory that used by this process to create sub-processes
@@ -306,6 +309,26 @@ impl Session
         
     }
 
+    /// Returns the resource limits that the administrator attached to an exported binary
+    pub async fn export_limits(&self, binary: &str) -> ResourceLimits
+    {
+        self.basics
+            .service_instance
+            .exports
+            .iter()
+            .await
+            .ok()
+            .and_then(|mut iter| {
+                iter.find(|e| e.binary.eq_ignore_ascii_case(binary))
+            })
+            .map(|e| ResourceLimits {
+                max_memory: e.limits.max_memory,
+                max_fuel: e.limits.max_fuel,
+                max_fds: e.limits.max_fds,
+            })
+            .unwrap_or_default()
+    }
+
     pub async fn call(&mut self, call: InstanceCall, request: Vec<u8>, tx_reply: mpsc::Sender<InstanceReply>) -> Result<(), Box<dyn std::error::Error>>
     {
         // Create the callbacks
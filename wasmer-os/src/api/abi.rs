@@ -23,6 +23,7 @@ pub struct ConsoleRect {
 pub struct ReqwestOptions {
     pub gzip: bool,
     pub cors_proxy: Option<String>,
+    pub timeout_ms: Option<u64>,
 }
 
 pub struct ReqwestResponse {
@@ -35,6 +36,14 @@ pub struct ReqwestResponse {
     pub headers: Vec<(String, String)>,
 }
 
+pub struct ReqwestResponseStream {
+    /// Status and headers of the response (the data is always `None`)
+    pub head: ReqwestResponse,
+    /// Chunks of the response body as they arrive, the channel closes
+    /// once the body has been fully received
+    pub body: mpsc::Receiver<Result<Vec<u8>, u32>>,
+}
+
 // This ABI implements a set of emulated operating system
 // functions that are specific to a console session
 #[async_trait]
@@ -127,9 +136,59 @@ where
         data: Option<Vec<u8>>,
     ) -> AsyncResult<Result<ReqwestResponse, u32>>;
 
+    /// Performs a HTTP or HTTPS request where the request body is read from
+    /// the `body` channel and the response body is streamed back in chunks.
+    /// By default this buffers the whole request and response.
+    async fn reqwest_stream(
+        &self,
+        url: &str,
+        method: &str,
+        options: ReqwestOptions,
+        headers: Vec<(String, String)>,
+        body: Option<mpsc::Receiver<Vec<u8>>>,
+    ) -> Result<ReqwestResponseStream, u32> {
+        let data = match body {
+            Some(mut body) => {
+                let mut data = Vec::new();
+                while let Some(chunk) = body.recv().await {
+                    data.extend_from_slice(&chunk[..]);
+                }
+                Some(data)
+            }
+            None => None,
+        };
+
+        let mut head = self
+            .reqwest(url, method, options, headers, data)
+            .await
+            .ok_or(crate::err::ERR_ECONNABORTED)??;
+
+        let (tx_body, rx_body) = mpsc::channel(1);
+        if let Some(data) = head.data.take() {
+            let _ = tx_body.try_send(Ok(data));
+        }
+        Ok(ReqwestResponseStream {
+            head,
+            body: rx_body,
+        })
+    }
+
     /// Make a web socket connection to a particular URL
     async fn web_socket(&self, url: &str) -> Result<Box<dyn WebSocketAbi>, String>;
 
+    /// Make a web socket connection that offers sub-protocols and sends extra
+    /// headers with the upgrade request
+    async fn web_socket_with(
+        &self,
+        url: &str,
+        options: WebSocketOptions,
+    ) -> Result<Box<dyn WebSocketAbi>, String> {
+        if options.is_empty() == false {
+            return Err("web socket options are not supported on this system".to_string());
+        }
+        self.web_socket(url).await
+    }
+
     /// Open the WebGL
     async fn webgl(&self) -> Option<Box<dyn WebGlAbi>>;
 }
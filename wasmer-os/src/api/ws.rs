@@ -1,5 +1,9 @@
 use async_trait::async_trait;
 
+pub use wasmer_bus_ws::model::CloseFrame as WebSocketClose;
+pub use wasmer_bus_ws::model::ConnectOptions as WebSocketOptions;
+pub use wasmer_bus_ws::model::Frame as WebSocketFrame;
+
 // This ABI implements a general purpose web socket
 #[async_trait]
 pub trait WebSocketAbi {
@@ -9,9 +13,36 @@ pub trait WebSocketAbi {
 
     fn set_onmessage(&mut self, callback: Box<dyn Fn(Vec<u8>) + Send + 'static>);
 
+    /// Receives every frame (text, binary, ping, pong and close) rather than
+    /// just the binary messages, by default only binary frames are passed on
+    fn set_onframe(&mut self, callback: Box<dyn Fn(WebSocketFrame) + Send + 'static>) {
+        self.set_onmessage(Box::new(move |data| callback(WebSocketFrame::Binary(data))));
+    }
+
+    /// Sub-protocol that the server selected during the handshake
+    fn protocol(&self) -> Option<String> {
+        None
+    }
+
     #[cfg(feature = "async_ws")]
     async fn send(&mut self, data: Vec<u8>) -> Result<(), String>;
 
     #[cfg(not(feature = "async_ws"))]
     fn send(&mut self, data: Vec<u8>) -> Result<(), String>;
+
+    #[cfg(feature = "async_ws")]
+    async fn send_frame(&mut self, frame: WebSocketFrame) -> Result<(), String> {
+        match frame {
+            WebSocketFrame::Binary(data) => self.send(data).await,
+            frame => Err(format!("unsupported web socket frame - {}", frame)),
+        }
+    }
+
+    #[cfg(not(feature = "async_ws"))]
+    fn send_frame(&mut self, frame: WebSocketFrame) -> Result<(), String> {
+        match frame {
+            WebSocketFrame::Binary(data) => self.send(data),
+            frame => Err(format!("unsupported web socket frame - {}", frame)),
+        }
+    }
 }
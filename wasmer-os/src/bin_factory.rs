@@ -12,6 +12,8 @@ use std::collections::HashSet;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::cell::RefCell;
@@ -26,6 +28,8 @@ use super::err;
 use super::fs::TmpFileSystem;
 use crate::api::*;
 use crate::fd::*;
+use crate::limits::*;
+use crate::lru::*;
 
 #[derive(Derivative, Clone)]
 #[derivative(Debug)]
@@ -39,6 +43,7 @@ pub struct BinaryPackage {
     pub fs: TmpFileSystem,
     pub mappings: Vec<String>,
     pub envs: HashMap<String, String>,
+    pub limits: ResourceLimits,
 }
 
 impl BinaryPackage {
@@ -54,6 +59,7 @@ impl BinaryPackage {
             fs: TmpFileSystem::new(),
             mappings: Vec::new(),
             envs: HashMap::default(),
+            limits: ResourceLimits::default(),
         }
     }
 }
@@ -71,144 +77,414 @@ pub struct AliasConfig {
     pub mappings: Vec<String>,
     #[serde(default)]
     pub envs: HashMap<String, String>,
+    /// Default resource limits applied to this binary when it runs
+    #[serde(default)]
+    pub limits: ResourceLimits,
 }
 
-#[derive(Debug)]
+/// Default amount of memory that compiled modules may consume before the
+/// least recently used ones are evicted
+pub const DEFAULT_COMPILED_MODULES_BUDGET: usize = 512 * 1024 * 1024;
+/// Default amount of memory that downloaded binaries may consume before the
+/// least recently used ones are evicted
+pub const DEFAULT_BINARY_CACHE_BUDGET: usize = 256 * 1024 * 1024;
+
+/// Length of the content hash that prefixes every module spilled to disk
+const DISK_HASH_LEN: usize = 32;
+
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct CachedCompiledModules {
     #[cfg(feature = "sys")]
-    modules: RwLock<HashMap<String, Module>>,
+    #[derivative(Debug = "ignore")]
+    modules: RwLock<LruCache<Module>>,
+    /// Combined weight of the modules held by all the thread local caches
+    #[cfg(not(feature = "sys"))]
+    thread_local_weight: Arc<AtomicUsize>,
     cache_dir: Option<String>,
+    budget: usize,
+    binary_budget: usize,
+    metrics: CacheMetrics,
+}
+
+/// Modules can not be shared between threads when they are not `sys` modules
+/// thus every thread keeps its own cache, all of them together must stay
+/// within the budget of the compiled modules
+#[cfg(not(feature = "sys"))]
+struct ThreadLocalModules {
+    cache: LruCache<Module>,
+    weight: Arc<AtomicUsize>,
+}
+
+#[cfg(not(feature = "sys"))]
+impl Drop for ThreadLocalModules {
+    fn drop(&mut self) {
+        self.weight.fetch_sub(self.cache.weight(), Ordering::AcqRel);
+    }
 }
 
+#[cfg(not(feature = "sys"))]
 thread_local! {
-    static THREAD_LOCAL_CACHED_MODULES: std::cell::RefCell<HashMap<String, Module>> 
-        = RefCell::new(HashMap::new());
+    static THREAD_LOCAL_CACHED_MODULES: std::cell::RefCell<Option<ThreadLocalModules>>
+        = RefCell::new(None);
 }
 
 impl CachedCompiledModules
 {
     pub fn new(cache_dir: Option<String>) -> CachedCompiledModules {
+        Self::new_with_budget(cache_dir, DEFAULT_COMPILED_MODULES_BUDGET)
+    }
+
+    /// Creates a cache of compiled modules that will hold at most `budget` bytes
+    /// of modules in memory, anything evicted is reloaded from the cache directory
+    pub fn new_with_budget(cache_dir: Option<String>, budget: usize) -> CachedCompiledModules {
         let cache_dir = cache_dir.map(|a| shellexpand::tilde(&a).to_string());
         CachedCompiledModules {
             #[cfg(feature = "sys")]
-            modules: RwLock::new(HashMap::default()),
+            modules: RwLock::new(LruCache::new(budget)),
+            #[cfg(not(feature = "sys"))]
+            thread_local_weight: Arc::new(AtomicUsize::new(0)),
             cache_dir,
+            budget,
+            binary_budget: DEFAULT_BINARY_CACHE_BUDGET,
+            metrics: CacheMetrics::default(),
         }
     }
 
+    #[cfg(not(feature = "sys"))]
+    fn with_thread_local<R>(&self, funct: impl FnOnce(&mut LruCache<Module>) -> R) -> R {
+        THREAD_LOCAL_CACHED_MODULES.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let cache = cache.get_or_insert_with(|| ThreadLocalModules {
+                // (the budget is enforced across all the threads by `insert`)
+                cache: LruCache::new(usize::MAX),
+                weight: self.thread_local_weight.clone(),
+            });
+            funct(&mut cache.cache)
+        })
+    }
+
     pub async fn get_compiled_module(&self, store: &impl AsStoreRef, data_hash: &String, compiler: Compiler) -> Option<Module> {
-        let key = format!("{}-{}", data_hash, compiler);
-        
-        // fastest path
+        let key = cache_key(data_hash, compiler);
+
+        // fast path
+        #[cfg(feature = "sys")]
+        {
+            let mut cache = self.modules.write().await;
+            if let Some(module) = cache.get(&key).map(|m| m.clone()) {
+                self.metrics.hit();
+                return Some(module);
+            }
+        }
+        #[cfg(not(feature = "sys"))]
         {
-            let module = THREAD_LOCAL_CACHED_MODULES.with(|cache| {
-                let cache = cache.borrow();
+            let module = self.with_thread_local(|cache| {
                 cache.get(&key).map(|m| m.clone())
             });
             if let Some(module) = module {
+                self.metrics.hit();
                 return Some(module);
             }
         }
 
-        // fast path
+        // slow path
+        if let Some(module_bytes) = self.read_from_disk(&key) {
+            let weight = module_bytes.len();
+
+            // Load the module (if it fails to load then its treated as corrupt)
+            let module = match unsafe { Module::deserialize(store, &module_bytes[..]) } {
+                Ok(a) => a,
+                Err(err) => {
+                    warn!("compiled module ({}) failed to load - {}", key, err);
+                    self.metrics.disk_corrupt();
+                    self.remove_from_disk(&key);
+                    self.metrics.miss();
+                    return None;
+                }
+            };
+            self.metrics.disk_hit();
+
+            self.insert(key, &module, weight).await;
+            return Some(module);
+        }
+
+        // Not found
+        self.metrics.miss();
+        None
+    }
+
+    /// Adds a module that was just compiled to the cache, its weighed by the size
+    /// of its compiled artifact (the same bytes that are spilled to disk and
+    /// that a reload from disk is weighed by)
+    pub async fn set_compiled_module(&self, data_hash: String, compiler: Compiler, module: &Module) {
+        let key = cache_key(&data_hash, compiler);
+        let compiled_bytes = match module.serialize() {
+            Ok(a) => a,
+            Err(err) => {
+                warn!("failed to serialize the compiled module ({}) - {}", key, err);
+                return;
+            }
+        };
+        self.insert(key.clone(), module, compiled_bytes.len()).await;
+
+        // We should also attempt to store it in the cache directory so
+        // that it can be reloaded once its evicted from memory
+        self.write_to_disk(&key, &compiled_bytes[..]);
+    }
+
+    /// Returns the metrics of the compiled modules that are held in memory
+    pub async fn stats(&self) -> CacheStats {
         #[cfg(feature = "sys")]
         {
             let cache = self.modules.read().await;
-            if let Some(module) = cache.get(&key) {
-                THREAD_LOCAL_CACHED_MODULES.with(|cache| {
-                    let mut cache = cache.borrow_mut();
-                    cache.insert(key.clone(), module.clone());
-                });
-                return Some(module.clone());
-            }
+            return self.metrics.stats(cache.deref());
         }
+        #[cfg(not(feature = "sys"))]
+        self.with_thread_local(|cache| CacheStats {
+            weight: self.thread_local_weight.load(Ordering::Acquire),
+            budget: self.budget,
+            ..self.metrics.stats(cache)
+        })
+    }
 
-        // slow path
-        if let Some(cache_dir) = &self.cache_dir {
-            let path = std::path::Path::new(cache_dir.as_str()).join(format!("{}.bin", key).as_str());
-            if let Ok(data) = std::fs::read(path) {
-                let mut decoder = weezl::decode::Decoder::new(weezl::BitOrder::Msb, 8);
-                if let Ok(data) = decoder.decode(&data[..]) {
-                    let module_bytes = Bytes::from(data);
-
-                    // Load the module
-                    let module = unsafe { Module::deserialize(store, &module_bytes[..])
-                        .unwrap()
-                    };
-
-                    #[cfg(feature = "sys")]
-                    {
-                        let mut cache = self.modules.write().await;
-                        cache.insert(key.clone(), module.clone());
+    #[cfg(feature = "sys")]
+    async fn insert(&self, key: String, module: &Module, weight: usize) {
+        let mut cache = self.modules.write().await;
+        let evicted = cache.insert(key, module.clone(), weight);
+        self.on_evicted(evicted);
+    }
+
+    /// Inserts the module into the cache of this thread and then evicts the least
+    /// recently used modules of this thread until all the threads together are
+    /// back within the budget (which may include the module that was just added)
+    #[cfg(not(feature = "sys"))]
+    async fn insert(&self, key: String, module: &Module, weight: usize) {
+        let shared = self.thread_local_weight.clone();
+        let budget = self.budget;
+        let evicted = self.with_thread_local(|cache| {
+            let before = cache.weight();
+            cache.insert(key, module.clone(), weight);
+            let after = cache.weight();
+            if after >= before {
+                shared.fetch_add(after - before, Ordering::AcqRel);
+            } else {
+                shared.fetch_sub(before - after, Ordering::AcqRel);
+            }
+
+            let mut evicted = Vec::new();
+            while shared.load(Ordering::Acquire) > budget {
+                match cache.pop_oldest() {
+                    Some((key, module, weight)) => {
+                        shared.fetch_sub(weight, Ordering::AcqRel);
+                        evicted.push((key, module));
                     }
-                    THREAD_LOCAL_CACHED_MODULES.with(|cache| {
-                        let mut cache = cache.borrow_mut();
-                        cache.insert(key.clone(), module.clone());
-                    });
-                    return Some(module);
+                    None => break,
                 }
             }
+            evicted
+        });
+        self.on_evicted(evicted);
+    }
+
+    fn on_evicted(&self, evicted: Vec<(String, Module)>) {
+        if evicted.len() > 0 {
+            for (key, _) in evicted.iter() {
+                debug!("evicted compiled module ({}) from memory", key);
+            }
+            self.metrics.evicted(evicted.len());
         }
+    }
 
-        // Not found
-        None
+    fn disk_path(&self, key: &str) -> Option<std::path::PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|cache_dir| std::path::Path::new(cache_dir.as_str()).join(format!("{}.bin", key).as_str()))
     }
 
-    pub async fn set_compiled_module(&self, data_hash: String, compiler: Compiler, module: &Module) {
-        let key = format!("{}-{}", data_hash, compiler);
-        
-        // Add the module to the local thread cache
-        THREAD_LOCAL_CACHED_MODULES.with(|cache| {
-            let mut cache = cache.borrow_mut();
-            let cache = cache.deref_mut();
-            cache.insert(key.clone(), module.clone());
-        });
+    /// Reads a module that was previously spilled to disk and validates that
+    /// its content hash is correct (corrupt files are removed)
+    fn read_from_disk(&self, key: &str) -> Option<Bytes> {
+        let path = self.disk_path(key)?;
+        match read_spilled(&path)? {
+            Ok(data) => Some(data),
+            Err(err) => {
+                warn!("compiled module ({}) {}", key, err);
+                self.metrics.disk_corrupt();
+                let _ = std::fs::remove_file(path);
+                None
+            }
+        }
+    }
 
-        // Serialize the compiled module into bytes and insert it into the cache
-        #[cfg(feature = "sys")]
-        {
-            let mut cache = self.modules.write().await;
-            cache.insert(key.clone(), module.clone());
+    fn write_to_disk(&self, key: &str, compiled_bytes: &[u8]) {
+        if let Some(path) = self.disk_path(key) {
+            if write_spilled(&path, compiled_bytes) {
+                self.metrics.disk_write();
+            }
+        }
+    }
+
+    fn remove_from_disk(&self, key: &str) {
+        if let Some(path) = self.disk_path(key) {
+            let _ = std::fs::remove_file(path);
         }
-        
-        // We should also attempt to store it in the cache directory
-        if let Some(cache_dir) = &self.cache_dir {
-            let compiled_bytes = module.serialize().unwrap();
-
-            let path = std::path::Path::new(cache_dir.as_str()).join(format!("{}.bin", key).as_str());
-            let _ = std::fs::create_dir_all(path.parent().unwrap().clone());
-            let mut encoder = weezl::encode::Encoder::new(weezl::BitOrder::Msb, 8);
-            if let Ok(compiled_bytes) = encoder.encode(&compiled_bytes[..]) {
-                let _ = std::fs::write(path, &compiled_bytes[..]);
+    }
+
+    /// Directory that evicted entries are spilled to (if any)
+    pub fn cache_dir(&self) -> Option<&str> {
+        self.cache_dir.as_ref().map(|a| a.as_str())
+    }
+
+    /// Amount of memory that the downloaded binaries of each `BinFactory`
+    /// created over this cache may consume
+    pub fn binary_budget(&self) -> usize {
+        self.binary_budget
+    }
+
+    /// Sets the amount of memory that the downloaded binaries of each
+    /// `BinFactory` may consume before they are spilled to disk
+    pub fn with_binary_budget(mut self, budget: usize) -> Self {
+        self.binary_budget = budget;
+        self
+    }
+}
+
+/// Reads a file that was spilled to disk by `write_spilled`, returns `None` if
+/// the file does not exist and an error if it fails validation
+fn read_spilled(path: &std::path::Path) -> Option<Result<Bytes, String>> {
+    let data = std::fs::read(path).ok()?;
+    if data.len() < DISK_HASH_LEN || Sha256::digest(&data[DISK_HASH_LEN..])[..] != data[..DISK_HASH_LEN] {
+        return Some(Err("failed validation".to_string()));
+    }
+
+    let mut decoder = weezl::decode::Decoder::new(weezl::BitOrder::Msb, 8);
+    Some(
+        decoder
+            .decode(&data[DISK_HASH_LEN..])
+            .map(Bytes::from)
+            .map_err(|err| format!("failed to decompress - {}", err)),
+    )
+}
+
+/// Spills data to disk prefixed with a hash of its contents, the file is written
+/// to a temporary file first so that readers never see a partial write
+fn write_spilled(path: &std::path::Path, data: &[u8]) -> bool {
+    let _ = std::fs::create_dir_all(path.parent().unwrap());
+    let mut encoder = weezl::encode::Encoder::new(weezl::BitOrder::Msb, 8);
+    if let Ok(compressed) = encoder.encode(data) {
+        let mut data = Vec::with_capacity(DISK_HASH_LEN + compressed.len());
+        data.extend_from_slice(&Sha256::digest(&compressed[..])[..]);
+        data.extend_from_slice(&compressed[..]);
+
+        let temp_path = path.with_extension(format!("tmp{}", fastrand::u32(..)));
+        if std::fs::write(&temp_path, &data[..]).is_ok() {
+            if std::fs::rename(&temp_path, path).is_ok() {
+                return true;
             }
+            let _ = std::fs::remove_file(&temp_path);
         }
     }
+    false
 }
 
 #[derive(Debug, Clone)]
 pub struct BinFactory {
     pub wax: Arc<Mutex<HashSet<String>>>,
     pub alias: Arc<RwLock<HashMap<String, Option<AliasConfig>>>>,
-    pub cache: Arc<RwLock<HashMap<String, Option<BinaryPackage>>>>,
+    pub cache: Arc<RwLock<LruCache<Option<BinaryPackage>>>>,
+    pub cache_metrics: Arc<CacheMetrics>,
+    /// Binaries that were evicted from memory and spilled to disk (name to hash)
+    pub spilled: Arc<Mutex<HashMap<String, String>>>,
     pub compiled_modules: Arc<CachedCompiledModules>,
 }
 
 impl BinFactory {
     pub fn new(
         compiled_modules: Arc<CachedCompiledModules>,
+    ) -> BinFactory {
+        let budget = compiled_modules.binary_budget();
+        Self::new_with_budget(compiled_modules, budget)
+    }
+
+    /// Creates a binary factory that will keep at most `budget` bytes of
+    /// downloaded binaries in memory, the rest are spilled to the cache
+    /// directory of the compiled modules (when there is one)
+    pub fn new_with_budget(
+        compiled_modules: Arc<CachedCompiledModules>,
+        budget: usize,
     ) -> BinFactory {
         BinFactory {
             wax: Arc::new(Mutex::new(HashSet::new())),
             alias: Arc::new(RwLock::new(HashMap::new())),
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(RwLock::new(LruCache::new(budget))),
+            cache_metrics: Arc::new(CacheMetrics::default()),
+            spilled: Arc::new(Mutex::new(HashMap::new())),
             compiled_modules,
         }
     }
 
+    fn spill_path(&self, hash: &str) -> Option<std::path::PathBuf> {
+        self.compiled_modules
+            .cache_dir()
+            .map(|cache_dir| std::path::Path::new(cache_dir).join("bin").join(format!("{}.wasm", hash)))
+    }
+
+    /// Writes binaries that were evicted from memory to disk so that they
+    /// do not need to be fetched again
+    fn spill(&self, evicted: Vec<(String, Option<BinaryPackage>)>) {
+        self.cache_metrics.evicted(evicted.len());
+        for (name, data) in evicted {
+            let data = match data {
+                Some(a) => a,
+                None => continue,
+            };
+            let path = match self.spill_path(&data.hash) {
+                Some(a) => a,
+                None => continue,
+            };
+            if path.exists() || write_spilled(&path, &data.data[..]) {
+                debug!("spilled binary ({}) to disk", name);
+                self.cache_metrics.disk_write();
+                self.spilled.lock().unwrap().insert(name, data.hash);
+            }
+        }
+    }
+
+    /// Reloads a binary that was spilled to disk, the binary must still
+    /// match the hash it was spilled with
+    fn unspill(&self, name: &str) -> Option<BinaryPackage> {
+        let hash = self.spilled.lock().unwrap().remove(name)?;
+        let path = self.spill_path(&hash)?;
+        let data = match read_spilled(&path)? {
+            Ok(a) => BinaryPackage::new(a),
+            Err(err) => {
+                warn!("spilled binary ({}) {}", name, err);
+                self.cache_metrics.disk_corrupt();
+                let _ = std::fs::remove_file(path);
+                return None;
+            }
+        };
+        if data.hash != hash {
+            warn!("spilled binary ({}) does not match its hash", name);
+            self.cache_metrics.disk_corrupt();
+            let _ = std::fs::remove_file(path);
+            return None;
+        }
+        self.cache_metrics.disk_hit();
+        Some(data)
+    }
+
+    /// Returns the metrics of the binary cache
+    pub async fn stats(&self) -> CacheStats {
+        let cache = self.cache.read().await;
+        self.cache_metrics.stats(cache.deref())
+    }
+
     pub async fn clear(&self) {
         self.wax.lock().unwrap().clear();
         self.alias.write().await.clear();
         self.cache.write().await.clear();
+        self.spilled.lock().unwrap().clear();
     }
 
     pub async fn get(&self, name: &str, mut stderr: Fd) -> Option<BinaryPackage> {
@@ -216,11 +492,13 @@ impl BinFactory {
 
         // Fast path
         {
-            let cache = self.cache.read().await;
+            let mut cache = self.cache.write().await;
             if let Some(data) = cache.get(&name) {
+                self.cache_metrics.hit();
                 return data.clone();
             }
         }
+        self.cache_metrics.miss();
 
         // Tell the console we are fetching
         {
@@ -246,13 +524,26 @@ impl BinFactory {
             return data.clone();
         }
 
+        // It may have been spilled to disk
+        if let Some(data) = self.unspill(name.as_str()) {
+            let weight = data.data.len();
+            let evicted = cache.insert(name, Some(data.clone()), weight);
+            self.spill(evicted);
+            if stderr.is_tty() {
+                stderr.write_clear_line().await;
+            }
+            return Some(data);
+        }
+
         // First just try to find it
         if let Ok(data) = fetch_file(format!("/bin/{}.wasm", name).as_str())
             .await
             .unwrap()
         {
             let data = BinaryPackage::new(Bytes::from(data));
-            cache.insert(name, Some(data.clone()));
+            let weight = data.data.len();
+            let evicted = cache.insert(name, Some(data.clone()), weight);
+            self.spill(evicted);
             if stderr.is_tty() {
                 stderr.write_clear_line().await;
             }
@@ -260,7 +551,9 @@ impl BinFactory {
         }
 
         // NAK
-        cache.insert(name, None);
+        let weight = name.len();
+        let evicted = cache.insert(name, None, weight);
+        self.spill(evicted);
         if stderr.is_tty() {
             stderr.write_clear_line().await;
         }
@@ -325,9 +618,78 @@ fn fetch_file(path: &str) -> AsyncResult<Result<Vec<u8>, u32>> {
     system.fetch_file(path)
 }
 
+/// Modules compiled with metering are not compatible with those without
+/// so they are stored under a different key
+fn cache_key(data_hash: &String, compiler: Compiler) -> String {
+    #[cfg(feature = "metering")]
+    return format!("{}-{}-metered", data_hash, compiler);
+    #[cfg(not(feature = "metering"))]
+    return format!("{}-{}", data_hash, compiler);
+}
+
 pub fn hash_of_binary(data: &Bytes) -> String {
     let mut hasher = Sha256::default();
     hasher.update(data.as_ref());
     let hash = hasher.finalize();
     hex::encode(&hash[..])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("wasmer-os-bins-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_spilled_files_round_trip() {
+        let dir = temp_dir();
+        let path = dir.join("a.bin");
+        assert!(read_spilled(&path).is_none());
+
+        assert!(write_spilled(&path, b"hello world"));
+        assert_eq!(read_spilled(&path), Some(Ok(Bytes::from_static(b"hello world"))));
+
+        // Any change to the file makes it fail validation
+        let mut data = std::fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        std::fs::write(&path, &data[..]).unwrap();
+        assert!(matches!(read_spilled(&path), Some(Err(_))));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_evicted_binaries_are_spilled_and_reloaded() {
+        let dir = temp_dir();
+        let modules = Arc::new(CachedCompiledModules::new(Some(dir.to_string_lossy().to_string())));
+        let bins = BinFactory::new_with_budget(modules, 10);
+
+        let a = BinaryPackage::new(Bytes::from_static(b"12345678"));
+        let b = BinaryPackage::new(Bytes::from_static(b"87654321"));
+        {
+            let mut cache = bins.cache.write().await;
+            let evicted = cache.insert("a".to_string(), Some(a.clone()), a.data.len());
+            bins.spill(evicted);
+            let evicted = cache.insert("b".to_string(), Some(b.clone()), b.data.len());
+            bins.spill(evicted);
+            assert!(cache.contains_key("a") == false);
+        }
+
+        let reloaded = bins.unspill("a").unwrap();
+        assert_eq!(reloaded.hash, a.hash);
+        assert_eq!(reloaded.data, a.data);
+        assert!(bins.unspill("a").is_none());
+
+        let stats = bins.stats().await;
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.disk_writes, 1);
+        assert_eq!(stats.disk_hits, 1);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,52 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::eval::EvalContext;
+use crate::eval::ExecResponse;
+use crate::lru::CacheStats;
+use crate::stdio::*;
+
+fn format_stats(name: &str, stats: &CacheStats) -> String {
+    format!(
+        "{}: entries={} weight={}/{} hits={} misses={} evictions={} disk_hits={} disk_writes={} disk_corrupt={}\r\n",
+        name,
+        stats.entries,
+        stats.weight,
+        stats.budget,
+        stats.hits,
+        stats.misses,
+        stats.evictions,
+        stats.disk_hits,
+        stats.disk_writes,
+        stats.disk_corrupt
+    )
+}
+
+pub(super) fn cache(
+    args: &[String],
+    ctx: EvalContext,
+    mut stdio: Stdio,
+) -> Pin<Box<dyn Future<Output = ExecResponse> + Send>> {
+    if args.len() > 1 {
+        return Box::pin(async move {
+            let _ = stdio
+                .stderr
+                .write(format!("cache: too many arguments\r\n").as_bytes())
+                .await;
+            ExecResponse::Immediate(ctx, 1)
+        });
+    }
+    Box::pin(async move {
+        let binaries = ctx.bins.stats().await;
+        let modules = ctx.bins.compiled_modules.stats().await;
+        let _ = stdio
+            .stdout
+            .write(format_stats("binaries", &binaries).as_bytes())
+            .await;
+        let _ = stdio
+            .stdout
+            .write(format_stats("compiled", &modules).as_bytes())
+            .await;
+        ExecResponse::Immediate(ctx, 0)
+    })
+}
@@ -1,4 +1,5 @@
 mod about;
+mod cache;
 mod cd;
 mod exit;
 mod export;
@@ -8,12 +9,14 @@ mod pwd;
 mod readonly;
 mod reset;
 mod source;
+mod ulimit;
 mod umount;
 mod unset;
 mod wax;
 mod call;
 
 use about::*;
+use cache::*;
 use cd::*;
 use exit::*;
 use export::*;
@@ -23,6 +26,7 @@ use pwd::*;
 use readonly::*;
 use reset::*;
 use source::*;
+use ulimit::*;
 use umount::*;
 use unset::*;
 use wax::*;
@@ -53,10 +57,12 @@ impl Builtins {
         b.insert("unset", unset);
         b.insert("help", help);
         b.insert("about", about);
+        b.insert("cache", cache);
         b.insert("source", source);
         b.insert("pwd", pwd);
         b.insert("reset", reset);
         b.insert("mount", mount);
+        b.insert("ulimit", ulimit);
         b.insert("umount", umount);
         b.insert("unmount", umount);
         b.insert("wax", wax);
@@ -0,0 +1,87 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::err;
+use crate::eval::EvalContext;
+use crate::eval::ExecResponse;
+use crate::limits::*;
+use crate::stdio::*;
+use crate::tty::Tty;
+
+pub(super) fn ulimit(
+    args: &[String],
+    mut ctx: EvalContext,
+    stdio: Stdio,
+) -> Pin<Box<dyn Future<Output = ExecResponse> + Send>> {
+    if args.len() <= 1 || args[1] == "-a" {
+        let limits = ctx.limits;
+        return Box::pin(async move {
+            let _ = stdio.println(format!("memory (bytes)  (-m) {}\r\n", show(limits.max_memory))).await;
+            let _ = stdio.println(format!("cpu (fuel)      (-t) {}\r\n", show(limits.max_fuel))).await;
+            let _ = stdio.println(format!("open files      (-n) {}\r\n", show(limits.max_fds))).await;
+            ExecResponse::Immediate(ctx, 0)
+        });
+    }
+
+    // Limits can only ever be lowered (which means processes can not escape
+    // the limits that were applied to them by the console)
+    let mut requested = ResourceLimits::default();
+    let mut args = args[1..].iter();
+    while let Some(flag) = args.next() {
+        let val = match args.next() {
+            Some(a) => a.clone(),
+            None => {
+                return error(ctx, stdio, format!("ulimit: {}: option requires an argument\r\n", flag));
+            }
+        };
+        if val == "unlimited" {
+            continue;
+        }
+        let ret = match flag.as_str() {
+            "-m" => parse_memory(val.as_str()).map(|a| requested.max_memory = Some(a)),
+            "-t" => val
+                .parse::<u64>()
+                .map(|a| requested.max_fuel = Some(a))
+                .map_err(|err| err.to_string()),
+            "-n" => val
+                .parse::<u32>()
+                .map(|a| requested.max_fds = Some(a))
+                .map_err(|err| err.to_string()),
+            _ => {
+                return error(ctx, stdio, Tty::ULIMIT_USAGE.replace("\n", "\r\n"));
+            }
+        };
+        if let Err(err) = ret {
+            return error(ctx, stdio, format!("ulimit: {}: {}\r\n", flag, err));
+        }
+    }
+    ctx.limits = ctx.limits.restrict(&requested);
+
+    Box::pin(async move { ExecResponse::Immediate(ctx, 0) })
+}
+
+/// Plain numbers are in kilobytes (like bash) otherwise a unit suffix can be used
+fn parse_memory(val: &str) -> Result<u64, String> {
+    match val.parse::<u64>() {
+        Ok(kb) => kb
+            .checked_mul(1024)
+            .ok_or_else(|| format!("invalid size '{}' - overflow", val)),
+        Err(_) => parse_size(val),
+    }
+}
+
+fn show<T: ToString>(val: Option<T>) -> String {
+    val.map(|a| a.to_string())
+        .unwrap_or_else(|| "unlimited".to_string())
+}
+
+fn error(
+    ctx: EvalContext,
+    stdio: Stdio,
+    msg: String,
+) -> Pin<Box<dyn Future<Output = ExecResponse> + Send>> {
+    Box::pin(async move {
+        let _ = stdio.eprintln(msg).await;
+        ExecResponse::Immediate(ctx, err::ERR_EINVAL)
+    })
+}
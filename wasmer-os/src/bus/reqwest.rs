@@ -1,12 +1,33 @@
 #![allow(dead_code)]
+use derivative::Derivative;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use tokio::select;
+use tokio::sync::mpsc;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
+use wasmer_bus::abi::SerializationFormat;
 use wasmer_bus_reqwest::api;
 use wasmer_bus_reqwest::prelude::*;
+use wasmer_vbus::BusDataFormat;
+use wasmer_vbus::BusInvocationEvent;
 use wasmer_vbus::InstantInvocation;
+use wasmer_vbus::VirtualBusError;
+use wasmer_vbus::VirtualBusInvocation;
+use wasmer_vbus::VirtualBusInvokable;
 use wasmer_vbus::VirtualBusInvoked;
 
+use super::*;
 use crate::api::*;
+use crate::common::MAX_MPSC;
+use crate::err;
+
+/// Number of request body chunks that can be queued before writes block
+const MAX_UPLOAD_BACKLOG: usize = 16;
 
 pub fn reqwest(
     system: System,
@@ -21,6 +42,7 @@ pub fn reqwest(
     let options = ReqwestOptions {
         gzip: options.gzip,
         cors_proxy: options.cors_proxy.clone(),
+        timeout_ms: options.timeout_ms,
     };
 
     debug!("executing HTTP {}", method);
@@ -29,15 +51,7 @@ pub fn reqwest(
     let result = system.spawn_shared(move || async move {
         if let Some(a) = ret.await {
             match a {
-                Ok(a) => Ok(Response {
-                    pos: a.pos,
-                    data: a.data,
-                    ok: a.ok,
-                    redirected: a.redirected,
-                    status: a.status,
-                    status_text: a.status_text,
-                    headers: a.headers,
-                }),
+                Ok(a) => Ok(conv_response(a)),
                 Err(err) => Err(err as u32),
             }
         } else {
@@ -47,3 +61,241 @@ pub fn reqwest(
 
     Box::new(InstantInvocation::call(Box::new(result)))
 }
+
+fn conv_response(a: ReqwestResponse) -> Response {
+    Response {
+        pos: a.pos,
+        data: a.data,
+        ok: a.ok,
+        redirected: a.redirected,
+        status: a.status,
+        status_text: a.status_text,
+        headers: a.headers,
+    }
+}
+
+#[derive(Debug)]
+enum StreamEvent {
+    Head(Response),
+    Data(Vec<u8>),
+    Finish(Result<(), i32>),
+}
+
+pub fn reqwest_stream(
+    system: System,
+    request: api::ReqwestStreamRequest,
+) -> ReqwestStream {
+    let url = request.url;
+    let method = request.method;
+    let headers = request.headers;
+    let timeout_ms = request.options.timeout_ms;
+    let options = ReqwestOptions {
+        gzip: request.options.gzip,
+        cors_proxy: request.options.cors_proxy,
+        timeout_ms,
+    };
+
+    // Construct the channels
+    let (tx_keepalive, mut rx_keepalive) = mpsc::channel::<()>(1);
+    let (tx_cancel, mut rx_cancel) = mpsc::channel::<()>(1);
+    let (tx_event, rx_event) = mpsc::channel::<StreamEvent>(MAX_MPSC);
+
+    // The request body is fed through a channel, if we are not uploading
+    // then the sender is dropped straight away which ends the body
+    let (tx_upload, rx_upload) = match (request.upload, request.body) {
+        (false, None) => (None, None),
+        (upload, body) => {
+            let (tx, rx) = mpsc::channel::<Vec<u8>>(MAX_UPLOAD_BACKLOG);
+            if let Some(body) = body {
+                let _ = tx.try_send(body);
+            }
+            (if upload { Some(tx) } else { None }, Some(rx))
+        }
+    };
+
+    debug!("executing streamed HTTP {}", method);
+
+    system.fork_shared(move || async move {
+        let tx_event2 = tx_event.clone();
+        let work = async move {
+            let mut stream = system
+                .reqwest_stream(&url, &method, options, headers, rx_upload)
+                .await?;
+            let _ = tx_event2.send(StreamEvent::Head(conv_response(stream.head))).await;
+            while let Some(chunk) = stream.body.recv().await {
+                trace!("reqwest recv {} bytes", chunk.as_ref().map(|a| a.len()).unwrap_or(0));
+                let _ = tx_event2.send(StreamEvent::Data(chunk?)).await;
+            }
+            Ok::<(), u32>(())
+        };
+        let timeout = async move {
+            match timeout_ms {
+                Some(ms) => { system.sleep(ms as u128).await; },
+                None => std::future::pending::<()>().await,
+            }
+        };
+
+        let ret = select! {
+            ret = work => ret,
+            _ = timeout => {
+                debug!("reqwest timed out after {}ms", timeout_ms.unwrap_or_default());
+                Err(err::ERR_ETIMEDOUT)
+            }
+            _ = rx_cancel.recv() => {
+                debug!("reqwest cancelled");
+                Err(err::ERR_ECONNABORTED)
+            }
+            _ = rx_keepalive.recv() => {
+                trace!("reqwest no longer alive");
+                return;
+            }
+        };
+        let _ = tx_event.send(StreamEvent::Finish(ret.map_err(|err| err as i32))).await;
+    });
+
+    ReqwestStream {
+        tx_keepalive,
+        tx_cancel,
+        tx_upload: Mutex::new(tx_upload),
+        rx_event,
+    }
+}
+
+#[derive(Debug)]
+pub struct ReqwestStream {
+    #[allow(dead_code)]
+    tx_keepalive: mpsc::Sender<()>,
+    tx_cancel: mpsc::Sender<()>,
+    tx_upload: Mutex<Option<mpsc::Sender<Vec<u8>>>>,
+    rx_event: mpsc::Receiver<StreamEvent>,
+}
+
+fn encode_callback<T>(data: T) -> BusInvocationEvent
+where T: Serialize
+{
+    BusInvocationEvent::Callback {
+        topic_hash: type_name_hash::<T>(),
+        format: BusDataFormat::Bincode,
+        data: match SerializationFormat::Bincode.serialize(data) {
+            Ok(d) => d,
+            Err(err) => {
+                debug!("failed to serialize reqwest callback");
+                return BusInvocationEvent::Fault { fault: conv_error_back(err) };
+            }
+        }
+    }
+}
+
+impl VirtualBusInvocation
+for ReqwestStream
+{
+    fn poll_event(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<BusInvocationEvent> {
+        match self.rx_event.poll_recv(cx) {
+            Poll::Ready(Some(StreamEvent::Head(head))) => {
+                Poll::Ready(encode_callback(api::ReqwestStreamHeadCallback(head)))
+            }
+            Poll::Ready(Some(StreamEvent::Data(data))) => {
+                Poll::Ready(encode_callback(api::ReqwestStreamReceiveCallback(data)))
+            }
+            Poll::Ready(Some(StreamEvent::Finish(ret))) => {
+                Poll::Ready(encode_callback(api::ReqwestStreamFinishCallback(ret)))
+            }
+            Poll::Ready(None) => {
+                Poll::Ready(BusInvocationEvent::Fault { fault: VirtualBusError::Aborted })
+            }
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
+
+impl VirtualBusInvokable
+for ReqwestStream {
+    /// Invokes a service within this instance
+    fn invoke(
+        &self,
+        topic_hash: u128,
+        format: BusDataFormat,
+        buf: Vec<u8>,
+    ) -> Box<dyn VirtualBusInvoked> {
+        if topic_hash == type_name_hash::<api::ReqwestStreamWriteRequest>() {
+            let data = match decode_request::<api::ReqwestStreamWriteRequest>(
+                format,
+                buf,
+            ) {
+                Ok(a) => a.data,
+                Err(err) => {
+                    return Box::new(InstantInvocation::fault(conv_error_back(err)));
+                }
+            };
+            let data_len = data.len();
+
+            let tx = match self.tx_upload.lock().unwrap().clone() {
+                Some(a) => a,
+                None => {
+                    debug!("reqwest body is not being uploaded");
+                    let ret: Result<usize, i32> = Err(err::ERR_EPIPE as i32);
+                    return Box::new(encode_instant_response(BusDataFormat::Bincode, &ret));
+                }
+            };
+            match tx.try_send(data) {
+                Ok(()) => {
+                    trace!("reqwest sent {} bytes", data_len);
+                    let ret: Result<usize, i32> = Ok(data_len);
+                    Box::new(encode_instant_response(BusDataFormat::Bincode, &ret))
+                },
+                Err(mpsc::error::TrySendError::Full(data)) => {
+                    Box::new(DelayedWrite {
+                        data_len,
+                        fut: Box::pin(async move {
+                            tx.send(data).await
+                        })
+                    })
+                },
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    debug!("reqwest body is closed");
+                    let ret: Result<usize, i32> = Err(err::ERR_EPIPE as i32);
+                    Box::new(encode_instant_response(BusDataFormat::Bincode, &ret))
+                }
+            }
+        } else if topic_hash == type_name_hash::<api::ReqwestStreamCloseRequest>() {
+            trace!("reqwest body closed");
+            self.tx_upload.lock().unwrap().take();
+            Box::new(encode_instant_response(BusDataFormat::Bincode, &()))
+        } else if topic_hash == type_name_hash::<api::ReqwestStreamCancelRequest>() {
+            let _ = self.tx_cancel.try_send(());
+            Box::new(encode_instant_response(BusDataFormat::Bincode, &()))
+        } else {
+            debug!("reqwest invalid topic (hash={})", topic_hash);
+            Box::new(InstantInvocation::fault(VirtualBusError::InvalidTopic))
+        }
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct DelayedWrite
+{
+    data_len: usize,
+    #[derivative(Debug = "ignore")]
+    fut: Pin<Box<dyn Future<Output = Result<(), mpsc::error::SendError<Vec<u8>>>> + Send>>
+}
+
+impl VirtualBusInvoked
+for DelayedWrite
+{
+    fn poll_invoked(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Box<dyn VirtualBusInvocation + Sync>, VirtualBusError>>
+    {
+        let fut = self.fut.as_mut();
+        match fut.poll(cx) {
+            Poll::Ready(Ok(())) => {
+                let ret: Result<usize, i32> = Ok(self.data_len);
+                Poll::Ready(Ok(Box::new(encode_instant_response(BusDataFormat::Bincode, &ret))))
+            },
+            Poll::Ready(Err(_)) => {
+                let ret: Result<usize, i32> = Err(err::ERR_EPIPE as i32);
+                Poll::Ready(Ok(Box::new(encode_instant_response(BusDataFormat::Bincode, &ret))))
+            },
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
@@ -96,6 +96,20 @@ for StandardBus
                     )
                 )
             }
+            h if h == type_name_hash::<wasmer_bus_ws::api::SocketBuilderConnectWithRequest>() =>
+            {
+                let request = match format.deserialize(buf) {
+                    Ok(a) => a,
+                    Err(err) => {
+                        return Box::new(InstantInvocation::fault(conv_error_back(err)))
+                    }
+                };
+                Box::new(
+                    InstantInvocation::call(
+                        Box::new(ws::web_socket_with(request))
+                    )
+                )
+            }
             h if h == type_name_hash::<wasmer_bus_time::api::TimeSleepRequest>() => {
                 let request: wasmer_bus_time::api::TimeSleepRequest = match format.deserialize(buf) {
                     Ok(a) => a,
@@ -114,6 +128,19 @@ for StandardBus
                 };
                 reqwest::reqwest(self.system, request)
             }
+            h if h == type_name_hash::<wasmer_bus_reqwest::api::ReqwestStreamRequest>() => {
+                let request: wasmer_bus_reqwest::api::ReqwestStreamRequest = match format.deserialize(buf) {
+                    Ok(a) => a,
+                    Err(err) => {
+                        return Box::new(InstantInvocation::fault(conv_error_back(err)))
+                    }
+                };
+                Box::new(
+                    InstantInvocation::call(
+                        Box::new(reqwest::reqwest_stream(self.system, request))
+                    )
+                )
+            }
             h if h == type_name_hash::<wasmer_bus_tty::api::TtyStdinRequest>() => {
                 let env = self.process_factory.launch_env();
                 let stdio = self.stdio(&env);
@@ -53,6 +53,7 @@ pub fn conv_error_back(fault: BusError) -> VirtualBusError {
         BusError::BusInvocationFailed => InvokeFailed,
         BusError::AlreadyConsumed => AlreadyConsumed,
         BusError::MemoryAccessViolation => MemoryAccessViolation,
+        BusError::Timeout => Aborted,
         BusError::Unknown => UnknownError,
         BusError::Success => UnknownError,
     }
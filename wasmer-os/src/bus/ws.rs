@@ -11,6 +11,8 @@ use wasmer_vbus::VirtualBusInvokable;
 use wasmer_vbus::VirtualBusInvoked;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::task::Context;
 use std::task::Poll;
 use tokio::select;
@@ -26,27 +28,45 @@ use crate::api::*;
 
 pub fn web_socket(
     connect: api::SocketBuilderConnectRequest,
+) -> WebSocket {
+    open(connect.url, model::ConnectOptions::default(), false)
+}
+
+pub fn web_socket_with(
+    connect: api::SocketBuilderConnectWithRequest,
+) -> WebSocket {
+    open(connect.url, connect.options, true)
+}
+
+/// Opens a web socket, when `frames` is set then every frame is passed back
+/// to the caller rather than just the binary messages
+fn open(
+    url: String,
+    options: model::ConnectOptions,
+    frames: bool,
 ) -> WebSocket {
     let system = System::default();
 
     // Construct the channels
     let (tx_keepalive, mut rx_keepalive) = mpsc::channel(1);
     let (tx_state, rx_state) = mpsc::channel::<model::SocketState>(MAX_MPSC);
-    let (tx_send, mut rx_send) = mpsc::channel::<Vec<u8>>(MAX_MPSC);
-    let (tx_recv, rx_recv) = mpsc::channel::<Vec<u8>>(MAX_MPSC);
+    let (tx_send, mut rx_send) = mpsc::channel::<model::Frame>(MAX_MPSC);
+    let (tx_recv, rx_recv) = mpsc::channel::<model::Frame>(MAX_MPSC);
+    let protocol = Arc::new(Mutex::new(None));
 
     // The web socket will be started in a background thread as it
     // is an asynchronous IO primative
     let sub_system = system.clone();
+    let sub_protocol = protocol.clone();
     system.spawn_dedicated_async(move || async move {
 
         // Open the web socket
         let (tx_state2, mut rx_state2) = broadcast::channel::<model::SocketState>(10);
-        let mut ws_sys = match system.web_socket(connect.url.as_str()).await {
+        let mut ws_sys = match system.web_socket_with(url.as_str(), options).await {
             Ok(a) => a,
             Err(err) => {
-                debug!("failed to create web socket ({}): {}", connect.url, err);
-                let _ = tx_state2.send(model::SocketState::Failed);
+                debug!("failed to create web socket ({}): {}", url, err);
+                let _ = tx_state.send(model::SocketState::Failed).await;
                 return;
             }
         };
@@ -68,9 +88,9 @@ pub fn web_socket(
         }
 
         {
-            ws_sys.set_onmessage(Box::new(move |data| {
-                debug!("websocket recv {} bytes", data.len());
-                sub_system.fire_and_forget(&tx_recv, data);
+            ws_sys.set_onframe(Box::new(move |frame| {
+                debug!("websocket recv {}", frame);
+                sub_system.fire_and_forget(&tx_recv, frame);
             }));
         }
 
@@ -83,6 +103,12 @@ pub fn web_socket(
                 state = rx_state2.recv() => {
                     match state {
                         Ok(state) => {
+                            // The sub-protocol is only negotiated once the socket opens (browsers
+                            // report an empty protocol before then) so it must be read on the
+                            // open event and before the caller is told the socket is open
+                            if state == model::SocketState::Opened {
+                                *sub_protocol.lock().unwrap() = ws_sys.protocol();
+                            }
                             let _ = tx_state.send(state.clone()).await;
                             if state != model::SocketState::Opened {
                                 return;
@@ -121,18 +147,24 @@ pub fn web_socket(
                     }
                 }
                 request = rx_send.recv() => {
-                    if let Some(data) = request {
-                        let data_len = data.len();
+                    if let Some(frame) = request {
+                        let desc = frame.to_string();
 
                         #[cfg(feature="async_ws")]
-                        let ret = ws_sys.send(data).await;
+                        let ret = match frame {
+                            model::Frame::Binary(data) => ws_sys.send(data).await,
+                            frame => ws_sys.send_frame(frame).await,
+                        };
                         #[cfg(not(feature="async_ws"))]
-                        let ret = ws_sys.send(data);
+                        let ret = match frame {
+                            model::Frame::Binary(data) => ws_sys.send(data),
+                            frame => ws_sys.send_frame(frame),
+                        };
 
                         if let Err(err) = ret {
                             debug!("error sending message: {}", err);
                         } else {
-                            trace!("websocket sent {} bytes", data_len);
+                            trace!("websocket sent {}", desc);
                         }
                     } else {
                         trace!("websocket send-side closed");
@@ -151,6 +183,8 @@ pub fn web_socket(
         tx_send,
         rx_recv,
         rx_state,
+        protocol,
+        frames,
     }
 }
 
@@ -158,9 +192,56 @@ pub fn web_socket(
 pub struct WebSocket {
     #[allow(dead_code)]
     tx_keepalive: mpsc::Sender<()>,
-    tx_send: mpsc::Sender<Vec<u8>>,
-    rx_recv: mpsc::Receiver<Vec<u8>>,
+    tx_send: mpsc::Sender<model::Frame>,
+    rx_recv: mpsc::Receiver<model::Frame>,
     rx_state: mpsc::Receiver<model::SocketState>,
+    protocol: Arc<Mutex<Option<String>>>,
+    frames: bool,
+}
+
+impl WebSocket {
+    fn callback<T>(&self, data: T) -> BusInvocationEvent
+    where T: serde::Serialize
+    {
+        BusInvocationEvent::Callback {
+            topic_hash: type_name_hash::<T>(),
+            format: BusDataFormat::Bincode,
+            data: match SerializationFormat::Bincode.serialize(data) {
+                Ok(d) => d,
+                Err(err) => {
+                    debug!("failed to serialize web socket callback");
+                    return BusInvocationEvent::Fault { fault: conv_error_back(err) };
+                }
+            }
+        }
+    }
+
+    fn send_frame(&self, frame: model::Frame) -> Box<dyn VirtualBusInvoked> {
+        let data_len = match &frame {
+            model::Frame::Binary(a) => a.len(),
+            model::Frame::Text(a) => a.len(),
+            _ => 0usize,
+        };
+        let tx = self.tx_send.clone();
+        match tx.try_send(frame) {
+            Ok(()) => {
+                Box::new(encode_instant_response(BusDataFormat::Bincode,
+                    &SendResult::Success(data_len)))
+            },
+            Err(mpsc::error::TrySendError::Full(frame)) => {
+                Box::new(DelayedSend {
+                    data_len,
+                    fut: Box::pin(async move {
+                        tx.send(frame).await
+                    })
+                })
+            },
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                debug!("websocket is closed");
+                Box::new(InstantInvocation::fault(VirtualBusError::Aborted))        
+            }
+        }
+    }
 }
 
 impl VirtualBusInvocation
@@ -169,19 +250,19 @@ for WebSocket
     fn poll_event(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<BusInvocationEvent> {
         loop {
             match self.rx_recv.poll_recv(cx) {
-                Poll::Ready(Some(data)) => {
-                    let data = api::SocketBuilderConnectReceiveCallback(data);
-                    return Poll::Ready(BusInvocationEvent::Callback {
-                        topic_hash: type_name_hash::<api::SocketBuilderConnectReceiveCallback>(),
-                        format: BusDataFormat::Bincode,
-                        data: match SerializationFormat::Bincode.serialize(data) {
-                            Ok(d) => d,
-                            Err(err) => {
-                                debug!("failed to serialize web socket received data");
-                                return Poll::Ready(BusInvocationEvent::Fault { fault: conv_error_back(err) });
-                            }
+                Poll::Ready(Some(frame)) => {
+                    if self.frames {
+                        return Poll::Ready(self.callback(api::SocketBuilderConnectWithReceiveCallback(frame)));
+                    }
+                    // Plain connections only ever see the data frames
+                    match frame.into_data() {
+                        Some(data) => {
+                            return Poll::Ready(self.callback(api::SocketBuilderConnectReceiveCallback(data)));
+                        }
+                        None => {
+                            continue;
                         }
-                    });
+                    }
                 },
                 Poll::Ready(None) => {
                     return Poll::Ready(BusInvocationEvent::Fault { fault: VirtualBusError::Aborted });
@@ -194,17 +275,10 @@ for WebSocket
                     match state {
                         model::SocketState::Opened => {
                             debug!("confirmed websocket successfully opened");
-                            let data = api::SocketBuilderConnectStateChangeCallback(state);
-                            return Poll::Ready(BusInvocationEvent::Callback {
-                                topic_hash: type_name_hash::<api::SocketBuilderConnectStateChangeCallback>(),
-                                format: BusDataFormat::Bincode,
-                                data: match SerializationFormat::Bincode.serialize(data) {
-                                    Ok(d) => d,
-                                    Err(err) => {
-                                        debug!("failed to serialize web socket received data");
-                                        return Poll::Ready(BusInvocationEvent::Fault { fault: conv_error_back(err) });
-                                    }
-                                }
+                            return Poll::Ready(if self.frames {
+                                self.callback(api::SocketBuilderConnectWithStateChangeCallback(state))
+                            } else {
+                                self.callback(api::SocketBuilderConnectStateChangeCallback(state))
                             });
                         },
                         _ => {
@@ -245,28 +319,22 @@ for WebSocket {
                     return Box::new(InstantInvocation::fault(conv_error_back(err)));
                 }
             };
-            let data_len = data.len();
-
-            let tx = self.tx_send.clone();
-            match tx.try_send(data) {
-                Ok(()) => {
-                    Box::new(encode_instant_response(BusDataFormat::Bincode,
-                        &SendResult::Success(data_len)))
-                },
-                Err(mpsc::error::TrySendError::Full(data)) => {
-                    Box::new(DelayedSend {
-                        data_len,
-                        fut: Box::pin(async move {
-                            tx.send(data).await
-                        })
-                    })
-                },
-                Err(mpsc::error::TrySendError::Closed(_)) => {
-                    debug!("websocket is closed");
-                    Box::new(InstantInvocation::fault(VirtualBusError::Aborted))        
+            self.send_frame(model::Frame::Binary(data))
+        } else if topic_hash == type_name_hash::<api::WebSocketSendFrameRequest>() {
+            let frame = match decode_request::<api::WebSocketSendFrameRequest>(
+                format,
+                buf,
+            ) {
+                Ok(a) => a.frame,
+                Err(err) => {
+                    return Box::new(InstantInvocation::fault(conv_error_back(err)));
                 }
-            }
-            
+            };
+            debug!("websocket send {}", frame);
+            self.send_frame(frame)
+        } else if topic_hash == type_name_hash::<api::WebSocketProtocolRequest>() {
+            let protocol = self.protocol.lock().unwrap().clone();
+            Box::new(encode_instant_response(BusDataFormat::Bincode, &protocol))
         } else {
             debug!("websocket invalid topic (hash={})", topic_hash);
             Box::new(InstantInvocation::fault(VirtualBusError::InvalidTopic))
@@ -288,7 +356,7 @@ struct DelayedSend
 {
     data_len: usize,
     #[derivative(Debug = "ignore")]
-    fut: Pin<Box<dyn Future<Output = Result<(), mpsc::error::SendError<Vec<u8>>>>>>
+    fut: Pin<Box<dyn Future<Output = Result<(), mpsc::error::SendError<model::Frame>>>>>
 }
 
 impl VirtualBusInvoked
@@ -116,6 +116,19 @@ umount <mountpoint>
 <mounpoint>: Location where the file-system to be unmounted is currently mounted
 
 Example: umount /www
+"#;
+
+    pub const ULIMIT_USAGE: &'static str = r#"Usage:
+ulimit [-a] [-m <memory>] [-t <fuel>] [-n <files>]
+
+-a: Displays the current limits
+-m: Maximum memory of a process (in kilobytes or with a K, M or G suffix)
+-t: Maximum amount of CPU fuel the process may consume
+-n: Maximum number of files a process may hold open
+
+Limits can only be lowered, once applied they are inherited by all sub-processes
+
+Example: ulimit -m 256M -n 64
 "#;
 
     pub const CALL_USAGE: &'static str = r#"Usage:
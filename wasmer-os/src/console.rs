@@ -6,6 +6,7 @@ use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::AtomicBool;
 use tokio::sync::mpsc;
+use tokio::sync::oneshot;
 use tokio::sync::RwLock;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
@@ -23,6 +24,7 @@ use super::eval::*;
 use super::fd::*;
 use super::fs::*;
 use super::job::*;
+use super::limits::*;
 use super::pipe::*;
 use super::reactor::*;
 use super::state::*;
@@ -332,10 +334,22 @@ impl Console {
         state.rootfs.clone()
     }
 
+    /// Sets the resource limits that will be applied to all the processes
+    /// that are started from this console
+    pub fn set_limits(&self, limits: ResourceLimits) {
+        let mut state = self.state.lock().unwrap();
+        state.limits = limits;
+    }
+
+    pub fn limits(&self) -> ResourceLimits {
+        let state = self.state.lock().unwrap();
+        state.limits
+    }
+
     pub fn new_spawn_context(&self, job: &Job) -> SpawnContext {
         let ctx = {
             let state = self.state.lock().unwrap();
-            SpawnContext::new(
+            let mut ctx = SpawnContext::new(
                 self.abi.clone(),
                 state.env.clone(),
                 job.clone(),
@@ -349,7 +363,9 @@ impl Console {
                 #[cfg(feature = "sys")]
                 self.engine.clone(),
                 self.compiler,
-            )
+            );
+            ctx.limits = state.limits;
+            ctx
         };
         ctx
     }
@@ -505,6 +521,7 @@ impl Console {
                         state.env = ctx.env;
                         state.path = ctx.working_dir;
                         state.last_return = ctx.last_return;
+                        state.limits = ctx.limits;
                     }
                 } else {
                     debug!("eval recv erro");
@@ -519,6 +536,67 @@ impl Console {
         });
     }
 
+    /// Runs a single command without an interactive shell (e.g. `ssh host cmd`),
+    /// the returned receiver yields the exit code once the command finishes
+    pub async fn exec(&mut self, cmd: String) -> oneshot::Receiver<u32> {
+        let (exit_tx, exit_rx) = oneshot::channel();
+
+        // Generate the job and make it the active version
+        let job = if let Some(j) = self.new_job().await {
+            j
+        } else {
+            let _ = exit_tx.send(err::ERR_EAGAIN);
+            return exit_rx;
+        };
+
+        // Input is fed straight into the process without any echo or line editing
+        let mut tty = self.tty.clone();
+        tty.enter_mode(TtyMode::StdIn(job.clone()), &self.reactor)
+            .await;
+        tty.set_buffering(false);
+        tty.set_echo(false).await;
+
+        // Spawn the process and attach it to the job
+        let ctx = self.new_spawn_context(&job);
+
+        let exec = self.exec.clone();
+        let reactor = self.reactor.clone();
+        let system = System::default();
+        let mut stdout = ctx.stdout.clone();
+        let mut stderr = ctx.stderr.clone();
+        system.fork_dedicated_async(move || {
+            let mut process = exec.eval(cmd, ctx);
+            async move {
+                // Wait for the process to finish
+                let rx = process.recv().await;
+                drop(process);
+
+                // Flush all the pipes
+                let _ = stdout.flush_async().await;
+                let _ = stderr.flush_async().await;
+                let _ = tty.flush_async().await;
+                tty.enter_mode(TtyMode::Null, &reactor).await;
+
+                let code = match rx.map(|a| a.status) {
+                    Some(EvalStatus::Executed { code, .. }) => {
+                        debug!("exec executed (code={})", code);
+                        code
+                    }
+                    Some(EvalStatus::MoreInput) | Some(EvalStatus::Invalid) => {
+                        debug!("exec invalid");
+                        err::ERR_EINVAL
+                    }
+                    Some(EvalStatus::InternalError) | None => {
+                        debug!("exec failed");
+                        err::ERR_EIO
+                    }
+                };
+                let _ = exit_tx.send(code);
+            }
+        });
+        exit_rx
+    }
+
     async fn update_prompt(multiline_input: bool, state: &Arc<Mutex<ConsoleState>>, tty: &Tty) {
         let (prompt, prompt_color) = {
             let state = state.lock().unwrap();
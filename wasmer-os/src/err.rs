@@ -130,6 +130,7 @@ pub const ERR_ENOMEDIUM: u32 = 123; /* No medium found */
 pub const ERR_EMEDIUMTYPE: u32 = 124; /* Wrong medium type */
 
 pub const ERR_TERMINATED: u32 = 130; /* Process was terminated */
+pub const ERR_XCPU: u32 = 152; /* CPU limit exceeded */
 pub const ERR_PANIC: u32 = 99999; /* Process has panicked */
 
 pub fn exit_code_to_message(code: u32) -> &'static str {
@@ -259,6 +260,7 @@ pub fn exit_code_to_message(code: u32) -> &'static str {
         ERR_EMEDIUMTYPE => "Wrong medium type",
         ERR_PANIC => "Process has panicked",
         ERR_TERMINATED => "Process was terminated",
+        ERR_XCPU => "CPU limit exceeded",
         _ => "Unknown error",
     }
 }
@@ -17,6 +17,7 @@ use std::ops::Deref;
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU32;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -43,12 +44,17 @@ use crate::err;
 use crate::err::*;
 use crate::fs::*;
 use crate::job::*;
+use crate::limits::*;
 use crate::pipe::*;
 use crate::poll::*;
 use crate::reactor::*;
 use crate::state::*;
 use crate::stdio::*;
 use crate::wasmer::{Imports, Instance, Module, Store};
+#[cfg(feature = "sys")]
+use crate::wasmer::{BaseTunables, Engine, Target};
+#[cfg(feature = "metering")]
+use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
 use crate::wasmer_vfs::FileSystem;
 use crate::wasmer_vfs::FsError;
 use crate::wasmer_wasi::Stdin;
@@ -116,6 +122,7 @@ pub async fn exec_process(
     let mut set_pwd = false;
     let mut base_dir = None;
     let mut chroot = ctx.chroot;
+    let mut limits = ctx.limits;
     let (program_data_hash, program_data, mut fs_private) = match load_bin(&ctx, cmd, &mut stdio).await {
         Some(a) => {
             if a.chroot {
@@ -133,6 +140,7 @@ pub async fn exec_process(
             for mapping in a.mappings {
                 preopen.push(mapping);
             }
+            limits = limits.restrict(&a.limits);
 
             (a.hash, a.data, a.fs)
         }
@@ -145,12 +153,15 @@ pub async fn exec_process(
         envs.insert("PWD".to_string(), pwd.clone());
     };
 
-    // Create a store for the module and memory
+    if limits.is_unlimited() == false {
+        debug!("process limits for {} ({})", cmd, limits);
+    }
+
+    // Create a store for the module and memory (the store will prevent the
+    // memory from growing beyond the limits of this process)
+    let memory_exceeded = Arc::new(AtomicBool::new(false));
     #[cfg(feature = "sys")]
-    let store = match ctx.engine.clone() {
-        Some(engine) => Store::new(engine),
-        None => Store::default()
-    };
+    let store = create_store(ctx.engine.clone(), &limits, &memory_exceeded);
     #[cfg(feature = "js")]
     let store = Store::default();
 
@@ -350,23 +361,38 @@ pub async fn exec_process(
         }
     };
 
+    // Any memories that are declared by the module must fit within the limits
+    for ty in module
+        .exports()
+        .memories()
+        .map(|a| *a.ty())
+    {
+        if let Err(err) = limits.clamp_memory(&ty) {
+            return on_early_exit(Some(format!("exec-failed: {}\n", err)), err::ERR_ENOMEM).await;
+        }
+    }
+
     // Determine if shared memory needs to be created and imported
-    let shared_memory = module
+    let shared_memory = match module
         .imports()
         .memories()
         .next()
-        .map(|a| *a.ty());
+        .map(|a| limits.clamp_memory(a.ty()))
+    {
+        Some(Ok(ty)) => Some(ty),
+        Some(Err(err)) => {
+            return on_early_exit(Some(format!("exec-failed: {}\n", err)), err::ERR_ENOMEM).await;
+        }
+        None => None,
+    };
 
     // Determine if we are going to create memory and import it or just rely on self creation of memory
     let memory_spawn = match shared_memory {
         Some(ty) => {
             #[cfg(feature = "sys")]
-            let style = match &ctx.engine {
-                    Some(engine) => Store::new(engine.clone()),
-                    None => Store::default()
-                }
+            let style = create_store(ctx.engine.clone(), &limits, &memory_exceeded)
                 .tunables()
-                .memory_style(&ty);            
+                .memory_style(&ty);
             SpawnType::CreateWithType(SpawnedMemory {
                 ty,
                 #[cfg(feature = "sys")]
@@ -402,6 +428,13 @@ pub async fn exec_process(
         caller_ctx.clone()));
     let ctx_taker = wasi_runtime.prepare_take_context();
 
+    // If there is a limit on the number of open files then the file system
+    // is wrapped so that it can count them
+    let wasi_fs: Box<dyn FileSystem> = match limits.max_fds {
+        Some(max_fds) => Box::new(FdLimitFileSystem::new(union, max_fds)),
+        None => Box::new(union),
+    };
+
     // Spawn the process on a background thread
     let cmd = cmd.clone();
     let args = args.clone();
@@ -423,7 +456,7 @@ pub async fn exec_process(
                 .stdin(Box::new(stdio.stdin.clone()))
                 .stdout(Box::new(stdio.stdout.clone()))
                 .stderr(Box::new(stdio.stderr.clone()))
-                .set_fs(Box::new(union))
+                .set_fs(wasi_fs)
                 .setup_fs(Box::new(move |_, fs| {
                     fs.set_current_dir(pwd.as_str());
                     Ok(())
@@ -506,10 +539,23 @@ pub async fn exec_process(
                 Err(err) => {
                     let _ = stderr.write(format!("instantiate error ({})\n", err.to_string()).as_bytes()).await;
                     let ctx = ctx_taker.take_context().unwrap();
+                    if memory_exceeded.load(Ordering::Acquire) {
+                        return (ctx, ERR_ENOMEM);
+                    }
                     return (ctx, ERR_ENOEXEC);
                 }
             };
 
+            // Give the process the fuel it is allowed to burn
+            #[cfg(feature = "metering")]
+            if let Some(fuel) = limits.max_fuel {
+                set_remaining_points(&mut store, &instance, fuel);
+            }
+            #[cfg(not(feature = "metering"))]
+            if limits.max_fuel.is_some() {
+                warn!("the fuel limit is ignored as metering is not enabled");
+            }
+
             // Initialize the WASI environment
             if let Err(err) = wasi_env.initialize(&mut store, &instance) {
                 let _ = stderr.write(format!("instantiate error ({})\n", err.to_string()).as_bytes()).await;
@@ -571,6 +617,17 @@ pub async fn exec_process(
             };
             debug!("main() has exited on {}", cmd);
 
+            // If the process was killed because it ran out of resources then
+            // we return a clearer exit code
+            if ret == err::ERR_PANIC {
+                if let Some((code, msg)) = limit_exceeded(&mut store, &instance, &limits, &memory_exceeded) {
+                    let _ = stderr
+                        .write(format!("exec-failed: {}\n", msg).as_bytes())
+                        .await;
+                    ret = code;
+                }
+            }
+
             // The second checkpoint is after the start method completes but before
             // all the background threads have exited
             checkpoint2_tx.send(()).await;
@@ -623,3 +680,35 @@ pub async fn exec_process(
 
     Ok((process, process_result, wasi_runtime, checkpoint2))
 }
+
+#[cfg(feature = "sys")]
+fn create_store(engine: Option<Engine>, limits: &ResourceLimits, memory_exceeded: &Arc<AtomicBool>) -> Store {
+    let engine = match engine {
+        Some(engine) => engine,
+        None => Store::default().engine().clone()
+    };
+    match limits.max_memory_pages() {
+        Some(limit) => {
+            let base = BaseTunables::for_target(&Target::default());
+            Store::new_with_tunables(engine, LimitingTunables::new(base, limit, memory_exceeded))
+        }
+        None => Store::new(engine)
+    }
+}
+
+/// Determines if a process that trapped did so because it exceeded one of its
+/// limits - a memory limit counts when the process tried to grow its memory
+/// past the limit (which the tunables record) as the failed allocation is what
+/// then makes it trap
+fn limit_exceeded(store: &mut Store, instance: &Instance, limits: &ResourceLimits, memory_exceeded: &AtomicBool) -> Option<(u32, &'static str)> {
+    #[cfg(feature = "metering")]
+    if limits.max_fuel.is_some() {
+        if let MeteringPoints::Exhausted = get_remaining_points(store, instance) {
+            return Some((err::ERR_XCPU, "cpu limit exceeded"));
+        }
+    }
+    if limits.max_memory.is_some() && memory_exceeded.load(Ordering::Acquire) {
+        return Some((err::ERR_ENOMEM, "memory limit exceeded"));
+    }
+    None
+}
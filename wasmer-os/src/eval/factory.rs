@@ -5,6 +5,7 @@ use crate::bin_factory::*;
 use crate::bus::WasmCheckpoint;
 use crate::eval::*;
 use crate::fd::*;
+use crate::limits::*;
 use crate::pipe::*;
 use crate::state::*;
 use crate::stdout::*;
@@ -25,6 +26,7 @@ pub struct SpawnContext {
     #[cfg(feature = "sys")]
     pub engine: Option<Engine>,
     pub compiler: Compiler,
+    pub limits: ResourceLimits,
     pub extra_args: Vec<String>,
     pub extra_redirects: Vec<Redirect>,    
     pub(crate) checkpoint1: Option<(mpsc::Sender<()>, Arc<WasmCheckpoint>)>,
@@ -61,6 +63,7 @@ impl SpawnContext {
             #[cfg(feature = "sys")]
             engine,
             compiler,
+            limits: ResourceLimits::default(),
             extra_args: Vec::new(),
             extra_redirects: Vec::new(),
             checkpoint1: None,
@@ -158,6 +161,7 @@ impl EvalFactory {
             #[cfg(feature = "sys")]
             engine: ctx.engine.clone(),
             compiler: ctx.compiler,
+            limits: ctx.limits,
             extra_args: ctx.extra_args,
             extra_redirects: ctx.extra_redirects,            
             checkpoint1: ctx.checkpoint1,
@@ -14,6 +14,7 @@ use super::BinaryPackage;
 use super::EvalContext;
 use crate::err::*;
 use crate::fs::*;
+use crate::limits::*;
 use crate::stdio::*;
 use crate::wasmer_vfs::FileSystem;
 use crate::pipe::*;
@@ -29,6 +30,7 @@ pub async fn load_bin(
     let mut wapm = None;
     let mut base_dir = None;
     let mut envs = HashMap::default();
+    let mut limits = ResourceLimits::default();
     let mut mappings = Vec::new();
     let mut already = HashSet::<String>::default();
     let mut name = name.clone();
@@ -78,6 +80,7 @@ pub async fn load_bin(
                             for (k, v) in next.envs {
                                 envs.insert(k, v);
                             }
+                            limits = limits.restrict(&next.limits);
                             mappings.extend(next.mappings.into_iter());
 
                             debug!("binary alias '{}' found for {}", next.run, name);
@@ -128,6 +131,7 @@ pub async fn load_bin(
                 ret.wapm = wapm;
                 ret.base_dir = base_dir;
                 ret.envs = envs;
+                ret.limits = limits;
                 ret.mappings.extend(mappings.into_iter());
                 return Some(ret);
             }
@@ -152,6 +156,7 @@ pub async fn load_bin(
         for (k, v) in next.envs {
             envs.insert(k, v);
         }
+        limits = limits.restrict(&next.limits);
         name = next.run;
     }
 
@@ -164,6 +169,7 @@ pub async fn load_bin(
         ret.wapm = wapm;
         ret.base_dir = base_dir;
         ret.envs = envs;
+        ret.limits = ret.limits.restrict(&limits);
         ret.mappings.extend(mappings.into_iter());
     }
     ret
@@ -55,6 +55,7 @@ use crate::wasmer_compiler::EngineBuilder;
 use crate::api::*;
 use crate::ast;
 use crate::environment::Environment;
+use crate::limits::ResourceLimits;
 
 use super::ast::*;
 use super::bin_factory::*;
@@ -155,7 +156,9 @@ impl Compiler
         match self {
             #[cfg(feature = "cranelift")]
             Compiler::Cranelift => {
-                let compiler = Cranelift::default();
+                let mut compiler = Cranelift::default();
+                #[cfg(feature = "metering")]
+                compiler.push_middleware(crate::limits::metering_middleware());
                 Some(
                     EngineBuilder::new(compiler)
                         .set_features(Some(features))
@@ -164,7 +167,9 @@ impl Compiler
             }
             #[cfg(feature = "llvm")]
             Compiler::LLVM => {
-                let compiler = LLVM::default();
+                let mut compiler = LLVM::default();
+                #[cfg(feature = "metering")]
+                compiler.push_middleware(crate::limits::metering_middleware());
                 Some(
                     EngineBuilder::new(compiler)
                         .set_features(Some(features))
@@ -173,7 +178,9 @@ impl Compiler
             }
             #[cfg(feature = "singlepass")]
             Compiler::Singlepass => {
-                let compiler = Singlepass::default();
+                let mut compiler = Singlepass::default();
+                #[cfg(feature = "metering")]
+                compiler.push_middleware(crate::limits::metering_middleware());
                 Some(
                     EngineBuilder::new(compiler)
                         .set_features(Some(features))
@@ -266,6 +273,7 @@ pub struct EvalContext {
     #[derivative(Debug = "ignore")]
     pub engine: Option<Engine>,
     pub compiler: Compiler,
+    pub limits: ResourceLimits,
     pub extra_args: Vec<String>,
     pub extra_redirects: Vec<Redirect>,    
     #[derivative(Debug = "ignore")]
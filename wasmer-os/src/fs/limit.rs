@@ -0,0 +1,257 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::io::{self};
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::wasmer_vfs::*;
+use crate::wasmer_wasi::{types as wasi_types, WasiFsError};
+
+use super::api::*;
+use super::union::*;
+use crate::bus::WasmCallerContext;
+
+/// Error returned when a process opens more files than it is allowed to, the
+/// file system errors have no equivalent of EMFILE so this fails the open with
+/// EIO (rather than EAGAIN which would make the process retry the open)
+pub const FD_LIMIT_ERROR: FsError = FsError::IOError;
+
+/// File system that wraps another file system and limits the number of
+/// files that can be held open at the same time (i.e. `ulimit -n`)
+#[derive(Debug, Clone)]
+pub struct FdLimitFileSystem {
+    inner: UnionFileSystem,
+    open: Arc<AtomicU32>,
+    max_fds: u32,
+}
+
+impl FdLimitFileSystem {
+    pub fn new(inner: UnionFileSystem, max_fds: u32) -> FdLimitFileSystem {
+        FdLimitFileSystem {
+            inner,
+            open: Arc::new(AtomicU32::new(0)),
+            max_fds,
+        }
+    }
+
+    /// Returns the number of files that are currently held open
+    pub fn open_files(&self) -> u32 {
+        self.open.load(Ordering::Acquire)
+    }
+}
+
+impl MountedFileSystem for FdLimitFileSystem {
+    fn set_ctx(&self, ctx: &WasmCallerContext) {
+        self.inner.set_ctx(ctx);
+    }
+}
+
+impl FileSystem for FdLimitFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        let opener = Box::new(FdLimitFileOpener {
+            inner: self.inner.clone(),
+            open: self.open.clone(),
+            max_fds: self.max_fds,
+        });
+        OpenOptions::new(opener)
+    }
+}
+
+#[derive(Debug)]
+pub struct FdLimitFileOpener {
+    inner: UnionFileSystem,
+    open: Arc<AtomicU32>,
+    max_fds: u32,
+}
+
+impl FileOpener for FdLimitFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync>> {
+        // Reserve a slot before we open the file so that concurrent opens
+        // can not exceed the limit
+        let max_fds = self.max_fds;
+        if self
+            .open
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |cur| {
+                if cur < max_fds {
+                    Some(cur + 1)
+                } else {
+                    None
+                }
+            })
+            .is_err()
+        {
+            warn!("open file limit reached (path={}, max_fds={})", path.display(), max_fds);
+            return Err(FD_LIMIT_ERROR);
+        }
+
+        match self
+            .inner
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)
+        {
+            Ok(inner) => Ok(Box::new(FdLimitFile {
+                inner,
+                open: self.open.clone(),
+            })),
+            Err(err) => {
+                self.open.fetch_sub(1, Ordering::AcqRel);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Open file that gives back its slot to the limit when its closed
+#[derive(Debug)]
+pub struct FdLimitFile {
+    inner: Box<dyn VirtualFile + Send + Sync>,
+    open: Arc<AtomicU32>,
+}
+
+impl Drop for FdLimitFile {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Seek for FdLimitFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl Write for FdLimitFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Read for FdLimitFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl VirtualFile for FdLimitFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+    fn set_len(&mut self, new_size: wasi_types::__wasi_filesize_t) -> StdResult<(), WasiFsError> {
+        self.inner.set_len(new_size)
+    }
+    fn unlink(&mut self) -> StdResult<(), WasiFsError> {
+        self.inner.unlink()
+    }
+    fn bytes_available(&self) -> StdResult<usize, WasiFsError> {
+        self.inner.bytes_available()
+    }
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::TmpFileSystem;
+
+    fn create_fs(max_fds: u32) -> FdLimitFileSystem {
+        let mut union = UnionFileSystem::new();
+        union.mount("root", "/", false, Box::new(TmpFileSystem::new()), None);
+        FdLimitFileSystem::new(union, max_fds)
+    }
+
+    fn open(fs: &FdLimitFileSystem, path: &str) -> Result<Box<dyn VirtualFile + Send + Sync>> {
+        fs.new_open_options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(Path::new(path))
+    }
+
+    #[test]
+    fn test_open_files_are_limited() {
+        let fs = create_fs(2);
+        let a = open(&fs, "/a").unwrap();
+        let _b = open(&fs, "/b").unwrap();
+        assert_eq!(fs.open_files(), 2);
+        assert_eq!(open(&fs, "/c").err(), Some(FD_LIMIT_ERROR));
+
+        // Closing a file gives back its slot
+        drop(a);
+        assert_eq!(fs.open_files(), 1);
+        let _c = open(&fs, "/c").unwrap();
+        assert_eq!(fs.open_files(), 2);
+    }
+
+    #[test]
+    fn test_failed_opens_do_not_hold_a_slot() {
+        let fs = create_fs(1);
+        assert!(fs
+            .new_open_options()
+            .read(true)
+            .open(Path::new("/missing"))
+            .is_err());
+        assert_eq!(fs.open_files(), 0);
+        let _a = open(&fs, "/a").unwrap();
+        assert_eq!(fs.open_files(), 1);
+    }
+
+    #[test]
+    fn test_clones_share_the_limit() {
+        let fs = create_fs(1);
+        let other = fs.clone();
+        let _a = open(&fs, "/a").unwrap();
+        assert_eq!(open(&other, "/b").err(), Some(FD_LIMIT_ERROR));
+    }
+}
@@ -2,6 +2,7 @@ mod api;
 mod asyncify;
 mod ext;
 mod fuse;
+mod limit;
 mod proc;
 mod tmp;
 mod union;
@@ -11,6 +12,7 @@ pub use api::*;
 pub use asyncify::*;
 pub use ext::*;
 pub use fuse::*;
+pub use limit::*;
 pub use proc::*;
 pub use tmp::*;
 pub use union::*;
@@ -12,6 +12,8 @@ pub mod environment;
 pub mod err;
 pub mod fd;
 pub mod job;
+pub mod limits;
+pub mod lru;
 pub mod pipe;
 pub mod poll;
 pub mod reactor;
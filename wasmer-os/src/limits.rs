@@ -0,0 +1,406 @@
+use serde::*;
+use std::fmt;
+#[cfg(feature = "sys")]
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "sys")]
+use std::sync::atomic::Ordering;
+#[cfg(any(feature = "sys", feature = "metering"))]
+use std::sync::Arc;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+
+#[cfg(feature = "sys")]
+use std::ptr::NonNull;
+use wasmer::MemoryType;
+use wasmer::Pages;
+#[cfg(feature = "sys")]
+use wasmer::vm::{self, LinearMemory, MemoryError, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition};
+#[cfg(feature = "sys")]
+use wasmer::{TableType, Tunables};
+#[cfg(feature = "metering")]
+use wasmer::wasmparser::Operator;
+#[cfg(feature = "metering")]
+use wasmer_middlewares::Metering;
+
+/// Size of a single web assembly memory page
+pub const WASM_PAGE_SIZE: u64 = 65536;
+
+/// Resource limits that are applied to a process (and all the sub-processes
+/// that it spawns) - any limit that is `None` is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum amount of linear memory (in bytes) that the process may allocate
+    #[serde(default)]
+    pub max_memory: Option<u64>,
+    /// Maximum number of metering points (fuel) the process may consume before
+    /// it is terminated - the fuel is given to the process once when it starts
+    /// and only the main thread burns it (requires the `metering` feature)
+    #[serde(default)]
+    pub max_fuel: Option<u64>,
+    /// Maximum number of files the process may hold open at the same time
+    #[serde(default)]
+    pub max_fds: Option<u32>,
+}
+
+impl ResourceLimits {
+    pub fn unlimited() -> ResourceLimits {
+        ResourceLimits::default()
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_memory.is_none() && self.max_fuel.is_none() && self.max_fds.is_none()
+    }
+
+    /// Combines two sets of limits together by taking the most restrictive
+    /// value for each of the individual limits
+    pub fn restrict(&self, other: &ResourceLimits) -> ResourceLimits {
+        fn min<T: Ord + Copy>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, None) => a,
+                (None, b) => b,
+            }
+        }
+        ResourceLimits {
+            max_memory: min(self.max_memory, other.max_memory),
+            max_fuel: min(self.max_fuel, other.max_fuel),
+            max_fds: min(self.max_fds, other.max_fds),
+        }
+    }
+
+    /// Returns the memory limit rounded down to whole web assembly pages
+    pub fn max_memory_pages(&self) -> Option<Pages> {
+        self.max_memory
+            .map(|a| Pages((a / WASM_PAGE_SIZE).min(u32::MAX as u64) as u32))
+    }
+
+    /// Clamps a memory type so that it can never grow beyond the memory limit,
+    /// if the minimum size of the memory is already over the limit then this
+    /// returns an error
+    pub fn clamp_memory(&self, ty: &MemoryType) -> Result<MemoryType, String> {
+        let limit = match self.max_memory_pages() {
+            Some(a) => a,
+            None => {
+                return Ok(ty.clone());
+            }
+        };
+        if ty.minimum > limit {
+            return Err(format!(
+                "memory limit exceeded (requested={} bytes, limit={} bytes)",
+                ty.minimum.bytes().0,
+                limit.bytes().0
+            ));
+        }
+        let mut ret = ty.clone();
+        ret.maximum = Some(match ty.maximum {
+            Some(max) if max < limit => max,
+            _ => limit,
+        });
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for ResourceLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn val<T: fmt::Display>(v: Option<T>) -> String {
+            v.map(|a| a.to_string())
+                .unwrap_or_else(|| "unlimited".to_string())
+        }
+        write!(
+            f,
+            "memory={}, fuel={}, fds={}",
+            val(self.max_memory),
+            val(self.max_fuel),
+            val(self.max_fds)
+        )
+    }
+}
+
+/// Parses a size with an optional unit suffix (e.g. 64M or 1G) into bytes
+pub fn parse_size(val: &str) -> Result<u64, String> {
+    let val = val.trim();
+    let (num, mul) = match val.chars().last().map(|a| a.to_ascii_uppercase()) {
+        Some('K') => (&val[..val.len() - 1], 1024u64),
+        Some('M') => (&val[..val.len() - 1], 1024u64 * 1024),
+        Some('G') => (&val[..val.len() - 1], 1024u64 * 1024 * 1024),
+        _ => (val, 1u64),
+    };
+    num.parse::<u64>()
+        .map_err(|err| format!("invalid size '{}' - {}", val, err))?
+        .checked_mul(mul)
+        .ok_or_else(|| format!("invalid size '{}' - overflow", val))
+}
+
+/// Tunables that prevent the web assembly memories created by a store
+/// from growing beyond the memory limit of the process, any attempt to go
+/// past the limit is recorded in the `exceeded` flag so that the process
+/// can report why it died
+#[cfg(feature = "sys")]
+pub struct LimitingTunables<T: Tunables> {
+    limit: Pages,
+    base: T,
+    exceeded: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "sys")]
+impl<T: Tunables> LimitingTunables<T> {
+    pub fn new(base: T, limit: Pages, exceeded: &Arc<AtomicBool>) -> Self {
+        Self {
+            limit,
+            base,
+            exceeded: exceeded.clone(),
+        }
+    }
+
+    fn adjust_memory(&self, requested: &MemoryType) -> MemoryType {
+        let mut adjusted = requested.clone();
+        adjusted.maximum = Some(match requested.maximum {
+            Some(max) if max < self.limit => max,
+            _ => self.limit,
+        });
+        adjusted
+    }
+
+    fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        if ty.minimum > self.limit {
+            self.exceeded.store(true, Ordering::Release);
+            return Err(MemoryError::Generic(
+                "minimum memory exceeds the allowed memory limit".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sys")]
+impl<T: Tunables> Tunables for LimitingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        let adjusted = self.adjust_memory(memory);
+        self.base.memory_style(&adjusted)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<vm::VMMemory, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        let memory = self.base.create_host_memory(&adjusted, style)?;
+        Ok(LimitedMemory::wrap(memory, self.limit, &self.exceeded))
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<vm::VMMemory, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        let memory = self
+            .base
+            .create_vm_memory(&adjusted, style, vm_definition_location)?;
+        Ok(LimitedMemory::wrap(memory, self.limit, &self.exceeded))
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<vm::VMTable, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<vm::VMTable, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+/// Linear memory that records when the process tried to grow it beyond the
+/// memory limit (the grow itself fails and the process sees -1 as usual)
+#[cfg(feature = "sys")]
+#[derive(Debug)]
+struct LimitedMemory {
+    inner: Box<dyn LinearMemory + 'static>,
+    limit: Pages,
+    exceeded: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "sys")]
+impl LimitedMemory {
+    fn wrap(memory: vm::VMMemory, limit: Pages, exceeded: &Arc<AtomicBool>) -> vm::VMMemory {
+        vm::VMMemory(Box::new(LimitedMemory {
+            inner: memory.0,
+            limit,
+            exceeded: exceeded.clone(),
+        }))
+    }
+}
+
+#[cfg(feature = "sys")]
+impl LinearMemory for LimitedMemory {
+    fn ty(&self) -> MemoryType {
+        self.inner.ty()
+    }
+
+    fn size(&self) -> Pages {
+        self.inner.size()
+    }
+
+    fn style(&self) -> MemoryStyle {
+        self.inner.style()
+    }
+
+    fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
+        let over = self
+            .inner
+            .size()
+            .0
+            .checked_add(delta.0)
+            .map(|a| a > self.limit.0)
+            .unwrap_or(true);
+        if over {
+            self.exceeded.store(true, Ordering::Release);
+        }
+        self.inner.grow(delta)
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.inner.vmmemory()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
+        self.inner.try_clone().map(|inner| {
+            Box::new(LimitedMemory {
+                inner,
+                limit: self.limit,
+                exceeded: self.exceeded.clone(),
+            }) as Box<dyn LinearMemory + 'static>
+        })
+    }
+}
+
+/// Every operator costs the same amount of fuel
+#[cfg(feature = "metering")]
+fn metering_cost(_operator: &Operator) -> u64 {
+    1
+}
+
+/// Creates the metering middleware that is compiled into every module so that
+/// fuel limits can be enforced - the points are set per instance after it is
+/// created, thus the initial limit is effectively unlimited
+#[cfg(feature = "metering")]
+pub fn metering_middleware() -> Arc<Metering<fn(&Operator) -> u64>> {
+    Arc::new(Metering::new(u64::MAX, metering_cost as fn(&Operator) -> u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restrict_takes_the_lowest_limit() {
+        let a = ResourceLimits {
+            max_memory: Some(64 * 1024 * 1024),
+            max_fuel: None,
+            max_fds: Some(10),
+        };
+        let b = ResourceLimits {
+            max_memory: Some(32 * 1024 * 1024),
+            max_fuel: Some(1000),
+            max_fds: None,
+        };
+        let c = a.restrict(&b);
+        assert_eq!(c.max_memory, Some(32 * 1024 * 1024));
+        assert_eq!(c.max_fuel, Some(1000));
+        assert_eq!(c.max_fds, Some(10));
+        assert_eq!(a.restrict(&ResourceLimits::unlimited()), a);
+        assert!(ResourceLimits::unlimited().is_unlimited());
+        assert!(c.is_unlimited() == false);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("4k"), Ok(4 * 1024));
+        assert_eq!(parse_size("64M"), Ok(64 * 1024 * 1024));
+        assert_eq!(parse_size(" 2G "), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("99999999999999G").is_err());
+    }
+
+    #[test]
+    fn test_clamp_memory() {
+        let limits = ResourceLimits {
+            max_memory: Some(10 * WASM_PAGE_SIZE + 100),
+            ..Default::default()
+        };
+        assert_eq!(limits.max_memory_pages(), Some(Pages(10)));
+
+        // Memories without a maximum (or one beyond the limit) are capped
+        let ty = limits
+            .clamp_memory(&MemoryType::new(1, None, false))
+            .unwrap();
+        assert_eq!(ty.maximum, Some(Pages(10)));
+        let ty = limits
+            .clamp_memory(&MemoryType::new(1, Some(100), false))
+            .unwrap();
+        assert_eq!(ty.maximum, Some(Pages(10)));
+
+        // Memories that are already smaller than the limit keep their maximum
+        let ty = limits
+            .clamp_memory(&MemoryType::new(1, Some(5), false))
+            .unwrap();
+        assert_eq!(ty.maximum, Some(Pages(5)));
+
+        // Memories that start larger than the limit can not be created
+        assert!(limits
+            .clamp_memory(&MemoryType::new(11, None, false))
+            .is_err());
+
+        // Without a limit nothing changes
+        let ty = MemoryType::new(11, None, false);
+        assert_eq!(ResourceLimits::unlimited().clamp_memory(&ty), Ok(ty));
+    }
+
+    #[cfg(feature = "sys")]
+    #[test]
+    fn test_growing_past_the_limit_is_recorded() {
+        use wasmer::{BaseTunables, Target};
+
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let tunables = LimitingTunables::new(BaseTunables::for_target(&Target::default()), Pages(4), &exceeded);
+        let ty = MemoryType::new(1, None, false);
+        let style = tunables.memory_style(&ty);
+        let mut memory = tunables.create_host_memory(&ty, &style).unwrap();
+
+        // Growing within the limit is fine
+        assert!(memory.0.grow(Pages(3)).is_ok());
+        assert_eq!(exceeded.load(Ordering::Acquire), false);
+
+        // Going past it fails and is remembered
+        assert!(memory.0.grow(Pages(1)).is_err());
+        assert_eq!(exceeded.load(Ordering::Acquire), true);
+    }
+
+    #[test]
+    fn test_display() {
+        let limits = ResourceLimits {
+            max_memory: Some(1024),
+            max_fuel: None,
+            max_fds: Some(3),
+        };
+        assert_eq!(
+            limits.to_string(),
+            "memory=1024, fuel=unlimited, fds=3".to_string()
+        );
+    }
+}
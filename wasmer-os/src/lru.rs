@@ -0,0 +1,262 @@
+use serde::*;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+
+struct LruEntry<V> {
+    val: V,
+    weight: usize,
+    tick: u64,
+}
+
+/// Least-recently-used cache that evicts entries once the combined weight
+/// of all the entries exceeds its budget
+pub struct LruCache<V> {
+    entries: HashMap<String, LruEntry<V>>,
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    weight: usize,
+    budget: usize,
+}
+
+impl<V> std::fmt::Debug for LruCache<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LruCache")
+            .field("entries", &self.entries.len())
+            .field("weight", &self.weight)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl<V> LruCache<V> {
+    pub fn new(budget: usize) -> LruCache<V> {
+        LruCache {
+            entries: HashMap::default(),
+            order: BTreeMap::default(),
+            tick: 0,
+            weight: 0,
+            budget,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Returns an entry and marks it as the most recently used
+    pub fn get(&mut self, key: &str) -> Option<&V> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        self.order.insert(tick, key.to_string());
+        entry.tick = tick;
+        Some(&entry.val)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Inserts an entry into the cache and returns all the entries that
+    /// had to be evicted in order to stay within the budget
+    pub fn insert(&mut self, key: String, val: V, weight: usize) -> Vec<(String, V)> {
+        self.remove(key.as_str());
+
+        let tick = self.next_tick();
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, LruEntry { val, weight, tick });
+        self.weight += weight;
+
+        let mut evicted = Vec::new();
+        while self.weight > self.budget {
+            match self.pop_oldest() {
+                Some((key, val, _)) => evicted.push((key, val)),
+                None => break,
+            }
+        }
+        evicted
+    }
+
+    /// Removes the least recently used entry and returns it along with its weight
+    pub fn pop_oldest(&mut self) -> Option<(String, V, usize)> {
+        let oldest = *self.order.keys().next()?;
+        let key = self.order.remove(&oldest)?;
+        let entry = self.entries.remove(&key)?;
+        self.weight -= entry.weight;
+        Some((key, entry.val, entry.weight))
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.weight -= entry.weight;
+        Some(entry.val)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.weight = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Combined weight of all the entries currently held in the cache
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+}
+
+/// Counters that track how well a cache is performing
+#[derive(Debug, Default)]
+pub struct CacheMetrics {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    pub evictions: AtomicU64,
+    pub disk_hits: AtomicU64,
+    pub disk_writes: AtomicU64,
+    pub disk_corrupt: AtomicU64,
+}
+
+/// Point in time copy of the cache metrics
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub disk_hits: u64,
+    pub disk_writes: u64,
+    pub disk_corrupt: u64,
+    pub entries: usize,
+    pub weight: usize,
+    pub budget: usize,
+}
+
+impl CacheMetrics {
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn evicted(&self, cnt: usize) {
+        self.evictions.fetch_add(cnt as u64, Ordering::Relaxed);
+    }
+
+    pub fn disk_hit(&self) {
+        self.disk_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn disk_write(&self) {
+        self.disk_writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn disk_corrupt(&self) {
+        self.disk_corrupt.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats<V>(&self, cache: &LruCache<V>) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            disk_hits: self.disk_hits.load(Ordering::Relaxed),
+            disk_writes: self.disk_writes.load(Ordering::Relaxed),
+            disk_corrupt: self.disk_corrupt.load(Ordering::Relaxed),
+            entries: cache.len(),
+            weight: cache.weight(),
+            budget: cache.budget(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = LruCache::new(10);
+        assert!(cache.insert("a".to_string(), 1, 4).is_empty());
+        assert!(cache.insert("b".to_string(), 2, 4).is_empty());
+
+        // Touching 'a' makes 'b' the oldest entry
+        assert_eq!(cache.get("a"), Some(&1));
+        let evicted = cache.insert("c".to_string(), 3, 4);
+        assert_eq!(evicted, vec![("b".to_string(), 2)]);
+        assert!(cache.contains_key("a"));
+        assert!(cache.contains_key("b") == false);
+        assert_eq!(cache.weight(), 8);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_replacing_an_entry_updates_its_weight() {
+        let mut cache = LruCache::new(10);
+        cache.insert("a".to_string(), 1, 6);
+        cache.insert("a".to_string(), 2, 3);
+        assert_eq!(cache.weight(), 3);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("a"), Some(&2));
+        assert_eq!(cache.remove("a"), Some(2));
+        assert_eq!(cache.weight(), 0);
+        assert_eq!(cache.remove("a"), None);
+    }
+
+    #[test]
+    fn test_entries_larger_than_the_budget_are_not_kept() {
+        let mut cache = LruCache::new(10);
+        cache.insert("a".to_string(), 1, 4);
+        let evicted = cache.insert("big".to_string(), 2, 11);
+        assert_eq!(evicted, vec![("a".to_string(), 1), ("big".to_string(), 2)]);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn test_pop_oldest() {
+        let mut cache = LruCache::new(usize::MAX);
+        cache.insert("a".to_string(), 1, 1);
+        cache.insert("b".to_string(), 2, 2);
+        cache.get("a");
+        assert_eq!(cache.pop_oldest(), Some(("b".to_string(), 2, 2)));
+        assert_eq!(cache.pop_oldest(), Some(("a".to_string(), 1, 1)));
+        assert_eq!(cache.pop_oldest(), None);
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut cache = LruCache::new(10);
+        let metrics = CacheMetrics::default();
+        cache.insert("a".to_string(), 1, 4);
+        metrics.hit();
+        metrics.hit();
+        metrics.miss();
+        metrics.evicted(3);
+        let stats = metrics.stats(&cache);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.evictions, 3);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.weight, 4);
+        assert_eq!(stats.budget, 10);
+
+        cache.clear();
+        assert_eq!(metrics.stats(&cache).entries, 0);
+        assert_eq!(metrics.stats(&cache).weight, 0);
+    }
+}
@@ -18,6 +18,7 @@ use super::eval::Process;
 use super::eval::*;
 use super::fd::*;
 use super::fs::*;
+use super::limits::*;
 use super::poll::*;
 use super::reactor::*;
 use super::tty::*;
@@ -29,6 +30,7 @@ pub struct ConsoleState {
     pub last_return: u32,
     pub unfinished_line: Arc<AtomicBool>,
     pub rootfs: UnionFileSystem,
+    pub limits: ResourceLimits,
 }
 
 impl ConsoleState {
@@ -40,6 +42,7 @@ impl ConsoleState {
             last_return: 0,
             unfinished_line,
             rootfs: root,
+            limits: ResourceLimits::default(),
         }
     }
 
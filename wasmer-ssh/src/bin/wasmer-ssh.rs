@@ -55,7 +55,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         wasmer_os::api::set_system_abi(sys);
 
                         // Start the SSH server
-                        let compiled_modules = Arc::new(CachedCompiledModules::new(Some(host.compiler_cache_path.clone())));
+                        let compiled_modules = Arc::new(CachedCompiledModules::new_with_budget(Some(host.compiler_cache_path.clone()), host.compiler_cache_memory * 1024 * 1024).with_binary_budget(host.binary_cache_memory * 1024 * 1024));
                         let server = Server::new(host, server_key, registry, compiled_modules, native_files, rx_exit).await;
                         server.listen().await?;
                         Ok(())
@@ -1,4 +1,5 @@
 use ate::mesh::Registry;
+use ate::prelude::*;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -10,6 +11,7 @@ use thrussh::server;
 use thrussh::server::Auth;
 use thrussh::server::Session;
 use thrussh::ChannelId;
+use thrussh::CryptoVec;
 use thrussh_keys::key::ed25519;
 use thrussh_keys::key::PublicKey;
 use wasmer_term::wasmer_os;
@@ -20,6 +22,8 @@ use wasmer_term::wasmer_os::bin_factory::CachedCompiledModules;
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
 
 use crate::native_files::NativeFileInterface;
+use crate::sftp::SftpSession;
+use crate::wizard::login_with_public_key;
 use crate::wizard::SshWizard;
 
 use super::console_handle::*;
@@ -31,8 +35,15 @@ pub struct Handler {
     pub peer_addr: Option<std::net::SocketAddr>,
     pub peer_addr_str: String,
     pub user: Option<String>,
+    /// Public key that the client authenticated with (if any)
     pub client_pubkey: Option<thrussh_keys::key::PublicKey>,
+    pub ssh_login_key: Option<EncryptKey>,
+    /// Session of the user that logged in with the public key of the client,
+    /// it is opened when the first channel is started (after authentication)
+    pub pubkey_session: Option<AteSessionType>,
     pub console: Option<Console>,
+    /// Channel that is running the SFTP subsystem (if any)
+    pub sftp: Option<(ChannelId, SftpSession)>,
     pub compiler: wasmer_os::eval::Compiler,
     pub rect: Arc<Mutex<ConsoleRect>>,
     pub wizard: Option<SshWizard>,
@@ -59,6 +70,31 @@ impl server::Handler for Handler {
         Box::pin(async move { Ok((self, session)) })
     }
 
+    fn auth_publickey(mut self, user: &str, public_key: &PublicKey) -> Self::FutureAuth {
+        debug!("authenticate with public key (user={})", user);
+        self.user = Some(user.to_string());
+        self.pubkey_session = None;
+        self.client_pubkey = None;
+
+        // Root is always rejected (as this is what bots attack on)
+        if user == "root" {
+            warn!("root attempt rejected from {}", self.peer_addr_str);
+            return self.finished_auth(Auth::Reject);
+        }
+
+        // Logins with keys are only possible when the authentication server trusts us
+        if self.ssh_login_key.is_none() || self.wizard.is_none() || user.contains('@') == false {
+            return self.finished_auth(Auth::Reject);
+        }
+
+        // Clients query the key before they sign with it (and the signature
+        // is not checked again afterwards) so only cheap checks are made here,
+        // the account is opened once authentication has finished by which
+        // point the client has proven that it holds the private key
+        self.client_pubkey = Some(clone_public_key(public_key));
+        self.finished_auth(Auth::Accept)
+    }
+
     fn auth_keyboard_interactive(
         mut self,
         user: &str,
@@ -68,6 +104,11 @@ impl server::Handler for Handler {
         debug!("authenticate with keyboard interactive (user={})", user);
         self.user = Some(user.to_string());
 
+        // The client only falls back to this method when it could not prove
+        // that it holds the public key (the key may only have been queried)
+        self.pubkey_session = None;
+        self.client_pubkey = None;
+
         // Get the current wizard or fail
         let wizard = match self.wizard.as_mut() {
             Some(a) => a,
@@ -100,6 +141,19 @@ impl server::Handler for Handler {
 
     fn data(mut self, channel: ChannelId, data: &[u8], session: Session) -> Self::FutureUnit {
         trace!("data on channel {:?}: len={:?}", channel, data.len());
+
+        // File transfers are binary so they are processed before the conversion
+        if let Some((sftp_channel, sftp)) = self.sftp.as_mut() {
+            if *sftp_channel == channel {
+                let replies = sftp.process(data);
+                let mut session = session;
+                for reply in replies {
+                    session.data(channel, CryptoVec::from_slice(&reply[..]));
+                }
+                return self.finished(session);
+            }
+        }
+
         let data = String::from_utf8(data.to_vec()).map_err(|_| {
             let err: SshServerError = SshServerErrorKind::BadData.into();
             err
@@ -132,6 +186,17 @@ impl server::Handler for Handler {
             let system = System::default();
             system
                 .spawn_shared(move || async move {
+                    // Users that logged in with their key skip the password
+                    if let Some(session) = self.login_with_client_key().await {
+                        if let Some(wizard) = self.wizard.as_mut() {
+                            wizard.state.email = self.user.clone();
+                            wizard.state.session = Some(session);
+                            if let Some(key) = self.client_pubkey.as_ref() {
+                                wizard.state.set_public_key(clone_public_key(key));
+                            }
+                        }
+                    }
+
                     // Get the wizard
                     let wizard = self.wizard.take().map(|a| {
                         Box::new(a) as Box<dyn term_api::WizardAbi + Send + Sync + 'static>
@@ -162,6 +227,156 @@ impl server::Handler for Handler {
         })
     }
 
+    fn exec_request(
+        mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: Session,
+    ) -> Self::FutureUnit {
+        let cmd = String::from_utf8_lossy(data).to_string();
+        debug!("exec_request (cmd={})", cmd);
+
+        Box::pin(async move {
+            // Create the handle
+            let handle = Arc::new(ConsoleHandle {
+                rect: self.rect.clone(),
+                channel: channel.clone(),
+                handle: session.handle(),
+                stdio_lock: self.stdio_lock.clone(),
+                enable_stderr: true,
+            });
+            let mut exit_handle = session.handle();
+
+            // Spawn a dedicated thread and wait for it to do its thing
+            let system = System::default();
+            system
+                .spawn_shared(move || async move {
+                    // There is no terminal to run the login wizard in so only users
+                    // that logged in with their key are able to run commands
+                    let mut console = match self.login_console(handle).await {
+                        Some(a) => a,
+                        None => {
+                            let msg = "exec requires a registered ssh key\r\n";
+                            let mut session = session;
+                            session.extended_data(
+                                channel,
+                                1,
+                                CryptoVec::from_slice(msg.as_bytes()),
+                            );
+                            session.exit_status_request(channel, 1);
+                            session.eof(channel);
+                            session.close(channel);
+                            return Ok((self, session));
+                        }
+                    };
+
+                    // Run the command and report its exit code once it finishes
+                    let exit_code = console.exec(cmd).await;
+                    self.console.replace(console);
+
+                    let system = System::default();
+                    system.fork_shared(move || async move {
+                        let code = exit_code.await.unwrap_or(1);
+                        debug!("exec finished (code={})", code);
+                        let _ = exit_handle.exit_status_request(channel, code).await;
+                        let _ = exit_handle.eof(channel).await;
+                        let _ = exit_handle.close(channel).await;
+                    });
+
+                    Ok((self, session))
+                })
+                .await
+                .unwrap()
+        })
+    }
+
+    fn subsystem_request(
+        mut self,
+        channel: ChannelId,
+        name: &str,
+        session: Session,
+    ) -> Self::FutureUnit {
+        debug!("subsystem_request (name={})", name);
+
+        if name != "sftp" {
+            warn!(
+                "unsupported subsystem ({}) from {}",
+                name, self.peer_addr_str
+            );
+            let mut session = session;
+            session.channel_failure(channel);
+            return self.finished(session);
+        }
+
+        Box::pin(async move {
+            // Create the handle
+            let handle = Arc::new(ConsoleHandle {
+                rect: self.rect.clone(),
+                channel: channel.clone(),
+                handle: session.handle(),
+                stdio_lock: self.stdio_lock.clone(),
+                enable_stderr: true,
+            });
+
+            // Spawn a dedicated thread and wait for it to do its thing
+            let system = System::default();
+            system
+                .spawn_shared(move || async move {
+                    // Files (for both sftp and scp) are served from a console that
+                    // is logged in the same way as the ones that run commands
+                    let mut session = session;
+                    let console = match self.login_console(handle).await {
+                        Some(a) => a,
+                        None => {
+                            warn!(
+                                "sftp requires a registered ssh key ({})",
+                                self.peer_addr_str
+                            );
+                            session.channel_failure(channel);
+                            return Ok((self, session));
+                        }
+                    };
+                    let fs = console.root_fs();
+                    self.sftp = Some((channel, SftpSession::new(fs)));
+                    if self.console.is_none() {
+                        self.console.replace(console);
+                    }
+                    Ok((self, session))
+                })
+                .await
+                .unwrap()
+        })
+    }
+
+    #[allow(unused_variables)]
+    fn window_change_request(
+        mut self,
+        channel: ChannelId,
+        col_width: u32,
+        row_height: u32,
+        pix_width: u32,
+        pix_height: u32,
+        session: Session,
+    ) -> Self::FutureUnit {
+        debug!(
+            "window_change_request (cols={}, rows={})",
+            col_width, row_height
+        );
+
+        {
+            let mut guard = self.rect.lock().unwrap();
+            guard.cols = col_width;
+            guard.rows = row_height;
+        }
+
+        Box::pin(async move {
+            if let Some(console) = self.console.as_mut() {
+                console.on_resize().await;
+            }
+            Ok((self, session))
+        })
+    }
+
     #[allow(unused_variables)]
     fn pty_request(
         self,
@@ -186,13 +401,83 @@ impl server::Handler for Handler {
     }
 }
 
+impl Handler {
+    /// Opens the account of the user with the public key that the client
+    /// authenticated with, this must only be called once authentication has
+    /// finished as only then has the client signed with the private key
+    async fn login_with_client_key(&mut self) -> Option<AteSessionType> {
+        if let Some(session) = self.pubkey_session.as_ref() {
+            return Some(session.clone());
+        }
+        let public_key = self.client_pubkey.as_ref().map(clone_public_key)?;
+        let user = self.user.clone()?;
+        let ssh_login_key = self.ssh_login_key.clone()?;
+        let auth = self.wizard.as_ref().map(|a| a.auth.clone())?;
+
+        let ret = login_with_public_key(
+            &self.registry,
+            &auth,
+            &ssh_login_key,
+            user.as_str(),
+            &public_key,
+            self.peer_addr,
+        )
+        .await;
+        match ret {
+            Ok(session) => {
+                info!("public key accepted (user={})", user);
+                self.pubkey_session = Some(session.clone());
+                Some(session)
+            }
+            Err(err) => {
+                debug!("public key rejected (user={}) - {:?}", user, err);
+                self.client_pubkey = None;
+                None
+            }
+        }
+    }
+
+    /// Creates a console that is logged in as the user that authenticated
+    /// with their key (used by commands and file transfers as neither of them
+    /// have a terminal to run the login wizard in)
+    async fn login_console(&mut self, handle: Arc<ConsoleHandle>) -> Option<Console> {
+        let session = self.login_with_client_key().await?;
+        let token = wasmer_auth::helper::session_to_b64(session).ok()?;
+
+        // Create the console
+        let fs = wasmer_os::fs::create_root_fs(None);
+        let location = "ssh://wasmer.sh/?no_welcome".to_string();
+        let user_agent = "ssh".to_string();
+        let compiled_modules = self.compiled_modules.clone();
+        let mut console = Console::new(
+            location,
+            user_agent,
+            self.compiler,
+            handle,
+            None,
+            fs,
+            compiled_modules,
+        );
+        console.prepare().await;
+
+        // Login with the session of the user before anything else is run
+        let cmd = format!("login --token {} > /dev/null", token);
+        match console.exec(cmd).await.await {
+            Ok(0) => Some(console),
+            code => {
+                warn!("console login failed (code={:?})", code);
+                None
+            }
+        }
+    }
+}
+
 impl Drop for Handler {
     fn drop(&mut self) {
         info!("ssh connection closed ({})", self.peer_addr_str);
     }
 }
 
-#[allow(dead_code)]
 fn clone_public_key(key: &PublicKey) -> PublicKey {
     match key {
         PublicKey::Ed25519(a) => PublicKey::Ed25519(ed25519::PublicKey { key: a.key.clone() }),
@@ -5,6 +5,7 @@ pub mod handler;
 pub mod key;
 pub mod opt;
 pub mod server;
+pub mod sftp;
 pub mod system;
 pub mod utils;
 pub mod wizard;
@@ -20,12 +20,24 @@ pub struct OptsHost {
     /// Location where cached compiled modules are stored
     #[clap(long, default_value = "~/wasmer/compiled")]
     pub compiler_cache_path: String,
+    /// Maximum amount of memory (in megabytes) used to hold compiled modules before
+    /// the least recently used ones are evicted (they are reloaded from the cache path)
+    #[clap(long, default_value = "512")]
+    pub compiler_cache_memory: usize,
+    /// Maximum amount of memory (in megabytes) used by each session to hold downloaded
+    /// binaries before the least recently used ones are spilled to the cache path
+    #[clap(long, default_value = "256")]
+    pub binary_cache_memory: usize,
     /// URL of the datachain servers (e.g. wss://wasmer.sh/db)
     #[clap(long)]
     pub db_url: Option<url::Url>,
     /// URL of the authentication servers (e.g. wss://wasmer.sh/auth)
     #[clap(long)]
     pub auth_url: Option<url::Url>,
+    /// Path to the secret key shared with the authentication servers that lets users login with
+    /// their SSH keys (when the key does not exist only passwords can be used)
+    #[clap(long, default_value = "~/wasmer/ssh-login.key.read")]
+    pub ssh_login_key_path: String,
     /// Location where the native binary files are stored
     #[clap(long, default_value = "wasmer.sh/www")]
     pub native_files: String,
@@ -26,6 +26,7 @@ pub struct Server {
     pub registry: Arc<Registry>,
     pub native_files: NativeFileInterface,
     pub auth: url::Url,
+    pub ssh_login_key: Option<EncryptKey>,
     pub compiled_modules: Arc<CachedCompiledModules>,
     pub exit_rx: watch::Receiver<bool>,
     pub stdio_lock: Arc<Mutex<()>>,
@@ -35,6 +36,10 @@ impl Server {
     pub async fn new(host: OptsHost, server_key: SshServerKey, registry: Arc<Registry>, compiled_modules: Arc<CachedCompiledModules>, native_files: NativeFileInterface, rx_exit: watch::Receiver<bool>) -> Self {
         // Succes
         let auth = wasmer_auth::prelude::origin_url(&host.auth_url, "auth");
+        let ssh_login_key = wasmer_auth::helper::try_load_key(host.ssh_login_key_path.clone());
+        if ssh_login_key.is_none() {
+            info!("logins with public keys are disabled as there is no ssh login key");
+        }
         Self {
             native_files,
             listen: host.listen,
@@ -45,6 +50,7 @@ impl Server {
             compiler: host.compiler,
             registry,
             auth,
+            ssh_login_key,
             compiled_modules,
             exit_rx: rx_exit,
             stdio_lock: Arc::new(Mutex::new(())),
@@ -89,10 +95,13 @@ impl server::Server for Server {
             native_files: self.native_files.clone(),
             compiler: self.compiler,
             console: None,
+            sftp: None,
             peer_addr,
             peer_addr_str,
             user: None,
             client_pubkey: None,
+            ssh_login_key: self.ssh_login_key.clone(),
+            pubkey_session: None,
             wizard: Some(wizard),
             compiled_modules: self.compiled_modules.clone(),
             stdio_lock: self.stdio_lock.clone(),
@@ -0,0 +1,775 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, instrument, span, trace, warn, Level};
+use wasmer_os::fs::UnionFileSystem;
+use wasmer_os::wasmer_vfs::FileSystem;
+use wasmer_os::wasmer_vfs::FsError;
+use wasmer_os::wasmer_vfs::Metadata;
+use wasmer_os::wasmer_vfs::VirtualFile;
+use wasmer_term::wasmer_os;
+
+// Version 3 of the protocol is the one that OpenSSH speaks
+// (draft-ietf-secsh-filexfer-02)
+const SFTP_VERSION: u32 = 3;
+
+const SSH_FXP_INIT: u8 = 1;
+const SSH_FXP_VERSION: u8 = 2;
+const SSH_FXP_OPEN: u8 = 3;
+const SSH_FXP_CLOSE: u8 = 4;
+const SSH_FXP_READ: u8 = 5;
+const SSH_FXP_WRITE: u8 = 6;
+const SSH_FXP_LSTAT: u8 = 7;
+const SSH_FXP_FSTAT: u8 = 8;
+const SSH_FXP_SETSTAT: u8 = 9;
+const SSH_FXP_FSETSTAT: u8 = 10;
+const SSH_FXP_OPENDIR: u8 = 11;
+const SSH_FXP_READDIR: u8 = 12;
+const SSH_FXP_REMOVE: u8 = 13;
+const SSH_FXP_MKDIR: u8 = 14;
+const SSH_FXP_RMDIR: u8 = 15;
+const SSH_FXP_REALPATH: u8 = 16;
+const SSH_FXP_STAT: u8 = 17;
+const SSH_FXP_RENAME: u8 = 18;
+const SSH_FXP_STATUS: u8 = 101;
+const SSH_FXP_HANDLE: u8 = 102;
+const SSH_FXP_DATA: u8 = 103;
+const SSH_FXP_NAME: u8 = 104;
+const SSH_FXP_ATTRS: u8 = 105;
+
+const SSH_FX_OK: u32 = 0;
+const SSH_FX_EOF: u32 = 1;
+const SSH_FX_NO_SUCH_FILE: u32 = 2;
+const SSH_FX_PERMISSION_DENIED: u32 = 3;
+const SSH_FX_FAILURE: u32 = 4;
+const SSH_FX_BAD_MESSAGE: u32 = 5;
+const SSH_FX_OP_UNSUPPORTED: u32 = 8;
+
+const SSH_FXF_READ: u32 = 0x00000001;
+const SSH_FXF_WRITE: u32 = 0x00000002;
+const SSH_FXF_APPEND: u32 = 0x00000004;
+const SSH_FXF_CREAT: u32 = 0x00000008;
+const SSH_FXF_TRUNC: u32 = 0x00000010;
+const SSH_FXF_EXCL: u32 = 0x00000020;
+
+const SSH_FILEXFER_ATTR_SIZE: u32 = 0x00000001;
+const SSH_FILEXFER_ATTR_UIDGID: u32 = 0x00000002;
+const SSH_FILEXFER_ATTR_PERMISSIONS: u32 = 0x00000004;
+const SSH_FILEXFER_ATTR_ACMODTIME: u32 = 0x00000008;
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+// Limits that protect the server from clients that ask for too much
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+const MAX_READ_SIZE: u32 = 256 * 1024;
+const MAX_DIR_ENTRIES: usize = 100;
+
+enum SftpHandle {
+    File(Box<dyn VirtualFile + Send + Sync>),
+    Dir(Vec<(String, Metadata)>),
+}
+
+/// Serves the files of a console over the SFTP protocol (version 3) which
+/// is what `sftp` and `scp` use to transfer files
+pub struct SftpSession {
+    fs: UnionFileSystem,
+    buffer: Vec<u8>,
+    handles: HashMap<u32, SftpHandle>,
+    next_handle: u32,
+}
+
+impl SftpSession {
+    pub fn new(fs: UnionFileSystem) -> SftpSession {
+        SftpSession {
+            fs,
+            buffer: Vec::new(),
+            handles: HashMap::default(),
+            next_handle: 1,
+        }
+    }
+
+    /// Feeds data received on the channel into the session and returns the
+    /// packets that should be sent back to the client
+    pub fn process(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut ret = Vec::new();
+        while self.buffer.len() >= 4 {
+            let len = u32::from_be_bytes([
+                self.buffer[0],
+                self.buffer[1],
+                self.buffer[2],
+                self.buffer[3],
+            ]) as usize;
+            if len > MAX_PACKET_SIZE {
+                warn!("sftp packet too big (len={})", len);
+                self.buffer.clear();
+                break;
+            }
+            if self.buffer.len() < 4 + len {
+                break;
+            }
+            let packet: Vec<u8> = self.buffer.drain(..4 + len).skip(4).collect();
+            if let Some(reply) = self.process_packet(&packet[..]) {
+                ret.push(reply);
+            }
+        }
+        ret
+    }
+
+    fn process_packet(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+        let mut reader = SftpReader::new(packet);
+        let kind = reader.u8()?;
+        if kind == SSH_FXP_INIT {
+            let version = reader.u32()?;
+            debug!("sftp init (version={})", version);
+            let mut reply = SftpWriter::new(SSH_FXP_VERSION);
+            reply.u32(SFTP_VERSION);
+            return Some(reply.finish());
+        }
+
+        let id = reader.u32()?;
+        let reply = match self.process_request(kind, id, &mut reader) {
+            Some(a) => a,
+            None => status(id, SSH_FX_BAD_MESSAGE),
+        };
+        Some(reply)
+    }
+
+    fn process_request(&mut self, kind: u8, id: u32, reader: &mut SftpReader) -> Option<Vec<u8>> {
+        let ret = match kind {
+            SSH_FXP_OPEN => {
+                let path = reader.path()?;
+                let pflags = reader.u32()?;
+                trace!("sftp open (path={}, pflags={})", path.display(), pflags);
+                let mut options = self.fs.new_open_options();
+                options
+                    .read(pflags & SSH_FXF_READ != 0)
+                    .write(pflags & SSH_FXF_WRITE != 0)
+                    .append(pflags & SSH_FXF_APPEND != 0)
+                    .truncate(pflags & SSH_FXF_TRUNC != 0);
+                if pflags & SSH_FXF_EXCL != 0 {
+                    options.create_new(true);
+                } else if pflags & SSH_FXF_CREAT != 0 {
+                    options.create(true);
+                }
+                match options.open(path.as_path()) {
+                    Ok(file) => self.new_handle(id, SftpHandle::File(file)),
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            SSH_FXP_CLOSE => {
+                let handle = reader.handle()?;
+                match self.handles.remove(&handle) {
+                    Some(SftpHandle::File(mut file)) => {
+                        let _ = file.flush();
+                        status(id, SSH_FX_OK)
+                    }
+                    Some(SftpHandle::Dir(_)) => status(id, SSH_FX_OK),
+                    None => status(id, SSH_FX_FAILURE),
+                }
+            }
+            SSH_FXP_READ => {
+                let handle = reader.handle()?;
+                let offset = reader.u64()?;
+                let len = reader.u32()?.min(MAX_READ_SIZE);
+                match self.handles.get_mut(&handle) {
+                    Some(SftpHandle::File(file)) => {
+                        let mut data = vec![0u8; len as usize];
+                        let read = file
+                            .seek(SeekFrom::Start(offset))
+                            .and_then(|_| file.read(&mut data[..]));
+                        match read {
+                            Ok(0) => status(id, SSH_FX_EOF),
+                            Ok(read) => {
+                                let mut reply = SftpWriter::new(SSH_FXP_DATA);
+                                reply.u32(id);
+                                reply.bytes(&data[..read]);
+                                reply.finish()
+                            }
+                            Err(err) => {
+                                debug!("sftp read failed - {}", err);
+                                status(id, SSH_FX_FAILURE)
+                            }
+                        }
+                    }
+                    _ => status(id, SSH_FX_FAILURE),
+                }
+            }
+            SSH_FXP_WRITE => {
+                let handle = reader.handle()?;
+                let offset = reader.u64()?;
+                let data = reader.bytes()?;
+                match self.handles.get_mut(&handle) {
+                    Some(SftpHandle::File(file)) => {
+                        let written = file
+                            .seek(SeekFrom::Start(offset))
+                            .and_then(|_| file.write_all(data));
+                        match written {
+                            Ok(_) => status(id, SSH_FX_OK),
+                            Err(err) => {
+                                debug!("sftp write failed - {}", err);
+                                status(id, SSH_FX_FAILURE)
+                            }
+                        }
+                    }
+                    _ => status(id, SSH_FX_FAILURE),
+                }
+            }
+            SSH_FXP_LSTAT | SSH_FXP_STAT => {
+                let path = reader.path()?;
+                match self.fs.metadata(path.as_path()) {
+                    Ok(meta) => {
+                        let mut reply = SftpWriter::new(SSH_FXP_ATTRS);
+                        reply.u32(id);
+                        reply.attrs(&meta);
+                        reply.finish()
+                    }
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            SSH_FXP_FSTAT => {
+                let handle = reader.handle()?;
+                match self.handles.get(&handle) {
+                    Some(SftpHandle::File(file)) => {
+                        let mut reply = SftpWriter::new(SSH_FXP_ATTRS);
+                        reply.u32(id);
+                        reply.file_attrs(file.as_ref());
+                        reply.finish()
+                    }
+                    _ => status(id, SSH_FX_FAILURE),
+                }
+            }
+            SSH_FXP_SETSTAT | SSH_FXP_FSETSTAT => {
+                // Permissions and times are not supported by the file system so
+                // they are ignored, however the size can still be changed
+                let target = if kind == SSH_FXP_SETSTAT {
+                    Err(reader.path()?)
+                } else {
+                    Ok(reader.handle()?)
+                };
+                let flags = reader.u32()?;
+                let size = if flags & SSH_FILEXFER_ATTR_SIZE != 0 {
+                    Some(reader.u64()?)
+                } else {
+                    None
+                };
+                match (size, target) {
+                    (Some(size), Ok(handle)) => match self.handles.get_mut(&handle) {
+                        Some(SftpHandle::File(file)) => match file.set_len(size) {
+                            Ok(_) => status(id, SSH_FX_OK),
+                            Err(err) => fs_status(id, err),
+                        },
+                        _ => status(id, SSH_FX_FAILURE),
+                    },
+                    (Some(size), Err(path)) => {
+                        let file = self.fs.new_open_options().write(true).open(path.as_path());
+                        match file.and_then(|mut a| a.set_len(size)) {
+                            Ok(_) => status(id, SSH_FX_OK),
+                            Err(err) => fs_status(id, err),
+                        }
+                    }
+                    (None, _) => status(id, SSH_FX_OK),
+                }
+            }
+            SSH_FXP_OPENDIR => {
+                let path = reader.path()?;
+                trace!("sftp opendir (path={})", path.display());
+                match self.fs.read_dir(path.as_path()) {
+                    Ok(dir) => {
+                        let mut entries = Vec::new();
+                        for entry in dir.filter_map(|a| a.ok()) {
+                            let name = match entry.path.file_name() {
+                                Some(a) => a.to_string_lossy().to_string(),
+                                None => continue,
+                            };
+                            let meta = match entry.metadata {
+                                Ok(a) => a,
+                                Err(_) => continue,
+                            };
+                            entries.push((name, meta));
+                        }
+                        entries.reverse();
+                        self.new_handle(id, SftpHandle::Dir(entries))
+                    }
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            SSH_FXP_READDIR => {
+                let handle = reader.handle()?;
+                match self.handles.get_mut(&handle) {
+                    Some(SftpHandle::Dir(entries)) if entries.len() <= 0 => status(id, SSH_FX_EOF),
+                    Some(SftpHandle::Dir(entries)) => {
+                        let count = entries.len().min(MAX_DIR_ENTRIES);
+                        let mut reply = SftpWriter::new(SSH_FXP_NAME);
+                        reply.u32(id);
+                        reply.u32(count as u32);
+                        for _ in 0..count {
+                            if let Some((name, meta)) = entries.pop() {
+                                reply.string(name.as_str());
+                                reply.string(long_name(name.as_str(), &meta).as_str());
+                                reply.attrs(&meta);
+                            }
+                        }
+                        reply.finish()
+                    }
+                    _ => status(id, SSH_FX_FAILURE),
+                }
+            }
+            SSH_FXP_REMOVE => {
+                let path = reader.path()?;
+                match self.fs.remove_file(path.as_path()) {
+                    Ok(_) => status(id, SSH_FX_OK),
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            SSH_FXP_MKDIR => {
+                let path = reader.path()?;
+                match self.fs.create_dir(path.as_path()) {
+                    Ok(_) => status(id, SSH_FX_OK),
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            SSH_FXP_RMDIR => {
+                let path = reader.path()?;
+                match self.fs.remove_dir(path.as_path()) {
+                    Ok(_) => status(id, SSH_FX_OK),
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            SSH_FXP_REALPATH => {
+                let path = reader.path()?;
+                let path = path.to_string_lossy().to_string();
+                let mut reply = SftpWriter::new(SSH_FXP_NAME);
+                reply.u32(id);
+                reply.u32(1);
+                reply.string(path.as_str());
+                reply.string(path.as_str());
+                reply.u32(0);
+                reply.finish()
+            }
+            SSH_FXP_RENAME => {
+                let from = reader.path()?;
+                let to = reader.path()?;
+                match self.fs.rename(from.as_path(), to.as_path()) {
+                    Ok(_) => status(id, SSH_FX_OK),
+                    Err(err) => fs_status(id, err),
+                }
+            }
+            kind => {
+                debug!("sftp unsupported request (type={})", kind);
+                status(id, SSH_FX_OP_UNSUPPORTED)
+            }
+        };
+        Some(ret)
+    }
+
+    fn new_handle(&mut self, id: u32, handle: SftpHandle) -> Vec<u8> {
+        let index = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.handles.insert(index, handle);
+
+        let mut reply = SftpWriter::new(SSH_FXP_HANDLE);
+        reply.u32(id);
+        reply.string(index.to_string().as_str());
+        reply.finish()
+    }
+}
+
+fn status(id: u32, code: u32) -> Vec<u8> {
+    let msg = match code {
+        SSH_FX_OK => "Success",
+        SSH_FX_EOF => "End of file",
+        SSH_FX_NO_SUCH_FILE => "No such file",
+        SSH_FX_PERMISSION_DENIED => "Permission denied",
+        SSH_FX_BAD_MESSAGE => "Bad message",
+        SSH_FX_OP_UNSUPPORTED => "Operation unsupported",
+        _ => "Failure",
+    };
+    let mut reply = SftpWriter::new(SSH_FXP_STATUS);
+    reply.u32(id);
+    reply.u32(code);
+    reply.string(msg);
+    reply.string("en");
+    reply.finish()
+}
+
+fn fs_status(id: u32, err: FsError) -> Vec<u8> {
+    let code = match err {
+        FsError::EntityNotFound | FsError::BaseNotDirectory => SSH_FX_NO_SUCH_FILE,
+        FsError::PermissionDenied => SSH_FX_PERMISSION_DENIED,
+        _ => SSH_FX_FAILURE,
+    };
+    status(id, code)
+}
+
+fn permissions(meta: &Metadata) -> u32 {
+    if meta.ft.dir {
+        S_IFDIR | 0o755
+    } else if meta.ft.symlink {
+        S_IFLNK | 0o777
+    } else {
+        S_IFREG | 0o644
+    }
+}
+
+fn long_name(name: &str, meta: &Metadata) -> String {
+    let kind = if meta.ft.dir {
+        "drwxr-xr-x"
+    } else if meta.ft.symlink {
+        "lrwxrwxrwx"
+    } else {
+        "-rw-r--r--"
+    };
+    format!("{} 1 wasmer wasmer {:>10} {}", kind, meta.len, name)
+}
+
+/// Paths are relative to the root of the file system and are normalized
+/// so that they can not escape it
+fn normalize_path(path: &str) -> PathBuf {
+    let mut ret = PathBuf::from("/");
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(a) => ret.push(a),
+            Component::ParentDir => {
+                ret.pop();
+            }
+            _ => {}
+        }
+    }
+    ret
+}
+
+struct SftpReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SftpReader<'a> {
+    fn new(data: &'a [u8]) -> SftpReader<'a> {
+        SftpReader { data }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (ret, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(ret)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|a| a[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let a = self.take(4)?;
+        Some(u32::from_be_bytes([a[0], a[1], a[2], a[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let hi = self.u32()? as u64;
+        let lo = self.u32()? as u64;
+        Some((hi << 32) | lo)
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn path(&mut self) -> Option<PathBuf> {
+        let path = String::from_utf8_lossy(self.bytes()?);
+        Some(normalize_path(path.as_ref()))
+    }
+
+    fn handle(&mut self) -> Option<u32> {
+        let handle = std::str::from_utf8(self.bytes()?).ok()?;
+        handle.parse().ok()
+    }
+}
+
+struct SftpWriter {
+    data: Vec<u8>,
+}
+
+impl SftpWriter {
+    fn new(kind: u8) -> SftpWriter {
+        let mut data = vec![0u8; 4];
+        data.push(kind);
+        SftpWriter { data }
+    }
+
+    fn u32(&mut self, val: u32) {
+        self.data.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn u64(&mut self, val: u64) {
+        self.data.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn bytes(&mut self, val: &[u8]) {
+        self.u32(val.len() as u32);
+        self.data.extend_from_slice(val);
+    }
+
+    fn string(&mut self, val: &str) {
+        self.bytes(val.as_bytes());
+    }
+
+    fn attrs(&mut self, meta: &Metadata) {
+        self.u32(
+            SSH_FILEXFER_ATTR_SIZE
+                | SSH_FILEXFER_ATTR_UIDGID
+                | SSH_FILEXFER_ATTR_PERMISSIONS
+                | SSH_FILEXFER_ATTR_ACMODTIME,
+        );
+        self.u64(meta.len);
+        self.u32(0);
+        self.u32(0);
+        self.u32(permissions(meta));
+        self.u32(to_secs(meta.accessed));
+        self.u32(to_secs(meta.modified));
+    }
+
+    fn file_attrs(&mut self, file: &(dyn VirtualFile + Send + Sync)) {
+        self.u32(
+            SSH_FILEXFER_ATTR_SIZE
+                | SSH_FILEXFER_ATTR_UIDGID
+                | SSH_FILEXFER_ATTR_PERMISSIONS
+                | SSH_FILEXFER_ATTR_ACMODTIME,
+        );
+        self.u64(file.size());
+        self.u32(0);
+        self.u32(0);
+        self.u32(S_IFREG | 0o644);
+        self.u32(to_secs(file.last_accessed()));
+        self.u32(to_secs(file.last_modified()));
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let len = (self.data.len() - 4) as u32;
+        self.data[..4].copy_from_slice(&len.to_be_bytes());
+        self.data
+    }
+}
+
+/// File times are held in nanoseconds while SFTP uses seconds
+fn to_secs(nanos: u64) -> u32 {
+    (nanos / 1_000_000_000) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_os::fs::TmpFileSystem;
+
+    fn create_session() -> SftpSession {
+        let mut fs = UnionFileSystem::new();
+        fs.mount("root", "/", false, Box::new(TmpFileSystem::new()), None);
+        SftpSession::new(fs)
+    }
+
+    /// Sends a single request and returns the kind and body of the reply
+    fn request(
+        session: &mut SftpSession,
+        kind: u8,
+        id: u32,
+        args: impl FnOnce(&mut SftpWriter),
+    ) -> (u8, Vec<u8>) {
+        let mut packet = SftpWriter::new(kind);
+        packet.u32(id);
+        args(&mut packet);
+        let mut replies = session.process(&packet.finish()[..]);
+        assert_eq!(replies.len(), 1);
+        let reply = replies.remove(0);
+        let mut reader = SftpReader::new(&reply[..]);
+        assert_eq!(reader.u32(), Some(reply.len() as u32 - 4));
+        let kind = reader.u8().unwrap();
+        assert_eq!(reader.u32(), Some(id));
+        (kind, reader.data.to_vec())
+    }
+
+    fn expect_status(reply: (u8, Vec<u8>), code: u32) {
+        assert_eq!(reply.0, SSH_FXP_STATUS);
+        assert_eq!(SftpReader::new(&reply.1[..]).u32(), Some(code));
+    }
+
+    fn expect_handle(reply: (u8, Vec<u8>)) -> String {
+        assert_eq!(reply.0, SSH_FXP_HANDLE);
+        let mut reader = SftpReader::new(&reply.1[..]);
+        String::from_utf8(reader.bytes().unwrap().to_vec()).unwrap()
+    }
+
+    fn open(session: &mut SftpSession, id: u32, path: &str, pflags: u32) -> String {
+        let reply = request(session, SSH_FXP_OPEN, id, |a| {
+            a.string(path);
+            a.u32(pflags);
+            a.u32(0);
+        });
+        expect_handle(reply)
+    }
+
+    fn write(session: &mut SftpSession, id: u32, path: &str, data: &[u8]) {
+        let handle = open(session, id, path, SSH_FXF_WRITE | SSH_FXF_CREAT);
+        let reply = request(session, SSH_FXP_WRITE, id, |a| {
+            a.string(handle.as_str());
+            a.u64(0);
+            a.bytes(data);
+        });
+        expect_status(reply, SSH_FX_OK);
+        let reply = request(session, SSH_FXP_CLOSE, id, |a| a.string(handle.as_str()));
+        expect_status(reply, SSH_FX_OK);
+    }
+
+    #[test]
+    fn test_init_negotiates_version_3() {
+        let mut session = create_session();
+        let mut packet = SftpWriter::new(SSH_FXP_INIT);
+        packet.u32(6);
+        let packet = packet.finish();
+
+        // Packets may arrive split across many channel messages
+        let (head, tail) = packet.split_at(3);
+        assert_eq!(session.process(head).len(), 0);
+        let replies = session.process(tail);
+        assert_eq!(replies.len(), 1);
+
+        let mut reader = SftpReader::new(&replies[0][..]);
+        assert_eq!(reader.u32(), Some(5));
+        assert_eq!(reader.u8(), Some(SSH_FXP_VERSION));
+        assert_eq!(reader.u32(), Some(SFTP_VERSION));
+    }
+
+    #[test]
+    fn test_open_write_read() {
+        let mut session = create_session();
+        let handle = open(
+            &mut session,
+            1,
+            "/hello.txt",
+            SSH_FXF_READ | SSH_FXF_WRITE | SSH_FXF_CREAT,
+        );
+        let reply = request(&mut session, SSH_FXP_WRITE, 2, |a| {
+            a.string(handle.as_str());
+            a.u64(0);
+            a.bytes(b"hello world");
+        });
+        expect_status(reply, SSH_FX_OK);
+
+        let reply = request(&mut session, SSH_FXP_READ, 3, |a| {
+            a.string(handle.as_str());
+            a.u64(6);
+            a.u32(100);
+        });
+        assert_eq!(reply.0, SSH_FXP_DATA);
+        assert_eq!(SftpReader::new(&reply.1[..]).bytes(), Some(&b"world"[..]));
+
+        // Reading past the end reports the end of the file
+        let reply = request(&mut session, SSH_FXP_READ, 4, |a| {
+            a.string(handle.as_str());
+            a.u64(11);
+            a.u32(100);
+        });
+        expect_status(reply, SSH_FX_EOF);
+
+        let reply = request(&mut session, SSH_FXP_CLOSE, 5, |a| {
+            a.string(handle.as_str())
+        });
+        expect_status(reply, SSH_FX_OK);
+        let reply = request(&mut session, SSH_FXP_READ, 6, |a| {
+            a.string(handle.as_str());
+            a.u64(0);
+            a.u32(100);
+        });
+        expect_status(reply, SSH_FX_FAILURE);
+
+        // Missing files can not be opened without the create flag
+        let reply = request(&mut session, SSH_FXP_OPEN, 7, |a| {
+            a.string("/missing.txt");
+            a.u32(SSH_FXF_READ);
+            a.u32(0);
+        });
+        expect_status(reply, SSH_FX_NO_SUCH_FILE);
+    }
+
+    #[test]
+    fn test_stat() {
+        let mut session = create_session();
+        write(&mut session, 1, "/hello.txt", b"hello world");
+
+        let reply = request(&mut session, SSH_FXP_STAT, 2, |a| a.string("/hello.txt"));
+        assert_eq!(reply.0, SSH_FXP_ATTRS);
+        let mut reader = SftpReader::new(&reply.1[..]);
+        assert_eq!(
+            reader.u32(),
+            Some(
+                SSH_FILEXFER_ATTR_SIZE
+                    | SSH_FILEXFER_ATTR_UIDGID
+                    | SSH_FILEXFER_ATTR_PERMISSIONS
+                    | SSH_FILEXFER_ATTR_ACMODTIME
+            )
+        );
+        assert_eq!(reader.u64(), Some(11));
+        assert_eq!(reader.u32(), Some(0));
+        assert_eq!(reader.u32(), Some(0));
+        assert_eq!(reader.u32(), Some(S_IFREG | 0o644));
+
+        // Paths can not escape the root of the file system
+        let reply = request(&mut session, SSH_FXP_LSTAT, 3, |a| {
+            a.string("../../hello.txt")
+        });
+        assert_eq!(reply.0, SSH_FXP_ATTRS);
+
+        let reply = request(&mut session, SSH_FXP_STAT, 4, |a| a.string("/missing.txt"));
+        expect_status(reply, SSH_FX_NO_SUCH_FILE);
+    }
+
+    #[test]
+    fn test_readdir() {
+        let mut session = create_session();
+        let reply = request(&mut session, SSH_FXP_MKDIR, 1, |a| {
+            a.string("/docs");
+            a.u32(0);
+        });
+        expect_status(reply, SSH_FX_OK);
+        write(&mut session, 2, "/docs/a.txt", b"a");
+        write(&mut session, 3, "/docs/b.txt", b"bb");
+
+        let reply = request(&mut session, SSH_FXP_OPENDIR, 4, |a| a.string("/docs"));
+        let handle = expect_handle(reply);
+        let reply = request(&mut session, SSH_FXP_READDIR, 5, |a| {
+            a.string(handle.as_str())
+        });
+        assert_eq!(reply.0, SSH_FXP_NAME);
+        let mut reader = SftpReader::new(&reply.1[..]);
+        assert_eq!(reader.u32(), Some(2));
+        let mut names = Vec::new();
+        for _ in 0..2 {
+            names.push(String::from_utf8(reader.bytes().unwrap().to_vec()).unwrap());
+            reader.bytes().unwrap();
+            assert!(reader.u32().unwrap() & SSH_FILEXFER_ATTR_SIZE != 0);
+            reader.u64().unwrap();
+            reader.take(20).unwrap();
+        }
+        names.sort();
+        assert_eq!(names, vec!["a.txt".to_string(), "b.txt".to_string()]);
+
+        // Once all the entries have been read the end is reported
+        let reply = request(&mut session, SSH_FXP_READDIR, 6, |a| {
+            a.string(handle.as_str())
+        });
+        expect_status(reply, SSH_FX_EOF);
+
+        let reply = request(&mut session, SSH_FXP_OPENDIR, 7, |a| a.string("/missing"));
+        expect_status(reply, SSH_FX_NO_SUCH_FILE);
+    }
+}
@@ -130,10 +130,26 @@ impl wasmer_os::api::SystemAbi for System {
         self.inner.reqwest(url, method, options, headers, data)
     }
 
+    /// Performs a HTTP or HTTPS request that streams the request and response bodies
+    async fn reqwest_stream(
+        &self,
+        url: &str,
+        method: &str,
+        options: ReqwestOptions,
+        headers: Vec<(String, String)>,
+        body: Option<mpsc::Receiver<Vec<u8>>>,
+    ) -> Result<ReqwestResponseStream, u32> {
+        self.inner.reqwest_stream(url, method, options, headers, body).await
+    }
+
     async fn web_socket(&self, url: &str) -> Result<Box<dyn WebSocketAbi>, String> {
         self.inner.web_socket(url).await
     }
 
+    async fn web_socket_with(&self, url: &str, options: WebSocketOptions) -> Result<Box<dyn WebSocketAbi>, String> {
+        self.inner.web_socket_with(url, options).await
+    }
+
     async fn webgl(&self) -> Option<Box<dyn WebGlAbi>> {
         self.inner.webgl().await
     }
@@ -151,6 +167,8 @@ fn conv_err(err: FileSystemError) -> u32 {
         FileSystemError(FileSystemErrorKind::NotDirectory, _) => err::ERR_ENOTDIR,
         FileSystemError(FileSystemErrorKind::IsDirectory, _) => err::ERR_EISDIR,
         FileSystemError(FileSystemErrorKind::NotImplemented, _) => err::ERR_ENOSYS,
+        FileSystemError(FileSystemErrorKind::WouldBlock, _) => err::ERR_EWOULDBLOCK,
+        FileSystemError(FileSystemErrorKind::QuotaExceeded, _) => err::ERR_EDQUOT,
         _ => err::ERR_EIO,
     }
 }
@@ -2,11 +2,13 @@ use super::cconst::CConst;
 use ate::prelude::*;
 use wasmer_auth::error::*;
 use wasmer_auth::helper::*;
+use wasmer_auth::model::SshKeyType;
 use wasmer_auth::request::*;
 use std::borrow::Cow;
 use std::sync::Arc;
 use thrussh::server::*;
 use thrussh_keys::key::PublicKey;
+use thrussh_keys::PublicKeyBase64;
 use wasmer_term::wasmer_os::api as term_api;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
@@ -583,6 +585,7 @@ async fn login(
         email: username.clone(),
         secret: read_key,
         verification_code: state.verify_code.clone(),
+        token: None,
     };
 
     // Attempt the login request with a 10 second timeout
@@ -621,6 +624,72 @@ async fn login(
     LoginResult::LoginWithUser
 }
 
+/// Logs the user in with an SSH key that the client has proven it holds, the
+/// authentication server only trusts this when the request carries a proof
+/// computed with the SSH login key it shares with this server (which also
+/// vouches for the address the client connected from)
+pub async fn login_with_public_key(
+    registry: &Arc<Registry>,
+    auth: &url::Url,
+    ssh_login_key: &EncryptKey,
+    email: &str,
+    public_key: &PublicKey,
+    peer_addr: Option<std::net::SocketAddr>,
+) -> Result<AteSessionType, LoginResult> {
+    // Open a command chain
+    let chain = match registry.open_cmd(&auth).await {
+        Ok(a) => a,
+        Err(err) => {
+            debug!("{}", err);
+            return Err(LoginResult::InternalError);
+        }
+    };
+
+    let key_type = match public_key {
+        PublicKey::Ed25519(_) => SshKeyType::ED25519,
+    };
+    let public_key = public_key.public_key_base64();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|a| a.as_secs() as i64)
+        .unwrap_or_default();
+    let source = peer_addr.map(|a| a.ip().to_string());
+    let proof = ssh_login_proof(
+        ssh_login_key,
+        email,
+        public_key.as_str(),
+        timestamp,
+        source.as_deref(),
+    );
+    let login = SshLoginRequest {
+        email: email.to_string(),
+        key_type,
+        public_key,
+        timestamp,
+        source,
+        proof,
+    };
+
+    trace!("invoking ssh login (email={})", login.email);
+    let response: Result<LoginResponse, SshKeyFailed> = match chain.invoke(login).await {
+        Ok(a) => a,
+        Err(err) => {
+            debug!("{}", err);
+            return Err(LoginResult::InternalError);
+        }
+    };
+    match response {
+        Ok(a) => Ok(AteSessionType::User(a.authority)),
+        Err(SshKeyFailed::UnknownKey) => Err(LoginResult::IncorrectPassword),
+        Err(SshKeyFailed::AccountLocked(_)) => Err(LoginResult::AccountLocked),
+        Err(SshKeyFailed::Unverified(_)) => Err(LoginResult::Unverified),
+        Err(err) => {
+            debug!("ssh login failed - {:?}", err);
+            Err(LoginResult::InternalError)
+        }
+    }
+}
+
 async fn user_exists(
     registry: &Arc<Registry>,
     auth: &url::Url,
@@ -741,6 +810,10 @@ async fn sudo(registry: &Arc<Registry>, auth: &url::Url, state: &mut SshWizardSt
             warn!("invoke sudo failed: unverified ({})", msg);
             return SudoResult::InternalError;
         }
+        Err(SudoFailed::BreakGlass(group)) => {
+            warn!("invoke sudo failed: break-glass ({})", group);
+            return SudoResult::InternalError;
+        }
         Err(SudoFailed::NoMasterKey) => {
             warn!("invoke sudo failed: no master key");
             return SudoResult::InternalError;
@@ -34,6 +34,14 @@ struct Opts {
     /// Location where cached compiled modules are stored
     #[clap(long, default_value = "~/wasmer/compiled")]
     pub compiler_cache_path: String,
+    /// Maximum amount of memory (in megabytes) used to hold compiled modules before
+    /// the least recently used ones are evicted (they are reloaded from the cache path)
+    #[clap(long, default_value = "512")]
+    pub compiler_cache_memory: usize,
+    /// Maximum amount of memory (in megabytes) used by each session to hold downloaded
+    /// binaries before the least recently used ones are spilled to the cache path
+    #[clap(long, default_value = "256")]
+    pub binary_cache_memory: usize,
     /// Uses a local directory for native files rather than the published ate chain
     #[clap(long)]
     pub native_files_path: Option<String>,
@@ -79,7 +87,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
 
     // Build the compiled modules
-    let compiled_modules = Arc::new(CachedCompiledModules::new(Some(opts.compiler_cache_path)));
+    let compiled_modules = Arc::new(CachedCompiledModules::new_with_budget(Some(opts.compiler_cache_path), opts.compiler_cache_memory * 1024 * 1024).with_binary_budget(opts.binary_cache_memory * 1024 * 1024));
 
     // If a command is passed in then pass it into the console
     let location = if let Some(run) = opts.run {
@@ -19,6 +19,7 @@ use wasmer_os::api::AsyncResult;
 use wasmer_os::api::SerializationFormat;
 use wasmer_os::api::ThreadLocal;
 use wasmer_os::api::WebSocketAbi;
+use wasmer_os::api::WebSocketOptions;
 use wasmer_os::api::WebGlAbi;
 use wasmer_os::err;
 use tokio::runtime::Builder;
@@ -236,7 +237,7 @@ impl SystemAbi for SysSystem {
         &self,
         url: &str,
         method: &str,
-        _options: ReqwestOptions,
+        options: ReqwestOptions,
         headers: Vec<(String, String)>,
         data: Option<Vec<u8>>,
     ) -> AsyncResult<Result<ReqwestResponse, u32>> {
@@ -252,22 +253,8 @@ impl SystemAbi for SysSystem {
                         err::ERR_EIO
                     })?;
 
-                    let client = reqwest::ClientBuilder::default().build().map_err(|err| {
-                        debug!("failed to build reqwest client - {}", err);
-                        err::ERR_EIO
-                    })?;
-
-                    let mut builder = client.request(method, url.as_str());
-                    for (header, val) in headers {
-                        if let Ok(header) =
-                            reqwest::header::HeaderName::from_bytes(header.as_bytes())
-                        {
-                            builder = builder.header(header, val);
-                        } else {
-                            debug!("failed to parse header - {}", header);
-                        }
-                    }
-
+                    let client = reqwest_client(&options)?;
+                    let mut builder = reqwest_builder(&client, method, url.as_str(), headers);
                     if let Some(data) = data {
                         builder = builder.body(reqwest::Body::from(data));
                     }
@@ -307,10 +294,103 @@ impl SystemAbi for SysSystem {
         AsyncResult::new(SerializationFormat::Bincode, rx_done)
     }
 
+    /// Performs a HTTP or HTTPS request that streams the request body up
+    /// and the response body back down
+    async fn reqwest_stream(
+        &self,
+        url: &str,
+        method: &str,
+        options: ReqwestOptions,
+        headers: Vec<(String, String)>,
+        body: Option<mpsc::Receiver<Vec<u8>>>,
+    ) -> Result<ReqwestResponseStream, u32> {
+        let method = reqwest::Method::try_from(method).map_err(|err| {
+            debug!("failed to convert method ({}) - {}", method, err);
+            err::ERR_EIO
+        })?;
+
+        let client = reqwest_client(&options)?;
+        let mut builder = reqwest_builder(&client, method, url, headers);
+        if let Some(body) = body {
+            let body = futures::stream::unfold(body, |mut body| async move {
+                body.recv()
+                    .await
+                    .map(|data| (Ok::<_, io::Error>(data), body))
+            });
+            builder = builder.body(reqwest::Body::wrap_stream(body));
+        }
+
+        let request = builder.build().map_err(|err| {
+            debug!("failed to convert request (url={}) - {}", url, err);
+            err::ERR_EIO
+        })?;
+
+        let mut response = client.execute(request).await.map_err(|err| {
+            debug!("failed to execute reqest - {}", err);
+            if err.is_timeout() {
+                err::ERR_ETIMEDOUT
+            } else {
+                err::ERR_EIO
+            }
+        })?;
+
+        let status = response.status();
+        let head = ReqwestResponse {
+            pos: 0usize,
+            ok: status.is_success(),
+            status: status.as_u16(),
+            status_text: status.as_str().to_string(),
+            redirected: response.url().as_str() != url,
+            data: None,
+            headers: response
+                .headers()
+                .iter()
+                .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.to_string(), v.to_string())))
+                .collect(),
+        };
+
+        // The response body is pumped in the background until its finished
+        // or the receiver is dropped
+        let (tx_body, rx_body) = mpsc::channel(1);
+        self.runtime.spawn(async move {
+            loop {
+                let ret = match response.chunk().await {
+                    Ok(Some(chunk)) => Ok(chunk.to_vec()),
+                    Ok(None) => break,
+                    Err(err) => {
+                        debug!("failed to read response chunk - {}", err);
+                        Err(if err.is_timeout() {
+                            err::ERR_ETIMEDOUT
+                        } else {
+                            err::ERR_EIO
+                        })
+                    }
+                };
+                let failed = ret.is_err();
+                if tx_body.send(ret).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(ReqwestResponseStream {
+            head,
+            body: rx_body,
+        })
+    }
+
     async fn web_socket(&self, url: &str) -> Result<Box<dyn WebSocketAbi>, String> {
         return Ok(Box::new(SysWebSocket::new(url).await?));
     }
 
+    async fn web_socket_with(
+        &self,
+        url: &str,
+        options: WebSocketOptions,
+    ) -> Result<Box<dyn WebSocketAbi>, String> {
+        return Ok(Box::new(SysWebSocket::new_with(url, options).await?));
+    }
+
     // WebGL is not supported here
     async fn webgl(&self) -> Option<Box<dyn WebGlAbi>> {
         None
@@ -379,3 +459,31 @@ impl ConsoleAbi for SysSystem {
         let _ = self.exit_tx.send(true);
     }
 }
+
+fn reqwest_client(options: &ReqwestOptions) -> Result<reqwest::Client, u32> {
+    let mut builder = reqwest::ClientBuilder::default();
+    if let Some(timeout_ms) = options.timeout_ms {
+        builder = builder.timeout(Duration::from_millis(timeout_ms));
+    }
+    builder.build().map_err(|err| {
+        debug!("failed to build reqwest client - {}", err);
+        err::ERR_EIO
+    })
+}
+
+fn reqwest_builder(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    url: &str,
+    headers: Vec<(String, String)>,
+) -> reqwest::RequestBuilder {
+    let mut builder = client.request(method, url);
+    for (header, val) in headers {
+        if let Ok(header) = reqwest::header::HeaderName::from_bytes(header.as_bytes()) {
+            builder = builder.header(header, val);
+        } else {
+            debug!("failed to parse header - {}", header);
+        }
+    }
+    builder
+}
@@ -8,9 +8,18 @@ use std::sync::Mutex;
 use wasmer_os::api::System;
 use wasmer_os::api::SystemAbiExt;
 use wasmer_os::api::WebSocketAbi;
+use wasmer_os::api::WebSocketClose;
+use wasmer_os::api::WebSocketFrame;
+use wasmer_os::api::WebSocketOptions;
 use tokio::net::TcpStream;
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::HeaderName;
+use tokio_tungstenite::tungstenite::http::header::HeaderValue;
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 
 #[allow(unused_imports)]
 use tracing::{debug, error, info, instrument, span, trace, warn, Level};
@@ -20,15 +29,40 @@ pub struct SysWebSocket {
     sink: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
     stream: Option<SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>>,
     on_close: Arc<Mutex<Option<Box<dyn Fn() + Send + 'static>>>>,
+    protocol: Option<String>,
 }
 
 impl SysWebSocket {
     pub async fn new(url: &str) -> Result<SysWebSocket, String> {
+        SysWebSocket::new_with(url, WebSocketOptions::default()).await
+    }
+
+    pub async fn new_with(url: &str, options: WebSocketOptions) -> Result<SysWebSocket, String> {
         let url = url::Url::parse(url)
             .map_err(|err| err.to_string())?;
 
-        let (ws_stream, _) = connect_async(url).await
+        let mut request = url.into_client_request()
+            .map_err(|err| err.to_string())?;
+        for (name, value) in options.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|err| format!("invalid header name ({}) - {}", name, err))?;
+            let value = HeaderValue::from_str(value.as_str())
+                .map_err(|err| format!("invalid header value - {}", err))?;
+            request.headers_mut().append(name, value);
+        }
+        if options.protocols.len() > 0 {
+            let protocols = HeaderValue::from_str(options.protocols.join(", ").as_str())
+                .map_err(|err| format!("invalid protocols - {}", err))?;
+            request.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, protocols);
+        }
+
+        let (ws_stream, response) = connect_async(request).await
             .map_err(|err| format!("failed to connect - {}", err))?;
+        let protocol = response
+            .headers()
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|a| a.to_str().ok())
+            .map(|a| a.to_string());
         let (sink, stream) = ws_stream.split();
 
         Ok(
@@ -37,6 +71,7 @@ impl SysWebSocket {
                 sink,
                 stream: Some(stream),
                 on_close: Arc::new(Mutex::new(None)),
+                protocol,
             }
         )
     }
@@ -55,18 +90,38 @@ impl WebSocketAbi for SysWebSocket {
     }
 
     fn set_onmessage(&mut self, callback: Box<dyn Fn(Vec<u8>) + Send + 'static>) {
+        self.set_onframe(Box::new(move |frame| {
+            match frame {
+                WebSocketFrame::Binary(msg) => {
+                    callback(msg);
+                }
+                a => {
+                    debug!("received invalid msg: {}", a);
+                }
+            }
+        }));
+    }
+
+    fn set_onframe(&mut self, callback: Box<dyn Fn(WebSocketFrame) + Send + 'static>) {
         if let Some(mut stream) = self.stream.take() {
             let on_close = self.on_close.clone();
             self.system.fork_shared(move || async move {
                 while let Some(msg) = stream.next().await {
-                    match msg {
-                        Ok(Message::Binary(msg)) => {
-                            callback(msg);
-                        }
+                    let frame = match msg {
+                        Ok(Message::Text(msg)) => WebSocketFrame::Text(msg),
+                        Ok(Message::Binary(msg)) => WebSocketFrame::Binary(msg),
+                        Ok(Message::Ping(msg)) => WebSocketFrame::Ping(msg),
+                        Ok(Message::Pong(msg)) => WebSocketFrame::Pong(msg),
+                        Ok(Message::Close(msg)) => WebSocketFrame::Close(msg.map(|a| WebSocketClose {
+                            code: u16::from(a.code),
+                            reason: a.reason.to_string(),
+                        })),
                         a => {
                             debug!("received invalid msg: {:?}", a);
+                            continue;
                         }
-                    }
+                    };
+                    callback(frame);
                 }
                 let on_close = on_close.lock().unwrap();
                 if let Some(on_close) = on_close.as_ref() {
@@ -76,6 +131,10 @@ impl WebSocketAbi for SysWebSocket {
         }
     }
 
+    fn protocol(&self) -> Option<String> {
+        self.protocol.clone()
+    }
+
     async fn send(&mut self, data: Vec<u8>) -> Result<(), String> {
         self.sink
             .send(Message::binary(data))
@@ -83,4 +142,22 @@ impl WebSocketAbi for SysWebSocket {
             .map_err(|err| err.to_string())?;
         Ok(())
     }
+
+    async fn send_frame(&mut self, frame: WebSocketFrame) -> Result<(), String> {
+        let msg = match frame {
+            WebSocketFrame::Text(a) => Message::Text(a),
+            WebSocketFrame::Binary(a) => Message::Binary(a),
+            WebSocketFrame::Ping(a) => Message::Ping(a),
+            WebSocketFrame::Pong(a) => Message::Pong(a),
+            WebSocketFrame::Close(a) => Message::Close(a.map(|a| CloseFrame {
+                code: CloseCode::from(a.code),
+                reason: a.reason.into(),
+            })),
+        };
+        self.sink
+            .send(msg)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 use js_sys::Function;
 use std::cell::Cell;
+use std::rc::Rc;
 #[allow(unused_imports, dead_code)]
 use tracing::{debug, error, info, trace, warn};
 use wasm_bindgen::prelude::*;
@@ -124,6 +125,71 @@ impl AnimationFrameCallbackWrapper /*<'a>*/ {
     }
 }
 
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &Function, timeout: i32) -> i32;
+    #[wasm_bindgen(js_name = clearTimeout)]
+    fn clear_timeout(id: i32);
+}
+
+/// Aborts a fetch (including the reading of its body) once the timeout
+/// elapses, the timer is cleared when this is dropped
+pub struct FetchTimeout {
+    controller: AbortController,
+    expired: Rc<Cell<bool>>,
+    id: i32,
+    _callback: Closure<dyn FnMut()>,
+}
+
+impl FetchTimeout {
+    pub fn new(timeout_ms: u64) -> Result<FetchTimeout, u32> {
+        let controller = AbortController::new().map_err(|_| err::ERR_EIO)?;
+        let expired = Rc::new(Cell::new(false));
+        let callback = {
+            let controller = controller.clone();
+            let expired = expired.clone();
+            Closure::wrap(Box::new(move || {
+                expired.set(true);
+                controller.abort();
+            }) as Box<dyn FnMut()>)
+        };
+        let id = set_timeout(
+            callback.as_ref().unchecked_ref(),
+            timeout_ms.min(i32::MAX as u64) as i32,
+        );
+        Ok(FetchTimeout {
+            controller,
+            expired,
+            id,
+            _callback: callback,
+        })
+    }
+
+    /// Converts an error into a timeout error if it happened because the timeout elapsed
+    pub fn error(&self, err: u32) -> u32 {
+        if self.expired.get() {
+            err::ERR_ETIMEDOUT
+        } else {
+            err
+        }
+    }
+}
+
+impl Drop for FetchTimeout {
+    fn drop(&mut self) {
+        clear_timeout(self.id);
+    }
+}
+
+/// Converts an error into a timeout error if the (optional) timeout caused it
+pub fn timeout_error(timeout: Option<&FetchTimeout>, err: u32) -> u32 {
+    match timeout {
+        Some(timeout) => timeout.error(err),
+        None => err,
+    }
+}
+
 fn fetch_internal(
     request: &Request
 ) -> JsFuture
@@ -148,10 +214,14 @@ pub async fn fetch(
     cors_proxy: Option<String>,
     headers: Vec<(String, String)>,
     data: Option<Vec<u8>>,
+    timeout: Option<&FetchTimeout>,
 ) -> Result<Response, u32> {
     let mut opts = RequestInit::new();
     opts.method(method);
     opts.mode(RequestMode::Cors);
+    if let Some(timeout) = timeout {
+        opts.signal(Some(&timeout.controller.signal()));
+    }
 
     if let Some(data) = data {
         let data_len = data.len();
@@ -176,6 +246,9 @@ pub async fn fetch(
     let resp_value = match fetch_internal(&request).await.ok()
     {
         Some(a) => a,
+        None if timeout.map(|a| a.expired.get()).unwrap_or(false) => {
+            return Err(err::ERR_ETIMEDOUT);
+        }
         None => {
             // If the request failed it may be because of CORS so if a cors proxy
             // is configured then try again with the cors proxy
@@ -196,7 +269,7 @@ pub async fn fetch(
                     .map_err(|_| err::ERR_EIO)?;
             }
         
-            fetch_internal(&request).await.map_err(|_| err::ERR_EIO)?
+            fetch_internal(&request).await.map_err(|_| timeout_error(timeout, err::ERR_EIO))?
         }
     };
     assert!(resp_value.is_instance_of::<Response>());
@@ -221,7 +294,7 @@ pub async fn fetch_data(
     headers: Vec<(String, String)>,
     data: Option<Vec<u8>>,
 ) -> Result<Vec<u8>, u32> {
-    Ok(get_response_data(fetch(url, method, gzip, cors_proxy, headers, data).await?).await?)
+    Ok(get_response_data(fetch(url, method, gzip, cors_proxy, headers, data, None).await?).await?)
 }
 
 pub async fn get_response_data(resp: Response) -> Result<Vec<u8>, u32> {
@@ -236,6 +309,23 @@ pub async fn get_response_data(resp: Response) -> Result<Vec<u8>, u32> {
     Ok(ret)
 }
 
+/// Reads the next chunk of a response body that is being streamed, once the
+/// body has been fully received this will return `None`
+pub async fn read_chunk(reader: &ReadableStreamDefaultReader) -> Result<Option<Vec<u8>>, u32> {
+    let ret = JsFuture::from(reader.read())
+        .await
+        .map_err(|_| err::ERR_EIO)?;
+
+    let done = js_sys::Reflect::get(&ret, &JsValue::from_str("done")).map_err(|_| err::ERR_EIO)?;
+    if done.as_bool().unwrap_or(true) {
+        return Ok(None);
+    }
+
+    let value = js_sys::Reflect::get(&ret, &JsValue::from_str("value")).map_err(|_| err::ERR_EIO)?;
+    let chunk: js_sys::Uint8Array = value.dyn_into().map_err(|_| err::ERR_EIO)?;
+    Ok(Some(chunk.to_vec()))
+}
+
 #[wasm_bindgen(module = "/public/worker.js")]
 extern "C" {
     #[wasm_bindgen(js_name = "isWorker")]
@@ -12,16 +12,22 @@ use tokio::sync::mpsc;
 use tracing::{debug, error, info, trace, warn};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::*;
+use web_sys::ReadableStreamDefaultReader;
 use web_sys::WebGl2RenderingContext;
 
 use super::common::*;
 use super::pool::WebThreadPool;
+use super::err;
 use super::ws::WebSocket;
 use wasmer_os::api::*;
 use super::webgl::WebGl;
 use super::webgl::GlContext;
 use super::webgl::WebGlCommand;
 
+/// Number of response chunks that may be buffered before the browser
+/// stops reading from the network
+const MAX_STREAM_BACKLOG: usize = 16;
+
 pub(crate) enum TerminalCommand {
     Print(String),
     ConsoleRect(mpsc::Sender<ConsoleRect>),
@@ -137,13 +143,21 @@ impl SystemAbi for WebSystem {
         let (tx, rx) = mpsc::channel(1);
         self.pool.spawn_shared(Box::new(move || {
             Box::pin(async move {
+                let timeout = match options.timeout_ms.map(FetchTimeout::new).transpose() {
+                    Ok(a) => a,
+                    Err(err) => {
+                        let _ = tx.send(Err(err)).await;
+                        return;
+                    }
+                };
                 let resp = match crate::common::fetch(
                     url.as_str(),
                     method.as_str(),
                     options.gzip,
                     options.cors_proxy,
                     headers,
-                    data)
+                    data,
+                    timeout.as_ref())
                     .await
                 {
                     Ok(a) => a,
@@ -161,7 +175,7 @@ impl SystemAbi for WebSystem {
                 let data = match crate::common::get_response_data(resp).await {
                     Ok(a) => a,
                     Err(err) => {
-                        let _ = tx.send(Err(err)).await;
+                        let _ = tx.send(Err(timeout_error(timeout.as_ref(), err))).await;
                         return;
                     }
                 };
@@ -188,10 +202,119 @@ impl SystemAbi for WebSystem {
         AsyncResult::new(SerializationFormat::Bincode, rx)
     }
 
+    async fn reqwest_stream(
+        &self,
+        url: &str,
+        method: &str,
+        options: ReqwestOptions,
+        headers: Vec<(String, String)>,
+        body: Option<mpsc::Receiver<Vec<u8>>>,
+    ) -> Result<ReqwestResponseStream, u32> {
+        // Browsers only accept streamed request bodies over HTTP/2 on some
+        // engines hence the upload is gathered before the request is sent
+        let data = match body {
+            Some(mut body) => {
+                let mut data = Vec::new();
+                while let Some(chunk) = body.recv().await {
+                    data.extend_from_slice(&chunk[..]);
+                }
+                Some(data)
+            }
+            None => None,
+        };
+
+        let url = url.to_string();
+        let method = method.to_string();
+
+        let (tx_head, mut rx_head) = mpsc::channel(1);
+        let (tx_body, rx_body) = mpsc::channel(MAX_STREAM_BACKLOG);
+        self.pool.spawn_shared(Box::new(move || {
+            Box::pin(async move {
+                // The timeout covers the whole request including the body
+                let timeout = match options.timeout_ms.map(FetchTimeout::new).transpose() {
+                    Ok(a) => a,
+                    Err(err) => {
+                        let _ = tx_head.send(Err(err)).await;
+                        return;
+                    }
+                };
+                let resp = match crate::common::fetch(
+                    url.as_str(),
+                    method.as_str(),
+                    options.gzip,
+                    options.cors_proxy,
+                    headers,
+                    data,
+                    timeout.as_ref())
+                    .await
+                {
+                    Ok(a) => a,
+                    Err(err) => {
+                        let _ = tx_head.send(Err(err)).await;
+                        return;
+                    }
+                };
+
+                let head = ReqwestResponse {
+                    pos: 0,
+                    ok: resp.ok(),
+                    redirected: resp.redirected(),
+                    status: resp.status(),
+                    status_text: resp.status_text(),
+                    headers: Vec::new(),
+                    data: None,
+                };
+                debug!("response status {}", head.status);
+
+                // Responses without a body (e.g. HEAD requests) finish straight away
+                let reader: ReadableStreamDefaultReader = match resp.body() {
+                    Some(a) => a.get_reader().unchecked_into(),
+                    None => {
+                        let _ = tx_head.send(Ok(head)).await;
+                        return;
+                    }
+                };
+                if tx_head.send(Ok(head)).await.is_err() {
+                    let _ = reader.cancel();
+                    return;
+                }
+
+                // Each chunk is passed on as soon as the browser hands it
+                // over, the bounded channel provides the back pressure
+                loop {
+                    let ret = match crate::common::read_chunk(&reader).await {
+                        Ok(Some(chunk)) => Ok(chunk),
+                        Ok(None) => break,
+                        Err(err) => Err(timeout_error(timeout.as_ref(), err)),
+                    };
+                    let failed = ret.is_err();
+                    if tx_body.send(ret).await.is_err() {
+                        trace!("response stream dropped");
+                        let _ = reader.cancel();
+                        break;
+                    }
+                    if failed {
+                        break;
+                    }
+                }
+            })
+        }));
+
+        let head = rx_head.recv().await.ok_or(err::ERR_ECONNABORTED)??;
+        Ok(ReqwestResponseStream {
+            head,
+            body: rx_body,
+        })
+    }
+
     async fn web_socket(&self, url: &str) -> Result<Box<dyn WebSocketAbi>, String> {
         WebSocket::new(url)
     }
 
+    async fn web_socket_with(&self, url: &str, options: WebSocketOptions) -> Result<Box<dyn WebSocketAbi>, String> {
+        WebSocket::new_with(url, options)
+    }
+
     /// Open the WebGL
     async fn webgl(&self) -> Option<Box<dyn WebGlAbi>> {
         Some(Box::new(WebGl::new(&self.webgl_tx)))
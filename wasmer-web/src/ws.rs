@@ -6,7 +6,7 @@ use wasmer_os::api::*;
 use tracing::{debug, error, info, trace, warn};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{MessageEvent, WebSocket as WebSocketSys};
+use web_sys::{CloseEvent, MessageEvent, WebSocket as WebSocketSys};
 
 #[derive(Clone)]
 
@@ -21,6 +21,22 @@ impl WebSocket {
 
         Ok(Box::new(WebSocket { sys: ws_sys }))
     }
+
+    pub fn new_with(url: &str, options: WebSocketOptions) -> Result<Box<dyn WebSocketAbi>, String> {
+        // Browsers do not let scripts set headers on the upgrade request
+        if options.headers.len() > 0 {
+            return Err("custom headers are not supported on browser web sockets".to_string());
+        }
+
+        let protocols = js_sys::Array::new();
+        for protocol in options.protocols.iter() {
+            protocols.push(&JsValue::from_str(protocol.as_str()));
+        }
+        let ws_sys = WebSocketSys::new_with_str_sequence(url, &protocols)
+            .map_err(|err| format!("{:?}", err))?;
+
+        Ok(Box::new(WebSocket { sys: ws_sys }))
+    }
 }
 
 #[async_trait]
@@ -43,8 +59,32 @@ impl WebSocketAbi for WebSocket {
     }
 
     fn set_onmessage(&mut self, callback: Box<dyn Fn(Vec<u8>) + Send + 'static>) {
+        self.set_onframe(Box::new(move |frame| {
+            match frame {
+                WebSocketFrame::Binary(data) => callback(data),
+                frame => debug!("message event, received {}", frame),
+            }
+        }));
+    }
+
+    fn set_onframe(&mut self, callback: Box<dyn Fn(WebSocketFrame) + Send + 'static>) {
         let callback = Arc::new(callback);
 
+        // The close code and reason are passed on as a close frame (browsers
+        // handle pings and pongs themselves so they are never seen here)
+        let onclose_callback = {
+            let callback = callback.clone();
+            Closure::wrap(Box::new(move |e: CloseEvent| {
+                callback.deref()(WebSocketFrame::Close(Some(WebSocketClose {
+                    code: e.code(),
+                    reason: e.reason(),
+                })));
+            }) as Box<dyn FnMut(CloseEvent)>)
+        };
+        let _ = self.sys
+            .add_event_listener_with_callback("close", onclose_callback.as_ref().unchecked_ref());
+        onclose_callback.forget();
+
         let fr = web_sys::FileReader::new().unwrap();
         let fr_c = fr.clone();
         let onloadend_cb = {
@@ -52,7 +92,7 @@ impl WebSocketAbi for WebSocket {
             Closure::wrap(Box::new(move |_e: web_sys::ProgressEvent| {
                 let array = js_sys::Uint8Array::new(&fr_c.result().unwrap());
                 let data = array.to_vec();
-                callback.deref()(data);
+                callback.deref()(WebSocketFrame::Binary(data));
             }) as Box<dyn FnMut(web_sys::ProgressEvent)>)
         };
         fr.set_onloadend(Some(onloadend_cb.as_ref().unchecked_ref()));
@@ -64,11 +104,11 @@ impl WebSocketAbi for WebSocket {
             Closure::wrap(Box::new(move |e: MessageEvent| {
                 if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
                     let data = js_sys::Uint8Array::new(&abuf).to_vec();
-                    callback.deref()(data);
+                    callback.deref()(WebSocketFrame::Binary(data));
                 } else if let Ok(blob) = e.data().dyn_into::<web_sys::Blob>() {
                     fr.read_as_array_buffer(&blob).expect("blob not readable");
-                } else if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
-                    debug!("message event, received Text: {:?}", txt);
+                } else if let Some(txt) = e.data().as_string() {
+                    callback.deref()(WebSocketFrame::Text(txt));
                 } else {
                     debug!("websocket received unknown message type");
                 }
@@ -80,6 +120,31 @@ impl WebSocketAbi for WebSocket {
         onmessage_callback.forget();
     }
 
+    fn protocol(&self) -> Option<String> {
+        let protocol = self.sys.protocol();
+        if protocol.len() > 0 {
+            Some(protocol)
+        } else {
+            None
+        }
+    }
+
+    fn send_frame(&mut self, frame: WebSocketFrame) -> Result<(), String> {
+        match frame {
+            WebSocketFrame::Binary(data) => self.send(data),
+            WebSocketFrame::Text(txt) => self
+                .sys
+                .send_with_str(txt.as_str())
+                .map_err(|err| format!("{:?}", err)),
+            WebSocketFrame::Close(Some(close)) => self
+                .sys
+                .close_with_code_and_reason(close.code, close.reason.as_str())
+                .map_err(|err| format!("{:?}", err)),
+            WebSocketFrame::Close(None) => self.sys.close().map_err(|err| format!("{:?}", err)),
+            frame => Err(format!("browsers can not send {} frames", frame)),
+        }
+    }
+
     fn send(&mut self, data: Vec<u8>) -> Result<(), String> {
         let data_len = data.len();
         let array = js_sys::Uint8Array::new_with_length(data_len as u32);